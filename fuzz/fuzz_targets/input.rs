@@ -0,0 +1,25 @@
+//! Fuzzes the parsing of commands, which must reject any input with an error rather than
+//! panic. Each line is parsed against a small spreadsheet, and the assignments are applied to
+//! it so that the values they compute are checked too.
+//!
+//! Run with `cargo fuzz run input` from the root of the repository.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use spreadsheet::SpreadsheetEngine;
+use spreadsheet::utils::input::input;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut sheet = SpreadsheetEngine::new(5, 5);
+    for line in text.lines() {
+        if let Ok(command) = input(line, 5, 5)
+            && let Ok(out) = command.into_assignment()
+        {
+            let _ = sheet.update(&out);
+        }
+    }
+});
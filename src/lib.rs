@@ -0,0 +1,2512 @@
+//! # Rust Spreadsheet Engine
+//!
+//! The engine of the Rust Spreadsheet application, which the terminal and graphical
+//! interfaces are built on and which other Rust programs can embed:
+//!
+//! ```
+//! use spreadsheet::SpreadsheetEngine;
+//!
+//! let mut engine = SpreadsheetEngine::new(3, 3);
+//! engine.set_cell("B1", "7").unwrap();
+//! engine.set_cell("A1", "=B1+3").unwrap();
+//! assert_eq!(engine.get_value("A1"), Some(10));
+//! ```
+//!
+//! The engine supports:
+//! - Formula-based cell calculations with dependency tracking
+//! - Cycle detection in cell references
+//! - Various operations including arithmetic, statistical functions, and time delays
+//! - Large recalculations and ranges spread over several threads
+//!
+//! The graphical interface is in `utils::ui`, behind the `gui` feature, which is on by
+//! default. Programs that only need the engine can turn default features off.
+
+// Without the GUI some engine helpers only it uses are left unused
+#![cfg_attr(not(feature = "gui"), allow(dead_code))]
+
+pub mod utils;
+
+pub use utils::error::SheetError;
+pub use utils::input::{Assignment, ParsedCommand};
+use utils::operations::RangeOptions;
+use utils::settings::Limits;
+pub use utils::sheet::Sheet as SpreadsheetEngine;
+
+use rayon::prelude::*;
+use utils::cancel::Cancel;
+use utils::cells::Cells;
+
+/// Represents an operation to be performed on a cell.
+///
+/// # Fields
+///
+/// * `opcpde` - Operation code specifying what calculation to perform
+/// * `cell1` - First operand (either a cell reference or direct value)
+/// * `cell2` - Second operand (either a cell reference or direct value)
+/// * `expr` - Parsed expression of an "EXP" operation, `None` for all other operations
+/// * `criterion` - Criterion of a conditional range function (see `graph::CONDITIONAL_OPS`),
+///   `None` for all other operations
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+pub struct Ops {
+    pub opcpde: String,
+    pub cell1: i32,
+    pub cell2: i32,
+    #[serde(default)]
+    pub expr: Option<utils::expr::Expr>,
+    #[serde(default)]
+    pub criterion: Option<utils::operations::Criterion>,
+}
+impl Clone for Ops {
+    fn clone(&self) -> Self {
+        Ops {
+            opcpde: self.opcpde.clone(),
+            cell1: self.cell1,
+            cell2: self.cell2,
+            expr: self.expr.clone(),
+            criterion: self.criterion,
+        }
+    }
+}
+
+/// The operation of an empty cell.
+impl Default for Ops {
+    fn default() -> Self {
+        Ops {
+            opcpde: String::new(),
+            cell1: -1,
+            cell2: -1,
+            expr: None,
+            criterion: None,
+        }
+    }
+}
+
+/// Converts a cell reference string (like "A1") to an integer representation.
+///
+/// # Arguments
+///
+/// * `a` - Cell reference string (e.g., "A1", "B2", etc.)
+///
+/// # Returns
+///
+/// An integer representation where column is multiplied by 1000 and added to row
+pub fn cell_to_int(a: &str) -> i32 {
+    if let Some(cell) = utils::cellref::CellRef::parse(a) {
+        return cell
+            .col
+            .checked_mul(1000)
+            .and_then(|c| c.checked_add(cell.row))
+            .unwrap_or(0);
+    }
+    let mut col: i32 = 0;
+    // Only ASCII letters are counted so that `part` is always a valid byte index
+    let part = a.chars().take_while(|c| c.is_ascii_alphabetic()).count();
+
+    for i in a[..part].chars() {
+        let diff = i as i32 - 'A' as i32 + 1;
+
+        if (1..=26).contains(&diff) {
+            // Column labels too long to represent are treated as invalid
+            match col.checked_mul(26).and_then(|c| c.checked_add(diff)) {
+                Some(c) => col = c,
+                None => return 0,
+            }
+        } else {
+            break;
+        }
+    }
+
+    let row: i32 = a[part..].parse().unwrap_or(0);
+
+    col.checked_mul(1000)
+        .and_then(|c| c.checked_add(row))
+        .unwrap_or(0)
+}
+
+/// Converts an integer cell representation to a linear index in the spreadsheet array.
+///
+/// # Arguments
+///
+/// * `a` - Integer representation of a cell
+/// * `len_h` - Width of the spreadsheet (number of columns)
+///
+/// # Returns
+///
+/// Linear index in the spreadsheet array
+pub fn int_to_ind(a: i32, len_h: i32) -> i32 {
+    (a / 1000) + (a % 1000 - 1) * len_h
+}
+
+/// Converts a cell reference string directly to a linear index in the spreadsheet array.
+///
+/// # Arguments
+///
+/// * `a` - Cell reference string (e.g., "A1", "B2", etc.)
+/// * `len_h` - Width of the spreadsheet (number of columns)
+///
+/// # Returns
+///
+/// Linear index in the spreadsheet array
+pub fn cell_to_ind(a: &str, len_h: i32) -> i32 {
+    int_to_ind(cell_to_int(a), len_h)
+}
+
+/// Converts a linear index in the spreadsheet array back to a cell reference string.
+///
+/// # Arguments
+///
+/// * `ind` - Linear index in the spreadsheet array (1-based)
+/// * `len_h` - Width of the spreadsheet (number of columns)
+///
+/// # Returns
+///
+/// Cell reference string in the A1 style (e.g., "B2"), as saved in formulas and files
+pub fn ind_to_cell(ind: i32, len_h: i32) -> String {
+    utils::cellref::CellRef::from_ind(ind, len_h).to_string()
+}
+
+/// Evaluates the operation of a cell without changing any cell, so cells that do not depend
+/// on each other can be evaluated at the same time.
+///
+/// # Arguments
+///
+/// * `cell` - Index of the cell to evaluate
+/// * `database` - Reference to the array of cell values
+/// * `opers` - Slice of operations for each cell
+/// * `len_h` - Width of the spreadsheet (number of columns)
+/// * `err` - Reference to the array tracking cell errors
+/// * `options` - Settings of the spreadsheet read by range functions (see `utils::operations::RangeOptions`)
+/// * `limits` - Safe mode and resource limits of the spreadsheet (see `utils::settings::Limits`)
+///
+/// # Returns
+///
+/// The new value of the cell, or `None` to keep its value, and whether it is in an error state
+fn eval(
+    cell: i32,
+    database: &[i32],
+    opers: &Cells<Ops>,
+    len_h: i32,
+    err: &[bool],
+    options: &RangeOptions,
+    limits: &Limits,
+) -> (Option<i32>, bool) {
+    let op = &opers[cell as usize];
+    match op.opcpde.as_str() {
+        "CCA" | "CVA" | "VCA" | "VVA" | "CCS" | "CVS" | "VCS" | "VVS" | "CCM" | "CVM" | "VCM"
+        | "VVM" | "CCD" | "CVD" | "VCD" | "VVD" => {
+            let code = op.opcpde.as_bytes();
+            // Operand types are given by the first two letters of the opcode
+            let (a, err_a) = if code[0] == b'C' {
+                (database[op.cell1 as usize], err[op.cell1 as usize])
+            } else {
+                (op.cell1, false)
+            };
+            let (b, err_b) = if code[1] == b'C' {
+                (database[op.cell2 as usize], err[op.cell2 as usize])
+            } else {
+                (op.cell2, false)
+            };
+            // Division by zero and overflow both give an error
+            let result = match code[2] {
+                b'A' => a.checked_add(b),
+                b'S' => a.checked_sub(b),
+                b'M' => a.checked_mul(b),
+                _ => a.checked_div(b),
+            };
+            (result, err_a || err_b || result.is_none())
+        }
+        "EQC" => (Some(database[op.cell1 as usize]), err[op.cell1 as usize]),
+        "EQV" => (Some(op.cell1), false),
+        // An emptied cell reads as 0, like one never assigned
+        "" => (Some(0), false),
+        code if utils::graph::RANGE_OPS.contains(&code) => {
+            let function = match code {
+                "SUI" => utils::operations::sum_if,
+                "NUI" => utils::operations::count_if,
+                "MEI" => utils::operations::avg_if,
+                "NUM" => utils::operations::count,
+                "NUA" => utils::operations::count_a,
+                "NUB" => utils::operations::count_blank,
+                _ => match &code[..2] {
+                    "MI" => utils::operations::min,
+                    "MA" => utils::operations::max,
+                    "ME" => utils::operations::avg,
+                    "ST" => utils::operations::stdev,
+                    _ => utils::operations::sum,
+                },
+            };
+            let (value, error) = function(
+                op.cell1, op.cell2, database, len_h, err, cell, opers, options,
+            );
+            (Some(value), error)
+        }
+        utils::expr::EXP_OP => {
+            let result = op
+                .expr
+                .as_ref()
+                .and_then(|e| e.eval(database, err, len_h, limits));
+            (result, result.is_none())
+        }
+        utils::structure::REF_OP => (None, true),
+        // SLEEP does not wait here, its cell is scheduled to resolve later (see `utils::schedule`)
+        "SLV" => (Some(op.cell1), false),
+        "SLC" => {
+            if err[op.cell1 as usize] {
+                (None, true)
+            } else {
+                (Some(database[op.cell1 as usize]), false)
+            }
+        }
+        _ => (None, err[cell as usize]),
+    }
+}
+
+/// Calculates the value of a cell based on its operation and dependencies.
+///
+/// # Arguments
+///
+/// * `cell` - Index of the cell to calculate
+/// * `database` - Mutable reference to the array of cell values
+/// * `opers` - Slice of operations for each cell
+/// * `len_h` - Width of the spreadsheet (number of columns)
+/// * `err` - Mutable reference to the array tracking cell errors
+/// * `options` - Settings of the spreadsheet read by range functions (see `utils::operations::RangeOptions`)
+/// * `limits` - Safe mode and resource limits of the spreadsheet (see `utils::settings::Limits`)
+fn calc(
+    cell: i32,
+    database: &mut [i32],
+    opers: &Cells<Ops>,
+    len_h: i32,
+    err: &mut [bool],
+    options: &RangeOptions,
+    limits: &Limits,
+) {
+    let (value, error) = eval(cell, database, opers, len_h, err, options, limits);
+    err[cell as usize] = error;
+    if let Some(value) = value {
+        database[cell as usize] = value;
+    }
+}
+
+/// Updates cell values according to a topological ordering of dependencies.
+///
+/// # Arguments
+///
+/// * `topo_arr` - Topologically sorted array of cell indices
+/// * `database` - Mutable reference to the array of cell values
+/// * `opers` - Slice of operations for each cell
+/// * `len_h` - Width of the spreadsheet (number of columns)
+/// * `err` - Mutable reference to the array tracking cell errors
+/// * `options` - Settings of the spreadsheet read by range functions (see `utils::operations::RangeOptions`)
+/// * `limits` - Safe mode and resource limits of the spreadsheet (see `utils::settings::Limits`)
+/// * `cancel` - Token checked before each cell, to stop when the user cancels
+///
+/// # Returns
+///
+/// `Ok(())` if all cells were updated, or `TimeLimitExceeded`, `SleepLimitExceeded` or
+/// `Cancelled`. The update stops at the first exceeded limit or once cancelled.
+#[allow(clippy::too_many_arguments)]
+fn val_update(
+    topo_arr: &[i32],
+    database: &mut [i32],
+    opers: &Cells<Ops>,
+    len_h: i32,
+    err: &mut [bool],
+    options: &RangeOptions,
+    limits: &Limits,
+    cancel: &Cancel,
+) -> Result<(), SheetError> {
+    let _span = tracing::debug_span!("evaluate", cells = topo_arr[0]).entered();
+    let start = std::time::Instant::now();
+    for i in 1..=topo_arr[0] {
+        let cell = topo_arr[i as usize] as usize;
+        if cancel.is_cancelled() {
+            tracing::info!(evaluated = i - 1, "recalculation cancelled");
+            return Err(SheetError::Cancelled);
+        }
+        if let Some(limit) = limits.max_sleep() {
+            let requested = match opers[cell].opcpde.as_str() {
+                "SLV" => opers[cell].cell1,
+                "SLC" => database[opers[cell].cell1 as usize],
+                _ => 0,
+            };
+            if requested > limit && !limits.safe_mode() {
+                tracing::warn!(cell, requested, limit, "sleep limit exceeded");
+                return Err(SheetError::SleepLimitExceeded);
+            }
+        }
+        calc(cell as i32, database, opers, len_h, err, options, limits);
+        tracing::trace!(cell, value = database[cell], error = err[cell], "evaluated");
+        if let Some(limit) = limits.max_eval_time()
+            && start.elapsed() > limit
+        {
+            tracing::warn!(evaluated = i, ?limit, "time limit exceeded");
+            return Err(SheetError::TimeLimitExceeded);
+        }
+    }
+    tracing::debug!(took = ?start.elapsed(), "evaluation finished");
+    Ok(())
+}
+
+/// Fewest cells in a level of an update for them to be evaluated on several threads.
+pub const PARALLEL_LEVEL: usize = 256;
+
+/// Updates cell values level by level, as planned by `utils::graph::recalc_plan`.
+///
+/// The cells of a level do not depend on each other, so levels with at least
+/// `PARALLEL_LEVEL` cells are evaluated on several threads. Smaller levels, and levels with
+/// SLEEP, whose limit is checked cell by cell, are evaluated by `val_update`.
+///
+/// # Arguments
+///
+/// * `levels` - The cells to update, in levels
+/// * `database` - Mutable reference to the array of cell values
+/// * `opers` - Slice of operations for each cell
+/// * `len_h` - Width of the spreadsheet (number of columns)
+/// * `err` - Mutable reference to the array tracking cell errors
+/// * `options` - Settings of the spreadsheet read by range functions (see `utils::operations::RangeOptions`)
+/// * `limits` - Safe mode and resource limits of the spreadsheet (see `utils::settings::Limits`)
+/// * `cancel` - Token checked before each cell, to stop when the user cancels
+///
+/// # Returns
+///
+/// `Ok(())` if all cells were updated, or `TimeLimitExceeded`, `SleepLimitExceeded` or
+/// `Cancelled`. The update stops at the first exceeded limit or once cancelled.
+#[allow(clippy::too_many_arguments)]
+fn level_update(
+    levels: &[Vec<i32>],
+    database: &mut [i32],
+    opers: &Cells<Ops>,
+    len_h: i32,
+    err: &mut [bool],
+    options: &RangeOptions,
+    limits: &Limits,
+    cancel: &Cancel,
+) -> Result<(), SheetError> {
+    let _span = tracing::debug_span!("evaluate_levels", levels = levels.len()).entered();
+    let start = std::time::Instant::now();
+    for level in levels {
+        let sleeps = level
+            .iter()
+            .any(|&c| matches!(opers[c as usize].opcpde.as_str(), "SLV" | "SLC"));
+        if level.len() < PARALLEL_LEVEL || sleeps {
+            let mut topo_arr = vec![level.len() as i32];
+            topo_arr.extend(level);
+            val_update(
+                &topo_arr, database, opers, len_h, err, options, limits, cancel,
+            )?;
+        } else {
+            // Each cell checks the token, and the level stops at the first one cancelled
+            let results: Vec<(Option<i32>, bool)> = level
+                .par_iter()
+                .map(|&cell| {
+                    if cancel.is_cancelled() {
+                        return Err(SheetError::Cancelled);
+                    }
+                    Ok(eval(cell, database, opers, len_h, err, options, limits))
+                })
+                .collect::<Result<_, _>>()?;
+            for (&cell, (value, error)) in level.iter().zip(results) {
+                err[cell as usize] = error;
+                if let Some(value) = value {
+                    database[cell as usize] = value;
+                }
+            }
+            tracing::trace!(cells = level.len(), "evaluated level in parallel");
+        }
+        if let Some(limit) = limits.max_eval_time()
+            && start.elapsed() > limit
+        {
+            tracing::warn!(?limit, "time limit exceeded");
+            return Err(SheetError::TimeLimitExceeded);
+        }
+    }
+    tracing::debug!(took = ?start.elapsed(), "evaluation finished");
+    Ok(())
+}
+
+/// Recalculates every cell from scratch, ignoring the values computed so far.
+///
+/// Cells are evaluated in an order derived from their operations alone, so the result does
+/// not depend on the sensitivity list maintained by `cell_update`. SLEEP is evaluated
+/// without waiting. This serves as an oracle for the incremental update.
+///
+/// # Arguments
+///
+/// * `opers` - Slice of operations for each cell
+/// * `len_h` - Width of the spreadsheet (number of columns)
+/// * `options` - Settings of the spreadsheet read by range functions (see `utils::operations::RangeOptions`)
+/// * `limits` - Safe mode and resource limits of the spreadsheet (see `utils::settings::Limits`)
+///
+/// # Returns
+///
+/// The recalculated values and error flags of every cell
+pub fn recalculate_all(
+    opers: &Cells<Ops>,
+    len_h: i32,
+    options: &RangeOptions,
+    limits: &Limits,
+) -> (Vec<i32>, Vec<bool>) {
+    let n = opers.len();
+    let mut database = vec![0; n];
+    let mut err = vec![false; n];
+    let mut dependents = vec![Vec::new(); n];
+    let mut indegree = vec![0; n];
+    for (cell, op) in opers.iter().enumerate().skip(1) {
+        for p in utils::graph::precedents(op, len_h) {
+            dependents[p as usize].push(cell);
+            indegree[cell] += 1;
+        }
+    }
+
+    let mut q: std::collections::VecDeque<usize> = (1..n).filter(|&c| indegree[c] == 0).collect();
+    while let Some(cell) = q.pop_front() {
+        let op = &opers[cell];
+        match op.opcpde.as_str() {
+            "SLV" => database[cell] = op.cell1,
+            "SLC" => {
+                err[cell] = err[op.cell1 as usize];
+                database[cell] = database[op.cell1 as usize];
+            }
+            _ => calc(
+                cell as i32,
+                &mut database,
+                opers,
+                len_h,
+                &mut err,
+                options,
+                limits,
+            ),
+        }
+        for &d in &dependents[cell] {
+            indegree[d] -= 1;
+            if indegree[d] == 0 {
+                q.push_back(d);
+            }
+        }
+    }
+    (database, err)
+}
+
+/// Compares the current values of all cells against a full recalculation.
+///
+/// # Arguments
+///
+/// * `database` - Reference to the array of cell values
+/// * `err` - Reference to the array tracking cell errors
+/// * `opers` - Slice of operations for each cell
+/// * `len_h` - Width of the spreadsheet (number of columns)
+/// * `options` - Settings of the spreadsheet read by range functions (see `utils::operations::RangeOptions`)
+/// * `limits` - Safe mode and resource limits of the spreadsheet (see `utils::settings::Limits`)
+///
+/// # Returns
+///
+/// Indices of the cells whose value or error state differs from `recalculate_all`.
+/// Values of cells in error are not compared.
+pub fn verify(
+    database: &[i32],
+    err: &[bool],
+    opers: &Cells<Ops>,
+    len_h: i32,
+    options: &RangeOptions,
+    limits: &Limits,
+) -> Vec<i32> {
+    let (expected, expected_err) = recalculate_all(opers, len_h, options, limits);
+    (1..opers.len())
+        .filter(|&c| err[c] != expected_err[c] || (!err[c] && database[c] != expected[c]))
+        .map(|c| c as i32)
+        .collect()
+}
+
+/// Removes a cell from the dependents of another, dropping the entry of a cell left without
+/// dependents so that sparse storage stays sparse.
+/// # Arguments
+/// * `sensi` - The sensitivity list
+/// * `cell` - The cell read by `target` until now
+/// * `target` - The cell no longer reading it
+fn unlink(sensi: &mut Cells<Vec<i32>>, cell: usize, target: usize) {
+    if let Some(dependents) = sensi.get_mut(cell) {
+        dependents.retain(|&x| x != target as i32);
+    }
+    sensi.remove_if_empty(cell);
+}
+
+/// Updates a cell with a new operation and recalculates dependent cells.
+///
+/// This function handles the dependency tracking, cycle detection, and propagation
+/// of changes through the spreadsheet.
+///
+/// # Arguments
+///
+/// * `cmd` - The assignment, as parsed by `utils::input::input`
+/// * `database` - Mutable reference to the array of cell values
+/// * `sensi` - Mutable reference to the sensitivity list for dependency tracking
+/// * `opers` - Mutable reference to the array of cell operations
+/// * `len_h` - Width of the spreadsheet (number of columns)
+/// * `indegree` - Mutable reference to the array tracking in-degrees for cycle detection (used in toposort)
+/// * `err` - Mutable reference to the array tracking cell errors
+/// * `options` - Settings of the spreadsheet read by range functions (see `utils::operations::RangeOptions`)
+/// * `limits` - Safe mode and resource limits of the spreadsheet (see `utils::settings::Limits`)
+/// * `cancel` - Token that stops the recalculation when the user cancels (see `utils::cancel`)
+///
+/// # Returns
+///
+/// `Ok(())` if the update was successful, otherwise `Cycle`, `CellLimitExceeded`,
+/// `TimeLimitExceeded`, `SleepLimitExceeded` or `Cancelled`. In all failure cases the change
+/// is reverted.
+#[allow(clippy::too_many_arguments)]
+pub fn cell_update(
+    cmd: &Assignment,
+    database: &mut [i32],
+    sensi: &mut Cells<Vec<i32>>,
+    opers: &mut Cells<Ops>,
+    len_h: i32,
+    indegree: &mut [i32],
+    err: &mut [bool],
+    options: &RangeOptions,
+    limits: &Limits,
+    cancel: &Cancel,
+) -> Result<(), SheetError> {
+    let expr = &cmd.expr;
+    let _span = tracing::info_span!("cell_update", cell = %cmd.target, op = %expr.op).entered();
+    let target = cell_to_ind(&cmd.target, len_h);
+    let target = target as usize;
+    // Storing temporary value of opers in case a cycle is present
+    let rev = opers[target].clone();
+
+    // Copying data to opers
+    opers[target].opcpde = expr.op.clone();
+    if let Ok(value) = expr.arg1.parse::<i32>() {
+        opers[target].cell1 = value;
+    } else {
+        opers[target].cell1 = cell_to_ind(&expr.arg1, len_h);
+    }
+
+    if let Ok(value) = expr.arg2.parse::<i32>() {
+        opers[target].cell2 = value;
+    } else {
+        opers[target].cell2 = cell_to_ind(&expr.arg2, len_h);
+    }
+    opers[target].expr = if expr.op == utils::expr::EXP_OP {
+        utils::expr::Expr::parse(&expr.arg1).ok()
+    } else {
+        None
+    };
+    opers[target].criterion = None;
+    if utils::graph::CONDITIONAL_OPS.contains(&expr.op.as_str())
+        && let Ok((c1, c2, criterion)) = utils::operations::conditional_args(&expr.arg1)
+    {
+        opers[target].cell1 = cell_to_ind(c1, len_h);
+        opers[target].cell2 = cell_to_ind(c2, len_h);
+        opers[target].criterion = Some(criterion);
+    }
+
+    //Removing older values from sensitivity list
+
+    // Handling arithmetic
+    if rev.opcpde.starts_with('C') {
+        unlink(sensi, rev.cell1 as usize, target);
+    }
+
+    if rev.opcpde.chars().nth(1) == Some('C') {
+        unlink(sensi, rev.cell2 as usize, target);
+    }
+
+    // Handling eq
+    if rev.opcpde == "EQC" {
+        unlink(sensi, rev.cell1 as usize, target);
+    }
+
+    // Handling sleep
+    if rev.opcpde == "SLC" {
+        unlink(sensi, rev.cell1 as usize, target);
+    }
+
+    // Handling expressions
+    if let Some(expr) = &rev.expr {
+        for c in expr.cells(len_h) {
+            unlink(sensi, c as usize, target);
+        }
+    }
+
+    // Handling ranges
+    if utils::graph::RANGE_OPS.contains(&rev.opcpde.as_str()) {
+        let mut x1 = (rev.cell1 % len_h) as usize;
+        let mut x2 = (rev.cell2 % len_h) as usize;
+        if x1 == 0 {
+            x1 = len_h as usize;
+        }
+        if x2 == 0 {
+            x2 = len_h as usize;
+        }
+
+        let y1 = (rev.cell1 / len_h) as usize + ((x1 != len_h as usize) as usize);
+        let y2 = (rev.cell2 / len_h) as usize + ((x2 != len_h as usize) as usize);
+
+        if utils::graph::RANGE_OPS.contains(&expr.op.as_str()) {
+            let mut xx1 = (opers[target].cell1 % len_h) as usize;
+            let mut xx2 = (opers[target].cell2 % len_h) as usize;
+            if xx1 == 0 {
+                xx1 = len_h as usize;
+            }
+            if xx2 == 0 {
+                xx2 = len_h as usize;
+            }
+
+            let xy1 = (opers[target].cell1 / len_h) as usize + ((xx1 != len_h as usize) as usize);
+            let xy2 = (opers[target].cell2 / len_h) as usize + ((xx2 != len_h as usize) as usize);
+
+            for i in x1..=x2 {
+                for j in y1..=y2 {
+                    if !(xx1 <= i && i <= xx2 && xy1 <= j && j <= xy2) {
+                        unlink(sensi, i + (j - 1) * len_h as usize, target);
+                    }
+                }
+            }
+        } else {
+            for i in x1..=x2 {
+                for j in y1..=y2 {
+                    unlink(sensi, i + (j - 1) * len_h as usize, target);
+                }
+            }
+        }
+    }
+
+    // Adding items to sensitivity list
+
+    // Handling arithmetic
+    if expr.op.starts_with('C')
+        && (sensi[opers[target].cell1 as usize].is_empty()
+            || *sensi[opers[target].cell1 as usize].last().unwrap() != target as i32)
+    {
+        sensi[opers[target].cell1 as usize].push(target as i32);
+    }
+
+    if expr.op.chars().nth(1) == Some('C')
+        && (sensi[opers[target].cell2 as usize].is_empty()
+            || *sensi[opers[target].cell2 as usize].last().unwrap() != target as i32)
+    {
+        sensi[opers[target].cell2 as usize].push(target as i32);
+    }
+
+    // Handling eq
+    if expr.op == "EQC"
+        && (sensi[opers[target].cell1 as usize].is_empty()
+            || *sensi[opers[target].cell1 as usize].last().unwrap() != target as i32)
+    {
+        sensi[opers[target].cell1 as usize].push(target as i32);
+    }
+
+    if expr.op == "SLC"
+        && (sensi[opers[target].cell1 as usize].is_empty()
+            || *sensi[opers[target].cell1 as usize].last().unwrap() != target as i32)
+    {
+        sensi[opers[target].cell1 as usize].push(target as i32);
+    }
+
+    // Handling expressions, whose cells are listed without duplicates
+    if let Some(expr) = &opers[target].expr {
+        for c in expr.cells(len_h) {
+            sensi[c as usize].push(target as i32);
+        }
+    }
+
+    // Handling ranges
+    if utils::graph::RANGE_OPS.contains(&expr.op.as_str()) {
+        let mut x1 = (opers[target].cell1 % len_h) as usize;
+        let mut x2 = (opers[target].cell2 % len_h) as usize;
+        if x1 == 0 {
+            x1 = len_h as usize;
+        }
+        if x2 == 0 {
+            x2 = len_h as usize;
+        }
+
+        let y1 = (opers[target].cell1 / len_h) as usize + ((x1 != len_h as usize) as usize);
+        let y2 = (opers[target].cell2 / len_h) as usize + ((x2 != len_h as usize) as usize);
+
+        if utils::graph::RANGE_OPS.contains(&rev.opcpde.as_str()) {
+            let mut xx1 = (rev.cell1 % len_h) as usize;
+            let mut xx2 = (rev.cell2 % len_h) as usize;
+            if xx1 == 0 {
+                xx1 = len_h as usize;
+            }
+            if xx2 == 0 {
+                xx2 = len_h as usize;
+            }
+
+            let xy1 = (rev.cell1 / len_h) as usize + ((xx1 != len_h as usize) as usize);
+            let xy2 = (rev.cell2 / len_h) as usize + ((xx2 != len_h as usize) as usize);
+
+            for i in x1..=x2 {
+                for j in y1..=y2 {
+                    if !(xx1 <= i && i <= xx2 && xy1 <= j && j <= xy2) {
+                        sensi[i + (j - 1) * len_h as usize].push(target as i32);
+                    }
+                }
+            }
+        } else {
+            for i in x1..=x2 {
+                for j in y1..=y2 {
+                    sensi[i + (j - 1) * len_h as usize].push(target as i32);
+                }
+            }
+        }
+    }
+
+    let topo = utils::toposort::topo_sort(sensi, target as i32, indegree);
+
+    let status;
+    if topo[0] == -1 {
+        status = Err(SheetError::Cycle);
+    } else if limits
+        .max_cells()
+        .is_some_and(|limit| topo[0] as usize > limit)
+    {
+        status = Err(SheetError::CellLimitExceeded);
+    } else {
+        let saved: Vec<(i32, bool)> = topo[1..=topo[0] as usize]
+            .iter()
+            .map(|&c| (database[c as usize], err[c as usize]))
+            .collect();
+        status = if topo[0] as usize >= PARALLEL_LEVEL {
+            let levels = utils::graph::recalc_plan(sensi, target as i32);
+            level_update(
+                &levels, database, opers, len_h, err, options, limits, cancel,
+            )
+        } else {
+            val_update(&topo, database, opers, len_h, err, options, limits, cancel)
+        };
+        if status.is_err() {
+            // Restoring values of cells touched by the aborted evaluation
+            for (&c, &(value, error)) in topo[1..=topo[0] as usize].iter().zip(saved.iter()) {
+                database[c as usize] = value;
+                err[c as usize] = error;
+            }
+        }
+    }
+
+    if status.is_err() {
+        // Removing items from sensitivity list
+        for p in utils::graph::precedents(&opers[target], len_h) {
+            unlink(sensi, p as usize, target);
+        }
+
+        // Adding back older values
+        for p in utils::graph::precedents(&rev, len_h) {
+            if !sensi[p as usize].contains(&(target as i32)) {
+                sensi[p as usize].push(target as i32);
+            }
+        }
+
+        // Restoring back previous ops in case of cycle or aborted evaluation
+        opers[target] = rev;
+    }
+    // An emptied cell, or one restored to empty, takes no room in sparse storage
+    opers.remove_if_empty(target);
+    tracing::info!(
+        status = utils::error::status(status),
+        affected = topo[0].max(0),
+        "update finished"
+    );
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses an assignment, which must be valid, and applies it to the sheet.
+    fn run(sheet: &mut utils::sheet::Sheet, cmd: &str) -> Result<(), SheetError> {
+        let out = sheet.parse_assignment(cmd).expect(cmd);
+        sheet.update(&out)
+    }
+
+    #[test]
+    fn test_cell_to_int() {
+        assert_eq!(cell_to_int("A1"), 1001);
+        assert_eq!(cell_to_int("B5"), 2005);
+        assert_eq!(cell_to_int("Z10"), 26010);
+        assert_eq!(cell_to_int("AA1"), 27001);
+    }
+
+    #[test]
+    fn test_cell_to_int_malformed() {
+        // Overlong column labels must not overflow
+        assert_eq!(cell_to_int("AAAAAAA1"), 0);
+        assert_eq!(cell_to_int("ZZZZZZZZZZZZ1"), 0);
+        // Non-ASCII letters must not split a character
+        assert_eq!(cell_to_int("é1"), 0);
+        assert_eq!(cell_to_int("Aé1"), 1000);
+        assert_eq!(cell_to_int(""), 0);
+    }
+
+    #[test]
+    fn test_arithmetic_overflow() {
+        let mut database = vec![0, i32::MAX, 0, 0];
+        let mut err = vec![false; 4];
+        let opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: i32::MAX,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::from("CVA"),
+                cell1: 1,
+                cell2: 1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::from("VVD"),
+                cell1: i32::MIN,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+        ]);
+        calc(
+            2,
+            &mut database,
+            &opers,
+            3,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+        );
+        calc(
+            3,
+            &mut database,
+            &opers,
+            3,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+        );
+        assert!(err[2]);
+        assert!(err[3]);
+
+        // Range functions add up in i64, and only a result too large for a cell is an error
+        let mut sheet = utils::sheet::Sheet::new(3, 3);
+        sheet.set_cell("A1", "2147483647").unwrap();
+        sheet.set_cell("A2", "1").unwrap();
+        for (cell, formula, value) in [
+            ("B1", "=SUM(A1:A2)", None),
+            ("B2", "=SUMIF(A1:A2, \">0\")", None),
+            ("B3", "=AVG(A1:A2)", Some(1073741824)),
+            ("C1", "=AVERAGEIF(A1:A2, \">0\")", Some(1073741824)),
+            ("C2", "=STDEV(A1:A2)", Some(1073741823)),
+        ] {
+            sheet.set_cell(cell, formula).unwrap();
+            assert_eq!(sheet.get_value(cell), value, "{}", formula);
+        }
+    }
+
+    #[test]
+    fn test_random_input_never_panics() {
+        let len_h = 5;
+        let len_v = 5;
+        let mut sheet = utils::sheet::Sheet::new(len_h, len_v);
+        let pieces = [
+            "A",
+            "B",
+            "E",
+            "Z",
+            "AAAAAAA",
+            "a",
+            "1",
+            "5",
+            "0",
+            "99999999999",
+            "-",
+            "+",
+            "*",
+            "/",
+            "=",
+            "(",
+            ")",
+            ":",
+            " ",
+            "SUM",
+            "AVG",
+            "MAX",
+            "STDEV",
+            "scroll_to",
+            "é",
+            "\u{0}",
+        ];
+        let mut seed: u64 = 0x2545F4914F6CDD1D;
+        for _ in 0..20000 {
+            let mut input = String::new();
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            for k in 0..(seed % 8) {
+                input.push_str(pieces[((seed >> (k * 5)) % pieces.len() as u64) as usize]);
+            }
+            if let Ok(out) = sheet.parse_assignment(&input)
+                && !out.expr.op.starts_with("SL")
+            {
+                let _ = sheet.update(&out);
+            }
+        }
+    }
+
+    #[test]
+    fn test_int_to_ind() {
+        assert_eq!(int_to_ind(1001, 10), 1); // A1 in 10x10 grid
+        assert_eq!(int_to_ind(2005, 10), 2 + (5 - 1) * 10); // B5 in 10x10 grid
+        assert_eq!(int_to_ind(3003, 5), 3 + (3 - 1) * 5); // C3 in 5x5 grid
+    }
+
+    #[test]
+    fn test_cell_to_ind() {
+        assert_eq!(cell_to_ind("A1", 10), 1);
+        assert_eq!(cell_to_ind("B5", 10), 2 + (5 - 1) * 10);
+        assert_eq!(cell_to_ind("C3", 5), 3 + (3 - 1) * 5);
+    }
+
+    #[test]
+    fn test_ind_to_cell() {
+        assert_eq!(ind_to_cell(1, 10), "A1");
+        assert_eq!(ind_to_cell(10, 10), "J1");
+        assert_eq!(ind_to_cell(2 + (5 - 1) * 10, 10), "B5");
+        assert_eq!(ind_to_cell(cell_to_ind("C3", 5), 5), "C3");
+    }
+
+    #[test]
+    fn test_calc_basic_arithmetic() {
+        let mut database = vec![0, 10, 5, 0]; // Index 0 unused, A1=10, B1=5, C1=0
+        let mut err = vec![false, false, false, false];
+        let opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // Unused
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 10,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // A1 = 10
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 5,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // B1 = 5
+            Ops {
+                opcpde: String::from("VVA"),
+                cell1: 7,
+                cell2: 3,
+                expr: None,
+                criterion: None,
+            }, // C1 = 7 + 3
+        ]);
+
+        calc(
+            3,
+            &mut database,
+            &opers,
+            3,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+        );
+        assert_eq!(database[3], 10); // 7 + 3 = 10
+        assert!(!err[3]);
+    }
+
+    #[test]
+    fn test_calc_all_arithmetics() {
+        let mut database = vec![0, 10, 5, 0, 0, 0, 0, 0, 0]; // Index 0 unused, A1=10, B1=5, rest are results
+        let mut err = vec![false; 9];
+        let opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // Unused
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 10,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // A1 = 10
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 5,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // B1 = 5
+            Ops {
+                opcpde: String::from("CCA"),
+                cell1: 1,
+                cell2: 2,
+                expr: None,
+                criterion: None,
+            }, // C1 = A1 + B1 = 15
+            Ops {
+                opcpde: String::from("CCS"),
+                cell1: 1,
+                cell2: 2,
+                expr: None,
+                criterion: None,
+            }, // D1 = A1 - B1 = 5
+            Ops {
+                opcpde: String::from("CCM"),
+                cell1: 1,
+                cell2: 2,
+                expr: None,
+                criterion: None,
+            }, // E1 = A1 * B1 = 50
+            Ops {
+                opcpde: String::from("CCD"),
+                cell1: 1,
+                cell2: 2,
+                expr: None,
+                criterion: None,
+            }, // F1 = A1 / B1 = 2
+            Ops {
+                opcpde: String::from("VVM"),
+                cell1: 3,
+                cell2: 4,
+                expr: None,
+                criterion: None,
+            }, // G1 = 3 * 4 = 12
+            Ops {
+                opcpde: String::from("CVS"),
+                cell1: 1,
+                cell2: 2,
+                expr: None,
+                criterion: None,
+            }, // H1 = A1 - 2 = 8
+        ]);
+
+        for i in 3..=8 {
+            calc(
+                i,
+                &mut database,
+                &opers,
+                3,
+                &mut err,
+                &RangeOptions::default(),
+                &Limits::default(),
+            );
+        }
+
+        assert_eq!(database[3], 15); // CCA: A1 + B1 = 10 + 5 = 15
+        assert_eq!(database[4], 5); // CCS: A1 - B1 = 10 - 5 = 5
+        assert_eq!(database[5], 50); // CCM: A1 * B1 = 10 * 5 = 50
+        assert_eq!(database[6], 2); // CCD: A1 / B1 = 10 / 5 = 2
+        assert_eq!(database[7], 12); // VVM: 3 * 4 = 12
+        assert_eq!(database[8], 8); // CVS: A1 - 2 = 10 - 2 = 8
+    }
+
+    #[test]
+    fn test_calc_specialized_operations() {
+        let mut database = vec![0, 10, 20, 30, 40, 0, 0]; // Index 0 unused, A1=10, B1=20, C1=30, D1=40
+        let mut err = vec![false; 7];
+        let opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // Unused
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 10,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // A1 = 10
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 20,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // B1 = 20
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 30,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // C1 = 30
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 40,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // D1 = 40
+            Ops {
+                opcpde: String::from("EQC"),
+                cell1: 3,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // E1 = C1 = 30
+            Ops {
+                opcpde: String::from("SLC"),
+                cell1: 1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // F1 = sleep(A1) then A1 = 10
+        ]);
+
+        calc(
+            5,
+            &mut database,
+            &opers,
+            4,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+        ); // EQC
+        calc(
+            6,
+            &mut database,
+            &opers,
+            4,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+        ); // SLC
+
+        assert_eq!(database[5], 30); // EQC: E1 = C1 = 30
+        assert_eq!(database[6], 10); // SLC: F1 = A1 = 10
+    }
+
+    #[test]
+    fn test_calc_value_combinations() {
+        let mut database = vec![0, 10, 5, 0, 0, 0, 0]; // Index 0 unused
+        let mut err = vec![false; 7];
+        let opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // Unused
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 10,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // A1 = 10
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 5,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // B1 = 5
+            Ops {
+                opcpde: String::from("VCA"),
+                cell1: 7,
+                cell2: 1,
+                expr: None,
+                criterion: None,
+            }, // C1 = 7 + A1 = 17
+            Ops {
+                opcpde: String::from("CVA"),
+                cell1: 2,
+                cell2: 8,
+                expr: None,
+                criterion: None,
+            }, // D1 = B1 + 8 = 13
+            Ops {
+                opcpde: String::from("VCS"),
+                cell1: 15,
+                cell2: 2,
+                expr: None,
+                criterion: None,
+            }, // E1 = 15 - B1 = 10
+            Ops {
+                opcpde: String::from("VCD"),
+                cell1: 100,
+                cell2: 1,
+                expr: None,
+                criterion: None,
+            }, // F1 = 100 / A1 = 10
+        ]);
+
+        for i in 3..=6 {
+            calc(
+                i,
+                &mut database,
+                &opers,
+                3,
+                &mut err,
+                &RangeOptions::default(),
+                &Limits::default(),
+            );
+        }
+
+        assert_eq!(database[3], 17); // VCA: 7 + A1 = 7 + 10 = 17
+        assert_eq!(database[4], 13); // CVA: B1 + 8 = 5 + 8 = 13
+        assert_eq!(database[5], 10); // VCS: 15 - B1 = 15 - 5 = 10
+        assert_eq!(database[6], 10); // VCD: 100 / A1 = 100 / 10 = 10
+    }
+
+    #[test]
+    fn test_calc_statistical_functions() {
+        // Set up a row of cells with values 10, 20, 30, 40, 50
+        let mut database = vec![0, 10, 20, 30, 40, 50, 0, 0, 0, 0, 0]; // Index 0 unused
+        let mut err = vec![false; 11];
+        let len_h = 5; // Width of 5 cells
+
+        let opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // Unused
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 10,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // A1 = 10
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 20,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // B1 = 20
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 30,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // C1 = 30
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 40,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // D1 = 40
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 50,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // E1 = 50
+            Ops {
+                opcpde: String::from("MIN"),
+                cell1: 1,
+                cell2: 5,
+                expr: None,
+                criterion: None,
+            }, // F1 = MIN(A1:E1) = 10
+            Ops {
+                opcpde: String::from("MAX"),
+                cell1: 1,
+                cell2: 5,
+                expr: None,
+                criterion: None,
+            }, // G1 = MAX(A1:E1) = 50
+            Ops {
+                opcpde: String::from("SUM"),
+                cell1: 1,
+                cell2: 5,
+                expr: None,
+                criterion: None,
+            }, // H1 = SUM(A1:E1) = 150
+            Ops {
+                opcpde: String::from("MEA"),
+                cell1: 1,
+                cell2: 5,
+                expr: None,
+                criterion: None,
+            }, // I1 = MEA(A1:E1) = 30
+            Ops {
+                opcpde: String::from("STD"),
+                cell1: 1,
+                cell2: 5,
+                expr: None,
+                criterion: None,
+            }, // J1 = STD(A1:E1)
+        ]);
+
+        // Calculate statistical operations
+        for i in 6..=10 {
+            calc(
+                i,
+                &mut database,
+                &opers,
+                len_h,
+                &mut err,
+                &RangeOptions::default(),
+                &Limits::default(),
+            );
+        }
+
+        assert_eq!(database[6], 10); // MIN(A1:E1) = 10
+        assert_eq!(database[7], 50); // MAX(A1:E1) = 50
+        assert_eq!(database[8], 150); // SUM(A1:E1) = 150
+        assert_eq!(database[9], 30); // MEA(A1:E1) = 30
+
+        // STD calculation should be approximately √((10-30)²+(20-30)²+(30-30)²+(40-30)²+(50-30)²)/5 = √500/5 ≈ 14.14
+        let expected_std = ((400.0 + 100.0 + 0.0 + 100.0 + 400.0) / 5.0_f32).sqrt() as i32;
+        assert_eq!(database[10], expected_std); // STD(A1:E1) ≈ 14.14 -> 15 (rounded)
+    }
+
+    #[test]
+    fn test_skip_error_functions() {
+        let mut sheet = utils::sheet::Sheet::new(5, 5);
+        for cmd in [
+            "A1=10",
+            "A2=1/0",
+            "A3=30",
+            "B1=SUM(A1:A3)",
+            "B2=SUM_SKIPERR(A1:A3)",
+            "B3=AVG_SKIPERR(A1:A3)",
+            "B4=MIN_SKIPERR(A2:A2)",
+        ] {
+            run(&mut sheet, cmd).unwrap();
+        }
+        assert!(sheet.err()[2]); // SUM propagates the error of A2
+        assert!(!sheet.err()[7]);
+        assert_eq!(sheet.database()[7], 40);
+        assert!(!sheet.err()[12]);
+        assert_eq!(sheet.database()[12], 20);
+        assert!(sheet.err()[17]); // Nothing left once the error is skipped
+    }
+
+    #[test]
+    fn test_expressions() {
+        let mut sheet = utils::sheet::Sheet::new(5, 5);
+        for cmd in [
+            "B1=4",
+            "C1=6",
+            "D1=9",
+            "A1=(B1+C1)*2-D1/3",
+            "E1=A1*-(B1-C1)",
+        ] {
+            assert_eq!(run(&mut sheet, cmd), Ok(()));
+        }
+        assert_eq!(sheet.database()[1], 17);
+        assert_eq!(sheet.database()[5], 34);
+
+        // Dependencies are registered for every cell of the expression
+        run(&mut sheet, "D1=3").unwrap();
+        assert_eq!((sheet.database()[1], sheet.database()[5]), (19, 38));
+        run(&mut sheet, "C1=0-B1").unwrap();
+        assert!(sheet.verify().is_empty());
+
+        // Cycles through an expression are rejected and the old formula is kept
+        assert_eq!(run(&mut sheet, "B1=(E1+1)*2"), Err(SheetError::Cycle));
+        assert_eq!(sheet.opers()[2].opcpde, "EQV");
+
+        // Replacing the expression removes its dependencies
+        run(&mut sheet, "A1=5").unwrap();
+        assert!(!sheet.sensi()[3].contains(&1));
+        run(&mut sheet, "D1=1/(B1-4)").unwrap();
+        assert!(sheet.err()[4]);
+        assert!(sheet.verify().is_empty());
+    }
+
+    #[test]
+    fn test_arithmetic_chains() {
+        let mut sheet = utils::sheet::Sheet::new(5, 5);
+        for cmd in [
+            "B1=1",
+            "C1=2",
+            "D1=3",
+            "A1=B1+C1+D1+5",
+            "A2=B1*C1*D1-B1-B1",
+            "A3=A1+A2+A1",
+        ] {
+            run(&mut sheet, cmd).unwrap();
+        }
+        assert_eq!(sheet.database()[1], 11);
+        assert_eq!(sheet.database()[6], 4);
+        assert_eq!(sheet.database()[11], 26);
+        // Every operand is a dependency, listed once however often it is used
+        assert_eq!(sheet.sensi()[2], [1, 6]);
+        assert_eq!(sheet.sensi()[1], [11]);
+        run(&mut sheet, "D1=10").unwrap();
+        assert_eq!(sheet.database()[11], 54);
+        assert!(sheet.verify().is_empty());
+    }
+
+    #[test]
+    fn test_if_function() {
+        let mut sheet = utils::sheet::Sheet::new(5, 5);
+        for cmd in ["B1=12", "C1=1", "D1=2", "A1=IF(B1>10, C1, D1)"] {
+            run(&mut sheet, cmd).unwrap();
+        }
+        assert_eq!(sheet.database()[1], 1);
+        // All three arguments are dependencies
+        for cell in [2, 3, 4] {
+            assert!(sheet.sensi()[cell].contains(&1));
+        }
+        run(&mut sheet, "B1=5").unwrap();
+        run(&mut sheet, "D1=7").unwrap();
+        assert_eq!(sheet.database()[1], 7);
+        run(&mut sheet, "C1=1/0").unwrap();
+        assert!(!sheet.err()[1]);
+        run(&mut sheet, "B1=11").unwrap();
+        assert!(sheet.err()[1]);
+        assert!(sheet.verify().is_empty());
+    }
+
+    #[test]
+    fn test_conditional_functions() {
+        let mut sheet = utils::sheet::Sheet::new(5, 5);
+        for cmd in [
+            "A1=2",
+            "A2=7",
+            "A3=9",
+            "B1=SUMIF(A1:A5, \">5\")",
+            "B2=COUNTIF(A1:A5, \"<>7\")",
+            "B3=AVERAGEIF(A1:A5, \">=7\")",
+            "B4=AVERAGEIF(A1:A5, \">100\")",
+        ] {
+            run(&mut sheet, cmd).unwrap();
+        }
+        // Empty cells A4 and A5 are left out of the count
+        assert_eq!(sheet.database()[2], 16);
+        assert_eq!(sheet.database()[7], 2);
+        assert_eq!(sheet.database()[12], 8);
+        assert!(sheet.err()[17]);
+
+        // The whole range is a dependency, like for SUM
+        run(&mut sheet, "A5=10").unwrap();
+        assert_eq!(sheet.database()[2], 26);
+        assert_eq!(sheet.database()[7], 3);
+        run(&mut sheet, "A1=1/0").unwrap();
+        assert!(sheet.err()[2]);
+        assert_eq!(run(&mut sheet, "A4=B1"), Err(SheetError::Cycle));
+
+        // Replacing the formula drops the criterion and the old range
+        run(&mut sheet, "A1=2").unwrap();
+        run(&mut sheet, "B1=SUM(A1:A2)").unwrap();
+        assert_eq!(sheet.database()[2], 9);
+        assert!(sheet.opers()[2].criterion.is_none());
+        assert!(!sheet.sensi()[21].contains(&2));
+        assert!(sheet.verify().is_empty());
+    }
+
+    #[test]
+    fn test_counting_functions() {
+        let mut sheet = utils::sheet::Sheet::new(5, 5);
+        for cmd in [
+            "A1=0",
+            "A2=7",
+            "A3=1/0",
+            "B1=COUNT(A1:A5)",
+            "B2=COUNTA(A1:A5)",
+            "B3=COUNTBLANK(A1:A5)",
+        ] {
+            run(&mut sheet, cmd).unwrap();
+        }
+        // A1 holds 0 but is not empty, A3 is in error
+        assert_eq!(sheet.database()[2], 2);
+        assert_eq!(sheet.database()[7], 3);
+        assert_eq!(sheet.database()[12], 2);
+        assert!(!sheet.err()[2] && !sheet.err()[7] && !sheet.err()[12]);
+
+        run(&mut sheet, "A4=0").unwrap();
+        assert_eq!(sheet.database()[2], 3);
+        assert_eq!(sheet.database()[7], 4);
+        assert_eq!(sheet.database()[12], 1);
+        run(&mut sheet, "A3=5").unwrap();
+        assert_eq!(sheet.database()[2], 4);
+        assert!(sheet.verify().is_empty());
+    }
+
+    #[test]
+    fn test_lookup_functions() {
+        let mut sheet = utils::sheet::Sheet::new(5, 5);
+        for cmd in [
+            "A2=1",
+            "B2=10",
+            "A3=2",
+            "B3=20",
+            "A4=3",
+            "B4=30",
+            "E1=2",
+            "D1=VLOOKUP(E1, A2:B4, 2)",
+            "D2=HLOOKUP(10, A2:B4, 3)",
+        ] {
+            run(&mut sheet, cmd).unwrap();
+        }
+        assert_eq!(sheet.database()[4], 20);
+        assert_eq!(sheet.database()[9], 30);
+
+        // Every cell of the range is a dependency
+        run(&mut sheet, "B3=25").unwrap();
+        assert_eq!(sheet.database()[4], 25);
+        run(&mut sheet, "A3=7").unwrap();
+        assert!(sheet.err()[4]);
+        run(&mut sheet, "E1=7").unwrap();
+        assert!(!sheet.err()[4]);
+        assert_eq!(sheet.database()[4], 25);
+        assert_eq!(run(&mut sheet, "A2=D1"), Err(SheetError::Cycle));
+        assert!(sheet.verify().is_empty());
+    }
+
+    #[test]
+    fn test_math_functions() {
+        let mut sheet = utils::sheet::Sheet::new(5, 5);
+        for cmd in ["B1=-9", "C1=ABS(B1)", "D1=POW(C1,2)", "E1=MOD(D1,7)"] {
+            run(&mut sheet, cmd).unwrap();
+        }
+        assert_eq!(&sheet.database()[3..=5], &[9, 81, 4]);
+        run(&mut sheet, "B1=-2").unwrap();
+        assert_eq!(&sheet.database()[3..=5], &[2, 4, 4]);
+        run(&mut sheet, "B1=100000").unwrap();
+        assert!(sheet.err()[4] && sheet.err()[5]);
+        assert!(sheet.verify().is_empty());
+    }
+
+    #[test]
+    fn test_date_functions() {
+        use utils::date::{Kind, cell_kind, value_text};
+        let mut sheet = utils::sheet::Sheet::new(5, 5);
+        for cmd in [
+            "A1=DATE(2024, 5, 1)",
+            "A2=A1+31",
+            "A3=A2-A1",
+            "A4=A2",
+            "A5=DATEDIF(A1, A4, \"M\")",
+            "B1=NOW()",
+        ] {
+            run(&mut sheet, cmd).unwrap();
+        }
+        let kinds: Vec<Kind> = (0..5)
+            .map(|r| cell_kind(sheet.opers(), 1 + r * 5, 5))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                Kind::Date,
+                Kind::Date,
+                Kind::Number,
+                Kind::Date,
+                Kind::Number
+            ]
+        );
+        assert_eq!(
+            value_text(sheet.database(), sheet.opers(), 6, 5),
+            "2024-06-01"
+        );
+        assert_eq!(value_text(sheet.database(), sheet.opers(), 11, 5), "31");
+        assert_eq!(sheet.database()[21], 1);
+        assert_eq!(cell_kind(sheet.opers(), 2, 5), Kind::Time);
+
+        // The kind follows the formulas it depends on
+        run(&mut sheet, "A1=19844").unwrap();
+        assert_eq!(cell_kind(sheet.opers(), 16, 5), Kind::Number);
+        assert_eq!(value_text(sheet.database(), sheet.opers(), 16, 5), "19875");
+        assert!(sheet.verify().is_empty());
+    }
+
+    #[test]
+    fn test_range_functions_skip_empty_cells() {
+        let mut sheet = utils::sheet::Sheet::new(5, 5);
+        for cmd in [
+            "A1=4",
+            "A3=8",
+            "B1=AVG(A1:A3)",
+            "B2=MIN(A1:A3)",
+            "B3=SUM(A1:A3)",
+            "B4=AVG(C1:C3)",
+        ] {
+            run(&mut sheet, cmd).unwrap();
+        }
+        assert_eq!(sheet.database()[2], 6);
+        assert_eq!(sheet.database()[7], 4);
+        assert_eq!(sheet.database()[12], 12);
+        assert!(sheet.err()[17]); // No values to average
+
+        // A2 counts once it is assigned, even as 0
+        run(&mut sheet, "A2=0").unwrap();
+        assert_eq!(sheet.database()[2], 4);
+        assert_eq!(sheet.database()[7], 0);
+    }
+
+    #[test]
+    fn test_sleep_operations() {
+        let mut database = vec![0, 0, 0];
+        let mut err = vec![false; 3];
+        let opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // Unused
+            Ops {
+                opcpde: String::from("SLV"),
+                cell1: 0,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // A1 = Sleep 0s, value 0
+            Ops {
+                opcpde: String::from("SLV"),
+                cell1: 1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // B1 = Sleep 1s, value 1
+        ]);
+
+        // SLEEP gives its value without waiting, the wait is scheduled by the caller
+        let start = std::time::Instant::now();
+        calc(
+            1,
+            &mut database,
+            &opers,
+            2,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+        );
+        calc(
+            2,
+            &mut database,
+            &opers,
+            2,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+        );
+        let elapsed = start.elapsed();
+
+        assert_eq!(database[1], 0);
+        assert_eq!(database[2], 1);
+        assert!(elapsed.as_millis() < 500);
+    }
+
+    #[test]
+    fn test_error_handling_in_operations() {
+        let mut database = vec![0, 10, 0, 0, 0, 0];
+        let mut err = vec![false, false, false, false, false, false];
+        let opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // Unused
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 10,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // A1 = 10
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 0,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // B1 = 0
+            Ops {
+                opcpde: String::from("CCD"),
+                cell1: 1,
+                cell2: 2,
+                expr: None,
+                criterion: None,
+            }, // C1 = A1 / B1 = 10 / 0 (error)
+            Ops {
+                opcpde: String::from("VVD"),
+                cell1: 20,
+                cell2: 0,
+                expr: None,
+                criterion: None,
+            }, // D1 = 20 / 0 (error)
+            Ops {
+                opcpde: String::from("CVA"),
+                cell1: 3,
+                cell2: 5,
+                expr: None,
+                criterion: None,
+            }, // E1 = C1 + 5 (propagated error)
+        ]);
+
+        for i in 3..=5 {
+            calc(
+                i,
+                &mut database,
+                &opers,
+                3,
+                &mut err,
+                &RangeOptions::default(),
+                &Limits::default(),
+            );
+        }
+
+        assert!(err[3]); // C1 has error (division by zero)
+        assert!(err[4]); // D1 has error (direct division by zero)
+        assert!(err[5]); // E1 has error (derived from C1's error)
+    }
+
+    #[test]
+    fn test_val_update_complex_dependencies() {
+        // Testing a more complex dependency chain: A1 -> B1 -> C1 -> D1
+        let mut database = vec![0, 0, 0, 0, 0]; // Index 0 unused, cells 1-4
+        let mut err = vec![false, false, false, false, false];
+        let opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // Unused
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 5,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // A1 = 5
+            Ops {
+                opcpde: String::from("CVM"),
+                cell1: 1,
+                cell2: 2,
+                expr: None,
+                criterion: None,
+            }, // B1 = A1 * 2 = 10
+            Ops {
+                opcpde: String::from("CVA"),
+                cell1: 2,
+                cell2: 5,
+                expr: None,
+                criterion: None,
+            }, // C1 = B1 + 5 = 15
+            Ops {
+                opcpde: String::from("CCM"),
+                cell1: 3,
+                cell2: 1,
+                expr: None,
+                criterion: None,
+            }, // D1 = C1 * A1 = 15 * 5 = 75
+        ]);
+
+        // Topo order: 1, 2, 3, 4 (A1, B1, C1, D1)
+        let topo_arr = vec![4, 1, 2, 3, 4]; // First element is count, then indices in order
+
+        val_update(
+            &topo_arr,
+            &mut database,
+            &opers,
+            4,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        )
+        .unwrap();
+
+        assert_eq!(database[1], 5); // A1 = 5
+        assert_eq!(database[2], 10); // B1 = 5 * 2 = 10
+        assert_eq!(database[3], 15); // C1 = 10 + 5 = 15
+        assert_eq!(database[4], 75); // D1 = 15 * 5 = 75
+    }
+
+    #[test]
+    fn test_error_propagation() {
+        let mut database = vec![0, 0, 0, 0];
+        let mut err = vec![false, true, false, false]; // A1 has an error
+        let opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 10,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 5,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::from("CCA"),
+                cell1: 1,
+                cell2: 2,
+                expr: None,
+                criterion: None,
+            }, // C1 = A1 + B1, A1 has error
+        ]);
+
+        calc(
+            3,
+            &mut database,
+            &opers,
+            3,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+        );
+        assert!(err[3]); // Error propagates
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let mut database = vec![0, 10, 0, 0]; // A1=10, B1=0
+        let mut err = vec![false, false, false, false];
+        let opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 10,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 0,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::from("CCD"),
+                cell1: 1,
+                cell2: 2,
+                expr: None,
+                criterion: None,
+            }, // C1 = A1 / B1
+        ]);
+
+        calc(
+            3,
+            &mut database,
+            &opers,
+            3,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+        );
+        assert!(err[3]); // Division by zero causes error
+    }
+
+    #[test]
+    fn test_val_update() {
+        let mut database = vec![0, 0, 0, 0, 0]; // Index 0 unused, cells 1-4
+        let mut err = vec![false, false, false, false, false];
+        let opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // Unused
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 10,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // A1 = 10
+            Ops {
+                opcpde: String::from("EQV"),
+                cell1: 5,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            }, // B1 = 5
+            Ops {
+                opcpde: String::from("CCA"),
+                cell1: 1,
+                cell2: 2,
+                expr: None,
+                criterion: None,
+            }, // C1 = A1 + B1
+            Ops {
+                opcpde: String::from("CCM"),
+                cell1: 3,
+                cell2: 1,
+                expr: None,
+                criterion: None,
+            }, // D1 = C1 * A1
+        ]);
+
+        // Topo order: 1, 2, 3, 4 (A1, B1, C1, D1)
+        let topo_arr = vec![4, 1, 2, 3, 4]; // First element is count, then indices in order
+
+        val_update(
+            &topo_arr,
+            &mut database,
+            &opers,
+            4,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        )
+        .unwrap();
+
+        assert_eq!(database[1], 10); // A1 = 10
+        assert_eq!(database[2], 5); // B1 = 5
+        assert_eq!(database[3], 15); // C1 = 10 + 5 = 15
+        assert_eq!(database[4], 150); // D1 = 15 * 10 = 150
+    }
+
+    #[test]
+    fn test_cell_update_simple() {
+        let mut database = vec![0, 0, 0, 0];
+        let mut err = vec![false, false, false, false];
+        let mut opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+        ]);
+        let mut sensi = Cells::from(vec![Vec::new(), Vec::new(), Vec::new(), Vec::new()]);
+        let mut indegree = vec![0, 0, 0, 0];
+
+        // Set A1 to 10
+        let inp_arr = Assignment::new("A1", "EQV", "10", "0");
+
+        let result = cell_update(
+            &inp_arr,
+            &mut database,
+            &mut sensi,
+            &mut opers,
+            2,
+            &mut indegree,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        );
+
+        assert_eq!(result, Ok(())); // Update successful
+        assert_eq!(database[1], 10); // A1 = 10
+        assert!(!err[1]); // No error
+    }
+
+    #[test]
+    fn test_cell_update_with_dependencies() {
+        let mut database = vec![0, 0, 0, 0];
+        let mut err = vec![false, false, false, false];
+        let mut opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+        ]);
+        let mut sensi = Cells::from(vec![Vec::new(), Vec::new(), Vec::new(), Vec::new()]);
+        let mut indegree = vec![0, 0, 0, 0];
+
+        // Set A1 to 10
+        let inp_arr1 = Assignment::new("A1", "EQV", "10", "0");
+
+        // Set B1 to 5
+        let inp_arr2 = Assignment::new("B1", "EQV", "5", "0");
+
+        // Set C1 to A1 + B1
+        let inp_arr3 = Assignment::new("C1", "CCA", "A1", "B1");
+
+        cell_update(
+            &inp_arr1,
+            &mut database,
+            &mut sensi,
+            &mut opers,
+            3,
+            &mut indegree,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        )
+        .unwrap();
+        cell_update(
+            &inp_arr2,
+            &mut database,
+            &mut sensi,
+            &mut opers,
+            3,
+            &mut indegree,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        )
+        .unwrap();
+        let result = cell_update(
+            &inp_arr3,
+            &mut database,
+            &mut sensi,
+            &mut opers,
+            3,
+            &mut indegree,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        );
+
+        assert_eq!(result, Ok(())); // Update successful
+        assert_eq!(database[3], 15); // C1 = A1 + B1 = 10 + 5 = 15
+
+        // Now update A1 and check if C1 updates
+        let inp_arr4 = Assignment::new("A1", "EQV", "20", "0");
+
+        cell_update(
+            &inp_arr4,
+            &mut database,
+            &mut sensi,
+            &mut opers,
+            3,
+            &mut indegree,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        )
+        .unwrap();
+        assert_eq!(database[1], 20); // A1 = 20
+        assert_eq!(database[3], 25); // C1 = A1 + B1 = 20 + 5 = 25
+    }
+
+    #[test]
+    fn test_cell_update_cycle_detection() {
+        let mut database = vec![0, 0, 0, 0];
+        let mut err = vec![false, false, false, false];
+        let mut opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            },
+        ]);
+        let mut sensi = Cells::from(vec![Vec::new(), Vec::new(), Vec::new(), Vec::new()]);
+        let mut indegree = vec![0, 0, 0, 0];
+
+        // Set A1 to B1 + 1
+        let inp_arr1 = Assignment::new("A1", "CVA", "B1", "1");
+
+        // Set B1 to A1 + 1 (creates cycle)
+        let inp_arr2 = Assignment::new("B1", "CVA", "A1", "1");
+
+        let result1 = cell_update(
+            &inp_arr1,
+            &mut database,
+            &mut sensi,
+            &mut opers,
+            3,
+            &mut indegree,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        );
+        let result2 = cell_update(
+            &inp_arr2,
+            &mut database,
+            &mut sensi,
+            &mut opers,
+            3,
+            &mut indegree,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        );
+
+        assert_eq!(result1, Ok(())); // First update is fine
+        assert_eq!(result2, Err(SheetError::Cycle)); // Second update creates cycle, should return 0
+    }
+
+    #[test]
+    fn test_range_operations() {
+        let mut database = vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]; // Cells 1-9 with values 1-9
+        let mut err = vec![false; 10];
+        let mut opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            };
+            10
+        ]);
+        let mut sensi = Cells::from(vec![Vec::new(); 10]);
+        let mut indegree = vec![0; 10];
+
+        // Initialize cells with values
+        for i in 1..9 {
+            let inp_arr = Assignment::new(&format!("A{}", i), "EQV", &format!("{}", i), "0");
+            cell_update(
+                &inp_arr,
+                &mut database,
+                &mut sensi,
+                &mut opers,
+                1,
+                &mut indegree,
+                &mut err,
+                &RangeOptions::default(),
+                &Limits::default(),
+                &Cancel::default(),
+            )
+            .unwrap();
+        }
+
+        // Set A9 to SUM of range A1:A8
+        let inp_arr = Assignment::new("A9", "SUM", "A1", "A8");
+
+        let result = cell_update(
+            &inp_arr,
+            &mut database,
+            &mut sensi,
+            &mut opers,
+            1,
+            &mut indegree,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        );
+
+        assert_eq!(result, Ok(())); // Update successful
+        assert_eq!(database[9], 36);
+
+        // Change A1 and check if A9 updates
+        let inp_arr_update = Assignment::new("A1", "EQV", "10", "0");
+
+        cell_update(
+            &inp_arr_update,
+            &mut database,
+            &mut sensi,
+            &mut opers,
+            1,
+            &mut indegree,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        )
+        .unwrap();
+        assert_eq!(database[1], 10); // A1 = 10
+        assert_eq!(database[9], 45);
+
+        // Update A9 to sum only A1:A5 instead of A1:A8
+        let inp_arr_range_update = Assignment::new("A9", "SUM", "A1", "A5");
+
+        cell_update(
+            &inp_arr_range_update,
+            &mut database,
+            &mut sensi,
+            &mut opers,
+            1,
+            &mut indegree,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        )
+        .unwrap();
+        assert_eq!(database[9], 24); // Sum of (10+2+3+4+5) = 24
+
+        // Make sure updating a cell outside the new range doesn't affect the sum
+        let inp_arr_out_of_range = Assignment::new("A8", "EQV", "100", "0");
+
+        cell_update(
+            &inp_arr_out_of_range,
+            &mut database,
+            &mut sensi,
+            &mut opers,
+            1,
+            &mut indegree,
+            &mut err,
+            &RangeOptions::default(),
+            &Limits::default(),
+            &Cancel::default(),
+        )
+        .unwrap();
+        assert_eq!(database[8], 100); // A8 = 100
+        assert_eq!(database[9], 24); // Sum remains unchanged as A8 is outside the range
+    }
+
+    #[test]
+    fn test_complex_cell_updates() {
+        let len_h = 10;
+        let len_v = 10;
+        let mut database = vec![0; (len_h * len_v + 1) as usize];
+        let mut err = vec![false; (len_h * len_v + 1) as usize];
+        let mut opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            };
+            (len_h * len_v + 1) as usize
+        ]);
+        let mut indegree = vec![0; (len_h * len_v + 1) as usize];
+        let mut sensi = Cells::from(vec![Vec::<i32>::new(); (len_h * len_v + 1) as usize]);
+
+        // Create a series of complex updates to test the spreadsheet functionality
+        let test_inputs = [
+            "A1=SUM(B1:B4)",
+            "A1=MIN(B2:B8)",
+            "A1=1",
+            "A1=MAX(B2:B8)",
+            "A1=B2",
+        ];
+
+        // Process each test input
+        for (i, input) in test_inputs.iter().enumerate() {
+            println!("Processing input {}: {}", i + 1, input);
+
+            let input = input.trim_end().to_string();
+            // rest of the existing code to process the input
+
+            if let Ok(out) =
+                utils::input::input(&input, len_h, len_v).and_then(ParsedCommand::into_assignment)
+            {
+                cell_update(
+                    &out,
+                    &mut database,
+                    &mut sensi,
+                    &mut opers,
+                    len_h,
+                    &mut indegree,
+                    &mut err,
+                    &RangeOptions::default(),
+                    &Limits::default(),
+                    &Cancel::default(),
+                )
+                .unwrap();
+            }
+        }
+        assert_eq!(database[1], 0); // A1 = 0
+    }
+
+    #[test]
+    fn test_complex_cell_updates_cyclic() {
+        let len_h = 10;
+        let len_v = 10;
+        let mut database = vec![0; (len_h * len_v + 1) as usize];
+        let mut err = vec![false; (len_h * len_v + 1) as usize];
+        let mut opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            };
+            (len_h * len_v + 1) as usize
+        ]);
+        let mut indegree = vec![0; (len_h * len_v + 1) as usize];
+        let mut sensi = Cells::from(vec![Vec::<i32>::new(); (len_h * len_v + 1) as usize]);
+
+        let mut suc = Ok(());
+
+        // Create a series of complex updates to test the spreadsheet functionality
+        let test_inputs = ["A1=A2", "A1=MAX(B2:B8)", "A1=A2", "A1=MIN(B2:B8)", "A1=A1"];
+
+        // Process each test input
+        for (i, input) in test_inputs.iter().enumerate() {
+            println!("Processing input {}: {}", i + 1, input);
+
+            let input = input.trim_end().to_string();
+            // rest of the existing code to process the input
+
+            if let Ok(out) =
+                utils::input::input(&input, len_h, len_v).and_then(ParsedCommand::into_assignment)
+            {
+                suc = cell_update(
+                    &out,
+                    &mut database,
+                    &mut sensi,
+                    &mut opers,
+                    len_h,
+                    &mut indegree,
+                    &mut err,
+                    &RangeOptions::default(),
+                    &Limits::default(),
+                    &Cancel::default(),
+                );
+            }
+        }
+        assert_eq!(suc, Err(SheetError::Cycle));
+    }
+
+    #[test]
+    fn test_complex_range_updates_cyclic() {
+        let len_h = 10;
+        let len_v = 10;
+        let mut database = vec![0; (len_h * len_v + 1) as usize];
+        let mut err = vec![false; (len_h * len_v + 1) as usize];
+        let mut opers = Cells::from(vec![
+            Ops {
+                opcpde: String::new(),
+                cell1: -1,
+                cell2: -1,
+                expr: None,
+                criterion: None,
+            };
+            (len_h * len_v + 1) as usize
+        ]);
+        let mut indegree = vec![0; (len_h * len_v + 1) as usize];
+        let mut sensi = Cells::from(vec![Vec::<i32>::new(); (len_h * len_v + 1) as usize]);
+
+        let mut suc = Ok(());
+
+        // Create a series of complex updates to test the spreadsheet functionality
+        let test_inputs = ["A1=MAX(B2:B8)", "A1=MAX(A1:B5)"];
+
+        // Process each test input
+        for (i, input) in test_inputs.iter().enumerate() {
+            println!("Processing input {}: {}", i + 1, input);
+
+            let input = input.trim_end().to_string();
+            // rest of the existing code to process the input
+
+            if let Ok(out) =
+                utils::input::input(&input, len_h, len_v).and_then(ParsedCommand::into_assignment)
+            {
+                suc = cell_update(
+                    &out,
+                    &mut database,
+                    &mut sensi,
+                    &mut opers,
+                    len_h,
+                    &mut indegree,
+                    &mut err,
+                    &RangeOptions::default(),
+                    &Limits::default(),
+                    &Cancel::default(),
+                );
+            }
+        }
+        assert_eq!(suc, Err(SheetError::Cycle));
+    }
+}
@@ -0,0 +1,131 @@
+//! This module contains the input of the terminal interface.
+//!
+//! Lines are read from standard input on a separate thread, so that commands can be typed
+//! while a long recalculation runs. Such commands are queued and run once it is finished.
+//! Ctrl+C cancels the recalculation meanwhile, and quits as usual otherwise.
+
+use crate::utils::sheet::{Finished, Recalc};
+use std::collections::VecDeque;
+use std::io::{IsTerminal, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Frames of the spinner shown while a recalculation runs.
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/// Time between two frames of the spinner.
+const FRAME: Duration = Duration::from_millis(100);
+
+/// Commands typed at the prompt.
+///
+/// # Fields
+/// * `lines` - Lines read from standard input
+/// * `queue` - Lines typed while a recalculation was running, oldest first
+/// * `interrupt` - Set when Ctrl+C is pressed during a recalculation
+/// * `idle` - Whether no recalculation is running, in which case Ctrl+C quits
+pub struct Input {
+    lines: Receiver<String>,
+    queue: VecDeque<String>,
+    interrupt: Arc<AtomicBool>,
+    idle: Arc<AtomicBool>,
+}
+
+impl Input {
+    /// Starts reading lines from standard input, and catching Ctrl+C.
+    pub fn stdin() -> Self {
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let idle = Arc::new(AtomicBool::new(true));
+        // Without the handlers Ctrl+C still quits, but cannot cancel a recalculation
+        let _ = signal_hook::flag::register_conditional_default(
+            signal_hook::consts::SIGINT,
+            Arc::clone(&idle),
+        );
+        let _ = signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&interrupt));
+        let (tx, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line.trim_end().to_string()).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            lines,
+            queue: VecDeque::new(),
+            interrupt,
+            idle,
+        }
+    }
+
+    /// Takes the next command, waiting for one to be typed if none is queued.
+    ///
+    /// Queued commands are echoed after the prompt on a terminal, since the line they were
+    /// typed on has been overwritten by the spinner.
+    /// # Returns
+    /// The command, or `None` at the end of the input.
+    pub fn next(&mut self) -> Option<String> {
+        if let Some(line) = self.queue.pop_front() {
+            if std::io::stdout().is_terminal() {
+                println!("{}", line);
+            }
+            return Some(line);
+        }
+        self.lines.recv().ok()
+    }
+
+    /// Waits for the user to press Enter.
+    pub fn pause(&mut self) {
+        let _ = self.lines.recv();
+    }
+
+    /// Waits for a recalculation running on a background thread.
+    ///
+    /// On a terminal the prompt shows "(busy)" with a spinner and how long the recalculation
+    /// has been running. Commands typed in the meantime are queued, and Ctrl+C cancels the
+    /// recalculation, which then finishes with `SheetError::Cancelled`.
+    /// # Arguments
+    /// * `recalc` - The running recalculation
+    /// # Returns
+    /// The result of `Recalc::finish`.
+    pub fn wait(&mut self, recalc: Recalc) -> Finished {
+        let terminal = std::io::stdout().is_terminal();
+        let start = Instant::now();
+        let mut frame = 0;
+        self.interrupt.store(false, Ordering::Relaxed);
+        self.idle.store(false, Ordering::Relaxed);
+        while !recalc.is_finished() {
+            if self.interrupt.swap(false, Ordering::Relaxed) {
+                recalc.cancel();
+            }
+            if terminal {
+                let queued = match self.queue.len() {
+                    0 => String::new(),
+                    n => format!(", {} queued", n),
+                };
+                print!(
+                    "\r[{:.1}] (busy, Ctrl+C to cancel) {}{} ",
+                    start.elapsed().as_secs_f64(),
+                    SPINNER[frame % SPINNER.len()],
+                    queued
+                );
+                std::io::stdout().flush().unwrap();
+            }
+            frame += 1;
+            match self.lines.recv_timeout(FRAME) {
+                Ok(line) => self.queue.push_back(line),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => std::thread::sleep(FRAME),
+            }
+        }
+        self.idle.store(true, Ordering::Relaxed);
+        if terminal {
+            // Clearing the spinner line
+            print!("\r\x1b[2K");
+            std::io::stdout().flush().unwrap();
+        }
+        recalc.finish()
+    }
+}
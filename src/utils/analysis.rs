@@ -0,0 +1,788 @@
+//! This module contains analysis commands, which summarise a range into other cells.
+//!
+//! Like auto-fill, each command builds the assignment commands for its result cells and
+//! leaves applying them to the caller. The results are plain values, so they can be used by
+//! formulas and plotted like any other cells.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+use crate::utils::display::get_label;
+use crate::utils::operations::RangeOptions;
+
+/// Splits a command of the form `<name> <arguments> -> <cell>`.
+///
+/// # Arguments
+/// * `command` - The full command string.
+/// * `len_h` - The number of columns in the data array.
+/// * `len_v` - The number of rows in the data array.
+/// # Returns
+/// The words before the arrow, without the command name, and the column and row of the
+/// target cell, or `None` if there is no valid target.
+fn split_target(command: &str, len_h: i32, len_v: i32) -> Option<(Vec<&str>, (i32, i32))> {
+    let (args, target) = command.split_once("->")?;
+    let target = target.trim();
+    if !crate::utils::input::is_valid_cell(target, len_h, len_v) {
+        return None;
+    }
+    let k = crate::cell_to_int(target);
+    Some((
+        args.split_whitespace().skip(1).collect(),
+        (k / 1000, k % 1000),
+    ))
+}
+
+/// Collects the values of a range, row by row, leaving out empty cells and cells in error.
+///
+/// # Arguments
+/// * `database` - A reference to the data array.
+/// * `err` - A reference to the error array.
+/// * `opers` - The operations for each cell.
+/// * `len_h` - The number of columns in the data array.
+/// * `range` - First column, first row, last column and last row of the range.
+/// # Returns
+/// The values of the cells that are assigned and not in error.
+pub fn values(
+    database: &[i32],
+    err: &[bool],
+    opers: &Cells<Ops>,
+    len_h: i32,
+    range: (i32, i32, i32, i32),
+) -> Vec<i32> {
+    let (x1, y1, x2, y2) = range;
+    let mut values = Vec::new();
+    for row in y1..=y2 {
+        for col in x1..=x2 {
+            let ind = ((row - 1) * len_h + col) as usize;
+            if !err[ind] && !opers[ind].opcpde.is_empty() {
+                values.push(database[ind]);
+            }
+        }
+    }
+    values
+}
+
+/// Summarizes values as shown for a selection, such as "Sum: 10  Average: 2.50  Count: 4".
+///
+/// # Arguments
+/// * `values` - The values, such as given by `values`.
+/// # Returns
+/// The sum, average, count, minimum and maximum, or `None` if there are no values.
+pub fn summary(values: &[i32]) -> Option<String> {
+    let min = values.iter().min()?;
+    let max = values.iter().max()?;
+    let sum: i64 = values.iter().map(|&v| v as i64).sum();
+    Some(format!(
+        "Sum: {}  Average: {:.2}  Count: {}  Min: {}  Max: {}",
+        sum,
+        sum as f64 / values.len() as f64,
+        values.len(),
+        min,
+        max
+    ))
+}
+
+/// Builds the assignment command for one cell.
+fn assign(col: i32, row: i32, value: impl std::fmt::Display) -> String {
+    format!("{}{}={}", get_label(col), row, value)
+}
+
+/// Computes the edges of `count` equal bins covering the values.
+///
+/// # Arguments
+/// * `values` - The values to cover, not empty.
+/// * `count` - The number of bins, at least 1.
+/// # Returns
+/// `count + 1` increasing edges, or `None` if they do not fit in a cell.
+fn equal_edges(values: &[i32], count: i64) -> Option<Vec<i32>> {
+    let min = *values.iter().min()? as i64;
+    let max = *values.iter().max()? as i64;
+    let width = ((max - min + count - 1) / count).max(1);
+    (0..=count)
+        .map(|i| i32::try_from(min + i * width).ok())
+        .collect()
+}
+
+/// Counts how many values fall in each bin.
+///
+/// Bins include their lower edge and exclude their upper edge, except the last bin which
+/// includes both. Values outside the edges are not counted.
+/// # Arguments
+/// * `values` - The values to count.
+/// * `edges` - The increasing bin edges.
+/// # Returns
+/// One count per bin.
+fn bin_counts(values: &[i32], edges: &[i32]) -> Vec<i32> {
+    let bins = edges.len().saturating_sub(1);
+    let mut counts = vec![0; bins];
+    for &v in values {
+        if bins == 0 || v < edges[0] || v > edges[bins] {
+            continue;
+        }
+        // Index of the last edge not above the value
+        let i = edges.partition_point(|&e| e <= v) - 1;
+        counts[i.min(bins - 1)] += 1;
+    }
+    counts
+}
+
+/// Runs a `histogram <range> [<bins>|<edge>,<edge>,...] -> <cell>` command.
+///
+/// The bins are either a number of equal bins covering the values (10 if not given) or a
+/// comma separated list of increasing edges. Starting at the target cell, one row is written
+/// per bin with its lower edge, upper edge and count in three neighbouring columns.
+/// # Arguments
+/// * `command` - The full command string.
+/// * `database` - A reference to the data array.
+/// * `err` - A reference to the error array.
+/// * `opers` - The operations for each cell.
+/// * `options` - The settings of the spreadsheet read by range functions.
+/// * `len_h` - The number of columns in the data array.
+/// * `len_v` - The number of rows in the data array.
+/// # Returns
+/// The assignment commands, or "Invalid Histogram" if the command is invalid, the range has
+/// no values or the result does not fit below and right of the target.
+pub fn histogram(
+    command: &str,
+    database: &[i32],
+    err: &[bool],
+    opers: &Cells<Ops>,
+    _options: &RangeOptions,
+    len_h: i32,
+    len_v: i32,
+) -> Result<Vec<String>, &'static str> {
+    const INVALID: &str = "Invalid Histogram";
+    let (args, (col, row)) = split_target(command, len_h, len_v).ok_or(INVALID)?;
+    let range = match args[..] {
+        [range] | [range, _] => {
+            crate::utils::input::parse_range(range, len_h, len_v).ok_or(INVALID)?
+        }
+        _ => return Err(INVALID),
+    };
+    let values = values(database, err, opers, len_h, range);
+    let edges = match args.get(1) {
+        Some(list) if list.contains(',') => {
+            let edges: Vec<i32> = list
+                .split(',')
+                .map(|e| e.trim().parse::<i32>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| INVALID)?;
+            if edges.len() < 2 || edges.windows(2).any(|w| w[0] >= w[1]) {
+                return Err(INVALID);
+            }
+            edges
+        }
+        bins => {
+            let count = match bins {
+                Some(b) => b.parse::<i64>().map_err(|_| INVALID)?,
+                None => 10,
+            };
+            if count < 1 || count > len_v as i64 || values.is_empty() {
+                return Err(INVALID);
+            }
+            equal_edges(&values, count).ok_or(INVALID)?
+        }
+    };
+    let bins = edges.len() as i32 - 1;
+    if col + 2 > len_h || row + bins - 1 > len_v {
+        return Err(INVALID);
+    }
+
+    let mut commands = Vec::new();
+    for (i, count) in bin_counts(&values, &edges).into_iter().enumerate() {
+        let r = row + i as i32;
+        commands.push(assign(col, r, edges[i]));
+        commands.push(assign(col + 1, r, edges[i + 1]));
+        commands.push(assign(col + 2, r, count));
+    }
+    Ok(commands)
+}
+
+/// Runs a `rolling <range> window <n> <mean|min|max|std> [values|formulas] -> <cell>` command.
+///
+/// The range must be a single column. One result is computed per full window of `n`
+/// consecutive cells and written down the column starting at the target, so the first result
+/// covers the first `n` cells of the range. By default the results are values, computed the
+/// same way as the AVG, MIN, MAX and STDEV functions; windows with a cell in error are skipped.
+/// With `formulas` each result is a live AVG, MIN, MAX or STDEV formula over its window.
+/// # Arguments
+/// * `command` - The full command string.
+/// * `database` - A reference to the data array.
+/// * `err` - A reference to the error array.
+/// * `opers` - The operations for each cell.
+/// * `options` - The settings of the spreadsheet read by range functions.
+/// * `len_h` - The number of columns in the data array.
+/// * `len_v` - The number of rows in the data array.
+/// # Returns
+/// The assignment commands, or "Invalid Rolling Window" if the command is invalid, the window
+/// is longer than the range or the results do not fit below the target.
+pub fn rolling(
+    command: &str,
+    database: &[i32],
+    err: &[bool],
+    opers: &Cells<Ops>,
+    options: &RangeOptions,
+    len_h: i32,
+    len_v: i32,
+) -> Result<Vec<String>, &'static str> {
+    const INVALID: &str = "Invalid Rolling Window";
+    let (args, (col, row)) = split_target(command, len_h, len_v).ok_or(INVALID)?;
+    let (range, window, stat, formulas) = match args[..] {
+        [range, "window", window, stat] => (range, window, stat, false),
+        [range, "window", window, stat, "values"] => (range, window, stat, false),
+        [range, "window", window, stat, "formulas"] => (range, window, stat, true),
+        _ => return Err(INVALID),
+    };
+    let (x1, y1, x2, y2) = crate::utils::input::parse_range(range, len_h, len_v).ok_or(INVALID)?;
+    let window = window.parse::<i32>().map_err(|_| INVALID)?;
+    let count = y2 - y1 + 2 - window;
+    if x1 != x2 || window < 1 || count < 1 || row + count - 1 > len_v {
+        return Err(INVALID);
+    }
+    type Stat = fn(i32, i32, &[i32], i32, &[bool], i32, &Cells<Ops>, &RangeOptions) -> (i32, bool);
+    let (function, stat): (&str, Stat) = match stat {
+        "mean" => ("AVG", crate::utils::operations::avg),
+        "min" => ("MIN", crate::utils::operations::min),
+        "max" => ("MAX", crate::utils::operations::max),
+        "std" => ("STDEV", crate::utils::operations::stdev),
+        _ => return Err(INVALID),
+    };
+
+    let label = get_label(x1);
+    let mut commands = Vec::new();
+    for k in 0..count {
+        let (first, last) = (y1 + k, y1 + k + window - 1);
+        if formulas {
+            commands.push(assign(
+                col,
+                row + k,
+                format!("{}({}{}:{}{})", function, label, first, label, last),
+            ));
+            continue;
+        }
+        // No cell holds the window, so index 0, which is not a cell, stands in for it
+        let (value, error) = stat(
+            (first - 1) * len_h + x1,
+            (last - 1) * len_h + x1,
+            database,
+            len_h,
+            err,
+            0,
+            opers,
+            options,
+        );
+        if !error {
+            commands.push(assign(col, row + k, value));
+        }
+    }
+    Ok(commands)
+}
+
+/// A small random number generator (SplitMix64), so samples can be repeated from a seed.
+struct Rng(u64);
+
+impl Rng {
+    /// Gives the next random number.
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Gives a random index below `n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// Draws a random sample of values.
+///
+/// # Arguments
+/// * `values` - The values to draw from.
+/// * `count` - The number of values to draw.
+/// * `replace` - Whether a value can be drawn more than once.
+/// * `seed` - Seed of the random numbers; the same seed gives the same sample.
+/// # Returns
+/// The drawn values, or fewer if drawing without replacement from fewer values.
+fn draw(values: &[i32], count: usize, replace: bool, seed: u64) -> Vec<i32> {
+    let mut rng = Rng(seed);
+    if values.is_empty() {
+        return Vec::new();
+    }
+    if replace {
+        return (0..count)
+            .map(|_| values[rng.below(values.len())])
+            .collect();
+    }
+    // Partial Fisher-Yates shuffle
+    let mut pool = values.to_vec();
+    let count = count.min(pool.len());
+    for i in 0..count {
+        let j = i + rng.below(pool.len() - i);
+        pool.swap(i, j);
+    }
+    pool.truncate(count);
+    pool
+}
+
+/// Runs a `sample <range> <n> [replace] [seed <seed>] -> <cell>` command.
+///
+/// Draws `n` values from the range, leaving out cells in error, and writes them down the
+/// column starting at the target. Without `replace` each cell is drawn at most once. Without
+/// a seed the sample is different every time, unless a global seed was given with `--seed`.
+/// # Arguments
+/// * `command` - The full command string.
+/// * `database` - A reference to the data array.
+/// * `err` - A reference to the error array.
+/// * `opers` - The operations for each cell.
+/// * `options` - The settings of the spreadsheet read by range functions.
+/// * `len_h` - The number of columns in the data array.
+/// * `len_v` - The number of rows in the data array.
+/// # Returns
+/// The assignment commands, or "Invalid Sample" if the command is invalid, the range has too
+/// few values or the sample does not fit below the target.
+pub fn sample(
+    command: &str,
+    database: &[i32],
+    err: &[bool],
+    opers: &Cells<Ops>,
+    _options: &RangeOptions,
+    len_h: i32,
+    len_v: i32,
+) -> Result<Vec<String>, &'static str> {
+    const INVALID: &str = "Invalid Sample";
+    let (args, (col, row)) = split_target(command, len_h, len_v).ok_or(INVALID)?;
+    let (range, count, options) = match args[..] {
+        [range, count, ref options @ ..] => (range, count, options),
+        _ => return Err(INVALID),
+    };
+    let range = crate::utils::input::parse_range(range, len_h, len_v).ok_or(INVALID)?;
+    let count = count.parse::<i32>().map_err(|_| INVALID)?;
+    let (replace, seed) = match options {
+        [] => (false, None),
+        ["replace"] => (true, None),
+        ["seed", seed] => (false, Some(seed)),
+        ["replace", "seed", seed] => (true, Some(seed)),
+        _ => return Err(INVALID),
+    };
+    let seed = match seed {
+        Some(seed) => seed.parse::<u64>().map_err(|_| INVALID)?,
+        None => crate::utils::settings::random_seed(),
+    };
+    let values = values(database, err, opers, len_h, range);
+    if count < 1
+        || row + count - 1 > len_v
+        || values.is_empty()
+        || (!replace && count as usize > values.len())
+    {
+        return Err(INVALID);
+    }
+
+    Ok(draw(&values, count as usize, replace, seed)
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| assign(col, row + i as i32, v))
+        .collect())
+}
+
+/// The signature shared by the analysis commands.
+type Analysis = fn(
+    &str,
+    &[i32],
+    &[bool],
+    &Cells<Ops>,
+    &RangeOptions,
+    i32,
+    i32,
+) -> Result<Vec<String>, &'static str>;
+
+/// Name, implementation and usage of each analysis command.
+const COMMANDS: [(&str, Analysis, &str); 3] = [
+    (
+        "histogram",
+        histogram,
+        "histogram <cell>:<cell> [<bins>|<edge>,<edge>,...] -> <cell>",
+    ),
+    (
+        "rolling",
+        rolling,
+        "rolling <cell>:<cell> window <n> <mean|min|max|std> [values|formulas] -> <cell>",
+    ),
+    (
+        "sample",
+        sample,
+        "sample <cell>:<cell> <n> [replace] [seed <seed>] -> <cell>",
+    ),
+];
+
+/// Runs an analysis command.
+///
+/// # Arguments
+/// * `command` - The full command string.
+/// * `database` - A reference to the data array.
+/// * `err` - A reference to the error array.
+/// * `opers` - The operations for each cell.
+/// * `options` - The settings of the spreadsheet read by range functions.
+/// * `len_h` - The number of columns in the data array.
+/// * `len_v` - The number of rows in the data array.
+/// # Returns
+/// `None` if the command is not an analysis command, otherwise the result of the command.
+pub fn run(
+    command: &str,
+    database: &[i32],
+    err: &[bool],
+    opers: &Cells<Ops>,
+    options: &RangeOptions,
+    len_h: i32,
+    len_v: i32,
+) -> Option<Result<Vec<String>, &'static str>> {
+    let name = command.split_whitespace().next()?;
+    let (_, analysis, _) = COMMANDS.iter().find(|(n, _, _)| *n == name)?;
+    Some(analysis(
+        command, database, err, opers, options, len_h, len_v,
+    ))
+}
+
+/// Gives the usage of an analysis command.
+///
+/// # Arguments
+/// * `command` - The full command string.
+/// # Returns
+/// The usage of the command, or an empty string if it is not an analysis command.
+pub fn usage(command: &str) -> &'static str {
+    let name = command.split_whitespace().next().unwrap_or("");
+    COMMANDS
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map_or("", |(_, _, usage)| usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the operations of a sheet where only the given cells are assigned.
+    fn assigned(n: usize, cells: &[usize]) -> Cells<Ops> {
+        let mut opers: Cells<Ops> = Cells::new(n);
+        for &cell in cells {
+            opers[cell].opcpde = "EQV".to_string();
+        }
+        opers
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(summary(&[]), None);
+        assert_eq!(
+            summary(&[4, -1, 2]).as_deref(),
+            Some("Sum: 5  Average: 1.67  Count: 3  Min: -1  Max: 4")
+        );
+        assert_eq!(
+            summary(&[i32::MAX, i32::MAX]).as_deref(),
+            Some(
+                "Sum: 4294967294  Average: 2147483647.00  Count: 2  Min: 2147483647  Max: 2147483647"
+            )
+        );
+    }
+
+    #[test]
+    fn test_bin_counts() {
+        let edges = [0, 10, 20];
+        assert_eq!(bin_counts(&[0, 5, 10, 19, 20, 21, -1], &edges), vec![2, 3]);
+        assert_eq!(bin_counts(&[1, 2], &[0]), Vec::<i32>::new());
+        assert_eq!(equal_edges(&[3, 7, 12], 2), Some(vec![3, 8, 13]));
+        assert_eq!(equal_edges(&[4, 4], 3), Some(vec![4, 5, 6, 7]));
+        assert_eq!(
+            equal_edges(&[i32::MAX - 1, i32::MAX], 1),
+            Some(vec![i32::MAX - 1, i32::MAX])
+        );
+        assert_eq!(equal_edges(&[0, i32::MAX], 2), None);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let options = RangeOptions::default();
+        // 5x5 grid with A1..A4 = 1, 2, 9, ERR
+        let mut database = vec![0; 26];
+        let mut err = vec![false; 26];
+        database[1] = 1;
+        database[6] = 2;
+        database[11] = 9;
+        err[16] = true;
+        let opers = assigned(26, &[1, 6, 11, 16]);
+        assert_eq!(
+            histogram(
+                "histogram A1:A4 2 -> B1",
+                &database,
+                &err,
+                &opers,
+                &options,
+                5,
+                5
+            ),
+            Ok(vec![
+                "B1=1".to_string(),
+                "C1=5".to_string(),
+                "D1=2".to_string(),
+                "B2=5".to_string(),
+                "C2=9".to_string(),
+                "D2=1".to_string(),
+            ])
+        );
+        assert_eq!(
+            histogram(
+                "histogram A1:A4 0,2,10 -> C4",
+                &database,
+                &err,
+                &opers,
+                &options,
+                5,
+                5
+            ),
+            Ok(vec![
+                "C4=0".to_string(),
+                "D4=2".to_string(),
+                "E4=1".to_string(),
+                "C5=2".to_string(),
+                "D5=10".to_string(),
+                "E5=2".to_string(),
+            ])
+        );
+        assert_eq!(
+            histogram(
+                "histogram A1:A4 -> B1",
+                &database,
+                &err,
+                &opers,
+                &options,
+                5,
+                5
+            ),
+            Err("Invalid Histogram")
+        );
+        assert_eq!(
+            histogram(
+                "histogram A1:A4 2 -> D1",
+                &database,
+                &err,
+                &opers,
+                &options,
+                5,
+                5
+            ),
+            Err("Invalid Histogram")
+        );
+        assert_eq!(
+            histogram(
+                "histogram A1:A4 5,1 -> B1",
+                &database,
+                &err,
+                &opers,
+                &options,
+                5,
+                5
+            ),
+            Err("Invalid Histogram")
+        );
+        assert_eq!(
+            histogram(
+                "histogram A4:A4 2 -> B1",
+                &database,
+                &err,
+                &opers,
+                &options,
+                5,
+                5
+            ),
+            Err("Invalid Histogram")
+        );
+        assert_eq!(
+            histogram("histogram A1:A4 2", &database, &err, &opers, &options, 5, 5),
+            Err("Invalid Histogram")
+        );
+    }
+
+    #[test]
+    fn test_rolling() {
+        let options = RangeOptions::default();
+        // 3x5 grid with A1..A5 = 1, 5, 3, ERR, 7
+        let mut database = vec![0; 16];
+        let mut err = vec![false; 16];
+        for (row, value) in [(1, 1), (2, 5), (3, 3), (5, 7)] {
+            database[(row - 1) * 3 + 1] = value;
+        }
+        err[10] = true;
+        let opers = assigned(16, &[1, 4, 7, 10, 13]);
+        assert_eq!(
+            rolling(
+                "rolling A1:A5 window 2 mean -> B1",
+                &database,
+                &err,
+                &opers,
+                &options,
+                3,
+                5
+            ),
+            Ok(vec!["B1=3".to_string(), "B2=4".to_string()])
+        );
+        assert_eq!(
+            rolling(
+                "rolling A1:A3 window 3 max values -> C5",
+                &database,
+                &err,
+                &opers,
+                &options,
+                3,
+                5
+            ),
+            Ok(vec!["C5=5".to_string()])
+        );
+        assert_eq!(
+            rolling(
+                "rolling A1:A3 window 2 std -> B1",
+                &database,
+                &err,
+                &opers,
+                &options,
+                3,
+                5
+            ),
+            Ok(vec!["B1=2".to_string(), "B2=1".to_string()])
+        );
+        assert_eq!(
+            rolling(
+                "rolling A2:A5 window 3 min formulas -> B2",
+                &database,
+                &err,
+                &opers,
+                &options,
+                3,
+                5
+            ),
+            Ok(vec![
+                "B2=MIN(A2:A4)".to_string(),
+                "B3=MIN(A3:A5)".to_string()
+            ])
+        );
+        for invalid in [
+            "rolling A1:A5 window 6 mean -> B1",
+            "rolling A1:A5 window 0 mean -> B1",
+            "rolling A1:A5 window 2 median -> B1",
+            "rolling A1:B5 window 2 mean -> C1",
+            "rolling A1:A5 window 2 mean -> B3",
+            "rolling A1:A5 2 mean -> B1",
+        ] {
+            assert_eq!(
+                rolling(invalid, &database, &err, &opers, &options, 3, 5),
+                Err("Invalid Rolling Window")
+            );
+        }
+    }
+
+    #[test]
+    fn test_draw() {
+        let values = [1, 2, 3, 4, 5];
+        let mut drawn = draw(&values, 5, false, 7);
+        assert_eq!(drawn, draw(&values, 5, false, 7));
+        drawn.sort();
+        assert_eq!(drawn, values);
+        assert_eq!(draw(&values, 9, false, 7).len(), 5);
+        let replaced = draw(&values, 50, true, 7);
+        assert_eq!(replaced.len(), 50);
+        assert!(replaced.iter().all(|v| values.contains(v)));
+        assert!(draw(&[], 3, true, 7).is_empty());
+    }
+
+    #[test]
+    fn test_sample() {
+        let options = RangeOptions::default();
+        // 3x4 grid with A1..A3 = 4, ERR, 6
+        let mut database = vec![0; 13];
+        let mut err = vec![false; 13];
+        database[1] = 4;
+        database[7] = 6;
+        err[4] = true;
+        let opers = assigned(13, &[1, 4, 7]);
+        let cells = sample(
+            "sample A1:A3 2 seed 1 -> B1",
+            &database,
+            &err,
+            &opers,
+            &options,
+            3,
+            4,
+        )
+        .unwrap();
+        assert!(cells == ["B1=4", "B2=6"] || cells == ["B1=6", "B2=4"]);
+        assert_eq!(
+            sample(
+                "sample A1:A3 2 seed 1 -> B1",
+                &database,
+                &err,
+                &opers,
+                &options,
+                3,
+                4
+            ),
+            sample(
+                "sample A1:A3 2 seed 1 -> B1",
+                &database,
+                &err,
+                &opers,
+                &options,
+                3,
+                4
+            )
+        );
+        assert_eq!(
+            sample(
+                "sample A1:A3 4 replace -> C1",
+                &database,
+                &err,
+                &opers,
+                &options,
+                3,
+                4
+            )
+            .unwrap()
+            .len(),
+            4
+        );
+        for invalid in [
+            "sample A1:A3 3 -> B1",
+            "sample A1:A3 2 replace -> B4",
+            "sample A1:A3 0 -> B1",
+            "sample A1:A3 2 seed -> B1",
+            "sample A1:A3 2 shuffle -> B1",
+            "sample A2:A2 1 replace -> B1",
+        ] {
+            assert_eq!(
+                sample(invalid, &database, &err, &opers, &options, 3, 4),
+                Err("Invalid Sample")
+            );
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let options = RangeOptions::default();
+        let (database, err, opers) = (vec![0; 10], vec![false; 10], assigned(10, &[]));
+        assert!(
+            run(
+                "rolling A1:A3 window 1 mean -> B1",
+                &database,
+                &err,
+                &opers,
+                &options,
+                3,
+                3
+            )
+            .is_some()
+        );
+        assert!(run("histogram", &database, &err, &opers, &options, 3, 3).is_some());
+        assert!(run("A1=histogram", &database, &err, &opers, &options, 3, 3).is_none());
+        assert!(usage("rolling x").starts_with("rolling "));
+        assert_eq!(usage("hist"), "");
+    }
+}
@@ -0,0 +1,117 @@
+//! This module contains assertions, which check the spreadsheet from a script.
+//!
+//! `assert <condition>` holds if the condition, written like a formula such as "A1 == 42" or
+//! "B1+B2 > 0", is not 0. `assert_err <cell>` holds if the cell is in an error state. A
+//! failed assertion makes the terminal interface exit with a nonzero status, so scripts piped
+//! into it can be used as regression tests.
+
+use crate::utils::error::SheetError;
+use crate::utils::expr::Expr;
+
+/// Runs an `assert <condition>` or `assert_err <cell>` command.
+/// # Arguments
+/// * `command` - The full command string.
+/// * `database` - A reference to the data array.
+/// * `err` - A reference to the error array.
+/// * `len_h` - The number of columns in the data array.
+/// * `len_v` - The number of rows in the data array.
+/// * `limits` - The limits of the spreadsheet, whose safe mode freezes TODAY and NOW.
+/// # Returns
+/// `Ok(None)` if the assertion holds, `Ok(Some(message))` describing the failure otherwise,
+/// or `Err(status)` if the command is not a valid assertion.
+pub fn check(
+    command: &str,
+    database: &[i32],
+    err: &[bool],
+    len_h: i32,
+    len_v: i32,
+    limits: &crate::utils::settings::Limits,
+) -> Result<Option<String>, &'static str> {
+    if let Some(cell) = command.strip_prefix("assert_err ") {
+        let cell = cell.trim();
+        if !crate::utils::input::is_valid_cell(cell, len_h, len_v) {
+            return Err("Invalid Cell");
+        }
+        let ind = crate::cell_to_ind(cell, len_h) as usize;
+        if err[ind] {
+            return Ok(None);
+        }
+        return Ok(Some(format!(
+            "Assertion failed: {} is {}, expected an error",
+            cell, database[ind]
+        )));
+    }
+    let condition = command
+        .strip_prefix("assert ")
+        .ok_or("Invalid Assertion")?
+        .trim();
+    let expr = Expr::parse(condition).map_err(SheetError::status)?;
+    expr.check(len_h, len_v).map_err(SheetError::status)?;
+    if expr
+        .eval(database, err, len_h, limits)
+        .is_some_and(|v| v != 0)
+    {
+        return Ok(None);
+    }
+    let cells: Vec<String> = expr
+        .cells(len_h)
+        .into_iter()
+        .map(|c| {
+            let value = if err[c as usize] {
+                String::from("ERR")
+            } else {
+                database[c as usize].to_string()
+            };
+            format!("{} = {}", crate::ind_to_cell(c, len_h), value)
+        })
+        .collect();
+    if cells.is_empty() {
+        Ok(Some(format!("Assertion failed: {}", condition)))
+    } else {
+        Ok(Some(format!(
+            "Assertion failed: {} ({})",
+            condition,
+            cells.join(", ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        // A1 = 42, B1 = 7, A2 is in error
+        let database = vec![0, 42, 7, 0, 0];
+        let err = vec![false, false, false, true, false];
+        let check = |command| check(command, &database, &err, 2, 2, &Default::default());
+
+        assert_eq!(check("assert A1 == 42"), Ok(None));
+        assert_eq!(check("assert A1 > B1*5"), Ok(None));
+        assert_eq!(check("assert_err A2"), Ok(None));
+        assert_eq!(
+            check("assert A1 != 42"),
+            Ok(Some(String::from("Assertion failed: A1 != 42 (A1 = 42)")))
+        );
+        assert_eq!(
+            check("assert A2+B1 == 0"),
+            Ok(Some(String::from(
+                "Assertion failed: A2+B1 == 0 (A2 = ERR, B1 = 7)"
+            )))
+        );
+        assert_eq!(
+            check("assert_err B1"),
+            Ok(Some(String::from(
+                "Assertion failed: B1 is 7, expected an error"
+            )))
+        );
+        assert_eq!(
+            check("assert 1 == 2").unwrap().unwrap(),
+            "Assertion failed: 1 == 2"
+        );
+        assert_eq!(check("assert_err C1"), Err("Invalid Cell"));
+        assert_eq!(check("assert A3 == 1"), Err("Invalid Cell"));
+        assert_eq!(check("assert A1 ="), Err("Invalid Expression"));
+    }
+}
@@ -0,0 +1,104 @@
+//! This module contains backups of workbooks, kept when a .rsk file is saved over.
+//!
+//! Before a save replaces a file, the file is copied to `<file>.bak1`, and earlier backups
+//! move along to `<file>.bak2` and so on, so the last few versions of the file are kept:
+//!
+//! ```text
+//! model.rsk       the last save
+//! model.rsk.bak1  the save before it
+//! model.rsk.bak2  the save before that
+//! ```
+//!
+//! The oldest backup is dropped once there are as many as the user asked to keep.
+
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Most backups of a file that can be kept.
+pub const MAX_BACKUPS: u32 = 20;
+
+/// Gives the path of a backup of a file.
+/// # Arguments
+/// * `file` - The path of the file
+/// * `n` - The number of the backup, 1 for the newest
+pub fn path(file: &str, n: u32) -> String {
+    format!("{}.bak{}", file, n)
+}
+
+/// Keeps a backup of a file that is about to be saved over.
+/// # Arguments
+/// * `file` - The path of the file
+/// * `keep` - The number of backups to keep, at most `MAX_BACKUPS`, or 0 to keep none
+/// # Returns
+/// `Ok(())` once the file is copied to its newest backup, or if there is nothing to back up.
+pub fn rotate(file: &str, keep: u32) -> io::Result<()> {
+    let keep = keep.min(MAX_BACKUPS);
+    if keep == 0 || !Path::new(file).is_file() {
+        return Ok(());
+    }
+    let oldest = path(file, keep);
+    if Path::new(&oldest).exists() {
+        std::fs::remove_file(oldest)?;
+    }
+    for n in (1..keep).rev() {
+        let from = path(file, n);
+        if Path::new(&from).exists() {
+            std::fs::rename(from, path(file, n + 1))?;
+        }
+    }
+    // The file is copied rather than moved, so a failed save still leaves it in place
+    std::fs::copy(file, path(file, 1))?;
+    Ok(())
+}
+
+/// Lists the backups of a file.
+/// # Arguments
+/// * `file` - The path of the file
+/// # Returns
+/// The number and modification time of each backup, newest first.
+pub fn list(file: &str) -> Vec<(u32, SystemTime)> {
+    (1..=MAX_BACKUPS)
+        .filter_map(|n| {
+            let modified = std::fs::metadata(path(file, n)).ok()?.modified().ok()?;
+            Some((n, modified))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate() {
+        let file = std::env::temp_dir().join(format!("backup_{}.rsk", std::process::id()));
+        let file = file.to_str().unwrap();
+        let read = |n| std::fs::read_to_string(path(file, n)).ok();
+
+        // Nothing to back up before the first save
+        rotate(file, 2).unwrap();
+        assert!(list(file).is_empty());
+
+        for version in ["one", "two", "three", "four"] {
+            rotate(file, 2).unwrap();
+            std::fs::write(file, version).unwrap();
+        }
+        assert_eq!(read(1).as_deref(), Some("three"));
+        assert_eq!(read(2).as_deref(), Some("two"));
+        assert_eq!(read(3), None);
+        assert_eq!(
+            list(file).iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+            [1, 2]
+        );
+
+        // Keeping none leaves the backups alone
+        rotate(file, 0).unwrap();
+        assert_eq!(read(1).as_deref(), Some("three"));
+
+        for n in 1..=2 {
+            std::fs::remove_file(path(file, n)).unwrap();
+        }
+        std::fs::remove_file(file).unwrap();
+    }
+}
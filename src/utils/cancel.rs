@@ -0,0 +1,25 @@
+//! This module contains the token that cancels a recalculation.
+//!
+//! The token is shared between the thread recalculating and whoever may cancel it: the GUI,
+//! from its Cancel button or Escape, and the terminal, from Ctrl+C. The recalculation checks
+//! it between cells and, once it is cancelled, stops with `SheetError::Cancelled`, which
+//! reverts the edit like any other aborted update.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A token that cancels the recalculations it is given to. Clones share the token.
+#[derive(Debug, Clone, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    /// Cancels the recalculations given this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Checks whether the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
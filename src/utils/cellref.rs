@@ -0,0 +1,192 @@
+//! This module contains cell references and the two styles they can be written in.
+//!
+//! In the A1 style a cell is written as its column letters and row number, e.g. "B3". In the
+//! R1C1 style it is written as its row and column numbers, e.g. "R3C2". Both styles are
+//! accepted in any letter case wherever a cell is typed, so "b3" and "r3c2" work too.
+//!
+//! Cells are always written in the A1 style in anything that is saved, such as formulas,
+//! files and the journal. The style a view is set to, with `ref_style r1c1` or the R1C1
+//! setting of the GUI, only decides how cells are shown there (see `CellRef::show`).
+
+use std::fmt;
+
+/// A reference to a single cell.
+///
+/// # Fields
+/// * `col` - The column number (1-based)
+/// * `row` - The row number (1-based)
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellRef {
+    pub col: i32,
+    pub row: i32,
+}
+
+impl CellRef {
+    /// Parses a cell reference in either style.
+    /// # Arguments
+    /// * `text` - The reference, e.g. "B3", "b3" or "R3C2".
+    /// # Returns
+    /// The cell, or `None` if the text is not a cell reference.
+    pub fn parse(text: &str) -> Option<Self> {
+        Self::parse_r1c1(text).or_else(|| Self::parse_a1(text))
+    }
+
+    /// Parses a reference such as "B3" in any letter case.
+    fn parse_a1(text: &str) -> Option<Self> {
+        let part = text.chars().take_while(|c| c.is_ascii_alphabetic()).count();
+        let (letters, digits) = text.split_at(part);
+        let mut col: i32 = 0;
+        for c in letters.chars() {
+            let diff = c.to_ascii_uppercase() as i32 - 'A' as i32 + 1;
+            col = col.checked_mul(26)?.checked_add(diff)?;
+        }
+        let row = number(digits)?;
+        (col > 0).then_some(Self { col, row })
+    }
+
+    /// Parses a reference such as "R3C2" in any letter case.
+    fn parse_r1c1(text: &str) -> Option<Self> {
+        let rest = text.strip_prefix(['R', 'r'])?;
+        let (row, col) = rest.split_once(['C', 'c'])?;
+        Some(Self {
+            col: number(col)?,
+            row: number(row)?,
+        })
+    }
+
+    /// Gives the cell at an index of the spreadsheet arrays.
+    /// # Arguments
+    /// * `ind` - The cell index (1-based).
+    /// * `len_h` - The number of columns in the spreadsheet.
+    pub fn from_ind(ind: i32, len_h: i32) -> Self {
+        Self {
+            col: (ind - 1) % len_h + 1,
+            row: (ind - 1) / len_h + 1,
+        }
+    }
+
+    /// Gives the index of the cell in the spreadsheet arrays.
+    /// # Arguments
+    /// * `len_h` - The number of columns in the spreadsheet.
+    pub fn ind(self, len_h: i32) -> i32 {
+        self.col + (self.row - 1) * len_h
+    }
+
+    /// Writes the cell in the A1 style, e.g. "B3".
+    pub fn to_a1(self) -> String {
+        format!("{}{}", crate::utils::display::get_label(self.col), self.row)
+    }
+
+    /// Writes the cell in the R1C1 style, e.g. "R3C2".
+    pub fn to_r1c1(self) -> String {
+        format!("R{}C{}", self.row, self.col)
+    }
+
+    /// Writes the cell as shown in a view.
+    /// # Arguments
+    /// * `r1c1` - Whether the view shows cells in the R1C1 style.
+    pub fn show(self, r1c1: bool) -> String {
+        if r1c1 { self.to_r1c1() } else { self.to_a1() }
+    }
+}
+
+/// Writes the cell in the A1 style, as it is saved.
+impl fmt::Display for CellRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_a1())
+    }
+}
+
+/// Parses a row or column number made of digits only.
+fn number(digits: &str) -> Option<i32> {
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok().filter(|&n| n > 0)
+}
+
+/// Gives the header of a column as shown in a view.
+/// # Arguments
+/// * `col` - The column number.
+/// * `r1c1` - Whether the view shows cells in the R1C1 style.
+/// # Returns
+/// The column letters, or the column number in the R1C1 style.
+pub fn col_header(col: i32, r1c1: bool) -> String {
+    if r1c1 {
+        col.to_string()
+    } else {
+        crate::utils::display::get_label(col)
+    }
+}
+
+/// Writes the cells of a formula, as saved in the A1 style, as shown in a view.
+/// # Arguments
+/// * `formula` - The text of the formula, such as `formula_text` gives.
+/// * `r1c1` - Whether the view shows cells in the R1C1 style.
+pub fn show_formula(formula: &str, r1c1: bool) -> String {
+    if !r1c1 {
+        return formula.to_string();
+    }
+    crate::utils::rewrite::map_names(formula, |name, call| {
+        (!call)
+            .then(|| CellRef::parse(name))
+            .flatten()
+            .map(CellRef::to_r1c1)
+    })
+}
+
+/// Rewrites a cell reference in the A1 style with capital letters, which is how the parser
+/// passes cells on.
+/// # Arguments
+/// * `text` - The text, which may or may not be a cell reference.
+/// # Returns
+/// The rewritten reference, or `text` unchanged if it is not a cell reference.
+pub fn normalize(text: &str) -> String {
+    match CellRef::parse(text) {
+        Some(cell) => cell.to_a1(),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let b3 = Some(CellRef { col: 2, row: 3 });
+        assert_eq!(CellRef::parse("B3"), b3);
+        assert_eq!(CellRef::parse("b3"), b3);
+        assert_eq!(CellRef::parse("R3C2"), b3);
+        assert_eq!(CellRef::parse("r3c2"), b3);
+        assert_eq!(CellRef::parse("aa1"), Some(CellRef { col: 27, row: 1 }));
+        assert_eq!(CellRef::parse("RC1"), Some(CellRef { col: 471, row: 1 }));
+        for bad in [
+            "", "B", "3", "B0", "R0C1", "R1C", "B3C", "B+3", "é1", "ZZZZZZZ1",
+        ] {
+            assert_eq!(CellRef::parse(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_styles() {
+        let cell = CellRef::from_ind(12, 5);
+        assert_eq!(cell, CellRef { col: 2, row: 3 });
+        assert_eq!(cell.ind(5), 12);
+        assert_eq!(cell.to_a1(), "B3");
+        assert_eq!(cell.to_r1c1(), "R3C2");
+        assert_eq!(cell.to_string(), "B3");
+        assert_eq!(cell.show(true), "R3C2");
+        assert_eq!(cell.show(false), "B3");
+        assert_eq!(col_header(28, false), "AB");
+        assert_eq!(col_header(28, true), "28");
+        assert_eq!(
+            show_formula("SUMIF(A1:B3, \">5\")+LOG10(C2)", true),
+            "SUMIF(R1C1:R3C2, \">5\")+LOG10(R2C3)"
+        );
+        assert_eq!(show_formula("A1+1", false), "A1+1");
+        assert_eq!(normalize("r3c2"), "B3");
+        assert_eq!(normalize("b3"), "B3");
+        assert_eq!(normalize("42"), "42");
+    }
+}
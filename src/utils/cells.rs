@@ -0,0 +1,242 @@
+//! This module contains the storage of per-cell data that is empty for most cells, such as the
+//! operation of each cell and its dependents.
+//!
+//! Small spreadsheets keep such data in a `Vec` with an entry for every cell, which is the
+//! fastest to read. Spreadsheets with more than `DENSE_LIMIT` cells keep only the cells that
+//! were written to, in a `HashMap`, so a mostly empty 1000x1000 grid does not allocate an entry
+//! for each of its million cells. Both are indexed the same way, and read an empty cell as
+//! `T::default()`. Writing through `IndexMut` adds an entry for the cell, so code that may
+//! leave a cell empty, such as removing a dependent, goes through `get_mut` and
+//! `remove_if_empty` instead.
+//!
+//! Files list every cell of small spreadsheets, and only the cells that are not empty, with
+//! their index, for large ones. Both are read either way, so files saved from a small
+//! spreadsheet open in a large one and the other way round.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut};
+
+/// Most cells kept with an entry for every cell.
+pub const DENSE_LIMIT: usize = 1 << 16;
+
+/// Data kept for every cell of a spreadsheet, indexed by cell index.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cells<T> {
+    /// An entry for every cell.
+    Dense(Vec<T>),
+    /// Entries for the cells written to. The others read as `empty`.
+    Sparse {
+        len: usize,
+        cells: HashMap<usize, T>,
+        empty: T,
+    },
+}
+
+impl<T: Default + Clone + PartialEq> Cells<T> {
+    /// Creates storage where every cell is empty.
+    /// # Arguments
+    /// * `len` - The number of cells
+    pub fn new(len: usize) -> Self {
+        if len <= DENSE_LIMIT {
+            Cells::Dense(vec![T::default(); len])
+        } else {
+            Cells::Sparse {
+                len,
+                cells: HashMap::new(),
+                empty: T::default(),
+            }
+        }
+    }
+
+    /// Creates storage from the data of every cell, keeping only the cells that are not empty
+    /// if there are more than `DENSE_LIMIT`.
+    /// # Arguments
+    /// * `cells` - The data of every cell
+    pub fn from_vec(cells: Vec<T>) -> Self {
+        if cells.len() <= DENSE_LIMIT {
+            return Cells::Dense(cells);
+        }
+        let empty = T::default();
+        Cells::Sparse {
+            len: cells.len(),
+            cells: cells
+                .into_iter()
+                .enumerate()
+                .filter(|(_, cell)| *cell != empty)
+                .collect(),
+            empty,
+        }
+    }
+}
+
+impl<T> Cells<T> {
+    /// Gives the number of cells.
+    pub fn len(&self) -> usize {
+        match self {
+            Cells::Dense(cells) => cells.len(),
+            Cells::Sparse { len, .. } => *len,
+        }
+    }
+
+    /// Checks whether there are no cells.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over the data of every cell, in order of cell index.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        (0..self.len()).map(move |i| &self[i])
+    }
+
+    /// Gives the data of a cell to change, without adding an entry for an empty cell.
+    /// # Arguments
+    /// * `i` - The cell index
+    /// # Returns
+    /// The data of the cell, or `None` for a cell without an entry.
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        match self {
+            Cells::Dense(cells) => cells.get_mut(i),
+            Cells::Sparse { cells, .. } => cells.get_mut(&i),
+        }
+    }
+}
+
+impl<T: PartialEq> Cells<T> {
+    /// Drops the entry of a cell that was left empty, so it takes no room in sparse storage.
+    /// # Arguments
+    /// * `i` - The cell index
+    pub fn remove_if_empty(&mut self, i: usize) {
+        if let Cells::Sparse { cells, empty, .. } = self
+            && cells.get(&i).is_some_and(|cell| cell == empty)
+        {
+            cells.remove(&i);
+        }
+    }
+}
+
+impl<T> Index<usize> for Cells<T> {
+    type Output = T;
+
+    fn index(&self, i: usize) -> &T {
+        match self {
+            Cells::Dense(cells) => &cells[i],
+            Cells::Sparse { len, cells, empty } => {
+                assert!(i < *len, "cell {} out of {}", i, len);
+                cells.get(&i).unwrap_or(empty)
+            }
+        }
+    }
+}
+
+impl<T: Clone> IndexMut<usize> for Cells<T> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        match self {
+            Cells::Dense(cells) => &mut cells[i],
+            Cells::Sparse { len, cells, empty } => {
+                assert!(i < *len, "cell {} out of {}", i, len);
+                cells.entry(i).or_insert_with(|| empty.clone())
+            }
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for Cells<T> {
+    fn from(cells: Vec<T>) -> Self {
+        Cells::Dense(cells)
+    }
+}
+
+/// How cells are written in files.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Stored<T> {
+    /// The data of every cell.
+    List(Vec<T>),
+    /// The number of cells, and the index and data of the cells that are not empty.
+    Sparse { len: usize, cells: Vec<(usize, T)> },
+}
+
+impl<T: Serialize + Clone> Serialize for Cells<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Cells::Dense(cells) => serializer.collect_seq(cells),
+            Cells::Sparse { len, cells, .. } => {
+                // In order of cell index, so the same spreadsheet is always saved the same way
+                let mut cells: Vec<(usize, T)> =
+                    cells.iter().map(|(&i, cell)| (i, cell.clone())).collect();
+                cells.sort_by_key(|&(i, _)| i);
+                Stored::Sparse { len: *len, cells }.serialize(serializer)
+            }
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de> + Default + Clone + PartialEq> Deserialize<'de> for Cells<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match Stored::deserialize(deserializer)? {
+            Stored::List(cells) => Cells::from_vec(cells),
+            Stored::Sparse { len, cells } => {
+                let mut res = Cells::new(len);
+                for (i, cell) in cells {
+                    if i >= len {
+                        return Err(serde::de::Error::custom(format!(
+                            "cell {} out of {}",
+                            i, len
+                        )));
+                    }
+                    res[i] = cell;
+                    res.remove_if_empty(i);
+                }
+                res
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparse_matches_dense() {
+        let len = DENSE_LIMIT + 10;
+        let mut sparse: Cells<Vec<i32>> = Cells::new(len);
+        assert!(matches!(sparse, Cells::Sparse { .. }));
+        let mut dense = Cells::Dense(vec![Vec::new(); len]);
+        for cells in [&mut sparse, &mut dense] {
+            cells[3].push(7);
+            cells[len - 1].push(9);
+        }
+        assert_eq!(sparse.len(), len);
+        assert_eq!(sparse[3], [7]);
+        assert!(sparse[4].is_empty());
+        assert!(sparse.iter().eq(dense.iter()));
+
+        // Files of large spreadsheets only list the cells that are not empty
+        let json = serde_json::to_string(&sparse).unwrap();
+        assert_eq!(
+            json,
+            format!(r#"{{"len":{},"cells":[[3,[7]],[{},[9]]]}}"#, len, len - 1)
+        );
+        for json in [json, serde_json::to_string(&dense).unwrap()] {
+            match serde_json::from_str::<Cells<Vec<i32>>>(&json).unwrap() {
+                Cells::Sparse { cells, .. } => assert_eq!(cells.len(), 2),
+                Cells::Dense(_) => panic!("read back with an entry for every cell"),
+            }
+        }
+
+        // Cells left empty take no room
+        sparse.get_mut(3).unwrap().clear();
+        sparse.remove_if_empty(3);
+        assert!(sparse.get_mut(3).is_none());
+        assert!(sparse[3].is_empty());
+        match &sparse {
+            Cells::Sparse { cells, .. } => assert_eq!(cells.len(), 1),
+            Cells::Dense(_) => unreachable!(),
+        }
+
+        let small: Cells<Vec<i32>> = Cells::new(DENSE_LIMIT);
+        assert!(matches!(small, Cells::Dense(_)));
+    }
+}
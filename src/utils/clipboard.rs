@@ -0,0 +1,572 @@
+//! This module contains copying of cell ranges and the paste operations applied to them.
+//!
+//! A copied range is stored as a `Clip` holding both the values and the operations of its
+//! cells. Pasting does not change the spreadsheet directly; it produces the assignment
+//! commands (e.g. "B2=A2+1") that the caller applies through `cell_update`, so every pasted
+//! cell goes through the same validation and cycle detection as a typed formula.
+//!
+//! Styles and number formats are copied along with the cells and pasted on their own with
+//! `PasteMode::Formats` (see `paste_formats`).
+//!
+//! Ranges are also exchanged with other applications through the system clipboard as
+//! tab-separated text (see `to_tsv` and `paste_text`).
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+use crate::utils::graph::RANGE_OPS;
+use crate::utils::numfmt::NumberFormat;
+use crate::utils::style::CellStyle;
+use std::collections::BTreeMap;
+
+/// A copied range of cells.
+///
+/// # Fields
+/// * `width` - Number of columns in the range
+/// * `height` - Number of rows in the range
+/// * `origin` - Column and row of the top-left cell of the range
+/// * `len_h` - Number of columns of the spreadsheet the range was copied from
+/// * `values` - Values of the cells in row-major order, `None` for cells in error
+/// * `opers` - Operations of the cells in row-major order
+/// * `styles` - Styles of the cells in row-major order, `None` for unstyled cells
+/// * `number_formats` - Number formats of the cells in row-major order, `None` for
+///   unformatted cells
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Clip {
+    pub width: i32,
+    pub height: i32,
+    pub origin: (i32, i32),
+    pub len_h: i32,
+    pub values: Vec<Option<i32>>,
+    pub opers: Vec<Ops>,
+    #[serde(default)]
+    pub styles: Vec<Option<CellStyle>>,
+    #[serde(default)]
+    pub number_formats: Vec<Option<NumberFormat>>,
+}
+
+/// What a paste writes into the target cells.
+///
+/// # Variants
+/// * `Values` - The copied values as constants
+/// * `Formulas` - The copied formulas, with cell references moved by the same offset as the cell
+/// * `Add` - The target value plus the copied value
+/// * `Subtract` - The target value minus the copied value
+/// * `Formats` - The copied styles and number formats, leaving the target values
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum PasteMode {
+    #[default]
+    Values,
+    Formulas,
+    Add,
+    Subtract,
+    Formats,
+}
+
+/// Copies a range of cells.
+/// # Arguments
+/// * `database` - A reference to the data array.
+/// * `err` - A reference to the error array.
+/// * `opers` - The operations for each cell.
+/// * `styles` - The style of each styled cell.
+/// * `number_formats` - The number format of each formatted cell.
+/// * `len_h` - The number of columns in the data array.
+/// * `range` - First column, first row, last column and last row of the range.
+/// # Returns
+/// The copied range.
+pub fn copy(
+    database: &[i32],
+    err: &[bool],
+    opers: &Cells<Ops>,
+    styles: &BTreeMap<i32, CellStyle>,
+    number_formats: &BTreeMap<i32, NumberFormat>,
+    len_h: i32,
+    range: (i32, i32, i32, i32),
+) -> Clip {
+    let (x1, y1, x2, y2) = range;
+    let mut clip = Clip {
+        width: x2 - x1 + 1,
+        height: y2 - y1 + 1,
+        origin: (x1, y1),
+        len_h,
+        values: Vec::new(),
+        opers: Vec::new(),
+        styles: Vec::new(),
+        number_formats: Vec::new(),
+    };
+    for y in y1..=y2 {
+        for x in x1..=x2 {
+            let ind = ((y - 1) * len_h + x) as usize;
+            clip.values
+                .push(if err[ind] { None } else { Some(database[ind]) });
+            clip.opers.push(opers[ind].clone());
+            clip.styles.push(styles.get(&(ind as i32)).copied());
+            clip.number_formats
+                .push(number_formats.get(&(ind as i32)).cloned());
+        }
+    }
+    clip
+}
+
+/// Moves a cell reference by an offset.
+/// # Arguments
+/// * `ind` - The cell index in the source spreadsheet.
+/// * `src_len_h` - The number of columns of the source spreadsheet.
+/// * `offset` - Columns and rows to move the reference by.
+/// * `len_h` - The number of columns of the target spreadsheet.
+/// * `len_v` - The number of rows of the target spreadsheet.
+/// # Returns
+/// The moved cell index in the target spreadsheet, or `None` if it falls outside it.
+fn shift_ref(ind: i32, src_len_h: i32, offset: (i32, i32), len_h: i32, len_v: i32) -> Option<i32> {
+    let mut col = ind % src_len_h;
+    if col == 0 {
+        col = src_len_h;
+    }
+    let row = (ind - col) / src_len_h + 1;
+    let (col, row) = (col + offset.0, row + offset.1);
+    if (1..=len_h).contains(&col) && (1..=len_v).contains(&row) {
+        Some(col + (row - 1) * len_h)
+    } else {
+        None
+    }
+}
+
+/// Moves all cell references of an operation by an offset.
+/// # Returns
+/// The moved operation, or `None` if a reference falls outside the target spreadsheet.
+pub fn shift_op(
+    op: &Ops,
+    src_len_h: i32,
+    offset: (i32, i32),
+    len_h: i32,
+    len_v: i32,
+) -> Option<Ops> {
+    let code = op.opcpde.as_str();
+    let range = RANGE_OPS.contains(&code);
+    let mut res = op.clone();
+    if let Some(expr) = &op.expr {
+        res.expr = Some(expr.map_cells(&mut |cell| {
+            let ind = shift_ref(cell.ind(src_len_h), src_len_h, offset, len_h, len_v)?;
+            Some(crate::utils::cellref::CellRef::from_ind(ind, len_h))
+        })?);
+        return Some(res);
+    }
+    if range || code.starts_with('C') || code == "EQC" || code == "SLC" {
+        res.cell1 = shift_ref(op.cell1, src_len_h, offset, len_h, len_v)?;
+    }
+    if range || code.chars().nth(1) == Some('C') {
+        res.cell2 = shift_ref(op.cell2, src_len_h, offset, len_h, len_v)?;
+    }
+    Some(res)
+}
+
+/// Builds the assignments that paste a copied range.
+/// # Arguments
+/// * `clip` - The copied range.
+/// * `target` - Column and row of the top-left cell to paste to.
+/// * `mode` - What to write into the target cells.
+/// * `transpose` - Whether rows of the range become columns.
+/// * `database` - A reference to the data array of the target spreadsheet.
+/// * `err` - A reference to the error array of the target spreadsheet.
+/// * `len_h` - The number of columns of the target spreadsheet.
+/// * `len_v` - The number of rows of the target spreadsheet.
+/// # Returns
+/// One assignment command per pasted cell. Cells that would fall outside the spreadsheet,
+/// copied cells in error, empty cells in value and formula mode and target cells in error in
+/// arithmetic modes are skipped. Format mode writes no assignments.
+#[allow(clippy::too_many_arguments)]
+pub fn paste(
+    clip: &Clip,
+    target: (i32, i32),
+    mode: PasteMode,
+    transpose: bool,
+    database: &[i32],
+    err: &[bool],
+    len_h: i32,
+    len_v: i32,
+) -> Vec<String> {
+    let mut commands = Vec::new();
+    for i in 0..clip.height {
+        for j in 0..clip.width {
+            let (col, row) = if transpose {
+                (target.0 + i, target.1 + j)
+            } else {
+                (target.0 + j, target.1 + i)
+            };
+            if col > len_h || row > len_v {
+                continue;
+            }
+            let k = (i * clip.width + j) as usize;
+            let ind = ((row - 1) * len_h + col) as usize;
+            let formula = match mode {
+                PasteMode::Formats => continue,
+                PasteMode::Formulas => {
+                    let offset = (col - clip.origin.0 - j, row - clip.origin.1 - i);
+                    match shift_op(&clip.opers[k], clip.len_h, offset, len_h, len_v) {
+                        Some(op) if !op.opcpde.is_empty() => {
+                            crate::utils::input::formula_text(&op, len_h)
+                        }
+                        _ => continue,
+                    }
+                }
+                PasteMode::Values => match clip.values[k] {
+                    Some(_) if clip.opers[k].opcpde.is_empty() => continue,
+                    Some(v) => v.to_string(),
+                    None => continue,
+                },
+                PasteMode::Add | PasteMode::Subtract => {
+                    let (Some(v), false) = (clip.values[k], err[ind]) else {
+                        continue;
+                    };
+                    let res = if mode == PasteMode::Add {
+                        database[ind].checked_add(v)
+                    } else {
+                        database[ind].checked_sub(v)
+                    };
+                    match res {
+                        Some(r) => r.to_string(),
+                        None => continue,
+                    }
+                }
+            };
+            commands.push(format!(
+                "{}{}={}",
+                crate::utils::display::get_label(col),
+                row,
+                formula
+            ));
+        }
+    }
+    commands
+}
+
+/// Pastes the styles and number formats of a copied range.
+///
+/// A copied cell without a style or number format removes the one of its target cell.
+/// # Arguments
+/// * `clip` - The copied range.
+/// * `target` - Column and row of the top-left cell to paste to.
+/// * `transpose` - Whether rows of the range become columns.
+/// * `styles` - The style of each styled cell of the target spreadsheet, updated in place.
+/// * `number_formats` - The number format of each formatted cell of the target spreadsheet,
+///   updated in place.
+/// * `len_h` - The number of columns of the target spreadsheet.
+/// * `len_v` - The number of rows of the target spreadsheet.
+/// # Returns
+/// The number of target cells whose format changed.
+pub fn paste_formats(
+    clip: &Clip,
+    target: (i32, i32),
+    transpose: bool,
+    styles: &mut BTreeMap<i32, CellStyle>,
+    number_formats: &mut BTreeMap<i32, NumberFormat>,
+    len_h: i32,
+    len_v: i32,
+) -> usize {
+    let mut changed = 0;
+    for i in 0..clip.height {
+        for j in 0..clip.width {
+            let (col, row) = if transpose {
+                (target.0 + i, target.1 + j)
+            } else {
+                (target.0 + j, target.1 + i)
+            };
+            if col > len_h || row > len_v {
+                continue;
+            }
+            let k = (i * clip.width + j) as usize;
+            let ind = (row - 1) * len_h + col;
+            let style = clip.styles.get(k).copied().flatten();
+            let format = clip.number_formats.get(k).cloned().flatten();
+            if styles.get(&ind).copied() == style && number_formats.get(&ind) == format.as_ref() {
+                continue;
+            }
+            match style {
+                Some(style) => styles.insert(ind, style),
+                None => styles.remove(&ind),
+            };
+            match format {
+                Some(format) => number_formats.insert(ind, format),
+                None => number_formats.remove(&ind),
+            };
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Writes a range of cells as tab-separated text, one line per row, as other applications
+/// paste it.
+/// # Arguments
+/// * `range` - First column, first row, last column and last row of the range.
+/// * `len_h` - The number of columns of the spreadsheet.
+/// * `text` - Gives the text of a cell from its index.
+pub fn to_tsv(range: (i32, i32, i32, i32), len_h: i32, text: impl Fn(i32) -> String) -> String {
+    let (x1, y1, x2, y2) = range;
+    let mut tsv = String::new();
+    for y in y1..=y2 {
+        let row: Vec<String> = (x1..=x2)
+            .map(|x| text((y - 1) * len_h + x).replace(['\t', '\n', '\r'], " "))
+            .collect();
+        tsv.push_str(&row.join("\t"));
+        tsv.push('\n');
+    }
+    tsv
+}
+
+/// Builds the assignments that paste text copied from another application.
+///
+/// The text is read as tab-separated values, or as comma-separated values if it has no tabs.
+/// Every field that is a whole number becomes a constant in the matching cell.
+/// # Arguments
+/// * `text` - The pasted text.
+/// * `target` - Column and row of the top-left cell to paste to.
+/// * `len_h` - The number of columns of the target spreadsheet.
+/// * `len_v` - The number of rows of the target spreadsheet.
+/// # Returns
+/// One assignment command per pasted cell, and the number of fields that were skipped because
+/// they are not whole numbers or would fall outside the spreadsheet. Empty fields are left
+/// out without being counted.
+pub fn paste_text(text: &str, target: (i32, i32), len_h: i32, len_v: i32) -> (Vec<String>, usize) {
+    let delimiter = if text.contains('\t') { b'\t' } else { b',' };
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(text.as_bytes());
+    let (mut commands, mut skipped) = (Vec::new(), 0);
+    for (i, record) in reader.records().enumerate() {
+        let Ok(record) = record else {
+            skipped += 1;
+            continue;
+        };
+        for (j, field) in record.iter().enumerate() {
+            let field = field.trim();
+            if field.is_empty() {
+                continue;
+            }
+            let (col, row) = (target.0 + j as i32, target.1 + i as i32);
+            match field.parse::<i32>() {
+                Ok(value) if col <= len_h && row <= len_v => commands.push(format!(
+                    "{}{}={}",
+                    crate::utils::display::get_label(col),
+                    row,
+                    value
+                )),
+                _ => skipped += 1,
+            }
+        }
+    }
+    (commands, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sheet::Sheet;
+
+    fn sheet(commands: &[&str]) -> Sheet {
+        let mut sheet = Sheet::new(4, 4);
+        for command in commands {
+            let out = sheet.parse_assignment(command).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        sheet
+    }
+
+    fn clip(sheet: &Sheet, range: (i32, i32, i32, i32)) -> Clip {
+        copy(
+            sheet.database(),
+            sheet.err(),
+            sheet.opers(),
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+            sheet.len_h,
+            range,
+        )
+    }
+
+    #[test]
+    fn test_paste_values_and_transpose() {
+        let s = sheet(&["A1=1", "B1=2", "A2=A1/0"]);
+        let c = clip(&s, (1, 1, 2, 2));
+        assert_eq!(
+            paste(
+                &c,
+                (3, 3),
+                PasteMode::Values,
+                false,
+                s.database(),
+                s.err(),
+                4,
+                4
+            ),
+            // B2 was never assigned, so nothing is pasted for it
+            vec!["C3=1", "D3=2"]
+        );
+        assert_eq!(
+            paste(
+                &c,
+                (1, 3),
+                PasteMode::Values,
+                true,
+                s.database(),
+                s.err(),
+                4,
+                4
+            ),
+            vec!["A3=1", "A4=2"]
+        );
+    }
+
+    #[test]
+    fn test_paste_formulas_shifts_references() {
+        let s = sheet(&["B1=A1+1", "C1=SUM(A1:B1)"]);
+        let c = clip(&s, (2, 1, 3, 1));
+        assert_eq!(
+            paste(
+                &c,
+                (3, 3),
+                PasteMode::Formulas,
+                false,
+                s.database(),
+                s.err(),
+                4,
+                4
+            ),
+            vec!["C3=B3+1", "D3=SUM(B3:C3)"]
+        );
+        // Cells whose references would move left of column A are skipped
+        assert!(
+            paste(
+                &c,
+                (1, 2),
+                PasteMode::Formulas,
+                false,
+                s.database(),
+                s.err(),
+                4,
+                4
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_paste_arithmetic() {
+        let s = sheet(&["A1=5", "B1=7", "C1=10", "D1=A1/0"]);
+        let c = clip(&s, (1, 1, 2, 1));
+        assert_eq!(
+            paste(
+                &c,
+                (3, 1),
+                PasteMode::Add,
+                false,
+                s.database(),
+                s.err(),
+                4,
+                4
+            ),
+            vec!["C1=15"]
+        );
+        assert_eq!(
+            paste(
+                &c,
+                (2, 1),
+                PasteMode::Subtract,
+                false,
+                s.database(),
+                s.err(),
+                4,
+                4
+            ),
+            vec!["B1=2", "C1=3"]
+        );
+    }
+
+    #[test]
+    fn test_paste_formats() {
+        let s = sheet(&["A1=5", "B1=7"]);
+        let bold = CellStyle {
+            bold: true,
+            ..CellStyle::default()
+        };
+        let percent = NumberFormat {
+            percent: true,
+            ..NumberFormat::default()
+        };
+        let c = copy(
+            s.database(),
+            s.err(),
+            s.opers(),
+            &BTreeMap::from([(1, bold)]),
+            &BTreeMap::from([(2, percent.clone())]),
+            4,
+            (1, 1, 2, 1),
+        );
+        assert!(
+            paste(
+                &c,
+                (1, 2),
+                PasteMode::Formats,
+                false,
+                s.database(),
+                s.err(),
+                4,
+                4
+            )
+            .is_empty()
+        );
+        let mut styles = BTreeMap::from([(13, bold)]);
+        let mut number_formats = BTreeMap::new();
+        assert_eq!(
+            paste_formats(&c, (1, 3), true, &mut styles, &mut number_formats, 4, 4),
+            2
+        );
+        assert_eq!(styles, BTreeMap::from([(9, bold)]));
+        assert_eq!(number_formats, BTreeMap::from([(13, percent)]));
+        assert_eq!(
+            paste_formats(&c, (1, 3), true, &mut styles, &mut number_formats, 4, 4),
+            0
+        );
+    }
+
+    #[test]
+    fn test_to_tsv() {
+        let s = sheet(&["A1=1", "B1=2", "A2=3"]);
+        let text = |ind: i32| match s.err()[ind as usize] {
+            false => s.database()[ind as usize].to_string(),
+            true => String::from("ERR"),
+        };
+        assert_eq!(to_tsv((1, 1, 2, 2), 4, text), "1\t2\n3\t0\n");
+        assert_eq!(to_tsv((2, 1, 2, 1), 4, |_| String::from("a\tb")), "a b\n");
+    }
+
+    #[test]
+    fn test_paste_text() {
+        assert_eq!(
+            paste_text("1\t2\n3\t\t\t5\r\n", (2, 2), 4, 4),
+            (
+                vec![
+                    String::from("B2=1"),
+                    String::from("C2=2"),
+                    String::from("B3=3"),
+                ],
+                1
+            )
+        );
+        assert_eq!(
+            paste_text("7,\"8\",x\n 9 ,1.5", (1, 3), 4, 4),
+            (
+                vec![
+                    String::from("A3=7"),
+                    String::from("B3=8"),
+                    String::from("A4=9")
+                ],
+                2
+            )
+        );
+        // Fields below the last row are skipped
+        assert_eq!(paste_text("1\n2", (1, 4), 4, 4).1, 1);
+        assert_eq!(paste_text("", (1, 1), 4, 4), (Vec::new(), 0));
+    }
+}
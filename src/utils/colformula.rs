@@ -0,0 +1,347 @@
+//! This module contains column formulas, which define the formula of a whole column once.
+//!
+//! A column formula is set with `col_formula <column> <formula>` and removed with
+//! `col_formula <column>`. In the formula, a column name such as `B` refers to the cell of that
+//! column in the same row, and a column followed by an offset in brackets such as `C[-1]` to a
+//! cell rows above or below it. Cells with a row, such as `A1`, stay where they are, so a
+//! running balance is written:
+//!
+//! ```text
+//! col_formula C2 C[-1]+B
+//! ```
+//!
+//! which gives C2=C1+B2, C3=C2+B3 and so on. The column can be given as its first cell, as
+//! above, or on its own, in which case it starts at the first row whose references are all
+//! inside the sheet.
+//!
+//! The formula is stored once for the column rather than as a copy for each cell. Setting it
+//! fills the column down to the last row that holds data, and a row that gets data later is
+//! filled when it is assigned. A cell of the column assigned by hand keeps its own formula.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+use std::collections::BTreeMap;
+
+/// The formula of a column.
+///
+/// # Fields
+/// * `first_row` - The first row the formula fills
+/// * `formula` - The formula, with columns standing for cells of the row being filled
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct ColFormula {
+    pub first_row: i32,
+    pub formula: String,
+}
+
+impl ColFormula {
+    /// Gives the formula of one row.
+    /// # Arguments
+    /// * `row` - The row to fill.
+    /// * `len_h` - The number of columns in the spreadsheet.
+    /// * `len_v` - The number of rows in the spreadsheet.
+    /// # Returns
+    /// The formula with every column replaced by a cell, or `None` if a cell would be outside
+    /// the spreadsheet or an offset is not a number.
+    pub fn expand(&self, row: i32, len_h: i32, len_v: i32) -> Option<String> {
+        map_columns(&self.formula, &mut |name, offset| {
+            let col = crate::utils::input::parse_col(name, len_h)?;
+            let row = row.checked_add(offset)?;
+            (1..=len_v)
+                .contains(&row)
+                .then(|| crate::utils::cellref::CellRef { col, row }.to_string())
+        })
+    }
+
+    /// Gives the first row whose references are all inside the spreadsheet.
+    fn earliest_row(&self, len_h: i32, len_v: i32) -> Option<i32> {
+        (1..=len_v).find(|&row| self.expand(row, len_h, len_v).is_some())
+    }
+}
+
+/// Replaces the columns of a formula, leaving functions, cells and quoted criteria alone.
+/// # Arguments
+/// * `formula` - The formula.
+/// * `replace` - Gives the text of a column and the offset after it, if any.
+/// # Returns
+/// The formula with every column replaced, or `None` if `replace` gives `None` for one of
+/// them or an offset is not a number.
+fn map_columns(
+    formula: &str,
+    replace: &mut dyn FnMut(&str, i32) -> Option<String>,
+) -> Option<String> {
+    let mut text = String::new();
+    let mut rest = formula;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric() || c == '"') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').map_or(rest.len(), |e| e + 2);
+            text.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+        rest = &rest[end..];
+        let call = rest.trim_start().starts_with('(');
+        if call || !name.chars().all(|c| c.is_ascii_alphabetic()) {
+            text.push_str(name);
+            continue;
+        }
+        let mut offset = 0;
+        if let Some(bracket) = rest.strip_prefix('[') {
+            let (number, after) = bracket.split_once(']')?;
+            offset = number.trim().parse().ok()?;
+            rest = after;
+        }
+        text.push_str(&replace(name, offset)?);
+    }
+    text.push_str(rest);
+    Some(text)
+}
+
+/// Gives the last row with an assigned cell outside the columns that have a formula.
+fn last_data_row(formulas: &BTreeMap<i32, ColFormula>, opers: &Cells<Ops>, len_h: i32) -> i32 {
+    (1..opers.len() as i32)
+        .rev()
+        .find(|&ind| {
+            !opers[ind as usize].opcpde.is_empty()
+                && !formulas.contains_key(&((ind - 1) % len_h + 1))
+        })
+        .map_or(0, |ind| (ind - 1) / len_h + 1)
+}
+
+/// Runs a `col_formula <column> [<formula>]` command.
+/// # Arguments
+/// * `command` - The full command string.
+/// * `formulas` - The formula of each column that has one, updated in place.
+/// * `opers` - The operations for each cell.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// * `len_v` - The number of rows in the spreadsheet.
+/// # Returns
+/// The assignments that fill the column down to the last row holding data, which are empty
+/// when the formula is removed, or "Invalid Column Formula".
+pub fn formula_command(
+    command: &str,
+    formulas: &mut BTreeMap<i32, ColFormula>,
+    opers: &Cells<Ops>,
+    len_h: i32,
+    len_v: i32,
+) -> Result<Vec<String>, &'static str> {
+    let invalid = "Invalid Column Formula";
+    let mut words = command.splitn(3, ' ');
+    let (Some("col_formula"), Some(target)) = (words.next(), words.next()) else {
+        return Err(invalid);
+    };
+    let (col, first_row) = match crate::utils::cellref::CellRef::parse(target) {
+        Some(cell) if cell.col <= len_h && cell.row <= len_v => (cell.col, Some(cell.row)),
+        Some(_) => return Err(invalid),
+        None => (
+            crate::utils::input::parse_col(target, len_h).ok_or(invalid)?,
+            None,
+        ),
+    };
+    let formula = words
+        .next()
+        .map(|f| f.trim().trim_start_matches('=').trim());
+    let Some(formula) = formula.filter(|f| !f.is_empty()) else {
+        if first_row.is_some() {
+            return Err(invalid);
+        }
+        formulas.remove(&col);
+        return Ok(Vec::new());
+    };
+    let mut rule = ColFormula {
+        first_row: 1,
+        formula: formula.to_string(),
+    };
+    rule.first_row = match first_row {
+        Some(row) => row,
+        None => rule.earliest_row(len_h, len_v).ok_or(invalid)?,
+    };
+    let first = rule.expand(rule.first_row, len_h, len_v).ok_or(invalid)?;
+    let label = crate::utils::display::get_label(col);
+    let check = format!("{}{}={}", label, rule.first_row, first);
+    if crate::utils::input::input(&check, len_h, len_v).is_err() {
+        return Err(invalid);
+    }
+    formulas.insert(col, rule);
+    let last = last_data_row(formulas, opers, len_h);
+    let rule = &formulas[&col];
+    Ok((rule.first_row..=last)
+        .filter_map(|row| {
+            let formula = rule.expand(row, len_h, len_v)?;
+            Some(format!("{}{}={}", label, row, formula))
+        })
+        .collect())
+}
+
+/// Fills the empty cells of column formulas in the row of a cell that was just assigned.
+/// # Arguments
+/// * `formulas` - The formula of each column that has one.
+/// * `opers` - The operations for each cell, after the assignment.
+/// * `cell` - The index of the assigned cell.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// * `len_v` - The number of rows in the spreadsheet.
+/// # Returns
+/// One assignment per filled cell, none if the cell is itself in a column with a formula.
+pub fn fill_row(
+    formulas: &BTreeMap<i32, ColFormula>,
+    opers: &Cells<Ops>,
+    cell: i32,
+    len_h: i32,
+    len_v: i32,
+) -> Vec<String> {
+    let (col, row) = ((cell - 1) % len_h + 1, (cell - 1) / len_h + 1);
+    if formulas.contains_key(&col) {
+        return Vec::new();
+    }
+    formulas
+        .iter()
+        .filter(|(c, rule)| {
+            rule.first_row <= row && opers[(**c + (row - 1) * len_h) as usize].opcpde.is_empty()
+        })
+        .filter_map(|(&c, rule)| {
+            let formula = rule.expand(row, len_h, len_v)?;
+            Some(format!(
+                "{}{}={}",
+                crate::utils::display::get_label(c),
+                row,
+                formula
+            ))
+        })
+        .collect()
+}
+
+/// Moves the column formulas when a row or column is inserted or deleted.
+///
+/// A formula moves with its column and its columns move with theirs. A formula is dropped
+/// when its column, or a column it reads, is deleted.
+/// # Arguments
+/// * `formulas` - The formula of each column that has one, updated in place.
+/// * `edit` - The inserted or deleted row or column.
+pub fn edit_lines(
+    formulas: &mut BTreeMap<i32, ColFormula>,
+    edit: crate::utils::structure::LineEdit,
+) {
+    *formulas = std::mem::take(formulas)
+        .into_iter()
+        .filter_map(|(col, mut rule)| {
+            if edit.row {
+                rule.first_row = edit.shift(rule.first_row).unwrap_or(rule.first_row);
+                return Some((col, rule));
+            }
+            rule.formula = map_columns(&rule.formula, &mut |name, offset| {
+                let moved = edit.shift(crate::utils::input::parse_col(name, edit.len)?)?;
+                let name = crate::utils::display::get_label(moved);
+                Some(match offset {
+                    0 => name,
+                    _ => format!("{}[{}]", name, offset),
+                })
+            })?;
+            Some((edit.shift(col)?, rule))
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sheet::Sheet;
+
+    fn sheet(commands: &[&str]) -> Sheet {
+        let mut sheet = Sheet::new(3, 5);
+        for command in commands {
+            let out = sheet.parse_assignment(command).expect(command);
+            sheet.update(&out).unwrap();
+        }
+        sheet
+    }
+
+    #[test]
+    fn test_expand() {
+        let rule = ColFormula {
+            first_row: 2,
+            formula: String::from("C[-1]+B*A1+SUM(A1:B[1]) + COUNTIF(B, \">5\")"),
+        };
+        assert_eq!(
+            rule.expand(3, 3, 5).unwrap(),
+            "C2+B3*A1+SUM(A1:B4) + COUNTIF(B3, \">5\")"
+        );
+        assert_eq!(rule.expand(5, 3, 5), None);
+        assert_eq!(rule.earliest_row(3, 5), Some(2));
+    }
+
+    #[test]
+    fn test_formula_command() {
+        let sheet = sheet(&["B1=0", "B2=10", "B3=-4", "B4=1"]);
+        let mut formulas = BTreeMap::new();
+        let command = |c, formulas: &mut BTreeMap<i32, ColFormula>| {
+            formula_command(c, formulas, sheet.opers(), 3, 5)
+        };
+        assert_eq!(
+            command("col_formula C2 =C[-1]+B", &mut formulas),
+            Ok(vec![
+                String::from("C2=C1+B2"),
+                String::from("C3=C2+B3"),
+                String::from("C4=C3+B4")
+            ])
+        );
+        assert_eq!(
+            command("col_formula A B[-2]", &mut formulas).unwrap(),
+            ["A3=B1", "A4=B2"]
+        );
+        for bad in [
+            "col_formula",
+            "col_formula D B",
+            "col_formula C6 B",
+            "col_formula C2 B[x]",
+            "col_formula C B[-9]",
+            "col_formula C2 B+",
+            "col_formula C2",
+        ] {
+            assert_eq!(
+                command(bad, &mut formulas),
+                Err("Invalid Column Formula"),
+                "{}",
+                bad
+            );
+        }
+        assert_eq!(command("col_formula A", &mut formulas), Ok(Vec::new()));
+        assert_eq!(formulas.keys().copied().collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    fn test_fill_row() {
+        let mut formulas = BTreeMap::new();
+        let sheet = sheet(&["B2=10", "C2=C1+B2", "B3=5", "C4=7", "B4=1"]);
+        formula_command("col_formula C2 C[-1]+B", &mut formulas, sheet.opers(), 3, 5).unwrap();
+        assert_eq!(fill_row(&formulas, sheet.opers(), 8, 3, 5), ["C3=C2+B3"]);
+        // Cells assigned by hand and rows above the first are left alone
+        assert!(fill_row(&formulas, sheet.opers(), 11, 3, 5).is_empty());
+        assert!(fill_row(&formulas, sheet.opers(), 2, 3, 5).is_empty());
+        // Assigning a cell of the column itself fills nothing
+        assert!(fill_row(&formulas, sheet.opers(), 9, 3, 5).is_empty());
+    }
+
+    #[test]
+    fn test_edit_lines() {
+        let mut formulas = BTreeMap::new();
+        formulas.insert(
+            3,
+            ColFormula {
+                first_row: 2,
+                formula: String::from("C[-1]+B"),
+            },
+        );
+        let edit = |command| crate::utils::structure::LineEdit::parse(command, 3, 5).unwrap();
+        edit_lines(&mut formulas, edit("insert_row 1"));
+        assert_eq!(formulas[&3].first_row, 3);
+        edit_lines(&mut formulas, edit("delete_col A"));
+        assert_eq!(formulas[&2].formula, "B[-1]+A");
+        edit_lines(&mut formulas, edit("delete_col A"));
+        assert!(formulas.is_empty());
+    }
+}
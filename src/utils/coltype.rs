@@ -0,0 +1,241 @@
+//! This module contains column types, which declare what the cells of a column hold.
+//!
+//! A column is declared with `type_col <column> <type>` and the declaration is removed with
+//! `type_col <column>`. Cell values are always integers, so the types tell numbers apart from
+//! dates (see `utils::date`):
+//!
+//! - `integer` - Plain numbers. A decimal such as `2.5` is rounded, with a warning.
+//! - `date` - Dates. An ISO date such as `2024-05-01` is stored as `DATE(2024, 5, 1)`, with
+//!   a warning.
+//!
+//! Assignments whose value is of another kind are refused. Cells assigned before the column
+//! was declared are left as they are.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+use crate::utils::date::Kind;
+use std::collections::BTreeMap;
+
+/// What the cells of a column hold.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColType {
+    Integer,
+    Date,
+}
+
+impl ColType {
+    /// All column types, in the order they are listed to the user.
+    pub const ALL: [ColType; 2] = [ColType::Integer, ColType::Date];
+
+    /// Finds a column type by name, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|t| t.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Gives the name of the type as typed.
+    pub fn name(self) -> &'static str {
+        match self {
+            ColType::Integer => "integer",
+            ColType::Date => "date",
+        }
+    }
+
+    /// Describes the values of the type, as in "column B holds dates".
+    fn values(self) -> &'static str {
+        match self {
+            ColType::Integer => "integers",
+            ColType::Date => "dates",
+        }
+    }
+
+    /// Gives the kind of value the type holds.
+    fn kind(self) -> Kind {
+        match self {
+            ColType::Integer => Kind::Number,
+            ColType::Date => Kind::Date,
+        }
+    }
+
+    /// Converts a value of another form into one of this type, such as 2.5 into 3.
+    /// # Returns
+    /// The formula to store instead, or `None` if the value cannot be converted.
+    fn coerce(self, value: &str) -> Option<String> {
+        match self {
+            ColType::Integer => {
+                let number = value.parse::<f64>().ok().filter(|_| value.contains('.'))?;
+                let rounded = number.round();
+                (rounded >= i32::MIN as f64 && rounded <= i32::MAX as f64)
+                    .then(|| (rounded as i32).to_string())
+            }
+            ColType::Date => {
+                use chrono::Datelike;
+                let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+                Some(format!(
+                    "DATE({}, {}, {})",
+                    date.year(),
+                    date.month(),
+                    date.day()
+                ))
+            }
+        }
+    }
+}
+
+/// Runs a `type_col <column> [<type>]` command.
+/// # Arguments
+/// * `command` - The full command string.
+/// * `types` - The declared type of each column, updated in place.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// # Returns
+/// "ok" if the declarations were updated, otherwise "Invalid Column Type".
+pub fn type_command(command: &str, types: &mut BTreeMap<i32, ColType>, len_h: i32) -> &'static str {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let Some(col) = words
+        .get(1)
+        .and_then(|c| crate::utils::input::parse_col(c, len_h))
+    else {
+        return "Invalid Column Type";
+    };
+    match words[2..] {
+        [] => {
+            types.remove(&col);
+        }
+        [name] => match ColType::parse(name) {
+            Some(col_type) => {
+                types.insert(col, col_type);
+            }
+            None => return "Invalid Column Type",
+        },
+        _ => return "Invalid Column Type",
+    }
+    "ok"
+}
+
+/// Checks an assignment against the type of the column of its cell.
+/// # Arguments
+/// * `types` - The declared type of each column.
+/// * `command` - The assignment, such as "B2=A1+1".
+/// * `opers` - The operations for each cell, used to find the kind of the cells it reads.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// # Returns
+/// The assignment to apply, converted if needed together with a warning saying so, or a
+/// message if the value does not fit the column.
+pub fn enforce(
+    types: &BTreeMap<i32, ColType>,
+    command: &str,
+    opers: &Cells<Ops>,
+    len_h: i32,
+) -> Result<(String, Option<String>), String> {
+    let unchanged = Ok((command.to_string(), None));
+    let Some((cell, value)) = command.split_once('=') else {
+        return unchanged;
+    };
+    let Some((col, col_type)) = crate::utils::cellref::CellRef::parse(cell.trim())
+        .and_then(|c| Some((c.col, *types.get(&c.col)?)))
+    else {
+        return unchanged;
+    };
+    let column = format!(
+        "Column {} holds {}",
+        crate::utils::display::get_label(col),
+        col_type.values()
+    );
+    let value = value.trim();
+    if let Some(coerced) = col_type.coerce(value) {
+        let warning = format!("{}, so {} was stored as {}", column, value, coerced);
+        return Ok((format!("{}={}", cell.trim(), coerced), Some(warning)));
+    }
+    // Formulas the expression parser does not read, such as SUM, give numbers
+    let kind = crate::utils::expr::Expr::parse(value).map_or(Kind::Number, |e| {
+        e.kind(&mut |c| crate::utils::date::cell_kind(opers, c.ind(len_h), len_h))
+    });
+    if kind == col_type.kind() {
+        unchanged
+    } else {
+        Err(format!("{}, and {} is not one", column, value))
+    }
+}
+
+/// Writes the declarations as the commands that set them, in column order.
+pub fn to_commands(types: &BTreeMap<i32, ColType>) -> Vec<String> {
+    types
+        .iter()
+        .map(|(&col, t)| {
+            format!(
+                "type_col {} {}",
+                crate::utils::display::get_label(col),
+                t.name()
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_type_command() {
+        let mut types = BTreeMap::new();
+        assert_eq!(type_command("type_col B date", &mut types, 3), "ok");
+        assert_eq!(type_command("type_col a Integer", &mut types, 3), "ok");
+        for bad in [
+            "type_col",
+            "type_col D date",
+            "type_col B text",
+            "type_col B date extra",
+        ] {
+            assert_eq!(
+                type_command(bad, &mut types, 3),
+                "Invalid Column Type",
+                "{}",
+                bad
+            );
+        }
+        assert_eq!(
+            to_commands(&types),
+            ["type_col A integer", "type_col B date"]
+        );
+        assert_eq!(type_command("type_col A", &mut types, 3), "ok");
+        assert_eq!(to_commands(&types), ["type_col B date"]);
+    }
+
+    #[test]
+    fn test_enforce() {
+        let mut sheet = crate::utils::sheet::Sheet::new(3, 3);
+        for command in ["A1=5", "B1=DATE(2024, 5, 1)"] {
+            let out = sheet.parse_assignment(command).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        let mut types = BTreeMap::new();
+        type_command("type_col A integer", &mut types, 3);
+        type_command("type_col B date", &mut types, 3);
+        let enforce = |command| enforce(&types, command, sheet.opers(), 3);
+
+        assert_eq!(enforce("A2=A1*2"), Ok((String::from("A2=A1*2"), None)));
+        assert_eq!(
+            enforce("A2=SUM(A1:A1)"),
+            Ok((String::from("A2=SUM(A1:A1)"), None))
+        );
+        assert_eq!(enforce("B2=B1+7"), Ok((String::from("B2=B1+7"), None)));
+        assert_eq!(enforce("C2=2.5"), Ok((String::from("C2=2.5"), None)));
+        assert_eq!(
+            enforce("A2=2.5"),
+            Ok((
+                String::from("A2=3"),
+                Some(String::from(
+                    "Column A holds integers, so 2.5 was stored as 3"
+                ))
+            ))
+        );
+        assert_eq!(enforce("b2 = 2024-05-09").unwrap().0, "b2=DATE(2024, 5, 9)");
+        assert_eq!(
+            enforce("A2=B1+1"),
+            Err(String::from("Column A holds integers, and B1+1 is not one"))
+        );
+        assert!(enforce("B2=5").is_err());
+        assert!(enforce("B2=B1-B1").is_err());
+    }
+}
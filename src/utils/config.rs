@@ -0,0 +1,519 @@
+//! This module contains user preferences of the GUI that are kept between runs.
+//!
+//! Preferences are stored as JSON in `rust-spreadsheet/config.json` inside the user's
+//! configuration directory. A missing or unreadable file gives the default preferences.
+//!
+//! The resource limits set in the Settings window are kept with the preferences and applied
+//! to each workbook the GUI opens, before the `--max-cells`, `--max-time` and `--max-sleep`
+//! flags, which take precedence.
+
+use std::path::PathBuf;
+
+/// Smallest UI scale that can be set.
+pub const MIN_SCALE: f32 = 0.5;
+
+/// Largest UI scale that can be set.
+pub const MAX_SCALE: f32 = 3.0;
+
+/// Most rows or columns the grid can be set to show at once.
+pub const MAX_VIEW: i32 = 100;
+
+/// Longest autosave interval that can be set, in minutes.
+pub const MAX_AUTOSAVE: u32 = 120;
+
+/// Delimiters CSV exports can use, with the names they are listed under.
+pub const DELIMITERS: [(char, &str); 4] = [
+    (',', "Comma"),
+    (';', "Semicolon"),
+    ('\t', "Tab"),
+    ('|', "Pipe"),
+];
+
+/// Colors of the GUI.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Theme {
+    /// Follows the light or dark mode of the system.
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    /// All themes, in the order they are listed to the user.
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Light, Theme::Dark];
+
+    /// Gives the name of the theme as listed to the user.
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::System => "System",
+            Theme::Light => "Light",
+            Theme::Dark => "Dark",
+        }
+    }
+}
+
+/// User preferences of the GUI.
+///
+/// Preferences missing from the file take their default values.
+///
+/// # Fields
+/// * `ui_scale` - Scale of all text and widgets, or `None` to pick one from the screen size
+/// * `date_format` - strftime format of the date in the header
+/// * `time_format` - strftime format of the time in the header
+/// * `timezone` - Timezone of the header clock: `local`, `UTC` or an offset such as `+05:30`
+/// * `elapsed_format` - Format of the session timer, where `%H`, `%M` and `%S` are replaced by
+///   the hours, minutes and seconds since the session started
+/// * `show_recalc_time` - Whether the header shows how long the last recalculation took
+/// * `r1c1` - Whether cells are shown in the R1C1 reference style rather than the A1 style
+/// * `toolbar` - Whether the image toolbar is shown below the menu bar
+/// * `view` - Rows and columns shown in the grid, or `None` to show as many as fit in the
+///   window
+/// * `theme` - Colors of the GUI
+/// * `autosave` - Minutes between saves of workbooks with unsaved changes to the .rsk file
+///   they were last saved to or opened from, or 0 to never save them unasked
+/// * `background_recalc` - Whether edits that recompute many cells are recalculated on a
+///   background thread rather than on the UI thread
+/// * `backups` - Number of earlier versions kept when a .rsk file is saved over, at most
+///   `backup::MAX_BACKUPS`, or 0 to keep none
+/// * `csv_delimiter` - Delimiter of CSV exports, one of `DELIMITERS`
+/// * `notify_saves` - Whether a notification tells when a file was saved or exported.
+///   Failures are always notified
+/// * `max_cells` - Most cells a single edit may recompute, or 0 for no limit
+/// * `max_time` - Longest a single recalculation may take in milliseconds, or 0 for no limit
+/// * `max_sleep` - Longest a single SLEEP may wait in seconds, or 0 for no limit
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub ui_scale: Option<f32>,
+    pub date_format: String,
+    pub time_format: String,
+    pub timezone: String,
+    pub elapsed_format: String,
+    pub show_recalc_time: bool,
+    pub r1c1: bool,
+    pub toolbar: bool,
+    pub view: Option<(i32, i32)>,
+    pub theme: Theme,
+    pub autosave: u32,
+    pub background_recalc: bool,
+    pub backups: u32,
+    pub csv_delimiter: char,
+    pub notify_saves: bool,
+    pub max_cells: u64,
+    pub max_time: u64,
+    pub max_sleep: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            ui_scale: None,
+            date_format: String::from("%A, %B %d, %Y"),
+            time_format: String::from("%H:%M:%S"),
+            timezone: String::from("local"),
+            elapsed_format: String::from("%H:%M:%S"),
+            show_recalc_time: false,
+            r1c1: false,
+            toolbar: true,
+            view: None,
+            theme: Theme::System,
+            autosave: 0,
+            background_recalc: true,
+            backups: 3,
+            csv_delimiter: ',',
+            notify_saves: true,
+            max_cells: 0,
+            max_time: 0,
+            max_sleep: 0,
+        }
+    }
+}
+
+impl Config {
+    /// Formats a point in time in the configured timezone.
+    /// # Arguments
+    /// * `time` - The time to format
+    /// * `format` - A strftime format, usually `date_format` or `time_format`
+    /// # Returns
+    /// The formatted time. An invalid timezone falls back to local time.
+    pub fn format_time(&self, time: chrono::DateTime<chrono::Utc>, format: &str) -> String {
+        let offset = parse_timezone(&self.timezone)
+            .unwrap_or_else(|| *time.with_timezone(&chrono::Local).offset());
+        time.with_timezone(&offset).format(format).to_string()
+    }
+
+    /// Checks preferences changed in the Settings window before they are stored.
+    /// # Returns
+    /// A message naming the first invalid preference, if any.
+    pub fn check(&self) -> Result<(), &'static str> {
+        if !valid_format(&self.date_format) {
+            return Err("The date format is not a valid strftime format");
+        }
+        if !valid_format(&self.time_format) {
+            return Err("The time format is not a valid strftime format");
+        }
+        if parse_timezone(&self.timezone).is_none() {
+            return Err("The timezone must be local, UTC or an offset such as +05:30");
+        }
+        if self.elapsed_format.is_empty() {
+            return Err("The session timer format is empty");
+        }
+        if self.autosave > MAX_AUTOSAVE {
+            return Err("The autosave interval is too long");
+        }
+        if self.backups > crate::utils::backup::MAX_BACKUPS {
+            return Err("Too many backups are kept");
+        }
+        if !DELIMITERS.iter().any(|(d, _)| *d == self.csv_delimiter) {
+            return Err("The CSV delimiter is not supported");
+        }
+        Ok(())
+    }
+
+    /// Gives the delimiter of CSV exports as a byte, falling back to a comma for delimiters
+    /// that are not supported.
+    pub fn delimiter(&self) -> u8 {
+        if DELIMITERS.iter().any(|(d, _)| *d == self.csv_delimiter) {
+            self.csv_delimiter as u8
+        } else {
+            b','
+        }
+    }
+
+    /// Gives the cell, time and sleep limits, with 0 for no limit.
+    pub fn limits(&self) -> [u64; 3] {
+        [self.max_cells, self.max_time, self.max_sleep]
+    }
+
+    /// Sets the cell, time and sleep limits.
+    /// # Arguments
+    /// * `limits` - The limits, with 0 for no limit
+    pub fn set_limits(&mut self, [cells, time, sleep]: [u64; 3]) {
+        self.max_cells = cells;
+        self.max_time = time;
+        self.max_sleep = sleep;
+    }
+
+    /// Gives the `limit` commands that set the resource limits of a spreadsheet
+    /// (see `Sheet::apply_setting`).
+    pub fn limit_commands(&self) -> Vec<String> {
+        ["cells", "time", "sleep"]
+            .into_iter()
+            .zip(self.limits())
+            .map(|(kind, limit)| format!("limit {} {}", kind, limit))
+            .collect()
+    }
+
+    /// Runs a `clock <date|time|zone|elapsed|recalc> <value>` command.
+    /// # Arguments
+    /// * `command` - The full command string
+    /// # Returns
+    /// "ok" if the setting was changed, otherwise "Invalid Clock Setting".
+    pub fn clock_command(&mut self, command: &str) -> &'static str {
+        let mut parts = command.splitn(3, ' ');
+        let (Some("clock"), Some(setting), Some(value)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return "Invalid Clock Setting";
+        };
+        let value = value.trim();
+        match setting {
+            "date" if valid_format(value) => self.date_format = value.to_string(),
+            "time" if valid_format(value) => self.time_format = value.to_string(),
+            "zone" if parse_timezone(value).is_some() => self.timezone = value.to_string(),
+            "elapsed" if !value.is_empty() => self.elapsed_format = value.to_string(),
+            "recalc" if value == "on" || value == "off" => self.show_recalc_time = value == "on",
+            _ => return "Invalid Clock Setting",
+        }
+        "ok"
+    }
+}
+
+/// Checks whether a strftime format can be used to format a time.
+/// # Arguments
+/// * `format` - The format, e.g. "%d/%m/%Y"
+fn valid_format(format: &str) -> bool {
+    !format.is_empty()
+        && !chrono::format::StrftimeItems::new(format)
+            .any(|item| matches!(item, chrono::format::Item::Error))
+}
+
+/// Parses a timezone of the header clock.
+/// # Arguments
+/// * `zone` - `local`, `UTC` or an offset such as `+05:30`
+/// # Returns
+/// The offset from UTC, or `None` if the timezone is invalid.
+pub fn parse_timezone(zone: &str) -> Option<chrono::FixedOffset> {
+    match zone {
+        "local" => Some(*chrono::Local::now().offset()),
+        "UTC" | "utc" => chrono::FixedOffset::east_opt(0),
+        _ => {
+            let sign = match zone.chars().next()? {
+                '+' => 1,
+                '-' => -1,
+                _ => return None,
+            };
+            let (hours, minutes) = zone[1..].split_once(':').unwrap_or((&zone[1..], "0"));
+            let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+            if !(0..60).contains(&minutes) {
+                return None;
+            }
+            chrono::FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+        }
+    }
+}
+
+/// Formats the time since the session started.
+/// # Arguments
+/// * `seconds` - Number of seconds since the session started
+/// * `format` - The format, where `%H`, `%M` and `%S` are replaced by hours, minutes and seconds
+/// # Returns
+/// The formatted duration, e.g. "01:02:03" for `%H:%M:%S`.
+pub fn format_elapsed(seconds: i64, format: &str) -> String {
+    format
+        .replace("%H", &format!("{:02}", seconds / 3600))
+        .replace("%M", &format!("{:02}", (seconds % 3600) / 60))
+        .replace("%S", &format!("{:02}", seconds % 60))
+}
+
+/// Finds the configuration file.
+/// # Returns
+/// The path of the file, or `None` if there is no configuration directory.
+pub fn path() -> Option<PathBuf> {
+    let dir = if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME") {
+        PathBuf::from(dir)
+    } else if let Some(home) = std::env::var_os("HOME") {
+        PathBuf::from(home).join(".config")
+    } else {
+        PathBuf::from(std::env::var_os("APPDATA")?)
+    };
+    Some(dir.join("rust-spreadsheet").join("config.json"))
+}
+
+/// Loads the preferences.
+/// # Returns
+/// The stored preferences, or the defaults if none are stored.
+pub fn load() -> Config {
+    path()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Stores the preferences.
+/// # Arguments
+/// * `config` - The preferences to store
+/// # Returns
+/// An error message if the file could not be written.
+pub fn save(config: &Config) -> Result<(), String> {
+    let path = path().ok_or("No configuration directory")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let text = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    std::fs::write(path, text).map_err(|e| e.to_string())
+}
+
+/// Parses the value of a `ui_scale` command.
+/// # Arguments
+/// * `value` - A scale such as `1.5`, or `auto`
+/// # Returns
+/// `Some(None)` for `auto`, `Some(Some(scale))` for a scale between `MIN_SCALE` and
+/// `MAX_SCALE`, and `None` for anything else.
+pub fn parse_scale(value: &str) -> Option<Option<f32>> {
+    if value == "auto" {
+        return Some(None);
+    }
+    let scale = value.parse::<f32>().ok()?;
+    (MIN_SCALE..=MAX_SCALE)
+        .contains(&scale)
+        .then_some(Some(scale))
+}
+
+/// Parses the size of the grid given to a `view` command or the `--view` flag.
+/// # Arguments
+/// * `value` - Rows and columns such as `20x8`, or `auto`
+/// # Returns
+/// `Some(None)` for `auto`, `Some(Some((rows, cols)))` for sizes between 1 and `MAX_VIEW`,
+/// and `None` for anything else.
+pub fn parse_view(value: &str) -> Option<Option<(i32, i32)>> {
+    if value == "auto" {
+        return Some(None);
+    }
+    let (rows, cols) = value.split_once('x')?;
+    let size = |n: &str| n.parse::<i32>().ok().filter(|n| (1..=MAX_VIEW).contains(n));
+    Some(Some((size(rows)?, size(cols)?)))
+}
+
+/// Picks a UI scale at which a window fits on the screen.
+///
+/// Scales above 1 go in steps of 0.5 and scales below 1 in steps of 0.25, so that text stays
+/// sharp on common screens.
+/// # Arguments
+/// * `screen` - Width and height of the screen
+/// * `window` - Width and height of the window at scale 1
+/// # Returns
+/// The largest such scale between `MIN_SCALE` and `MAX_SCALE`.
+pub fn fit_scale(screen: (f32, f32), window: (f32, f32)) -> f32 {
+    let fit = (screen.0 / window.0).min(screen.1 / window.1);
+    let scale = if fit >= 1.0 {
+        (fit * 2.0).floor() / 2.0
+    } else {
+        (fit * 4.0).floor() / 4.0
+    };
+    scale.clamp(MIN_SCALE, MAX_SCALE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scale() {
+        assert_eq!(parse_scale("auto"), Some(None));
+        assert_eq!(parse_scale("1.5"), Some(Some(1.5)));
+        assert_eq!(parse_scale("0.5"), Some(Some(0.5)));
+        assert_eq!(parse_scale("0.1"), None);
+        assert_eq!(parse_scale("4"), None);
+        assert_eq!(parse_scale("big"), None);
+    }
+
+    #[test]
+    fn test_parse_view() {
+        assert_eq!(parse_view("auto"), Some(None));
+        assert_eq!(parse_view("20x8"), Some(Some((20, 8))));
+        assert_eq!(parse_view("1x100"), Some(Some((1, 100))));
+        for bad in ["0x5", "5x101", "20", "20x", "x8", "20*8", "-1x5"] {
+            assert_eq!(parse_view(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_fit_scale() {
+        let window = (1200.0, 860.0);
+        assert_eq!(fit_scale((1920.0, 1080.0), window), 1.0);
+        assert_eq!(fit_scale((2560.0, 1440.0), window), 1.5);
+        assert_eq!(fit_scale((3840.0, 2160.0), window), 2.5);
+        assert_eq!(fit_scale((1366.0, 768.0), window), 0.75);
+        assert_eq!(fit_scale((320.0, 240.0), window), MIN_SCALE);
+    }
+
+    #[test]
+    fn test_clock_command() {
+        let mut config = Config::default();
+        assert_eq!(config.clock_command("clock date %d/%m/%Y"), "ok");
+        assert_eq!(config.date_format, "%d/%m/%Y");
+        assert_eq!(config.clock_command("clock time %I:%M %p"), "ok");
+        assert_eq!(config.time_format, "%I:%M %p");
+        assert_eq!(config.clock_command("clock zone +05:30"), "ok");
+        assert_eq!(config.clock_command("clock recalc on"), "ok");
+        assert!(config.show_recalc_time);
+        assert_eq!(
+            config.clock_command("clock time %Q"),
+            "Invalid Clock Setting"
+        );
+        assert_eq!(
+            config.clock_command("clock zone Mars"),
+            "Invalid Clock Setting"
+        );
+        assert_eq!(
+            config.clock_command("clock zone +05:99"),
+            "Invalid Clock Setting"
+        );
+        assert_eq!(
+            config.clock_command("clock recalc maybe"),
+            "Invalid Clock Setting"
+        );
+        assert_eq!(config.clock_command("clock date"), "Invalid Clock Setting");
+        assert_eq!(config.time_format, "%I:%M %p");
+    }
+
+    #[test]
+    fn test_format_time() {
+        let time = chrono::DateTime::parse_from_rfc3339("2025-03-01T22:15:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let mut config = Config {
+            timezone: String::from("UTC"),
+            ..Config::default()
+        };
+        assert_eq!(config.format_time(time, "%H:%M"), "22:15");
+        config.timezone = String::from("+05:30");
+        assert_eq!(
+            config.format_time(time, "%Y-%m-%d %H:%M"),
+            "2025-03-02 03:45"
+        );
+        config.timezone = String::from("-08");
+        assert_eq!(config.format_time(time, "%H:%M"), "14:15");
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(3723, "%H:%M:%S"), "01:02:03");
+        assert_eq!(format_elapsed(59, "%Mm %Ss"), "00m 59s");
+        assert_eq!(format_elapsed(360000, "%H h"), "100 h");
+    }
+
+    #[test]
+    fn test_check() {
+        let config = Config::default();
+        assert_eq!(config.check(), Ok(()));
+        assert_eq!(config.delimiter(), b',');
+        for bad in [
+            Config {
+                date_format: String::from("%Q"),
+                ..Config::default()
+            },
+            Config {
+                timezone: String::from("Mars"),
+                ..Config::default()
+            },
+            Config {
+                autosave: MAX_AUTOSAVE + 1,
+                ..Config::default()
+            },
+            Config {
+                backups: crate::utils::backup::MAX_BACKUPS + 1,
+                ..Config::default()
+            },
+            Config {
+                csv_delimiter: 'x',
+                ..Config::default()
+            },
+        ] {
+            assert!(bad.check().is_err(), "{:?}", bad);
+            assert_eq!(bad.delimiter(), b',');
+        }
+        let config = Config {
+            csv_delimiter: '\t',
+            ..Config::default()
+        };
+        assert_eq!(config.delimiter(), b'\t');
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert_eq!(config, Config::default());
+        let config: Config = serde_json::from_str(r#"{"ui_scale": 2.0}"#).unwrap();
+        assert_eq!(config.ui_scale, Some(2.0));
+        assert_eq!(config.time_format, Config::default().time_format);
+        let config = Config {
+            ui_scale: Some(1.25),
+            timezone: String::from("UTC"),
+            theme: Theme::Dark,
+            csv_delimiter: ';',
+            max_cells: 5000,
+            max_sleep: 10,
+            ..Config::default()
+        };
+        let text = serde_json::to_string(&config).unwrap();
+        assert_eq!(serde_json::from_str::<Config>(&text).unwrap(), config);
+        assert_eq!(config.limits(), [5000, 0, 10]);
+        assert_eq!(
+            config.limit_commands(),
+            ["limit cells 5000", "limit time 0", "limit sleep 10"]
+        );
+    }
+}
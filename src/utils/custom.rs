@@ -0,0 +1,147 @@
+//! This module contains custom functions, which add new functions to expressions without
+//! changing the parser or the evaluator.
+//!
+//! A custom function implements `CustomFunction` and is added with `register`, usually at
+//! startup. From then on it can be used in a formula like a math function, such as
+//! `A1=DOUBLE(B1)+1`. Formulas store the name of the function, so a workbook using a
+//! function that is not registered when it is loaded shows ERR in those cells.
+
+use std::sync::{Arc, RwLock};
+
+/// A value passed to and returned by a custom function.
+pub type Value = i32;
+
+/// A function that can be added to expressions.
+pub trait CustomFunction: Send + Sync {
+    /// Name of the function as typed, made of ASCII letters and digits.
+    fn name(&self) -> &str;
+
+    /// Number of arguments of the function.
+    fn arity(&self) -> usize;
+
+    /// Applies the function.
+    /// # Arguments
+    /// * `args` - The values of the arguments, as many as `arity` gives.
+    /// # Returns
+    /// The result, or a message if the function is not defined for the arguments. The cell
+    /// then shows ERR.
+    fn evaluate(&self, args: &[Value]) -> Result<Value, String>;
+}
+
+/// Registered custom functions. Functions are never removed, so their index stays valid.
+static REGISTRY: RwLock<Vec<Arc<dyn CustomFunction>>> = RwLock::new(Vec::new());
+
+/// Adds a custom function to expressions.
+/// # Arguments
+/// * `function` - The function to add.
+/// # Returns
+/// "ok", "Invalid Function" if the name is not made of letters and digits starting with a
+/// letter or could be read as a cell, or "Function Exists" if a built-in or registered
+/// function has the name.
+// Called by code embedding the engine; the binary itself registers no functions
+#[allow(dead_code)]
+pub fn register(function: impl CustomFunction + 'static) -> &'static str {
+    let name = function.name();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic())
+        || !name.chars().all(|c| c.is_ascii_alphanumeric())
+        || crate::utils::cellref::CellRef::parse(name).is_some()
+    {
+        return "Invalid Function";
+    }
+    if crate::utils::functions::find(name).is_some()
+        || crate::utils::expr::EXPR_FUNCTIONS
+            .iter()
+            .any(|f| f.eq_ignore_ascii_case(name))
+        || find(name).is_some()
+    {
+        return "Function Exists";
+    }
+    REGISTRY.write().unwrap().push(Arc::new(function));
+    "ok"
+}
+
+/// Finds a registered function by name, ignoring case.
+/// # Returns
+/// The index of the function, or `None` if no function has the name.
+pub fn find(name: &str) -> Option<usize> {
+    REGISTRY
+        .read()
+        .unwrap()
+        .iter()
+        .position(|f| f.name().eq_ignore_ascii_case(name))
+}
+
+/// Gives a registered function by index (see `find`).
+pub fn get(index: usize) -> Arc<dyn CustomFunction> {
+    Arc::clone(&REGISTRY.read().unwrap()[index])
+}
+
+/// Applies a registered function by name.
+/// # Returns
+/// The result, or `None` if no function has the name or the function fails.
+pub fn apply(name: &str, args: &[Value]) -> Option<Value> {
+    let function = get(find(name)?);
+    function.evaluate(args).ok()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Doubles its argument, failing on overflow.
+    pub(crate) struct Double;
+
+    impl CustomFunction for Double {
+        fn name(&self) -> &str {
+            "DOUBLE"
+        }
+
+        fn arity(&self) -> usize {
+            1
+        }
+
+        fn evaluate(&self, args: &[Value]) -> Result<Value, String> {
+            args[0]
+                .checked_mul(2)
+                .ok_or_else(|| String::from("Overflow"))
+        }
+    }
+
+    /// Registers `Double`, once for all tests.
+    pub(crate) fn register_double() {
+        static ONCE: std::sync::Once = std::sync::Once::new();
+        ONCE.call_once(|| assert_eq!(register(Double), "ok"));
+    }
+
+    struct Named(&'static str);
+
+    impl CustomFunction for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn arity(&self) -> usize {
+            0
+        }
+
+        fn evaluate(&self, _: &[Value]) -> Result<Value, String> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        register_double();
+        assert_eq!(apply("double", &[21]), Some(42));
+        assert_eq!(apply("DOUBLE", &[i32::MAX]), None);
+        assert_eq!(apply("TRIPLE", &[1]), None);
+
+        assert_eq!(register(Named("Double")), "Function Exists");
+        assert_eq!(register(Named("SUM")), "Function Exists");
+        assert_eq!(register(Named("ABS")), "Function Exists");
+        assert_eq!(register(Named("AB12")), "Invalid Function");
+        assert_eq!(register(Named("R1C2")), "Invalid Function");
+        assert_eq!(register(Named("MY_FN")), "Invalid Function");
+        assert_eq!(register(Named("1X")), "Invalid Function");
+    }
+}
@@ -0,0 +1,328 @@
+//! This module contains dates and times, and the functions that give them, such as
+//! `DATE(2024, 5, 1)` or `TODAY()`.
+//!
+//! Cell values stay integers: a date is a number of days since 1970-01-01 and a time is a
+//! number of seconds since midnight. Whether a value is a date or a time is not stored but
+//! follows from the formula of its cell (see `cell_kind`): `DATE` and `TODAY` give dates,
+//! `NOW` gives a time, and adding days to a date or subtracting them from it gives a date
+//! again. Dates and times are shown and exported in ISO format, such as 2024-05-01.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+use chrono::{Datelike, Months, NaiveDate, Timelike};
+
+/// First day counted by dates.
+const EPOCH: NaiveDate = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+
+/// Seconds in a day.
+const DAY: i32 = 86400;
+
+/// Longest chain of references followed to find the kind of a cell. Cells at the end of
+/// longer chains are shown as numbers.
+const MAX_DEPTH: usize = 256;
+
+/// A date or time function.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateFn {
+    Date,
+    Today,
+    Now,
+}
+
+impl DateFn {
+    /// All date and time functions, except DATEDIF which takes a unit (see `Unit`).
+    pub const ALL: [DateFn; 3] = [DateFn::Date, DateFn::Today, DateFn::Now];
+
+    /// Finds a function by name, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|f| f.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Gives the name of the function as typed.
+    pub fn name(self) -> &'static str {
+        match self {
+            DateFn::Date => "DATE",
+            DateFn::Today => "TODAY",
+            DateFn::Now => "NOW",
+        }
+    }
+
+    /// Gives the number of arguments of the function.
+    pub fn arity(self) -> usize {
+        match self {
+            DateFn::Date => 3,
+            DateFn::Today | DateFn::Now => 0,
+        }
+    }
+
+    /// Gives the kind of value the function gives.
+    pub fn kind(self) -> Kind {
+        match self {
+            DateFn::Date | DateFn::Today => Kind::Date,
+            DateFn::Now => Kind::Time,
+        }
+    }
+
+    /// Applies the function.
+    /// # Arguments
+    /// * `args` - The values of the arguments, as many as `arity` gives.
+    /// * `now` - The time TODAY and NOW read (see `settings::Limits::now`).
+    /// # Returns
+    /// The result, or `None` if the arguments are not a date that can be stored.
+    pub fn apply(self, args: &[i32], now: chrono::DateTime<chrono::Local>) -> Option<i32> {
+        match self {
+            DateFn::Date => from_ymd(args[0], args[1], args[2]),
+            DateFn::Today => to_serial(now.date_naive()),
+            DateFn::Now => Some(now.num_seconds_from_midnight() as i32),
+        }
+    }
+}
+
+/// Unit of the difference given by DATEDIF.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Days,
+    Months,
+    Years,
+}
+
+impl Unit {
+    /// Parses a unit written as "D", "M" or "Y", with the quotes, ignoring case.
+    pub fn parse(text: &str) -> Option<Self> {
+        let letter = text.strip_prefix('"')?.strip_suffix('"')?;
+        match letter.to_ascii_uppercase().as_str() {
+            "D" => Some(Unit::Days),
+            "M" => Some(Unit::Months),
+            "Y" => Some(Unit::Years),
+            _ => None,
+        }
+    }
+
+    /// Writes the unit as typed, with quotes.
+    pub fn text(self) -> &'static str {
+        match self {
+            Unit::Days => "\"D\"",
+            Unit::Months => "\"M\"",
+            Unit::Years => "\"Y\"",
+        }
+    }
+}
+
+/// What a cell value stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Number,
+    Date,
+    Time,
+}
+
+impl Kind {
+    /// Gives the kind of the result of an arithmetic operation.
+    ///
+    /// Adding a number to a date or time, or subtracting one from it, keeps its kind. Any
+    /// other operation, such as the difference of two dates, gives a number.
+    pub fn combine(op: char, a: Kind, b: Kind) -> Kind {
+        match (op, a, b) {
+            ('+', Kind::Number, kind) | ('+' | '-', kind, Kind::Number) => kind,
+            _ => Kind::Number,
+        }
+    }
+
+    /// Writes a value of this kind, in ISO format for dates and times.
+    pub fn format(self, value: i32) -> String {
+        match self {
+            Kind::Number => value.to_string(),
+            Kind::Date => match from_serial(value) {
+                Some(date) => date.format("%Y-%m-%d").to_string(),
+                None => value.to_string(),
+            },
+            Kind::Time => {
+                let seconds = value.rem_euclid(DAY);
+                format!(
+                    "{:02}:{:02}:{:02}",
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60
+                )
+            }
+        }
+    }
+}
+
+/// Converts a date to a number of days since 1970-01-01.
+fn to_serial(date: NaiveDate) -> Option<i32> {
+    i32::try_from(date.signed_duration_since(EPOCH).num_days()).ok()
+}
+
+/// Converts a number of days since 1970-01-01 to a date.
+fn from_serial(serial: i32) -> Option<NaiveDate> {
+    EPOCH.checked_add_signed(chrono::TimeDelta::try_days(serial.into())?)
+}
+
+/// Gives the date of a year, month and day, like DATE.
+///
+/// Months and days outside their usual range carry over, so month 13 is January of the next
+/// year and day 0 is the last day of the month before.
+/// # Returns
+/// The number of days since 1970-01-01, or `None` if the date cannot be stored.
+pub fn from_ymd(year: i32, month: i32, day: i32) -> Option<i32> {
+    let months = i64::from(year) * 12 + i64::from(month) - 1;
+    let year = i32::try_from(months.div_euclid(12)).ok()?;
+    let first = NaiveDate::from_ymd_opt(year, months.rem_euclid(12) as u32 + 1, 1)?;
+    let date = first.checked_add_signed(chrono::TimeDelta::try_days(i64::from(day) - 1)?)?;
+    to_serial(date)
+}
+
+/// Gives the number of whole days, months or years from one date to another, like DATEDIF.
+/// # Arguments
+/// * `start` - The first date, in days since 1970-01-01.
+/// * `end` - The second date, which may not be before the first.
+/// * `unit` - The unit of the result.
+/// # Returns
+/// The difference, or `None` if the end is before the start or a date cannot be stored.
+pub fn datedif(start: i32, end: i32, unit: Unit) -> Option<i32> {
+    if end < start {
+        return None;
+    }
+    let (a, b) = (from_serial(start)?, from_serial(end)?);
+    let months = (b.year() - a.year()) * 12 + b.month() as i32 - a.month() as i32;
+    // A month is only whole once the day of the month is reached again
+    let months = if a.checked_add_months(Months::new(months as u32))? > b {
+        months - 1
+    } else {
+        months
+    };
+    match unit {
+        Unit::Days => Some(end - start),
+        Unit::Months => Some(months),
+        Unit::Years => Some(months / 12),
+    }
+}
+
+/// Finds what the value of a cell stands for, from its formula and the cells it reads.
+/// # Arguments
+/// * `opers` - The operations for each cell.
+/// * `cell` - The index of the cell.
+/// * `len_h` - The number of columns in the spreadsheet.
+pub fn cell_kind(opers: &Cells<Ops>, cell: i32, len_h: i32) -> Kind {
+    kind_at(opers, cell, len_h, 0)
+}
+
+/// Finds the kind of a cell reached through `depth` references.
+fn kind_at(opers: &Cells<Ops>, cell: i32, len_h: i32, depth: usize) -> Kind {
+    if depth > MAX_DEPTH {
+        return Kind::Number;
+    }
+    let op = &opers[cell as usize];
+    let code = op.opcpde.as_bytes();
+    let operand = |kind: u8, v: i32| {
+        if kind == b'C' {
+            kind_at(opers, v, len_h, depth + 1)
+        } else {
+            Kind::Number
+        }
+    };
+    match op.opcpde.as_str() {
+        "EQC" | "SLC" => kind_at(opers, op.cell1, len_h, depth + 1),
+        crate::utils::expr::EXP_OP => op.expr.as_ref().map_or(Kind::Number, |e| {
+            e.kind(&mut |c| kind_at(opers, c.ind(len_h), len_h, depth + 1))
+        }),
+        _ if code.len() == 3
+            && b"CV".contains(&code[0])
+            && b"CV".contains(&code[1])
+            && b"ASMD".contains(&code[2]) =>
+        {
+            let op_char = match code[2] {
+                b'A' => '+',
+                b'S' => '-',
+                b'M' => '*',
+                _ => '/',
+            };
+            Kind::combine(
+                op_char,
+                operand(code[0], op.cell1),
+                operand(code[1], op.cell2),
+            )
+        }
+        _ => Kind::Number,
+    }
+}
+
+/// Writes the value of a cell as shown to the user, as a date or time if it is one.
+/// # Arguments
+/// * `database` - The values of all cells.
+/// * `opers` - The operations for each cell.
+/// * `cell` - The index of the cell.
+/// * `len_h` - The number of columns in the spreadsheet.
+pub fn value_text(database: &[i32], opers: &Cells<Ops>, cell: i32, len_h: i32) -> String {
+    cell_kind(opers, cell, len_h).format(database[cell as usize])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_ymd() {
+        assert_eq!(from_ymd(1970, 1, 1), Some(0));
+        assert_eq!(from_ymd(2024, 5, 1), Some(19844));
+        assert_eq!(from_ymd(2024, 13, 1), from_ymd(2025, 1, 1));
+        assert_eq!(from_ymd(2024, 3, 0), from_ymd(2024, 2, 29));
+        assert_eq!(from_ymd(2024, 0, 1), from_ymd(2023, 12, 1));
+        assert_eq!(from_ymd(1969, 12, 31), Some(-1));
+        assert_eq!(from_ymd(i32::MAX, 1, 1), None);
+    }
+
+    #[test]
+    fn test_datedif() {
+        let date = |y, m, d| from_ymd(y, m, d).unwrap();
+        assert_eq!(
+            datedif(date(2024, 1, 31), date(2024, 3, 1), Unit::Days),
+            Some(30)
+        );
+        assert_eq!(
+            datedif(date(2024, 1, 15), date(2024, 3, 14), Unit::Months),
+            Some(1)
+        );
+        assert_eq!(
+            datedif(date(2024, 1, 15), date(2024, 3, 15), Unit::Months),
+            Some(2)
+        );
+        assert_eq!(
+            datedif(date(2020, 2, 29), date(2024, 2, 28), Unit::Years),
+            Some(3)
+        );
+        assert_eq!(
+            datedif(date(2020, 2, 29), date(2024, 2, 29), Unit::Years),
+            Some(4)
+        );
+        assert_eq!(
+            datedif(date(2024, 3, 1), date(2024, 2, 1), Unit::Days),
+            None
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Kind::Date.format(19844), "2024-05-01");
+        assert_eq!(Kind::Date.format(-1), "1969-12-31");
+        assert_eq!(Kind::Date.format(i32::MAX), i32::MAX.to_string());
+        assert_eq!(Kind::Time.format(3723), "01:02:03");
+        assert_eq!(Kind::Number.format(-5), "-5");
+    }
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(Kind::combine('+', Kind::Date, Kind::Number), Kind::Date);
+        assert_eq!(Kind::combine('+', Kind::Number, Kind::Time), Kind::Time);
+        assert_eq!(Kind::combine('-', Kind::Date, Kind::Number), Kind::Date);
+        assert_eq!(Kind::combine('-', Kind::Number, Kind::Date), Kind::Number);
+        assert_eq!(Kind::combine('-', Kind::Date, Kind::Date), Kind::Number);
+        assert_eq!(Kind::combine('+', Kind::Date, Kind::Date), Kind::Number);
+        assert_eq!(Kind::combine('*', Kind::Date, Kind::Number), Kind::Number);
+        assert_eq!(Unit::parse("\"m\""), Some(Unit::Months));
+        assert_eq!(Unit::parse("M"), None);
+    }
+}
@@ -0,0 +1,120 @@
+//! This module contains cell decorations, the small markers drawn in the corner of cells that
+//! carry more than a value: a declared column type, a value imported from elsewhere, a watch
+//! or a recalculation still running.
+//!
+//! The markers are read from the per-cell and per-column maps kept for each of these, so the
+//! query is cheap enough to run for every cell drawn.
+
+use crate::utils::coltype::ColType;
+use crate::utils::provenance::Provenance;
+use std::collections::BTreeMap;
+
+/// A marker drawn in the corner of a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoration {
+    /// The value is being recalculated on a background thread.
+    Pending,
+    /// The column of the cell has a declared type, which typed values are checked against.
+    Typed,
+    /// The value was imported, for example pasted from another app.
+    Imported,
+    /// Editing pauses when the value of the cell changes.
+    Watched,
+}
+
+impl Decoration {
+    /// All decorations, in the order they are drawn from the corner inwards.
+    pub const ALL: [Decoration; 4] = [
+        Decoration::Pending,
+        Decoration::Typed,
+        Decoration::Imported,
+        Decoration::Watched,
+    ];
+
+    /// Gives the color of the marker, as RGB.
+    pub fn rgb(self) -> [u8; 3] {
+        match self {
+            Decoration::Pending => [230, 130, 20],
+            Decoration::Typed => [50, 110, 220],
+            Decoration::Imported => [40, 160, 70],
+            Decoration::Watched => [200, 60, 160],
+        }
+    }
+
+    /// Tells what the marker means, as listed in the legend.
+    pub fn meaning(self) -> &'static str {
+        match self {
+            Decoration::Pending => "Value is being recalculated",
+            Decoration::Typed => "Column has a declared type that values are checked against",
+            Decoration::Imported => "Value was imported or pasted from another app",
+            Decoration::Watched => "Cell is watched, editing pauses when it changes",
+        }
+    }
+}
+
+/// The per-cell and per-column data decorations are read from.
+///
+/// # Fields
+/// * `col_types` - Declared types of columns
+/// * `provenance` - Source of imported cells, by cell
+/// * `watches` - Watched cells
+/// * `len_h` - The number of columns in the spreadsheet
+#[derive(Debug, Clone, Copy)]
+pub struct Layers<'a> {
+    pub col_types: &'a BTreeMap<i32, ColType>,
+    pub provenance: &'a BTreeMap<i32, Provenance>,
+    pub watches: &'a [i32],
+    pub len_h: i32,
+}
+
+impl Layers<'_> {
+    /// Gives the decorations of a cell.
+    /// # Arguments
+    /// * `cell` - The index of the cell
+    /// * `pending` - Whether the cell is being recalculated
+    /// # Returns
+    /// The decorations, in the order of `Decoration::ALL`.
+    pub fn decorations(&self, cell: i32, pending: bool) -> impl Iterator<Item = Decoration> + '_ {
+        let col = (cell - 1) % self.len_h + 1;
+        Decoration::ALL.into_iter().filter(move |d| match d {
+            Decoration::Pending => pending,
+            Decoration::Typed => self.col_types.contains_key(&col),
+            Decoration::Imported => self.provenance.contains_key(&cell),
+            Decoration::Watched => self.watches.contains(&cell),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decorations() {
+        let mut col_types = BTreeMap::new();
+        col_types.insert(2, ColType::Integer);
+        let mut provenance = BTreeMap::new();
+        provenance.insert(
+            5,
+            Provenance {
+                source: String::from("clipboard"),
+                time: 0,
+            },
+        );
+        let layers = Layers {
+            col_types: &col_types,
+            provenance: &provenance,
+            watches: &[5, 7],
+            len_h: 3,
+        };
+        let of = |cell, pending| layers.decorations(cell, pending).collect::<Vec<_>>();
+        assert_eq!(of(1, false), []);
+        assert_eq!(of(1, true), [Decoration::Pending]);
+        assert_eq!(
+            of(5, false),
+            [Decoration::Typed, Decoration::Imported, Decoration::Watched]
+        );
+        assert_eq!(of(7, false), [Decoration::Watched]);
+        assert_eq!(of(8, false), [Decoration::Typed]);
+    }
+}
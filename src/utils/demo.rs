@@ -0,0 +1,95 @@
+//! This module contains the demo workbook shown to new users.
+//!
+//! The demo is a small sales table: months in column A, units sold in column B, the unit
+//! price in column C and the revenue in column D, with a summary in column F that uses the
+//! range, conditional, lookup and IF functions. It is built through the engine, so the same
+//! generator is used by the GUI and by tests.
+
+use crate::utils::sheet::Sheet;
+
+/// Smallest spreadsheet the demo fits in, as columns and rows.
+pub const MIN_SIZE: (i32, i32) = (6, 8);
+
+/// Labels of the demo columns, as column number and text.
+pub const LABELS: [(i32, &str); 5] = [
+    (1, "Month"),
+    (2, "Units"),
+    (3, "Price"),
+    (4, "Revenue"),
+    (6, "Summary"),
+];
+
+/// Units sold and unit price for each month of the demo.
+const SALES: [(i32, i32); 6] = [(30, 12), (45, 12), (38, 13), (52, 13), (61, 14), (47, 14)];
+
+/// Gives the assignments that fill in the demo, data first.
+pub fn commands() -> Vec<String> {
+    let mut commands = Vec::new();
+    for (i, (units, price)) in SALES.iter().enumerate() {
+        let row = i + 1;
+        commands.push(format!("A{}={}", row, row));
+        commands.push(format!("B{}={}", row, units));
+        commands.push(format!("C{}={}", row, price));
+        commands.push(format!("D{}=B{}*C{}", row, row, row));
+    }
+    commands.extend(
+        [
+            "F1=SUM(D1:D6)",
+            "F2=AVG(B1:B6)",
+            "F3=MAX(D1:D6)",
+            "F4=COUNTIF(B1:B6, \">40\")",
+            "F5=SUMIF(D1:D6, \">=600\")",
+            "F6=VLOOKUP(4, A1:D6, 4)",
+            "F7=IF(F1 > 3500, 1, 0)",
+        ]
+        .map(String::from),
+    );
+    commands
+}
+
+/// Builds the demo in an empty spreadsheet.
+/// # Arguments
+/// * `len_h` - Width of the spreadsheet (number of columns)
+/// * `len_v` - Height of the spreadsheet (number of rows)
+/// # Returns
+/// The evaluated spreadsheet, or `None` if it is smaller than `MIN_SIZE`.
+pub fn sheet(len_h: i32, len_v: i32) -> Option<Sheet> {
+    if len_h < MIN_SIZE.0 || len_v < MIN_SIZE.1 {
+        return None;
+    }
+    let mut sheet = Sheet::new(len_h, len_v);
+    for command in commands() {
+        let out = sheet.parse_assignment(&command);
+        debug_assert!(out.is_ok(), "{}", command);
+        if let Ok(out) = out {
+            let _ = sheet.update(&out);
+        }
+    }
+    Some(sheet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sheet() {
+        assert!(sheet(5, 10).is_none());
+        assert!(sheet(10, 7).is_none());
+
+        let sheet = sheet(10, 10).unwrap();
+        let value = |cell: &str| {
+            let ind = crate::cell_to_ind(cell, 10) as usize;
+            assert!(!sheet.err()[ind], "{} is in error", cell);
+            sheet.database()[ind]
+        };
+        assert_eq!(value("D2"), 540);
+        assert_eq!(value("F1"), 3582);
+        assert_eq!(value("F2"), 45);
+        assert_eq!(value("F3"), 854);
+        assert_eq!(value("F4"), 4);
+        assert_eq!(value("F5"), 2188);
+        assert_eq!(value("F6"), 676);
+        assert_eq!(value("F7"), 1);
+    }
+}
@@ -1,6 +1,9 @@
 //! This module contains functions to display a grid of data with labels.
 //! It includes functions to shift characters for labeling columns and to display the grid with error handling.
 
+/// Rows and columns of the grid printed by the terminal interface unless `--view` is given.
+pub const DEFAULT_VIEW: (i32, i32) = (10, 10);
+
 /// Shifts a character by a given integer value.
 /// # Arguments
 /// * `c` - The character to be shifted.
@@ -50,39 +53,57 @@ pub fn get_label(a: i32) -> String {
 /// # Arguments
 /// * `top_h` - The starting horizontal index.
 /// * `top_v` - The starting vertical index.
+/// * `view` - The number of rows and columns to display.
 /// * `len_h` - The length of the horizontal axis.
 /// * `len_v` - The length of the vertical axis.
 /// * `database` - A slice of integers representing the data.
 /// * `err` - A slice of booleans representing error states for each data point.
+/// * `opers` - The operations for each cell, used to show dates and times in ISO format.
+/// * `schedule` - When the cells waiting on SLEEP resolve.
+/// * `formats` - The number formats of formatted cells.
+/// * `show_formulas` - Whether cells show their formulas instead of their values, as written
+///   by `formula_cell`.
+/// * `r1c1` - Whether cells are shown in the R1C1 reference style.
 /// # Returns
 /// This function does not return a value.
 /// It prints the grid to the console.
 /// The grid is displayed with labels for the columns and rows.
-/// The labels are generated using the `get_label` function.
-/// The data points are displayed in the grid, with "ERR" printed for any data point that has an error.
+/// The labels are generated using the `col_header` function, so they follow the reference style.
+/// The cells are displayed in the grid as written by `value_cell`, so cells that were never
+/// assigned are left blank, "#REF!" is printed for a formula that referenced a deleted cell
+/// and "ERR" for any other cell that has an error.
+/// Formulas are shown even if their cell has an error.
+#[allow(clippy::too_many_arguments)]
 pub fn display_grid(
     top_h: i32,
     top_v: i32,
+    view: (i32, i32),
     len_h: i32,
     len_v: i32,
     database: &[i32],
     err: &[bool],
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    schedule: &crate::utils::schedule::Schedule,
+    formats: &std::collections::BTreeMap<i32, crate::utils::numfmt::NumberFormat>,
+    show_formulas: bool,
+    r1c1: bool,
 ) {
+    let now = std::time::Instant::now();
     let i1 = top_h;
-    let mut i2 = top_h + 9;
+    let mut i2 = top_h + view.1 - 1;
 
     if i2 > len_h {
         i2 = len_h;
     }
 
     for i in i1..=i2 {
-        print!("\t{}", get_label(i));
+        print!("\t{}", crate::utils::cellref::col_header(i, r1c1));
     }
 
     println!();
 
     let i3 = top_v;
-    let mut i4 = top_v + 9;
+    let mut i4 = top_v + view.0 - 1;
 
     if i4 > len_v {
         i4 = len_v;
@@ -91,16 +112,75 @@ pub fn display_grid(
     for j in i3..=i4 {
         print!("{j}");
         for i in i1..=i2 {
-            if err[((j - 1) * len_h + i) as usize] {
-                print!("\tERR");
+            let ind = (j - 1) * len_h + i;
+            if show_formulas {
+                print!("\t{}", formula_cell(&opers[ind as usize], len_h, r1c1));
             } else {
-                print!("\t{}", database[((j - 1) * len_h + i) as usize]);
+                print!(
+                    "\t{}",
+                    value_cell(database, err, opers, schedule, formats, ind, len_h, now)
+                );
             }
         }
         println!();
     }
 }
 
+/// Writes what a cell shows while values are shown.
+/// # Arguments
+/// * `database` - The values of all cells.
+/// * `err` - Whether each cell is in an error state.
+/// * `opers` - The operations for each cell.
+/// * `schedule` - When the cells waiting on SLEEP resolve.
+/// * `formats` - The number formats of formatted cells.
+/// * `ind` - The index of the cell.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// * `now` - The time the grid is shown at.
+/// # Returns
+/// "..." for a cell waiting on SLEEP, nothing for a cell that was never assigned, "#REF!" for
+/// a formula that referenced a deleted cell, "ERR" for any other cell in error, and the value
+/// in its format otherwise.
+#[allow(clippy::too_many_arguments)]
+pub fn value_cell(
+    database: &[i32],
+    err: &[bool],
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    schedule: &crate::utils::schedule::Schedule,
+    formats: &std::collections::BTreeMap<i32, crate::utils::numfmt::NumberFormat>,
+    ind: i32,
+    len_h: i32,
+    now: std::time::Instant,
+) -> String {
+    if schedule.is_pending(ind, now) {
+        String::from("...")
+    } else if opers[ind as usize].opcpde.is_empty() {
+        String::new()
+    } else if opers[ind as usize].opcpde == crate::utils::structure::REF_OP {
+        String::from(crate::utils::structure::REF_TEXT)
+    } else if err[ind as usize] {
+        String::from("ERR")
+    } else {
+        crate::utils::numfmt::value_text(formats, database, opers, ind, len_h)
+    }
+}
+
+/// Writes what a cell shows while formulas are shown instead of values.
+/// # Arguments
+/// * `op` - The operation stored for the cell.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// * `r1c1` - Whether cells are shown in the R1C1 reference style.
+/// # Returns
+/// Nothing for an empty cell, the number for a constant, and the formula starting with "="
+/// for any other cell.
+pub fn formula_cell(op: &crate::Ops, len_h: i32, r1c1: bool) -> String {
+    let text =
+        crate::utils::cellref::show_formula(&crate::utils::input::formula_text(op, len_h), r1c1);
+    match op.opcpde.as_str() {
+        "" | "EQV" => text,
+        _ => format!("={}", text),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -162,6 +242,64 @@ mod tests {
         let mut err = vec![false; 10];
         err[4] = true; // Mark element at position (2,2) as error
 
-        display_grid(1, 1, len_h, len_v, &database, &err);
+        let opers = crate::utils::sheet::Sheet::new(len_h, len_v)
+            .opers()
+            .clone();
+
+        display_grid(
+            1,
+            1,
+            DEFAULT_VIEW,
+            len_h,
+            len_v,
+            &database,
+            &err,
+            &opers,
+            &crate::utils::schedule::Schedule::default(),
+            &std::collections::BTreeMap::new(),
+            false,
+            false,
+        );
+    }
+
+    #[test]
+    fn test_value_cell() {
+        let mut sheet = crate::utils::sheet::Sheet::new(3, 3);
+        for command in ["A1=5", "B1=0", "C1=A1/0", "A2=#REF!"] {
+            let out = sheet.parse_assignment(command).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        let cell = |ind: i32| {
+            value_cell(
+                sheet.database(),
+                sheet.err(),
+                sheet.opers(),
+                sheet.schedule(),
+                &std::collections::BTreeMap::new(),
+                ind,
+                3,
+                std::time::Instant::now(),
+            )
+        };
+        assert_eq!(cell(1), "5");
+        assert_eq!(cell(2), "0");
+        assert_eq!(cell(3), "ERR");
+        assert_eq!(cell(4), "#REF!");
+        assert_eq!(cell(5), "");
+    }
+
+    #[test]
+    fn test_formula_cell() {
+        let mut sheet = crate::utils::sheet::Sheet::new(3, 3);
+        for command in ["A1=5", "B1=A1*2", "C1=SUM(A1:B1)"] {
+            let out = sheet.parse_assignment(command).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        let cell = |ind: usize| formula_cell(&sheet.opers()[ind], 3, false);
+        assert_eq!(cell(1), "5");
+        assert_eq!(cell(2), "=A1*2");
+        assert_eq!(cell(3), "=SUM(A1:B1)");
+        assert_eq!(cell(4), "");
+        assert_eq!(formula_cell(&sheet.opers()[3], 3, true), "=SUM(R1C1:R1C2)");
     }
 }
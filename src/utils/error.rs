@@ -0,0 +1,78 @@
+//! This module contains the errors of the engine: why a command could not be parsed or an
+//! assignment could not be applied.
+//!
+//! Errors are shown to the user with the same text the engine has always used, such as
+//! "Invalid Range" or "cycle_detected", so the terminal status line reads as before.
+
+use std::fmt;
+
+/// Why a command was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SheetError {
+    /// A cell read by the formula is outside the spreadsheet or not a cell.
+    InvalidCell,
+    /// A range is outside the spreadsheet or its corners are the wrong way round.
+    InvalidRange,
+    /// A number does not fit in a cell.
+    InvalidValue,
+    /// The operation or function is not known.
+    InvalidOperation,
+    /// The formula is not a valid expression.
+    InvalidExpression,
+    /// The criterion of a conditional range function is not valid.
+    InvalidCriterion,
+    /// The assigned cell is outside the spreadsheet.
+    AssignedCellOutOfBounds,
+    /// The cell scrolled to is outside the spreadsheet.
+    ScrollCellOutOfBounds,
+    /// The assignment would make a cell depend on itself.
+    Cycle,
+    /// The assignment would recompute more cells than allowed (see `utils::settings`).
+    CellLimitExceeded,
+    /// The recalculation took longer than allowed.
+    TimeLimitExceeded,
+    /// A SLEEP would wait longer than allowed.
+    SleepLimitExceeded,
+    /// The recalculation was cancelled by the user (see `utils::cancel`).
+    Cancelled,
+    /// A `limit` command names no limit or gives an invalid value.
+    InvalidLimit,
+}
+
+impl SheetError {
+    /// Gives the status shown to the user.
+    pub fn status(self) -> &'static str {
+        match self {
+            SheetError::InvalidCell => "Invalid Cell",
+            SheetError::InvalidRange => "Invalid Range",
+            SheetError::InvalidValue => "Invalid Value",
+            SheetError::InvalidOperation => "Invalid Operation",
+            SheetError::InvalidExpression => "Invalid Expression",
+            SheetError::InvalidCriterion => "Invalid Criterion",
+            SheetError::AssignedCellOutOfBounds => "Assigned Cell out of bounds",
+            SheetError::ScrollCellOutOfBounds => "Scroll Cell out of bounds",
+            SheetError::Cycle => "cycle_detected",
+            SheetError::CellLimitExceeded => "cell_limit_exceeded",
+            SheetError::TimeLimitExceeded => "time_limit_exceeded",
+            SheetError::SleepLimitExceeded => "sleep_limit_exceeded",
+            SheetError::Cancelled => "cancelled",
+            SheetError::InvalidLimit => "Invalid Limit",
+        }
+    }
+}
+
+impl fmt::Display for SheetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.status())
+    }
+}
+
+impl std::error::Error for SheetError {}
+
+/// Gives the status of the result of a command, "ok" if it was applied.
+pub fn status(result: Result<(), SheetError>) -> &'static str {
+    match result {
+        Ok(()) => "ok",
+        Err(e) => e.status(),
+    }
+}
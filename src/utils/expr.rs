@@ -0,0 +1,1017 @@
+//! This module contains arithmetic expressions such as `(B1+C1)*2-D1/3`.
+//!
+//! Formulas with at most one operator keep their compact three-letter opcodes. Longer
+//! formulas are stored with the `EXP_OP` operation and the parsed expression. Expressions
+//! are made of integers, cell references, `+`, `-`, `*`, `/`, unary minus and parentheses,
+//! where `*` and `/` bind tighter than `+` and `-` and operators of equal precedence are
+//! evaluated from left to right. A comparison such as `B1>=10` gives 1 or 0 and binds
+//! loosest of all, and `IF(condition, a, b)` gives `a` if the condition is not 0 and `b`
+//! otherwise. `VLOOKUP(key, range, n)` finds the first row of the range whose first cell is
+//! equal to the key and gives its n-th cell; `HLOOKUP` does the same with columns. The math
+//! functions of `utils::math`, such as `ABS(B1)`, can be used anywhere in an expression, and
+//! so can the date functions of `utils::date`, such as `DATE(2024, 5, 1)` and
+//! `DATEDIF(A1, B1, "M")`, whose last argument is a unit in quotes. Functions added through
+//! `utils::custom` are used the same way.
+
+use crate::utils::cellref::CellRef;
+use crate::utils::date::{DateFn, Kind, Unit};
+use crate::utils::error::SheetError;
+use crate::utils::math::Math;
+use std::collections::HashSet;
+
+/// Operation of a formula stored as an expression.
+pub const EXP_OP: &str = "EXP";
+
+/// Functions that are part of the expression syntax.
+pub const EXPR_FUNCTIONS: [&str; 14] = [
+    "IF", "VLOOKUP", "HLOOKUP", "ABS", "CEIL", "FLOOR", "MOD", "POW", "ROUND", "SQRT", "DATE",
+    "TODAY", "NOW", "DATEDIF",
+];
+
+/// A parsed arithmetic expression.
+///
+/// # Variants
+/// * `Num` - An integer
+/// * `Cell` - The value of a cell
+/// * `Neg` - The negation of an expression
+/// * `Bin` - Two expressions joined by one of the operators `+`, `-`, `*` and `/`
+/// * `Cmp` - A comparison of two expressions, 1 if it holds and 0 otherwise
+/// * `If` - A condition and the expressions given if it is not 0 and if it is 0
+/// * `Lookup` - A lookup of a key in the first and last cell of a range, giving the cell at
+///   an index (1-based) of the matching row or column
+/// * `Math` - A math function and its arguments
+/// * `Date` - A date or time function and its arguments
+/// * `DateDif` - The difference between two dates in a unit
+/// * `Custom` - A custom function, by name, and its arguments
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(i32),
+    Cell(CellRef),
+    Neg(Box<Expr>),
+    Bin(char, Box<Expr>, Box<Expr>),
+    Cmp(Comparison, Box<Expr>, Box<Expr>),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    Lookup(Direction, Box<Expr>, CellRef, CellRef, Box<Expr>),
+    Math(Math, Vec<Expr>),
+    Date(DateFn, Vec<Expr>),
+    DateDif(Box<Expr>, Box<Expr>, Unit),
+    Custom(String, Vec<Expr>),
+}
+
+/// Where a lookup searches for its key.
+///
+/// # Variants
+/// * `Vertical` - Down the first column of the range (VLOOKUP)
+/// * `Horizontal` - Along the first row of the range (HLOOKUP)
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Vertical,
+    Horizontal,
+}
+
+impl Direction {
+    /// Gives the name of the function.
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::Vertical => "VLOOKUP",
+            Direction::Horizontal => "HLOOKUP",
+        }
+    }
+}
+
+/// A comparison operator.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Comparison {
+    /// Gives the operator as typed.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+        }
+    }
+
+    /// Checks whether the comparison holds for two values.
+    pub fn holds(self, a: i32, b: i32) -> bool {
+        match self {
+            Comparison::Lt => a < b,
+            Comparison::Le => a <= b,
+            Comparison::Gt => a > b,
+            Comparison::Ge => a >= b,
+            Comparison::Eq => a == b,
+            Comparison::Ne => a != b,
+        }
+    }
+}
+
+/// A token of an expression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Num(i64),
+    Cell(CellRef),
+    Op(char),
+    Cmp(Comparison),
+    If,
+    Lookup(Direction),
+    Math(Math),
+    Date(DateFn),
+    DateDif,
+    Custom(usize),
+    Unit(Unit),
+    Comma,
+    Colon,
+    Open,
+    Close,
+}
+
+/// Splits an expression into tokens.
+/// # Returns
+/// The tokens, or the status of the first invalid word or character.
+fn tokenize(text: &str) -> Result<Vec<Token>, SheetError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_ascii_alphanumeric() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if word.chars().all(|c| c.is_ascii_digit()) {
+                tokens.push(Token::Num(
+                    word.parse().map_err(|_| SheetError::InvalidValue)?,
+                ));
+            } else if let Some(cell) = CellRef::parse(&word) {
+                tokens.push(Token::Cell(cell));
+            } else if word.eq_ignore_ascii_case("IF") && chars.get(i) == Some(&'(') {
+                tokens.push(Token::If);
+            } else if let Some(direction) = [Direction::Vertical, Direction::Horizontal]
+                .into_iter()
+                .find(|d| word.eq_ignore_ascii_case(d.name()) && chars.get(i) == Some(&'('))
+            {
+                tokens.push(Token::Lookup(direction));
+            } else if let Some(function) = Math::parse(&word)
+                && chars.get(i) == Some(&'(')
+            {
+                tokens.push(Token::Math(function));
+            } else if let Some(function) = DateFn::parse(&word)
+                && chars.get(i) == Some(&'(')
+            {
+                tokens.push(Token::Date(function));
+            } else if word.eq_ignore_ascii_case("DATEDIF") && chars.get(i) == Some(&'(') {
+                tokens.push(Token::DateDif);
+            } else if let Some(index) = crate::utils::custom::find(&word)
+                && chars.get(i) == Some(&'(')
+            {
+                tokens.push(Token::Custom(index));
+            } else if chars.get(i) == Some(&'(') {
+                // Functions can only be used on their own
+                return Err(SheetError::InvalidOperation);
+            } else {
+                return Err(SheetError::InvalidCell);
+            }
+            continue;
+        }
+        let equals = chars.get(i + 1) == Some(&'=');
+        let comparison = match (c, equals) {
+            ('<', false) => Some(Comparison::Lt),
+            ('<', true) => Some(Comparison::Le),
+            ('>', false) => Some(Comparison::Gt),
+            ('>', true) => Some(Comparison::Ge),
+            ('=', true) => Some(Comparison::Eq),
+            ('!', true) => Some(Comparison::Ne),
+            _ => None,
+        };
+        if let Some(comparison) = comparison {
+            tokens.push(Token::Cmp(comparison));
+            i += comparison.symbol().len();
+            continue;
+        }
+        if c == '"' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|&c| c == '"')
+                .ok_or(SheetError::InvalidExpression)?;
+            let text: String = chars[i..i + end + 2].iter().collect();
+            tokens.push(Token::Unit(
+                Unit::parse(&text).ok_or(SheetError::InvalidExpression)?,
+            ));
+            i += end + 2;
+            continue;
+        }
+        match c {
+            ' ' => {}
+            ',' => tokens.push(Token::Comma),
+            ':' => tokens.push(Token::Colon),
+            '+' | '-' | '*' | '/' => tokens.push(Token::Op(c)),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            _ => return Err(SheetError::InvalidExpression),
+        }
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser over the tokens of an expression.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    /// Takes a token that has to come next.
+    fn expect(&mut self, token: Token) -> Result<(), SheetError> {
+        if self.next() == Some(token) {
+            Ok(())
+        } else {
+            Err(SheetError::InvalidExpression)
+        }
+    }
+
+    /// Parses a sum, or a comparison of two sums.
+    fn comparison(&mut self) -> Result<Expr, SheetError> {
+        let left = self.sum()?;
+        if let Some(Token::Cmp(op)) = self.peek() {
+            self.pos += 1;
+            return Ok(Expr::Cmp(op, Box::new(left), Box::new(self.sum()?)));
+        }
+        Ok(left)
+    }
+
+    /// Parses a sum or difference of terms.
+    fn sum(&mut self) -> Result<Expr, SheetError> {
+        let mut left = self.term()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek() {
+            self.pos += 1;
+            left = Expr::Bin(op, Box::new(left), Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    /// Parses a product or quotient of factors.
+    fn term(&mut self) -> Result<Expr, SheetError> {
+        let mut left = self.factor()?;
+        while let Some(Token::Op(op @ ('*' | '/'))) = self.peek() {
+            self.pos += 1;
+            left = Expr::Bin(op, Box::new(left), Box::new(self.factor()?));
+        }
+        Ok(left)
+    }
+
+    /// Parses a signed number, a cell, a negation, a function or a parenthesized expression.
+    fn factor(&mut self) -> Result<Expr, SheetError> {
+        match self.next() {
+            Some(Token::Op(sign @ ('+' | '-'))) => match self.peek() {
+                // Signs of numbers are part of the number, so "-5" is a value
+                Some(Token::Num(n)) => {
+                    self.pos += 1;
+                    let n = if sign == '-' { -n } else { n };
+                    i32::try_from(n)
+                        .map(Expr::Num)
+                        .map_err(|_| SheetError::InvalidValue)
+                }
+                _ if sign == '-' => Ok(Expr::Neg(Box::new(self.factor()?))),
+                _ => self.factor(),
+            },
+            Some(Token::Num(n)) => i32::try_from(n)
+                .map(Expr::Num)
+                .map_err(|_| SheetError::InvalidValue),
+            Some(Token::Cell(cell)) => Ok(Expr::Cell(cell)),
+            Some(Token::Open) => {
+                let inner = self.comparison()?;
+                self.expect(Token::Close)?;
+                Ok(inner)
+            }
+            Some(Token::If) => {
+                self.expect(Token::Open)?;
+                let condition = self.comparison()?;
+                self.expect(Token::Comma)?;
+                let then = self.comparison()?;
+                self.expect(Token::Comma)?;
+                let otherwise = self.comparison()?;
+                self.expect(Token::Close)?;
+                Ok(Expr::If(
+                    Box::new(condition),
+                    Box::new(then),
+                    Box::new(otherwise),
+                ))
+            }
+            Some(Token::Lookup(direction)) => {
+                self.expect(Token::Open)?;
+                let key = self.comparison()?;
+                self.expect(Token::Comma)?;
+                let (Some(Token::Cell(first)), Some(Token::Colon), Some(Token::Cell(last))) =
+                    (self.next(), self.next(), self.next())
+                else {
+                    return Err(SheetError::InvalidRange);
+                };
+                if first.col > last.col || first.row > last.row {
+                    return Err(SheetError::InvalidRange);
+                }
+                self.expect(Token::Comma)?;
+                let index = self.comparison()?;
+                self.expect(Token::Close)?;
+                Ok(Expr::Lookup(
+                    direction,
+                    Box::new(key),
+                    first,
+                    last,
+                    Box::new(index),
+                ))
+            }
+            Some(Token::Math(function)) => {
+                self.expect(Token::Open)?;
+                let mut args = vec![self.comparison()?];
+                while args.len() < function.arity() {
+                    self.expect(Token::Comma)?;
+                    args.push(self.comparison()?);
+                }
+                self.expect(Token::Close)?;
+                Ok(Expr::Math(function, args))
+            }
+            Some(Token::Date(function)) => {
+                self.expect(Token::Open)?;
+                let mut args = Vec::new();
+                while args.len() < function.arity() {
+                    if !args.is_empty() {
+                        self.expect(Token::Comma)?;
+                    }
+                    args.push(self.comparison()?);
+                }
+                self.expect(Token::Close)?;
+                Ok(Expr::Date(function, args))
+            }
+            Some(Token::DateDif) => {
+                self.expect(Token::Open)?;
+                let start = self.comparison()?;
+                self.expect(Token::Comma)?;
+                let end = self.comparison()?;
+                self.expect(Token::Comma)?;
+                let Some(Token::Unit(unit)) = self.next() else {
+                    return Err(SheetError::InvalidExpression);
+                };
+                self.expect(Token::Close)?;
+                Ok(Expr::DateDif(Box::new(start), Box::new(end), unit))
+            }
+            Some(Token::Custom(index)) => {
+                let function = crate::utils::custom::get(index);
+                self.expect(Token::Open)?;
+                let mut args = Vec::new();
+                while args.len() < function.arity() {
+                    if !args.is_empty() {
+                        self.expect(Token::Comma)?;
+                    }
+                    args.push(self.comparison()?);
+                }
+                self.expect(Token::Close)?;
+                Ok(Expr::Custom(function.name().to_string(), args))
+            }
+            _ => Err(SheetError::InvalidExpression),
+        }
+    }
+}
+
+impl Expr {
+    /// Parses an expression.
+    /// # Arguments
+    /// * `text` - The expression, e.g. "(B1+C1)*2-D1/3". Cells may be written in either
+    ///   reference style.
+    /// # Returns
+    /// The expression, or an error such as `InvalidCell` if the text is not an expression.
+    /// Cells are not checked against the size of the spreadsheet (see `check`).
+    pub fn parse(text: &str) -> Result<Self, SheetError> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+        };
+        let expr = parser.comparison()?;
+        if parser.pos != parser.tokens.len() {
+            return Err(SheetError::InvalidExpression);
+        }
+        Ok(expr)
+    }
+
+    /// Checks that all cells of the expression are inside the spreadsheet.
+    /// # Arguments
+    /// * `len_h` - The number of columns in the spreadsheet.
+    /// * `len_v` - The number of rows in the spreadsheet.
+    /// # Returns
+    /// `Ok(())`, or `InvalidCell` if a cell is outside the spreadsheet.
+    pub fn check(&self, len_h: i32, len_v: i32) -> Result<(), SheetError> {
+        let mut inside = true;
+        self.visit(&mut |cell| inside &= cell.col <= len_h && cell.row <= len_v);
+        if inside {
+            Ok(())
+        } else {
+            Err(SheetError::InvalidCell)
+        }
+    }
+
+    /// Calls a function for every cell of the expression, from left to right. Every cell of a
+    /// range is visited, row by row.
+    fn visit(&self, f: &mut impl FnMut(CellRef)) {
+        match self {
+            Expr::Num(_) => {}
+            Expr::Cell(cell) => f(*cell),
+            Expr::Neg(inner) => inner.visit(f),
+            Expr::Bin(_, left, right) | Expr::Cmp(_, left, right) => {
+                left.visit(f);
+                right.visit(f);
+            }
+            Expr::If(condition, then, otherwise) => {
+                condition.visit(f);
+                then.visit(f);
+                otherwise.visit(f);
+            }
+            Expr::Lookup(_, key, first, last, index) => {
+                key.visit(f);
+                for row in first.row..=last.row {
+                    for col in first.col..=last.col {
+                        f(CellRef { col, row });
+                    }
+                }
+                index.visit(f);
+            }
+            Expr::Math(_, args) | Expr::Date(_, args) | Expr::Custom(_, args) => {
+                for arg in args {
+                    arg.visit(f);
+                }
+            }
+            Expr::DateDif(start, end, _) => {
+                start.visit(f);
+                end.visit(f);
+            }
+        }
+    }
+
+    /// Finds the cells the expression reads.
+    /// # Arguments
+    /// * `len_h` - The number of columns in the spreadsheet.
+    /// # Returns
+    /// The indices of the cells, without duplicates.
+    pub fn cells(&self, len_h: i32) -> Vec<i32> {
+        let mut res = Vec::new();
+        let mut seen = HashSet::new();
+        self.visit(&mut |cell| {
+            let ind = cell.ind(len_h);
+            if seen.insert(ind) {
+                res.push(ind);
+            }
+        });
+        res
+    }
+
+    /// Checks whether the expression calls TODAY or NOW, whose value changes over time.
+    pub fn is_volatile(&self) -> bool {
+        match self {
+            Expr::Num(_) | Expr::Cell(_) => false,
+            Expr::Neg(inner) => inner.is_volatile(),
+            Expr::Bin(_, left, right) | Expr::Cmp(_, left, right) => {
+                left.is_volatile() || right.is_volatile()
+            }
+            Expr::If(condition, then, otherwise) => {
+                condition.is_volatile() || then.is_volatile() || otherwise.is_volatile()
+            }
+            Expr::Lookup(_, key, _, _, index) => key.is_volatile() || index.is_volatile(),
+            Expr::Date(DateFn::Today | DateFn::Now, _) => true,
+            Expr::Math(_, args) | Expr::Date(_, args) | Expr::Custom(_, args) => {
+                args.iter().any(Expr::is_volatile)
+            }
+            Expr::DateDif(start, end, _) => start.is_volatile() || end.is_volatile(),
+        }
+    }
+
+    /// Moves every cell of the expression.
+    /// # Arguments
+    /// * `f` - Gives the new cell, or `None` if the cell has no new place.
+    /// # Returns
+    /// The moved expression, or `None` if a cell has no new place.
+    pub fn map_cells(&self, f: &mut impl FnMut(CellRef) -> Option<CellRef>) -> Option<Self> {
+        Some(match self {
+            Expr::Num(n) => Expr::Num(*n),
+            Expr::Cell(cell) => Expr::Cell(f(*cell)?),
+            Expr::Neg(inner) => Expr::Neg(Box::new(inner.map_cells(f)?)),
+            Expr::Bin(op, left, right) => Expr::Bin(
+                *op,
+                Box::new(left.map_cells(f)?),
+                Box::new(right.map_cells(f)?),
+            ),
+            Expr::Cmp(op, left, right) => Expr::Cmp(
+                *op,
+                Box::new(left.map_cells(f)?),
+                Box::new(right.map_cells(f)?),
+            ),
+            Expr::If(condition, then, otherwise) => Expr::If(
+                Box::new(condition.map_cells(f)?),
+                Box::new(then.map_cells(f)?),
+                Box::new(otherwise.map_cells(f)?),
+            ),
+            // Only the corners of a range are moved
+            Expr::Lookup(direction, key, first, last, index) => Expr::Lookup(
+                *direction,
+                Box::new(key.map_cells(f)?),
+                f(*first)?,
+                f(*last)?,
+                Box::new(index.map_cells(f)?),
+            ),
+            Expr::Math(function, args) => Expr::Math(
+                *function,
+                args.iter()
+                    .map(|arg| arg.map_cells(f))
+                    .collect::<Option<_>>()?,
+            ),
+            Expr::Date(function, args) => Expr::Date(
+                *function,
+                args.iter()
+                    .map(|arg| arg.map_cells(f))
+                    .collect::<Option<_>>()?,
+            ),
+            Expr::DateDif(start, end, unit) => Expr::DateDif(
+                Box::new(start.map_cells(f)?),
+                Box::new(end.map_cells(f)?),
+                *unit,
+            ),
+            Expr::Custom(name, args) => Expr::Custom(
+                name.clone(),
+                args.iter()
+                    .map(|arg| arg.map_cells(f))
+                    .collect::<Option<_>>()?,
+            ),
+        })
+    }
+
+    /// Evaluates the expression.
+    /// # Arguments
+    /// * `database` - The values of all cells.
+    /// * `err` - The error states of all cells.
+    /// * `len_h` - The number of columns in the spreadsheet.
+    /// * `limits` - The limits of the spreadsheet, whose safe mode freezes TODAY and NOW.
+    /// # Returns
+    /// The value, or `None` if a cell is in error, a division by zero or an overflow happens,
+    /// or a lookup finds no match or has an index outside its range. Only the branch of an
+    /// IF that is chosen is evaluated, so errors in the other one are not passed on. Likewise
+    /// a lookup only reads the cells it compares and the cell it gives, and empty cells are
+    /// compared as 0.
+    pub fn eval(
+        &self,
+        database: &[i32],
+        err: &[bool],
+        len_h: i32,
+        limits: &crate::utils::settings::Limits,
+    ) -> Option<i32> {
+        match self {
+            Expr::Num(n) => Some(*n),
+            Expr::Cell(cell) => {
+                let ind = cell.ind(len_h) as usize;
+                (!err[ind]).then_some(database[ind])
+            }
+            Expr::Neg(inner) => inner.eval(database, err, len_h, limits)?.checked_neg(),
+            Expr::Bin(op, left, right) => {
+                let a = left.eval(database, err, len_h, limits)?;
+                let b = right.eval(database, err, len_h, limits)?;
+                match op {
+                    '+' => a.checked_add(b),
+                    '-' => a.checked_sub(b),
+                    '*' => a.checked_mul(b),
+                    _ => a.checked_div(b),
+                }
+            }
+            Expr::Cmp(op, left, right) => {
+                let a = left.eval(database, err, len_h, limits)?;
+                let b = right.eval(database, err, len_h, limits)?;
+                Some(op.holds(a, b) as i32)
+            }
+            Expr::If(condition, then, otherwise) => {
+                if condition.eval(database, err, len_h, limits)? != 0 {
+                    then.eval(database, err, len_h, limits)
+                } else {
+                    otherwise.eval(database, err, len_h, limits)
+                }
+            }
+            Expr::Lookup(direction, key, first, last, index) => {
+                let key = key.eval(database, err, len_h, limits)?;
+                let index = index.eval(database, err, len_h, limits)?;
+                let (width, height) = (last.col - first.col + 1, last.row - first.row + 1);
+                let (count, size) = match direction {
+                    Direction::Vertical => (height, width),
+                    Direction::Horizontal => (width, height),
+                };
+                if !(1..=size).contains(&index) {
+                    return None;
+                }
+                // The j-th cell of the i-th row or column searched
+                let at = |i: i32, j: i32| match direction {
+                    Direction::Vertical => CellRef {
+                        col: first.col + j,
+                        row: first.row + i,
+                    },
+                    Direction::Horizontal => CellRef {
+                        col: first.col + i,
+                        row: first.row + j,
+                    },
+                };
+                let value = |cell: CellRef| {
+                    let ind = cell.ind(len_h) as usize;
+                    (!err[ind]).then_some(database[ind])
+                };
+                let found = (0..count).find(|&i| value(at(i, 0)) == Some(key))?;
+                value(at(found, index - 1))
+            }
+            Expr::Math(function, args) => {
+                let values = args
+                    .iter()
+                    .map(|arg| arg.eval(database, err, len_h, limits))
+                    .collect::<Option<Vec<i32>>>()?;
+                function.apply(&values)
+            }
+            Expr::Date(function, args) => {
+                let values = args
+                    .iter()
+                    .map(|arg| arg.eval(database, err, len_h, limits))
+                    .collect::<Option<Vec<i32>>>()?;
+                function.apply(&values, limits.now())
+            }
+            Expr::DateDif(start, end, unit) => crate::utils::date::datedif(
+                start.eval(database, err, len_h, limits)?,
+                end.eval(database, err, len_h, limits)?,
+                *unit,
+            ),
+            Expr::Custom(name, args) => {
+                let values = args
+                    .iter()
+                    .map(|arg| arg.eval(database, err, len_h, limits))
+                    .collect::<Option<Vec<i32>>>()?;
+                crate::utils::custom::apply(name, &values)
+            }
+        }
+    }
+
+    /// Finds what the value of the expression stands for (see `utils::date`).
+    /// # Arguments
+    /// * `cell_kind` - Gives the kind of a cell the expression reads.
+    pub fn kind(&self, cell_kind: &mut impl FnMut(CellRef) -> Kind) -> Kind {
+        match self {
+            Expr::Cell(cell) => cell_kind(*cell),
+            Expr::Bin(op, left, right) => {
+                let a = left.kind(cell_kind);
+                Kind::combine(*op, a, right.kind(cell_kind))
+            }
+            Expr::If(_, then, otherwise) => {
+                let kind = then.kind(cell_kind);
+                if kind == otherwise.kind(cell_kind) {
+                    kind
+                } else {
+                    Kind::Number
+                }
+            }
+            Expr::Date(function, _) => function.kind(),
+            _ => Kind::Number,
+        }
+    }
+
+    /// Binding strength of the expression, used to decide where parentheses are needed.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Cmp(_, _, _) => 0,
+            Expr::Bin('+' | '-', _, _) => 1,
+            Expr::Bin(_, _, _) => 2,
+            Expr::Neg(_) => 3,
+            Expr::Num(_)
+            | Expr::Cell(_)
+            | Expr::If(_, _, _)
+            | Expr::Lookup(_, _, _, _, _)
+            | Expr::Math(_, _)
+            | Expr::Date(_, _)
+            | Expr::DateDif(_, _, _)
+            | Expr::Custom(_, _) => 4,
+        }
+    }
+
+    /// Writes the expression with as few parentheses as needed to parse back to it.
+    ///
+    /// Cells are written in the A1 style.
+    pub fn text(&self) -> String {
+        let wrap = |e: &Expr, parens: bool| {
+            if parens {
+                format!("({})", e.text())
+            } else {
+                e.text()
+            }
+        };
+        match self {
+            Expr::Num(n) => n.to_string(),
+            Expr::Cell(cell) => cell.to_string(),
+            Expr::Neg(inner) => format!("-{}", wrap(inner, inner.precedence() < 3)),
+            Expr::Bin(op, left, right) => format!(
+                "{}{}{}",
+                wrap(left, left.precedence() < self.precedence()),
+                op,
+                wrap(right, right.precedence() <= self.precedence())
+            ),
+            // Comparisons cannot be chained, so a comparison inside one is always wrapped
+            Expr::Cmp(op, left, right) => format!(
+                "{}{}{}",
+                wrap(left, left.precedence() == 0),
+                op.symbol(),
+                wrap(right, right.precedence() == 0)
+            ),
+            Expr::If(condition, then, otherwise) => format!(
+                "IF({}, {}, {})",
+                condition.text(),
+                then.text(),
+                otherwise.text()
+            ),
+            Expr::Lookup(direction, key, first, last, index) => format!(
+                "{}({}, {}:{}, {})",
+                direction.name(),
+                key.text(),
+                first,
+                last,
+                index.text()
+            ),
+            Expr::Math(function, args) => {
+                let args: Vec<String> = args.iter().map(Expr::text).collect();
+                format!("{}({})", function.name(), args.join(", "))
+            }
+            Expr::Date(function, args) => {
+                let args: Vec<String> = args.iter().map(Expr::text).collect();
+                format!("{}({})", function.name(), args.join(", "))
+            }
+            Expr::DateDif(start, end, unit) => {
+                format!("DATEDIF({}, {}, {})", start.text(), end.text(), unit.text())
+            }
+            Expr::Custom(name, args) => {
+                let args: Vec<String> = args.iter().map(Expr::text).collect();
+                format!("{}({})", name, args.join(", "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str, database: &[i32], err: &[bool]) -> Option<i32> {
+        Expr::parse(text)
+            .unwrap()
+            .eval(database, err, 2, &Default::default())
+    }
+
+    #[test]
+    fn test_precedence() {
+        // A1=1, B1=2, A2=3, B2=4
+        let database = [0, 1, 2, 3, 4];
+        let err = [false; 5];
+        assert_eq!(eval("(A1+B1)*2-A2/3", &database, &err), Some(5));
+        assert_eq!(eval("A1+B1*2", &database, &err), Some(5));
+        assert_eq!(eval("B2-A2-A1", &database, &err), Some(0));
+        assert_eq!(eval("B2/B1/B1", &database, &err), Some(1));
+        assert_eq!(eval("-(A1+B1)*-2", &database, &err), Some(6));
+        assert_eq!(eval("((b2))", &database, &err), Some(4));
+        assert_eq!(eval("A1/(B1-2)", &database, &err), None);
+        assert_eq!(
+            eval("A1+B1", &database, &[false, true, false, false, false]),
+            None
+        );
+        assert_eq!(eval("-2147483648", &database, &err), Some(i32::MIN));
+    }
+
+    #[test]
+    fn test_conditions() {
+        // A1=1, B1=2, A2=3, B2=4
+        let database = [0, 1, 2, 3, 4];
+        let err = [false; 5];
+        assert_eq!(eval("IF(B2>3, A1, B1)", &database, &err), Some(1));
+        assert_eq!(eval("if(A1+B1==A2, 10, 20)*2", &database, &err), Some(20));
+        assert_eq!(eval("IF(A1!=1, B1, A2<=B2)", &database, &err), Some(1));
+        assert_eq!(eval("(A1>=B1)+(A1<B1)", &database, &err), Some(1));
+        // The branch that is not chosen may be in error
+        let err_a2 = [false, false, false, true, false];
+        assert_eq!(eval("IF(A1<B1, B2, A2)", &database, &err_a2), Some(4));
+        assert_eq!(eval("IF(A2<B1, B2, A1)", &database, &err_a2), None);
+        assert_eq!(Expr::parse("A1<B1<A2"), Err(SheetError::InvalidExpression));
+        assert_eq!(
+            Expr::parse("IF(A1, B1)"),
+            Err(SheetError::InvalidExpression)
+        );
+        assert_eq!(Expr::parse("A1=B1"), Err(SheetError::InvalidExpression));
+        let expr = Expr::parse("IF(B1>10, A1, IF(A2, 1, -1))").unwrap();
+        assert_eq!(expr.cells(2), [2, 1, 3]);
+    }
+
+    #[test]
+    fn test_lookups() {
+        // A1=1, B1=10, C1=100, A2=2, B2=20, C2=200, A3=3, B3=30, C3=300
+        let database = [0, 1, 10, 100, 2, 20, 200, 3, 30, 300];
+        let err = [false; 10];
+        let eval = |text: &str, err: &[bool]| {
+            Expr::parse(text)
+                .unwrap()
+                .eval(&database, err, 3, &Default::default())
+        };
+        assert_eq!(eval("VLOOKUP(2, A1:C3, 3)", &err), Some(200));
+        assert_eq!(eval("vlookup(A3, A1:C3, 2)", &err), Some(30));
+        assert_eq!(eval("HLOOKUP(10, A1:C3, 3)", &err), Some(30));
+        assert_eq!(eval("HLOOKUP(100, A1:C2, A2)", &err), Some(200));
+        assert_eq!(eval("VLOOKUP(2, B1:C3, 1)", &err), None);
+        assert_eq!(eval("VLOOKUP(2, A1:C3, 4)", &err), None);
+        assert_eq!(eval("VLOOKUP(2, A1:C3, 0)", &err), None);
+        // Only the cells compared and the cell given are read
+        let mut err_c3 = err;
+        err_c3[9] = true;
+        assert_eq!(eval("VLOOKUP(1, A1:C3, 3)", &err_c3), Some(100));
+        assert_eq!(eval("VLOOKUP(3, A1:C3, 3)", &err_c3), None);
+
+        let expr = Expr::parse("VLOOKUP(C3, A1:B2, 2)").unwrap();
+        assert_eq!(expr.cells(3), [9, 1, 2, 4, 5]);
+        assert_eq!(expr.check(2, 3), Err(SheetError::InvalidCell));
+        assert_eq!(
+            Expr::parse("VLOOKUP(1, B2:A1, 1)"),
+            Err(SheetError::InvalidRange)
+        );
+        assert_eq!(
+            Expr::parse("VLOOKUP(1, A1, 1)"),
+            Err(SheetError::InvalidRange)
+        );
+        assert_eq!(
+            Expr::parse("VLOOKUP(1, A1:B2)"),
+            Err(SheetError::InvalidExpression)
+        );
+    }
+
+    #[test]
+    fn test_math_functions() {
+        // A1=1, B1=2, A2=3, B2=4
+        let database = [0, 1, 2, 3, 4];
+        let err = [false; 5];
+        assert_eq!(eval("ABS(A1-B2)*2", &database, &err), Some(6));
+        assert_eq!(eval("pow(B1, A2) + mod(B2, A2)", &database, &err), Some(9));
+        assert_eq!(eval("SQRT(B2*B2+A2*A2)", &database, &err), Some(5));
+        assert_eq!(eval("ROUND(1250*A1, -2)", &database, &err), Some(1300));
+        assert_eq!(
+            eval("FLOOR(17, B2)+CEIL(17, B2)", &database, &err),
+            Some(36)
+        );
+        assert_eq!(eval("MOD(A1, A1-1)", &database, &err), None);
+        assert_eq!(eval("SQRT(-B2)", &database, &err), None);
+        assert_eq!(
+            eval("ABS(B1)", &database, &[false, false, true, false, false]),
+            None
+        );
+        assert_eq!(
+            Expr::parse("ABS(A1, B1)"),
+            Err(SheetError::InvalidExpression)
+        );
+        assert_eq!(Expr::parse("POW(A1)"), Err(SheetError::InvalidExpression));
+        assert_eq!(Expr::parse("ABS"), Err(SheetError::InvalidCell));
+    }
+
+    #[test]
+    fn test_custom_functions() {
+        crate::utils::custom::tests::register_double();
+        // A1=1, B1=2, A2=3, B2=4
+        let database = [0, 1, 2, 3, 4];
+        let err = [false; 5];
+        assert_eq!(eval("double(B2)+A1", &database, &err), Some(9));
+        assert_eq!(eval("DOUBLE(2147483647*A1)", &database, &err), None);
+        assert_eq!(
+            Expr::parse("DOUBLE(A1, B1)"),
+            Err(SheetError::InvalidExpression)
+        );
+        assert_eq!(Expr::parse("TRIPLE(A1)"), Err(SheetError::InvalidOperation));
+
+        let expr = Expr::parse("double(A1)*B1").unwrap();
+        assert_eq!(expr.text(), "DOUBLE(A1)*B1");
+        assert_eq!(expr.cells(2), [1, 2]);
+        let out = crate::utils::sheet::Sheet::new(2, 2)
+            .parse_assignment("A2=DOUBLE(B1)")
+            .unwrap();
+        assert_eq!(out.expr.op, EXP_OP);
+    }
+
+    #[test]
+    fn test_date_functions() {
+        // A1=2024-05-01, B1=2024-07-15, A2=3, B2=4
+        let database = [0, 19844, 19919, 3, 4];
+        let err = [false; 5];
+        assert_eq!(eval("DATE(2024, 5, 1)", &database, &err), Some(19844));
+        assert_eq!(eval("date(2024, A2+B2, 0)", &database, &err), Some(19904));
+        assert_eq!(eval("DATEDIF(A1, B1, \"D\")", &database, &err), Some(75));
+        assert_eq!(eval("DATEDIF(A1, B1, \"m\")", &database, &err), Some(2));
+        assert_eq!(eval("DATEDIF(A1, B1, \"Y\")", &database, &err), Some(0));
+        assert_eq!(eval("DATEDIF(B1, A1, \"D\")", &database, &err), None);
+        assert!(eval("TODAY()", &database, &err).is_some_and(|d| d > 19844));
+        assert!(eval("NOW()", &database, &err).is_some_and(|t| (0..86400).contains(&t)));
+        assert_eq!(
+            Expr::parse("DATE(2024, 5)"),
+            Err(SheetError::InvalidExpression)
+        );
+        assert_eq!(Expr::parse("TODAY(1)"), Err(SheetError::InvalidExpression));
+        assert_eq!(
+            Expr::parse("DATEDIF(A1, B1, \"W\")"),
+            Err(SheetError::InvalidExpression)
+        );
+        assert_eq!(
+            Expr::parse("DATEDIF(A1, B1, D)"),
+            Err(SheetError::InvalidCell)
+        );
+        let volatile = |text: &str| Expr::parse(text).unwrap().is_volatile();
+        assert!(volatile("DATEDIF(A1, TODAY(), \"D\")"));
+        assert!(volatile("IF(A2, 0, -NOW())"));
+        assert!(!volatile("DATE(2024, A2, 1)+B2"));
+        assert_eq!(
+            Expr::parse("DATEDIF(A1, B1, \"D)"),
+            Err(SheetError::InvalidExpression)
+        );
+
+        let kind = |text: &str| {
+            Expr::parse(text).unwrap().kind(&mut |cell| {
+                if cell.col == 1 {
+                    Kind::Date
+                } else {
+                    Kind::Number
+                }
+            })
+        };
+        assert_eq!(kind("DATE(2024, 5, 1)+7"), Kind::Date);
+        assert_eq!(kind("A1-B1"), Kind::Date);
+        assert_eq!(kind("A1-A2"), Kind::Number);
+        assert_eq!(kind("IF(B1, A1, TODAY())"), Kind::Date);
+        assert_eq!(kind("IF(B1, A1, 0)"), Kind::Number);
+        assert_eq!(kind("NOW()"), Kind::Time);
+        assert_eq!(kind("DATEDIF(A1, A2, \"D\")"), Kind::Number);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Expr::parse("(A1+B1"), Err(SheetError::InvalidExpression));
+        assert_eq!(Expr::parse("A1+"), Err(SheetError::InvalidExpression));
+        assert_eq!(Expr::parse("A1 B1"), Err(SheetError::InvalidExpression));
+        assert_eq!(Expr::parse("A1%2"), Err(SheetError::InvalidExpression));
+        assert_eq!(Expr::parse("A1+XY"), Err(SheetError::InvalidCell));
+        assert_eq!(
+            Expr::parse("SUM(A1:B1)*2"),
+            Err(SheetError::InvalidOperation)
+        );
+        assert_eq!(Expr::parse("2147483648+A1"), Err(SheetError::InvalidValue));
+        assert_eq!(
+            Expr::parse("(A1+B9)*2").unwrap().check(2, 2),
+            Err(SheetError::InvalidCell)
+        );
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        for text in [
+            "(A1+B1)*2-A2/3",
+            "A1-(B1-A2)",
+            "-(A1*B1)",
+            "A1*-B1",
+            "A1--5",
+            "A1/(B1*A2)",
+            "IF(B1>10, A1, A2+1)",
+            "(A1<B1)==(A2<B1)",
+            "-IF(A1!=0, 1, 2)*3",
+            "VLOOKUP(A1, A1:B2, 2)+1",
+            "HLOOKUP(A1*2, A2:B2, A1)",
+            "ABS(A1-B1)*2",
+            "POW(MOD(A1, 7), SQRT(B1))",
+            "-ROUND(A1, -2)+FLOOR(B1, 5)-CEIL(A2, 10)",
+            "DATE(2024, A1, B1+1)-TODAY()",
+            "DATEDIF(A1, DATE(2024, 5, 1), \"M\")",
+            "NOW()",
+        ] {
+            let expr = Expr::parse(text).unwrap();
+            assert_eq!(expr.text(), text);
+            assert_eq!(Expr::parse(&expr.text()), Ok(expr));
+        }
+        assert_eq!(Expr::parse("((A1)+(B1*2))").unwrap().text(), "A1+B1*2");
+        assert_eq!(Expr::parse("B1+A1+B1").unwrap().cells(2), [2, 1]);
+    }
+}
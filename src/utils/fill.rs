@@ -0,0 +1,439 @@
+//! This module contains auto-fill, which extends the values of a range into neighbouring cells.
+//!
+//! Like pasting, filling builds the assignment commands for the filled cells and leaves
+//! applying them to the caller. Formulas are filled like a paste of formulas: their cell
+//! references move with the cell, so `B1=A1*2` filled down to B3 gives `B3=A3*2`.
+//!
+//! Both interfaces fill with the `fill` command (see `parse_command`):
+//!
+//! ```text
+//! fill A1:A10
+//! fill A1:A2 A10
+//! fill A1:B1 F1 copy
+//! ```
+//!
+//! A range alone fills down from its top row, or right along a single row, by copying.
+//! Otherwise the range is the source, the cell is the last one to fill and the mode is
+//! `series` unless `copy` is given.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+
+/// How the filled cells continue the source values.
+///
+/// # Variants
+/// * `Series` - Continue the arithmetic sequence of the source values
+/// * `Copy` - Repeat the source values
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum FillMode {
+    #[default]
+    Series,
+    Copy,
+}
+
+/// Extends a sequence of values.
+///
+/// In series mode the step is the average difference between the source values, rounded
+/// towards zero, or 1 for a single value. The sequence stops early if it would overflow.
+/// # Arguments
+/// * `source` - The values to extend, in fill order.
+/// * `count` - The number of values to produce.
+/// * `mode` - How to continue the values.
+/// # Returns
+/// The next `count` values.
+pub fn extend(source: &[i32], count: usize, mode: FillMode) -> Vec<i32> {
+    if source.is_empty() {
+        return Vec::new();
+    }
+    match mode {
+        FillMode::Copy => source.iter().copied().cycle().take(count).collect(),
+        FillMode::Series => {
+            let n = source.len() as i64;
+            let last = *source.last().unwrap() as i64;
+            let step = if n == 1 {
+                1
+            } else {
+                (last - source[0] as i64) / (n - 1)
+            };
+            (1..=count as i64)
+                .map_while(|k| i32::try_from(last + step * k).ok())
+                .collect()
+        }
+    }
+}
+
+/// Splits a range for fill down or fill right.
+///
+/// Fill down copies the top row of the range into the rows below it, and fill right copies
+/// the left column into the columns to its right.
+/// # Arguments
+/// * `range` - First column, first row, last column and last row of the range.
+/// * `down` - Whether to fill down rather than right.
+/// # Returns
+/// The source range and the last cell to fill, as taken by `fill`.
+pub fn fill_in(range: (i32, i32, i32, i32), down: bool) -> ((i32, i32, i32, i32), (i32, i32)) {
+    let (x1, y1, x2, y2) = range;
+    if down {
+        ((x1, y1, x2, y1), (x2, y2))
+    } else {
+        ((x1, y1, x1, y2), (x2, y2))
+    }
+}
+
+/// A parsed fill: the source range, the last cell to fill and the fill mode.
+pub type Fill = ((i32, i32, i32, i32), (i32, i32), FillMode);
+
+/// Parses a `fill <range> [<cell>] [series|copy]` command.
+/// # Arguments
+/// * `command` - The full command string.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// * `len_v` - The number of rows in the spreadsheet.
+/// # Returns
+/// The source range, the last cell to fill and the fill mode, as taken by `fill`, or `None`
+/// if the command is invalid.
+pub fn parse_command(command: &str, len_h: i32, len_v: i32) -> Option<Fill> {
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    if parts.first() != Some(&"fill") {
+        return None;
+    }
+    let range = crate::utils::input::parse_range(parts.get(1)?, len_h, len_v)?;
+    if parts.len() == 2 {
+        let (source, end) = fill_in(range, range.3 > range.1);
+        return Some((source, end, FillMode::Copy));
+    }
+    let end = parts
+        .get(2)
+        .filter(|c| crate::utils::input::is_valid_cell(c, len_h, len_v))
+        .map(|c| crate::cell_to_int(c))?;
+    let mode = match parts.get(3) {
+        None | Some(&"series") => FillMode::Series,
+        Some(&"copy") => FillMode::Copy,
+        _ => return None,
+    };
+    (parts.len() <= 4).then_some((range, (end / 1000, end % 1000), mode))
+}
+
+/// Checks whether a cell holds a formula rather than a constant or nothing.
+fn is_formula(op: &Ops) -> bool {
+    !op.opcpde.is_empty() && op.opcpde != "EQV"
+}
+
+/// Builds the assignments that copy the formulas of a row into the row below it.
+///
+/// Constants are left out, so the new row of a ledger starts with its formula columns
+/// filled and its input columns empty.
+/// # Arguments
+/// * `opers` - The operations for each cell.
+/// * `len_h` - The number of columns in the data array.
+/// * `len_v` - The number of rows in the data array.
+/// * `row` - The row to copy from.
+/// # Returns
+/// One assignment command per formula, skipping formulas whose references would leave the
+/// spreadsheet.
+pub fn copy_formulas_down(opers: &Cells<Ops>, len_h: i32, len_v: i32, row: i32) -> Vec<String> {
+    (1..=len_h)
+        .filter_map(|col| {
+            let op = &opers[((row - 1) * len_h + col) as usize];
+            if !is_formula(op) {
+                return None;
+            }
+            let moved = crate::utils::clipboard::shift_op(op, len_h, (0, 1), len_h, len_v)?;
+            Some(format!(
+                "{}{}={}",
+                crate::utils::display::get_label(col),
+                row + 1,
+                crate::utils::input::formula_text(&moved, len_h)
+            ))
+        })
+        .collect()
+}
+
+/// Builds the assignments that fill from a range up to a cell.
+///
+/// The direction of the fill is given by the position of `end` relative to the range: each
+/// column is filled when `end` lies above or below it, each row when it lies left or right.
+/// Lines whose source holds a formula repeat the source cells with their references moved,
+/// whatever the mode; other lines continue the source values.
+/// # Arguments
+/// * `database` - A reference to the data array.
+/// * `err` - A reference to the error array.
+/// * `opers` - The operations for each cell.
+/// * `len_h` - The number of columns in the data array.
+/// * `len_v` - The number of rows in the data array.
+/// * `source` - First column, first row, last column and last row of the source range.
+/// * `end` - Column and row of the last cell to fill.
+/// * `mode` - How to continue the values.
+/// # Returns
+/// One assignment command per filled cell. Lines of values whose source contains a cell in
+/// error are skipped, as are formulas whose references would leave the spreadsheet, and
+/// everything if `end` is inside the range or diagonal to it.
+#[allow(clippy::too_many_arguments)]
+pub fn fill(
+    database: &[i32],
+    err: &[bool],
+    opers: &Cells<Ops>,
+    len_h: i32,
+    len_v: i32,
+    source: (i32, i32, i32, i32),
+    end: (i32, i32),
+    mode: FillMode,
+) -> Vec<String> {
+    let (x1, y1, x2, y2) = source;
+    let inside_cols = (x1..=x2).contains(&end.0);
+    let inside_rows = (y1..=y2).contains(&end.1);
+    // (lines to fill, source cells along each line, cells to fill along each line)
+    let (lines, along, targets): (Vec<i32>, Vec<i32>, Vec<i32>) = if inside_cols && end.1 > y2 {
+        (
+            (x1..=x2).collect(),
+            (y1..=y2).collect(),
+            (y2 + 1..=end.1).collect(),
+        )
+    } else if inside_cols && end.1 < y1 {
+        (
+            (x1..=x2).collect(),
+            (y1..=y2).rev().collect(),
+            (end.1..y1).rev().collect(),
+        )
+    } else if inside_rows && end.0 > x2 {
+        (
+            (y1..=y2).collect(),
+            (x1..=x2).collect(),
+            (x2 + 1..=end.0).collect(),
+        )
+    } else if inside_rows && end.0 < x1 {
+        (
+            (y1..=y2).collect(),
+            (x1..=x2).rev().collect(),
+            (end.0..x1).rev().collect(),
+        )
+    } else {
+        return Vec::new();
+    };
+    let vertical = inside_cols;
+    let cell = |line: i32, pos: i32| {
+        if vertical { (line, pos) } else { (pos, line) }
+    };
+
+    let mut commands = Vec::new();
+    for &line in &lines {
+        let sources: Vec<(i32, i32)> = along.iter().map(|&pos| cell(line, pos)).collect();
+        let op_at = |(col, row): (i32, i32)| &opers[((row - 1) * len_h + col) as usize];
+        if sources.iter().any(|&c| is_formula(op_at(c))) {
+            for (k, &pos) in targets.iter().enumerate() {
+                let (sc, sr) = sources[k % sources.len()];
+                let (col, row) = cell(line, pos);
+                let op = op_at((sc, sr));
+                let moved = if is_formula(op) {
+                    let offset = (col - sc, row - sr);
+                    crate::utils::clipboard::shift_op(op, len_h, offset, len_h, len_v)
+                } else {
+                    Some(op.clone()).filter(|op| !op.opcpde.is_empty())
+                };
+                if let Some(op) = moved {
+                    commands.push(format!(
+                        "{}{}={}",
+                        crate::utils::display::get_label(col),
+                        row,
+                        crate::utils::input::formula_text(&op, len_h)
+                    ));
+                }
+            }
+            continue;
+        }
+        let mut values = Vec::new();
+        for &pos in &along {
+            let (col, row) = cell(line, pos);
+            let ind = ((row - 1) * len_h + col) as usize;
+            if err[ind] {
+                break;
+            }
+            values.push(database[ind]);
+        }
+        if values.len() != along.len() {
+            continue;
+        }
+        for (&pos, value) in targets.iter().zip(extend(&values, targets.len(), mode)) {
+            let (col, row) = cell(line, pos);
+            commands.push(format!(
+                "{}{}={}",
+                crate::utils::display::get_label(col),
+                row,
+                value
+            ));
+        }
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extend() {
+        assert_eq!(extend(&[1, 2], 3, FillMode::Series), vec![3, 4, 5]);
+        assert_eq!(extend(&[10, 7, 4], 2, FillMode::Series), vec![1, -2]);
+        assert_eq!(extend(&[5], 2, FillMode::Series), vec![6, 7]);
+        assert_eq!(extend(&[1, 2], 3, FillMode::Copy), vec![1, 2, 1]);
+        assert_eq!(extend(&[i32::MAX - 1], 3, FillMode::Series), vec![i32::MAX]);
+        assert!(extend(&[], 3, FillMode::Series).is_empty());
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            parse_command("fill A1:A3", 3, 3),
+            Some(((1, 1, 1, 1), (1, 3), FillMode::Copy))
+        );
+        assert_eq!(
+            parse_command("fill A1:C1", 3, 3),
+            Some(((1, 1, 1, 1), (3, 1), FillMode::Copy))
+        );
+        assert_eq!(
+            parse_command("fill A1:A2 A3", 3, 3),
+            Some(((1, 1, 1, 2), (1, 3), FillMode::Series))
+        );
+        assert_eq!(
+            parse_command("fill A1:B1 C1 copy", 3, 3),
+            Some(((1, 1, 2, 1), (3, 1), FillMode::Copy))
+        );
+        assert_eq!(parse_command("fill A1:A2 A4", 3, 3), None);
+        assert_eq!(parse_command("fill A1:A2 A3 twice", 3, 3), None);
+        assert_eq!(parse_command("fill A1:A2 A3 copy now", 3, 3), None);
+        assert_eq!(parse_command("fill", 3, 3), None);
+    }
+
+    #[test]
+    fn test_fill_directions() {
+        // 3x3 grid with A1=1, A2=2, B1=ERR
+        let database = vec![0, 1, 0, 0, 2, 0, 0, 0, 0, 0];
+        let err = vec![
+            false, false, true, false, false, false, false, false, false, false,
+        ];
+        let opers = crate::utils::sheet::Sheet::new(3, 3).opers().clone();
+        assert_eq!(
+            fill(
+                &database,
+                &err,
+                &opers,
+                3,
+                3,
+                (1, 1, 1, 2),
+                (1, 3),
+                FillMode::Series
+            ),
+            vec!["A3=3"]
+        );
+        assert_eq!(
+            fill(
+                &database,
+                &err,
+                &opers,
+                3,
+                3,
+                (1, 2, 1, 2),
+                (1, 1),
+                FillMode::Copy
+            ),
+            vec!["A1=2"]
+        );
+        assert_eq!(
+            fill(
+                &database,
+                &err,
+                &opers,
+                3,
+                3,
+                (1, 1, 1, 2),
+                (3, 2),
+                FillMode::Series
+            ),
+            vec!["B1=2", "C1=3", "B2=3", "C2=4"]
+        );
+        // Lines starting with an error are skipped
+        assert_eq!(
+            fill(
+                &database,
+                &err,
+                &opers,
+                3,
+                3,
+                (2, 1, 2, 1),
+                (2, 3),
+                FillMode::Copy
+            ),
+            Vec::<String>::new()
+        );
+        assert!(
+            fill(
+                &database,
+                &err,
+                &opers,
+                3,
+                3,
+                (1, 1, 1, 2),
+                (3, 3),
+                FillMode::Copy
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_fill_formulas() {
+        let mut sheet = crate::utils::sheet::Sheet::new(3, 4);
+        for command in [
+            "A1=1",
+            "B1=A1*2",
+            "C1=SUM(A1:B1)",
+            "A2=5",
+            "B2=7",
+            "C2=A1+1",
+        ] {
+            let out = sheet.parse_assignment(command).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        let fill = |source, end| {
+            fill(
+                sheet.database(),
+                sheet.err(),
+                sheet.opers(),
+                3,
+                4,
+                source,
+                end,
+                FillMode::Series,
+            )
+        };
+        assert_eq!(
+            fill((2, 1, 3, 1), (3, 3)),
+            vec!["B2=A2*2", "B3=A3*2", "C2=SUM(A2:B2)", "C3=SUM(A3:B3)"]
+        );
+        // A line with a formula repeats its constants instead of continuing them
+        assert_eq!(fill((2, 1, 2, 2), (2, 4)), vec!["B3=A3*2", "B4=7"]);
+        assert_eq!(fill((1, 1, 1, 2), (1, 4)), vec!["A3=9", "A4=13"]);
+        assert_eq!(fill((2, 1, 2, 1), (3, 1)), vec!["C1=B1*2"]);
+        // References that would move above row 1 are skipped
+        assert!(fill((3, 2, 3, 2), (3, 1)).is_empty());
+
+        let (source, end) = fill_in((2, 1, 3, 3), true);
+        assert_eq!((source, end), ((2, 1, 3, 1), (3, 3)));
+        assert_eq!(fill(source, end).len(), 4);
+        assert_eq!(fill_in((2, 1, 3, 3), false), ((2, 1, 2, 3), (3, 3)));
+    }
+
+    #[test]
+    fn test_copy_formulas_down() {
+        let mut sheet = crate::utils::sheet::Sheet::new(3, 3);
+        for command in ["A2=5", "B2=A2*2", "C2=C1+B2", "B3=A3*2"] {
+            let out = sheet.parse_assignment(command).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        assert_eq!(
+            copy_formulas_down(sheet.opers(), 3, 3, 2),
+            vec!["B3=A3*2", "C3=C2+B3"]
+        );
+        // Formulas on the last row would read past the end
+        assert!(copy_formulas_down(sheet.opers(), 3, 3, 3).is_empty());
+    }
+}
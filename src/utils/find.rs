@@ -0,0 +1,180 @@
+//! This module contains find and replace, which search the values and formulas of cells.
+//!
+//! A cell matches when its value as shown or its formula as typed contains the text looked
+//! for, ignoring case, so `find b2` finds every cell reading B2 and `find 2024-05` finds the
+//! dates of May 2024. Replacing rewrites the formulas of the matching cells and assigns them
+//! again, so the sheet is recalculated as if they were typed:
+//!
+//! ```text
+//! find B2
+//! replace B2 C2
+//! ```
+//!
+//! Cells can also be found by their value, with a comparison written as in COUNTIF such as
+//! `find_where >1000`, or by being in an error state, with `find_where ERR`.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+
+/// Finds the byte offsets where a text occurs in another, ignoring ASCII case.
+fn occurrences(text: &str, query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    // Uppercasing ASCII keeps every byte offset, so the offsets are valid in `text`
+    let (text, query) = (text.to_ascii_uppercase(), query.to_ascii_uppercase());
+    text.match_indices(&query).map(|(i, _)| i).collect()
+}
+
+/// Finds the cells whose value or formula contains a text, ignoring case.
+/// # Arguments
+/// * `query` - The text to look for.
+/// * `opers` - The operations for each cell.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// * `value_text` - Gives the value of a cell as shown.
+/// # Returns
+/// The indices of the matching cells, row by row. Cells never assigned are not searched.
+pub fn matches(
+    query: &str,
+    opers: &Cells<Ops>,
+    len_h: i32,
+    value_text: impl Fn(i32) -> String,
+) -> Vec<i32> {
+    let query = query.trim();
+    (1..opers.len() as i32)
+        .filter(|&ind| !opers[ind as usize].opcpde.is_empty())
+        .filter(|&ind| {
+            let formula = crate::utils::input::formula_text(&opers[ind as usize], len_h);
+            !occurrences(&formula, query).is_empty()
+                || !occurrences(&value_text(ind), query).is_empty()
+        })
+        .collect()
+}
+
+/// Finds the cells whose value meets a comparison, or that are in an error state.
+/// # Arguments
+/// * `predicate` - A comparison such as ">1000" or "<>0" (see `Criterion::parse`), or "ERR".
+/// * `data` - The values of the cells.
+/// * `err` - The error flags of the cells.
+/// * `opers` - The operations for each cell.
+/// # Returns
+/// The indices of the matching cells, row by row, or `None` if the predicate is invalid.
+/// Cells never assigned are not searched, and cells in an error state only match "ERR".
+pub fn matches_where(
+    predicate: &str,
+    data: &[i32],
+    err: &[bool],
+    opers: &Cells<Ops>,
+) -> Option<Vec<i32>> {
+    let errors = predicate.trim().eq_ignore_ascii_case("ERR");
+    let criterion = if errors {
+        None
+    } else {
+        Some(crate::utils::operations::Criterion::parse(predicate)?)
+    };
+    let found = (1..opers.len())
+        .filter(|&ind| !opers[ind].opcpde.is_empty())
+        .filter(|&ind| match criterion {
+            Some(criterion) => !err[ind] && criterion.holds(data[ind]),
+            None => err[ind],
+        })
+        .map(|ind| ind as i32)
+        .collect();
+    Some(found)
+}
+
+/// Gives the assignments that replace a text in the formulas of cells, ignoring case.
+/// # Arguments
+/// * `query` - The text to replace.
+/// * `replacement` - The text to put in its place.
+/// * `opers` - The operations for each cell.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// # Returns
+/// Commands such as "A1=C2+1", one for each cell whose formula changes, row by row. A
+/// command may be invalid if the new formula is, and is then refused when applied.
+pub fn replace(query: &str, replacement: &str, opers: &Cells<Ops>, len_h: i32) -> Vec<String> {
+    let query = query.trim();
+    let mut commands = Vec::new();
+    for ind in 1..opers.len() as i32 {
+        let op = &opers[ind as usize];
+        if op.opcpde.is_empty() {
+            continue;
+        }
+        let formula = crate::utils::input::formula_text(op, len_h);
+        let found = occurrences(&formula, query);
+        if found.is_empty() {
+            continue;
+        }
+        let mut text = String::new();
+        let mut last = 0;
+        for start in found {
+            text.push_str(&formula[last..start]);
+            text.push_str(replacement);
+            last = start + query.len();
+        }
+        text.push_str(&formula[last..]);
+        if text != formula {
+            commands.push(format!("{}={}", crate::ind_to_cell(ind, len_h), text));
+        }
+    }
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sheet::Sheet;
+
+    fn sheet(commands: &[&str]) -> Sheet {
+        let mut sheet = Sheet::new(3, 3);
+        for command in commands {
+            let out = sheet.parse_assignment(command).expect(command);
+            sheet.update(&out).unwrap();
+        }
+        sheet
+    }
+
+    #[test]
+    fn test_matches() {
+        let sheet = sheet(&["A1=5", "B1=A1+1", "C2=SUM(A1:B1)", "A3=DATE(2024,5,1)"]);
+        let find = |query| {
+            matches(query, sheet.opers(), 3, |c| {
+                crate::utils::date::value_text(sheet.database(), sheet.opers(), c, 3)
+            })
+        };
+        assert_eq!(find("a1"), vec![2, 6]);
+        assert_eq!(find("6"), vec![2]);
+        assert_eq!(find("sum("), vec![6]);
+        assert_eq!(find("2024-05"), vec![7]);
+        assert_eq!(find("0"), vec![7]);
+        assert!(find("").is_empty());
+        assert!(find("D1").is_empty());
+    }
+
+    #[test]
+    fn test_matches_where() {
+        let sheet = sheet(&["A1=5", "B1=2000", "C1=1/0", "A2=C1+1", "B2=0"]);
+        let find =
+            |predicate| matches_where(predicate, sheet.database(), sheet.err(), sheet.opers());
+        assert_eq!(find(">1000"), Some(vec![2]));
+        assert_eq!(find("\"<=5\""), Some(vec![1, 5]));
+        assert_eq!(find("0"), Some(vec![5]));
+        assert_eq!(find("err"), Some(vec![3, 4]));
+        // Cells never assigned are not searched
+        assert_eq!(find("<>5"), Some(vec![2, 5]));
+        assert_eq!(find(">"), None);
+        assert_eq!(find("big"), None);
+    }
+
+    #[test]
+    fn test_replace() {
+        let sheet = sheet(&["A1=5", "B1=A1+1", "C2=SUM(A1:B1)", "C3=a1*A1"]);
+        assert_eq!(
+            replace("a1", "C1", sheet.opers(), 3),
+            vec!["B1=C1+1", "C2=SUM(C1:B1)", "C3=C1*C1"]
+        );
+        assert_eq!(replace("5", "7", sheet.opers(), 3), vec!["A1=7"]);
+        assert_eq!(replace("A1", "A1", sheet.opers(), 3), Vec::<String>::new());
+        assert!(replace("", "x", sheet.opers(), 3).is_empty());
+    }
+}
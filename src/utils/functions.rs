@@ -0,0 +1,337 @@
+//! This module contains the reference of the functions that can be used in formulas.
+//!
+//! The reference is used by the `help` command, the Help dialog and autocomplete, so a new
+//! function only has to be described here.
+
+/// Description of a function.
+///
+/// # Fields
+/// * `name` - Name of the function as typed
+/// * `args` - Arguments of the function
+/// * `description` - What the function computes
+/// * `example` - An example formula using the function
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FunctionInfo {
+    pub name: &'static str,
+    pub args: &'static str,
+    pub description: &'static str,
+    pub example: &'static str,
+}
+
+/// All functions, in alphabetical order.
+pub const FUNCTIONS: [FunctionInfo; 33] = [
+    FunctionInfo {
+        name: "ABS",
+        args: "value",
+        description: "Absolute value.",
+        example: "A1=ABS(B1-C1)",
+    },
+    FunctionInfo {
+        name: "AVERAGEIF",
+        args: "range, criterion",
+        description: "Average of the values in the range meeting the criterion, rounded towards zero. The criterion is a comparison with a value such as \">5\" or \"<>0\"; a value alone such as \"3\" means equal to it.",
+        example: "C1=AVERAGEIF(A1:A10, \">0\")",
+    },
+    FunctionInfo {
+        name: "AVG",
+        args: "range",
+        description: "Average of the values in the range, rounded towards zero.",
+        example: "C1=AVG(A1:B10)",
+    },
+    FunctionInfo {
+        name: "AVG_SKIPERR",
+        args: "range",
+        description: "Like AVG, but cells in an error state are left out instead of giving an error.",
+        example: "C1=AVG_SKIPERR(A1:B10)",
+    },
+    FunctionInfo {
+        name: "CEIL",
+        args: "value, multiple",
+        description: "Rounds the value up to a multiple, e.g. CEIL(17, 5) is 20.",
+        example: "A1=CEIL(B1, 10)",
+    },
+    FunctionInfo {
+        name: "CHITEST",
+        args: "observed, expected",
+        description: "Chi-square goodness-of-fit test of observed against expected counts. Typed in the terminal; shows the statistic and p-value in the Stats Tests dialog.",
+        example: "CHITEST(A1:A5, B1:B5)",
+    },
+    FunctionInfo {
+        name: "COUNT",
+        args: "range",
+        description: "Number of cells in the range holding a value. Empty cells and cells in an error state are not counted; a cell holding 0 is.",
+        example: "C1=COUNT(A1:A10)",
+    },
+    FunctionInfo {
+        name: "COUNTA",
+        args: "range",
+        description: "Number of cells in the range that are not empty, including cells in an error state.",
+        example: "C1=COUNTA(A1:A10)",
+    },
+    FunctionInfo {
+        name: "COUNTBLANK",
+        args: "range",
+        description: "Number of empty cells in the range, which were never assigned. A cell holding 0 is not empty.",
+        example: "C1=COUNTBLANK(A1:A10)",
+    },
+    FunctionInfo {
+        name: "COUNTIF",
+        args: "range, criterion",
+        description: "Number of values in the range meeting the criterion, written as for AVERAGEIF.",
+        example: "C1=COUNTIF(A1:A10, \">=50\")",
+    },
+    FunctionInfo {
+        name: "DATE",
+        args: "year, month, day",
+        description: "A date, shown as 2024-05-01. Months and days past the end carry over, so DATE(2024, 13, 1) is 2025-01-01. Adding days to a date gives a date; the difference of two dates is a number of days.",
+        example: "A1=DATE(2024, 5, 1)",
+    },
+    FunctionInfo {
+        name: "DATEDIF",
+        args: "start, end, unit",
+        description: "Whole days (\"D\"), months (\"M\") or years (\"Y\") from the start date to the end date. Gives an error if the end is before the start.",
+        example: "C1=DATEDIF(A1, B1, \"M\")",
+    },
+    FunctionInfo {
+        name: "FLOOR",
+        args: "value, multiple",
+        description: "Rounds the value down to a multiple, e.g. FLOOR(17, 5) is 15.",
+        example: "A1=FLOOR(B1, 10)",
+    },
+    FunctionInfo {
+        name: "HLOOKUP",
+        args: "key, range, row",
+        description: "Finds the first column of the range whose top cell equals the key and gives the value in the given row of that column (1 is the top row). Gives an error if no column matches.",
+        example: "A1=HLOOKUP(B1, C1:H3, 2)",
+    },
+    FunctionInfo {
+        name: "IF",
+        args: "condition, if_true, if_false",
+        description: "Gives if_true when the condition is not 0, otherwise if_false. Conditions compare two expressions with >, <, >=, <=, == or !=, which gives 1 or 0.",
+        example: "A1=IF(B1>10, C1, D1)",
+    },
+    FunctionInfo {
+        name: "MAX",
+        args: "range",
+        description: "Largest value in the range.",
+        example: "C1=MAX(A1:B10)",
+    },
+    FunctionInfo {
+        name: "MAX_SKIPERR",
+        args: "range",
+        description: "Like MAX, but cells in an error state are left out instead of giving an error.",
+        example: "C1=MAX_SKIPERR(A1:B10)",
+    },
+    FunctionInfo {
+        name: "MIN",
+        args: "range",
+        description: "Smallest value in the range.",
+        example: "C1=MIN(A1:B10)",
+    },
+    FunctionInfo {
+        name: "MIN_SKIPERR",
+        args: "range",
+        description: "Like MIN, but cells in an error state are left out instead of giving an error.",
+        example: "C1=MIN_SKIPERR(A1:B10)",
+    },
+    FunctionInfo {
+        name: "MOD",
+        args: "value, divisor",
+        description: "Remainder of the division, with the sign of the divisor, e.g. MOD(-3, 7) is 4.",
+        example: "A1=MOD(B1, 7)",
+    },
+    FunctionInfo {
+        name: "NOW",
+        args: "",
+        description: "The current time of day, shown as 14:30:00. It is read again whenever the cell is recalculated.",
+        example: "A1=NOW()",
+    },
+    FunctionInfo {
+        name: "POW",
+        args: "base, exponent",
+        description: "The base raised to a power that is not negative.",
+        example: "A1=POW(B1, 3)",
+    },
+    FunctionInfo {
+        name: "ROUND",
+        args: "value, digits",
+        description: "Rounds half away from zero. A negative number of digits rounds left of the units, e.g. ROUND(1250, -2) is 1300; other values are kept.",
+        example: "A1=ROUND(B1, -2)",
+    },
+    FunctionInfo {
+        name: "SLEEP",
+        args: "seconds",
+        description: "Waits for the given number of seconds, then gives that number. The seconds can be a value or a cell.",
+        example: "B1=SLEEP(A1)",
+    },
+    FunctionInfo {
+        name: "SQRT",
+        args: "value",
+        description: "Square root of a value that is not negative, rounded down.",
+        example: "A1=SQRT(B1)",
+    },
+    FunctionInfo {
+        name: "STDEV",
+        args: "range",
+        description: "Population standard deviation of the values in the range, rounded to an integer.",
+        example: "C1=STDEV(A1:A10)",
+    },
+    FunctionInfo {
+        name: "STDEV_SKIPERR",
+        args: "range",
+        description: "Like STDEV, but cells in an error state are left out instead of giving an error.",
+        example: "C1=STDEV_SKIPERR(A1:A10)",
+    },
+    FunctionInfo {
+        name: "SUM",
+        args: "range",
+        description: "Sum of the values in the range.",
+        example: "C1=SUM(A1:B10)",
+    },
+    FunctionInfo {
+        name: "SUMIF",
+        args: "range, criterion",
+        description: "Sum of the values in the range meeting the criterion, written as for AVERAGEIF.",
+        example: "C1=SUMIF(A1:A10, \">5\")",
+    },
+    FunctionInfo {
+        name: "SUM_SKIPERR",
+        args: "range",
+        description: "Like SUM, but cells in an error state are left out instead of giving an error.",
+        example: "C1=SUM_SKIPERR(A1:B10)",
+    },
+    FunctionInfo {
+        name: "TODAY",
+        args: "",
+        description: "The current date. It is read again whenever the cell is recalculated.",
+        example: "A1=TODAY()+7",
+    },
+    FunctionInfo {
+        name: "TTEST",
+        args: "sample1, sample2",
+        description: "Welch's two-sample t-test. Typed in the terminal; shows the statistic and p-value in the Stats Tests dialog.",
+        example: "TTEST(A1:A10, B1:B10)",
+    },
+    FunctionInfo {
+        name: "VLOOKUP",
+        args: "key, range, column",
+        description: "Finds the first row of the range whose leftmost cell equals the key and gives the value in the given column of that row (1 is the leftmost column). Gives an error if no row matches.",
+        example: "A1=VLOOKUP(B1, C1:E20, 2)",
+    },
+];
+
+impl FunctionInfo {
+    /// The function with its arguments, such as "SUM(range)".
+    pub fn signature(&self) -> String {
+        format!("{}({})", self.name, self.args)
+    }
+}
+
+/// Finds a function by name, ignoring case.
+/// # Arguments
+/// * `name` - The name of the function.
+/// # Returns
+/// The function, or `None` if there is no function with that name.
+pub fn find(name: &str) -> Option<&'static FunctionInfo> {
+    FUNCTIONS.iter().find(|f| f.name.eq_ignore_ascii_case(name))
+}
+
+/// Searches the functions, ignoring case.
+/// # Arguments
+/// * `query` - Text to look for in the name or description. Empty text matches everything.
+/// # Returns
+/// The matching functions, those whose name matches first.
+pub fn search(query: &str) -> Vec<&'static FunctionInfo> {
+    let query = query.trim().to_ascii_lowercase();
+    let (mut by_name, by_description): (Vec<_>, Vec<_>) = FUNCTIONS
+        .iter()
+        .filter(|f| {
+            f.name.to_ascii_lowercase().contains(&query)
+                || f.description.to_ascii_lowercase().contains(&query)
+        })
+        .partition(|f| f.name.to_ascii_lowercase().contains(&query));
+    by_name.extend(by_description);
+    by_name
+}
+
+/// Finds the function name being typed at the end of a formula.
+/// # Arguments
+/// * `text` - The text typed so far.
+/// # Returns
+/// The letters typed of the name, if the text ends in letters following `=`, `(` or an
+/// operator.
+fn typed_name(text: &str) -> Option<&str> {
+    let start = text
+        .rfind(|c: char| !c.is_ascii_alphabetic() && c != '_')
+        .map_or(0, |i| i + 1);
+    let before = text[..start].chars().last()?;
+    let name = &text[start..];
+    (!name.is_empty() && "=(+-*/,".contains(before)).then_some(name)
+}
+
+/// Suggests functions for the name being typed at the end of a formula.
+/// # Arguments
+/// * `text` - The text typed so far.
+/// # Returns
+/// The functions whose name starts with the typed letters, ignoring case.
+pub fn completions(text: &str) -> Vec<&'static FunctionInfo> {
+    let Some(typed) = typed_name(text) else {
+        return Vec::new();
+    };
+    FUNCTIONS
+        .iter()
+        .filter(|f| f.name.len() > typed.len() && f.name[..typed.len()].eq_ignore_ascii_case(typed))
+        .collect()
+}
+
+/// Completes the function name being typed at the end of a formula.
+/// # Arguments
+/// * `text` - The text typed so far.
+/// * `function` - The function to complete to.
+/// # Returns
+/// The text with the typed letters replaced by the name and an opening parenthesis.
+pub fn complete(text: &str, function: &FunctionInfo) -> String {
+    let typed = typed_name(text).map_or(0, str::len);
+    format!("{}{}(", &text[..text.len() - typed], function.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_and_search() {
+        assert_eq!(find("sum").map(|f| f.name), Some("SUM"));
+        assert_eq!(find("sumif").map(|f| f.name), Some("SUMIF"));
+        assert_eq!(find("SUMPRODUCT"), None);
+        let names: Vec<&str> = search("st").iter().map(|f| f.name).collect();
+        assert_eq!(names[..2], ["CHITEST", "STDEV"]);
+        assert!(names.contains(&"TTEST"));
+        assert_eq!(search("").len(), FUNCTIONS.len());
+        assert!(FUNCTIONS.windows(2).all(|w| w[0].name < w[1].name));
+    }
+
+    #[test]
+    fn test_completions() {
+        let names = |text| -> Vec<&str> { completions(text).iter().map(|f| f.name).collect() };
+        assert_eq!(
+            names("A1=S"),
+            vec![
+                "SLEEP",
+                "SQRT",
+                "STDEV",
+                "STDEV_SKIPERR",
+                "SUM",
+                "SUMIF",
+                "SUM_SKIPERR"
+            ]
+        );
+        assert_eq!(names("A1=su"), vec!["SUM", "SUMIF", "SUM_SKIPERR"]);
+        assert_eq!(names("A1=MAX_"), vec!["MAX_SKIPERR"]);
+        assert_eq!(names("A1=B1+MI"), vec!["MIN", "MIN_SKIPERR"]);
+        assert!(names("A1=SLEEP").is_empty());
+        assert!(names("A1=B1").is_empty());
+        assert!(names("S").is_empty());
+        assert_eq!(complete("A1=B1+ma", find("MAX").unwrap()), "A1=B1+MAX(");
+    }
+}
@@ -0,0 +1,74 @@
+//! This module contains fuzzy search, which finds names from a few of their letters, as in
+//! the command palette of the GUI where "expdf" finds "Export PDF…".
+//!
+//! The letters of the query must appear in the name in order, ignoring case and spaces.
+//! Names where they start words or follow each other rank first.
+
+/// Scores how well a name matches a query.
+/// # Arguments
+/// * `query` - The letters typed by the user
+/// * `name` - The name to match
+/// # Returns
+/// The score, higher for better matches, or `None` if the letters of the query are not all
+/// in the name in order. An empty query matches every name with a score of 0.
+pub fn score(query: &str, name: &str) -> Option<u32> {
+    let name: Vec<char> = name.chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut last = None;
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let found = (next..name.len()).find(|&i| name[i].eq_ignore_ascii_case(&q))?;
+        score += 1;
+        if found == 0 || !name[found - 1].is_alphanumeric() {
+            score += 5;
+        }
+        if last.is_some_and(|l| l + 1 == found) {
+            score += 3;
+        }
+        last = Some(found);
+        next = found + 1;
+    }
+    Some(score)
+}
+
+/// Ranks names by how well they match a query.
+/// # Arguments
+/// * `query` - The letters typed by the user
+/// * `names` - The names to search
+/// # Returns
+/// The indices of the names that match, best first. Names that match equally well keep
+/// their order.
+pub fn rank(query: &str, names: &[&str]) -> Vec<usize> {
+    let mut scored: Vec<(usize, u32)> = names
+        .iter()
+        .enumerate()
+        .filter_map(|(i, name)| Some((i, score(query, name)?)))
+        .collect();
+    scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+    scored.into_iter().map(|(i, _)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        assert_eq!(score("", "Save…"), Some(0));
+        assert!(score("expdf", "Export PDF…").is_some());
+        assert!(score("EXPORT pdf", "Export PDF…").is_some());
+        assert_eq!(score("pdfx", "Export PDF…"), None);
+        assert_eq!(score("sv", "Settings…"), None);
+        // Letters starting words score more than letters inside them
+        assert!(score("gc", "Go to Cell").unwrap() > score("gc", "Paste Special…").unwrap_or(0));
+    }
+
+    #[test]
+    fn test_rank() {
+        let names = ["Save…", "Settings…", "Export PDF…", "Stats Tests…"];
+        assert_eq!(rank("", &names), [0, 1, 2, 3]);
+        assert_eq!(rank("st", &names), [3, 1]);
+        assert_eq!(rank("se", &names), [1, 0, 3]);
+        assert!(rank("zz", &names).is_empty());
+    }
+}
@@ -0,0 +1,637 @@
+//! This module contains functions for querying the dependency graph of cells.
+//! Precedents of a cell are read from its operation, dependents from the sensitivity list.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+
+/// Opcodes which operate on a range of cells.
+pub const RANGE_OPS: [&str; 16] = [
+    "SUM", "MIN", "MAX", "MEA", "STD", "SUE", "MIE", "MAE", "MEE", "STE", "SUI", "NUI", "MEI",
+    "NUM", "NUA", "NUB",
+];
+
+/// Range opcodes of the `_SKIPERR` functions, which leave out cells in an error state.
+pub const SKIP_ERR_OPS: [&str; 5] = ["SUE", "MIE", "MAE", "MEE", "STE"];
+
+/// Range opcodes of SUMIF, COUNTIF and AVERAGEIF, which only use the values meeting a criterion.
+pub const CONDITIONAL_OPS: [&str; 3] = ["SUI", "NUI", "MEI"];
+
+/// Converts a pair of range corners into column and row bounds.
+/// # Arguments
+/// * `c1` - The starting cell index (1-based).
+/// * `c2` - The ending cell index (1-based).
+/// * `n_cols` - The number of columns in the data array.
+/// # Returns
+/// A tuple `(x1, y1, x2, y2)` of the first/last column and first/last row of the range.
+pub fn range_bounds(c1: i32, c2: i32, n_cols: i32) -> (i32, i32, i32, i32) {
+    let mut x1 = c1 % n_cols;
+    if x1 == 0 {
+        x1 = n_cols;
+    }
+    let mut x2 = c2 % n_cols;
+    if x2 == 0 {
+        x2 = n_cols;
+    }
+    let y1 = c1 / n_cols + ((x1 != n_cols) as i32);
+    let y2 = c2 / n_cols + ((x2 != n_cols) as i32);
+    (x1, y1, x2, y2)
+}
+
+/// Finds all cells referenced by an operation.
+/// # Arguments
+/// * `op` - The operation stored for a cell.
+/// * `n_cols` - The number of columns in the data array.
+/// # Returns
+/// A vector with the indices of the cells the operation reads from, without duplicates.
+pub fn precedents(op: &Ops, n_cols: i32) -> Vec<i32> {
+    let mut res = Vec::new();
+    if RANGE_OPS.contains(&op.opcpde.as_str()) {
+        let (x1, y1, x2, y2) = range_bounds(op.cell1, op.cell2, n_cols);
+        for j in y1..=y2 {
+            for i in x1..=x2 {
+                res.push(i + (j - 1) * n_cols);
+            }
+        }
+        return res;
+    }
+    if let Some(expr) = &op.expr {
+        return expr.cells(n_cols);
+    }
+    if op.opcpde == "EQC" || op.opcpde == "SLC" || op.opcpde.starts_with('C') {
+        res.push(op.cell1);
+    }
+    if op.opcpde.chars().nth(1) == Some('C') && !res.contains(&op.cell2) {
+        res.push(op.cell2);
+    }
+    res
+}
+
+/// Builds the sensitivity list (dependents of each cell) from the operations alone.
+/// # Arguments
+/// * `opers` - The operations for each cell.
+/// * `n_cols` - The number of columns in the data array.
+/// # Returns
+/// For each cell, the cells whose operation reads it.
+pub fn sensitivity(opers: &Cells<Ops>, n_cols: i32) -> Cells<Vec<i32>> {
+    let mut sensi: Cells<Vec<i32>> = Cells::new(opers.len());
+    for (cell, op) in opers.iter().enumerate().skip(1) {
+        for p in precedents(op, n_cols) {
+            sensi[p as usize].push(cell as i32);
+        }
+    }
+    sensi
+}
+
+/// Finds all cells which directly depend on a cell.
+/// # Arguments
+/// * `sensi` - The sensitivity list of the spreadsheet.
+/// * `cell` - The cell index (1-based).
+/// # Returns
+/// A sorted vector with the indices of the dependent cells, without duplicates.
+pub fn dependents(sensi: &Cells<Vec<i32>>, cell: i32) -> Vec<i32> {
+    let mut res = sensi[cell as usize].clone();
+    res.sort();
+    res.dedup();
+    res
+}
+
+/// Finds all cells whose value may change when a cell is updated.
+/// # Arguments
+/// * `sensi` - The sensitivity list of the spreadsheet.
+/// * `cell` - The cell index (1-based).
+/// # Returns
+/// A sorted vector with the cell itself and all its direct and indirect dependents.
+pub fn affected(sensi: &Cells<Vec<i32>>, cell: i32) -> Vec<i32> {
+    let mut seen = vec![false; sensi.len()];
+    seen[cell as usize] = true;
+    let mut stack = vec![cell];
+    let mut res = Vec::new();
+    while let Some(c) = stack.pop() {
+        res.push(c);
+        for &d in &sensi[c as usize] {
+            if !seen[d as usize] {
+                seen[d as usize] = true;
+                stack.push(d);
+            }
+        }
+    }
+    res.sort();
+    res
+}
+
+/// Orders the cells recomputed when a cell changes into levels.
+///
+/// Level 0 is the cell itself, and every other cell is one level below the deepest cell it
+/// reads from, so the cells of a level only depend on earlier levels and could be computed
+/// at the same time. Read in order, the levels give a topological order of the update.
+/// # Arguments
+/// * `sensi` - The sensitivity list of the spreadsheet.
+/// * `cell` - The cell index (1-based).
+/// # Returns
+/// The cells of each level, sorted.
+pub fn recalc_plan(sensi: &Cells<Vec<i32>>, cell: i32) -> Vec<Vec<i32>> {
+    let cells = affected(sensi, cell);
+    let mut indegree = vec![0; sensi.len()];
+    for &c in &cells {
+        for d in dependents(sensi, c) {
+            indegree[d as usize] += 1;
+        }
+    }
+    let mut levels = Vec::new();
+    let mut level = vec![cell];
+    while !level.is_empty() {
+        let mut next = Vec::new();
+        for &c in &level {
+            for d in dependents(sensi, c) {
+                indegree[d as usize] -= 1;
+                if indegree[d as usize] == 0 {
+                    next.push(d);
+                }
+            }
+        }
+        next.sort();
+        levels.push(level);
+        level = next;
+    }
+    levels
+}
+
+/// Maximum number of cells listed for each level of a recalculation plan.
+pub const PLAN_LIMIT: usize = 10;
+
+/// Describes the recalculation plan of a cell as human readable lines.
+/// # Arguments
+/// * `levels` - The plan, as given by `recalc_plan`.
+/// * `n_cols` - The number of columns in the data array.
+/// # Returns
+/// A summary line, followed by the number of cells and the first cells of each level.
+pub fn plan_report(levels: &[Vec<i32>], n_cols: i32) -> Vec<String> {
+    let total: usize = levels.iter().map(Vec::len).sum();
+    let mut lines = vec![format!(
+        "{} cells in {} levels, widest level {}",
+        total,
+        levels.len(),
+        levels.iter().map(Vec::len).max().unwrap_or(0)
+    )];
+    for (i, level) in levels.iter().enumerate() {
+        let mut shown: Vec<String> = level
+            .iter()
+            .take(PLAN_LIMIT)
+            .map(|&c| crate::ind_to_cell(c, n_cols))
+            .collect();
+        if level.len() > PLAN_LIMIT {
+            shown.push(format!("... {} more", level.len() - PLAN_LIMIT));
+        }
+        lines.push(format!(
+            "Level {} ({}): {}",
+            i,
+            level.len(),
+            shown.join(", ")
+        ));
+    }
+    lines
+}
+
+/// Finds a shortest chain of dependencies from one cell to another.
+/// # Arguments
+/// * `sensi` - The sensitivity list of the spreadsheet.
+/// * `from` - The cell index (1-based) the chain starts at.
+/// * `to` - The cell index (1-based) the chain ends at.
+/// # Returns
+/// The cells of the chain from `from` to `to`, each depending directly on the one before, or
+/// `None` if `to` does not depend on `from`.
+pub fn dependency_path(sensi: &Cells<Vec<i32>>, from: i32, to: i32) -> Option<Vec<i32>> {
+    let mut parent = vec![-1; sensi.len()];
+    parent[from as usize] = from;
+    let mut queue = std::collections::VecDeque::from([from]);
+    while let Some(c) = queue.pop_front() {
+        if c == to {
+            let mut path = vec![to];
+            let mut c = to;
+            while c != from {
+                c = parent[c as usize];
+                path.push(c);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for &d in &sensi[c as usize] {
+            if parent[d as usize] == -1 {
+                parent[d as usize] = c;
+                queue.push_back(d);
+            }
+        }
+    }
+    None
+}
+
+/// Describes why a cell is in an error state.
+/// # Arguments
+/// * `cell` - The cell index (1-based).
+/// * `database` - A reference to the data array.
+/// * `opers` - The operations for each cell.
+/// * `err` - A reference to the error array.
+/// * `n_cols` - The number of columns in the data array.
+/// # Returns
+/// `None` if the cell is not in error, otherwise a short description of the cause.
+pub fn error_cause(
+    cell: i32,
+    database: &[i32],
+    opers: &Cells<Ops>,
+    err: &[bool],
+    n_cols: i32,
+) -> Option<String> {
+    if !err[cell as usize] {
+        return None;
+    }
+    let op = &opers[cell as usize];
+    if op.opcpde == crate::utils::structure::REF_OP {
+        return Some(String::from("Reference to a deleted cell (#REF!)"));
+    }
+    let code: Vec<char> = op.opcpde.chars().collect();
+    if code.len() == 3 && code[2] == 'D' && "CV".contains(code[0]) && "CV".contains(code[1]) {
+        let divisor = if code[1] == 'C' {
+            database[op.cell2 as usize]
+        } else {
+            op.cell2
+        };
+        if divisor == 0 {
+            return Some(String::from("Division by zero"));
+        }
+    }
+    for p in precedents(op, n_cols) {
+        if err[p as usize] {
+            return Some(format!(
+                "Depends on {} which is in error",
+                crate::ind_to_cell(p, n_cols)
+            ));
+        }
+    }
+    if code.len() == 3 && "ASMD".contains(code[2]) && "CV".contains(code[0]) {
+        return Some(String::from("Arithmetic overflow"));
+    }
+    if op.opcpde == crate::utils::expr::EXP_OP {
+        return Some(String::from(
+            "Division by zero, arithmetic overflow or lookup without a match",
+        ));
+    }
+    if op.opcpde == "MEI" {
+        return Some(String::from("No value in the range meets the criterion"));
+    }
+    Some(String::from("Unknown error"))
+}
+
+/// Maximum number of lines in an evaluation trace, so traces over large ranges stay readable.
+pub const TRACE_LIMIT: usize = 500;
+
+/// Describes how the value of a cell was computed.
+///
+/// Each line shows a cell, its value and its formula, followed by the lines of the cells the
+/// formula reads from, indented one level deeper. Cells already shown are not expanded again.
+/// Cells where an error originates are marked with its cause.
+/// # Arguments
+/// * `cell` - The cell index (1-based).
+/// * `database` - A reference to the data array.
+/// * `opers` - The operations for each cell.
+/// * `err` - A reference to the error array.
+/// * `n_cols` - The number of columns in the data array.
+/// # Returns
+/// The lines of the trace, at most `TRACE_LIMIT` plus one saying how many were left out.
+pub fn trace(
+    cell: i32,
+    database: &[i32],
+    opers: &Cells<Ops>,
+    err: &[bool],
+    n_cols: i32,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut seen = vec![false; opers.len()];
+    let mut omitted = 0;
+    let mut stack = vec![(cell, 0)];
+    while let Some((c, depth)) = stack.pop() {
+        if lines.len() == TRACE_LIMIT {
+            omitted += 1;
+            continue;
+        }
+        let op = &opers[c as usize];
+        let mut line = format!(
+            "{}{} = {}",
+            "  ".repeat(depth),
+            crate::ind_to_cell(c, n_cols),
+            if err[c as usize] {
+                String::from("ERR")
+            } else {
+                database[c as usize].to_string()
+            }
+        );
+        if seen[c as usize] {
+            line.push_str("  (see above)");
+            lines.push(line);
+            continue;
+        }
+        seen[c as usize] = true;
+        if !matches!(op.opcpde.as_str(), "" | "EQV") {
+            line.push_str(&format!(
+                "  [={}]",
+                crate::utils::input::formula_text(op, n_cols)
+            ));
+        }
+        if let Some(cause) = error_cause(c, database, opers, err, n_cols)
+            && !cause.starts_with("Depends on")
+        {
+            line.push_str(&format!("  <- error originates here: {}", cause));
+        }
+        lines.push(line);
+        for p in precedents(op, n_cols).into_iter().rev() {
+            stack.push((p, depth + 1));
+        }
+    }
+    if omitted > 0 {
+        lines.push(format!("... {} more lines", omitted));
+    }
+    lines
+}
+
+/// Summary statistics of the dependency graph.
+///
+/// # Fields
+/// * `formula_cells` - Number of cells whose operation references other cells
+/// * `edges` - Number of distinct dependency edges
+/// * `max_depth` - Length of the longest dependency chain
+/// * `max_fan_out` - Cell with the most direct dependents and the number of dependents
+/// * `largest_cluster` - Number of cells in the largest connected group of dependent cells
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GraphStats {
+    pub formula_cells: i32,
+    pub edges: i32,
+    pub max_depth: i32,
+    pub max_fan_out: (i32, i32),
+    pub largest_cluster: i32,
+}
+
+impl GraphStats {
+    /// Formats the statistics as human readable lines.
+    /// # Arguments
+    /// * `n_cols` - The number of columns in the data array.
+    /// # Returns
+    /// One line per statistic.
+    pub fn report(&self, n_cols: i32) -> Vec<String> {
+        let fan_out = if self.max_fan_out.1 > 0 {
+            format!(
+                "{} ({} dependents)",
+                crate::ind_to_cell(self.max_fan_out.0, n_cols),
+                self.max_fan_out.1
+            )
+        } else {
+            String::from("-")
+        };
+        vec![
+            format!("Formula cells: {}", self.formula_cells),
+            format!("Dependency edges: {}", self.edges),
+            format!("Max chain depth: {}", self.max_depth),
+            format!("Largest fan-out: {}", fan_out),
+            format!("Largest cluster: {} cells", self.largest_cluster),
+        ]
+    }
+}
+
+/// Finds the root of a cell in a union-find forest, compressing the path on the way.
+fn find(parent: &mut [usize], x: usize) -> usize {
+    let mut root = x;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    let mut cur = x;
+    while parent[cur] != root {
+        let next = parent[cur];
+        parent[cur] = root;
+        cur = next;
+    }
+    root
+}
+
+/// Computes summary statistics of the dependency graph.
+/// # Arguments
+/// * `opers` - The operations for each cell.
+/// * `sensi` - The sensitivity list of the spreadsheet.
+/// * `n_cols` - The number of columns in the data array.
+/// # Returns
+/// A `GraphStats` describing the graph.
+pub fn graph_stats(opers: &Cells<Ops>, sensi: &Cells<Vec<i32>>, n_cols: i32) -> GraphStats {
+    let n = sensi.len();
+    let mut stats = GraphStats::default();
+    let mut indegree = vec![0; n];
+    let mut parent: Vec<usize> = (0..n).collect();
+    let mut size = vec![1; n];
+
+    for (cell, op) in opers.iter().enumerate().take(n).skip(1) {
+        if !precedents(op, n_cols).is_empty() {
+            stats.formula_cells += 1;
+        }
+        let deps = dependents(sensi, cell as i32);
+        if deps.len() as i32 > stats.max_fan_out.1 {
+            stats.max_fan_out = (cell as i32, deps.len() as i32);
+        }
+        for d in deps {
+            stats.edges += 1;
+            indegree[d as usize] += 1;
+            let (a, b) = (find(&mut parent, cell), find(&mut parent, d as usize));
+            if a != b {
+                parent[a] = b;
+                size[b] += size[a];
+            }
+        }
+    }
+
+    for (cell, &cluster) in size.iter().enumerate().skip(1) {
+        if find(&mut parent, cell) == cell && cluster > 1 {
+            stats.largest_cluster = stats.largest_cluster.max(cluster);
+        }
+    }
+
+    // Longest chain using Kahn's algorithm over the whole graph
+    let mut depth = vec![0; n];
+    let mut q: std::collections::VecDeque<usize> = (1..n).filter(|&c| indegree[c] == 0).collect();
+    while let Some(node) = q.pop_front() {
+        for d in dependents(sensi, node as i32) {
+            let d = d as usize;
+            depth[d] = depth[d].max(depth[node] + 1);
+            indegree[d] -= 1;
+            if indegree[d] == 0 {
+                q.push_back(d);
+            }
+        }
+    }
+    stats.max_depth = depth.into_iter().max().unwrap_or(0);
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(code: &str, cell1: i32, cell2: i32) -> Ops {
+        Ops {
+            opcpde: String::from(code),
+            cell1,
+            cell2,
+            expr: None,
+            criterion: None,
+        }
+    }
+
+    #[test]
+    fn test_range_bounds() {
+        // B1:C2 in a 3 column grid
+        assert_eq!(range_bounds(2, 6, 3), (2, 1, 3, 2));
+        assert_eq!(range_bounds(3, 3, 3), (3, 1, 3, 1));
+    }
+
+    #[test]
+    fn test_precedents() {
+        assert_eq!(precedents(&op("CCA", 1, 2), 3), vec![1, 2]);
+        assert_eq!(precedents(&op("CCA", 1, 1), 3), vec![1]);
+        assert_eq!(precedents(&op("VCM", 5, 2), 3), vec![2]);
+        assert_eq!(precedents(&op("CVD", 4, 0), 3), vec![4]);
+        assert_eq!(precedents(&op("EQC", 4, -1), 3), vec![4]);
+        assert_eq!(precedents(&op("SLC", 4, -1), 3), vec![4]);
+        assert!(precedents(&op("EQV", 4, -1), 3).is_empty());
+        assert!(precedents(&op("SLV", 4, -1), 3).is_empty());
+        assert_eq!(precedents(&op("SUM", 2, 6), 3), vec![2, 3, 5, 6]);
+    }
+
+    #[test]
+    fn test_dependents() {
+        let sensi = Cells::from(vec![vec![], vec![3, 2, 3], vec![]]);
+        assert_eq!(dependents(&sensi, 1), vec![2, 3]);
+        assert!(dependents(&sensi, 2).is_empty());
+    }
+
+    #[test]
+    fn test_affected() {
+        // 1 -> 2 -> 3, 1 -> 3, 4 unrelated
+        let sensi = Cells::from(vec![vec![], vec![2, 3], vec![3], vec![], vec![]]);
+        assert_eq!(affected(&sensi, 1), vec![1, 2, 3]);
+        assert_eq!(affected(&sensi, 2), vec![2, 3]);
+        assert_eq!(affected(&sensi, 4), vec![4]);
+    }
+
+    #[test]
+    fn test_dependency_path() {
+        // 1 -> 2 -> 3 and 1 -> 3 -> 4
+        let sensi = Cells::from(vec![vec![], vec![2, 3], vec![3], vec![4], vec![]]);
+        assert_eq!(dependency_path(&sensi, 1, 4), Some(vec![1, 3, 4]));
+        assert_eq!(dependency_path(&sensi, 2, 3), Some(vec![2, 3]));
+        assert_eq!(dependency_path(&sensi, 2, 2), Some(vec![2]));
+        assert_eq!(dependency_path(&sensi, 4, 1), None);
+    }
+
+    #[test]
+    fn test_error_cause() {
+        let database = vec![0, 0, 5, 0];
+        let err = vec![false, false, true, true];
+        let opers = Cells::from(vec![
+            op("", -1, -1),
+            op("EQV", 0, -1),
+            op("VCD", 5, 1),
+            op("CVA", 2, 1),
+        ]);
+        assert_eq!(error_cause(1, &database, &opers, &err, 3), None);
+        assert_eq!(
+            error_cause(2, &database, &opers, &err, 3),
+            Some(String::from("Division by zero"))
+        );
+        assert_eq!(
+            error_cause(3, &database, &opers, &err, 3),
+            Some(String::from("Depends on B1 which is in error"))
+        );
+    }
+
+    #[test]
+    fn test_trace() {
+        // A1=5, B1=1/0, C1=A1+B1, D1=SUM(A1:C1) in a 4 column grid
+        let database = vec![0, 5, 0, 0, 0];
+        let err = vec![false, false, true, true, true];
+        let opers = Cells::from(vec![
+            op("", -1, -1),
+            op("EQV", 5, -1),
+            op("VVD", 1, 0),
+            op("CCA", 1, 2),
+            op("SUM", 1, 3),
+        ]);
+        assert_eq!(
+            trace(4, &database, &opers, &err, 4),
+            vec![
+                "D1 = ERR  [=SUM(A1:C1)]",
+                "  A1 = 5",
+                "  B1 = ERR  [=1/0]  <- error originates here: Division by zero",
+                "  C1 = ERR  [=A1+B1]",
+                "    A1 = 5  (see above)",
+                "    B1 = ERR  (see above)",
+            ]
+        );
+        assert_eq!(trace(1, &database, &opers, &err, 4), vec!["A1 = 5"]);
+    }
+
+    #[test]
+    fn test_graph_stats() {
+        // A1=5, B1=A1+1, C1=B1*2, D1=SUM(A1:C1), E1 unrelated
+        let opers = Cells::from(vec![
+            op("", -1, -1),
+            op("EQV", 5, -1),
+            op("CVA", 1, 1),
+            op("CVM", 2, 2),
+            op("SUM", 1, 3),
+            op("EQV", 1, -1),
+        ]);
+        let sensi = Cells::from(vec![
+            vec![],
+            vec![2, 4],
+            vec![3, 4],
+            vec![4],
+            vec![],
+            vec![],
+        ]);
+        let stats = graph_stats(&opers, &sensi, 5);
+        assert_eq!(stats.formula_cells, 3);
+        assert_eq!(stats.edges, 5);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.max_fan_out, (1, 2));
+        assert_eq!(stats.largest_cluster, 4);
+    }
+
+    #[test]
+    fn test_recalc_plan() {
+        // B1=A1+1, C1=B1*2, D1=SUM(A1:C1), E1=A1
+        let sensi = Cells::from(vec![
+            vec![],
+            vec![2, 4, 5],
+            vec![3, 4],
+            vec![4],
+            vec![],
+            vec![],
+        ]);
+        let levels = recalc_plan(&sensi, 1);
+        assert_eq!(levels, vec![vec![1], vec![2, 5], vec![3], vec![4]]);
+        assert_eq!(recalc_plan(&sensi, 3), vec![vec![3], vec![4]]);
+        assert_eq!(
+            plan_report(&levels, 5),
+            vec![
+                "5 cells in 4 levels, widest level 2",
+                "Level 0 (1): A1",
+                "Level 1 (2): B1, E1",
+                "Level 2 (1): C1",
+                "Level 3 (1): D1",
+            ]
+        );
+
+        // Only the first cells of a wide level are listed
+        let mut sensi = Cells::from(vec![vec![]; 14]);
+        sensi[1] = (2..=13).collect();
+        let report = plan_report(&recalc_plan(&sensi, 1), 20);
+        assert_eq!(
+            report[2],
+            "Level 1 (12): B1, C1, D1, E1, F1, G1, H1, I1, J1, K1, ... 2 more"
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! This module contains the command history of a terminal session and its timing statistics.
+//!
+//! Every command run at the prompt is recorded with the time it took and whether it changed
+//! the spreadsheet. The `stats` command summarizes the history.
+
+use std::time::Duration;
+
+/// A command run at the prompt.
+///
+/// # Fields
+/// * `command` - The command as typed
+/// * `elapsed` - The time the command took
+/// * `edit` - Whether the command changed the spreadsheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub command: String,
+    pub elapsed: Duration,
+    pub edit: bool,
+}
+
+/// The commands run in a session, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub entries: Vec<Entry>,
+}
+
+impl History {
+    /// Records a command.
+    /// # Arguments
+    /// * `command` - The command as typed.
+    /// * `elapsed` - The time the command took.
+    /// * `edit` - Whether the command changed the spreadsheet.
+    pub fn record(&mut self, command: &str, elapsed: Duration, edit: bool) {
+        self.entries.push(Entry {
+            command: command.to_string(),
+            elapsed,
+            edit,
+        });
+    }
+
+    /// Gives the time taken by the edits, sorted from fastest to slowest.
+    fn update_latencies(&self) -> Vec<Duration> {
+        let mut latencies: Vec<Duration> = self
+            .entries
+            .iter()
+            .filter(|e| e.edit)
+            .map(|e| e.elapsed)
+            .collect();
+        latencies.sort();
+        latencies
+    }
+
+    /// Formats a summary of the session as human readable lines.
+    /// # Returns
+    /// One line per statistic.
+    pub fn report(&self) -> Vec<String> {
+        let latencies = self.update_latencies();
+        let latency = if latencies.is_empty() {
+            String::from("-")
+        } else {
+            let total: Duration = latencies.iter().sum();
+            format!(
+                "avg {}, p50 {}, p95 {}, max {}",
+                millis(total / latencies.len() as u32),
+                millis(percentile(&latencies, 0.5)),
+                millis(percentile(&latencies, 0.95)),
+                millis(latencies[latencies.len() - 1])
+            )
+        };
+        let slowest = match self.entries.iter().max_by_key(|e| e.elapsed) {
+            Some(e) => format!("{} ({})", e.command, millis(e.elapsed)),
+            None => String::from("-"),
+        };
+        vec![
+            format!("Commands: {}", self.entries.len()),
+            format!("Edits: {}", latencies.len()),
+            format!("Update latency: {}", latency),
+            format!("Slowest command: {}", slowest),
+        ]
+    }
+}
+
+/// Gives a percentile of sorted durations (nearest-rank method).
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Formats a duration in milliseconds.
+fn millis(d: Duration) -> String {
+    format!("{:.1}ms", d.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut history = History::default();
+        assert_eq!(
+            history.report(),
+            vec![
+                "Commands: 0",
+                "Edits: 0",
+                "Update latency: -",
+                "Slowest command: -"
+            ]
+        );
+
+        for ms in [4, 1, 3, 2] {
+            history.record(&format!("A1={}", ms), Duration::from_millis(ms), true);
+        }
+        history.record("w", Duration::from_millis(10), false);
+        assert_eq!(
+            history.report(),
+            vec![
+                "Commands: 5",
+                "Edits: 4",
+                "Update latency: avg 2.5ms, p50 2.0ms, p95 4.0ms, max 4.0ms",
+                "Slowest command: w (10.0ms)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_percentile() {
+        let sorted: Vec<Duration> = (1..=20).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&sorted, 0.5), Duration::from_millis(10));
+        assert_eq!(percentile(&sorted, 0.95), Duration::from_millis(19));
+        assert_eq!(percentile(&sorted, 0.0), Duration::from_millis(1));
+        assert_eq!(percentile(&sorted, 1.0), Duration::from_millis(20));
+    }
+}
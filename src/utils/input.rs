@@ -1,5 +1,83 @@
 //! This module contains functions for parsing input and checking if input is valid.
 use crate::cell_to_int;
+use crate::utils::error::SheetError;
+use crate::utils::expr::{EXP_OP, Expr};
+
+/// A formula as parsed, before its cells are resolved into an `Ops`.
+///
+/// # Fields
+/// * `op` - The opcode (see `help_input`), such as "CVA" or "SUM"
+/// * `arg1` - The first operand: a cell, a value, or the text of an expression
+/// * `arg2` - The second operand, which may be empty
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Formula {
+    pub op: String,
+    pub arg1: String,
+    pub arg2: String,
+}
+
+/// An assignment of a formula to a cell, such as "A1=B1+3".
+///
+/// # Fields
+/// * `target` - The assigned cell, such as "A1"
+/// * `expr` - The formula
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    pub target: String,
+    pub expr: Formula,
+}
+
+impl Assignment {
+    /// Creates an assignment from its parsed components.
+    /// # Arguments
+    /// * `target` - The assigned cell
+    /// * `op` - The opcode
+    /// * `arg1` - The first operand
+    /// * `arg2` - The second operand, which may be empty
+    pub fn new(target: &str, op: &str, arg1: &str, arg2: &str) -> Self {
+        Self {
+            target: target.to_string(),
+            expr: Formula {
+                op: op.to_string(),
+                arg1: arg1.to_string(),
+                arg2: arg2.to_string(),
+            },
+        }
+    }
+
+    /// Creates an assignment that empties a cell, leaving it as if it was never assigned.
+    /// # Arguments
+    /// * `target` - The emptied cell
+    pub fn empty(target: &str) -> Self {
+        Self::new(target, "", "-1", "-1")
+    }
+
+    /// Checks whether the assignment empties its cell (see `empty`).
+    pub fn empties(&self) -> bool {
+        self.expr.op.is_empty()
+    }
+}
+
+/// A command typed by the user, as parsed by `input`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsedCommand {
+    /// `<cell>=<formula>`, which assigns a formula to a cell.
+    Assign(Assignment),
+    /// `scroll_to <cell>`, which moves the view so that the cell is at its top left.
+    ScrollTo { cell: String },
+}
+
+impl ParsedCommand {
+    /// Gives the assignment of an assignment command.
+    /// # Returns
+    /// The assignment, or `SheetError::InvalidOperation` for a command that assigns no cell.
+    pub fn into_assignment(self) -> Result<Assignment, SheetError> {
+        match self {
+            ParsedCommand::Assign(assignment) => Ok(assignment),
+            ParsedCommand::ScrollTo { .. } => Err(SheetError::InvalidOperation),
+        }
+    }
+}
 
 /// Checks if the input is of arithmetic type.
 ///
@@ -17,6 +95,29 @@ fn is_arth(input: &str) -> bool {
     true
 }
 
+/// Checks if the right-hand side of an assignment is a single function call.
+///
+/// # Arguments
+/// * `rhs` - A string slice containing the text after '='
+///
+/// # Returns
+/// * `bool` - true for text such as "SUM(A1:B5)", false for expressions such as "SUM(A1:B5)*2"
+///   and for the functions that are part of the expression syntax, such as IF, or custom
+fn is_call(rhs: &str) -> bool {
+    let rhs = rhs.trim();
+    let Some((name, args)) = rhs.split_once('(') else {
+        return false;
+    };
+    !name.is_empty()
+        && !crate::utils::expr::EXPR_FUNCTIONS
+            .iter()
+            .any(|f| name.eq_ignore_ascii_case(f))
+        && crate::utils::custom::find(name).is_none()
+        && name.chars().all(|c| c.is_ascii_alphabetic() || c == '_')
+        && args.ends_with(')')
+        && !args[..args.len() - 1].contains(['(', ')'])
+}
+
 /// Checks if the input is a scroll operation.
 ///
 /// # Arguments
@@ -42,6 +143,9 @@ fn is_scroll(input: &str) -> bool {
 /// # Returns
 /// * `bool` - true if input is an integer value, false otherwise
 fn is_integer(input: &str) -> bool {
+    if !input.chars().any(|c| c.is_ascii_digit()) {
+        return false;
+    }
     let mut first = 1;
     for c in input.chars() {
         if first == 1 {
@@ -58,50 +162,32 @@ fn is_integer(input: &str) -> bool {
     true
 }
 
+/// Parses a column label such as "C" or "AB".
+/// # Arguments
+/// * `label` - The column label.
+/// * `len_h` - Number of columns in the spreadsheet.
+/// # Returns
+/// The column number, or `None` if the label is not a column of the spreadsheet.
+pub fn parse_col(label: &str, len_h: i32) -> Option<i32> {
+    let cell = format!("{}1", label);
+    if is_valid_cell(&cell, len_h, 1) {
+        Some(crate::cell_to_int(&cell) / 1000)
+    } else {
+        None
+    }
+}
+
 /// Validates if a cell reference is within bounds.
 ///
 /// # Arguments
-/// * `cell` - A string slice containing the cell reference (e.g., "A1")
+/// * `cell` - A string slice containing the cell reference in either style (e.g., "A1", "a1" or "R1C1")
 /// * `len_h` - An i32 representing the horizontal boundary (columns)
 /// * `len_v` - An i32 representing the vertical boundary (rows)
 ///
 /// # Returns
 /// * `bool` - true if the cell is valid and within bounds, false otherwise
 pub fn is_valid_cell(cell: &str, len_h: i32, len_v: i32) -> bool {
-    // input no of rows,no of cols
-    let n = cell.len();
-    if n < 2 {
-        return false;
-    }
-    let mut first = 1;
-    let mut state = 0;
-    for i in cell.chars() {
-        if first == 1 {
-            first = 0;
-            if !i.is_ascii_uppercase() {
-                return false;
-            }
-            continue;
-        }
-
-        if state == 0 {
-            if !i.is_ascii_uppercase() {
-                state = 1;
-            }
-        } else if !i.is_ascii_digit() {
-            return false;
-        }
-    }
-    if state == 0 {
-        return false;
-    }
-    let k = cell_to_int(cell);
-    let r = k % 1000;
-    let c = k / 1000;
-    if r <= len_v && c <= len_h && r > 0 && c > 0 {
-        return true;
-    }
-    false
+    crate::utils::cellref::CellRef::parse(cell).is_some_and(|c| c.col <= len_h && c.row <= len_v)
 }
 
 /// Validates if a cell range is valid and within bounds.
@@ -129,6 +215,29 @@ fn is_valid_range(cell1: &str, cell2: &str, len_h: i32, len_v: i32) -> bool {
         && (r2 > 0 && c2 > 0)
 }
 
+/// Parses a range such as "A1:C4".
+///
+/// # Arguments
+/// * `range` - A string slice containing the range
+/// * `len_h` - An i32 representing the horizontal boundary (columns)
+/// * `len_v` - An i32 representing the vertical boundary (rows)
+///
+/// # Returns
+/// * `Option<(i32, i32, i32, i32)>` - First column, first row, last column and last row, or
+///   `None` if the range is invalid
+pub fn parse_range(range: &str, len_h: i32, len_v: i32) -> Option<(i32, i32, i32, i32)> {
+    let (c1, c2) = range.split_once(':')?;
+    let (c1, c2) = (c1.trim(), c2.trim());
+    if !is_valid_cell(c1, len_h, len_v)
+        || !is_valid_cell(c2, len_h, len_v)
+        || !is_valid_range(c1, c2, len_h, len_v)
+    {
+        return None;
+    }
+    let (k1, k2) = (cell_to_int(c1), cell_to_int(c2));
+    Some((k1 / 1000, k1 % 1000, k2 / 1000, k2 % 1000))
+}
+
 /// Checks for errors in the parsed input based on operation type and cell references.
 ///
 /// # Arguments
@@ -138,73 +247,76 @@ fn is_valid_range(cell1: &str, cell2: &str, len_h: i32, len_v: i32) -> bool {
 /// * `len_v` - An i32 representing the vertical boundary (rows)
 ///
 /// # Returns
-/// * `String` - "ok" if no errors, otherwise a relevant error message
-fn check_err(input: &str, output: &[String], len_h: i32, len_v: i32) -> String {
-    let mut message = String::from("ok");
-    let vec1 = ["MEA", "STD", "SUM", "MIN", "MAX"];
+/// * `Result<(), SheetError>` - `Ok(())` if no errors, otherwise the relevant error
+fn check_err(input: &str, output: &[String], len_h: i32, len_v: i32) -> Result<(), SheetError> {
+    let vec1 = crate::utils::graph::RANGE_OPS;
     let vec2 = [
         "VVA", "CVA", "VCA", "CCA", "VVS", "CVS", "VCS", "CCS", "VVM", "CVM", "VCM", "CCM", "VVD",
         "CVD", "VCD", "CCD",
     ];
     if output[1].len() != 3 {
-        message = String::from("Invalid Operation");
-        return message;
+        return Err(SheetError::InvalidOperation);
     }
     if output[1] == "SRL" {
-        let mut temp = String::new();
-        for i in input.chars() {
-            if i == ' ' {
-                break;
-            }
-            temp.push(i);
-        }
-        if temp != "scroll_to" {
-            message = String::from("Invalid Operation");
+        let command = input.split(' ').next().unwrap_or_default();
+        if command != "scroll_to" {
+            return Err(SheetError::InvalidOperation);
         } else if !is_valid_cell(&output[0], len_h, len_v) {
-            message = String::from("Scroll Cell out of bounds");
+            return Err(SheetError::ScrollCellOutOfBounds);
         }
-    } else {
-        if !is_valid_cell(&output[0], len_h, len_v) {
-            message = String::from("Assigned Cell out of bounds");
-            return message;
-        }
-
-        if output[1] == "SLC" || output[1] == "EQC" {
-            if !is_valid_cell(&output[2], len_h, len_v) {
-                message = String::from("Invalid Cell");
-                return message;
-            }
-        } else if output[1] == "SLV" || output[1] == "EQV" {
-            return message;
-        } else if vec1.contains(&(output[1].as_str())) {
-            if !is_valid_range(&output[2], &output[3], len_h, len_v) {
-                message = String::from("Invalid Range");
-                return message;
-            }
-            return message;
-        } else if vec2.contains(&(output[1].as_str())) {
-            let f = output[1].chars().next().unwrap();
-            let s = output[1].chars().nth(1).unwrap();
-            if f == 'C' && !is_valid_cell(&output[2], len_h, len_v) {
-                message = String::from("Invalid Cell");
-                return message;
-            }
+        return Ok(());
+    }
+    if !is_valid_cell(&output[0], len_h, len_v) {
+        return Err(SheetError::AssignedCellOutOfBounds);
+    }
 
-            if s == 'C' {
-                if !is_valid_cell(&output[3], len_h, len_v) {
-                    message = String::from("Invalid Cell");
-                    return message;
-                }
-                return message;
-            } else {
-                return message;
-            }
+    if output[1] == "SLC" || output[1] == "EQC" {
+        if !is_valid_cell(&output[2], len_h, len_v) {
+            return Err(SheetError::InvalidCell);
+        }
+        Ok(())
+    } else if output[1] == crate::utils::structure::REF_OP {
+        Ok(())
+    } else if output[1] == EXP_OP {
+        Expr::parse(&output[2])?.check(len_h, len_v)
+    } else if output[1] == "SLV" || output[1] == "EQV" {
+        if output[2].parse::<i32>().is_err() {
+            return Err(SheetError::InvalidValue);
+        }
+        Ok(())
+    } else if crate::utils::graph::CONDITIONAL_OPS.contains(&output[1].as_str()) {
+        let (c1, c2, _) = crate::utils::operations::conditional_args(&output[2])?;
+        if is_valid_cell(c1, len_h, len_v)
+            && is_valid_cell(c2, len_h, len_v)
+            && is_valid_range(c1, c2, len_h, len_v)
+        {
+            Ok(())
         } else {
-            message = String::from("Invalid Operation");
-            return message;
+            Err(SheetError::InvalidRange)
+        }
+    } else if vec1.contains(&(output[1].as_str())) {
+        if !is_valid_range(&output[2], &output[3], len_h, len_v) {
+            return Err(SheetError::InvalidRange);
+        }
+        Ok(())
+    } else if vec2.contains(&(output[1].as_str())) {
+        let f = output[1].chars().next().unwrap();
+        let s = output[1].chars().nth(1).unwrap();
+        if f == 'C' && !is_valid_cell(&output[2], len_h, len_v) {
+            return Err(SheetError::InvalidCell);
         }
+        if (f == 'V' && output[2].parse::<i32>().is_err())
+            || (s == 'V' && output[3].parse::<i32>().is_err())
+        {
+            return Err(SheetError::InvalidValue);
+        }
+        if s == 'C' && !is_valid_cell(&output[3], len_h, len_v) {
+            return Err(SheetError::InvalidCell);
+        }
+        Ok(())
+    } else {
+        Err(SheetError::InvalidOperation)
     }
-    message
 }
 
 /// Parses input into components without validation.
@@ -237,8 +349,21 @@ fn check_err(input: &str, output: &[String], len_h: i32, len_v: i32) -> String {
 /// - "SUM": Sum function
 /// - "MIN": Minimum value function
 /// - "MAX": Maximum value function
+/// - "SUE", "MIE", "MAE", "MEE", "STE": The same functions with the `_SKIPERR` suffix, which
+///   leave out cells in an error state (e.g., A1=SUM_SKIPERR(B1:B5))
+/// - "SUI", "NUI", "MEI": SUMIF, COUNTIF and AVERAGEIF, which only use the values meeting a
+///   criterion (e.g., A1=SUMIF(B1:B5, ">5")). The arguments text is kept in `output[2]`.
+/// - "NUM", "NUA", "NUB": COUNT, COUNTA and COUNTBLANK, which count the cells holding a value,
+///   the cells that are not empty and the empty cells (e.g., A1=COUNTBLANK(B1:B5))
+///
+/// ## Expressions
+/// - "EXP": Any other arithmetic, with parentheses, more than one operator, comparisons,
+///   IF, lookups or math functions (e.g., A1=(B1+C1)*2-D1/3, A1=IF(B1>10, C1, D1),
+///   A1=VLOOKUP(B1, C1:E20, 2), A1=POW(B1, 3), A1=DATE(2024, 5, 1)). The expression text is
+///   kept in `output[2]`.
 ///
 /// ## Special Operations
+/// - "REF": Formula whose reference was deleted (e.g., A1=#REF!), always in error
 /// - "SRL": Scroll to a specific cell
 /// - "SLV": Sleep for a value (time in ms)
 /// - "SLC": Sleep for a cell value (time in ms)
@@ -277,76 +402,48 @@ pub fn help_input(input: &str) -> Vec<String> {
         i += 1;
     }
 
-    if is_arth(input) {
-        i += 1;
-        while i < n && input_arr[i] == ' ' {
-            i += 1;
-        }
-        output[2].push(input_arr[i]);
-        i += 1;
-        let mut oper;
-        if i == n {
-            output[1].push('E');
-            output[1].push('Q');
-            if is_integer(&output[2]) {
-                output[1].push('V');
-            } else {
-                output[1].push('C');
-            }
+    // A formula whose reference was deleted
+    if i < n
+        && input_arr[i + 1..].iter().collect::<String>().trim() == crate::utils::structure::REF_TEXT
+    {
+        output[1] = String::from(crate::utils::structure::REF_OP);
+        return output;
+    }
+
+    let rhs: String = input_arr[(i + 1).min(n)..].iter().collect();
+    if is_arth(input) || !is_call(&rhs) {
+        if rhs.trim().is_empty() {
             return output;
         }
-        while i < n
-            && (input_arr[i] != '*'
-                && input_arr[i] != '/'
-                && input_arr[i] != '+'
-                && input_arr[i] != '-')
+        // Formulas with at most one operator keep their opcodes, anything longer is an expression
+        let operand = |e: &Expr| match e {
+            Expr::Num(n) => Some(('V', n.to_string())),
+            Expr::Cell(cell) => Some(('C', cell.to_a1())),
+            _ => None,
+        };
+        let parsed = Expr::parse(&rhs);
+        if let Ok(e) = &parsed
+            && let Some((kind, text)) = operand(e)
         {
-            output[2].push(input_arr[i]);
-            i += 1;
-            if i == n {
-                output[1].push('E');
-                output[1].push('Q');
-                if is_integer(&output[2]) {
-                    output[1].push('V');
-                } else {
-                    output[1].push('C');
-                }
-                return output;
-            }
-        }
-
-        oper = input_arr[i];
-        if oper == '+' {
-            oper = 'A';
-        } else if oper == '-' {
-            oper = 'S';
-        } else if oper == '*' {
-            oper = 'M';
-        } else if oper == '/' {
-            oper = 'D';
-        }
-        i += 1;
-        while input_arr[i] == ' ' {
-            i += 1;
-        }
-        while i < n {
-            output[3].push(input_arr[i]);
-            i += 1;
-        }
-
-        if is_integer(&output[2]) {
-            output[1].push('V');
-        } else {
-            output[1].push('C');
-        }
-
-        if is_integer(&output[3]) {
-            output[1].push('V');
+            output[1] = format!("EQ{}", kind);
+            output[2] = text;
+        } else if let Ok(Expr::Bin(op, left, right)) = &parsed
+            && let (Some((k1, t1)), Some((k2, t2))) = (operand(left), operand(right))
+        {
+            let oper = match op {
+                '+' => 'A',
+                '-' => 'S',
+                '*' => 'M',
+                _ => 'D',
+            };
+            output[1] = format!("{}{}{}", k1, k2, oper);
+            output[2] = t1;
+            output[3] = t2;
         } else {
-            output[1].push('C');
+            // Invalid expressions are reported by `check_err`
+            output[1] = String::from(EXP_OP);
+            output[2] = rhs.trim().to_string();
         }
-
-        output[1].push(oper);
     } else {
         i += 1;
         while i < n && input_arr[i] == ' ' {
@@ -357,12 +454,25 @@ pub fn help_input(input: &str) -> Vec<String> {
             i += 1;
         }
         i += 1;
+        let conditional = match output[1].as_str() {
+            "SUMIF" => Some("SUI"),
+            "COUNTIF" => Some("NUI"),
+            "AVERAGEIF" => Some("MEI"),
+            _ => None,
+        };
         if output[1] == *"SLEEP" {
             output[1] = String::from("SL");
             while i < n && input_arr[i] != ')' {
                 output[2].push(input_arr[i]);
                 i += 1;
             }
+        } else if let Some(code) = conditional {
+            // The range and the criterion are split by `check_err` and `cell_update`
+            output[1] = code.to_string();
+            while i < n && input_arr[i] != ')' {
+                output[2].push(input_arr[i]);
+                i += 1;
+            }
         } else {
             while i < n && input_arr[i] != ':' {
                 output[2].push(input_arr[i]);
@@ -376,10 +486,25 @@ pub fn help_input(input: &str) -> Vec<String> {
         }
     }
 
+    // Range functions ending in _SKIPERR share the opcode of the plain function up to the last letter
+    let skip_err = match output[1].strip_suffix("_SKIPERR") {
+        Some(name) if ["SUM", "MIN", "MAX", "AVG", "STDEV"].contains(&name) => {
+            output[1] = name.to_string();
+            true
+        }
+        _ => false,
+    };
+
     if output[1] == *"STDEV" {
         output[1] = String::from("STD");
     } else if output[1] == *"AVG" {
         output[1] = String::from("MEA");
+    } else if output[1] == *"COUNT" {
+        output[1] = String::from("NUM");
+    } else if output[1] == *"COUNTA" {
+        output[1] = String::from("NUA");
+    } else if output[1] == *"COUNTBLANK" {
+        output[1] = String::from("NUB");
     } else if output[1] == *"SL" {
         if is_integer(&output[2]) {
             output[1].push('V');
@@ -387,10 +512,182 @@ pub fn help_input(input: &str) -> Vec<String> {
             output[1].push('C');
         }
     }
+    if skip_err {
+        output[1].truncate(2);
+        output[1].push('E');
+    }
 
     output
 }
 
+/// Rebuilds the text of a formula from its parsed operation.
+///
+/// Formulas are not stored as text, so this is used whenever a formula has to be shown or
+/// saved. Cells are written in the A1 style, and the result parses back to the same
+/// operation. Views in the R1C1 style show it through `cellref::show_formula`.
+///
+/// # Arguments
+/// * `op` - The operation stored for a cell
+/// * `len_h` - An i32 representing the horizontal boundary (columns)
+///
+/// # Returns
+/// * `String` - The formula without the leading '=', or an empty string for an empty cell
+pub fn formula_text(op: &crate::Ops, len_h: i32) -> String {
+    let cell = |c: i32| crate::ind_to_cell(c, len_h);
+    let code: Vec<char> = op.opcpde.chars().collect();
+    match op.opcpde.as_str() {
+        "" => String::new(),
+        crate::utils::structure::REF_OP => crate::utils::structure::REF_TEXT.to_string(),
+        EXP_OP => op.expr.as_ref().map(Expr::text).unwrap_or_default(),
+        "EQV" => op.cell1.to_string(),
+        "EQC" => cell(op.cell1),
+        "SLV" => format!("SLEEP({})", op.cell1),
+        "SLC" => format!("SLEEP({})", cell(op.cell1)),
+        code if crate::utils::graph::CONDITIONAL_OPS.contains(&code) => {
+            let name = match code {
+                "SUI" => "SUMIF",
+                "NUI" => "COUNTIF",
+                _ => "AVERAGEIF",
+            };
+            let criterion = op.criterion.map(|c| c.text()).unwrap_or_default();
+            format!(
+                "{}({}:{}, {})",
+                name,
+                cell(op.cell1),
+                cell(op.cell2),
+                criterion
+            )
+        }
+        code if crate::utils::graph::RANGE_OPS.contains(&code) => {
+            let name = match code {
+                "NUM" => "COUNT",
+                "NUA" => "COUNTA",
+                "NUB" => "COUNTBLANK",
+                _ => match &code[..2] {
+                    "SU" => "SUM",
+                    "MI" => "MIN",
+                    "MA" => "MAX",
+                    "ME" => "AVG",
+                    _ => "STDEV",
+                },
+            };
+            let suffix = if crate::utils::graph::SKIP_ERR_OPS.contains(&code) {
+                "_SKIPERR"
+            } else {
+                ""
+            };
+            format!("{}{}({}:{})", name, suffix, cell(op.cell1), cell(op.cell2))
+        }
+        _ => {
+            let operand = |kind: char, v: i32| if kind == 'C' { cell(v) } else { v.to_string() };
+            let oper = match code[2] {
+                'A' => '+',
+                'S' => '-',
+                'M' => '*',
+                _ => '/',
+            };
+            format!(
+                "{}{}{}",
+                operand(code[0], op.cell1),
+                oper,
+                operand(code[1], op.cell2)
+            )
+        }
+    }
+}
+
+/// Checks whether a cell reference can be inserted at the end of a partially typed formula.
+///
+/// # Arguments
+/// * `text` - The formula typed so far
+///
+/// # Returns
+/// * `bool` - True if the text ends with '=', an operator, a comparison, '(', ',' or ':'
+pub fn accepts_reference(text: &str) -> bool {
+    text.trim_end()
+        .chars()
+        .last()
+        .is_some_and(|c| "=+-*/<>(,:".contains(c))
+}
+
+/// Builds the reference text for a range of cells given by two corners.
+///
+/// # Arguments
+/// * `anchor` - Index of the cell where the range starts
+/// * `cursor` - Index of the cell where the range ends
+/// * `len_h` - An i32 representing the horizontal boundary (columns)
+///
+/// # Returns
+/// * `String` - A single cell such as "B2" if both corners are the same, otherwise a range such as "B2:D9"
+pub fn reference_text(anchor: i32, cursor: i32, len_h: i32) -> String {
+    let pos = |ind: i32| ((ind - 1) % len_h, (ind - 1) / len_h);
+    let ((c1, r1), (c2, r2)) = (pos(anchor), pos(cursor));
+    let first = c1.min(c2) + 1 + r1.min(r2) * len_h;
+    let last = c1.max(c2) + 1 + r1.max(r2) * len_h;
+    if first == last {
+        crate::ind_to_cell(first, len_h)
+    } else {
+        format!(
+            "{}:{}",
+            crate::ind_to_cell(first, len_h),
+            crate::ind_to_cell(last, len_h)
+        )
+    }
+}
+
+/// Validates a formula while it is being typed.
+///
+/// # Arguments
+/// * `cell` - The cell the formula is typed into, e.g. "B2"
+/// * `text` - The formula typed so far, with or without a leading '='
+/// * `len_h` - An i32 representing the horizontal boundary (columns)
+/// * `len_v` - An i32 representing the vertical boundary (rows)
+///
+/// # Returns
+/// * `Option<(String, std::ops::Range<usize>)>` - None if the formula is valid or empty, otherwise the
+///   error message and the byte range of `text` to mark. The range covers the first offending cell
+///   reference or number if one can be found, and the whole formula otherwise.
+pub fn formula_error(
+    cell: &str,
+    text: &str,
+    len_h: i32,
+    len_v: i32,
+) -> Option<(String, std::ops::Range<usize>)> {
+    let start = usize::from(text.starts_with('='));
+    let body = &text[start..];
+    if body.trim().is_empty() {
+        return None;
+    }
+    let error = input(&format!("{}={}", cell, body), len_h, len_v).err()?;
+
+    // Split the formula into runs of letters and digits
+    let mut words = Vec::new();
+    let mut begin = None;
+    for (i, c) in body.char_indices().chain([(body.len(), ' ')]) {
+        if c.is_ascii_alphanumeric() {
+            begin.get_or_insert(i);
+        } else if let Some(b) = begin.take() {
+            words.push(b..i);
+        }
+    }
+    let bad = words.into_iter().find(|w| {
+        let word = &body[w.clone()];
+        let is_cell = word.starts_with(|c: char| c.is_ascii_alphabetic())
+            && word.ends_with(|c: char| c.is_ascii_digit());
+        match error {
+            SheetError::InvalidCell | SheetError::InvalidRange => {
+                is_cell && !is_valid_cell(word, len_h, len_v)
+            }
+            SheetError::InvalidValue => {
+                word.chars().all(|c| c.is_ascii_digit()) && word.parse::<i32>().is_err()
+            }
+            _ => false,
+        }
+    });
+    let span = bad.unwrap_or(0..body.len());
+    Some((error.to_string(), span.start + start..span.end + start))
+}
+
 /// Parses and validates input for spreadsheet operations.
 ///
 /// # Arguments
@@ -399,20 +696,42 @@ pub fn help_input(input: &str) -> Vec<String> {
 /// * `len_v` - An i32 representing the vertical boundary (rows)
 ///
 /// # Returns
-/// * `Vec<String>` - Vector containing the parsed components(output of `help_input` function) and validation message (output of `check_err` function).
-pub fn input(input: &str, len_h: i32, len_v: i32) -> Vec<String> {
+/// * `Result<ParsedCommand, SheetError>` - The parsed command, or the error found by `check_err`.
+pub fn input(input: &str, len_h: i32, len_v: i32) -> Result<ParsedCommand, SheetError> {
     let mut output = help_input(input);
+    // Cells typed in lowercase or in the R1C1 style are passed on as "A1"
+    for i in [0, 2, 3] {
+        output[i] = crate::utils::cellref::normalize(&output[i]);
+    }
 
-    let message = check_err(input, &output, len_h, len_v);
-    output.push(message);
-
-    output
+    check_err(input, &output, len_h, len_v)?;
+    let [target, op, arg1, arg2] = <[String; 4]>::try_from(output).unwrap_or_default();
+    if op == "SRL" {
+        return Ok(ParsedCommand::ScrollTo { cell: target });
+    }
+    Ok(ParsedCommand::Assign(Assignment {
+        target,
+        expr: Formula { op, arg1, arg2 },
+    }))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Gives the command assigning a formula with the given components.
+    fn assign(target: &str, op: &str, arg1: &str, arg2: &str) -> ParsedCommand {
+        ParsedCommand::Assign(Assignment::new(target, op, arg1, arg2))
+    }
+
+    /// Gives the opcode of a valid assignment in a 26x100 spreadsheet.
+    fn op(command: &str) -> String {
+        match input(command, 26, 100) {
+            Ok(ParsedCommand::Assign(assignment)) => assignment.expr.op,
+            other => panic!("{} is not an assignment: {:?}", command, other),
+        }
+    }
+
     #[test]
     fn test_is_arth() {
         assert!(is_arth("A1=B1+C1"));
@@ -437,6 +756,8 @@ mod tests {
         assert!(!is_integer("12A"));
         assert!(!is_integer("A1"));
         assert!(!is_integer("12.3"));
+        assert!(!is_integer(""));
+        assert!(!is_integer("-"));
     }
 
     #[test]
@@ -500,6 +821,13 @@ mod tests {
         assert_eq!(result[1], "STD");
         assert_eq!(result[2], "B1");
         assert_eq!(result[3], "C5");
+
+        assert_eq!(help_input("A1=AVG_SKIPERR(B1:C5)")[1], "MEE");
+        assert_eq!(help_input("A1=STDEV_SKIPERR(B1:C5)")[1], "STE");
+        assert_eq!(
+            input("A1=SLEEP_SKIPERR(B1)", 5, 5),
+            Err(SheetError::InvalidOperation)
+        );
     }
 
     #[test]
@@ -540,7 +868,7 @@ mod tests {
             String::from("5"),
             String::new(),
         ];
-        assert_eq!(check_err("A1=5", &output, 26, 100), "ok");
+        assert_eq!(check_err("A1=5", &output, 26, 100), Ok(()));
 
         output = vec![
             String::from("Z101"),
@@ -550,7 +878,7 @@ mod tests {
         ];
         assert_eq!(
             check_err("Z101=5", &output, 26, 100),
-            "Assigned Cell out of bounds"
+            Err(SheetError::AssignedCellOutOfBounds)
         );
 
         output = vec![
@@ -561,94 +889,363 @@ mod tests {
         ];
         assert_eq!(
             check_err("A1=SUM(B2:A1)", &output, 26, 100),
-            "Invalid Range"
+            Err(SheetError::InvalidRange)
         );
     }
 
     #[test]
     fn test_input_integration() {
-        let result = input("A1=B1+C1", 26, 100);
-        assert_eq!(result[0], "A1");
-        assert_eq!(result[1], "CCA");
-        assert_eq!(result[2], "B1");
-        assert_eq!(result[3], "C1");
-        assert_eq!(result[4], "ok");
+        assert_eq!(
+            input("A1=B1+C1", 26, 100),
+            Ok(assign("A1", "CCA", "B1", "C1"))
+        );
 
-        let result = input("Z101=5", 26, 100);
-        assert_eq!(result[4], "Assigned Cell out of bounds");
+        assert_eq!(
+            input("Z101=5", 26, 100),
+            Err(SheetError::AssignedCellOutOfBounds)
+        );
 
-        let result = input("A1=SUM(B2:A1)", 26, 100);
-        assert_eq!(result[4], "Invalid Range");
+        assert_eq!(
+            input("A1=SUM(B2:A1)", 26, 100),
+            Err(SheetError::InvalidRange)
+        );
     }
 
     #[test]
     fn test_input_with_spaces() {
-        let result = input("A1=B1+C1", 26, 100);
-        assert_eq!(result[0], "A1");
-        assert_eq!(result[1], "CCA");
-        assert_eq!(result[2], "B1");
-        assert_eq!(result[3], "C1");
-        assert_eq!(result[4], "ok");
+        assert_eq!(
+            input("A1=B1+C1", 26, 100),
+            Ok(assign("A1", "CCA", "B1", "C1"))
+        );
 
-        let result = input("A1=5", 26, 100);
-        assert_eq!(result[0], "A1");
-        assert_eq!(result[1], "EQV");
-        assert_eq!(result[2], "5");
-        assert_eq!(result[4], "ok");
+        assert_eq!(input("A1=5", 26, 100), Ok(assign("A1", "EQV", "5", "")));
     }
 
     #[test]
     fn test_min_max_functions() {
-        let result = input("A1=MIN(B1:C5)", 26, 100);
-        assert_eq!(result[0], "A1");
-        assert_eq!(result[1], "MIN");
-        assert_eq!(result[2], "B1");
-        assert_eq!(result[3], "C5");
-        assert_eq!(result[4], "ok");
+        assert_eq!(
+            input("A1=MIN(B1:C5)", 26, 100),
+            Ok(assign("A1", "MIN", "B1", "C5"))
+        );
 
-        let result = input("A1=MAX(B1:C5)", 26, 100);
-        assert_eq!(result[0], "A1");
-        assert_eq!(result[1], "MAX");
-        assert_eq!(result[2], "B1");
-        assert_eq!(result[3], "C5");
-        assert_eq!(result[4], "ok");
+        assert_eq!(
+            input("A1=MAX(B1:C5)", 26, 100),
+            Ok(assign("A1", "MAX", "B1", "C5"))
+        );
+    }
+
+    #[test]
+    fn test_reference_styles() {
+        assert_eq!(
+            input("a1=b2+3", 26, 100),
+            Ok(assign("A1", "CVA", "B2", "3"))
+        );
+        assert_eq!(
+            input("R1C1=SUM(r1c2:R3C2)", 26, 100),
+            Ok(assign("A1", "SUM", "B1", "B3"))
+        );
+        assert_eq!(
+            input("scroll_to r2c3", 26, 100),
+            Ok(ParsedCommand::ScrollTo {
+                cell: String::from("C2")
+            })
+        );
+        assert_eq!(input("A1=R1C27", 26, 100), Err(SheetError::InvalidCell));
+    }
+
+    #[test]
+    fn test_help_input_expressions() {
+        assert_eq!(help_input("A1=(B1+C1)*2"), ["A1", "EXP", "(B1+C1)*2", ""]);
+        assert_eq!(help_input("A1=B1+C1+D1"), ["A1", "EXP", "B1+C1+D1", ""]);
+        // A single operator keeps the compact opcodes
+        assert_eq!(help_input("A1=(B1)+(2)"), ["A1", "CVA", "B1", "2"]);
+        assert_eq!(help_input("A1=-B1"), ["A1", "EXP", "-B1", ""]);
+        assert_eq!(
+            help_input("A1=IF(B1>10, C1, D1)"),
+            ["A1", "EXP", "IF(B1>10, C1, D1)", ""]
+        );
+        assert_eq!(
+            input("A1=IF(B1>=10, C1)", 26, 100),
+            Err(SheetError::InvalidExpression)
+        );
+        assert_eq!(
+            input("A1=VLOOKUP(B1, C1:E20, 2)", 26, 100),
+            Ok(assign("A1", "EXP", "VLOOKUP(B1, C1:E20, 2)", ""))
+        );
+        assert_eq!(
+            input("A1=HLOOKUP(B1, C1:E200, 2)", 26, 100),
+            Err(SheetError::InvalidCell)
+        );
+        assert_eq!(
+            input("A1=VLOOKUP(B1, E1:C20, 2)", 26, 100),
+            Err(SheetError::InvalidRange)
+        );
+        assert_eq!(help_input("A1=ABS(B1)"), ["A1", "EXP", "ABS(B1)", ""]);
+        assert!(input("A1=MOD(B1,7)", 26, 100).is_ok());
+        assert_eq!(
+            input("A1=POW(B1)", 26, 100),
+            Err(SheetError::InvalidExpression)
+        );
+        assert_eq!(
+            input("A1=SUM(B1:B2)*2", 26, 100),
+            Err(SheetError::InvalidOperation)
+        );
+        assert_eq!(
+            input("A1=(B1+C1", 26, 100),
+            Err(SheetError::InvalidExpression)
+        );
+        assert_eq!(
+            input("A1=(B1+C101)*2", 26, 100),
+            Err(SheetError::InvalidCell)
+        );
     }
 
     #[test]
     fn test_invalid_operations() {
-        let result = input("A1=INVALID(B1:C5)", 26, 100);
-        assert_eq!(result[4], "Invalid Operation");
+        assert_eq!(
+            input("A1=INVALID(B1:C5)", 26, 100),
+            Err(SheetError::InvalidOperation)
+        );
+
+        assert_eq!(
+            input("scroll_invalid A1", 26, 100),
+            Err(SheetError::InvalidOperation)
+        );
+    }
 
-        let result = input("scroll_invalid A1", 26, 100);
-        assert_eq!(result[4], "Invalid Operation");
+    #[test]
+    fn test_into_assignment() {
+        assert_eq!(
+            assign("B2", "CVA", "A1", "3").into_assignment(),
+            Ok(Assignment::new("B2", "CVA", "A1", "3"))
+        );
+        let scroll = input("scroll_to B2", 26, 100).unwrap();
+        assert_eq!(scroll.into_assignment(), Err(SheetError::InvalidOperation));
     }
 
     #[test]
     fn test_division_and_multiplication() {
-        let result = input("A1=B1/C1", 26, 100);
-        assert_eq!(result[0], "A1");
-        assert_eq!(result[1], "CCD");
-        assert_eq!(result[2], "B1");
-        assert_eq!(result[3], "C1");
-        assert_eq!(result[4], "ok");
+        assert_eq!(
+            input("A1=B1/C1", 26, 100),
+            Ok(assign("A1", "CCD", "B1", "C1"))
+        );
 
-        let result = input("A1=B1*5", 26, 100);
-        assert_eq!(result[0], "A1");
-        assert_eq!(result[1], "CVM");
-        assert_eq!(result[2], "B1");
-        assert_eq!(result[3], "5");
-        assert_eq!(result[4], "ok");
+        assert_eq!(
+            input("A1=B1*5", 26, 100),
+            Ok(assign("A1", "CVM", "B1", "5"))
+        );
     }
 
     #[test]
     fn test_cell_out_of_bounds() {
-        let result = input("scroll_to Z101", 26, 100);
-        assert_eq!(result[4], "Scroll Cell out of bounds");
+        assert_eq!(
+            input("scroll_to Z101", 26, 100),
+            Err(SheetError::ScrollCellOutOfBounds)
+        );
 
-        let result = input("A1=Z101", 26, 100);
-        assert_eq!(result[4], "Invalid Cell");
+        assert_eq!(input("A1=Z101", 26, 100), Err(SheetError::InvalidCell));
 
-        let result = input("A1=B1+Z101", 26, 100);
-        assert_eq!(result[4], "Invalid Cell");
+        assert_eq!(input("A1=B1+Z101", 26, 100), Err(SheetError::InvalidCell));
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        assert_eq!(input("A1=", 10, 10), Err(SheetError::InvalidOperation));
+        assert_eq!(input("A1=5+", 10, 10), Err(SheetError::InvalidExpression));
+        assert_eq!(input("A1=5+ ", 10, 10), Err(SheetError::InvalidExpression));
+        assert_eq!(
+            input("A1=99999999999", 10, 10),
+            Err(SheetError::InvalidValue)
+        );
+        assert_eq!(
+            input("A1=99999999999+1", 10, 10),
+            Err(SheetError::InvalidValue)
+        );
+        assert_eq!(
+            input("A1=SLEEP(99999999999)", 10, 10),
+            Err(SheetError::InvalidValue)
+        );
+        assert_eq!(
+            input("AAAAAAA1=1", 10, 10),
+            Err(SheetError::AssignedCellOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn test_random_bytes_never_panic() {
+        let alphabet: Vec<char> = "AZaz09=+-*/():, \u{0}é😀scroll_toSUMSLEEP"
+            .chars()
+            .collect();
+        let mut seed: u64 = 0x9E3779B97F4A7C15;
+        for _ in 0..50000 {
+            let mut s = String::new();
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            for k in 0..(seed % 12) {
+                s.push(alphabet[((seed >> (k * 5)) % alphabet.len() as u64) as usize]);
+            }
+            let _ = input(&s, 18278, 999);
+        }
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("A1:C4", 5, 5), Some((1, 1, 3, 4)));
+        assert_eq!(parse_range("B2:B2", 5, 5), Some((2, 2, 2, 2)));
+        assert_eq!(parse_range("C4:A1", 5, 5), None);
+        assert_eq!(parse_range("A1:F1", 5, 5), None);
+        assert_eq!(parse_range("A1", 5, 5), None);
+    }
+
+    #[test]
+    fn test_accepts_reference() {
+        assert!(accepts_reference("="));
+        assert!(accepts_reference("A1+"));
+        assert!(accepts_reference("SUM("));
+        assert!(accepts_reference("SUM(A1:"));
+        assert!(accepts_reference("5 * "));
+        assert!(accepts_reference("IF(A1 > "));
+        assert!(accepts_reference("IF(A1, "));
+        assert!(!accepts_reference(""));
+        assert!(!accepts_reference("A1"));
+        assert!(!accepts_reference("SUM(A1:B2)"));
+    }
+
+    #[test]
+    fn test_reference_text() {
+        // 10 columns: B2 is 12, D9 is 84
+        assert_eq!(reference_text(12, 12, 10), "B2");
+        assert_eq!(reference_text(12, 84, 10), "B2:D9");
+        assert_eq!(reference_text(84, 12, 10), "B2:D9");
+        // Corners B9 and D2 give the same range
+        assert_eq!(reference_text(82, 14, 10), "B2:D9");
+        assert_eq!(reference_text(10, 20, 10), "J1:J2");
+    }
+
+    #[test]
+    fn test_formula_error() {
+        assert_eq!(formula_error("A1", "B2+3", 10, 10), None);
+        assert_eq!(formula_error("A1", "=SUM(B2:C3)", 10, 10), None);
+        assert_eq!(formula_error("A1", "", 10, 10), None);
+        assert_eq!(
+            formula_error("A1", "B2+Z99", 10, 10),
+            Some((String::from("Invalid Cell"), 3..6))
+        );
+        assert_eq!(
+            formula_error("A1", "=SUM(B2:K1)", 10, 10),
+            Some((String::from("Invalid Range"), 8..10))
+        );
+        assert_eq!(
+            formula_error("A1", "1+99999999999", 10, 10),
+            Some((String::from("Invalid Value"), 2..13))
+        );
+        // Nothing in particular to blame, so the whole formula is marked
+        assert_eq!(
+            formula_error("A1", "=B2+", 10, 10),
+            Some((String::from("Invalid Expression"), 1..4))
+        );
+        assert_eq!(
+            formula_error("A1", "FOO(B2)", 10, 10),
+            Some((String::from("Invalid Operation"), 0..7))
+        );
+    }
+
+    #[test]
+    fn test_conditional_functions() {
+        assert_eq!(
+            input("A1=SUMIF(B1:B20, \">5\")", 26, 100),
+            Ok(assign("A1", "SUI", "B1:B20, \">5\"", ""))
+        );
+        assert_eq!(op("A1=COUNTIF(B1:C2,\"3\")"), "NUI");
+        assert_eq!(op("A1=AVERAGEIF(b1:b2, <>0)"), "MEI");
+        assert_eq!(
+            input("A1=SUMIF(B2:B1, \">5\")", 26, 100),
+            Err(SheetError::InvalidRange)
+        );
+        assert_eq!(
+            input("A1=SUMIF(B1, \">5\")", 26, 100),
+            Err(SheetError::InvalidRange)
+        );
+        assert_eq!(
+            input("A1=SUMIF(B1:B2)", 26, 100),
+            Err(SheetError::InvalidCriterion)
+        );
+        assert_eq!(
+            input("A1=SUMIF(B1:B2, \">x\")", 26, 100),
+            Err(SheetError::InvalidCriterion)
+        );
+        assert_eq!(
+            input("A1=SUMIF(B1:B2, \">5\")+1", 26, 100),
+            Err(SheetError::InvalidOperation)
+        );
+    }
+
+    #[test]
+    fn test_counting_functions() {
+        assert_eq!(
+            input("A1=COUNT(B1:B20)", 26, 100),
+            Ok(assign("A1", "NUM", "B1", "B20"))
+        );
+        assert_eq!(op("A1=COUNTA(B1:C2)"), "NUA");
+        assert_eq!(op("A1=COUNTBLANK(B1:C2)"), "NUB");
+        assert_eq!(
+            input("A1=COUNT(B2:B1)", 26, 100),
+            Err(SheetError::InvalidRange)
+        );
+        assert_eq!(
+            input("A1=COUNT_SKIPERR(B1:B2)", 26, 100),
+            Err(SheetError::InvalidOperation)
+        );
+    }
+
+    #[test]
+    fn test_formula_text_round_trip() {
+        let mut sheet = crate::utils::sheet::Sheet::new(30, 30);
+        for formula in [
+            "5",
+            "-7",
+            "B2",
+            "AB3",
+            "A1+B1",
+            "-5*C3",
+            "C3/-2",
+            "4-3",
+            "SUM(A1:B2)",
+            "AVG(B2:C3)",
+            "STDEV(A1:A5)",
+            "MIN(A1:Z1)",
+            "MAX(A1:A1)",
+            "SLEEP(0)",
+            "SLEEP(B1)",
+            "SUM_SKIPERR(A1:B2)",
+            "AVG_SKIPERR(B2:C3)",
+            "(A1+B1)*2-C1/3",
+            "-(B2*C3)",
+            "A1-(B1-C1)",
+            "SUMIF(A1:B2, \">5\")",
+            "COUNTIF(A1:A5, \"0\")",
+            "AVERAGEIF(B2:C3, \"<>-1\")",
+            "COUNT(A1:B2)",
+            "COUNTA(A1:A5)",
+            "COUNTBLANK(B2:C3)",
+            "DATE(2024, 5, 1)+B1",
+            "DATEDIF(A1, TODAY(), \"Y\")",
+        ] {
+            let out = sheet
+                .parse_assignment(&format!("AD30={}", formula))
+                .expect(formula);
+            sheet.update(&out).unwrap();
+            assert_eq!(formula_text(&sheet.opers()[900], 30), formula);
+        }
+        let out = sheet
+            .parse_assignment("AD30=COUNTIF(A1:A5, \"!=2\")")
+            .unwrap();
+        sheet.update(&out).unwrap();
+        assert_eq!(
+            formula_text(&sheet.opers()[900], 30),
+            "COUNTIF(A1:A5, \"<>2\")"
+        );
+        assert_eq!(formula_text(&sheet.opers()[1], 30), "");
     }
 }
@@ -0,0 +1,203 @@
+//! This module contains an append-only journal of accepted commands.
+//!
+//! Every command that changed the spreadsheet, including the settings that change what
+//! formulas give (see `Sheet::apply_setting`), is written on its own line and flushed
+//! immediately, so the journal survives a crash. Replaying it on an empty spreadsheet of the
+//! same size rebuilds the same state, which also makes it useful for reproducing bugs.
+//!
+//! The accepted commands are also kept in memory, so a session started without a journal
+//! file can still be saved to one.
+
+use crate::utils::sheet::Sheet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+
+/// The accepted commands of a session, appended to a journal file if one is open.
+///
+/// # Fields
+/// * `file` - The open journal file, if any
+/// * `commands` - The commands recorded so far, oldest first
+#[derive(Debug, Default)]
+pub struct Journal {
+    file: Option<File>,
+    commands: Vec<String>,
+}
+
+impl Journal {
+    /// Starts a journal that is only kept in memory.
+    pub fn memory() -> Self {
+        Self::default()
+    }
+
+    /// Opens a journal for appending, creating the file if needed.
+    /// # Arguments
+    /// * `path` - Path of the journal file
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        tracing::info!(path, "journal opened");
+        Ok(Self {
+            file: Some(file),
+            commands: Vec::new(),
+        })
+    }
+
+    /// Whether recorded commands are written to a journal file.
+    pub fn on_disk(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Keeps a command in memory without writing it to the journal file, e.g. one that was
+    /// read back from that file.
+    /// # Arguments
+    /// * `command` - The command exactly as it was typed
+    pub fn remember(&mut self, command: &str) {
+        self.commands.push(command.to_string());
+    }
+
+    /// Appends a command to the journal and flushes it to disk.
+    /// # Arguments
+    /// * `command` - The command exactly as it was typed
+    pub fn record(&mut self, command: &str) -> io::Result<()> {
+        self.remember(command);
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        let result = writeln!(file, "{}", command).and_then(|_| file.sync_data());
+        if let Err(e) = &result {
+            tracing::warn!(error = %e, "journal write failed");
+        }
+        result
+    }
+
+    /// Writes every recorded command to a new journal file, replacing any existing file.
+    /// # Arguments
+    /// * `path` - Path of the journal file
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        for command in &self.commands {
+            writeln!(file, "{}", command)?;
+        }
+        file.sync_data()?;
+        tracing::info!(path, commands = self.commands.len(), "journal saved");
+        Ok(())
+    }
+}
+
+/// Reads the commands stored in a journal.
+/// # Arguments
+/// * `path` - Path of the journal file
+/// # Returns
+/// The commands in the order they were recorded. Empty lines and lines starting with `#`
+/// are skipped.
+pub fn read(path: &str) -> io::Result<Vec<String>> {
+    let mut commands = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if !line.trim().is_empty() && !line.starts_with('#') {
+            commands.push(line);
+        }
+    }
+    tracing::info!(path, commands = commands.len(), "journal read");
+    Ok(commands)
+}
+
+/// Applies journaled commands to a spreadsheet in order.
+/// # Arguments
+/// * `sheet` - The spreadsheet to update
+/// * `commands` - The commands to apply
+/// # Returns
+/// The number of commands that could not be applied.
+pub fn replay(sheet: &mut Sheet, commands: &[String]) -> usize {
+    let mut failed = 0;
+    for command in commands {
+        if let Some(edit) =
+            crate::utils::structure::LineEdit::parse(command, sheet.len_h, sheet.len_v)
+        {
+            if edit.loses_cells(sheet.opers(), sheet.len_h) {
+                failed += 1;
+            } else {
+                sheet.edit_lines(edit);
+            }
+            continue;
+        }
+        if let Some(result) = sheet.apply_setting(command) {
+            failed += result.is_err() as usize;
+            continue;
+        }
+        if sheet
+            .parse_assignment(command)
+            .and_then(|out| sheet.update(&out))
+            .is_err()
+        {
+            failed += 1;
+        }
+    }
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_replay() {
+        let path = std::env::temp_dir().join(format!("journal_{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let mut journal = Journal::open(path).unwrap();
+        for command in ["A1=5", "B1=A1*2", "C1=SUM(A1:B1)"] {
+            journal.record(command).unwrap();
+        }
+        drop(journal);
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .unwrap()
+            .write_all(b"# comment\n\nA1=B1\n")
+            .unwrap();
+
+        let commands = read(path).unwrap();
+        assert_eq!(commands.len(), 4);
+
+        let mut sheet = Sheet::new(3, 3);
+        // The last command forms a cycle and is rejected
+        assert_eq!(replay(&mut sheet, &commands), 1);
+        assert_eq!(&sheet.database()[1..=3], &[5, 10, 15]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_settings() {
+        let commands: Vec<String> = ["A1=1", "A2=A1/0", "A3=SUM(A1:A2)", "skip_errors on"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        let mut sheet = Sheet::new(1, 3);
+        assert_eq!(replay(&mut sheet, &commands), 0);
+        assert!(sheet.ranges().skip_errors);
+        assert_eq!(sheet.get_value("A3"), Some(1));
+
+        let commands = ["safe_mode on".to_string(), "limit cells 1".to_string()];
+        assert_eq!(replay(&mut sheet, &commands), 0);
+        assert!(sheet.limits().safe_mode());
+        assert_eq!(sheet.limits().max_cells(), Some(1));
+    }
+
+    #[test]
+    fn test_save_memory_journal() {
+        let path = std::env::temp_dir().join(format!("saved_{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "Z9=1\n").unwrap();
+
+        let mut journal = Journal::memory();
+        assert!(!journal.on_disk());
+        journal.remember("A1=5");
+        journal.record("B1=A1+1").unwrap();
+        journal.save(path).unwrap();
+
+        // The previous content is replaced
+        assert_eq!(read(path).unwrap(), vec!["A1=5", "B1=A1+1"]);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,60 @@
+//! This module sets up diagnostic logging with the `tracing` crate.
+//!
+//! The engine emits a span for every edit with the number of cells recomputed, and file
+//! operations log where they read and write. Nothing is logged unless a level is chosen with
+//! `--log-level` or a file is given with `--log-file`.
+
+use std::fs::File;
+use std::sync::Mutex;
+use tracing::Level;
+
+/// Parses a log level name.
+/// # Arguments
+/// * `level` - One of error, warn, info, debug or trace, in any case.
+/// # Returns
+/// The level, or `None` if the name is unknown.
+pub fn parse_level(level: &str) -> Option<Level> {
+    match level.to_ascii_lowercase().as_str() {
+        "error" => Some(Level::ERROR),
+        "warn" => Some(Level::WARN),
+        "info" => Some(Level::INFO),
+        "debug" => Some(Level::DEBUG),
+        "trace" => Some(Level::TRACE),
+        _ => None,
+    }
+}
+
+/// Starts logging for the rest of the program.
+/// # Arguments
+/// * `level` - The most detailed level to log.
+/// * `file` - File to write the log to, or `None` for standard error.
+/// # Returns
+/// `Ok(())` if logging started, or a description of the error.
+pub fn init(level: Level, file: Option<&str>) -> Result<(), String> {
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false);
+    let result = match file {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| e.to_string())?;
+            builder
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .try_init()
+        }
+        None => builder.with_writer(std::io::stderr).try_init(),
+    };
+    result.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("debug"), Some(Level::DEBUG));
+        assert_eq!(parse_level("WARN"), Some(Level::WARN));
+        assert_eq!(parse_level("verbose"), None);
+    }
+}
@@ -0,0 +1,148 @@
+//! This module contains the math functions that can be used in expressions, such as
+//! `ABS(B1)` or `POW(B1, 3)`.
+//!
+//! All values are integers, so SQRT rounds down and ROUND, FLOOR and CEIL round to a number
+//! of digits or a multiple instead of to a number of decimals. Results that do not fit in an
+//! integer, and arguments a function is not defined for, give an error.
+
+/// A math function.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Math {
+    Abs,
+    Ceil,
+    Floor,
+    Mod,
+    Pow,
+    Round,
+    Sqrt,
+}
+
+impl Math {
+    /// All math functions.
+    pub const ALL: [Math; 7] = [
+        Math::Abs,
+        Math::Ceil,
+        Math::Floor,
+        Math::Mod,
+        Math::Pow,
+        Math::Round,
+        Math::Sqrt,
+    ];
+
+    /// Finds a function by name, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|f| f.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Gives the name of the function as typed.
+    pub fn name(self) -> &'static str {
+        match self {
+            Math::Abs => "ABS",
+            Math::Ceil => "CEIL",
+            Math::Floor => "FLOOR",
+            Math::Mod => "MOD",
+            Math::Pow => "POW",
+            Math::Round => "ROUND",
+            Math::Sqrt => "SQRT",
+        }
+    }
+
+    /// Gives the number of arguments of the function.
+    pub fn arity(self) -> usize {
+        match self {
+            Math::Abs | Math::Sqrt => 1,
+            _ => 2,
+        }
+    }
+
+    /// Applies the function.
+    /// # Arguments
+    /// * `args` - The values of the arguments, as many as `arity` gives.
+    /// # Returns
+    /// The result, or `None` if it is undefined or does not fit in an integer.
+    pub fn apply(self, args: &[i32]) -> Option<i32> {
+        let (a, b) = (args[0], args.get(1).copied().unwrap_or(0));
+        match self {
+            Math::Abs => a.checked_abs(),
+            Math::Sqrt => (a >= 0).then(|| a.isqrt()),
+            // The result has the sign of the divisor, so MOD(-3, 7) is 4
+            Math::Mod => {
+                let r = a.checked_rem(b)?;
+                if r != 0 && (r < 0) != (b < 0) {
+                    Some(r + b)
+                } else {
+                    Some(r)
+                }
+            }
+            Math::Pow => a.checked_pow(u32::try_from(b).ok()?),
+            // Rounds to a multiple of the absolute value of b
+            Math::Floor => {
+                let m = b.checked_abs()?;
+                a.checked_div_euclid(m)?.checked_mul(m)
+            }
+            Math::Ceil => {
+                let m = b.checked_abs()?;
+                let floor = a.checked_div_euclid(m)?;
+                match floor.checked_mul(m) {
+                    Some(f) if f == a => Some(a),
+                    _ => floor.checked_add(1)?.checked_mul(m),
+                }
+            }
+            // Rounds half away from zero to -b digits, so ROUND(1250, -2) is 1300
+            Math::Round => {
+                if b >= 0 {
+                    return Some(a);
+                }
+                let Some(m) = 10i64.checked_pow(b.unsigned_abs()) else {
+                    return Some(0);
+                };
+                let rounded = (i64::from(a).abs() + m / 2) / m * m;
+                i32::try_from(rounded * i64::from(a).signum()).ok()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let apply = |f: Math, args: &[i32]| f.apply(args);
+        assert_eq!(apply(Math::Abs, &[-5]), Some(5));
+        assert_eq!(apply(Math::Abs, &[i32::MIN]), None);
+        assert_eq!(apply(Math::Sqrt, &[17]), Some(4));
+        assert_eq!(apply(Math::Sqrt, &[-1]), None);
+        assert_eq!(apply(Math::Mod, &[17, 7]), Some(3));
+        assert_eq!(apply(Math::Mod, &[-3, 7]), Some(4));
+        assert_eq!(apply(Math::Mod, &[3, -7]), Some(-4));
+        assert_eq!(apply(Math::Mod, &[3, 0]), None);
+        assert_eq!(apply(Math::Pow, &[2, 10]), Some(1024));
+        assert_eq!(apply(Math::Pow, &[2, 31]), None);
+        assert_eq!(apply(Math::Pow, &[2, -1]), None);
+        assert_eq!(apply(Math::Floor, &[17, 5]), Some(15));
+        assert_eq!(apply(Math::Floor, &[-17, 5]), Some(-20));
+        assert_eq!(apply(Math::Floor, &[17, 0]), None);
+        assert_eq!(apply(Math::Ceil, &[17, 5]), Some(20));
+        assert_eq!(apply(Math::Ceil, &[15, -5]), Some(15));
+        assert_eq!(apply(Math::Ceil, &[-17, 5]), Some(-15));
+        assert_eq!(apply(Math::Ceil, &[i32::MAX, 2]), None);
+        assert_eq!(apply(Math::Round, &[1250, -2]), Some(1300));
+        assert_eq!(apply(Math::Round, &[-1249, -2]), Some(-1200));
+        assert_eq!(apply(Math::Round, &[1249, 3]), Some(1249));
+        assert_eq!(apply(Math::Round, &[1249, -20]), Some(0));
+        assert_eq!(apply(Math::Round, &[i32::MAX, -1]), None);
+    }
+
+    #[test]
+    fn test_names() {
+        for f in Math::ALL {
+            assert_eq!(Math::parse(&f.name().to_lowercase()), Some(f));
+            assert!(crate::utils::expr::EXPR_FUNCTIONS.contains(&f.name()));
+        }
+        assert_eq!(Math::parse("SUM"), None);
+    }
+}
@@ -1,6 +1,45 @@
 //! This module contains basic utilities for the Spreasheet (excluding ui submodule).
+pub mod analysis;
+pub mod assertion;
+pub mod backup;
+pub mod cancel;
+pub mod cellref;
+pub mod cells;
+pub mod clipboard;
+pub mod colformula;
+pub mod coltype;
+pub mod config;
+pub mod custom;
+pub mod date;
+pub mod decoration;
+pub mod demo;
 pub mod display;
+pub mod error;
+pub mod expr;
+pub mod fill;
+pub mod find;
+pub mod functions;
+pub mod fuzzy;
+pub mod graph;
+pub mod history;
 pub mod input;
+pub mod journal;
+pub mod logging;
+pub mod math;
+pub mod numfmt;
 pub mod operations;
+pub mod provenance;
+pub mod refresh;
+pub mod rewrite;
+pub mod rules;
+pub mod schedule;
+pub mod settings;
+pub mod sheet;
+pub mod structure;
+pub mod style;
+pub mod table;
+pub mod textformat;
 pub mod toposort;
 pub mod ui;
+pub mod validate;
+pub mod watch;
@@ -0,0 +1,307 @@
+//! This module contains number formats, which change how the values of cells are written
+//! without changing the values.
+//!
+//! A format is set with `format <range> <options>` and removed with `unformat <range>`,
+//! where the range is a cell such as `B2`, a column such as `B` or a block such as `B1:D10`.
+//! The options are:
+//!
+//! - `thousands` - Separates thousands with commas, so 1234567 is written 1,234,567.
+//! - `decimals <places>` - Writes the last digits of the value after a decimal point.
+//! - `percent` - Ends the value with a percent sign.
+//! - `currency [symbol]` - Starts the value with a currency symbol, `$` unless one is given,
+//!   and separates thousands.
+//!
+//! Cell values are integers, so decimal places are implied: a column of amounts in cents
+//! formatted with `currency decimals 2` writes 123457 as $1,234.57, and a column of basis
+//! points formatted with `percent decimals 2` writes 1235 as 12.35%.
+//!
+//! ```text
+//! format B thousands
+//! format C2:C20 currency decimals 2
+//! unformat B
+//! ```
+//!
+//! Formats are shown in the grid, the terminal and CSV and PDF exports. Dates and times are
+//! not changed, and formulas still read the values.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Most decimal places a format can have.
+pub const MAX_DECIMALS: u32 = 6;
+
+/// How the value of a cell is written.
+///
+/// # Fields
+/// * `decimals` - Number of digits of the value written after the decimal point
+/// * `thousands` - Whether thousands are separated with commas
+/// * `percent` - Whether the value ends with a percent sign
+/// * `currency` - Symbol the value starts with, if any
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub decimals: u32,
+    pub thousands: bool,
+    pub percent: bool,
+    pub currency: Option<String>,
+}
+
+impl NumberFormat {
+    /// Parses the options of a `format` command, such as `currency € decimals 2`.
+    /// # Returns
+    /// The format, or `None` if the options are invalid or empty.
+    fn parse(words: &[&str]) -> Option<Self> {
+        if words.is_empty() {
+            return None;
+        }
+        let mut format = Self::default();
+        let mut words = words.iter().peekable();
+        while let Some(word) = words.next() {
+            match *word {
+                "thousands" => format.thousands = true,
+                "percent" => format.percent = true,
+                "decimals" => {
+                    format.decimals = words.next()?.parse().ok()?;
+                    if format.decimals > MAX_DECIMALS {
+                        return None;
+                    }
+                }
+                "currency" => {
+                    let options = ["thousands", "percent", "decimals", "currency"];
+                    let symbol = words.next_if(|w| !options.contains(w));
+                    format.currency = Some(symbol.map_or("$", |s| s).to_string());
+                }
+                _ => return None,
+            }
+        }
+        Some(format)
+    }
+
+    /// Parses the options of a format as written by `text`.
+    /// # Returns
+    /// The format, or `None` if the options are invalid or empty.
+    pub fn parse_text(options: &str) -> Option<Self> {
+        Self::parse(&options.split_whitespace().collect::<Vec<_>>())
+    }
+
+    /// Writes the format as the options that set it.
+    pub fn text(&self) -> String {
+        let mut options = Vec::new();
+        if let Some(symbol) = &self.currency {
+            options.push(format!("currency {}", symbol));
+        } else if self.thousands {
+            options.push(String::from("thousands"));
+        }
+        if self.decimals > 0 {
+            options.push(format!("decimals {}", self.decimals));
+        }
+        if self.percent {
+            options.push(String::from("percent"));
+        }
+        options.join(" ")
+    }
+
+    /// Writes a value in the format, such as 123457 as "$1,234.57".
+    pub fn apply(&self, value: i32) -> String {
+        let scale = 10u32.pow(self.decimals);
+        let digits = value.unsigned_abs();
+        let whole = (digits / scale).to_string();
+        let mut text = String::new();
+        if value < 0 {
+            text.push('-');
+        }
+        if let Some(symbol) = &self.currency {
+            text.push_str(symbol);
+        }
+        if self.thousands || self.currency.is_some() {
+            for (i, digit) in whole.chars().enumerate() {
+                if i > 0 && (whole.len() - i).is_multiple_of(3) {
+                    text.push(',');
+                }
+                text.push(digit);
+            }
+        } else {
+            text.push_str(&whole);
+        }
+        if self.decimals > 0 {
+            text.push_str(&format!(
+                ".{:0width$}",
+                digits % scale,
+                width = self.decimals as usize
+            ));
+        }
+        if self.percent {
+            text.push('%');
+        }
+        text
+    }
+}
+
+/// Runs a `format <range> <options>` or `unformat <range>` command.
+/// # Arguments
+/// * `command` - The full command string.
+/// * `formats` - The format of each formatted cell, updated in place.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// * `len_v` - The number of rows in the spreadsheet.
+/// # Returns
+/// "ok" if the formats were updated, otherwise "Invalid Format".
+pub fn format_command(
+    command: &str,
+    formats: &mut BTreeMap<i32, NumberFormat>,
+    len_h: i32,
+    len_v: i32,
+) -> &'static str {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let Some((x1, y1, x2, y2)) = words
+        .get(1)
+        .and_then(|r| crate::utils::style::parse_bounds(r, len_h, len_v))
+    else {
+        return "Invalid Format";
+    };
+    let format = match words[0] {
+        "format" => match NumberFormat::parse(&words[2..]) {
+            Some(format) => Some(format),
+            None => return "Invalid Format",
+        },
+        "unformat" if words.len() == 2 => None,
+        _ => return "Invalid Format",
+    };
+    for y in y1..=y2 {
+        for x in x1..=x2 {
+            let ind = x + (y - 1) * len_h;
+            match &format {
+                Some(format) => formats.insert(ind, format.clone()),
+                None => formats.remove(&ind),
+            };
+        }
+    }
+    "ok"
+}
+
+/// Writes the value of a cell as shown to the user, in its format if it has one.
+/// # Arguments
+/// * `formats` - The format of each formatted cell.
+/// * `database` - The values of all cells.
+/// * `opers` - The operations for each cell.
+/// * `cell` - The index of the cell.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// # Returns
+/// The value in its format, or as written by `date::value_text` for dates, times and cells
+/// without a format.
+pub fn value_text(
+    formats: &BTreeMap<i32, NumberFormat>,
+    database: &[i32],
+    opers: &Cells<Ops>,
+    cell: i32,
+    len_h: i32,
+) -> String {
+    match formats.get(&cell) {
+        Some(format)
+            if crate::utils::date::cell_kind(opers, cell, len_h)
+                == crate::utils::date::Kind::Number =>
+        {
+            format.apply(database[cell as usize])
+        }
+        _ => crate::utils::date::value_text(database, opers, cell, len_h),
+    }
+}
+
+/// Moves the formats along with their cells when a row or column is inserted or deleted.
+/// # Arguments
+/// * `formats` - The format of each formatted cell, updated in place.
+/// * `edit` - The inserted or deleted row or column.
+/// * `len_h` - The number of columns in the spreadsheet.
+pub fn edit_lines(
+    formats: &mut BTreeMap<i32, NumberFormat>,
+    edit: crate::utils::structure::LineEdit,
+    len_h: i32,
+) {
+    *formats = std::mem::take(formats)
+        .into_iter()
+        .filter_map(|(ind, format)| Some((edit.move_cell(ind, len_h)?, format)))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(options: &str) -> NumberFormat {
+        let words: Vec<&str> = options.split_whitespace().collect();
+        NumberFormat::parse(&words).unwrap()
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(format("thousands").apply(1234567), "1,234,567");
+        assert_eq!(format("thousands").apply(-123), "-123");
+        assert_eq!(format("thousands").apply(i32::MIN), "-2,147,483,648");
+        assert_eq!(format("percent decimals 2").apply(1235), "12.35%");
+        assert_eq!(format("currency decimals 2").apply(123457), "$1,234.57");
+        assert_eq!(format("currency € decimals 2").apply(-5), "-€0.05");
+        assert_eq!(format("decimals 3").apply(1234567), "1234.567");
+        assert_eq!(format("percent").apply(12), "12%");
+    }
+
+    #[test]
+    fn test_format_command() {
+        let mut formats = BTreeMap::new();
+        assert_eq!(
+            format_command("format A1:B1 currency decimals 2", &mut formats, 3, 4),
+            "ok"
+        );
+        assert_eq!(
+            format_command("format c thousands", &mut formats, 3, 4),
+            "ok"
+        );
+        assert_eq!(
+            formats.keys().copied().collect::<Vec<_>>(),
+            [1, 2, 3, 6, 9, 12]
+        );
+        assert_eq!(formats[&1].text(), "currency $ decimals 2");
+        assert_eq!(format("percent thousands").text(), "thousands percent");
+
+        for bad in [
+            "format",
+            "format A1",
+            "format D1 percent",
+            "format A1 decimals",
+            "format A1 decimals 7",
+            "format A1 decimals x",
+            "format A1 bold",
+            "unformat",
+            "unformat A1 percent",
+        ] {
+            assert_eq!(
+                format_command(bad, &mut formats, 3, 4),
+                "Invalid Format",
+                "{}",
+                bad
+            );
+        }
+
+        assert_eq!(format_command("unformat C", &mut formats, 3, 4), "ok");
+        assert_eq!(format_command("unformat A1", &mut formats, 3, 4), "ok");
+        assert_eq!(formats.keys().copied().collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn test_value_text() {
+        let mut sheet = crate::utils::sheet::Sheet::new(3, 3);
+        for command in ["A1=1234567", "B1=DATE(2024, 5, 1)"] {
+            let out = sheet.parse_assignment(command).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        let mut formats = BTreeMap::new();
+        format_command("format A1:B1 thousands", &mut formats, 3, 3);
+        let text = |cell| value_text(&formats, sheet.database(), sheet.opers(), cell, 3);
+        assert_eq!(text(1), "1,234,567");
+        assert_eq!(text(2), "2024-05-01");
+        assert_eq!(text(4), "0");
+
+        let edit = crate::utils::structure::LineEdit::parse("insert_col A", 3, 3).unwrap();
+        edit_lines(&mut formats, edit, 3);
+        assert_eq!(formats.keys().copied().collect::<Vec<_>>(), [2, 3]);
+    }
+}
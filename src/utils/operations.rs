@@ -1,6 +1,224 @@
 //! This module contains functions for performing various operations on a 2D data array.
 //! The operations include finding the minimum, maximum, sum, average, and standard deviation of elements
 //! within a specified range of the data array. The functions also handle error checking and return the results accordingly.
+//! Empty cells, which were never assigned, and the cells holding a header label (see
+//! `RangeOptions`) are left out of every range. Cells in an error state make the result an
+//! error, unless the function or the spreadsheet skips them (see `graph::SKIP_ERR_OPS`).
+//! The conditional functions (SUMIF, COUNTIF and AVERAGEIF) only use the values meeting the
+//! criterion stored with the formula.
+//! The counting functions (COUNT, COUNTA and COUNTBLANK) tell empty cells from cells holding 0
+//! and never give an error themselves.
+//! Values are added up in i64, and a sum that does not fit in a cell makes the result an error.
+//! Ranges of at least `PARALLEL_RANGE` cells are read on several threads with rayon.
+
+use crate::utils::error::SheetError;
+use crate::utils::expr::Comparison;
+use rayon::prelude::*;
+
+/// Fewest cells in a range for it to be read on several threads.
+pub const PARALLEL_RANGE: usize = 1 << 14;
+
+/// A criterion of a conditional range function, such as ">5".
+///
+/// # Fields
+/// * `cmp` - How a value is compared
+/// * `value` - The value it is compared to
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Criterion {
+    pub cmp: Comparison,
+    pub value: i32,
+}
+
+impl Criterion {
+    /// Parses a criterion such as `">5"`. The quotes are optional and a value without a
+    /// comparison, such as `"5"`, means equal to it. `<>` is accepted for `!=`.
+    /// # Arguments
+    /// * `text` - The criterion as typed.
+    /// # Returns
+    /// The criterion, or `None` if the text is not a criterion.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let text = text
+            .strip_prefix('"')
+            .and_then(|t| t.strip_suffix('"'))
+            .unwrap_or(text)
+            .trim();
+        let symbols = [
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("<>", Comparison::Ne),
+            ("!=", Comparison::Ne),
+            ("==", Comparison::Eq),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+            ("=", Comparison::Eq),
+        ];
+        let (cmp, rest) = symbols
+            .iter()
+            .find_map(|&(symbol, cmp)| Some((cmp, text.strip_prefix(symbol)?)))
+            .unwrap_or((Comparison::Eq, text));
+        let value = rest.trim().parse().ok()?;
+        Some(Self { cmp, value })
+    }
+
+    /// Checks whether a value meets the criterion.
+    pub fn holds(self, value: i32) -> bool {
+        self.cmp.holds(value, self.value)
+    }
+
+    /// Writes the criterion as typed in a formula, with quotes. Not equal is written `<>`, as
+    /// in other spreadsheets.
+    pub fn text(self) -> String {
+        match self.cmp {
+            Comparison::Eq => format!("\"{}\"", self.value),
+            Comparison::Ne => format!("\"<>{}\"", self.value),
+            cmp => format!("\"{}{}\"", cmp.symbol(), self.value),
+        }
+    }
+}
+
+/// Settings of a spreadsheet that change which cells of a range are read by range functions.
+///
+/// # Fields
+/// * `header_row` - Whether row 1 is a header row, whose labels are left out of every range
+/// * `labels` - Text labels typed into row 1, by cell. They are kept while the header row is
+///   off, but only shown and left out of ranges while it is on
+/// * `skip_errors` - Whether every range function leaves out cells in an error state, like the
+///   `_SKIPERR` functions
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct RangeOptions {
+    pub header_row: bool,
+    #[serde(rename = "header_text", deserialize_with = "cell_keys")]
+    pub labels: std::collections::BTreeMap<i32, String>,
+    pub skip_errors: bool,
+}
+
+/// Reads a map keyed by cell index, whose keys JSON keeps as strings. Serde cannot read them
+/// as numbers once the map is buffered for a flattened struct, which `Sheet` and the GUI
+/// workbook are.
+fn cell_keys<'de, D>(deserializer: D) -> Result<std::collections::BTreeMap<i32, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let map: std::collections::BTreeMap<String, String> =
+        serde::Deserialize::deserialize(deserializer)?;
+    map.into_iter()
+        .map(|(key, value)| Ok((key.parse().map_err(serde::de::Error::custom)?, value)))
+        .collect()
+}
+
+impl RangeOptions {
+    /// Gets the text label a cell shows in the header row.
+    /// # Arguments
+    /// * `ind` - Index of the cell
+    /// # Returns
+    /// The label, or `None` if the cell shows its value.
+    pub fn label(&self, ind: i32) -> Option<&String> {
+        self.labels.get(&ind).filter(|_| self.header_row)
+    }
+}
+
+/// Splits the arguments of a conditional range function, such as `B1:B20, ">5"`.
+/// # Arguments
+/// * `args` - The text between the parentheses.
+/// # Returns
+/// The first and last cell of the range and the criterion, or `InvalidRange` or
+/// `InvalidCriterion`.
+pub fn conditional_args(args: &str) -> Result<(&str, &str, Criterion), SheetError> {
+    let (range, criterion) = args.split_once(',').ok_or(SheetError::InvalidCriterion)?;
+    let (c1, c2) = range.split_once(':').ok_or(SheetError::InvalidRange)?;
+    let criterion = Criterion::parse(criterion).ok_or(SheetError::InvalidCriterion)?;
+    Ok((c1.trim(), c2.trim(), criterion))
+}
+
+/// Gives the indices of the cells in a range.
+/// # Arguments
+/// * `c1` - The starting cell index (1-based).
+/// * `c2` - The ending cell index (1-based).
+/// * `n_cols` - The number of cells in the data array.
+fn range_cells(c1: i32, c2: i32, n_cols: i32) -> impl Iterator<Item = usize> {
+    let (x1, y1, x2, y2) = crate::utils::graph::range_bounds(c1, c2, n_cols);
+    (x1..x2 + 1).flat_map(move |i| (y1..y2 + 1).map(move |j| (i + (j - 1) * n_cols) as usize))
+}
+
+/// Keeps what a function gives for each cell of a range, in the order of `range_cells`.
+/// Ranges of at least `PARALLEL_RANGE` cells are split across threads.
+/// # Arguments
+/// * `c1` - The starting cell index (1-based).
+/// * `c2` - The ending cell index (1-based).
+/// * `n_cols` - The number of cells in the data array.
+/// * `options` - The settings of the spreadsheet, whose header labels are left out.
+/// * `f` - The function, giving `None` for cells to leave out.
+fn range_filter_map<T: Send>(
+    c1: i32,
+    c2: i32,
+    n_cols: i32,
+    options: &RangeOptions,
+    f: impl Fn(usize) -> Option<T> + Sync + Send,
+) -> Vec<T> {
+    let (x1, y1, x2, y2) = crate::utils::graph::range_bounds(c1, c2, n_cols);
+    let f = |ind: usize| {
+        if options.label(ind as i32).is_some() {
+            return None;
+        }
+        f(ind)
+    };
+    let rows = (y2 - y1 + 1).max(0);
+    let size = ((x2 - x1 + 1).max(0) * rows) as usize;
+    if size < PARALLEL_RANGE {
+        return range_cells(c1, c2, n_cols).filter_map(f).collect();
+    }
+    // A flat index splits evenly however wide or tall the range is
+    (0..size as i32)
+        .into_par_iter()
+        .map(|k| (x1 + k / rows + (y1 + k % rows - 1) * n_cols) as usize)
+        .filter_map(f)
+        .collect()
+}
+
+/// Collects the values of a range that take part in a range function.
+/// # Arguments
+/// * `c1` - The starting cell index (1-based).
+/// * `c2` - The ending cell index (1-based).
+/// * `data_base` - A reference to the data array.
+/// * `n_cols` - The number of cells in the data array.
+/// * `err` - A reference to the error flags of the data array.
+/// * `opers` - The operations for each cell.
+/// * `dest` - The index of the cell holding the range function.
+/// * `options` - The settings of the spreadsheet.
+/// # Returns
+/// The values in the range, and whether a cell in an error state was included.
+#[allow(clippy::too_many_arguments)]
+fn range_values(
+    c1: i32,
+    c2: i32,
+    data_base: &[i32],
+    n_cols: i32,
+    err: &[bool],
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    dest: i32,
+    options: &RangeOptions,
+) -> (Vec<i32>, bool) {
+    let skip_err = crate::utils::graph::SKIP_ERR_OPS
+        .contains(&opers[dest as usize].opcpde.as_str())
+        || options.skip_errors;
+    let cells = range_filter_map(c1, c2, n_cols, options, |ind| {
+        if opers[ind].opcpde.is_empty() || (err[ind] && skip_err) {
+            return None;
+        }
+        Some((data_base[ind], err[ind]))
+    });
+    let yn = cells.iter().any(|&(_, e)| e);
+    (cells.into_iter().map(|(value, _)| value).collect(), yn)
+}
+
+/// Adds up values without overflowing.
+/// # Arguments
+/// * `values` - The values to add up.
+fn total(values: &[i32]) -> i64 {
+    values.iter().map(|&value| value as i64).sum()
+}
 
 /// Find the minimum value in a specified range of the data array.
 /// # Arguments
@@ -8,41 +226,28 @@
 /// * `c2` - The ending cell index (1-based).
 /// * `data_base` - A reference to the data array.
 /// * `n_cols` - The number of cells in the data array.
-/// * `err` - A mutable reference to a boolean array for error checking.
-/// * `dest` - The destination index in the error array to store the error status.
+/// * `err` - A reference to the error flags of the data array.
+/// * `dest` - The index of the cell holding the function.
+/// * `opers` - The operations for each cell.
+/// * `options` - The settings of the spreadsheet (see `RangeOptions`).
 /// # Returns
 /// The minimum value found in the specified range.
-/// If there is err in the range, it sets the error flag for the destination index and the return value is discarded by the caller.
-pub fn min(c1: i32, c2: i32, data_base: &[i32], n_cols: i32, err: &mut [bool], dest: i32) -> i32 {
-    let mut y1 = c1 / n_cols;
-    let mut y2 = c2 / n_cols;
-    let mut x1 = c1 % (n_cols);
-    if x1 == 0 {
-        x1 = n_cols;
-    }
-    let mut x2 = c2 % (n_cols);
-    if x2 == 0 {
-        x2 = n_cols;
-    }
-    if x1 != n_cols {
-        y1 += 1;
-    }
-    if x2 != n_cols {
-        y2 += 1;
-    }
-
-    let mut ans = i32::MAX;
-    let mut yn = false;
-    for i in x1..x2 + 1 {
-        for j in y1..y2 + 1 {
-            yn |= err[(i + (j - 1) * n_cols) as usize];
-            if (data_base[(i + (j - 1) * n_cols) as usize]) < ans {
-                ans = data_base[(i + (j - 1) * n_cols) as usize];
-            }
-        }
-    }
-    err[dest as usize] = yn;
-    ans
+/// Paired with whether it is an error, as when there is err in the range, in which case the value is discarded by the caller.
+#[allow(clippy::too_many_arguments)]
+pub fn min(
+    c1: i32,
+    c2: i32,
+    data_base: &[i32],
+    n_cols: i32,
+    err: &[bool],
+    dest: i32,
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    options: &RangeOptions,
+) -> (i32, bool) {
+    let (values, yn) = range_values(c1, c2, data_base, n_cols, err, opers, dest, options);
+    // A range with no values left gives an error
+    let empty = values.is_empty();
+    (values.into_iter().min().unwrap_or(0), yn || empty)
 }
 
 /// Find the maximum value in a specified range of the data array.
@@ -51,41 +256,27 @@ pub fn min(c1: i32, c2: i32, data_base: &[i32], n_cols: i32, err: &mut [bool], d
 /// * `c2` - The ending cell index (1-based).
 /// * `data_base` - A reference to the data array.
 /// * `n_cols` - The number of cells in the data array.
-/// * `err` - A mutable reference to a boolean array for error checking.
-/// * `dest` - The destination index in the error array to store the error status.
+/// * `err` - A reference to the error flags of the data array.
+/// * `dest` - The index of the cell holding the function.
+/// * `opers` - The operations for each cell.
+/// * `options` - The settings of the spreadsheet (see `RangeOptions`).
 /// # Returns
 /// The maximum value found in the specified range of the data array.
-/// If there is err in the range, it sets the error flag for the destination index and the return value is discarded by the caller.
-pub fn max(c1: i32, c2: i32, data_base: &[i32], n_cols: i32, err: &mut [bool], dest: i32) -> i32 {
-    let mut y1 = c1 / n_cols;
-    let mut y2 = c2 / n_cols;
-    let mut x1 = c1 % (n_cols);
-    if x1 == 0 {
-        x1 = n_cols;
-    }
-    let mut x2 = c2 % (n_cols);
-    if x2 == 0 {
-        x2 = n_cols;
-    }
-    if x1 != n_cols {
-        y1 += 1;
-    }
-    if x2 != n_cols {
-        y2 += 1;
-    }
-
-    let mut ans = i32::MIN;
-    let mut yn = false;
-    for i in x1..x2 + 1 {
-        for j in y1..y2 + 1 {
-            yn |= err[(i + (j - 1) * n_cols) as usize];
-            if data_base[(i + (j - 1) * n_cols) as usize] > ans {
-                ans = data_base[(i + (j - 1) * n_cols) as usize];
-            }
-        }
-    }
-    err[dest as usize] = yn;
-    ans
+/// Paired with whether it is an error, as when there is err in the range, in which case the value is discarded by the caller.
+#[allow(clippy::too_many_arguments)]
+pub fn max(
+    c1: i32,
+    c2: i32,
+    data_base: &[i32],
+    n_cols: i32,
+    err: &[bool],
+    dest: i32,
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    options: &RangeOptions,
+) -> (i32, bool) {
+    let (values, yn) = range_values(c1, c2, data_base, n_cols, err, opers, dest, options);
+    let empty = values.is_empty();
+    (values.into_iter().max().unwrap_or(0), yn || empty)
 }
 
 /// Find the sum of all values in a specified range of the data array.
@@ -94,39 +285,29 @@ pub fn max(c1: i32, c2: i32, data_base: &[i32], n_cols: i32, err: &mut [bool], d
 /// * `c2` - The ending cell index (1-based).
 /// * `data_base` - A reference to the data array.
 /// * `n_cols` - The number of cells in the data array.
-/// * `err` - A mutable reference to a boolean array for error checking.
-/// * `dest` - The destination index in the error array to store the error status.
+/// * `err` - A reference to the error flags of the data array.
+/// * `dest` - The index of the cell holding the function.
+/// * `opers` - The operations for each cell.
+/// * `options` - The settings of the spreadsheet (see `RangeOptions`).
 /// # Returns
 /// The sum of all values found in the specified range.
-/// If there is err in the range, it sets the error flag for the destination index and the return value is discarded by the caller.
-pub fn sum(c1: i32, c2: i32, data_base: &[i32], n_cols: i32, err: &mut [bool], dest: i32) -> i32 {
-    let mut y1 = c1 / n_cols;
-    let mut y2 = c2 / n_cols;
-    let mut x1 = c1 % (n_cols);
-    if x1 == 0 {
-        x1 = n_cols;
-    }
-    let mut x2 = c2 % (n_cols);
-    if x2 == 0 {
-        x2 = n_cols;
-    }
-    if x1 != n_cols {
-        y1 += 1;
+/// Paired with whether it is an error, as when there is err in the range, in which case the value is discarded by the caller.
+#[allow(clippy::too_many_arguments)]
+pub fn sum(
+    c1: i32,
+    c2: i32,
+    data_base: &[i32],
+    n_cols: i32,
+    err: &[bool],
+    dest: i32,
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    options: &RangeOptions,
+) -> (i32, bool) {
+    let (values, yn) = range_values(c1, c2, data_base, n_cols, err, opers, dest, options);
+    match i32::try_from(total(&values)) {
+        Ok(ans) => (ans, yn),
+        Err(_) => (0, true),
     }
-    if x2 != n_cols {
-        y2 += 1;
-    }
-
-    let mut ans = 0;
-    let mut yn = false;
-    for i in x1..x2 + 1 {
-        for j in y1..y2 + 1 {
-            yn |= err[(i + (j - 1) * n_cols) as usize];
-            ans += data_base[(i + (j - 1) * n_cols) as usize];
-        }
-    }
-    err[dest as usize] = yn;
-    ans
 }
 
 /// Find the average of all values in a specified range of the data array.
@@ -135,41 +316,30 @@ pub fn sum(c1: i32, c2: i32, data_base: &[i32], n_cols: i32, err: &mut [bool], d
 /// * `c2` - The ending cell index (1-based).
 /// * `data_base` - A reference to the data array.
 /// * `n_cols` - The number of cells in the data array.
-/// * `err` - A mutable reference to a boolean array for error checking.
-/// * `dest` - The destination index in the error array to store the error status.
+/// * `err` - A reference to the error flags of the data array.
+/// * `dest` - The index of the cell holding the function.
+/// * `opers` - The operations for each cell.
+/// * `options` - The settings of the spreadsheet (see `RangeOptions`).
 /// # Returns
 /// The average of all values found in the specified range.
-/// If there is err in the range, it sets the error flag for the destination index and the return value is discarded by the caller.
-pub fn avg(c1: i32, c2: i32, data_base: &[i32], n_cols: i32, err: &mut [bool], dest: i32) -> i32 {
-    let mut y1 = c1 / n_cols;
-    let mut y2 = c2 / n_cols;
-    let mut x1 = c1 % (n_cols);
-    if x1 == 0 {
-        x1 = n_cols;
-    }
-    let mut x2 = c2 % (n_cols);
-    if x2 == 0 {
-        x2 = n_cols;
-    }
-    if x1 != n_cols {
-        y1 += 1;
-    }
-    if x2 != n_cols {
-        y2 += 1;
-    }
-
-    let mut ans = 0;
-    let mut ct = 0;
-    let mut yn = false;
-    for i in x1..x2 + 1 {
-        for j in y1..y2 + 1 {
-            ct += 1;
-            yn |= err[(i + (j - 1) * n_cols) as usize];
-            ans += data_base[(i + (j - 1) * n_cols) as usize];
-        }
+/// Paired with whether it is an error, as when there is err in the range, in which case the value is discarded by the caller.
+#[allow(clippy::too_many_arguments)]
+pub fn avg(
+    c1: i32,
+    c2: i32,
+    data_base: &[i32],
+    n_cols: i32,
+    err: &[bool],
+    dest: i32,
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    options: &RangeOptions,
+) -> (i32, bool) {
+    let (values, yn) = range_values(c1, c2, data_base, n_cols, err, opers, dest, options);
+    if values.is_empty() {
+        return (0, true);
     }
-    err[dest as usize] = yn;
-    ans / ct
+    // The average of values that fit in a cell fits too
+    ((total(&values) / values.len() as i64) as i32, yn)
 }
 
 /// Find the standard deviation of all values in a specified range of the data array.
@@ -178,50 +348,240 @@ pub fn avg(c1: i32, c2: i32, data_base: &[i32], n_cols: i32, err: &mut [bool], d
 /// * `c2` - The ending cell index (1-based).
 /// * `data_base` - A reference to the data array.
 /// * `n_cols` - The number of cells in the data array.
-/// * `err` - A mutable reference to a boolean array for error checking.
-/// * `dest` - The destination index in the error array to store the error status.
+/// * `err` - A reference to the error flags of the data array.
+/// * `dest` - The index of the cell holding the function.
+/// * `opers` - The operations for each cell.
+/// * `options` - The settings of the spreadsheet (see `RangeOptions`).
 /// # Returns
 /// The standard deviation of all values found in the specified range.
-/// If there is err in the range, it sets the error flag for the destination index and the return value is discarded by the caller.
-pub fn stdev(c1: i32, c2: i32, data_base: &[i32], n_cols: i32, err: &mut [bool], dest: i32) -> i32 {
-    let mut y1 = c1 / n_cols;
-    let mut y2 = c2 / n_cols;
-    let mut x1 = c1 % (n_cols);
-    if x1 == 0 {
-        x1 = n_cols;
+/// Paired with whether it is an error, as when there is err in the range, in which case the value is discarded by the caller.
+#[allow(clippy::too_many_arguments)]
+pub fn stdev(
+    c1: i32,
+    c2: i32,
+    data_base: &[i32],
+    n_cols: i32,
+    err: &[bool],
+    dest: i32,
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    options: &RangeOptions,
+) -> (i32, bool) {
+    let (values, yn) = range_values(c1, c2, data_base, n_cols, err, opers, dest, options);
+    if values.is_empty() {
+        return (0, true);
     }
-    let mut x2 = c2 % (n_cols);
-    if x2 == 0 {
-        x2 = n_cols;
-    }
-    if x1 != n_cols {
-        y1 += 1;
+    let ct = values.len() as i64;
+    let mean = total(&values) / ct;
+    let mut var = 0.0;
+    for &value in &values {
+        var += (value as i64 - mean) as f64 * (value as i64 - mean) as f64;
     }
-    if x2 != n_cols {
-        y2 += 1;
+    var /= ct as f64;
+
+    // A deviation that does not fit in a cell is an error like an overflowing sum
+    let dev = var.sqrt().round();
+    if dev > i32::MAX as f64 {
+        return (0, true);
     }
+    (dev as i32, yn)
+}
 
-    let mut var = 0.0;
-    let mut ct = 0;
-    let mut ans = 0;
-    let mut yn = false;
-    for i in x1..x2 + 1 {
-        for j in y1..y2 + 1 {
-            ct += 1;
-            yn |= err[(i + (j - 1) * n_cols) as usize];
-            ans += data_base[(i + (j - 1) * n_cols) as usize];
-        }
+/// Collects the values of a range that meet the criterion of a conditional range function.
+/// # Returns
+/// The values meeting the criterion, and whether a cell in an error state was included.
+#[allow(clippy::too_many_arguments)]
+fn matching_values(
+    c1: i32,
+    c2: i32,
+    data_base: &[i32],
+    n_cols: i32,
+    err: &[bool],
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    dest: i32,
+    options: &RangeOptions,
+) -> (Vec<i32>, bool) {
+    let (mut values, yn) = range_values(c1, c2, data_base, n_cols, err, opers, dest, options);
+    let criterion = opers[dest as usize].criterion;
+    values.retain(|&v| criterion.is_some_and(|c| c.holds(v)));
+    (values, yn)
+}
+
+/// Find the sum of the values meeting a criterion in a specified range of the data array (SUMIF).
+/// # Arguments
+/// * `c1` - The starting cell index (1-based).
+/// * `c2` - The ending cell index (1-based).
+/// * `data_base` - A reference to the data array.
+/// * `n_cols` - The number of cells in the data array.
+/// * `err` - A reference to the error flags of the data array.
+/// * `dest` - The index of the cell holding the function.
+/// * `opers` - The operations for each cell, the criterion is read from `opers[dest]`.
+/// * `options` - The settings of the spreadsheet (see `RangeOptions`).
+/// # Returns
+/// The sum of the values meeting the criterion.
+/// Paired with whether it is an error, as when there is err in the range, in which case the value is discarded by the caller.
+#[allow(clippy::too_many_arguments)]
+pub fn sum_if(
+    c1: i32,
+    c2: i32,
+    data_base: &[i32],
+    n_cols: i32,
+    err: &[bool],
+    dest: i32,
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    options: &RangeOptions,
+) -> (i32, bool) {
+    let (values, yn) = matching_values(c1, c2, data_base, n_cols, err, opers, dest, options);
+    match i32::try_from(total(&values)) {
+        Ok(ans) => (ans, yn),
+        Err(_) => (0, true),
     }
-    let mean = ans / ct;
-    for i in x1..x2 + 1 {
-        for j in y1..y2 + 1 {
-            yn |= err[(i + (j - 1) * n_cols) as usize];
-            var += (data_base[(i + (j - 1) * n_cols) as usize] - mean) as f64
-                * (data_base[(i + (j - 1) * n_cols) as usize] - mean) as f64;
-        }
+}
+
+/// Count the values meeting a criterion in a specified range of the data array (COUNTIF).
+/// # Arguments
+/// * `c1` - The starting cell index (1-based).
+/// * `c2` - The ending cell index (1-based).
+/// * `data_base` - A reference to the data array.
+/// * `n_cols` - The number of cells in the data array.
+/// * `err` - A reference to the error flags of the data array.
+/// * `dest` - The index of the cell holding the function.
+/// * `opers` - The operations for each cell, the criterion is read from `opers[dest]`.
+/// * `options` - The settings of the spreadsheet (see `RangeOptions`).
+/// # Returns
+/// The number of values meeting the criterion.
+/// Paired with whether it is an error, as when there is err in the range, in which case the value is discarded by the caller.
+#[allow(clippy::too_many_arguments)]
+pub fn count_if(
+    c1: i32,
+    c2: i32,
+    data_base: &[i32],
+    n_cols: i32,
+    err: &[bool],
+    dest: i32,
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    options: &RangeOptions,
+) -> (i32, bool) {
+    let (values, yn) = matching_values(c1, c2, data_base, n_cols, err, opers, dest, options);
+    (values.len() as i32, yn)
+}
+
+/// Find the average of the values meeting a criterion in a specified range of the data array (AVERAGEIF).
+/// # Arguments
+/// * `c1` - The starting cell index (1-based).
+/// * `c2` - The ending cell index (1-based).
+/// * `data_base` - A reference to the data array.
+/// * `n_cols` - The number of cells in the data array.
+/// * `err` - A reference to the error flags of the data array.
+/// * `dest` - The index of the cell holding the function.
+/// * `opers` - The operations for each cell, the criterion is read from `opers[dest]`.
+/// * `options` - The settings of the spreadsheet (see `RangeOptions`).
+/// # Returns
+/// The average of the values meeting the criterion, rounded towards zero.
+/// Paired with whether it is an error, as when there is err in the range or no value meets the criterion, in which case the value is discarded by the caller.
+#[allow(clippy::too_many_arguments)]
+pub fn avg_if(
+    c1: i32,
+    c2: i32,
+    data_base: &[i32],
+    n_cols: i32,
+    err: &[bool],
+    dest: i32,
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    options: &RangeOptions,
+) -> (i32, bool) {
+    let (values, yn) = matching_values(c1, c2, data_base, n_cols, err, opers, dest, options);
+    if values.is_empty() {
+        return (0, true);
     }
-    var /= ct as f64;
-    err[dest as usize] = yn;
+    ((total(&values) / values.len() as i64) as i32, yn)
+}
+
+/// Count the cells holding a value in a specified range of the data array (COUNT).
+/// Empty cells and cells in an error state are not counted.
+/// # Arguments
+/// * `c1` - The starting cell index (1-based).
+/// * `c2` - The ending cell index (1-based).
+/// * `_data_base` - A reference to the data array.
+/// * `n_cols` - The number of cells in the data array.
+/// * `err` - A reference to the error flags of the data array.
+/// * `_dest` - The index of the cell holding the function.
+/// * `opers` - The operations for each cell.
+/// * `options` - The settings of the spreadsheet (see `RangeOptions`).
+/// # Returns
+/// The number of cells holding a value, which is never an error.
+#[allow(clippy::too_many_arguments)]
+pub fn count(
+    c1: i32,
+    c2: i32,
+    _data_base: &[i32],
+    n_cols: i32,
+    err: &[bool],
+    _dest: i32,
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    options: &RangeOptions,
+) -> (i32, bool) {
+    let cells = range_filter_map(c1, c2, n_cols, options, |ind| {
+        (!opers[ind].opcpde.is_empty() && !err[ind]).then_some(())
+    });
+    (cells.len() as i32, false)
+}
+
+/// Count the cells that are not empty in a specified range of the data array (COUNTA).
+/// Cells in an error state are counted.
+/// # Arguments
+/// * `c1` - The starting cell index (1-based).
+/// * `c2` - The ending cell index (1-based).
+/// * `_data_base` - A reference to the data array.
+/// * `n_cols` - The number of cells in the data array.
+/// * `_err` - A reference to the error flags of the data array.
+/// * `_dest` - The index of the cell holding the function.
+/// * `opers` - The operations for each cell.
+/// * `options` - The settings of the spreadsheet (see `RangeOptions`).
+/// # Returns
+/// The number of cells that were assigned, which is never an error.
+#[allow(clippy::too_many_arguments)]
+pub fn count_a(
+    c1: i32,
+    c2: i32,
+    _data_base: &[i32],
+    n_cols: i32,
+    _err: &[bool],
+    _dest: i32,
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    options: &RangeOptions,
+) -> (i32, bool) {
+    let cells = range_filter_map(c1, c2, n_cols, options, |ind| {
+        (!opers[ind].opcpde.is_empty()).then_some(())
+    });
+    (cells.len() as i32, false)
+}
 
-    var.sqrt().round() as i32
+/// Count the empty cells, which were never assigned, in a specified range of the data array (COUNTBLANK).
+/// Cells holding 0 are not empty.
+/// # Arguments
+/// * `c1` - The starting cell index (1-based).
+/// * `c2` - The ending cell index (1-based).
+/// * `_data_base` - A reference to the data array.
+/// * `n_cols` - The number of cells in the data array.
+/// * `_err` - A reference to the error flags of the data array.
+/// * `_dest` - The index of the cell holding the function.
+/// * `opers` - The operations for each cell.
+/// * `options` - The settings of the spreadsheet (see `RangeOptions`).
+/// # Returns
+/// The number of empty cells, which is never an error.
+#[allow(clippy::too_many_arguments)]
+pub fn count_blank(
+    c1: i32,
+    c2: i32,
+    _data_base: &[i32],
+    n_cols: i32,
+    _err: &[bool],
+    _dest: i32,
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    options: &RangeOptions,
+) -> (i32, bool) {
+    let cells = range_filter_map(c1, c2, n_cols, options, |ind| {
+        opers[ind].opcpde.is_empty().then_some(())
+    });
+    (cells.len() as i32, false)
 }
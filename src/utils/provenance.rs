@@ -0,0 +1,100 @@
+//! This module contains the provenance of imported cells, which records where their values
+//! came from and when.
+//!
+//! Cells filled by an import, such as text pasted from another application, are tagged with
+//! the source and the time of the import. The tag is shown in the cell tooltip and written to
+//! the log, and is dropped when the cell is assigned again, since its value no longer comes
+//! from the import.
+
+use std::collections::BTreeMap;
+
+/// Where the value of an imported cell came from.
+///
+/// # Fields
+/// * `source` - What the value was imported from, such as "clipboard"
+/// * `time` - When it was imported, in seconds since 1970-01-01 UTC
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub source: String,
+    pub time: i64,
+}
+
+impl Provenance {
+    /// Describes the provenance as shown in the cell tooltip.
+    pub fn text(&self) -> String {
+        match chrono::DateTime::from_timestamp(self.time, 0) {
+            Some(time) => format!(
+                "Imported from {} on {}",
+                self.source,
+                time.format("%Y-%m-%d %H:%M:%S UTC")
+            ),
+            None => format!("Imported from {}", self.source),
+        }
+    }
+}
+
+/// Tags the cells assigned by an import with their source.
+/// # Arguments
+/// * `tags` - The provenance of each imported cell, updated in place.
+/// * `commands` - The assignments of the import, such as "B2=5".
+/// * `source` - What the values were imported from.
+/// * `time` - When they were imported, in seconds since 1970-01-01 UTC.
+/// * `len_h` - The number of columns in the spreadsheet.
+pub fn tag(
+    tags: &mut BTreeMap<i32, Provenance>,
+    commands: &[String],
+    source: &str,
+    time: i64,
+    len_h: i32,
+) {
+    for command in commands {
+        let Some((cell, _)) = command.split_once('=') else {
+            continue;
+        };
+        let provenance = Provenance {
+            source: source.to_string(),
+            time,
+        };
+        tags.insert(crate::cell_to_ind(cell, len_h), provenance);
+    }
+    tracing::info!(source, cells = commands.len(), "cells imported");
+}
+
+/// Moves the tags along with their cells when a row or column is inserted or deleted.
+/// # Arguments
+/// * `tags` - The provenance of each imported cell, updated in place.
+/// * `edit` - The inserted or deleted row or column.
+/// * `len_h` - The number of columns in the spreadsheet.
+pub fn edit_lines(
+    tags: &mut BTreeMap<i32, Provenance>,
+    edit: crate::utils::structure::LineEdit,
+    len_h: i32,
+) {
+    *tags = std::mem::take(tags)
+        .into_iter()
+        .filter_map(|(ind, provenance)| Some((edit.move_cell(ind, len_h)?, provenance)))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag() {
+        let mut tags = BTreeMap::new();
+        let commands = [String::from("B1=5"), String::from("A2=7")];
+        tag(&mut tags, &commands, "clipboard", 1715212800, 3);
+        assert_eq!(tags.keys().copied().collect::<Vec<_>>(), [2, 4]);
+        assert_eq!(
+            tags[&2].text(),
+            "Imported from clipboard on 2024-05-09 00:00:00 UTC"
+        );
+
+        let edit = |command| crate::utils::structure::LineEdit::parse(command, 3, 4).unwrap();
+        edit_lines(&mut tags, edit("insert_row 2"), 3);
+        assert_eq!(tags.keys().copied().collect::<Vec<_>>(), [2, 7]);
+        edit_lines(&mut tags, edit("delete_col B"), 3);
+        assert_eq!(tags.keys().copied().collect::<Vec<_>>(), [7]);
+    }
+}
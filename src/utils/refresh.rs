@@ -0,0 +1,185 @@
+//! This module contains the auto refresh of the GUI, which recalculates the sheet every few
+//! seconds so that cells using TODAY or NOW stay current, like a live dashboard.
+//!
+//! Auto refresh is set with `auto_refresh <seconds>` and stopped with `auto_refresh off`.
+//! It can be paused and resumed with `auto_refresh pause` and `auto_refresh resume`, or with
+//! the countdown shown in the menu bar. The interval is saved with the workbook; whether it
+//! is paused is not. Nothing is refreshed in safe mode, where TODAY and NOW are frozen anyway
+//! (see `settings::Limits::now`).
+
+use std::time::{Duration, Instant};
+
+/// Longest interval that can be set, in seconds (a day).
+pub const MAX_INTERVAL: u64 = 86400;
+
+/// Auto refresh settings and the time of the next refresh.
+///
+/// # Fields
+/// * `interval` - Seconds between refreshes, or `None` if auto refresh is off
+/// * `paused` - Whether refreshes are paused
+/// * `next` - When the next refresh is due, set on the first check after it is turned on
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct Refresh {
+    pub interval: Option<u64>,
+    #[serde(skip)]
+    pub paused: bool,
+    #[serde(skip)]
+    next: Option<Instant>,
+}
+
+impl Refresh {
+    /// Runs an `auto_refresh <seconds|off|pause|resume>` command.
+    /// # Arguments
+    /// * `command` - The full command string
+    /// * `now` - The current time
+    /// # Returns
+    /// "ok" if the setting was changed, otherwise "Invalid Refresh".
+    pub fn command(&mut self, command: &str, now: Instant) -> &'static str {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let [_, value] = words[..] else {
+            return "Invalid Refresh";
+        };
+        match value {
+            "off" => self.interval = None,
+            "pause" | "resume" if self.interval.is_some() => {
+                self.paused = value == "pause";
+                self.next = None;
+            }
+            _ => match value.parse::<u64>() {
+                Ok(seconds) if (1..=MAX_INTERVAL).contains(&seconds) => {
+                    self.interval = Some(seconds);
+                    self.paused = false;
+                    self.next = Some(now + Duration::from_secs(seconds));
+                }
+                _ => return "Invalid Refresh",
+            },
+        }
+        "ok"
+    }
+
+    /// Pauses refreshes if they are running, or resumes them if they are paused.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.next = None;
+    }
+
+    /// Checks whether a refresh is due, and if so schedules the one after it.
+    /// # Arguments
+    /// * `now` - The current time
+    pub fn due(&mut self, now: Instant) -> bool {
+        let Some(seconds) = self.interval.filter(|_| !self.paused) else {
+            return false;
+        };
+        let interval = Duration::from_secs(seconds);
+        match self.next {
+            Some(next) if now < next => false,
+            Some(_) => {
+                self.next = Some(now + interval);
+                true
+            }
+            None => {
+                self.next = Some(now + interval);
+                false
+            }
+        }
+    }
+
+    /// Gives the countdown to show, such as "Refresh in 12 s" or "Refresh paused".
+    /// # Arguments
+    /// * `now` - The current time
+    /// # Returns
+    /// The text, or `None` if auto refresh is off.
+    pub fn label(&self, now: Instant) -> Option<String> {
+        self.interval?;
+        if self.paused {
+            return Some(String::from("Refresh paused"));
+        }
+        let left = self
+            .next
+            .map_or(Duration::ZERO, |n| n.saturating_duration_since(now));
+        // Rounded up, so the countdown reaches 0 s only when the refresh is due
+        Some(format!(
+            "Refresh in {} s",
+            left.as_secs() + (left.subsec_nanos() > 0) as u64
+        ))
+    }
+}
+
+/// Checks whether a cell's value changes over time, because its formula calls TODAY or NOW.
+pub fn is_volatile(op: &crate::Ops) -> bool {
+    op.opcpde == crate::utils::expr::EXP_OP && op.expr.as_ref().is_some_and(|e| e.is_volatile())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let now = Instant::now();
+        let mut refresh = Refresh::default();
+        assert_eq!(
+            refresh.command("auto_refresh pause", now),
+            "Invalid Refresh"
+        );
+        for bad in [
+            "auto_refresh",
+            "auto_refresh 0",
+            "auto_refresh 90000",
+            "auto_refresh x",
+        ] {
+            assert_eq!(refresh.command(bad, now), "Invalid Refresh", "{}", bad);
+        }
+        assert_eq!(refresh.interval, None);
+
+        assert_eq!(refresh.command("auto_refresh 10", now), "ok");
+        assert_eq!(refresh.interval, Some(10));
+        assert_eq!(refresh.command("auto_refresh pause", now), "ok");
+        assert!(refresh.paused);
+        assert_eq!(refresh.command("auto_refresh resume", now), "ok");
+        assert!(!refresh.paused);
+        assert_eq!(refresh.command("auto_refresh off", now), "ok");
+        assert_eq!(refresh.interval, None);
+    }
+
+    #[test]
+    fn test_due() {
+        let now = Instant::now();
+        let later = |seconds| now + Duration::from_secs(seconds);
+        let mut refresh = Refresh::default();
+        assert!(!refresh.due(now));
+        assert_eq!(refresh.label(now), None);
+
+        refresh.command("auto_refresh 10", now);
+        assert_eq!(refresh.label(later(3)).as_deref(), Some("Refresh in 7 s"));
+        assert!(!refresh.due(later(9)));
+        assert!(refresh.due(later(10)));
+        assert!(!refresh.due(later(15)));
+        assert!(refresh.due(later(20)));
+
+        refresh.toggle_pause();
+        assert_eq!(refresh.label(later(25)).as_deref(), Some("Refresh paused"));
+        assert!(!refresh.due(later(40)));
+        // Resuming waits a full interval before the next refresh
+        refresh.toggle_pause();
+        assert!(!refresh.due(later(41)));
+        assert!(!refresh.due(later(50)));
+        assert!(refresh.due(later(51)));
+    }
+
+    #[test]
+    fn test_is_volatile() {
+        let op = |formula: &str| {
+            let mut sheet = crate::utils::sheet::Sheet::new(3, 3);
+            let out = sheet
+                .parse_assignment(&format!("A1={}", formula))
+                .expect(formula);
+            sheet.update(&out).unwrap();
+            sheet.opers()[1].clone()
+        };
+        assert!(is_volatile(&op("TODAY()")));
+        assert!(is_volatile(&op("NOW()-B1")));
+        assert!(!is_volatile(&op("DATE(2024, 5, 1)")));
+        assert!(!is_volatile(&op("B1+1")));
+    }
+}
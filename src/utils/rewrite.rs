@@ -0,0 +1,249 @@
+//! This module contains formula rewrites, which change a reference or a function in every
+//! formula of the sheet at once.
+//!
+//! Unlike replace (see `utils::find`), a rewrite reads the formulas rather than their text,
+//! so `rewrite col B C` changes B1 and B10 but not AB1, and `rewrite fn SUM AVG` changes the
+//! function but not a column named SUM:
+//!
+//! ```text
+//! rewrite col B C
+//! rewrite fn SUM AVG
+//! ```
+//!
+//! The changed cells are listed before the rewrite is applied, and the rewrite is applied as
+//! one batch: if any new formula is invalid or makes a cycle, no cell is changed.
+
+use crate::Ops;
+use crate::utils::cellref::CellRef;
+use crate::utils::cells::Cells;
+use crate::utils::sheet::Sheet;
+
+/// What a rewrite changes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rewrite {
+    /// References to a column become references to another column, keeping their row.
+    Column { from: i32, to: i32 },
+    /// Calls of a function become calls of another function.
+    Function { from: String, to: String },
+}
+
+impl Rewrite {
+    /// Parses a `rewrite col <from> <to>` or `rewrite fn <from> <to>` command.
+    /// # Arguments
+    /// * `command` - The full command string.
+    /// * `len_h` - The number of columns in the spreadsheet.
+    /// # Returns
+    /// The rewrite, or `None` if the command is invalid or names an unknown function.
+    pub fn parse(command: &str, len_h: i32) -> Option<Self> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let ["rewrite", kind, from, to] = words[..] else {
+            return None;
+        };
+        match kind {
+            "col" => Some(Self::Column {
+                from: crate::utils::input::parse_col(from, len_h)?,
+                to: crate::utils::input::parse_col(to, len_h)?,
+            }),
+            "fn" => Some(Self::Function {
+                from: crate::utils::functions::find(from)?.name.to_string(),
+                to: crate::utils::functions::find(to)?.name.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Rewrites the text of a formula.
+    fn formula(&self, formula: &str) -> String {
+        map_names(formula, |name, call| match self {
+            Self::Function { from, to } if call && name.eq_ignore_ascii_case(from) => {
+                Some(to.clone())
+            }
+            Self::Column { from, to } if !call => {
+                CellRef::parse(name).filter(|c| c.col == *from).map(|c| {
+                    CellRef {
+                        col: *to,
+                        row: c.row,
+                    }
+                    .to_string()
+                })
+            }
+            _ => None,
+        })
+    }
+}
+
+/// Replaces names in the text of a formula.
+///
+/// Names followed by an opening parenthesis are functions and other names are cells.
+/// Criteria in quotes are left alone.
+/// # Arguments
+/// * `formula` - The text of the formula.
+/// * `f` - Gives the replacement of a name, told whether it is a function, or `None` to keep it.
+pub fn map_names(formula: &str, mut f: impl FnMut(&str, bool) -> Option<String>) -> String {
+    let mut text = String::new();
+    let mut rest = formula;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric() || c == '"') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').map_or(rest.len(), |e| e + 2);
+            text.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+        rest = &rest[end..];
+        let call = rest.trim_start().starts_with('(');
+        text.push_str(f(name, call).as_deref().unwrap_or(name));
+    }
+    text.push_str(rest);
+    text
+}
+
+/// A formula changed by a rewrite.
+///
+/// # Fields
+/// * `cell` - Index of the cell
+/// * `before` - The formula before the rewrite
+/// * `after` - The formula after the rewrite
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub cell: i32,
+    pub before: String,
+    pub after: String,
+}
+
+impl Change {
+    /// Writes the change as shown in the preview, such as "B3: =SUM(B1:B2) -> =SUM(C1:C2)".
+    pub fn text(&self, len_h: i32) -> String {
+        format!(
+            "{}: ={} -> ={}",
+            crate::ind_to_cell(self.cell, len_h),
+            self.before,
+            self.after
+        )
+    }
+}
+
+/// Finds the formulas a rewrite changes.
+/// # Arguments
+/// * `rewrite` - The rewrite.
+/// * `opers` - The operations for each cell.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// # Returns
+/// The changed formulas, row by row.
+pub fn preview(rewrite: &Rewrite, opers: &Cells<Ops>, len_h: i32) -> Vec<Change> {
+    (1..opers.len() as i32)
+        .filter_map(|cell| {
+            let before = crate::utils::input::formula_text(&opers[cell as usize], len_h);
+            let after = rewrite.formula(&before);
+            (after != before).then_some(Change {
+                cell,
+                before,
+                after,
+            })
+        })
+        .collect()
+}
+
+/// Applies the changes of a rewrite as one batch.
+/// # Arguments
+/// * `sheet` - The spreadsheet, left unchanged.
+/// * `changes` - The changes, as given by `preview`.
+/// # Returns
+/// The spreadsheet with every change applied, or a message naming the first change that
+/// could not be applied, in which case none is.
+pub fn apply(sheet: &Sheet, changes: &[Change]) -> Result<Sheet, String> {
+    let mut sheet = sheet.clone();
+    for change in changes {
+        let cell = crate::ind_to_cell(change.cell, sheet.len_h);
+        sheet
+            .parse_assignment(&format!("{}={}", cell, change.after))
+            .and_then(|out| sheet.update(&out))
+            .map_err(|e| format!("{} would be ={}: {}", cell, change.after, e))?;
+    }
+    Ok(sheet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet(commands: &[&str]) -> Sheet {
+        let mut sheet = Sheet::new(3, 4);
+        for command in commands {
+            let out = sheet.parse_assignment(command).expect(command);
+            sheet.update(&out).unwrap();
+        }
+        sheet
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Rewrite::parse("rewrite col b C", 3),
+            Some(Rewrite::Column { from: 2, to: 3 })
+        );
+        assert_eq!(
+            Rewrite::parse("rewrite fn sum AVG", 3),
+            Some(Rewrite::Function {
+                from: String::from("SUM"),
+                to: String::from("AVG")
+            })
+        );
+        for bad in [
+            "rewrite col B",
+            "rewrite col B D",
+            "rewrite fn SUM TOTAL",
+            "rewrite row 1 2",
+            "rewrite col B C extra",
+        ] {
+            assert_eq!(Rewrite::parse(bad, 3), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_formula() {
+        let column = Rewrite::Column { from: 2, to: 3 };
+        assert_eq!(column.formula("SUM(A1:B4)"), "SUM(A1:C4)");
+        assert_eq!(column.formula("B1+B10*2"), "C1+C10*2");
+        assert_eq!(
+            column.formula("COUNTIF(B1:B2, \">5\")"),
+            "COUNTIF(C1:C2, \">5\")"
+        );
+        assert_eq!(column.formula("AB1+A1"), "AB1+A1");
+        let function = Rewrite::Function {
+            from: String::from("SUM"),
+            to: String::from("AVG"),
+        };
+        assert_eq!(function.formula("sum(A1:B2)"), "AVG(A1:B2)");
+        assert_eq!(function.formula("SUM_SKIPERR(A1:B2)"), "SUM_SKIPERR(A1:B2)");
+    }
+
+    #[test]
+    fn test_apply() {
+        let column = Rewrite::Column { from: 2, to: 3 };
+        let before = sheet(&["B1=5", "C1=7", "A2=SUM(A1:B1)", "A3=B1*2", "C2=A1+1"]);
+        let changes = preview(&column, before.opers(), 3);
+        assert_eq!(
+            changes.iter().map(|c| c.text(3)).collect::<Vec<_>>(),
+            ["A2: =SUM(A1:B1) -> =SUM(A1:C1)", "A3: =B1*2 -> =C1*2"]
+        );
+        let after = apply(&before, &changes).unwrap();
+        assert_eq!(after.database()[4], 12);
+        assert_eq!(after.database()[7], 14);
+        assert_eq!(before.database()[7], 10);
+
+        // A2 reading C2 would make a cycle, so nothing is changed
+        let before = sheet(&["B1=5", "C2=A2+1", "A2=B2+1", "A3=B1"]);
+        let changes = preview(&column, before.opers(), 3);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            apply(&before, &changes).unwrap_err(),
+            "A2 would be =C2+1: cycle_detected"
+        );
+    }
+}
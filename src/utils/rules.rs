@@ -0,0 +1,343 @@
+//! This module contains display rules, which change how the numbers in a range are shown
+//! without changing their values.
+//!
+//! A rule can show negatives in red with parentheses, such as (25) for -25, and color values
+//! above or below a threshold. Rules are set with `rule <range> <options>` and removed with
+//! `unrule <range>`, where the range is a column such as `B` or a block such as `B1:D10`:
+//!
+//! ```text
+//! rule B negative
+//! rule C1:C20 above 1000 green below 100 orange
+//! unrule C
+//! ```
+//!
+//! When rules overlap, the rule set last decides the color. Dates and times are not changed.
+
+use serde::{Deserialize, Serialize};
+
+/// A color a display rule can show values in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    Red,
+    Green,
+    Blue,
+    Orange,
+    Gray,
+}
+
+impl Tone {
+    /// All colors, in the order they are listed to the user.
+    pub const ALL: [Tone; 5] = [Tone::Red, Tone::Green, Tone::Blue, Tone::Orange, Tone::Gray];
+
+    /// Finds a color by name, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|t| t.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Gives the name of the color as typed.
+    pub fn name(self) -> &'static str {
+        match self {
+            Tone::Red => "red",
+            Tone::Green => "green",
+            Tone::Blue => "blue",
+            Tone::Orange => "orange",
+            Tone::Gray => "gray",
+        }
+    }
+
+    /// Gives the color as RGB, readable on both light and dark backgrounds.
+    pub fn rgb(self) -> [u8; 3] {
+        match self {
+            Tone::Red => [220, 50, 50],
+            Tone::Green => [40, 160, 70],
+            Tone::Blue => [50, 110, 220],
+            Tone::Orange => [230, 130, 20],
+            Tone::Gray => [140, 140, 140],
+        }
+    }
+}
+
+/// How the numbers in a range are shown.
+///
+/// # Fields
+/// * `cell1` - Top-left cell of the range
+/// * `cell2` - Bottom-right cell of the range
+/// * `negative` - Whether negatives are shown in red with parentheses
+/// * `above` - Values greater than this threshold are shown in this color
+/// * `below` - Values less than this threshold are shown in this color
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DisplayRule {
+    pub cell1: i32,
+    pub cell2: i32,
+    pub negative: bool,
+    pub above: Option<(i32, Tone)>,
+    pub below: Option<(i32, Tone)>,
+}
+
+impl DisplayRule {
+    /// Checks whether a range overlaps the range of the rule.
+    /// # Arguments
+    /// * `bounds` - First column, first row, last column and last row of the range.
+    /// * `len_h` - The number of columns in the spreadsheet.
+    fn overlaps(&self, (x1, y1, x2, y2): (i32, i32, i32, i32), len_h: i32) -> bool {
+        let (a1, b1, a2, b2) = crate::utils::graph::range_bounds(self.cell1, self.cell2, len_h);
+        x1 <= a2 && a1 <= x2 && y1 <= b2 && b1 <= y2
+    }
+
+    /// Writes the rule as the command that sets it.
+    pub fn text(&self, len_h: i32) -> String {
+        let mut text = format!(
+            "rule {}:{}",
+            crate::ind_to_cell(self.cell1, len_h),
+            crate::ind_to_cell(self.cell2, len_h)
+        );
+        if self.negative {
+            text.push_str(" negative");
+        }
+        for (name, threshold) in [("above", self.above), ("below", self.below)] {
+            if let Some((value, tone)) = threshold {
+                text.push_str(&format!(" {} {} {}", name, value, tone.name()));
+            }
+        }
+        text
+    }
+}
+
+/// Parses a column such as "B" or a block such as "B1:D10".
+/// # Returns
+/// First column, first row, last column and last row, or `None` if the range is invalid.
+pub fn parse_bounds(range: &str, len_h: i32, len_v: i32) -> Option<(i32, i32, i32, i32)> {
+    if range.contains(':') {
+        crate::utils::input::parse_range(range, len_h, len_v)
+    } else {
+        let col = crate::utils::input::parse_col(range, len_h)?;
+        Some((col, 1, col, len_v))
+    }
+}
+
+/// Parses the options of a `rule` command, such as `negative above 100 green`.
+/// # Returns
+/// The options as (negative, above, below), or `None` if they are invalid or empty.
+#[allow(clippy::type_complexity)]
+fn parse_options(words: &[&str]) -> Option<(bool, Option<(i32, Tone)>, Option<(i32, Tone)>)> {
+    let (mut negative, mut above, mut below) = (false, None, None);
+    let mut words = words.iter().peekable();
+    while let Some(word) = words.next() {
+        match *word {
+            "negative" => negative = true,
+            "above" | "below" => {
+                let value = words.next()?.parse::<i32>().ok()?;
+                let tone = words.peek().and_then(|w| Tone::parse(w));
+                if tone.is_some() {
+                    words.next();
+                }
+                if *word == "above" {
+                    above = Some((value, tone.unwrap_or(Tone::Green)));
+                } else {
+                    below = Some((value, tone.unwrap_or(Tone::Red)));
+                }
+            }
+            _ => return None,
+        }
+    }
+    (negative || above.is_some() || below.is_some()).then_some((negative, above, below))
+}
+
+/// Runs a `rule <range> <options>` or `unrule <range>` command.
+///
+/// The options are `negative`, `above <value> [color]` and `below <value> [color]`. Values
+/// above a threshold are green and values below it are red unless a color is given.
+/// `unrule` removes every rule overlapping the range.
+/// # Arguments
+/// * `command` - The full command string.
+/// * `rules` - The display rules, updated in place.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// * `len_v` - The number of rows in the spreadsheet.
+/// # Returns
+/// "ok" if the rules were updated, otherwise "Invalid Rule".
+pub fn rule_command(
+    command: &str,
+    rules: &mut Vec<DisplayRule>,
+    len_h: i32,
+    len_v: i32,
+) -> &'static str {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let Some(bounds) = words.get(1).and_then(|r| parse_bounds(r, len_h, len_v)) else {
+        return "Invalid Rule";
+    };
+    match words[0] {
+        "rule" => {
+            let Some((negative, above, below)) = parse_options(&words[2..]) else {
+                return "Invalid Rule";
+            };
+            let (x1, y1, x2, y2) = bounds;
+            rules.push(DisplayRule {
+                cell1: x1 + (y1 - 1) * len_h,
+                cell2: x2 + (y2 - 1) * len_h,
+                negative,
+                above,
+                below,
+            });
+        }
+        "unrule" if words.len() == 2 => rules.retain(|r| !r.overlaps(bounds, len_h)),
+        _ => return "Invalid Rule",
+    }
+    "ok"
+}
+
+/// Applies the display rules to the value of a cell.
+/// # Arguments
+/// * `rules` - The display rules.
+/// * `ind` - The index of the cell.
+/// * `value` - The value of the cell.
+/// * `text` - The value as shown without rules, in its number format if it has one.
+/// * `number` - Whether the value is a number rather than a date or time.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// # Returns
+/// The text to show and its color, or `None` for the normal color.
+pub fn apply(
+    rules: &[DisplayRule],
+    ind: i32,
+    value: i32,
+    text: String,
+    number: bool,
+    len_h: i32,
+) -> (String, Option<[u8; 3]>) {
+    // Dates and times are left alone
+    if !number {
+        return (text, None);
+    }
+    let col = (ind - 1) % len_h + 1;
+    let row = (ind - 1) / len_h + 1;
+    let mut shown = (text.clone(), None);
+    for rule in rules {
+        if !rule.overlaps((col, row, col, row), len_h) {
+            continue;
+        }
+        if rule.negative && value < 0 {
+            let digits = text.strip_prefix('-').unwrap_or(&text);
+            shown = (format!("({})", digits), Some(Tone::Red.rgb()));
+        } else if let Some((_, tone)) = rule.above.filter(|&(t, _)| value > t) {
+            shown.1 = Some(tone.rgb());
+        } else if let Some((_, tone)) = rule.below.filter(|&(t, _)| value < t) {
+            shown.1 = Some(tone.rgb());
+        }
+    }
+    shown
+}
+
+/// Moves the rules along with their cells when a row or column is inserted or deleted.
+///
+/// Rules covering only a deleted line, or only lines moved off the end, are removed.
+/// # Arguments
+/// * `rules` - The display rules, updated in place.
+/// * `edit` - The inserted or deleted row or column.
+/// * `len_h` - The number of columns in the spreadsheet.
+pub fn edit_lines(
+    rules: &mut Vec<DisplayRule>,
+    edit: crate::utils::structure::LineEdit,
+    len_h: i32,
+) {
+    *rules = std::mem::take(rules)
+        .into_iter()
+        .filter_map(|mut rule| {
+            (rule.cell1, rule.cell2) = edit.move_range(rule.cell1, rule.cell2, len_h)?;
+            Some(rule)
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_command() {
+        let mut rules = Vec::new();
+        assert_eq!(rule_command("rule B negative", &mut rules, 3, 4), "ok");
+        assert_eq!(
+            rule_command("rule A1:C2 above 10 below 0 blue", &mut rules, 3, 4),
+            "ok"
+        );
+        assert_eq!(rules[0].text(3), "rule B1:B4 negative");
+        assert_eq!(rules[1].text(3), "rule A1:C2 above 10 green below 0 blue");
+
+        for bad in [
+            "rule B",
+            "rule D negative",
+            "rule B1:A1 negative",
+            "rule B above",
+            "rule B above x",
+            "rule B bold",
+            "unrule",
+            "unrule B extra",
+        ] {
+            assert_eq!(
+                rule_command(bad, &mut rules, 3, 4),
+                "Invalid Rule",
+                "{}",
+                bad
+            );
+        }
+        assert_eq!(rules.len(), 2);
+
+        assert_eq!(rule_command("unrule C2", &mut rules, 3, 4), "Invalid Rule");
+        assert_eq!(rule_command("unrule A3:A4", &mut rules, 3, 4), "ok");
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rule_command("unrule A1:A1", &mut rules, 3, 4), "ok");
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rule_command("unrule B", &mut rules, 3, 4), "ok");
+        assert!(rules.is_empty());
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut rules = Vec::new();
+        rule_command("rule A negative above 100", &mut rules, 3, 4);
+        rule_command("rule A3:B4 below 5 orange", &mut rules, 3, 4);
+        let apply = |ind: i32, value: i32| apply(&rules, ind, value, value.to_string(), true, 3);
+        let red = Some(Tone::Red.rgb());
+
+        assert_eq!(apply(1, -25), (String::from("(25)"), red));
+        assert_eq!(
+            apply(1, 150),
+            (String::from("150"), Some(Tone::Green.rgb()))
+        );
+        assert_eq!(apply(1, 50), (String::from("50"), None));
+        assert_eq!(apply(2, -25), (String::from("-25"), None));
+        // A3 is covered by both rules, and the last one decides the color
+        assert_eq!(apply(7, 3), (String::from("3"), Some(Tone::Orange.rgb())));
+        assert_eq!(
+            apply(7, -3),
+            (String::from("(3)"), Some(Tone::Orange.rgb()))
+        );
+        assert_eq!(apply(8, -3), (String::from("-3"), Some(Tone::Orange.rgb())));
+        assert_eq!(
+            super::apply(&rules, 1, -1, String::from("1969-12-31"), false, 3),
+            (String::from("1969-12-31"), None)
+        );
+        assert_eq!(
+            super::apply(&rules, 1, -1234, String::from("-$1,234"), true, 3),
+            (String::from("($1,234)"), red)
+        );
+    }
+
+    #[test]
+    fn test_edit_lines() {
+        let mut rules = Vec::new();
+        rule_command("rule B negative", &mut rules, 3, 4);
+        rule_command("rule C1:C2 negative", &mut rules, 3, 4);
+        let edit = |command| crate::utils::structure::LineEdit::parse(command, 3, 4).unwrap();
+
+        edit_lines(&mut rules, edit("delete_row 1"), 3);
+        assert_eq!(rules[0].text(3), "rule B1:B3 negative");
+        assert_eq!(rules[1].text(3), "rule C1:C1 negative");
+        edit_lines(&mut rules, edit("delete_col B"), 3);
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].text(3), "rule B1:B1 negative");
+        edit_lines(&mut rules, edit("insert_row 1"), 3);
+        assert_eq!(rules[0].text(3), "rule B2:B2 negative");
+    }
+}
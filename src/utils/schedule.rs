@@ -0,0 +1,189 @@
+//! This module contains the schedule of cells waiting on SLEEP.
+//!
+//! SLEEP does not block the thread evaluating it. Its value is computed at once, and the time
+//! it would have been ready at is recorded instead. The cell, and every cell reading it, is
+//! pending until then: the terminal prints "..." for it and the GUI marks it as being
+//! recalculated. A cell reading a pending cell only starts its own wait once that cell
+//! resolves, so sleeps one after another add up as when SLEEP blocked.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// When the cells waiting on SLEEP resolve.
+///
+/// # Fields
+/// * `ready` - The time each pending cell resolves at, by cell index
+#[derive(Debug, Clone, Default)]
+pub struct Schedule {
+    ready: HashMap<i32, Instant>,
+}
+
+impl Schedule {
+    /// Schedules the cells recomputed by an update.
+    /// # Arguments
+    /// * `cell` - The updated cell
+    /// * `sensi` - The sensitivity list of the spreadsheet
+    /// * `opers` - The operations for each cell
+    /// * `database` - The values of each cell, after the update
+    /// * `err` - Whether each cell is in an error state, after the update
+    /// * `len_h` - The number of columns in the spreadsheet
+    /// * `limits` - The limits of the spreadsheet, as safe mode keeps SLEEP from waiting
+    /// * `now` - The time of the update
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        cell: i32,
+        sensi: &Cells<Vec<i32>>,
+        opers: &Cells<Ops>,
+        database: &[i32],
+        err: &[bool],
+        len_h: i32,
+        limits: &crate::utils::settings::Limits,
+        now: Instant,
+    ) {
+        self.ready.retain(|_, ready| *ready > now);
+        let order = order(sensi, cell);
+        if self.ready.is_empty()
+            && !order
+                .iter()
+                .any(|&c| opers[c as usize].opcpde.starts_with("SL"))
+        {
+            return;
+        }
+        for c in order {
+            let op = &opers[c as usize];
+            let start = crate::utils::graph::precedents(op, len_h)
+                .into_iter()
+                .filter_map(|p| self.ready.get(&p).copied())
+                .max()
+                .unwrap_or(now);
+            let wait = match op.opcpde.as_str() {
+                "SLV" => limits.sleep_duration(op.cell1),
+                "SLC" if !err[op.cell1 as usize] => {
+                    limits.sleep_duration(database[op.cell1 as usize])
+                }
+                _ => Duration::ZERO,
+            };
+            let ready = start + wait;
+            if ready > now {
+                self.ready.insert(c, ready);
+            } else {
+                self.ready.remove(&c);
+            }
+        }
+    }
+
+    /// Gives how long a cell is still pending.
+    /// # Arguments
+    /// * `cell` - The cell index (1-based)
+    /// * `now` - The current time
+    /// # Returns
+    /// The time left until the cell resolves, or `None` if it is not pending.
+    pub fn left(&self, cell: i32, now: Instant) -> Option<Duration> {
+        let ready = *self.ready.get(&cell)?;
+        (ready > now).then(|| ready - now)
+    }
+
+    /// Checks whether a cell is still waiting on SLEEP.
+    /// # Arguments
+    /// * `cell` - The cell index (1-based)
+    /// * `now` - The current time
+    pub fn is_pending(&self, cell: i32, now: Instant) -> bool {
+        self.left(cell, now).is_some()
+    }
+
+    /// Gives the time the next pending cell resolves at, if any, so a GUI knows when to repaint.
+    /// # Arguments
+    /// * `now` - The current time
+    pub fn next(&self, now: Instant) -> Option<Instant> {
+        self.ready.values().copied().filter(|&t| t > now).min()
+    }
+}
+
+/// Gives a cell and all its direct and indirect dependents in topological order.
+/// # Arguments
+/// * `sensi` - The sensitivity list of the spreadsheet
+/// * `cell` - The cell index (1-based)
+fn order(sensi: &Cells<Vec<i32>>, cell: i32) -> Vec<i32> {
+    // Only the cells reached are counted, so an update costs nothing for the rest of the grid
+    let mut indegree: HashMap<i32, usize> = HashMap::new();
+    let mut seen = HashSet::from([cell]);
+    let mut stack = vec![cell];
+    while let Some(c) = stack.pop() {
+        for d in crate::utils::graph::dependents(sensi, c) {
+            *indegree.entry(d).or_default() += 1;
+            if seen.insert(d) {
+                stack.push(d);
+            }
+        }
+    }
+    let mut res = Vec::new();
+    let mut q = VecDeque::from([cell]);
+    while let Some(c) = q.pop_front() {
+        res.push(c);
+        for d in crate::utils::graph::dependents(sensi, c) {
+            let n = indegree.get_mut(&d).unwrap();
+            *n -= 1;
+            if *n == 0 {
+                q.push_back(d);
+            }
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::sheet::Sheet;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_sleeps_add_up() {
+        let mut sheet = Sheet::new(3, 3);
+        let start = Instant::now();
+        for (cell, formula) in [
+            ("A1", "=SLEEP(2)"),
+            ("B1", "=SLEEP(A1)"),
+            ("C1", "=B1+1"),
+            ("A2", "=SLEEP(1)"),
+            ("B2", "7"),
+        ] {
+            sheet.set_cell(cell, formula).unwrap();
+        }
+        // Nothing waited, and the values are already known
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(sheet.get_value("C1"), Some(3));
+
+        let left = |sheet: &Sheet, cell: &str| {
+            let ind = crate::cell_to_ind(cell, 3);
+            sheet
+                .schedule()
+                .left(ind, start)
+                .map(|d| d.as_secs_f64().round())
+        };
+        assert_eq!(left(&sheet, "A1"), Some(2.0));
+        assert_eq!(left(&sheet, "B1"), Some(4.0));
+        assert_eq!(left(&sheet, "C1"), Some(4.0));
+        assert_eq!(left(&sheet, "A2"), Some(1.0));
+        assert_eq!(left(&sheet, "B2"), None);
+        assert!(sheet.is_pending("C1"));
+        assert!(!sheet.is_pending("B2"));
+
+        // Cells are scheduled again from their new formula, along with the cells reading them
+        sheet.set_cell("B1", "=A2").unwrap();
+        assert_eq!(left(&sheet, "B1"), Some(1.0));
+        assert_eq!(left(&sheet, "C1"), Some(1.0));
+        sheet.set_cell("B1", "5").unwrap();
+        assert_eq!(left(&sheet, "C1"), None);
+
+        let later = start + Duration::from_secs(5);
+        assert!(
+            !sheet
+                .schedule()
+                .is_pending(crate::cell_to_ind("C1", 3), later)
+        );
+        assert_eq!(sheet.schedule().next(later), None);
+    }
+}
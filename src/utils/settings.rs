@@ -0,0 +1,270 @@
+//! This module contains runtime settings of the evaluation engine.
+//!
+//! Safe mode and the resource limits belong to one spreadsheet (see `Limits`), so sheets
+//! embedded in the same process do not share them. They are changed with commands:
+//!
+//! ```text
+//! safe_mode on
+//! limit cells 10000
+//! limit time off
+//! ```
+//!
+//! The seed of the random features is shared by the whole process and stored in atomics, so
+//! that it can be read from any thread without locking.
+
+use crate::utils::error::SheetError;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Whether the random features are seeded by `SEED` rather than the clock.
+static SEEDED: AtomicBool = AtomicBool::new(false);
+
+/// Seed given with `--seed`, used when `SEEDED` is set.
+static SEED: AtomicU64 = AtomicU64::new(0);
+
+/// Number of random seeds handed out so far.
+static DRAWS: AtomicU64 = AtomicU64::new(0);
+
+/// Sets the seed of all random features, making them reproducible across runs.
+/// # Arguments
+/// * `seed` - The seed, or `None` to seed from the clock.
+pub fn set_seed(seed: Option<u64>) {
+    SEEDED.store(seed.is_some(), Ordering::Relaxed);
+    SEED.store(seed.unwrap_or(0), Ordering::Relaxed);
+    DRAWS.store(0, Ordering::Relaxed);
+}
+
+/// Derives the seed of one random operation.
+/// # Arguments
+/// * `seed` - The global seed.
+/// * `draw` - How many seeds were handed out before this one.
+/// # Returns
+/// A seed that differs between draws.
+fn derive_seed(seed: u64, draw: u64) -> u64 {
+    seed ^ draw.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// Gives the seed for a random operation such as a sample.
+///
+/// With a global seed set, the n-th random operation of a run always gets the same seed.
+/// # Returns
+/// The seed to use.
+pub fn random_seed() -> u64 {
+    let draw = DRAWS.fetch_add(1, Ordering::Relaxed);
+    let seed = if SEEDED.load(Ordering::Relaxed) {
+        SEED.load(Ordering::Relaxed)
+    } else {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+    };
+    derive_seed(seed, draw)
+}
+
+/// Safe mode and resource limits of one spreadsheet.
+///
+/// # Fields
+/// * `frozen_at` - Time safe mode was enabled at, in seconds since the Unix epoch, or `None`
+///   if it is off. In safe mode SLEEP evaluates without waiting, and TODAY and NOW give this
+///   time.
+/// * `max_cells` - Maximum number of cells recomputed by a single edit (0 for no limit)
+/// * `max_eval_ms` - Maximum wall-time of a single recalculation in milliseconds (0 for no
+///   limit)
+/// * `max_sleep` - Maximum duration of a single SLEEP in seconds (0 for no limit)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    frozen_at: Option<i64>,
+    max_cells: u64,
+    max_eval_ms: u64,
+    max_sleep: u64,
+}
+
+/// Converts a stored limit into an optional value.
+fn limit(value: u64) -> Option<u64> {
+    (value != 0).then_some(value)
+}
+
+impl Limits {
+    /// Enables or disables safe mode.
+    /// # Arguments
+    /// * `on` - `true` to enable safe mode.
+    pub fn set_safe_mode(&mut self, on: bool) {
+        if !on {
+            self.frozen_at = None;
+        } else if self.frozen_at.is_none() {
+            self.frozen_at = Some(chrono::Utc::now().timestamp());
+        }
+    }
+
+    /// Checks whether safe mode is enabled.
+    pub fn safe_mode(&self) -> bool {
+        self.frozen_at.is_some()
+    }
+
+    /// Gets the maximum number of cells a single edit may recompute.
+    /// # Returns
+    /// `None` if there is no limit.
+    pub fn max_cells(&self) -> Option<usize> {
+        limit(self.max_cells).map(|v| v as usize)
+    }
+
+    /// Gets the maximum wall-time of a single recalculation.
+    /// # Returns
+    /// `None` if there is no limit.
+    pub fn max_eval_time(&self) -> Option<std::time::Duration> {
+        limit(self.max_eval_ms).map(std::time::Duration::from_millis)
+    }
+
+    /// Gets the maximum duration of a single SLEEP in seconds.
+    /// # Returns
+    /// `None` if there is no limit.
+    pub fn max_sleep(&self) -> Option<i32> {
+        limit(self.max_sleep).map(|v| v.min(i32::MAX as u64) as i32)
+    }
+
+    /// Runs a `limit <cells|time|sleep> <value>` command.
+    ///
+    /// A value of 0 or `off` removes the limit. Time is given in milliseconds and sleep in
+    /// seconds.
+    /// # Arguments
+    /// * `command` - The full command string.
+    /// # Returns
+    /// `Ok(())` if the limit was set, otherwise `SheetError::InvalidLimit`.
+    pub fn limit_command(&mut self, command: &str) -> Result<(), SheetError> {
+        let parts: Vec<&str> = command.split_whitespace().collect();
+        if parts.len() != 3 || parts[0] != "limit" {
+            return Err(SheetError::InvalidLimit);
+        }
+        let value = if parts[2] == "off" {
+            0
+        } else {
+            parts[2]
+                .parse::<u64>()
+                .map_err(|_| SheetError::InvalidLimit)?
+        };
+        let target = match parts[1] {
+            "cells" => &mut self.max_cells,
+            "time" => &mut self.max_eval_ms,
+            "sleep" => &mut self.max_sleep,
+            _ => return Err(SheetError::InvalidLimit),
+        };
+        *target = value;
+        Ok(())
+    }
+
+    /// Gives the time TODAY and NOW read: the current time, or the time safe mode was
+    /// enabled at, so their cells do not change while it is on.
+    pub fn now(&self) -> chrono::DateTime<chrono::Local> {
+        clock_time(self.frozen_at, chrono::Local::now())
+    }
+
+    /// Computes how long a SLEEP should wait.
+    /// # Arguments
+    /// * `requested` - The requested duration in seconds.
+    /// # Returns
+    /// The duration to sleep for.
+    pub fn sleep_duration(&self, requested: i32) -> std::time::Duration {
+        std::time::Duration::from_secs(clamp_sleep(requested, self.safe_mode()))
+    }
+}
+
+/// Gives the time TODAY and NOW read.
+/// # Arguments
+/// * `frozen_at` - The time safe mode was enabled at, if it is enabled, in seconds since the
+///   Unix epoch.
+/// * `now` - The current time.
+fn clock_time(
+    frozen_at: Option<i64>,
+    now: chrono::DateTime<chrono::Local>,
+) -> chrono::DateTime<chrono::Local> {
+    frozen_at
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .map_or(now, |at| at.with_timezone(&chrono::Local))
+}
+
+/// Computes how long a SLEEP should actually wait.
+/// # Arguments
+/// * `requested` - The requested duration in seconds.
+/// * `safe` - Whether safe mode is enabled.
+/// # Returns
+/// The number of seconds to sleep. Negative durations and safe mode give zero.
+fn clamp_sleep(requested: i32, safe: bool) -> u64 {
+    if safe || requested < 0 {
+        0
+    } else {
+        requested as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_command_rejects_invalid() {
+        let mut limits = Limits::default();
+        for command in [
+            "limit cells",
+            "limit rows 10",
+            "limit time -5",
+            "limits time 5",
+        ] {
+            assert_eq!(limits.limit_command(command), Err(SheetError::InvalidLimit));
+        }
+        assert_eq!(limits, Limits::default());
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut limits = Limits::default();
+        limits.limit_command("limit cells 100").unwrap();
+        limits.limit_command("limit time 50").unwrap();
+        limits.limit_command("limit sleep 3").unwrap();
+        assert_eq!(limits.max_cells(), Some(100));
+        assert_eq!(
+            limits.max_eval_time(),
+            Some(std::time::Duration::from_millis(50))
+        );
+        assert_eq!(limits.max_sleep(), Some(3));
+        limits.limit_command("limit cells off").unwrap();
+        assert_eq!(limits.max_cells(), None);
+
+        assert_eq!(limits.sleep_duration(3).as_secs(), 3);
+        limits.set_safe_mode(true);
+        assert!(limits.safe_mode());
+        assert_eq!(limits.sleep_duration(3).as_secs(), 0);
+        // The clock stays frozen when safe mode is enabled again
+        let frozen = limits.now();
+        limits.set_safe_mode(true);
+        assert_eq!(limits.now(), frozen);
+        limits.set_safe_mode(false);
+        assert!(!limits.safe_mode());
+    }
+
+    #[test]
+    fn test_derive_seed() {
+        assert_eq!(derive_seed(42, 3), derive_seed(42, 3));
+        assert_eq!(derive_seed(42, 0), 42);
+        assert_ne!(derive_seed(42, 1), derive_seed(42, 2));
+        assert_ne!(derive_seed(42, 1), derive_seed(43, 1));
+    }
+
+    #[test]
+    fn test_clock_time() {
+        let now = chrono::Local::now();
+        assert_eq!(clock_time(None, now), now);
+        let frozen = clock_time(Some(86400), now);
+        assert_eq!(frozen.timestamp(), 86400);
+        assert_eq!(
+            clock_time(Some(86400), now + chrono::TimeDelta::hours(5)),
+            frozen
+        );
+    }
+
+    #[test]
+    fn test_clamp_sleep() {
+        assert_eq!(clamp_sleep(5, false), 5);
+        assert_eq!(clamp_sleep(-3, false), 0);
+        assert_eq!(clamp_sleep(5, true), 0);
+        assert_eq!(clamp_sleep(0, true), 0);
+    }
+}
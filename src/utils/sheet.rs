@@ -0,0 +1,890 @@
+//! This module contains the state of a spreadsheet and a thread-safe handle to it.
+//!
+//! `Sheet` is the engine other programs embed, exported as `SpreadsheetEngine`: cells are
+//! set with `set_cell` and read with `get_value` or `iter_cells`, while frontends apply
+//! parsed commands with `parse` and `update` to get the status of each. The cell arrays are
+//! read through `database`, `err`, `opers` and `sensi` and only changed by updates, so they
+//! always agree with each other.
+//!
+//! `SharedSheet` wraps the state in an `Arc<RwLock>` so that any number of readers can
+//! query values concurrently while a single writer applies updates. `Recalc` instead applies
+//! updates to a copy on a background thread, so the owner of the state can keep reading the
+//! last consistent values without any locking. Updates worth moving off the caller's thread
+//! are told by `Sheet::is_slow`, and may be cancelled with `Recalc::cancel`.
+
+use crate::Ops;
+use crate::utils::cancel::Cancel;
+use crate::utils::cells::Cells;
+use crate::utils::error::SheetError;
+use crate::utils::input::{Assignment, ParsedCommand};
+use crate::utils::operations::RangeOptions;
+use crate::utils::schedule::Schedule;
+use crate::utils::settings::Limits;
+use std::cell::OnceCell;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Holds all data needed to evaluate a spreadsheet.
+///
+/// # Fields
+/// * `len_h` - Width of the spreadsheet (number of columns)
+/// * `len_v` - Height of the spreadsheet (number of rows)
+/// * `database` - Values of each cell
+/// * `err` - Whether each cell is in an error state
+/// * `opers` - Operation stored for each cell, kept sparse in large spreadsheets (see `utils::cells`)
+/// * `indegree` - Scratch array used by the topological sort
+/// * `sensi` - Sensitivity list (dependents of each cell), kept sparse like `opers`
+/// * `schedule` - When the cells waiting on SLEEP resolve (see `utils::schedule`)
+/// * `ranges` - Header labels and error skipping, read by range functions (see `RangeOptions`).
+///   Their fields are saved next to the others, where workbooks of the GUI always kept them
+/// * `limits` - Safe mode and resource limits (see `utils::settings::Limits`). They are not
+///   saved, so a file cannot lift the limits of whoever opens it
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Sheet {
+    pub len_h: i32,
+    pub len_v: i32,
+    database: Vec<i32>,
+    err: Vec<bool>,
+    opers: Cells<Ops>,
+    indegree: Vec<i32>,
+    sensi: Cells<Vec<i32>>,
+    #[serde(skip)]
+    schedule: Schedule,
+    #[serde(flatten)]
+    ranges: RangeOptions,
+    #[serde(skip)]
+    limits: Limits,
+}
+
+impl Sheet {
+    /// Creates an empty spreadsheet.
+    /// # Arguments
+    /// * `len_h` - Width of the spreadsheet (number of columns)
+    /// * `len_v` - Height of the spreadsheet (number of rows)
+    pub fn new(len_h: i32, len_v: i32) -> Self {
+        let n = (len_h * len_v + 1) as usize;
+        Self {
+            len_h,
+            len_v,
+            database: vec![0; n],
+            err: vec![false; n],
+            opers: Cells::new(n),
+            indegree: vec![0; n],
+            sensi: Cells::new(n),
+            schedule: Schedule::default(),
+            ranges: RangeOptions::default(),
+            limits: Limits::default(),
+        }
+    }
+
+    /// Gives the value of each cell, by index. Cells in an error state hold a stale value.
+    pub fn database(&self) -> &[i32] {
+        &self.database
+    }
+
+    /// Gives whether each cell is in an error state, by index.
+    pub fn err(&self) -> &[bool] {
+        &self.err
+    }
+
+    /// Gives the operation stored for each cell, by index.
+    pub fn opers(&self) -> &Cells<Ops> {
+        &self.opers
+    }
+
+    /// Gives the dependents of each cell, by index.
+    pub fn sensi(&self) -> &Cells<Vec<i32>> {
+        &self.sensi
+    }
+
+    /// Gives when the cells waiting on SLEEP resolve.
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    /// Gives the settings read by range functions (see `set_ranges`).
+    pub fn ranges(&self) -> &RangeOptions {
+        &self.ranges
+    }
+
+    /// Gives the safe mode and resource limits (see `apply_setting`).
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Parses and validates a command against the bounds of this spreadsheet.
+    /// # Arguments
+    /// * `input` - The command typed by the user
+    /// # Returns
+    /// The output of `utils::input::input`.
+    pub fn parse(&self, input: &str) -> Result<ParsedCommand, SheetError> {
+        crate::utils::input::input(input, self.len_h, self.len_v)
+    }
+
+    /// Parses and validates an assignment against the bounds of this spreadsheet.
+    /// # Arguments
+    /// * `input` - The assignment typed by the user, such as "A1=B1+3"
+    /// # Returns
+    /// The assignment, or the error saying why the command is not one.
+    pub fn parse_assignment(&self, input: &str) -> Result<Assignment, SheetError> {
+        self.parse(input)?.into_assignment()
+    }
+
+    /// Applies a parsed assignment to the spreadsheet, scheduling the cells it leaves waiting
+    /// on SLEEP.
+    /// # Arguments
+    /// * `out` - The parsed assignment
+    /// # Returns
+    /// The result of `cell_update`.
+    pub fn update(&mut self, out: &Assignment) -> Result<(), SheetError> {
+        self.update_with_cancel(out, &Cancel::default())
+    }
+
+    /// Applies a parsed assignment like `update`, stopping if the token is cancelled.
+    /// # Arguments
+    /// * `out` - The parsed assignment
+    /// * `cancel` - The token that stops the recalculation (see `utils::cancel`)
+    /// # Returns
+    /// The result of `cell_update`, `SheetError::Cancelled` leaving the spreadsheet as it was.
+    pub fn update_with_cancel(
+        &mut self,
+        out: &Assignment,
+        cancel: &Cancel,
+    ) -> Result<(), SheetError> {
+        crate::cell_update(
+            out,
+            &mut self.database,
+            &mut self.sensi,
+            &mut self.opers,
+            self.len_h,
+            &mut self.indegree,
+            &mut self.err,
+            &self.ranges,
+            &self.limits,
+            cancel,
+        )?;
+        self.schedule.update(
+            crate::cell_to_ind(&out.target, self.len_h),
+            &self.sensi,
+            &self.opers,
+            &self.database,
+            &self.err,
+            self.len_h,
+            &self.limits,
+            Instant::now(),
+        );
+        Ok(())
+    }
+
+    /// Assigns a formula or a value to a cell, as if `<cell>=<formula>` were typed.
+    /// # Arguments
+    /// * `cell` - The cell, such as "A1"
+    /// * `formula` - The formula or value, with or without a leading "=", such as "=B1+3"
+    /// # Returns
+    /// `Ok(())` if the cell was assigned, or the error saying why not, such as
+    /// `SheetError::Cycle`, in which case no cell changes.
+    pub fn set_cell(&mut self, cell: &str, formula: &str) -> Result<(), SheetError> {
+        let cell = cell.trim();
+        if !crate::utils::input::is_valid_cell(cell, self.len_h, self.len_v) {
+            return Err(SheetError::AssignedCellOutOfBounds);
+        }
+        let formula = formula.trim();
+        let out = self.parse_assignment(&format!(
+            "{}={}",
+            cell,
+            formula.strip_prefix('=').unwrap_or(formula)
+        ))?;
+        self.update(&out)
+    }
+
+    /// Empties a cell, so it holds no value and range functions leave it out again. Cells
+    /// reading it see 0, as for a cell never assigned.
+    /// # Arguments
+    /// * `cell` - The cell, such as "A1"
+    /// # Returns
+    /// `Ok(())` if the cell was emptied, or the error saying why not, in which case no cell
+    /// changes.
+    pub fn clear_cell(&mut self, cell: &str) -> Result<(), SheetError> {
+        let ind = self
+            .index(cell)
+            .ok_or(SheetError::AssignedCellOutOfBounds)?;
+        self.update(&Assignment::empty(&crate::ind_to_cell(
+            ind as i32, self.len_h,
+        )))
+    }
+
+    /// Checks whether a cell is waiting on SLEEP (see `utils::schedule`). Its value is already
+    /// given by `get_value`.
+    /// # Arguments
+    /// * `cell` - The cell, such as "A1"
+    pub fn is_pending(&self, cell: &str) -> bool {
+        self.index(cell)
+            .is_some_and(|ind| self.schedule.is_pending(ind as i32, Instant::now()))
+    }
+
+    /// Gives the value of a cell.
+    /// # Arguments
+    /// * `cell` - The cell, such as "A1"
+    /// # Returns
+    /// The value, or `None` if there is no such cell or it is in an error state.
+    pub fn get_value(&self, cell: &str) -> Option<i32> {
+        let ind = self.index(cell)?;
+        (!self.err[ind]).then_some(self.database[ind])
+    }
+
+    /// Gives the formula of a cell, as it would be typed after the "=".
+    /// # Arguments
+    /// * `cell` - The cell, such as "A1"
+    /// # Returns
+    /// The formula, or `None` if there is no such cell or it was never assigned.
+    pub fn get_formula(&self, cell: &str) -> Option<String> {
+        let op = &self.opers[self.index(cell)?];
+        (!op.opcpde.is_empty()).then(|| crate::utils::input::formula_text(op, self.len_h))
+    }
+
+    /// Iterates over the cells that were assigned, row by row.
+    /// # Returns
+    /// The name and value of each cell, with `None` for cells in an error state.
+    pub fn iter_cells(&self) -> impl Iterator<Item = (String, Option<i32>)> + '_ {
+        (1..self.opers.len())
+            .filter(|&ind| !self.opers[ind].opcpde.is_empty())
+            .map(|ind| {
+                (
+                    crate::ind_to_cell(ind as i32, self.len_h),
+                    (!self.err[ind]).then_some(self.database[ind]),
+                )
+            })
+    }
+
+    /// Finds the index of a cell by name, if it is in the spreadsheet.
+    fn index(&self, cell: &str) -> Option<usize> {
+        let cell = cell.trim();
+        crate::utils::input::is_valid_cell(cell, self.len_h, self.len_v)
+            .then(|| crate::cell_to_ind(cell, self.len_h) as usize)
+    }
+
+    /// Evaluates every cell again, after a change of setting that changes what formulas give.
+    /// Cells waiting on SLEEP resolve at once.
+    pub fn recalculate(&mut self) {
+        let (database, err) =
+            crate::recalculate_all(&self.opers, self.len_h, &self.ranges, &self.limits);
+        self.database = database;
+        self.err = err;
+        self.schedule = Schedule::default();
+    }
+
+    /// Changes the settings read by range functions, evaluating every cell again if they
+    /// differ from the current ones.
+    /// # Arguments
+    /// * `ranges` - The header labels and whether errors are skipped
+    pub fn set_ranges(&mut self, ranges: RangeOptions) {
+        if self.ranges != ranges {
+            self.ranges = ranges;
+            self.recalculate();
+        }
+    }
+
+    /// Runs a command that changes a setting of the spreadsheet: `skip_errors on|off`,
+    /// `safe_mode on|off` or `limit <cells|time|sleep> <value>` (see `utils::settings`).
+    /// Settings change what formulas give or which edits are accepted, so these commands are
+    /// journaled like assignments.
+    /// # Arguments
+    /// * `command` - The command typed by the user
+    /// # Returns
+    /// `None` if the command is not a setting, otherwise the result of applying it.
+    pub fn apply_setting(&mut self, command: &str) -> Option<Result<(), SheetError>> {
+        match command.trim() {
+            "skip_errors on" | "skip_errors off" => {
+                self.set_ranges(RangeOptions {
+                    skip_errors: command.trim() == "skip_errors on",
+                    ..self.ranges.clone()
+                });
+                Some(Ok(()))
+            }
+            "safe_mode on" | "safe_mode off" => {
+                self.limits.set_safe_mode(command.trim() == "safe_mode on");
+                Some(Ok(()))
+            }
+            cmd if cmd.starts_with("limit ") => Some(self.limits.limit_command(cmd)),
+            _ => None,
+        }
+    }
+
+    /// Inserts or deletes a row or column, moving the cells after it (see `utils::structure`).
+    /// Header labels move along with their cells, and are dropped once they leave the header
+    /// row.
+    /// # Arguments
+    /// * `edit` - The row or column to insert or delete
+    pub fn edit_lines(&mut self, edit: crate::utils::structure::LineEdit) {
+        self.ranges.labels = std::mem::take(&mut self.ranges.labels)
+            .into_iter()
+            .filter_map(|(ind, text)| Some((edit.move_cell(ind, self.len_h)?, text)))
+            .filter(|&(ind, _)| ind <= self.len_h)
+            .collect();
+        self.opers = edit.apply(&self.opers, self.len_h);
+        self.sensi = crate::utils::graph::sensitivity(&self.opers, self.len_h);
+        self.recalculate();
+    }
+
+    /// Checks whether applying an assignment may take long enough to be run on a background
+    /// thread, as it recomputes at least `SLOW_UPDATE` cells. SLEEP does not make an update
+    /// slow, as it does not wait (see `utils::schedule`).
+    /// # Arguments
+    /// * `out` - A parsed assignment
+    pub fn is_slow(&self, out: &Assignment) -> bool {
+        let target = crate::cell_to_ind(&out.target, self.len_h);
+        crate::utils::graph::affected(&self.sensi, target).len() >= SLOW_UPDATE
+    }
+
+    /// Checks the current values against a full recalculation of the spreadsheet.
+    /// # Returns
+    /// Indices of the cells whose value or error state is wrong (see `verify`).
+    pub fn verify(&self) -> Vec<i32> {
+        crate::verify(
+            &self.database,
+            &self.err,
+            &self.opers,
+            self.len_h,
+            &self.ranges,
+            &self.limits,
+        )
+    }
+}
+
+/// A cloneable, thread-safe handle to a `Sheet`.
+#[derive(Debug, Clone)]
+pub struct SharedSheet(Arc<RwLock<Sheet>>);
+
+impl SharedSheet {
+    /// Wraps a spreadsheet in a shared handle.
+    /// # Arguments
+    /// * `sheet` - The spreadsheet to share
+    pub fn new(sheet: Sheet) -> Self {
+        Self(Arc::new(RwLock::new(sheet)))
+    }
+
+    /// Locks the spreadsheet for reading. Several readers may hold the lock at once.
+    ///
+    /// A lock poisoned by a panicking writer is recovered, since every update
+    /// leaves the spreadsheet in a consistent state before it can panic.
+    pub fn read(&self) -> RwLockReadGuard<'_, Sheet> {
+        self.0.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Locks the spreadsheet for writing.
+    pub fn write(&self) -> RwLockWriteGuard<'_, Sheet> {
+        self.0.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Fewest cells an update recomputes for it to be run on a background thread (see
+/// `Sheet::is_slow`).
+pub const SLOW_UPDATE: usize = 10_000;
+
+/// The outcome of a `Recalc`: the updated spreadsheet, the result of `cell_update` for each
+/// assignment, and how long the update took.
+pub type Finished = (Sheet, Vec<Result<(), SheetError>>, Duration);
+
+/// An update of a spreadsheet running on a background thread.
+///
+/// The thread works on its own copy of the spreadsheet. The copy is sent back over a channel
+/// and replaces the caller's state once the update is finished, so values are never seen
+/// half-way through a recalculation. A cancelled update sends back the spreadsheet as it was
+/// before any of the assignments, which all finish with `SheetError::Cancelled`.
+#[derive(Debug)]
+pub struct Recalc {
+    handle: JoinHandle<()>,
+    result: Receiver<Finished>,
+    received: OnceCell<Finished>,
+    pending: Vec<bool>,
+    cancel: Cancel,
+}
+
+impl Recalc {
+    /// Starts applying parsed assignments to a copy of a spreadsheet.
+    /// # Arguments
+    /// * `sheet` - The copy to update
+    /// * `commands` - Parsed assignments, applied in order
+    pub fn start(sheet: Sheet, commands: Vec<Assignment>) -> Self {
+        Self::start_with_wake(sheet, commands, || {})
+    }
+
+    /// Starts applying parsed assignments to a copy of a spreadsheet, calling back once the
+    /// result is sent, so a GUI can be woken up to take it over.
+    /// # Arguments
+    /// * `sheet` - The copy to update
+    /// * `commands` - Parsed assignments, applied in order
+    /// * `wake` - Called on the background thread once `is_finished` holds
+    pub fn start_with_wake(
+        sheet: Sheet,
+        commands: Vec<Assignment>,
+        wake: impl FnOnce() + Send + 'static,
+    ) -> Self {
+        let mut pending = vec![false; sheet.database.len()];
+        for out in &commands {
+            let target = crate::cell_to_ind(&out.target, sheet.len_h);
+            for cell in crate::utils::graph::affected(&sheet.sensi, target) {
+                pending[cell as usize] = true;
+            }
+        }
+        let (sender, result) = mpsc::channel();
+        let cancel = Cancel::default();
+        let token = cancel.clone();
+        let handle = std::thread::spawn(move || {
+            let original = sheet.clone();
+            let mut sheet = sheet;
+            let start = Instant::now();
+            let mut statuses: Vec<_> = commands
+                .iter()
+                .map(|out| {
+                    if token.is_cancelled() {
+                        return Err(SheetError::Cancelled);
+                    }
+                    sheet.update_with_cancel(out, &token)
+                })
+                .collect();
+            // Assignments applied before the cancel are undone with the rest of the batch
+            if statuses.contains(&Err(SheetError::Cancelled)) {
+                sheet = original;
+                statuses.fill(Err(SheetError::Cancelled));
+            }
+            // The caller may have stopped waiting, in which case the result is dropped
+            let _ = sender.send((sheet, statuses, start.elapsed()));
+            wake();
+        });
+        Self {
+            handle,
+            result,
+            received: OnceCell::new(),
+            pending,
+            cancel,
+        }
+    }
+
+    /// Cancels the update. All of its assignments finish with `SheetError::Cancelled`, and
+    /// `finish` still has to be called for the spreadsheet, which is as it was before them.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Checks whether a cell may change when the update is finished.
+    /// # Arguments
+    /// * `cell` - The cell index (1-based)
+    pub fn is_pending(&self, cell: i32) -> bool {
+        self.pending[cell as usize]
+    }
+
+    /// Checks whether the update is finished, without waiting.
+    pub fn is_finished(&self) -> bool {
+        if self.received.get().is_some() {
+            return true;
+        }
+        match self.result.try_recv() {
+            Ok(finished) => {
+                let _ = self.received.set(finished);
+                true
+            }
+            // The thread panicked, which `finish` passes on
+            Err(TryRecvError::Disconnected) => true,
+            Err(TryRecvError::Empty) => false,
+        }
+    }
+
+    /// Waits for the update to finish.
+    /// # Returns
+    /// The updated spreadsheet, the result of `cell_update` for each assignment, and how long
+    /// the update took.
+    pub fn finish(self) -> Finished {
+        if let Some(finished) = self.received.into_inner() {
+            return finished;
+        }
+        match self.result.recv() {
+            Ok(finished) => finished,
+            Err(_) => match self.handle.join() {
+                Err(e) => std::panic::resume_unwind(e),
+                Ok(()) => unreachable!("the update thread returned without a result"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// A cell reference in a 4x4 spreadsheet.
+    fn cell() -> impl Strategy<Value = String> {
+        (0u8..4, 1..=4).prop_map(|(c, r)| format!("{}{}", (b'A' + c) as char, r))
+    }
+
+    /// A single assignment. Values are kept small so that ranges cannot overflow.
+    fn edit() -> impl Strategy<Value = String> {
+        let op = prop::sample::select(vec!['+', '-', '/']);
+        let func = prop::sample::select(vec!["SUM", "MIN", "MAX", "AVG", "STDEV"]);
+        prop_oneof![
+            (cell(), -50..50).prop_map(|(t, v)| format!("{}={}", t, v)),
+            (cell(), cell()).prop_map(|(t, a)| format!("{}={}", t, a)),
+            (cell(), cell(), op.clone(), cell())
+                .prop_map(|(t, a, o, b)| format!("{}={}{}{}", t, a, o, b)),
+            (cell(), cell(), op, -5..5).prop_map(|(t, a, o, v)| format!("{}={}{}{}", t, a, o, v)),
+            (cell(), func, cell(), cell())
+                .prop_map(|(t, f, a, b)| format!("{}={}({}:{})", t, f, a, b)),
+        ]
+    }
+
+    #[test]
+    fn test_engine_api() {
+        let mut sheet = Sheet::new(3, 3);
+        sheet.set_cell("B1", "7").unwrap();
+        sheet.set_cell("A1", "=B1+3").unwrap();
+        sheet.set_cell("C2", " =A1/0 ").unwrap();
+        assert_eq!(sheet.get_value("A1"), Some(10));
+        assert_eq!(sheet.get_value("C2"), None);
+        assert_eq!(sheet.get_value("C3"), Some(0));
+        assert_eq!(sheet.get_value("D1"), None);
+        assert_eq!(sheet.get_formula("A1").as_deref(), Some("B1+3"));
+        assert_eq!(sheet.get_formula("C3"), None);
+
+        // A rejected assignment changes nothing
+        assert_eq!(sheet.set_cell("B1", "=A1"), Err(SheetError::Cycle));
+        assert_eq!(
+            sheet.set_cell("D1", "1"),
+            Err(SheetError::AssignedCellOutOfBounds)
+        );
+        assert!(sheet.set_cell("B1", "=B1+").is_err());
+        assert_eq!(sheet.get_value("B1"), Some(7));
+
+        assert_eq!(
+            sheet.iter_cells().collect::<Vec<_>>(),
+            [
+                (String::from("A1"), Some(10)),
+                (String::from("B1"), Some(7)),
+                (String::from("C2"), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clear_cell() {
+        let mut sheet = Sheet::new(3, 3);
+        sheet.set_cell("A1", "5").unwrap();
+        sheet.set_cell("B1", "A1+1").unwrap();
+        sheet.set_cell("C1", "COUNTA(A1:B1)").unwrap();
+        sheet.set_cell("C2", "COUNTBLANK(A1:B1)").unwrap();
+        sheet.clear_cell("a1").unwrap();
+        assert_eq!(sheet.get_formula("A1"), None);
+        assert_eq!(sheet.get_value("B1"), Some(1));
+        assert_eq!(sheet.get_value("C1"), Some(1));
+        assert_eq!(sheet.get_value("C2"), Some(1));
+        // B1 no longer reads A1 once it is emptied
+        sheet.clear_cell("B1").unwrap();
+        assert!(!sheet.sensi[1].contains(&2));
+        assert_eq!(sheet.get_value("C2"), Some(2));
+        assert_eq!(
+            sheet.clear_cell("D1"),
+            Err(SheetError::AssignedCellOutOfBounds)
+        );
+        assert!(sheet.verify().is_empty());
+    }
+
+    #[test]
+    fn test_range_options() {
+        let mut sheet = Sheet::new(3, 3);
+        for (cell, formula) in [
+            ("A1", "5"),
+            ("A2", "1"),
+            ("A3", "2"),
+            ("C1", "4"),
+            ("B1", "SUM(A1:A3)"),
+            ("B2", "A2/0"),
+            ("B3", "SUM(B1:C2)"),
+        ] {
+            sheet.set_cell(cell, formula).unwrap();
+        }
+        let other = sheet.clone();
+        sheet.set_ranges(RangeOptions {
+            header_row: true,
+            labels: [(1, String::from("Total"))].into(),
+            skip_errors: false,
+        });
+        // Only the label is left out, numbers in row 1 are still read
+        assert_eq!(sheet.get_value("B1"), Some(3));
+        assert_eq!(sheet.get_value("B3"), None);
+        sheet.set_ranges(RangeOptions {
+            skip_errors: true,
+            ..sheet.ranges().clone()
+        });
+        assert_eq!(sheet.get_value("B3"), Some(7));
+        assert!(sheet.verify().is_empty());
+        // The settings are saved with the spreadsheet
+        let text = serde_json::to_string(&sheet).unwrap();
+        let loaded: Sheet = serde_json::from_str(&text).unwrap();
+        assert_eq!(loaded.ranges(), sheet.ranges());
+        assert_eq!(loaded.ranges().label(1).map(String::as_str), Some("Total"));
+        // Turning the header row off keeps the labels, but reads their cells again
+        sheet.set_ranges(RangeOptions {
+            header_row: false,
+            ..sheet.ranges().clone()
+        });
+        assert_eq!(sheet.get_value("B1"), Some(8));
+        assert_eq!(sheet.ranges().label(1), None);
+        assert_eq!(sheet.ranges().labels.len(), 1);
+        // The settings belong to the spreadsheet they were set on
+        assert_eq!(other.get_value("B1"), Some(8));
+        assert_eq!(other.get_value("B3"), None);
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut sheet = Sheet::new(3, 1);
+        let mut other = sheet.clone();
+        sheet.set_cell("B1", "A1+1").unwrap();
+        sheet.set_cell("C1", "B1+1").unwrap();
+        other.set_cell("B1", "A1+1").unwrap();
+        other.set_cell("C1", "B1+1").unwrap();
+        assert_eq!(sheet.apply_setting("limit cells 2"), Some(Ok(())));
+        assert_eq!(
+            sheet.apply_setting("limit cells many"),
+            Some(Err(SheetError::InvalidLimit))
+        );
+        assert_eq!(sheet.apply_setting("safe_mode on"), Some(Ok(())));
+        assert!(sheet.limits().safe_mode());
+        assert_eq!(
+            sheet.set_cell("A1", "1"),
+            Err(SheetError::CellLimitExceeded)
+        );
+        // The limits belong to the spreadsheet they were set on
+        assert!(!other.limits().safe_mode());
+        assert_eq!(other.set_cell("A1", "1"), Ok(()));
+        assert_eq!(other.get_value("C1"), Some(3));
+    }
+
+    #[test]
+    fn test_large_sheet_is_sparse() {
+        let mut sheet = Sheet::new(1000, 999);
+        assert!(matches!(sheet.opers, Cells::Sparse { .. }));
+        sheet.set_cell("A1", "5").unwrap();
+        sheet.set_cell("ALL999", "=SUM(A1:A3)").unwrap();
+        sheet.set_cell("A2", "=A1*2").unwrap();
+        assert_eq!(sheet.get_value("ALL999"), Some(15));
+        assert_eq!(sheet.get_formula("A2").as_deref(), Some("A1*2"));
+        match (&sheet.opers, &sheet.sensi) {
+            (Cells::Sparse { cells: opers, .. }, Cells::Sparse { cells: sensi, .. }) => {
+                assert_eq!(opers.len(), 3);
+                assert!(sensi.len() <= 3);
+            }
+            _ => panic!("a large sheet keeps an entry for every cell"),
+        }
+        assert!(sheet.verify().is_empty());
+
+        // Ranges that are no longer read, and formulas reverted after a cycle, leave no entries
+        sheet.set_cell("ALL999", "=SUM(A1:A500)").unwrap();
+        sheet.set_cell("ALL999", "7").unwrap();
+        assert_eq!(sheet.set_cell("B5", "=B5+1"), Err(SheetError::Cycle));
+        match (&sheet.opers, &sheet.sensi) {
+            (Cells::Sparse { cells: opers, .. }, Cells::Sparse { cells: sensi, .. }) => {
+                assert_eq!(opers.len(), 3);
+                assert_eq!(sensi.len(), 1);
+            }
+            _ => unreachable!(),
+        }
+        // Files only list the cells that were assigned
+        let json = serde_json::to_string(&sheet.opers).unwrap();
+        assert!(json.len() < 1000);
+    }
+
+    #[test]
+    fn test_parallel_evaluation() {
+        let mut sheet = Sheet::new(100, 200);
+        sheet.set_cell("A1", "1").unwrap();
+        // 300 cells reading A1 form a level large enough to be evaluated in parallel
+        for row in 2..=4 {
+            for col in 1..=100 {
+                let cell = crate::ind_to_cell(col + (row - 1) * 100, 100);
+                sheet.set_cell(&cell, &format!("=A1*{}", col)).unwrap();
+            }
+        }
+        // A range large enough to be read in parallel
+        sheet.set_cell("A200", "=SUM(A1:CV199)").unwrap();
+        const { assert!(crate::utils::operations::PARALLEL_RANGE <= 100 * 199) };
+        assert_eq!(sheet.get_value("A200"), Some(1 + 3 * 5050));
+
+        sheet.set_cell("A1", "2").unwrap();
+        assert_eq!(sheet.get_value("CV4"), Some(200));
+        assert_eq!(sheet.get_value("A200"), Some(2 + 6 * 5050));
+        assert!(sheet.verify().is_empty());
+
+        sheet.set_cell("A1", "=1/0").unwrap();
+        assert_eq!(sheet.get_value("B3"), None);
+        assert_eq!(sheet.get_value("A200"), None);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_incremental_matches_full_recalculation(edits in prop::collection::vec(edit(), 1..40)) {
+            let mut sheet = Sheet::new(4, 4);
+            for e in edits {
+                if let Ok(out) = sheet.parse_assignment(&e) {
+                    let _ = sheet.update(&out);
+                }
+                prop_assert!(sheet.verify().is_empty(), "mismatch after {}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_detects_stale_value() {
+        let mut sheet = Sheet::new(3, 3);
+        let out = sheet.parse_assignment("B1=A1+1").unwrap();
+        sheet.update(&out).unwrap();
+        assert!(sheet.verify().is_empty());
+        sheet.database[2] = 7;
+        assert_eq!(sheet.verify(), vec![2]);
+    }
+
+    #[test]
+    fn test_sheet_update_and_value() {
+        let mut sheet = Sheet::new(3, 3);
+        let out = sheet.parse_assignment("A1=5").unwrap();
+        assert_eq!(sheet.update(&out), Ok(()));
+        let out = sheet.parse_assignment("B1=A1/0").unwrap();
+        assert_eq!(sheet.update(&out), Ok(()));
+        assert_eq!(sheet.database[1], 5);
+        assert!(!sheet.err[1]);
+        assert!(sheet.err[2]);
+    }
+
+    #[test]
+    fn test_recalc_in_background() {
+        let mut sheet = Sheet::new(3, 3);
+        for cmd in ["A1=1", "B1=A1+1", "C3=5"] {
+            let out = sheet.parse_assignment(cmd).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        let commands = vec![
+            sheet.parse_assignment("A1=10").unwrap(),
+            sheet.parse_assignment("A2=A2+1").unwrap(),
+        ];
+        let recalc = Recalc::start(sheet.clone(), commands);
+        // The caller's copy is untouched while the update runs
+        assert_eq!(sheet.database[2], 2);
+        assert!(recalc.is_pending(1));
+        assert!(recalc.is_pending(2));
+        assert!(recalc.is_pending(4));
+        assert!(!recalc.is_pending(9));
+        let (done, statuses, _) = recalc.finish();
+        assert_eq!(statuses, vec![Ok(()), Err(SheetError::Cycle)]);
+        assert_eq!(done.database[2], 11);
+        assert!(done.verify().is_empty());
+    }
+
+    #[test]
+    fn test_recalc_wakes_when_finished() {
+        let mut sheet = Sheet::new(3, 3);
+        sheet.set_cell("A1", "1").unwrap();
+        let (woken, wait) = mpsc::channel();
+        let commands = vec![sheet.parse_assignment("B1=A1+1").unwrap()];
+        let recalc = Recalc::start_with_wake(sheet, commands, move || woken.send(()).unwrap());
+        wait.recv().unwrap();
+        // The result is ready by the time the caller is woken up
+        assert!(recalc.is_finished());
+        let (done, statuses, _) = recalc.finish();
+        assert_eq!(statuses, vec![Ok(())]);
+        assert_eq!(done.get_value("B1"), Some(2));
+    }
+
+    #[test]
+    fn test_recalc_cancelled() {
+        let mut sheet = Sheet::new(3, 3);
+        sheet.set_cell("A1", "1").unwrap();
+        sheet.set_cell("B1", "A1+1").unwrap();
+        let cancel = Cancel::default();
+        cancel.cancel();
+        let out = sheet.parse_assignment("A1=10").unwrap();
+        assert_eq!(
+            sheet.update_with_cancel(&out, &cancel),
+            Err(SheetError::Cancelled)
+        );
+        // The edit is reverted
+        assert_eq!(sheet.get_value("A1"), Some(1));
+        assert_eq!(sheet.get_value("B1"), Some(2));
+        assert_eq!(sheet.get_formula("A1"), Some("1".to_string()));
+        assert!(sheet.verify().is_empty());
+    }
+
+    #[test]
+    fn test_recalc_cancel_reverts_batch() {
+        let mut sheet = Sheet::new(3, 3);
+        sheet.set_cell("A1", "1").unwrap();
+        sheet.set_cell("B1", "A1+1").unwrap();
+        let commands = vec![
+            sheet.parse_assignment("A1=10").unwrap(),
+            sheet.parse_assignment("C1=B1*2").unwrap(),
+        ];
+        let recalc = Recalc::start(sheet.clone(), commands);
+        recalc.cancel();
+        let (done, statuses, _) = recalc.finish();
+        // The batch may finish before the cancel, but is never left half applied
+        if statuses == vec![Ok(()), Ok(())] {
+            assert_eq!(done.get_value("C1"), Some(22));
+        } else {
+            assert_eq!(statuses, vec![Err(SheetError::Cancelled); 2]);
+            assert_eq!(done.get_value("A1"), Some(1));
+            assert_eq!(done.get_formula("C1"), sheet.get_formula("C1"));
+        }
+        assert!(done.verify().is_empty());
+    }
+
+    #[test]
+    fn test_is_slow() {
+        // Every other cell of a 100x100 spreadsheet reads A1
+        let mut sheet = Sheet::new(100, 100);
+        sheet.set_cell("A1", "1").unwrap();
+        for ind in 2..=100 * 100 {
+            let out = sheet
+                .parse_assignment(&format!("{}=A1+1", crate::ind_to_cell(ind, 100)))
+                .unwrap();
+            sheet.update(&out).unwrap();
+        }
+        let slow = |cmd: &str| sheet.is_slow(&sheet.parse_assignment(cmd).unwrap());
+        assert!(slow("A1=2"));
+        assert!(!slow("B1=3"));
+        assert!(!slow("B1=SLEEP(30)"));
+    }
+
+    #[test]
+    fn test_shared_sheet_concurrent_readers() {
+        let shared = SharedSheet::new(Sheet::new(3, 3));
+        let writer = {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                for i in 1..=50 {
+                    let out = shared
+                        .read()
+                        .parse_assignment(&format!("A1={}", i))
+                        .unwrap();
+                    shared.write().update(&out).unwrap();
+                }
+            })
+        };
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..50 {
+                        let sheet = shared.read();
+                        assert!(sheet.database[1] <= 50);
+                    }
+                })
+            })
+            .collect();
+        writer.join().unwrap();
+        for r in readers {
+            r.join().unwrap();
+        }
+        assert_eq!(shared.read().database[1], 50);
+    }
+}
@@ -0,0 +1,485 @@
+//! This module contains structural edits, which insert or delete a whole row or column of a
+//! spreadsheet.
+//!
+//! The size of the spreadsheet stays the same. Deleting a line moves the cells after it up or
+//! left by one and leaves the last line empty; inserting a line moves them down or right and
+//! drops the last line, which is only allowed while it is empty (see `LineEdit::loses_cells`).
+//! References move with the cells they point at. A formula that pointed at a deleted cell gets
+//! the `REF_OP` operation instead, which evaluates to an error shown as "#REF!", rather than
+//! silently reading the cell that moved into place. Ranges which only partly cover a deleted
+//! line shrink by one, and ranges an inserted line falls inside grow by one.
+//!
+//! Before a deletion is applied its impact can be checked (see `LineEdit::impact`), so the
+//! user can confirm a deletion that removes values or breaks formulas.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+
+/// Operation of a formula that referenced a deleted cell.
+pub const REF_OP: &str = "REF";
+
+/// Text shown for a formula that referenced a deleted cell, and typed to enter one.
+pub const REF_TEXT: &str = "#REF!";
+
+/// Builds the operation of a formula that referenced a deleted cell.
+pub fn ref_op() -> Ops {
+    Ops {
+        opcpde: REF_OP.to_string(),
+        cell1: -1,
+        cell2: -1,
+        expr: None,
+        criterion: None,
+    }
+}
+
+/// How a structural edit changes the cells of a spreadsheet, found before it is applied.
+///
+/// Cells are named by their index before the edit.
+///
+/// # Fields
+/// * `removed` - Assigned cells on the deleted line
+/// * `rewritten` - Formulas whose references move
+/// * `broken` - Formulas that reference a removed cell and will show "#REF!"
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Impact {
+    pub removed: Vec<i32>,
+    pub rewritten: Vec<i32>,
+    pub broken: Vec<i32>,
+}
+
+impl Impact {
+    /// Most cells listed by name in the summary of each change.
+    const LISTED: usize = 10;
+
+    /// Checks whether the edit loses anything, so it should be confirmed first.
+    pub fn is_destructive(&self) -> bool {
+        !self.removed.is_empty() || !self.broken.is_empty()
+    }
+
+    /// Describes the impact as shown before the edit is confirmed, one line per change.
+    pub fn summary(&self, len_h: i32) -> Vec<String> {
+        let list = |cells: &[i32]| {
+            let mut names: Vec<String> = cells
+                .iter()
+                .take(Self::LISTED)
+                .map(|&c| crate::ind_to_cell(c, len_h))
+                .collect();
+            if cells.len() > Self::LISTED {
+                names.push(format!("and {} more", cells.len() - Self::LISTED));
+            }
+            names.join(", ")
+        };
+        let mut lines = Vec::new();
+        if !self.removed.is_empty() {
+            lines.push(format!(
+                "Removes {} assigned cells: {}",
+                self.removed.len(),
+                list(&self.removed)
+            ));
+        }
+        if !self.broken.is_empty() {
+            lines.push(format!(
+                "Breaks {} formulas, which will show {}: {}",
+                self.broken.len(),
+                REF_TEXT,
+                list(&self.broken)
+            ));
+        }
+        if !self.rewritten.is_empty() {
+            lines.push(format!(
+                "Rewrites the references of {} formulas",
+                self.rewritten.len()
+            ));
+        }
+        lines
+    }
+}
+
+/// A row or column to insert or delete.
+///
+/// # Fields
+/// * `row` - `true` for a row, `false` for a column
+/// * `line` - The row or column number. An inserted line takes this number and the line
+///   that had it moves down or right.
+/// * `insert` - `true` to insert the line, `false` to delete it
+/// * `len` - The number of rows or columns of the spreadsheet along the edited axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineEdit {
+    pub row: bool,
+    pub line: i32,
+    pub insert: bool,
+    pub len: i32,
+}
+
+impl LineEdit {
+    /// Parses a `delete_row <row>`, `delete_col <column>`, `insert_row <row>` or
+    /// `insert_col <column>` command.
+    /// # Arguments
+    /// * `command` - The full command string.
+    /// * `len_h` - The number of columns in the spreadsheet.
+    /// * `len_v` - The number of rows in the spreadsheet.
+    /// # Returns
+    /// The edit, or `None` if the command is not a valid structural edit.
+    pub fn parse(command: &str, len_h: i32, len_v: i32) -> Option<Self> {
+        let (name, line) = command.trim().split_once(' ')?;
+        let line = line.trim();
+        let (insert, axis) = match name {
+            "delete_row" => (false, "row"),
+            "delete_col" => (false, "col"),
+            "insert_row" => (true, "row"),
+            "insert_col" => (true, "col"),
+            _ => return None,
+        };
+        if axis == "row" {
+            match line.parse::<i32>() {
+                Ok(row) if (1..=len_v).contains(&row) => Some(Self {
+                    row: true,
+                    line: row,
+                    insert,
+                    len: len_v,
+                }),
+                _ => None,
+            }
+        } else {
+            crate::utils::input::parse_col(line, len_h).map(|col| Self {
+                row: false,
+                line: col,
+                insert,
+                len: len_h,
+            })
+        }
+    }
+
+    /// Gives the new number of a row or column along the edited axis.
+    /// # Returns
+    /// The new number, or `None` for a deleted line or a line moved past the end.
+    pub fn shift(self, line: i32) -> Option<i32> {
+        if line < self.line {
+            Some(line)
+        } else if self.insert {
+            Some(line + 1).filter(|&l| l <= self.len)
+        } else if line == self.line {
+            None
+        } else {
+            Some(line - 1)
+        }
+    }
+
+    /// Gives the new index of a cell.
+    /// # Arguments
+    /// * `ind` - The cell index (1-based).
+    /// * `len_h` - The number of columns in the spreadsheet.
+    /// # Returns
+    /// The new index, or `None` if the cell is deleted or moved past the end.
+    pub fn move_cell(self, ind: i32, len_h: i32) -> Option<i32> {
+        let col = (ind - 1) % len_h + 1;
+        let row = (ind - 1) / len_h + 1;
+        if self.row {
+            Some(col + (self.shift(row)? - 1) * len_h)
+        } else {
+            Some(self.shift(col)? + (row - 1) * len_h)
+        }
+    }
+
+    /// Moves the corners of a range, shrinking it if it covers a deleted line and growing it
+    /// if an inserted line falls inside it.
+    /// # Returns
+    /// The new corners, or `None` if the whole range is deleted or moved past the end.
+    pub fn move_range(self, c1: i32, c2: i32, len_h: i32) -> Option<(i32, i32)> {
+        let (mut x1, mut y1, mut x2, mut y2) = crate::utils::graph::range_bounds(c1, c2, len_h);
+        let (first, last) = if self.row {
+            (&mut y1, &mut y2)
+        } else {
+            (&mut x1, &mut x2)
+        };
+        if self.insert {
+            *first = self.shift(*first)?;
+            *last = self.shift(*last).unwrap_or(self.len);
+        } else {
+            if *first == self.line && *last == self.line {
+                return None;
+            }
+            if *first > self.line {
+                *first -= 1;
+            }
+            if *last >= self.line {
+                *last -= 1;
+            }
+        }
+        Some((x1 + (y1 - 1) * len_h, x2 + (y2 - 1) * len_h))
+    }
+
+    /// Checks whether an insertion would drop assigned cells off the end of the spreadsheet.
+    /// # Arguments
+    /// * `opers` - The operations for each cell.
+    /// * `len_h` - The number of columns in the spreadsheet.
+    pub fn loses_cells(self, opers: &Cells<Ops>, len_h: i32) -> bool {
+        self.insert
+            && (1..opers.len()).any(|ind| {
+                !opers[ind].opcpde.is_empty() && self.move_cell(ind as i32, len_h).is_none()
+            })
+    }
+
+    /// Finds what the edit would change, without applying it.
+    /// # Arguments
+    /// * `opers` - The operations for each cell.
+    /// * `len_h` - The number of columns in the spreadsheet.
+    pub fn impact(self, opers: &Cells<Ops>, len_h: i32) -> Impact {
+        let mut impact = Impact::default();
+        for (ind, op) in opers.iter().enumerate().skip(1) {
+            let ind = ind as i32;
+            if op.opcpde.is_empty() {
+                continue;
+            }
+            if self.move_cell(ind, len_h).is_none() {
+                impact.removed.push(ind);
+                continue;
+            }
+            let moved = self.move_op(op, len_h);
+            if moved.opcpde == REF_OP && op.opcpde != REF_OP {
+                impact.broken.push(ind);
+            } else if crate::utils::input::formula_text(&moved, len_h)
+                != crate::utils::input::formula_text(op, len_h)
+            {
+                impact.rewritten.push(ind);
+            }
+        }
+        impact
+    }
+
+    /// Moves the references of an operation.
+    /// # Returns
+    /// The moved operation, or a `REF_OP` operation if it referenced a removed cell.
+    fn move_op(self, op: &Ops, len_h: i32) -> Ops {
+        let code = op.opcpde.as_str();
+        let mut res = op.clone();
+        if let Some(expr) = &op.expr {
+            let moved = expr.map_cells(&mut |cell| {
+                let ind = self.move_cell(cell.ind(len_h), len_h)?;
+                Some(crate::utils::cellref::CellRef::from_ind(ind, len_h))
+            });
+            let Some(moved) = moved else {
+                return ref_op();
+            };
+            res.expr = Some(moved);
+            return res;
+        }
+        if crate::utils::graph::RANGE_OPS.contains(&code) {
+            let Some((c1, c2)) = self.move_range(op.cell1, op.cell2, len_h) else {
+                return ref_op();
+            };
+            (res.cell1, res.cell2) = (c1, c2);
+            return res;
+        }
+        if code.starts_with('C') || code == "EQC" || code == "SLC" {
+            let Some(c1) = self.move_cell(op.cell1, len_h) else {
+                return ref_op();
+            };
+            res.cell1 = c1;
+        }
+        if code.chars().nth(1) == Some('C') {
+            let Some(c2) = self.move_cell(op.cell2, len_h) else {
+                return ref_op();
+            };
+            res.cell2 = c2;
+        }
+        res
+    }
+
+    /// Inserts or deletes the line in the operations of a spreadsheet.
+    ///
+    /// The values and the sensitivity list have to be rebuilt from the result.
+    /// # Arguments
+    /// * `opers` - The operations for each cell.
+    /// * `len_h` - The number of columns in the spreadsheet.
+    /// # Returns
+    /// The operations after the edit.
+    pub fn apply(self, opers: &Cells<Ops>, len_h: i32) -> Cells<Ops> {
+        let mut res = Cells::new(opers.len());
+        for (ind, op) in opers.iter().enumerate().skip(1) {
+            if let Some(new) = self.move_cell(ind as i32, len_h) {
+                res[new as usize] = self.move_op(op, len_h);
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sheet::Sheet;
+
+    fn formulas(sheet: &Sheet) -> Vec<String> {
+        (1..sheet.opers().len())
+            .filter(|&i| !sheet.opers()[i].opcpde.is_empty())
+            .map(|i| {
+                format!(
+                    "{}={}",
+                    crate::ind_to_cell(i as i32, sheet.len_h),
+                    crate::utils::input::formula_text(&sheet.opers()[i], sheet.len_h)
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let row = LineEdit::parse("delete_row 3", 4, 5);
+        assert_eq!(
+            row,
+            Some(LineEdit {
+                row: true,
+                line: 3,
+                insert: false,
+                len: 5
+            })
+        );
+        let col = LineEdit::parse("insert_col D", 4, 5);
+        assert_eq!(
+            col,
+            Some(LineEdit {
+                row: false,
+                line: 4,
+                insert: true,
+                len: 4
+            })
+        );
+        assert_eq!(LineEdit::parse("delete_row 6", 4, 5), None);
+        assert_eq!(LineEdit::parse("insert_row 0", 4, 5), None);
+        assert_eq!(LineEdit::parse("delete_col E", 4, 5), None);
+        assert_eq!(LineEdit::parse("delete_cell A1", 4, 5), None);
+    }
+
+    #[test]
+    fn test_delete_row() {
+        let mut sheet = Sheet::new(3, 4);
+        for cmd in [
+            "A1=1",
+            "A2=2",
+            "A3=3",
+            "B1=A2+1",
+            "B2=A3*2",
+            "B3=SUM(A1:A3)",
+            "C1=SUM(A2:B2)",
+        ] {
+            let out = sheet.parse_assignment(cmd).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        sheet.edit_lines(LineEdit::parse("delete_row 2", 3, 4).unwrap());
+        assert_eq!(
+            formulas(&sheet),
+            ["A1=1", "B1=#REF!", "C1=#REF!", "A2=3", "B2=SUM(A1:A2)"]
+        );
+        assert!(sheet.err()[2]);
+        assert_eq!(sheet.database()[5], 4);
+        assert!(sheet.verify().is_empty());
+    }
+
+    #[test]
+    fn test_delete_col() {
+        let mut sheet = Sheet::new(3, 2);
+        for cmd in ["A1=5", "B1=7", "C1=A1+B1", "C2=MAX(A1:C1)", "A2=C1"] {
+            let out = sheet.parse_assignment(cmd).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        sheet.edit_lines(LineEdit::parse("delete_col A", 3, 2).unwrap());
+        assert_eq!(formulas(&sheet), ["A1=7", "B1=#REF!", "B2=MAX(A1:B1)"]);
+        assert!(sheet.err()[2] && sheet.err()[5]);
+        assert!(sheet.verify().is_empty());
+    }
+
+    #[test]
+    fn test_insert_row() {
+        let mut sheet = Sheet::new(3, 4);
+        for cmd in [
+            "A1=1",
+            "A2=2",
+            "A3=3",
+            "B1=A2+1",
+            "B2=SUM(A1:A3)",
+            "C1=SUM(A2:A3)",
+            "C2=B3",
+        ] {
+            let out = sheet.parse_assignment(cmd).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        let insert = LineEdit::parse("insert_row 2", 3, 4).unwrap();
+        assert!(!insert.loses_cells(sheet.opers(), 3));
+        sheet.edit_lines(insert);
+        assert_eq!(
+            formulas(&sheet),
+            [
+                "A1=1",
+                "B1=A3+1",
+                "C1=SUM(A3:A4)",
+                "A3=2",
+                "B3=SUM(A1:A4)",
+                "C3=B4",
+                "A4=3"
+            ]
+        );
+        assert_eq!(sheet.database()[2 + 2 * 3], 6);
+        assert!(sheet.verify().is_empty());
+
+        // Row 4 is no longer empty, so another insertion would drop cells
+        assert!(insert.loses_cells(sheet.opers(), 3));
+        assert!(
+            !LineEdit::parse("delete_row 4", 3, 4)
+                .unwrap()
+                .loses_cells(sheet.opers(), 3)
+        );
+    }
+
+    #[test]
+    fn test_impact() {
+        let mut sheet = Sheet::new(3, 3);
+        for cmd in [
+            "A1=5",
+            "B1=7",
+            "C1=A1+B1",
+            "C2=MAX(A1:C1)",
+            "A2=C1",
+            "B3=A1",
+        ] {
+            let out = sheet.parse_assignment(cmd).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        let impact = LineEdit::parse("delete_col A", 3, 3)
+            .unwrap()
+            .impact(sheet.opers(), 3);
+        assert_eq!(
+            impact,
+            Impact {
+                removed: vec![1, 4],
+                rewritten: vec![6],
+                broken: vec![3, 8],
+            }
+        );
+        assert!(impact.is_destructive());
+        assert_eq!(
+            impact.summary(3),
+            [
+                "Removes 2 assigned cells: A1, A2",
+                "Breaks 2 formulas, which will show #REF!: C1, B3",
+                "Rewrites the references of 1 formulas"
+            ]
+        );
+
+        // Nothing reads the last row, so deleting it only removes its cells
+        let impact = LineEdit::parse("delete_row 3", 3, 3)
+            .unwrap()
+            .impact(sheet.opers(), 3);
+        assert_eq!(impact.removed, [8]);
+        assert!(impact.rewritten.is_empty() && impact.broken.is_empty());
+    }
+
+    #[test]
+    fn test_insert_range_end() {
+        let edit = LineEdit::parse("insert_row 2", 1, 3).unwrap();
+        // A1:A3 grows past the last row and is cut back to it; A3:A3 moves off the sheet
+        assert_eq!(edit.move_range(1, 3, 1), Some((1, 3)));
+        assert_eq!(edit.move_range(3, 3, 1), None);
+        assert_eq!(edit.move_range(2, 2, 1), Some((3, 3)));
+    }
+}
@@ -0,0 +1,273 @@
+//! This module contains cell styles, which set the colors, weight and alignment of the text
+//! of cells.
+//!
+//! Styles are set from the formatting toolbar of the GUI, or with `style <range> <options>`
+//! and removed with `unstyle <range>`, where the range is a cell such as `B2`, a column such
+//! as `B` or a block such as `B1:D10`:
+//!
+//! ```text
+//! style A1:D1 bold background #ffe699 align center
+//! style B color red
+//! unstyle B
+//! ```
+//!
+//! Options add to the style a cell already has, and `plain` turns bold off. Colors are the
+//! names of the display rules (see `utils::rules`) or hex codes such as `#1f4e79`.
+
+use crate::utils::rules::Tone;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How the text of a cell is placed across it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    /// All alignments, in the order they are listed to the user.
+    pub const ALL: [Align; 3] = [Align::Left, Align::Center, Align::Right];
+
+    /// Finds an alignment by name, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|a| a.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Gives the name of the alignment as typed.
+    pub fn name(self) -> &'static str {
+        match self {
+            Align::Left => "left",
+            Align::Center => "center",
+            Align::Right => "right",
+        }
+    }
+}
+
+/// How the text of a cell is shown.
+///
+/// # Fields
+/// * `background` - Fill color of the cell, as RGB
+/// * `color` - Color of the text, as RGB
+/// * `bold` - Whether the text is bold
+/// * `align` - Where the text is placed, or `None` for the place of unstyled cells
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CellStyle {
+    pub background: Option<[u8; 3]>,
+    pub color: Option<[u8; 3]>,
+    pub bold: bool,
+    pub align: Option<Align>,
+}
+
+impl CellStyle {
+    /// Checks whether the style changes nothing, so the cell can be left unstyled.
+    pub fn is_plain(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Parses a color name such as "red" or a hex code such as "#1f4e79".
+pub fn parse_color(text: &str) -> Option<[u8; 3]> {
+    if let Some(tone) = Tone::parse(text) {
+        return Some(tone.rgb());
+    }
+    let hex = text.strip_prefix('#').filter(|h| h.len() == 6)?;
+    let byte = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([byte(0)?, byte(2)?, byte(4)?])
+}
+
+/// Parses a cell such as "B2", a column such as "B" or a block such as "B1:D10".
+/// # Returns
+/// First column, first row, last column and last row, or `None` if the range is invalid.
+pub fn parse_bounds(range: &str, len_h: i32, len_v: i32) -> Option<(i32, i32, i32, i32)> {
+    match crate::utils::cellref::CellRef::parse(range) {
+        Some(cell) if !range.contains(':') => (cell.col <= len_h && cell.row <= len_v)
+            .then_some((cell.col, cell.row, cell.col, cell.row)),
+        _ => crate::utils::rules::parse_bounds(range, len_h, len_v),
+    }
+}
+
+/// Changes the style of every cell of a block, leaving cells whose style becomes plain
+/// unstyled.
+/// # Arguments
+/// * `styles` - The style of each styled cell, updated in place.
+/// * `range` - First column, first row, last column and last row of the block.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// * `change` - Changes the style of one cell.
+pub fn restyle(
+    styles: &mut BTreeMap<i32, CellStyle>,
+    (x1, y1, x2, y2): (i32, i32, i32, i32),
+    len_h: i32,
+    change: impl Fn(&mut CellStyle),
+) {
+    for y in y1..=y2 {
+        for x in x1..=x2 {
+            let ind = x + (y - 1) * len_h;
+            let mut style = styles.get(&ind).copied().unwrap_or_default();
+            change(&mut style);
+            if style.is_plain() {
+                styles.remove(&ind);
+            } else {
+                styles.insert(ind, style);
+            }
+        }
+    }
+}
+
+/// Runs a `style <range> <options>` or `unstyle <range>` command.
+///
+/// The options are `bold`, `plain`, `align <left|center|right>`, `color <color>` and
+/// `background <color>`.
+/// # Arguments
+/// * `command` - The full command string.
+/// * `styles` - The style of each styled cell, updated in place.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// * `len_v` - The number of rows in the spreadsheet.
+/// # Returns
+/// "ok" if the styles were updated, otherwise "Invalid Style".
+pub fn style_command(
+    command: &str,
+    styles: &mut BTreeMap<i32, CellStyle>,
+    len_h: i32,
+    len_v: i32,
+) -> &'static str {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let Some(bounds) = words.get(1).and_then(|r| parse_bounds(r, len_h, len_v)) else {
+        return "Invalid Style";
+    };
+    match words[0] {
+        "style" if words.len() > 2 => {
+            let mut options = CellStyle::default();
+            let (mut bold, mut words) = (None, words[2..].iter());
+            while let Some(word) = words.next() {
+                let parsed = match *word {
+                    "bold" | "plain" => {
+                        bold = Some(*word == "bold");
+                        Some(())
+                    }
+                    "align" => words
+                        .next()
+                        .and_then(|w| Align::parse(w))
+                        .map(|a| options.align = Some(a)),
+                    "color" => words
+                        .next()
+                        .and_then(|w| parse_color(w))
+                        .map(|c| options.color = Some(c)),
+                    "background" => words
+                        .next()
+                        .and_then(|w| parse_color(w))
+                        .map(|c| options.background = Some(c)),
+                    _ => None,
+                };
+                if parsed.is_none() {
+                    return "Invalid Style";
+                }
+            }
+            restyle(styles, bounds, len_h, |style| {
+                style.bold = bold.unwrap_or(style.bold);
+                style.align = options.align.or(style.align);
+                style.color = options.color.or(style.color);
+                style.background = options.background.or(style.background);
+            });
+        }
+        "unstyle" if words.len() == 2 => {
+            restyle(styles, bounds, len_h, |style| *style = CellStyle::default())
+        }
+        _ => return "Invalid Style",
+    }
+    "ok"
+}
+
+/// Moves the styles along with their cells when a row or column is inserted or deleted.
+/// # Arguments
+/// * `styles` - The style of each styled cell, updated in place.
+/// * `edit` - The inserted or deleted row or column.
+/// * `len_h` - The number of columns in the spreadsheet.
+pub fn edit_lines(
+    styles: &mut BTreeMap<i32, CellStyle>,
+    edit: crate::utils::structure::LineEdit,
+    len_h: i32,
+) {
+    *styles = std::mem::take(styles)
+        .into_iter()
+        .filter_map(|(ind, style)| Some((edit.move_cell(ind, len_h)?, style)))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("Red"), Some(Tone::Red.rgb()));
+        assert_eq!(parse_color("#1F4e79"), Some([0x1f, 0x4e, 0x79]));
+        for bad in ["purple", "#12345", "#12345g", "1f4e79", "#ééé"] {
+            assert_eq!(parse_color(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_style_command() {
+        let mut styles = BTreeMap::new();
+        assert_eq!(
+            style_command("style A1:B1 bold background #ffe699", &mut styles, 3, 4),
+            "ok"
+        );
+        assert_eq!(
+            style_command("style b1 color red align right", &mut styles, 3, 4),
+            "ok"
+        );
+        assert_eq!(styles.keys().copied().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(
+            styles[&2],
+            CellStyle {
+                background: Some([0xff, 0xe6, 0x99]),
+                color: Some(Tone::Red.rgb()),
+                bold: true,
+                align: Some(Align::Right),
+            }
+        );
+
+        for bad in [
+            "style",
+            "style A1",
+            "style D1 bold",
+            "style A1 italic",
+            "style A1 align",
+            "style A1 align top",
+            "style A1 color purple",
+            "unstyle",
+            "unstyle A1 bold",
+        ] {
+            assert_eq!(
+                style_command(bad, &mut styles, 3, 4),
+                "Invalid Style",
+                "{}",
+                bad
+            );
+        }
+
+        // A style that becomes plain leaves the cell unstyled
+        assert_eq!(style_command("style A plain", &mut styles, 3, 4), "ok");
+        assert_eq!(styles.len(), 2);
+        assert!(!styles[&1].bold);
+        assert_eq!(style_command("unstyle A1", &mut styles, 3, 4), "ok");
+        assert_eq!(styles.keys().copied().collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn test_edit_lines() {
+        let mut styles = BTreeMap::new();
+        style_command("style B1:C2 bold", &mut styles, 3, 4);
+        let edit = |command| crate::utils::structure::LineEdit::parse(command, 3, 4).unwrap();
+
+        edit_lines(&mut styles, edit("insert_row 2"), 3);
+        assert_eq!(styles.keys().copied().collect::<Vec<_>>(), [2, 3, 8, 9]);
+        edit_lines(&mut styles, edit("delete_col B"), 3);
+        assert_eq!(styles.keys().copied().collect::<Vec<_>>(), [2, 8]);
+    }
+}
@@ -0,0 +1,342 @@
+//! This module contains structured tables, which name a block of cells and its columns so
+//! formulas can refer to a column by name.
+//!
+//! A table is declared over its data rows with `table <name> <range> <column>,<column>,...`,
+//! giving one name per column of the range, and removed with `table <name>`:
+//!
+//! ```text
+//! table Sales A2:C10 Date,Amount,Region
+//! D1=SUM(Sales[Amount])
+//! ```
+//!
+//! A reference such as `Sales[Amount]` stands for the cells of the column, here `B2:B10`, and
+//! is replaced by them when the formula is typed. Assigning a cell in the row just below a
+//! table appends the row to the table, and formulas whose range covers a whole column of the
+//! table grow with it.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+
+/// A named block of cells with named columns.
+///
+/// # Fields
+/// * `name` - Name of the table, used in references
+/// * `range` - First column, first row, last column and last row of the data rows
+/// * `columns` - Name of each column, from left to right
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct Table {
+    pub name: String,
+    pub range: (i32, i32, i32, i32),
+    pub columns: Vec<String>,
+}
+
+impl Table {
+    /// Gives the cells of a column of the table, such as "B2:B10".
+    /// # Returns
+    /// The range, or `None` if the table has no column of that name.
+    pub fn column_range(&self, column: &str) -> Option<String> {
+        let (x1, y1, _, y2) = self.range;
+        let offset = self
+            .columns
+            .iter()
+            .position(|c| c.eq_ignore_ascii_case(column.trim()))?;
+        let label = crate::utils::display::get_label(x1 + offset as i32);
+        Some(format!("{}{}:{}{}", label, y1, label, y2))
+    }
+}
+
+/// Checks that a table name can be told apart from the rest of a formula.
+///
+/// Names that read as a cell, such as `AB12`, or as the start of an R1C1 reference, such as
+/// `R` in `R[1]C[-1]` or `R2C` in `R2C[1]`, are not allowed.
+fn is_valid_name(name: &str) -> bool {
+    let r1c1 = name
+        .strip_prefix(['R', 'r'])
+        .map(|rest| rest.trim_start_matches(|c: char| c.is_ascii_digit()))
+        .map(|rest| rest.strip_prefix(['C', 'c']).unwrap_or(rest))
+        .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit()));
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !r1c1
+        && !matches!(name, "C" | "c")
+        && crate::utils::cellref::CellRef::parse(name).is_none()
+}
+
+/// Runs a `table <name> [<range> <columns>]` command.
+/// # Arguments
+/// * `command` - The full command string.
+/// * `tables` - The tables of the spreadsheet, updated in place.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// * `len_v` - The number of rows in the spreadsheet.
+/// # Returns
+/// "ok" if the tables were updated, otherwise "Invalid Table".
+pub fn table_command(
+    command: &str,
+    tables: &mut Vec<Table>,
+    len_h: i32,
+    len_v: i32,
+) -> &'static str {
+    let mut words = command.splitn(4, ' ');
+    let (Some("table"), Some(name)) = (words.next(), words.next()) else {
+        return "Invalid Table";
+    };
+    if !is_valid_name(name) {
+        return "Invalid Table";
+    }
+    let position = tables
+        .iter()
+        .position(|t| t.name.eq_ignore_ascii_case(name));
+    let (range, columns) = match (words.next(), words.next()) {
+        (None, None) => {
+            return match position {
+                Some(position) => {
+                    tables.remove(position);
+                    "ok"
+                }
+                None => "Invalid Table",
+            };
+        }
+        (Some(range), Some(columns)) => (range, columns),
+        _ => return "Invalid Table",
+    };
+    let Some(range) = crate::utils::input::parse_range(range, len_h, len_v) else {
+        return "Invalid Table";
+    };
+    let columns: Vec<String> = columns.split(',').map(|c| c.trim().to_string()).collect();
+    let unique = columns.iter().enumerate().all(|(i, c)| {
+        !c.is_empty()
+            && !c.contains(['[', ']'])
+            && !columns[..i].iter().any(|d| d.eq_ignore_ascii_case(c))
+    });
+    if columns.len() as i32 != range.2 - range.0 + 1 || !unique {
+        return "Invalid Table";
+    }
+    let table = Table {
+        name: name.to_string(),
+        range,
+        columns,
+    };
+    match position {
+        Some(position) => tables[position] = table,
+        None => tables.push(table),
+    }
+    "ok"
+}
+
+/// Replaces the table references of a formula with the cells they stand for.
+///
+/// Brackets after a name that is not a table, as in the R1C1 reference `R[1]C[-1]`, are
+/// left alone.
+/// # Arguments
+/// * `formula` - The formula, or a whole assignment such as "D1=SUM(Sales[Amount])".
+/// * `tables` - The tables of the spreadsheet.
+/// # Returns
+/// The formula with every reference replaced, or a message naming the first reference to a
+/// column the table does not have.
+pub fn resolve(formula: &str, tables: &[Table]) -> Result<String, String> {
+    let mut text = String::new();
+    let mut rest = formula;
+    while let Some(open) = rest.find('[') {
+        let start = rest[..open]
+            .rfind(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .map_or(0, |i| i + 1);
+        let Some(close) = rest[open..].find(']').map(|c| open + c) else {
+            break;
+        };
+        let (name, column) = (&rest[start..open], &rest[open + 1..close]);
+        let Some(table) = tables.iter().find(|t| t.name.eq_ignore_ascii_case(name)) else {
+            text.push_str(&rest[..=open]);
+            rest = &rest[open + 1..];
+            continue;
+        };
+        let range = table
+            .column_range(column)
+            .ok_or_else(|| format!("No table column {}[{}]", name, column))?;
+        text.push_str(&rest[..start]);
+        text.push_str(&range);
+        rest = &rest[close + 1..];
+    }
+    text.push_str(rest);
+    Ok(text)
+}
+
+/// Appends a row to the table a cell was assigned just below, if any.
+/// # Arguments
+/// * `tables` - The tables of the spreadsheet, updated in place.
+/// * `opers` - The operations for each cell.
+/// * `cell` - The index of the assigned cell.
+/// * `len_h` - The number of columns in the spreadsheet.
+/// # Returns
+/// The assignments that make formulas covering a whole column of the grown table cover the
+/// new row too.
+pub fn grow(tables: &mut [Table], opers: &Cells<Ops>, cell: i32, len_h: i32) -> Vec<String> {
+    let (col, row) = ((cell - 1) % len_h + 1, (cell - 1) / len_h + 1);
+    let Some(table) = tables
+        .iter_mut()
+        .find(|t| row == t.range.3 + 1 && (t.range.0..=t.range.2).contains(&col))
+    else {
+        return Vec::new();
+    };
+    let (x1, y1, x2, y2) = table.range;
+    table.range.3 += 1;
+    tracing::info!(table = %table.name, rows = y2 - y1 + 2, "table grown");
+    let columns: Vec<(i32, i32)> = (x1..=x2)
+        .map(|x| (x + (y1 - 1) * len_h, x + (y2 - 1) * len_h))
+        .collect();
+    let mut commands = Vec::new();
+    for (ind, op) in opers.iter().enumerate().skip(1) {
+        if crate::utils::graph::RANGE_OPS.contains(&op.opcpde.as_str())
+            && columns.contains(&(op.cell1, op.cell2))
+        {
+            let mut grown = op.clone();
+            grown.cell2 += len_h;
+            commands.push(format!(
+                "{}={}",
+                crate::ind_to_cell(ind as i32, len_h),
+                crate::utils::input::formula_text(&grown, len_h)
+            ));
+        }
+    }
+    commands
+}
+
+/// Moves the tables along with their cells when a row or column is inserted or deleted.
+///
+/// A table whose cells are all deleted is removed, and a table that loses a column loses
+/// its name.
+/// # Arguments
+/// * `tables` - The tables of the spreadsheet, updated in place.
+/// * `edit` - The inserted or deleted row or column.
+/// * `len_h` - The number of columns in the spreadsheet.
+pub fn edit_lines(tables: &mut Vec<Table>, edit: crate::utils::structure::LineEdit, len_h: i32) {
+    tables.retain_mut(|table| {
+        let (x1, y1, x2, y2) = table.range;
+        let corners = (x1 + (y1 - 1) * len_h, x2 + (y2 - 1) * len_h);
+        let Some((c1, c2)) = edit.move_range(corners.0, corners.1, len_h) else {
+            return false;
+        };
+        if !edit.row && !edit.insert && (x1..=x2).contains(&edit.line) {
+            table.columns.remove((edit.line - x1) as usize);
+        }
+        if !edit.row && edit.insert && (x1 + 1..=x2).contains(&edit.line) {
+            let mut name = String::from("Column");
+            while table.columns.iter().any(|c| c.eq_ignore_ascii_case(&name)) {
+                name.push('_');
+            }
+            table.columns.insert((edit.line - x1) as usize, name);
+        }
+        table.range = crate::utils::graph::range_bounds(c1, c2, len_h);
+        table
+            .columns
+            .truncate((table.range.2 - table.range.0 + 1) as usize);
+        true
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables() -> Vec<Table> {
+        let mut tables = Vec::new();
+        assert_eq!(
+            table_command("table Sales A2:C3 Date,Amount,Region", &mut tables, 4, 5),
+            "ok"
+        );
+        tables
+    }
+
+    #[test]
+    fn test_table_command() {
+        let mut tables = tables();
+        assert_eq!(tables[0].column_range("amount").unwrap(), "B2:B3");
+        for bad in [
+            "table",
+            "table 1Sales A2:C3 A,B,C",
+            "table AB12 A2:C3 A,B,C",
+            "table R2C A2:C3 A,B,C",
+            "table c A2:C3 A,B,C",
+            "table Sales A2:C3 A,B",
+            "table Sales A2:C3 A,a,C",
+            "table Sales A2:C3 A,,C",
+            "table Sales A2:E3 A,B,C,D,E",
+            "table Sales A2:C3",
+            "table Other",
+        ] {
+            assert_eq!(
+                table_command(bad, &mut tables, 4, 5),
+                "Invalid Table",
+                "{}",
+                bad
+            );
+        }
+        assert_eq!(
+            table_command("table sales B1:B5 Total", &mut tables, 4, 5),
+            "ok"
+        );
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].column_range("Total").unwrap(), "B1:B5");
+        assert_eq!(table_command("table SALES", &mut tables, 4, 5), "ok");
+        assert!(tables.is_empty());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let tables = tables();
+        assert_eq!(
+            resolve("D1=SUM(Sales[Amount])+0", &tables).unwrap(),
+            "D1=SUM(B2:B3)+0"
+        );
+        assert_eq!(
+            resolve("COUNTIF(sales[ Region ], \">1\")", &tables).unwrap(),
+            "COUNTIF(C2:C3, \">1\")"
+        );
+        assert_eq!(resolve("A1+B2", &tables).unwrap(), "A1+B2");
+        assert_eq!(
+            resolve("SUM(Sales[Price])", &tables).unwrap_err(),
+            "No table column Sales[Price]"
+        );
+        assert_eq!(
+            resolve("R[1]C[-1]+Sales[Date]", &tables).unwrap(),
+            "R[1]C[-1]+A2:A3"
+        );
+    }
+
+    #[test]
+    fn test_grow() {
+        let mut sheet = crate::utils::sheet::Sheet::new(4, 5);
+        for command in [
+            "B2=5",
+            "B3=7",
+            "D1=SUM(B2:B3)",
+            "D2=MAX(B2:C3)",
+            "D3=SUM(B2:B2)",
+        ] {
+            let out = sheet.parse_assignment(command).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        let mut tables = tables();
+        assert!(grow(&mut tables, sheet.opers(), 4 + 4 * 4, 4).is_empty());
+        assert_eq!(
+            grow(&mut tables, sheet.opers(), 2 + 3 * 4, 4),
+            ["D1=SUM(B2:B4)"]
+        );
+        assert_eq!(tables[0].range, (1, 2, 3, 4));
+    }
+
+    #[test]
+    fn test_edit_lines() {
+        let mut tables = tables();
+        let edit = |command| crate::utils::structure::LineEdit::parse(command, 4, 5).unwrap();
+        edit_lines(&mut tables, edit("insert_row 1"), 4);
+        assert_eq!(tables[0].range, (1, 3, 3, 4));
+        edit_lines(&mut tables, edit("delete_col B"), 4);
+        assert_eq!(tables[0].range, (1, 3, 2, 4));
+        assert_eq!(tables[0].columns, ["Date", "Region"]);
+        edit_lines(&mut tables, edit("insert_col B"), 4);
+        assert_eq!(tables[0].columns, ["Date", "Column", "Region"]);
+        edit_lines(&mut tables, edit("delete_row 3"), 4);
+        edit_lines(&mut tables, edit("delete_row 3"), 4);
+        assert!(tables.is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+//! This module contains a plain-text save format meant to be kept under version control.
+//!
+//! The file starts with a header giving the size of the spreadsheet and the declared column
+//! types (see `utils::coltype`), followed by one line per non-empty or formatted cell in
+//! row-major order, written as the command that assigns the cell and, after a comma, the
+//! options of its number format (see `utils::numfmt`):
+//!
+//! ```text
+//! # rust-spreadsheet text 1
+//! size 10 20
+//! type_col B date
+//! A1=5
+//! B1=A1*2, currency $ decimals 2
+//! A2=SUM(A1:B1)
+//! C2=, percent
+//! ```
+//!
+//! `size` gives the number of columns and then rows. Only formulas and formats are stored, so
+//! the output depends on nothing but the cell contents and changing one cell changes one line.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+use crate::utils::coltype::ColType;
+use crate::utils::numfmt::NumberFormat;
+use crate::utils::sheet::Sheet;
+use std::collections::BTreeMap;
+
+/// File extension used for the text format.
+pub const EXTENSION: &str = "rskt";
+
+/// First line of every file in the text format.
+const HEADER: &str = "# rust-spreadsheet text 1";
+
+/// Writes a spreadsheet in the text format.
+/// # Arguments
+/// * `opers` - The operations for each cell
+/// * `len_h` - Width of the spreadsheet (number of columns)
+/// * `len_v` - Height of the spreadsheet (number of rows)
+/// * `types` - The declared type of each column
+/// * `number_formats` - The number format of each formatted cell
+/// # Returns
+/// The contents of the file.
+pub fn to_text(
+    opers: &Cells<Ops>,
+    len_h: i32,
+    len_v: i32,
+    types: &BTreeMap<i32, ColType>,
+    number_formats: &BTreeMap<i32, NumberFormat>,
+) -> String {
+    let mut text = format!("{}\nsize {} {}\n", HEADER, len_h, len_v);
+    for command in crate::utils::coltype::to_commands(types) {
+        text.push_str(&command);
+        text.push('\n');
+    }
+    for (ind, op) in opers.iter().enumerate().skip(1) {
+        let format = number_formats.get(&(ind as i32));
+        if op.opcpde.is_empty() && format.is_none() {
+            continue;
+        }
+        text.push_str(&crate::ind_to_cell(ind as i32, len_h));
+        text.push('=');
+        if !op.opcpde.is_empty() {
+            text.push_str(&crate::utils::input::formula_text(op, len_h));
+        }
+        if let Some(format) = format {
+            text.push_str(", ");
+            text.push_str(&format.text());
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// The format read from a file: the spreadsheet, its column types and its number formats.
+pub type Loaded = (Sheet, BTreeMap<i32, ColType>, BTreeMap<i32, NumberFormat>);
+
+/// Reads a spreadsheet from the text format and evaluates it.
+///
+/// Loading does not wait on SLEEP formulas: the cells they leave waiting are scheduled
+/// (see `utils::schedule`) and resolve once their delay has passed.
+/// # Arguments
+/// * `text` - The contents of the file
+/// # Returns
+/// The loaded spreadsheet, its column types and its number formats, or a message naming the
+/// first line that could not be read.
+pub fn from_text(text: &str) -> Result<Loaded, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty() && !l.starts_with('#'));
+
+    let size: Vec<i32> = match lines.next() {
+        Some((_, l)) if l.starts_with("size ") => l[5..]
+            .split_whitespace()
+            .map(|v| v.parse().unwrap_or(0))
+            .collect(),
+        _ => return Err(String::from("Missing size line")),
+    };
+    if size.len() != 2 || !(1..=18278).contains(&size[0]) || !(1..=999).contains(&size[1]) {
+        return Err(String::from("Invalid size"));
+    }
+
+    let mut sheet = Sheet::new(size[0], size[1]);
+    let mut types = BTreeMap::new();
+    let mut number_formats = BTreeMap::new();
+    for (n, line) in lines {
+        if line.starts_with("type_col ") {
+            let status = crate::utils::coltype::type_command(line, &mut types, size[0]);
+            if status != "ok" {
+                return Err(format!("Line {}: {}", n + 1, status));
+            }
+            continue;
+        }
+        // Formulas can hold commas too, but never end in the options of a format.
+        let (assignment, format) = match line.rsplit_once(", ") {
+            Some((assignment, options)) => match NumberFormat::parse_text(options) {
+                Some(format) => (assignment, Some(format)),
+                None => (line, None),
+            },
+            None => (line, None),
+        };
+        let assignment = assignment.trim();
+        if let Some(format) = format {
+            let cell = assignment.split('=').next().unwrap_or_default().trim();
+            if !crate::utils::input::is_valid_cell(cell, size[0], size[1]) {
+                return Err(format!("Line {}: Invalid Format", n + 1));
+            }
+            number_formats.insert(crate::cell_to_ind(cell, size[0]), format);
+            if assignment.ends_with('=') {
+                continue;
+            }
+        }
+        let applied = sheet
+            .parse_assignment(assignment)
+            .and_then(|out| sheet.update(&out));
+        if let Err(e) = applied {
+            return Err(format!("Line {}: {}", n + 1, e));
+        }
+    }
+    Ok((sheet, types, number_formats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut sheet = Sheet::new(3, 3);
+        for command in ["C3=A1+B1", "A1=5", "B1=A1*2", "A2=SUM(A1:B1)", "B2=C3/0"] {
+            let out = sheet.parse_assignment(command).unwrap();
+            sheet.update(&out).unwrap();
+        }
+        let types = BTreeMap::from([(3, ColType::Integer)]);
+        let currency = NumberFormat {
+            decimals: 2,
+            currency: Some(String::from("$")),
+            ..NumberFormat::default()
+        };
+        let percent = NumberFormat {
+            percent: true,
+            ..NumberFormat::default()
+        };
+        let formats = BTreeMap::from([(2, currency), (6, percent)]);
+        let text = to_text(sheet.opers(), 3, 3, &types, &formats);
+        assert_eq!(
+            text,
+            "# rust-spreadsheet text 1\nsize 3 3\ntype_col C integer\nA1=5\nB1=A1*2, currency $ decimals 2\nA2=SUM(A1:B1)\nB2=C3/0\nC2=, percent\nC3=A1+B1\n"
+        );
+
+        let (loaded, loaded_types, loaded_formats) = from_text(&text).unwrap();
+        assert_eq!(loaded.database(), sheet.database());
+        assert_eq!(loaded.err(), sheet.err());
+        assert_eq!(loaded_types, types);
+        assert_eq!(loaded_formats, formats);
+        assert_eq!(
+            to_text(loaded.opers(), 3, 3, &loaded_types, &loaded_formats),
+            text
+        );
+    }
+
+    #[test]
+    fn test_from_text_errors() {
+        assert_eq!(from_text("A1=5").unwrap_err(), "Missing size line");
+        assert_eq!(from_text("size 0 3").unwrap_err(), "Invalid size");
+        assert_eq!(
+            from_text("size 2 2\nA1=5\nC1=1").unwrap_err(),
+            "Line 3: Assigned Cell out of bounds"
+        );
+        assert_eq!(
+            from_text("size 2 2\ntype_col C date").unwrap_err(),
+            "Line 2: Invalid Column Type"
+        );
+        assert_eq!(
+            from_text("size 2 2\nC1=, percent").unwrap_err(),
+            "Line 2: Invalid Format"
+        );
+    }
+}
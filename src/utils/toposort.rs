@@ -1,6 +1,7 @@
 //! Implementation of Topological Sort using BFS (Kahn's Algorithm) for Directed Acyclic Graphs (DAGs).
 //! Topological sort is used to solve dependencies of cells.
 
+use crate::utils::cells::Cells;
 use std::collections::VecDeque;
 
 /// Perform a topological sort on a directed graph represented as an adjacency list.
@@ -10,7 +11,8 @@ use std::collections::VecDeque;
 /// * `indegree` - A mutable reference to a vector representing the indegree of each node.(zero initialized vector)
 /// # Returns
 /// A vector containing the topological order of the nodes. If a cycle is detected, the first element will be -1 else the first element will be the count of nodes in the connected component of cell.
-pub fn topo_sort(adj: &[Vec<i32>], cell: i32, indegree: &mut [i32]) -> Vec<i32> {
+pub fn topo_sort(adj: &Cells<Vec<i32>>, cell: i32, indegree: &mut [i32]) -> Vec<i32> {
+    let _span = tracing::debug_span!("topo_sort", cell).entered();
     let mut q: VecDeque<i32> = VecDeque::new(); // queue initialization
     q.push_back(cell);
     let mut is_cycle = 0;
@@ -52,10 +54,12 @@ pub fn topo_sort(adj: &[Vec<i32>], cell: i32, indegree: &mut [i32]) -> Vec<i32>
                 indegree[*c as usize] = 0;
             }
         }
+        tracing::debug!("cycle detected");
         return res;
     }
     // No cycle,so make first element of the output array to be count of cells in connected component of cell
     res[0] = ct - 1;
+    tracing::debug!(cells = res[0], "sorted");
     let mut leng = 1;
     //Run Kahn's Algorithm
     while !q.is_empty() {
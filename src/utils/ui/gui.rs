@@ -2,18 +2,221 @@
 
 use crate::utils;
 use crate::utils::ui;
+use crate::{Assignment, ParsedCommand, SheetError};
 use eframe::egui;
 use egui::{Button, Color32, FontId, RichText};
 use notify_rust::Notification;
 
-/// Gives minimum of two integers.
+/// Width of a column that has not been resized.
+const DEFAULT_COL_WIDTH: f32 = 100.0;
+
+/// Narrowest and widest a column can be made by dragging its header edge.
+const COL_WIDTH_RANGE: std::ops::RangeInclusive<f32> = 40.0..=600.0;
+
+/// Lays out text with a red underline under part of it.
+///
+/// # Arguments
+/// * `text` - The text to lay out
+/// * `mark` - Byte range of the text to underline, if any
+/// * `color` - Color of the text
+fn marked_text(
+    text: &str,
+    mark: Option<std::ops::Range<usize>>,
+    color: Color32,
+) -> egui::text::LayoutJob {
+    let mut job = egui::text::LayoutJob::default();
+    let format = egui::TextFormat::simple(FontId::proportional(20.0), color);
+    let mark = mark.unwrap_or(text.len()..text.len());
+    job.append(&text[..mark.start], 0.0, format.clone());
+    job.append(
+        &text[mark.clone()],
+        0.0,
+        egui::TextFormat {
+            underline: egui::Stroke::new(2.0, Color32::RED),
+            ..format.clone()
+        },
+    );
+    job.append(&text[mark.end..], 0.0, format);
+    job
+}
+
+/// Adds a toolbar button showing an icon.
+///
+/// The name is shown on hover and given to screen readers, since the icon has no text.
+///
+/// # Arguments
+/// * `ui` - The ui to add the button to
+/// * `image` - The icon of the button
+/// * `name` - What the button does, e.g. "Save"
+fn tool_button(ui: &mut egui::Ui, image: egui::ImageSource<'_>, name: &str) -> egui::Response {
+    let response = ui
+        .add_sized(
+            [120.0, 100.0],
+            egui::Button::image(
+                egui::Image::new(image).fit_to_exact_size(egui::Vec2 { x: 100.0, y: 80.0 }),
+            ),
+        )
+        .on_hover_text(name);
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, name));
+    response
+}
+
+/// Adds a footer button that scrolls the viewport by a page.
+///
+/// # Arguments
+/// * `ui` - The ui to add the button to
+/// * `symbol` - The arrow shown on the button
+/// * `name` - The direction of the scroll, given to screen readers, e.g. "Scroll left"
+fn scroll_button(ui: &mut egui::Ui, symbol: &str, name: &str) -> egui::Response {
+    let response = ui
+        .add_sized(
+            [50.0, 30.0],
+            Button::new(RichText::new(symbol).font(FontId::proportional(20.0))),
+        )
+        .on_hover_text(name);
+    response.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, name));
+    response
+}
+
+/// Number of previous analyses kept in the Describe dialog.
+const DESCRIBE_HISTORY: usize = 20;
+
+/// Distance the mouse wheel or touchpad has to move to scroll by one row or column.
+const SCROLL_STEP: f32 = 45.0;
+
+/// Menus of the menu bar, with the key that opens each together with Alt.
+const MENUS: [(&str, egui::Key); 6] = [
+    ("File", egui::Key::F),
+    ("Edit", egui::Key::E),
+    ("View", egui::Key::V),
+    ("Insert", egui::Key::I),
+    ("Data", egui::Key::D),
+    ("Help", egui::Key::H),
+];
+
+/// Actions of the command palette, with their keyboard shortcuts. The menus run the same
+/// actions.
+const ACTIONS: [(&str, &str); 26] = [
+    ("Open…", ""),
+    ("Save…", ""),
+    ("Export PDF…", ""),
+    ("Export Report…", ""),
+    ("Settings…", ""),
+    ("Command Palette…", "Ctrl+P"),
+    ("Go to Cell", "Ctrl+G"),
+    ("Command Terminal", "Ctrl+K"),
+    ("Find and Replace…", "Ctrl+F"),
+    ("Replace in Formulas…", ""),
+    ("Paste Special…", ""),
+    ("Insert Row Below", "Ctrl+Enter"),
+    ("Toggle Toolbar", ""),
+    ("Toggle Error Panel", ""),
+    ("Toggle Formulas", "Ctrl+`"),
+    ("Go to Top", "Ctrl+Home"),
+    ("Go to End", "Ctrl+End"),
+    ("Unhide All Rows", ""),
+    ("Unhide All Columns", ""),
+    ("Plot…", ""),
+    ("Describe…", ""),
+    ("Stats Tests…", ""),
+    ("Dependency Graph Stats", ""),
+    ("Verify Recalculation", ""),
+    ("Function Reference", ""),
+    ("About", ""),
+];
+
+/// Source range (first column, first row, last column, last row) and end cell of an auto-fill.
+type FillTarget = ((i32, i32, i32, i32), (i32, i32));
+
+/// Lists the lines (rows or columns) shown in the viewport.
+/// # Arguments
+/// * `top` - First line of the viewport.
+/// * `len` - Number of lines in the spreadsheet.
+/// * `hidden` - Hidden lines.
+/// * `count` - Number of lines the viewport shows.
+/// # Returns
+/// Up to `count` lines starting at `top`, skipping hidden lines.
+fn visible_lines(top: i32, len: i32, hidden: &[i32], count: i32) -> Vec<i32> {
+    (top..=len)
+        .filter(|l| !hidden.contains(l))
+        .take(count as usize)
+        .collect()
+}
+
+/// Gives the last line that can be the top of a viewport, so that the viewport stays full.
+/// # Arguments
+/// * `len` - Number of lines in the spreadsheet.
+/// * `count` - Number of lines the viewport shows.
+fn max_top(len: i32, count: i32) -> i32 {
+    (len - count + 1).max(1)
+}
+
+/// Name of the font family of bold cell text, installed when the window opens.
+const BOLD_FONT: &str = "bold";
+
+/// Gets the font of the text of a cell.
+/// # Arguments
+/// * `bold` - Whether the cell is styled bold.
+fn cell_font(bold: bool) -> FontId {
+    if bold {
+        FontId::new(20.0, egui::FontFamily::Name(BOLD_FONT.into()))
+    } else {
+        FontId::proportional(20.0)
+    }
+}
+
+/// Adds the bold font used by bold cells to the fonts of the window.
+fn install_fonts(ctx: &egui::Context) {
+    let mut fonts = egui::FontDefinitions::default();
+    fonts.font_data.insert(
+        BOLD_FONT.to_owned(),
+        std::sync::Arc::new(egui::FontData::from_static(include_bytes!(
+            "assets/ARIAL-Bold.ttf"
+        ))),
+    );
+    // Characters missing from the bold font come from the normal fonts
+    let mut family = vec![BOLD_FONT.to_owned()];
+    family.extend(
+        fonts.families[&egui::FontFamily::Proportional]
+            .iter()
+            .cloned(),
+    );
+    fonts
+        .families
+        .insert(egui::FontFamily::Name(BOLD_FONT.into()), family);
+    ctx.set_fonts(fonts);
+}
+
+/// Gets the border of a row or column header.
+/// # Arguments
+/// * `marked` - Whether the header is next to a hidden line.
+/// # Returns
+/// A highlighted stroke for marked headers, otherwise the normal grid stroke.
+fn header_stroke(marked: bool) -> egui::Stroke {
+    if marked {
+        egui::Stroke::new(2.0, Color32::LIGHT_BLUE)
+    } else {
+        egui::Stroke::new(1.0, Color32::GRAY)
+    }
+}
+
+/// Finds the first line of a viewport that ends at a given line.
 /// # Arguments
-/// * `a` - 1st Integer value.
-/// * `b` - 2nd Integer value.
+/// * `line` - The line that should be the last visible one.
+/// * `hidden` - Hidden lines.
+/// * `lines` - Number of lines the viewport shows.
 /// # Returns
-/// The minimum of two integers.
-fn min(a: i32, b: i32) -> i32 {
-    if a < b { a } else { b }
+/// The line to use as the top of the viewport.
+fn top_for(line: i32, hidden: &[i32], lines: i32) -> i32 {
+    let mut top = line;
+    let mut count = 1;
+    while top > 1 && count < lines {
+        top -= 1;
+        if !hidden.contains(&top) {
+            count += 1;
+        }
+    }
+    top
 }
 
 /// Represents the file format used for saving spreadsheet data.
@@ -26,10 +229,15 @@ fn min(a: i32, b: i32) -> i32 {
 /// * `Csv` - Save in Comma-Separated Values format (.csv). This format only saves visible cell
 ///   values and is compatible with other spreadsheet applications, but formulas and other
 ///   application state will be lost.
+///
+/// * `Text` - Save in the plain-text format (.rskt). This format stores one line per formula
+///   in a fixed order so that files diff and merge cleanly under version control. Application
+///   state is not saved.
 #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Clone)]
 enum Save {
     Rsk,
     Csv,
+    Text,
 }
 
 /// Represents the plot type for data visualization.
@@ -48,6 +256,22 @@ enum Plot {
     Scatter,
 }
 
+/// A named bookmark of a viewport position.
+///
+/// # Fields
+///
+/// * `name` - Name of the view, e.g. "Summary"
+/// * `top_h` - Leftmost visible column of the view
+/// * `top_v` - Topmost visible row of the view
+/// * `selected_cell` - Cell selected when the view was saved, if any
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Clone)]
+struct View {
+    name: String,
+    top_h: i32,
+    top_v: i32,
+    selected_cell: Option<i32>,
+}
+
 /// Represents the main spreadsheet application state.
 ///
 /// This struct contains all data needed to maintain the state of the spreadsheet including
@@ -56,13 +280,9 @@ enum Plot {
 /// # Fields
 ///
 /// ## Core Spreadsheet Data
-/// * `len_h` - Horizontal length (number of columns) in the spreadsheet
-/// * `len_v` - Vertical length (number of rows) in the spreadsheet
 /// * `top_h` - Current leftmost visible column index
 /// * `top_v` - Current topmost visible row index
-/// * `database` - Vector storing all cell values as integers
-/// * `err` - Vector indicating whether each cell contains an error
-/// * `formula` - Vector storing formulas for each cell
+/// * `view` - Rows and columns shown in the grid, from the preferences or fitted to the window
 ///
 /// ## UI State
 /// * `terminal` - Current input in the command terminal
@@ -70,12 +290,20 @@ enum Plot {
 /// * `selected_cell` - Currently selected cell index, if any
 /// * `hovered_cell` - Cell index currently being hovered over, if any
 /// * `temp_txt` - Tuple containing (temporary text for cell editing, needs_focus)
+/// * `focus_todo` - Cell that should get keyboard focus, if any
+/// * `menu_todo` - Menu of the menu bar to open, after its Alt shortcut was pressed
+/// * `menu_focus` - Whether the first entry of the menu being opened should get keyboard focus
+/// * `scroll_accum` - Wheel and touchpad movement not yet turned into whole rows and columns
+/// * `pointing` - Tuple containing (formula text before the pointed reference, anchor cell,
+///   cursor cell) while cells are being pointed at during formula entry
+/// * `block` - Block of selected cells, as (anchor cell, cursor cell)
+/// * `select_drag` - Whether a drag is selecting a block
+/// * `typed` - Text typed on a focused cell, which starts editing the cell with that text
 /// * `clipbaord` - Content stored in the application clipboard
 ///
 /// ## Formula Processing
-/// * `opers` - Vector of operations to be performed on cells
-/// * `indegree` - Vector tracking dependencies between cells for cycle detection
-/// * `sensi` - Vector of vectors tracking which cells depend on other cells
+/// * `engine` - Size, values, formulas and dependencies of the cells, which recalculates the
+///   cells depending on an edit; saved inline with the other fields
 ///
 /// ## Dialog States
 /// * `save_dialog` - Whether save dialog is open
@@ -91,39 +319,149 @@ enum Plot {
 /// * `plot_dialog` - Whether plot dialog is open
 /// * `plot_x_axis` - X-axis column selection for plotting
 /// * `plot_y_axis` - Y-axis column selection for plotting
+/// * `plot_err_axis` - Column holding the error of each point, or empty for no error bars
+/// * `plot_y2_axis` - Column plotted against a secondary Y axis, or empty for none
 /// * `plot_rows` - Row range selection for plotting
 /// * `plot_type` - Selected plot type
+/// * `plot_style` - Colors, markers, grid and titles of the plot
 /// * `plot_save` - Path for saving plot image
 /// * `plot_todo` - Whether a plot operation is pending
+/// * `plot_preview` - Path of the plot shown in the preview window, if any
 ///
 /// * `pdf_dialog` - Whether PDF export dialog is open
 /// * `pdf_path` - Path for saving PDF file
 /// * `pdf_todo` - Whether a PDF export operation is pending
+/// * `pdf_report` - Whether the PDF is a report with a title page, the chart and statistics
+/// * `pdf_title` - Title of the report, or empty for the workbook title
+/// * `pdf_export` - PDF export running on a worker thread, if any
 ///
 /// * `describe_dialog` - Whether statistical description dialog is open
 /// * `describe_range` - Cell range for statistical analysis
 /// * `describe_data` - Array storing statistical results [count, mean, std, min, p25, p50, p75, max]
+/// * `describe_shown` - Range the shown statistics were computed over
+/// * `describe_target` - Top cell of the column the statistics are written into
+/// * `describe_history` - Previous analyses, newest first, as (range, statistics)
+///
+/// * `tests_dialog` - Whether the Stats Tests dialog is open
+/// * `tests_kind` - The test selected in the Stats Tests dialog
+/// * `tests_ranges` - The two ranges the test is run on
+/// * `tests_result` - Result of the last test run, if any
 ///
 /// * `about_dialog` - Whether about dialog is open
 ///
+/// * `help_dialog` - Whether the function reference is open
+/// * `help_query` - Text the function reference is filtered by
+///
+/// * `error_panel` - Whether the error inspector panel is open
+/// * `show_formulas` - Whether the grid shows the formulas of cells instead of their values
+///
+/// * `graph_stats` - Dependency graph statistics shown by the `graph_stats` command, if open
+/// * `trace` - Cell and evaluation trace shown by the `trace` command, if open
+/// * `palette` - Query and highlighted match of the command palette, if open
+///
+/// * `hidden_cols` - Columns hidden from the viewport
+/// * `hidden_rows` - Rows hidden from the viewport
+/// * `skip_hidden` - Whether hidden rows and columns are left out of CSV and PDF exports
+///
+/// * `views` - Named viewport bookmarks
+///
+/// * `refresh` - Auto refresh of cells using TODAY or NOW
+///
+/// * `paste_dialog` - Whether the paste special dialog is open
+/// * `paste_target` - Top-left cell to paste to
+/// * `paste_mode` - What the paste writes into the target cells
+/// * `paste_transpose` - Whether the paste turns rows into columns
+/// * `paste_todo` - Whether a paste operation is pending
+///
+/// * `find_dialog` - Whether the find and replace dialog is open
+/// * `find_query` - Text looked for in cell values and formulas
+/// * `find_replacement` - Text that replaces the query in formulas
+/// * `find_where` - Whether the query is a comparison of values, such as >1000, or ERR
+/// * `find_matches` - Cells matching the query when it was last searched for
+///
+/// * `rewrite_dialog` - Whether the Replace in Formulas dialog is open
+/// * `rewrite_function` - Whether the dialog rewrites a function rather than a column
+/// * `rewrite_from` - Column or function to rewrite
+/// * `rewrite_to` - Column or function it becomes
+/// * `rewrite_changes` - Formulas the rewrite changes, as last previewed
+///
+/// * `fill_drag` - Start and current cell of an auto-fill drag, if one is in progress
+/// * `fill_last` - Source range and end cell of the last auto-fill, if its options are open
+/// * `fill_mode` - How the last auto-fill continued the source values
+///
+/// * `col_widths` - Widths of columns that do not use the default width
+/// * `fit_todo` - Column whose width should be fitted to its content, if any
+///
+/// * `col_labels` - Custom labels shown in a header row above the column letters
+/// * `col_types` - Declared types of columns, checked when cells are typed or pasted
+/// * `col_formulas` - Formulas of whole columns, filled into rows as they get data
+/// * `tables` - Named blocks of cells whose columns formulas refer to by name
+///
+/// * `provenance` - Source and time of import of imported cells, by cell
+///
+/// ## Session State
+/// * `title` - Name shown on the tab of this workbook
+/// * `dirty` - Whether the workbook has changes that are not saved to a .rsk file
+/// * `saved_path` - The .rsk file the workbook was last saved to or opened from, if any
+/// * `autosave_since` - When the changes waiting for an autosave were first seen, if any
+/// * `open_todo` - Path of a workbook the user asked to open in a new tab, if any
+/// * `restore_todo` - A .rsk file and the number of the backup of it the user asked to open
+///   in a new tab, if any
+/// * `config` - User preferences, shared by all workbooks
+/// * `config_todo` - Preferences the user changed, to be applied to the whole window, if any
+/// * `settings_draft` - Preferences and resource limits being changed in the Settings window,
+///   if it is open
+///
 /// * `initialized_time` - Timestamp when the spreadsheet was initialized
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+/// * `last_recalc` - How long the last recalculation took, if there was one
+/// * `recalc` - Recalculation running on a background thread, if any
+/// * `recalc_queue` - Assignments waiting for the background recalculation to finish
+/// * `recalc_edits` - Cells assigned by the background recalculation
+/// * `repaint` - The context of the window, which a background recalculation repaints when it
+///   finishes
+///
+/// * `display_rules` - Rules coloring the numbers of ranges, in the order they were set
+/// * `styles` - Colors, weight and alignment of styled cells
+/// * `number_formats` - How the values of formatted cells are written
+///
+/// * `watches` - Cells whose changes pause editing
+/// * `watch_break` - Description of the changes to watched cells editing is paused at, if any
+/// * `watch_pending` - Assignments held back until editing continues after a watch break
+///
+/// * `validation` - Problems found before a save or export, with their total count, while the
+///   user decides whether to go ahead
+/// * `export_confirmed` - Whether the user chose to save or export despite the problems
+/// * `line_impact` - A row or column deletion and what it removes and breaks, while the user
+///   decides whether to go ahead
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct Spreadsheet {
-    len_h: i32,
-    len_v: i32,
+    #[serde(flatten)]
+    engine: crate::SpreadsheetEngine,
     top_h: i32,
     top_v: i32,
-    database: Vec<i32>,
-    err: Vec<bool>,
+    #[serde(skip)]
+    view: (i32, i32),
     terminal: String,
     cell_ref: (String, bool, bool),
     selected_cell: Option<i32>,
     hovered_cell: Option<i32>,
-    opers: Vec<crate::Ops>,
-    indegree: Vec<i32>,
-    sensi: Vec<Vec<i32>>,
     temp_txt: (String, bool),
-    formula: Vec<String>,
+    #[serde(skip)]
+    focus_todo: Option<i32>,
+    #[serde(skip)]
+    menu_todo: Option<usize>,
+    #[serde(skip)]
+    menu_focus: bool,
+    #[serde(skip)]
+    scroll_accum: egui::Vec2,
+    #[serde(skip)]
+    pointing: Option<(String, i32, i32)>,
+    #[serde(skip)]
+    block: Option<(i32, i32)>,
+    #[serde(skip)]
+    select_drag: bool,
+    #[serde(skip)]
+    typed: Option<String>,
 
     // Save_dialog
     save_dialog: bool,
@@ -141,55 +479,303 @@ pub struct Spreadsheet {
     plot_dialog: bool,
     plot_x_axis: String,
     plot_y_axis: String,
+    #[serde(default)]
+    plot_err_axis: String,
+    #[serde(default)]
+    plot_y2_axis: String,
     plot_rows: String,
     plot_type: Plot,
+    #[serde(default)]
+    plot_style: utils::ui::plot::PlotStyle,
     plot_save: String,
     plot_todo: bool,
+    #[serde(skip)]
+    plot_preview: Option<String>,
 
     // PDF dialog
     pdf_dialog: bool,
     pdf_path: String,
     pdf_todo: bool,
+    #[serde(default)]
+    pdf_report: bool,
+    #[serde(default)]
+    pdf_title: String,
+    #[serde(skip)]
+    pdf_export: Option<utils::ui::loadnsave::PdfExport>,
 
-    clipbaord: String,
+    pub clipbaord: String,
 
     // Describe dialog
     describe_dialog: bool,
     describe_range: String,
     describe_data: [f64; 8],
+    #[serde(default)]
+    describe_shown: String,
+    #[serde(default)]
+    describe_target: String,
+    #[serde(default)]
+    describe_history: Vec<(String, [f64; 8])>,
+
+    // Stats Tests dialog
+    #[serde(default)]
+    tests_dialog: bool,
+    #[serde(default)]
+    tests_kind: utils::ui::stats::StatTest,
+    #[serde(default)]
+    tests_ranges: (String, String),
+    #[serde(default)]
+    tests_result: Option<utils::ui::stats::TestResult>,
 
     // About dialog
     about_dialog: bool,
 
+    // Help dialog
+    #[serde(skip)]
+    help_dialog: bool,
+    #[serde(skip)]
+    help_query: String,
+
+    // Error inspector
+    #[serde(default)]
+    error_panel: bool,
+    #[serde(skip)]
+    show_formulas: bool,
+
+    // Graph stats dialog
+    #[serde(skip)]
+    graph_stats: Option<utils::graph::GraphStats>,
+    #[serde(skip)]
+    trace: Option<(String, Vec<String>)>,
+    #[serde(skip)]
+    palette: Option<(String, usize)>,
+
+    // Hidden rows and columns
+    #[serde(default)]
+    hidden_cols: Vec<i32>,
+    #[serde(default)]
+    hidden_rows: Vec<i32>,
+    #[serde(default)]
+    skip_hidden: bool,
+
+    // Named views
+    #[serde(default)]
+    views: Vec<View>,
+
+    // Auto refresh
+    #[serde(default)]
+    refresh: utils::refresh::Refresh,
+
+    // Paste special dialog
+    #[serde(skip)]
+    paste_dialog: bool,
+    #[serde(skip)]
+    paste_target: String,
+    #[serde(skip)]
+    paste_mode: utils::clipboard::PasteMode,
+    #[serde(skip)]
+    paste_transpose: bool,
+    #[serde(skip)]
+    paste_todo: bool,
+
+    // Find and replace dialog
+    #[serde(skip)]
+    find_dialog: bool,
+    #[serde(skip)]
+    find_query: String,
+    #[serde(skip)]
+    find_replacement: String,
+    #[serde(skip)]
+    find_where: bool,
+    #[serde(skip)]
+    find_matches: Vec<i32>,
+
+    // Replace in formulas dialog
+    #[serde(skip)]
+    rewrite_dialog: bool,
+    #[serde(skip)]
+    rewrite_function: bool,
+    #[serde(skip)]
+    rewrite_from: String,
+    #[serde(skip)]
+    rewrite_to: String,
+    #[serde(skip)]
+    rewrite_changes: Vec<utils::rewrite::Change>,
+
+    // Auto-fill
+    #[serde(skip)]
+    fill_drag: Option<(i32, i32)>,
+    #[serde(skip)]
+    fill_last: Option<FillTarget>,
+    #[serde(skip)]
+    fill_mode: utils::fill::FillMode,
+
+    // Column widths
+    #[serde(default)]
+    col_widths: std::collections::HashMap<i32, f32>,
+    #[serde(skip)]
+    fit_todo: Option<i32>,
+
+    // Column labels
+    #[serde(default)]
+    col_labels: std::collections::BTreeMap<i32, String>,
+    #[serde(default)]
+    col_types: std::collections::BTreeMap<i32, utils::coltype::ColType>,
+    #[serde(default)]
+    col_formulas: std::collections::BTreeMap<i32, utils::colformula::ColFormula>,
+    #[serde(default)]
+    tables: Vec<utils::table::Table>,
+
+    #[serde(default)]
+    provenance: std::collections::BTreeMap<i32, utils::provenance::Provenance>,
+
+    // Session state
+    #[serde(skip)]
+    pub title: String,
+    #[serde(skip)]
+    pub dirty: bool,
+    #[serde(skip)]
+    pub saved_path: Option<String>,
+    #[serde(skip)]
+    autosave_since: Option<std::time::Instant>,
+    #[serde(skip)]
+    pub open_todo: Option<String>,
+    #[serde(skip)]
+    pub restore_todo: Option<(String, u32)>,
+    #[serde(skip)]
+    pub config: utils::config::Config,
+    #[serde(skip)]
+    pub config_todo: Option<utils::config::Config>,
+    #[serde(skip)]
+    settings_draft: Option<(utils::config::Config, [u64; 3])>,
+
     initialized_time: i64,
+    #[serde(skip)]
+    last_recalc: Option<std::time::Duration>,
+    #[serde(skip)]
+    recalc: Option<utils::sheet::Recalc>,
+    #[serde(skip)]
+    recalc_queue: Vec<Assignment>,
+    #[serde(skip)]
+    recalc_edits: Vec<i32>,
+    #[serde(skip)]
+    repaint: Option<egui::Context>,
+
+    #[serde(default)]
+    display_rules: Vec<utils::rules::DisplayRule>,
+    #[serde(default)]
+    styles: std::collections::BTreeMap<i32, utils::style::CellStyle>,
+    #[serde(default)]
+    number_formats: std::collections::BTreeMap<i32, utils::numfmt::NumberFormat>,
+
+    #[serde(default)]
+    watches: Vec<i32>,
+    #[serde(skip)]
+    watch_break: Option<Vec<String>>,
+    #[serde(skip)]
+    watch_pending: Vec<Assignment>,
+
+    #[serde(skip)]
+    validation: Option<(Vec<utils::validate::Problem>, usize)>,
+    #[serde(skip)]
+    export_confirmed: bool,
+    #[serde(skip)]
+    line_impact: Option<(utils::structure::LineEdit, utils::structure::Impact)>,
 }
 
 impl Spreadsheet {
-    pub fn new(
-        len_h: i32,
-        len_v: i32,
-        database: Vec<i32>,
-        err: Vec<bool>,
-        opers: Vec<crate::Ops>,
-        indegree: Vec<i32>,
-        sensi: Vec<Vec<i32>>,
+    /// Creates an empty spreadsheet.
+    ///
+    /// # Arguments
+    /// * `len_h` - Number of columns
+    /// * `len_v` - Number of rows
+    pub fn blank(len_h: i32, len_v: i32) -> Self {
+        Self::from_sheet(crate::SpreadsheetEngine::new(len_h, len_v))
+    }
+
+    /// Declares the types of columns, such as those read with the formulas of a text file.
+    ///
+    /// # Arguments
+    /// * `col_types` - The declared type of each column
+    pub fn with_col_types(
+        mut self,
+        col_types: std::collections::BTreeMap<i32, utils::coltype::ColType>,
+    ) -> Self {
+        self.col_types = col_types;
+        self
+    }
+
+    /// Sets the number format of each formatted cell (see `utils::numfmt`).
+    ///
+    /// # Arguments
+    /// * `number_formats` - The format of each formatted cell
+    pub fn with_number_formats(
+        mut self,
+        number_formats: std::collections::BTreeMap<i32, utils::numfmt::NumberFormat>,
     ) -> Self {
+        self.number_formats = number_formats;
+        self
+    }
+
+    /// Creates the demo workbook (see `utils::demo`).
+    ///
+    /// Besides the data and formulas, the demo has column labels, a saved view of the
+    /// summary, and the plot dialog opened on the revenue per month.
+    ///
+    /// # Arguments
+    /// * `len_h` - Number of columns
+    /// * `len_v` - Number of rows
+    ///
+    /// # Returns
+    /// The demo, or `None` if the spreadsheet is too small for it.
+    pub fn demo(len_h: i32, len_v: i32) -> Option<Self> {
+        let mut demo = Self::from_sheet(utils::demo::sheet(len_h, len_v)?);
+        for (col, text) in utils::demo::LABELS {
+            demo.set_col_label(col, text);
+        }
+        demo.views.push(View {
+            name: String::from("Summary"),
+            top_h: 1,
+            top_v: 1,
+            selected_cell: Some(crate::cell_to_ind("F1", len_h)),
+        });
+        demo.plot_x_axis = String::from("A");
+        demo.plot_y_axis = String::from("D");
+        demo.plot_rows = String::from("1:6");
+        demo.plot_style.title = String::from("Revenue per month");
+        demo.plot_style.x_label = String::from("Month");
+        demo.plot_style.y_label = String::from("Revenue");
+        demo.plot_save = std::env::temp_dir()
+            .join("demo_revenue.png")
+            .display()
+            .to_string();
+        demo.plot_dialog = true;
+        demo.dirty = false;
+        Some(demo)
+    }
+
+    /// Creates a spreadsheet from an evaluated engine state.
+    ///
+    /// # Arguments
+    /// * `engine` - The engine state
+    pub fn from_sheet(engine: crate::SpreadsheetEngine) -> Self {
         Self {
-            len_h,
-            len_v,
+            engine,
             top_h: 1,
             top_v: 1,
-            database,
-            err,
+            view: utils::display::DEFAULT_VIEW,
             terminal: String::new(),
             cell_ref: (String::new(), false, false),
             selected_cell: None,
             hovered_cell: None,
-            opers,
-            indegree,
-            sensi,
             temp_txt: (String::new(), false),
-            formula: vec![String::new(); (len_h * len_v + 1) as usize],
+            focus_todo: None,
+            menu_todo: None,
+            menu_focus: false,
+            scroll_accum: egui::Vec2::ZERO,
+            pointing: None,
+            block: None,
+            select_drag: false,
+            typed: None,
 
             // Save_dialog
             save_dialog: false,
@@ -207,15 +793,22 @@ impl Spreadsheet {
             plot_dialog: false,
             plot_x_axis: String::new(),
             plot_y_axis: String::new(),
+            plot_err_axis: String::new(),
+            plot_y2_axis: String::new(),
             plot_rows: String::new(),
             plot_type: Plot::Line,
+            plot_style: utils::ui::plot::PlotStyle::default(),
             plot_save: String::new(),
             plot_todo: false,
+            plot_preview: None,
 
             // PDF dialog
             pdf_dialog: false,
             pdf_path: String::new(),
             pdf_todo: false,
+            pdf_report: false,
+            pdf_title: String::new(),
+            pdf_export: None,
 
             clipbaord: String::new(),
 
@@ -223,647 +816,4900 @@ impl Spreadsheet {
             describe_dialog: false,
             describe_range: String::new(),
             describe_data: [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            describe_shown: String::new(),
+            describe_target: String::new(),
+            describe_history: Vec::new(),
+
+            // Stats Tests dialog
+            tests_dialog: false,
+            tests_kind: utils::ui::stats::StatTest::TTest,
+            tests_ranges: (String::new(), String::new()),
+            tests_result: None,
 
             // About dialog
             about_dialog: false,
 
-            initialized_time: chrono::Local::now().timestamp(),
-        }
-    }
-}
+            // Help dialog
+            help_dialog: false,
+            help_query: String::new(),
 
-impl eframe::App for Spreadsheet {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Save dialog
-        egui::Window::new("Save Spreadsheet")
-        .open(&mut self.save_dialog)
-        .order(egui::Order::Foreground)
-        .fixed_size(egui::vec2(800.0, 500.0))
-        .collapsible(false)
-        .show(ctx, |ui| {
-            ui.add_space(10.0);
-            ui.add_sized([500.0,30.0],egui::TextEdit::singleline(&mut self.save_name).hint_text("Enter file name").font(FontId::proportional(20.0)));
-            ui.add_space(10.0);
-            ui.horizontal(|ui| {
-                ui.add_sized([400.0,30.0],egui::TextEdit::singleline(&mut self.save_path).hint_text("Enter folder path").font(FontId::proportional(20.0)));
-                // ui.text_edit_singleline(&mut self.save_path);
-                if ui.add_sized([90.0,30.0],Button::new(RichText::new("Browse").font(FontId::proportional(20.0)))).clicked() {
-                    if let Some(path) = rfd::FileDialog::new().pick_folder() {
-                        self.save_path = path.display().to_string();
-                    }};});
-            ui.add_space(10.0);
-            ui.horizontal(|ui| {
-                ui.label("\t\t\t\t\t\t\t");
-                if ui.add(egui::RadioButton::new(self.save_type==Save::Rsk, RichText::new("RSK\t\t\t\t\t\t\t\t").font(FontId::proportional(20.0)))).on_hover_text("Save to a custom file extension that saves the state of program when you next load it").clicked() {
-                    self.save_type = Save::Rsk;
-                }
-                if ui.add(egui::RadioButton::new(self.save_type==Save::Csv, RichText::new("CSV").font(FontId::proportional(20.0)))).on_hover_text("Save all visible values to a CSV but all the formula's are lost").clicked() {
-                    self.save_type = Save::Csv;
-                }
+            // Error inspector
+            error_panel: false,
+            show_formulas: false,
 
-            });
-            ui.horizontal(|ui|{
-                ui.label("\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t");
+            // Graph stats dialog
+            graph_stats: None,
+            trace: None,
+            palette: None,
 
-                if ui.add_sized([100.0,30.0], Button::new(RichText::new("Save").font(FontId::proportional(20.0)))).clicked() {
-                    if self.save_type == Save::Rsk {
-                        let path = format!("{}/{}.rsk", self.save_path,self.save_name);
-                        self.save_todo = Some((self.save_type.clone(),path));
-                    } else if self.save_type == Save::Csv {
-                        let path = format!("{}/{}.csv", self.save_path,self.save_name);
-                        self.save_todo = Some((self.save_type.clone(),path));
-                    }
-                }
-            });
-        });
+            // Hidden rows and columns
+            hidden_cols: Vec::new(),
+            hidden_rows: Vec::new(),
+            skip_hidden: false,
 
-        if self.save_todo.is_some() {
-            println!("{:?}", self.save_todo);
-            let (save_type, path) = self.save_todo.clone().unwrap();
-            self.save_todo = None;
-            self.save_dialog = false;
-            match save_type {
-                Save::Rsk => {
-                    ui::loadnsave::save_to_file(self, &path);
-                }
-                Save::Csv => {
-                    ui::loadnsave::save_1d_as_csv(
-                        &self.database,
-                        &self.err,
-                        self.len_h,
-                        self.len_v,
-                        &path,
-                    )
-                    .unwrap();
-                }
-            }
+            // Named views
+            views: Vec::new(),
 
-            Notification::new()
-                .summary("File Saved")
-                .body(format!("File saved to {}", path).as_str())
-                .show()
-                .unwrap();
-        }
+            // Auto refresh
+            refresh: utils::refresh::Refresh::default(),
 
-        // Load dialog
-        egui::Window::new("Load Spreadsheet")
-            .open(&mut self.load_dialog)
-            .order(egui::Order::Foreground)
-            .fixed_size(egui::vec2(800.0, 500.0))
-            .collapsible(false)
-            .show(ctx, |ui| {
-                ui.add_space(10.0);
+            // Paste special dialog
+            paste_dialog: false,
+            paste_target: String::new(),
+            paste_mode: utils::clipboard::PasteMode::Values,
+            paste_transpose: false,
+            paste_todo: false,
 
-                ui.horizontal(|ui| {
-                    ui.add_sized(
-                        [400.0, 30.0],
-                        egui::TextEdit::singleline(&mut self.load_path)
-                            .hint_text("Enter file path")
-                            .font(FontId::proportional(20.0)),
-                    );
-                    // ui.text_edit_singleline(&mut self.save_path);
-                    if ui
-                        .add_sized(
-                            [90.0, 30.0],
-                            Button::new(RichText::new("Browse").font(FontId::proportional(20.0))),
-                        )
-                        .clicked()
-                    {
-                        if let Some(path) = rfd::FileDialog::new()
-                            .add_filter("Rust Spreadsheet", &["rsk"])
-                            .pick_file()
-                        {
-                            self.load_path = path.display().to_string();
-                        }
-                    };
-                });
-                ui.add_space(10.0);
+            // Find and replace dialog
+            find_dialog: false,
+            find_query: String::new(),
+            find_replacement: String::new(),
+            find_where: false,
+            find_matches: Vec::new(),
 
-                ui.horizontal(|ui| {
-                    ui.label("\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t");
+            // Replace in formulas dialog
+            rewrite_dialog: false,
+            rewrite_function: false,
+            rewrite_from: String::new(),
+            rewrite_to: String::new(),
+            rewrite_changes: Vec::new(),
 
-                    if ui
-                        .add_sized(
-                            [100.0, 30.0],
-                            Button::new(RichText::new("Load").font(FontId::proportional(20.0))),
-                        )
-                        .clicked()
-                    {
-                        self.load_todo = true;
-                    }
-                });
-            });
+            // Auto-fill
+            fill_drag: None,
+            fill_last: None,
+            fill_mode: utils::fill::FillMode::Series,
 
-        if self.load_todo {
-            self.load_dialog = false;
-            self.load_todo = false;
-            let path = self.load_path.clone();
-            let tm = self.initialized_time;
-            *self = ui::loadnsave::read_from_file(self.load_path.as_str());
-            self.initialized_time = tm;
+            // Column widths
+            col_widths: std::collections::HashMap::new(),
+            fit_todo: None,
+
+            // Column labels
+            col_labels: std::collections::BTreeMap::new(),
+            col_types: std::collections::BTreeMap::new(),
+            col_formulas: std::collections::BTreeMap::new(),
+            tables: Vec::new(),
+
+            // Header row
+            provenance: std::collections::BTreeMap::new(),
+
+            // Session state
+            title: String::new(),
+            dirty: false,
+            saved_path: None,
+            autosave_since: None,
+            open_todo: None,
+            restore_todo: None,
+            config: utils::config::Config::default(),
+            config_todo: None,
+            settings_draft: None,
+
+            initialized_time: chrono::Local::now().timestamp(),
+            last_recalc: None,
+            recalc: None,
+            recalc_queue: Vec::new(),
+            recalc_edits: Vec::new(),
+            repaint: None,
+
+            display_rules: Vec::new(),
+            styles: std::collections::BTreeMap::new(),
+            number_formats: std::collections::BTreeMap::new(),
+
+            watches: Vec::new(),
+            watch_break: None,
+            watch_pending: Vec::new(),
+
+            validation: None,
+            export_confirmed: false,
+            line_impact: None,
+        }
+    }
+
+    /// Scrolls the viewport so that a cell is visible and selects it for editing.
+    ///
+    /// # Arguments
+    /// * `t` - Index of the cell to jump to
+    fn jump_to_cell(&mut self, t: i32) {
+        self.scroll_into_view(t);
+        self.selected_cell = Some(t);
+        self.temp_txt.1 = true;
+    }
+
+    /// Sets how many rows and columns the grid shows and keeps it between runs.
+    ///
+    /// # Arguments
+    /// * `view` - Rows and columns, or `None` to show as many as fit in the window
+    fn set_view_size(&mut self, view: Option<(i32, i32)>) {
+        self.config.view = view;
+        if let Err(e) = utils::config::save(&self.config) {
             Notification::new()
-                .summary("File Loaded")
-                .body(format!("File Loaded from {}", path).as_str())
+                .summary("Settings Not Saved")
+                .body(format!("The grid size applies until the app is closed: {}", e).as_str())
                 .show()
                 .unwrap();
         }
+    }
 
-        //  Plot dialog
-        egui::Window::new("Plot Data")
-            .open(&mut self.plot_dialog)
-            .order(egui::Order::Foreground)
-            .fixed_size(egui::vec2(800.0, 500.0))
-            .collapsible(false)
-            .show(ctx, |ui| {
-                ui.add_space(10.0);
+    /// Counts the rows and columns of the grid that fit in the space left for it.
+    ///
+    /// Columns are counted from the leftmost visible one with their own widths. Room is left
+    /// for the headers, the scroll bars and the command terminal below the grid.
+    ///
+    /// # Arguments
+    /// * `space` - Width and height left for the grid and the terminal
+    /// * `spacing` - Space between cells
+    ///
+    /// # Returns
+    /// The number of rows and columns, each at least 1 and at most `MAX_VIEW`.
+    fn fit_view(&self, space: egui::Vec2, spacing: egui::Vec2) -> (i32, i32) {
+        let labels = if self.col_labels.is_empty() {
+            0.0
+        } else {
+            30.0
+        };
+        let height = space.y - 35.0 - labels - 100.0;
+        let rows = (height / (45.0 + spacing.y)).floor() as i32;
+        let mut width = space.x - 70.0 - 40.0;
+        let mut cols = 0;
+        for col in (self.top_h..=self.engine.len_h).filter(|c| !self.hidden_cols.contains(c)) {
+            width -= self.col_width(col) + spacing.x;
+            if width < 0.0 {
+                break;
+            }
+            cols += 1;
+        }
+        let max = utils::config::MAX_VIEW;
+        (rows.clamp(1, max), cols.clamp(1, max))
+    }
 
-                ui.horizontal(|ui| {
-                    ui.label(RichText::new("X-Axis:\t").font(FontId::proportional(20.0)));
-                    ui.add_sized(
-                        [450.0, 30.0],
-                        egui::TextEdit::singleline(&mut self.plot_x_axis)
-                            .hint_text("Enter column for X-axis")
-                            .font(FontId::proportional(20.0)),
-                    );
-                });
+    /// Scrolls the viewport by a number of rows and columns, stopping at the edges.
+    ///
+    /// # Arguments
+    /// * `rows` - Rows to scroll down by (negative to scroll up)
+    /// * `cols` - Columns to scroll right by (negative to scroll left)
+    fn scroll_by(&mut self, rows: i32, cols: i32) {
+        self.top_v =
+            (self.top_v.saturating_add(rows)).clamp(1, max_top(self.engine.len_v, self.view.0));
+        self.top_h =
+            (self.top_h.saturating_add(cols)).clamp(1, max_top(self.engine.len_h, self.view.1));
+    }
 
-                ui.add_space(10.0);
+    /// Scrolls the viewport just far enough to show a cell.
+    ///
+    /// # Arguments
+    /// * `t` - The cell index
+    fn scroll_into_view(&mut self, t: i32) {
+        let mut x1 = t % self.engine.len_h;
+        if x1 == 0 {
+            x1 = self.engine.len_h;
+        }
+        let y1 = t / self.engine.len_h + ((x1 != self.engine.len_h) as i32);
 
-                ui.horizontal(|ui| {
-                    ui.label(RichText::new("Y-Axis:\t").font(FontId::proportional(20.0)));
-                    ui.add_sized(
-                        [450.0, 30.0],
-                        egui::TextEdit::singleline(&mut self.plot_y_axis)
-                            .hint_text("Enter column for Y-axis")
-                            .font(FontId::proportional(20.0)),
-                    );
-                });
+        if x1 < self.top_h {
+            self.top_h = x1;
+        } else if !visible_lines(
+            self.top_h,
+            self.engine.len_h,
+            &self.hidden_cols,
+            self.view.1,
+        )
+        .contains(&x1)
+        {
+            self.top_h = top_for(x1, &self.hidden_cols, self.view.1);
+        }
 
-                ui.add_space(10.0);
+        if y1 < self.top_v {
+            self.top_v = y1;
+        } else if !visible_lines(
+            self.top_v,
+            self.engine.len_v,
+            &self.hidden_rows,
+            self.view.0,
+        )
+        .contains(&y1)
+        {
+            self.top_v = top_for(y1, &self.hidden_rows, self.view.0);
+        }
+    }
+
+    /// Gives the nearest visible cell in a direction, skipping hidden rows and columns.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the cell to start from
+    /// * `dc` - Columns to move by, -1, 0 or 1
+    /// * `dr` - Rows to move by, -1, 0 or 1
+    ///
+    /// # Returns
+    /// The index of the cell, or `ind` at the edge of the sheet.
+    fn neighbour(&self, ind: i32, dc: i32, dr: i32) -> i32 {
+        let (col, row) = (
+            (ind - 1) % self.engine.len_h + 1,
+            (ind - 1) / self.engine.len_h + 1,
+        );
+        let (mut c, mut r) = (col + dc, row + dr);
+        while (1..=self.engine.len_h).contains(&c) && (1..=self.engine.len_v).contains(&r) {
+            if !self.hidden_cols.contains(&c) && !self.hidden_rows.contains(&r) {
+                return c + (r - 1) * self.engine.len_h;
+            }
+            (c, r) = (c + dc, r + dr);
+        }
+        ind
+    }
+
+    /// Moves keyboard focus between cells with the arrow keys, and right or left with Tab
+    /// and Shift+Tab.
+    ///
+    /// Hidden rows and columns are skipped and the viewport follows the focused cell. Shift
+    /// with an arrow key extends the selected block instead of starting a new one.
+    ///
+    /// # Arguments
+    /// * `ui` - The ui whose key presses are used
+    /// * `ind` - Index of the focused cell
+    fn move_focus_with_keys(&mut self, ui: &egui::Ui, ind: i32) {
+        let keys = [
+            (egui::Key::ArrowLeft, -1, 0),
+            (egui::Key::ArrowRight, 1, 0),
+            (egui::Key::ArrowUp, 0, -1),
+            (egui::Key::ArrowDown, 0, 1),
+        ];
+        for (key, dc, dr) in keys {
+            // Shift is checked first, since a plain key press also matches Shift
+            let extend = ui.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, key));
+            if !extend && !ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, key)) {
+                continue;
+            }
+            let next = self.neighbour(ind, dc, dr);
+            let anchor = match self.block {
+                Some((anchor, _)) if extend => anchor,
+                _ => next,
+            };
+            self.block = Some((anchor, next));
+            self.scroll_into_view(next);
+            self.focus_todo = Some(next);
+        }
+        for (modifiers, dc) in [(egui::Modifiers::SHIFT, -1), (egui::Modifiers::NONE, 1)] {
+            if ui.input_mut(|i| i.consume_key(modifiers, egui::Key::Tab)) {
+                let next = self.neighbour(ind, dc, 0);
+                self.scroll_into_view(next);
+                self.focus_todo = Some(next);
+            }
+        }
+    }
+
+    /// Gives the selected block, or the focused cell if no block ends at it.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the focused cell
+    ///
+    /// # Returns
+    /// First column, first row, last column and last row of the block.
+    fn block_range(&self, ind: i32) -> (i32, i32, i32, i32) {
+        let (anchor, cursor) = self.block.filter(|b| b.1 == ind).unwrap_or((ind, ind));
+        let pos = |c: i32| {
+            (
+                (c - 1) % self.engine.len_h + 1,
+                (c - 1) / self.engine.len_h + 1,
+            )
+        };
+        let ((c1, r1), (c2, r2)) = (pos(anchor), pos(cursor));
+        (c1.min(c2), r1.min(r2), c1.max(c2), r1.max(r2))
+    }
+
+    /// Gives the text of a cell as copied to other applications.
+    ///
+    /// Values are written without display rules or number formats, so they can be pasted
+    /// back, and unassigned cells are empty.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the cell
+    fn copy_text(&self, ind: i32) -> String {
+        if let Some(text) = self.header_label(ind) {
+            text.clone()
+        } else if self.engine.opers()[ind as usize].opcpde.is_empty() {
+            String::new()
+        } else if !self.engine.err()[ind as usize] {
+            utils::date::value_text(
+                self.engine.database(),
+                self.engine.opers(),
+                ind,
+                self.engine.len_h,
+            )
+        } else if self.engine.opers()[ind as usize].opcpde == utils::structure::REF_OP {
+            utils::structure::REF_TEXT.to_string()
+        } else {
+            "ERR".to_string()
+        }
+    }
+
+    /// Copies the selected block, or the focused cell, to the system clipboard.
+    ///
+    /// The block is written as tab-separated text and is also kept in the application
+    /// clipboard, so `paste` and Paste Special can use it. Cutting empties the copied cells.
+    ///
+    /// # Arguments
+    /// * `ctx` - The egui context whose clipboard is written
+    /// * `ind` - Index of the focused cell
+    /// * `cut` - Whether the copied cells are emptied
+    fn copy_block(&mut self, ctx: &egui::Context, ind: i32, cut: bool) {
+        let range = self.block_range(ind);
+
+        ctx.copy_text(utils::clipboard::to_tsv(range, self.engine.len_h, |c| {
+            self.copy_text(c)
+        }));
+        let clip = utils::clipboard::copy(
+            self.engine.database(),
+            self.engine.err(),
+            self.engine.opers(),
+            &self.styles,
+            &self.number_formats,
+            self.engine.len_h,
+            range,
+        );
+        self.clipbaord = serde_json::to_string(&clip).expect("Failed to serialize clipboard");
+
+        if cut {
+            self.clear_block(range);
+        }
+    }
+
+    /// Empties the assigned cells of a block (see `Assignment::empty`). Labels of the header
+    /// row are kept.
+    ///
+    /// # Arguments
+    /// * `range` - First column, first row, last column and last row of the block
+    fn clear_block(&mut self, (x1, y1, x2, y2): (i32, i32, i32, i32)) {
+        let outs = (y1..=y2)
+            .flat_map(|y| (x1..=x2).map(move |x| (x, y)))
+            .filter(|&(x, y)| {
+                let c = x + (y - 1) * self.engine.len_h;
+                !self.engine.opers()[c as usize].opcpde.is_empty() && self.header_label(c).is_none()
+            })
+            .map(|(x, y)| Assignment::empty(&format!("{}{}", utils::display::get_label(x), y)))
+            .collect();
+        self.apply_assignments(outs);
+    }
+
+    /// Summarizes the values of the selected block, if it has more than one cell.
+    ///
+    /// # Returns
+    /// The sum, average, count, minimum and maximum, or `None` if no block is selected or it
+    /// has no values.
+    fn block_summary(&self) -> Option<String> {
+        let (_, cursor) = self.block.filter(|b| b.0 != b.1)?;
+        let range = self.block_range(cursor);
+        let values = utils::analysis::values(
+            self.engine.database(),
+            self.engine.err(),
+            self.engine.opers(),
+            self.engine.len_h,
+            range,
+        );
+        utils::analysis::summary(&values)
+    }
+
+    /// Pastes text from the system clipboard with its top-left corner at a cell.
+    ///
+    /// Whole numbers in tab- or comma-separated text become constants, tagged as imported
+    /// from the clipboard, and the result is reported in a notification. Values that do not
+    /// fit the type of their column are refused.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the top-left cell of the pasted block
+    /// * `text` - The pasted text
+    fn paste_system(&mut self, ind: i32, text: &str) {
+        let target = (
+            (ind - 1) % self.engine.len_h + 1,
+            (ind - 1) / self.engine.len_h + 1,
+        );
+        let (pasted, skipped) =
+            utils::clipboard::paste_text(text, target, self.engine.len_h, self.engine.len_v);
+        let (mut commands, mut refused) = (Vec::new(), 0);
+        for command in pasted {
+            match utils::coltype::enforce(
+                &self.col_types,
+                &command,
+                self.engine.opers(),
+                self.engine.len_h,
+            ) {
+                Ok((command, _)) => commands.push(command),
+                Err(_) => refused += 1,
+            }
+        }
+        let applied = self.apply_commands(&commands);
+        utils::provenance::tag(
+            &mut self.provenance,
+            &commands,
+            "clipboard",
+            chrono::Utc::now().timestamp(),
+            self.engine.len_h,
+        );
+        let mut body = format!("Pasted {} cells.", applied);
+        if skipped > 0 {
+            body.push_str(&format!(
+                " Skipped {} values that are not whole numbers or fall outside the sheet.",
+                skipped
+            ));
+        }
+        if refused > 0 {
+            body.push_str(&format!(
+                " Refused {} values that do not fit the type of their column.",
+                refused
+            ));
+        }
+        Notification::new()
+            .summary("Paste")
+            .body(&body)
+            .show()
+            .unwrap();
+    }
+
+    /// Checks a typed assignment against the type of the column of its cell, after replacing
+    /// its table references with the cells they stand for.
+    ///
+    /// A notification tells if the value was converted to the type or refused, or if a table
+    /// reference names a column the table does not have.
+    ///
+    /// # Arguments
+    /// * `command` - The assignment, such as "B2=5"
+    ///
+    /// # Returns
+    /// The assignment to apply, or `None` if it is refused.
+    fn check_type(&self, command: &str) -> Option<String> {
+        let command = match utils::table::resolve(command, &self.tables) {
+            Ok(command) => command,
+            Err(message) => {
+                Notification::new()
+                    .summary("Invalid Table")
+                    .body(&message)
+                    .show()
+                    .unwrap();
+                return None;
+            }
+        };
+        match utils::coltype::enforce(
+            &self.col_types,
+            &command,
+            self.engine.opers(),
+            self.engine.len_h,
+        ) {
+            Ok((command, warning)) => {
+                if let Some(warning) = warning {
+                    Notification::new()
+                        .summary("Value Converted")
+                        .body(&warning)
+                        .show()
+                        .unwrap();
+                }
+                Some(command)
+            }
+            Err(message) => {
+                Notification::new()
+                    .summary("Wrong Column Type")
+                    .body(&message)
+                    .show()
+                    .unwrap();
+                None
+            }
+        }
+    }
+
+    /// Gives the name of a cell in the reference style of the view.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the cell
+    fn cell_name(&self, ind: i32) -> String {
+        utils::cellref::CellRef::from_ind(ind, self.engine.len_h).show(self.config.r1c1)
+    }
+
+    /// Gives the formula of a cell without the leading `=`, in the reference style of the view.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the cell
+    fn shown_formula(&self, ind: i32) -> String {
+        utils::cellref::show_formula(
+            &utils::input::formula_text(&self.engine.opers()[ind as usize], self.engine.len_h),
+            self.config.r1c1,
+        )
+    }
+
+    /// Gives the value of a cell as shown, in its number format, with dates and times in ISO
+    /// format.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the cell
+    fn value_text(&self, ind: i32) -> String {
+        utils::numfmt::value_text(
+            &self.number_formats,
+            self.engine.database(),
+            self.engine.opers(),
+            ind,
+            self.engine.len_h,
+        )
+    }
+
+    /// Describes a cell for screen readers.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the cell
+    ///
+    /// # Returns
+    /// The cell name, its value and its formula, e.g. "B2, 7, formula A1+5".
+    fn cell_label(&self, ind: i32) -> String {
+        let value = if self.engine.err()[ind as usize] {
+            String::from("error")
+        } else {
+            self.value_text(ind)
+        };
+        let mut label = format!("{}, {}", self.cell_name(ind), value);
+        let formula = self.shown_formula(ind);
+        if !formula.is_empty() {
+            label.push_str(&format!(", formula {}", formula));
+        }
+        if self.is_pending(ind) {
+            label.push_str(", recalculating");
+        }
+        label
+    }
+
+    /// Runs a command typed in the terminal that is not a cell assignment.
+    ///
+    /// # Arguments
+    /// * `command` - The trimmed terminal input
+    ///
+    /// # Returns
+    /// `true` if the command was recognised and handled, `false` if it should be
+    /// treated as a formula.
+    fn run_command(&mut self, command: &str) -> bool {
+        match command {
+            "graph_stats" => {
+                self.graph_stats = Some(utils::graph::graph_stats(
+                    self.engine.opers(),
+                    self.engine.sensi(),
+                    self.engine.len_h,
+                ));
+                true
+            }
+            cmd if cmd == "help" || cmd.starts_with("help ") => {
+                self.help_query = cmd["help".len()..].trim().to_string();
+                self.help_dialog = true;
+                true
+            }
+            cmd if cmd.starts_with("watch ") || cmd.starts_with("unwatch ") => {
+                let status = utils::watch::watch_command(
+                    cmd,
+                    &mut self.watches,
+                    self.engine.len_h,
+                    self.engine.len_v,
+                );
+                if status != "ok" {
+                    Notification::new()
+                        .summary(status)
+                        .body("Usage: watch <cell> or unwatch <cell>")
+                        .show()
+                        .unwrap();
+                }
+                true
+            }
+            cmd if cmd.starts_with("rule ") || cmd.starts_with("unrule ") => {
+                let status = utils::rules::rule_command(
+                    cmd,
+                    &mut self.display_rules,
+                    self.engine.len_h,
+                    self.engine.len_v,
+                );
+                if status == "ok" {
+                    self.dirty = true;
+                } else {
+                    Notification::new()
+                        .summary(status)
+                        .body(
+                            "Usage: rule <range> [negative] [above <value> [color]] \
+                             [below <value> [color]] or unrule <range>",
+                        )
+                        .show()
+                        .unwrap();
+                }
+                true
+            }
+            cmd if cmd.starts_with("style ") || cmd.starts_with("unstyle ") => {
+                let status = utils::style::style_command(
+                    cmd,
+                    &mut self.styles,
+                    self.engine.len_h,
+                    self.engine.len_v,
+                );
+                if status == "ok" {
+                    self.dirty = true;
+                } else {
+                    Notification::new()
+                        .summary(status)
+                        .body(
+                            "Usage: style <range> [bold|plain] [align left|center|right] \
+                             [color <color>] [background <color>] or unstyle <range>",
+                        )
+                        .show()
+                        .unwrap();
+                }
+                true
+            }
+            cmd if cmd.starts_with("format ") || cmd.starts_with("unformat ") => {
+                let status = utils::numfmt::format_command(
+                    cmd,
+                    &mut self.number_formats,
+                    self.engine.len_h,
+                    self.engine.len_v,
+                );
+                if status == "ok" {
+                    self.dirty = true;
+                } else {
+                    Notification::new()
+                        .summary(status)
+                        .body(
+                            "Usage: format <range> [thousands] [decimals <places>] [percent] \
+                             [currency [symbol]] or unformat <range>",
+                        )
+                        .show()
+                        .unwrap();
+                }
+                true
+            }
+            "rules" => {
+                let lines: Vec<String> = self
+                    .display_rules
+                    .iter()
+                    .map(|r| r.text(self.engine.len_h))
+                    .collect();
+                let body = if lines.is_empty() {
+                    String::from("No display rules.")
+                } else {
+                    lines.join("\n")
+                };
+                Notification::new()
+                    .summary("Display Rules")
+                    .body(&body)
+                    .show()
+                    .unwrap();
+                true
+            }
+            cmd if cmd.starts_with("trace ") => {
+                let cell = cmd["trace ".len()..].trim();
+                if utils::input::is_valid_cell(cell, self.engine.len_h, self.engine.len_v) {
+                    let lines = utils::graph::trace(
+                        crate::cell_to_ind(cell, self.engine.len_h),
+                        self.engine.database(),
+                        self.engine.opers(),
+                        self.engine.err(),
+                        self.engine.len_h,
+                    );
+                    self.trace = Some((cell.to_string(), lines));
+                } else {
+                    Notification::new()
+                        .summary("Invalid Cell")
+                        .body("Usage: trace <cell>")
+                        .show()
+                        .unwrap();
+                }
+                true
+            }
+            cmd if cmd.starts_with("limit ") => {
+                self.finish_recalc();
+                let status = self.engine.apply_setting(cmd).unwrap_or(Ok(()));
+                Notification::new()
+                    .summary("Resource Limits")
+                    .body(if status.is_ok() {
+                        "Limit updated."
+                    } else {
+                        "Usage: limit <cells|time|sleep> <value|off>"
+                    })
+                    .show()
+                    .unwrap();
+                true
+            }
+            "verify" => {
+                let mismatches = self.engine.verify();
+                let body = if mismatches.is_empty() {
+                    String::from("All cells match a full recalculation.")
+                } else {
+                    let cells: Vec<String> =
+                        mismatches.iter().map(|&c| self.cell_name(c)).collect();
+                    format!("Mismatch in cells: {}", cells.join(", "))
+                };
+                Notification::new()
+                    .summary("Verify")
+                    .body(&body)
+                    .show()
+                    .unwrap();
+                true
+            }
+            cmd if cmd.starts_with("hide_col ") || cmd.starts_with("unhide_col ") => {
+                let (action, label) = cmd.split_once(' ').unwrap();
+                match utils::input::parse_col(label.trim(), self.engine.len_h) {
+                    Some(col) => self.set_hidden(false, col, action == "hide_col"),
+                    None => {
+                        Notification::new()
+                            .summary("Invalid Column")
+                            .body("Usage: hide_col <column> or unhide_col <column>")
+                            .show()
+                            .unwrap();
+                    }
+                }
+                true
+            }
+            cmd if cmd.starts_with("hide_row ") || cmd.starts_with("unhide_row ") => {
+                let (action, row) = cmd.split_once(' ').unwrap();
+                match row.trim().parse::<i32>() {
+                    Ok(row) if (1..=self.engine.len_v).contains(&row) => {
+                        self.set_hidden(true, row, action == "hide_row")
+                    }
+                    _ => {
+                        Notification::new()
+                            .summary("Invalid Row")
+                            .body("Usage: hide_row <row> or unhide_row <row>")
+                            .show()
+                            .unwrap();
+                    }
+                }
+                true
+            }
+            cmd if cmd.starts_with("copy ") => {
+                if !self.copy_range(cmd["copy ".len()..].trim()) {
+                    Notification::new()
+                        .summary("Invalid Range")
+                        .body("Usage: copy <cell>:<cell>")
+                        .show()
+                        .unwrap();
+                }
+                true
+            }
+            cmd if cmd.starts_with("paste ") => {
+                let cell = cmd["paste ".len()..].trim().to_string();
+                self.paste_and_notify(&cell, utils::clipboard::PasteMode::Values, false);
+                true
+            }
+            cmd if cmd.starts_with("fill ") => {
+                match utils::fill::parse_command(cmd, self.engine.len_h, self.engine.len_v) {
+                    Some((source, end, mode)) => self.apply_fill(source, end, mode),
+                    None => {
+                        Notification::new()
+                            .summary("Invalid Fill")
+                            .body("Usage: fill <cell>:<cell> [<cell>] [series|copy]")
+                            .show()
+                            .unwrap();
+                    }
+                }
+                true
+            }
+            cmd if let Some(result) = utils::analysis::run(
+                cmd,
+                self.engine.database(),
+                self.engine.err(),
+                self.engine.opers(),
+                self.engine.ranges(),
+                self.engine.len_h,
+                self.engine.len_v,
+            ) =>
+            {
+                match result {
+                    Ok(commands) => {
+                        self.apply_commands(&commands);
+                    }
+                    Err(e) => {
+                        Notification::new()
+                            .summary(e)
+                            .body(format!("Usage: {}", utils::analysis::usage(cmd)).as_str())
+                            .show()
+                            .unwrap();
+                    }
+                }
+                true
+            }
+            cmd if cmd.starts_with("TTEST(") || cmd.starts_with("CHITEST(") => {
+                match utils::ui::stats::StatTest::parse(cmd) {
+                    Some((kind, first, second)) => {
+                        self.tests_kind = kind;
+                        self.tests_ranges = (first.to_string(), second.to_string());
+                        self.tests_dialog = true;
+                        self.run_test();
+                    }
+                    None => {
+                        Notification::new()
+                            .summary("Invalid Test")
+                            .body("Usage: TTEST(<cell>:<cell>, <cell>:<cell>) or CHITEST(<observed>, <expected>)")
+                            .show()
+                            .unwrap();
+                    }
+                }
+                true
+            }
+            "stats_tests" => {
+                self.tests_dialog = true;
+                true
+            }
+            "find" => {
+                self.find_dialog = true;
+                true
+            }
+            cmd if cmd.starts_with("find ") || cmd.starts_with("find_where ") => {
+                let (name, query) = cmd.split_once(' ').unwrap_or_default();
+                self.find_query = query.trim().to_string();
+                self.find_where = name == "find_where";
+                self.find_dialog = true;
+                self.find_step(true);
+                true
+            }
+            cmd if cmd.starts_with("replace ") => {
+                let words: Vec<&str> = cmd.split_whitespace().collect();
+                if words.len() == 3 {
+                    self.find_query = words[1].to_string();
+                    self.find_replacement = words[2].to_string();
+                    self.find_where = false;
+                    self.replace_all();
+                } else {
+                    Notification::new()
+                        .summary("Invalid Replace")
+                        .body("Usage: replace <text> <replacement>")
+                        .show()
+                        .unwrap();
+                }
+                true
+            }
+            "rewrite" => {
+                self.rewrite_dialog = true;
+                true
+            }
+            cmd if cmd.starts_with("rewrite ") => {
+                let words: Vec<&str> = cmd.split_whitespace().collect();
+                if let [_, kind @ ("col" | "fn"), from, to] = words[..] {
+                    self.rewrite_function = kind == "fn";
+                    self.rewrite_from = from.to_string();
+                    self.rewrite_to = to.to_string();
+                }
+                match self.rewrite_preview() {
+                    Some(changes) => {
+                        self.rewrite_changes = changes;
+                        self.rewrite_dialog = true;
+                    }
+                    None => {
+                        Notification::new()
+                            .summary("Invalid Rewrite")
+                            .body("Usage: rewrite col <column> <column> or rewrite fn <function> <function>")
+                            .show()
+                            .unwrap();
+                    }
+                }
+                true
+            }
+            "settings" => {
+                self.open_settings();
+                true
+            }
+            "show_formulas" => {
+                self.show_formulas = !self.show_formulas;
+                true
+            }
+            "paste_special" => {
+                if let Some(selected) = self.selected_cell {
+                    self.paste_target = self.cell_name(selected);
+                }
+                self.paste_dialog = true;
+                true
+            }
+            cmd if cmd.starts_with("type_col ") => {
+                if utils::coltype::type_command(cmd, &mut self.col_types, self.engine.len_h) == "ok"
+                {
+                    self.dirty = true;
+                } else {
+                    let types: Vec<&str> = utils::coltype::ColType::ALL
+                        .iter()
+                        .map(|t| t.name())
+                        .collect();
+                    Notification::new()
+                        .summary("Invalid Column Type")
+                        .body(format!("Usage: type_col <column> [{}]", types.join("|")).as_str())
+                        .show()
+                        .unwrap();
+                }
+                true
+            }
+            cmd if cmd.starts_with("table ") => {
+                let status = utils::table::table_command(
+                    cmd,
+                    &mut self.tables,
+                    self.engine.len_h,
+                    self.engine.len_v,
+                );
+                if status == "ok" {
+                    self.dirty = true;
+                } else {
+                    Notification::new()
+                        .summary(status)
+                        .body("Usage: table <name> [<range> <column>,<column>,...], such as table Sales A2:C10 Date,Amount,Region")
+                        .show()
+                        .unwrap();
+                }
+                true
+            }
+            cmd if cmd.starts_with("col_formula ") => {
+                self.finish_recalc();
+                let filled = utils::colformula::formula_command(
+                    cmd,
+                    &mut self.col_formulas,
+                    self.engine.opers(),
+                    self.engine.len_h,
+                    self.engine.len_v,
+                );
+                match filled {
+                    Ok(commands) => {
+                        self.apply_commands(&commands);
+                        self.dirty = true;
+                    }
+                    Err(e) => {
+                        Notification::new()
+                            .summary(e)
+                            .body("Usage: col_formula <column or first cell> [<formula>], such as col_formula C2 C[-1]+B")
+                            .show()
+                            .unwrap();
+                    }
+                }
+                true
+            }
+            cmd if cmd.starts_with("label_col ") => {
+                let rest = cmd["label_col ".len()..].trim();
+                let (col, text) = rest.split_once(' ').unwrap_or((rest, ""));
+                match utils::input::parse_col(col, self.engine.len_h) {
+                    Some(col) => self.set_col_label(col, text.trim()),
+                    None => {
+                        Notification::new()
+                            .summary("Invalid Column")
+                            .body("Usage: label_col <column> [<label>]")
+                            .show()
+                            .unwrap();
+                    }
+                }
+                true
+            }
+            cmd if ["delete_row ", "delete_col ", "insert_row ", "insert_col "]
+                .iter()
+                .any(|p| cmd.starts_with(p)) =>
+            {
+                match utils::structure::LineEdit::parse(cmd, self.engine.len_h, self.engine.len_v) {
+                    Some(edit) => self.edit_lines(edit),
+                    None => {
+                        Notification::new()
+                            .summary("Invalid Row/Column")
+                            .body("Usage: delete_row <row>, delete_col <column>, insert_row <row> or insert_col <column>")
+                            .show()
+                            .unwrap();
+                    }
+                }
+                true
+            }
+            cmd if cmd.starts_with("fit_col ") => {
+                match utils::input::parse_col(cmd["fit_col ".len()..].trim(), self.engine.len_h) {
+                    Some(col) => self.fit_todo = Some(col),
+                    None => {
+                        Notification::new()
+                            .summary("Invalid Column")
+                            .body("Usage: fit_col <column>")
+                            .show()
+                            .unwrap();
+                    }
+                }
+                true
+            }
+            cmd if cmd.starts_with("save_view ") => {
+                let name = cmd["save_view ".len()..].trim().to_string();
+                self.views.retain(|v| !v.name.eq_ignore_ascii_case(&name));
+                self.views.push(View {
+                    name,
+                    top_h: self.top_h,
+                    top_v: self.top_v,
+                    selected_cell: self.selected_cell,
+                });
+                self.dirty = true;
+                true
+            }
+            cmd if cmd.starts_with("delete_view ") => {
+                let name = cmd["delete_view ".len()..].trim();
+                self.views.retain(|v| !v.name.eq_ignore_ascii_case(name));
+                self.dirty = true;
+                true
+            }
+            cmd if cmd.starts_with("view ") => {
+                let name = cmd["view ".len()..].trim();
+                if let Some(view) = self
+                    .views
+                    .iter()
+                    .find(|v| v.name.eq_ignore_ascii_case(name))
+                    .cloned()
+                {
+                    self.show_view(&view);
+                } else {
+                    Notification::new()
+                        .summary("Unknown View")
+                        .body(format!("There is no view named {}.", name).as_str())
+                        .show()
+                        .unwrap();
+                }
+                true
+            }
+            "header_row on" | "header_row off" => {
+                self.finish_recalc();
+                self.engine.set_ranges(utils::operations::RangeOptions {
+                    header_row: command == "header_row on",
+                    ..self.engine.ranges().clone()
+                });
+                self.dirty = true;
+                true
+            }
+            "skip_errors on" | "skip_errors off" => {
+                self.finish_recalc();
+                let _ = self.engine.apply_setting(command);
+                self.dirty = true;
+                true
+            }
+            "ref_style a1" | "ref_style r1c1" => {
+                self.config.r1c1 = command == "ref_style r1c1";
+                if let Err(e) = utils::config::save(&self.config) {
+                    Notification::new()
+                        .summary("Settings Not Saved")
+                        .body(
+                            format!("The reference style applies until the app is closed: {}", e)
+                                .as_str(),
+                        )
+                        .show()
+                        .unwrap();
+                }
+                self.config_todo = Some(self.config.clone());
+                true
+            }
+            "safe_mode on" | "safe_mode off" => {
+                self.finish_recalc();
+                let _ = self.engine.apply_setting(command);
+                Notification::new()
+                    .summary("Safe Mode")
+                    .body(if self.engine.limits().safe_mode() {
+                        "Safe mode enabled. SLEEP no longer waits, and TODAY, NOW and auto refresh are frozen."
+                    } else {
+                        "Safe mode disabled."
+                    })
+                    .show()
+                    .unwrap();
+                true
+            }
+            cmd if cmd.starts_with("ui_scale ") => {
+                match utils::config::parse_scale(cmd["ui_scale ".len()..].trim()) {
+                    Some(scale) => {
+                        self.config.ui_scale = scale;
+                        if let Err(e) = utils::config::save(&self.config) {
+                            Notification::new()
+                                .summary("Settings Not Saved")
+                                .body(
+                                    format!("The UI scale applies until the app is closed: {}", e)
+                                        .as_str(),
+                                )
+                                .show()
+                                .unwrap();
+                        }
+                        self.config_todo = Some(self.config.clone());
+                    }
+                    None => {
+                        Notification::new()
+                            .summary("Invalid Scale")
+                            .body(
+                                format!(
+                                    "Usage: ui_scale <{}-{}|auto>",
+                                    utils::config::MIN_SCALE,
+                                    utils::config::MAX_SCALE
+                                )
+                                .as_str(),
+                            )
+                            .show()
+                            .unwrap();
+                    }
+                }
+                true
+            }
+            cmd if cmd.starts_with("view_size ") => {
+                match utils::config::parse_view(cmd["view_size ".len()..].trim()) {
+                    Some(view) => self.set_view_size(view),
+                    None => {
+                        Notification::new()
+                            .summary("Invalid View Size")
+                            .body(
+                                format!(
+                                    "Usage: view_size <rows>x<columns>|auto, with up to {} of each",
+                                    utils::config::MAX_VIEW
+                                )
+                                .as_str(),
+                            )
+                            .show()
+                            .unwrap();
+                    }
+                }
+                true
+            }
+            cmd if cmd.starts_with("auto_refresh ") => {
+                if self.refresh.command(cmd, std::time::Instant::now()) == "ok" {
+                    self.dirty = true;
+                } else {
+                    Notification::new()
+                        .summary("Invalid Refresh")
+                        .body(
+                            format!(
+                                "Usage: auto_refresh <1-{} seconds|off|pause|resume>",
+                                utils::refresh::MAX_INTERVAL
+                            )
+                            .as_str(),
+                        )
+                        .show()
+                        .unwrap();
+                }
+                true
+            }
+            cmd if cmd.starts_with("clock ") => {
+                if self.config.clock_command(cmd) == "ok" {
+                    if let Err(e) = utils::config::save(&self.config) {
+                        Notification::new()
+                            .summary("Settings Not Saved")
+                            .body(
+                                format!("The clock setting applies until the app is closed: {}", e)
+                                    .as_str(),
+                            )
+                            .show()
+                            .unwrap();
+                    }
+                    self.config_todo = Some(self.config.clone());
+                } else {
+                    Notification::new()
+                        .summary("Invalid Clock Setting")
+                        .body("Usage: clock <date|time> <strftime format>, clock zone <local|UTC|+HH:MM>, clock elapsed <format with %H %M %S>, clock recalc <on|off>")
+                        .show()
+                        .unwrap();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Updates a cell and records how long the recalculation took.
+    ///
+    /// # Arguments
+    /// * `out` - The parsed command, as returned by `utils::input::input`
+    ///
+    /// # Returns
+    /// The result of `cell_update`.
+    fn update_cell(&mut self, out: &Assignment) -> Result<(), SheetError> {
+        let before =
+            utils::watch::snapshot(&self.watches, self.engine.database(), self.engine.err());
+        let start = std::time::Instant::now();
+        let suc = self.engine.update(out);
+        self.last_recalc = Some(start.elapsed());
+        if suc.is_ok() {
+            let ind = crate::cell_to_ind(&out.target, self.engine.len_h);
+            self.assigned(ind);
+            self.check_watches(&[ind], &before);
+        }
+        // An emptied cell gives its row no data, so nothing is filled
+        if suc.is_ok() && !out.empties() {
+            let ind = crate::cell_to_ind(&out.target, self.engine.len_h);
+            // A row that gets data gets the formulas of its columns. Cells filled this way are
+            // in columns with a formula, so they fill nothing further.
+            let mut filled = utils::colformula::fill_row(
+                &self.col_formulas,
+                self.engine.opers(),
+                ind,
+                self.engine.len_h,
+                self.engine.len_v,
+            );
+            // A row assigned just below a table joins it, and ranges over its columns grow
+            filled.extend(utils::table::grow(
+                &mut self.tables,
+                self.engine.opers(),
+                ind,
+                self.engine.len_h,
+            ));
+            for command in filled {
+                if let Ok(out) = self.engine.parse_assignment(&command) {
+                    let _ = self.update_cell(&out);
+                }
+            }
+        }
+        suc
+    }
+
+    /// Forgets what a cell showed before it was assigned.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the cell
+    fn assigned(&mut self, ind: i32) {
+        self.provenance.remove(&ind);
+        // A formula or number replaces the text label of a header cell
+        if self.engine.ranges().labels.contains_key(&ind) {
+            self.finish_recalc();
+            let mut ranges = self.engine.ranges().clone();
+            ranges.labels.remove(&ind);
+            self.engine.set_ranges(ranges);
+        }
+    }
+
+    /// Pauses editing if an update changed a watched cell.
+    ///
+    /// # Arguments
+    /// * `edits` - The cells assigned by the update
+    /// * `before` - Snapshot of the watched cells taken before the update
+    fn check_watches(&mut self, edits: &[i32], before: &[(i32, bool)]) {
+        let lines = utils::watch::changes(
+            edits,
+            &self.watches,
+            before,
+            self.engine.database(),
+            self.engine.err(),
+            self.engine.sensi(),
+            self.engine.len_h,
+        );
+        if !lines.is_empty() {
+            self.watch_break = Some(lines);
+        }
+    }
+
+    /// Continues editing after a watch break, applying the held back assignments.
+    fn continue_watch(&mut self) {
+        self.watch_break = None;
+        for out in std::mem::take(&mut self.watch_pending) {
+            self.commit(out);
+        }
+    }
+
+    /// Starts a background recalculation of parsed assignments.
+    ///
+    /// # Arguments
+    /// * `outs` - The parsed assignments, applied in order
+    fn start_recalc(&mut self, outs: Vec<Assignment>) {
+        self.recalc_edits = outs
+            .iter()
+            .map(|out| crate::cell_to_ind(&out.target, self.engine.len_h))
+            .collect();
+        let repaint = self.repaint.clone();
+        self.recalc = Some(utils::sheet::Recalc::start_with_wake(
+            self.engine.clone(),
+            outs,
+            move || {
+                if let Some(ctx) = repaint {
+                    ctx.request_repaint();
+                }
+            },
+        ));
+    }
+
+    /// Applies an assignment typed by the user.
+    ///
+    /// Assignments that recompute many cells (see `Sheet::is_slow`) are
+    /// recalculated on a background thread, unless turned off in the Settings window, so the
+    /// grid stays responsive and keeps showing the last consistent values in the meantime.
+    /// While a background recalculation runs, further assignments are queued behind it.
+    ///
+    /// # Arguments
+    /// * `out` - The parsed assignment
+    fn commit(&mut self, out: Assignment) {
+        if self.watch_break.is_some() {
+            self.watch_pending.push(out);
+            return;
+        }
+        if self.recalc.is_some() {
+            self.recalc_queue.push(out);
+            return;
+        }
+        if self.config.background_recalc && self.engine.is_slow(&out) {
+            self.start_recalc(vec![out]);
+            return;
+        }
+        let suc = self.update_cell(&out);
+        self.report_update(suc);
+    }
+
+    /// Reports the result of an assignment typed by the user.
+    ///
+    /// # Arguments
+    /// * `suc` - The result of `cell_update`
+    fn report_update(&mut self, suc: Result<(), SheetError>) {
+        if suc == Err(SheetError::Cancelled) {
+            // Cancelled by the user, who needs no notification
+        } else if suc == Err(SheetError::Cycle) {
+            Notification::new()
+                .summary("Cycle Detected")
+                .body("Cycle detected in the graph. Please check your formulas. The change has been reverted")
+                .show()
+                .unwrap();
+        } else if let Err(e) = suc {
+            Notification::new()
+                .summary("Resource Limit Exceeded")
+                .body(
+                    format!(
+                        "The update was aborted ({}). The change has been reverted",
+                        e
+                    )
+                    .as_str(),
+                )
+                .show()
+                .unwrap();
+        } else {
+            self.dirty = true;
+        }
+    }
+
+    /// Gives the number of columns and rows of the spreadsheet.
+    pub fn size(&self) -> (i32, i32) {
+        (self.engine.len_h, self.engine.len_v)
+    }
+
+    /// Takes over the result of a finished background recalculation.
+    ///
+    /// Queued assignments are started as the next background recalculation.
+    ///
+    /// # Arguments
+    /// * `recalc` - The recalculation, which is waited for if it is still running
+    fn install(&mut self, recalc: utils::sheet::Recalc) {
+        let (sheet, statuses, took) = recalc.finish();
+        let before =
+            utils::watch::snapshot(&self.watches, self.engine.database(), self.engine.err());
+        self.engine = sheet;
+        self.last_recalc = Some(took);
+        let edits = std::mem::take(&mut self.recalc_edits);
+        for (&ind, suc) in edits.iter().zip(statuses) {
+            if suc.is_ok() {
+                self.assigned(ind);
+            }
+            self.report_update(suc);
+        }
+        self.check_watches(&edits, &before);
+        let queue = std::mem::take(&mut self.recalc_queue);
+        if self.watch_break.is_some() {
+            self.watch_pending.extend(queue);
+        } else if !queue.is_empty() {
+            self.start_recalc(queue);
+        }
+    }
+
+    /// Cancels the background recalculation, leaving the spreadsheet as it was before the
+    /// assignments it was applying.
+    ///
+    /// Queued assignments are dropped with it, as they were typed after the cancelled ones.
+    fn cancel_recalc(&mut self) {
+        if let Some(recalc) = &self.recalc {
+            recalc.cancel();
+            self.recalc_queue.clear();
+        }
+    }
+
+    /// Waits for background recalculations, including queued ones, to finish.
+    ///
+    /// Called before anything that changes or saves the engine state directly.
+    fn finish_recalc(&mut self) {
+        while let Some(recalc) = self.recalc.take() {
+            self.install(recalc);
+        }
+    }
+
+    /// Sets safe mode and the resource limits of the workbook.
+    ///
+    /// # Arguments
+    /// * `commands` - `safe_mode` and `limit` commands, see `Sheet::apply_setting`
+    pub fn apply_limits(&mut self, commands: &[String]) {
+        self.finish_recalc();
+        for command in commands {
+            let _ = self.engine.apply_setting(command);
+        }
+    }
+
+    /// Gives the per-cell data that the markers drawn in the corner of cells are read from.
+    fn decoration_layers(&self) -> utils::decoration::Layers<'_> {
+        utils::decoration::Layers {
+            col_types: &self.col_types,
+            provenance: &self.provenance,
+            watches: &self.watches,
+            len_h: self.engine.len_h,
+        }
+    }
+
+    /// Checks whether a cell shows a value that is being recalculated or waits on SLEEP.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the cell
+    fn is_pending(&self, ind: i32) -> bool {
+        self.recalc.as_ref().is_some_and(|r| r.is_pending(ind))
+            || self
+                .engine
+                .schedule()
+                .is_pending(ind, std::time::Instant::now())
+            || self
+                .recalc_queue
+                .iter()
+                .any(|out| crate::cell_to_ind(&out.target, self.engine.len_h) == ind)
+    }
+
+    /// Saves results of the Describe dialog to a CSV file chosen by the user.
+    ///
+    /// # Arguments
+    /// * `analyses` - Pairs of a range and the statistics computed over it
+    fn export_stats(&self, analyses: &[(String, [f64; 8])]) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("CSV", &["csv"])
+            .set_file_name("statistics.csv")
+            .save_file()
+        else {
+            return;
+        };
+        let saved = ui::loadnsave::save_stats_as_csv(
+            analyses,
+            self.config.delimiter(),
+            &path.display().to_string(),
+        );
+        let body = match saved {
+            Ok(()) if !self.config.notify_saves => return,
+            Ok(()) => format!("Statistics saved to {}", path.display()),
+            Err(e) => format!("Could not save the statistics: {}", e),
+        };
+        Notification::new()
+            .summary("Export Statistics")
+            .body(&body)
+            .show()
+            .unwrap();
+    }
+
+    /// Writes the shown statistics into the column of cells below `describe_target`.
+    ///
+    /// Cells hold integers, so the statistics are rounded.
+    fn write_stats(&mut self) {
+        let target = self.describe_target.trim().to_string();
+        let fits = utils::input::is_valid_cell(&target, self.engine.len_h, self.engine.len_v)
+            && crate::cell_to_int(&target) % 1000 + 7 <= self.engine.len_v;
+        if !fits {
+            Notification::new()
+                .summary("Invalid Cell")
+                .body("The statistics need 8 cells going down from a valid cell.")
+                .show()
+                .unwrap();
+            return;
+        }
+        let key = crate::cell_to_int(&target);
+        let commands: Vec<String> = self
+            .describe_data
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                format!(
+                    "{}{}={}",
+                    utils::display::get_label(key / 1000),
+                    key % 1000 + i as i32,
+                    v.round() as i32
+                )
+            })
+            .collect();
+        let applied = self.apply_commands(&commands);
+        Notification::new()
+            .summary("Write Statistics")
+            .body(&format!(
+                "Wrote {} of 8 statistics ({}) starting at {}. Values are rounded to integers.",
+                applied,
+                utils::ui::stats::STAT_NAMES.join(", "),
+                target
+            ))
+            .show()
+            .unwrap();
+    }
+
+    /// Runs the test selected in the Stats Tests dialog on its two ranges.
+    ///
+    /// Cells in error are left out of the ranges.
+    fn run_test(&mut self) {
+        let ranges = (
+            self.parse_range(&self.tests_ranges.0),
+            self.parse_range(&self.tests_ranges.1),
+        );
+        let (Some(first), Some(second)) = ranges else {
+            Notification::new()
+                .summary("Invalid Range")
+                .body("The range is invalid. Please check your input.")
+                .show()
+                .unwrap();
+            return;
+        };
+        let values = |range| {
+            utils::analysis::values(
+                self.engine.database(),
+                self.engine.err(),
+                self.engine.opers(),
+                self.engine.len_h,
+                range,
+            )
+        };
+        self.tests_result = self.tests_kind.run(&values(first), &values(second));
+        if self.tests_result.is_none() {
+            let body = match self.tests_kind {
+                utils::ui::stats::StatTest::TTest => {
+                    "Each sample needs at least two values and the samples cannot both be constant."
+                }
+                utils::ui::stats::StatTest::ChiSquare => {
+                    "The ranges need the same number of values, at least two, and every expected count must be positive."
+                }
+            };
+            Notification::new()
+                .summary("Test Not Run")
+                .body(body)
+                .show()
+                .unwrap();
+        }
+    }
+
+    /// Copies a range to the clipboard.
+    ///
+    /// The clipboard is shared by all open workbooks, so the range can be pasted into
+    /// another tab. Values, formulas, styles and number formats are copied.
+    ///
+    /// # Arguments
+    /// * `range` - The range to copy, e.g. "A1:C4"
+    ///
+    /// # Returns
+    /// `false` if the range is invalid.
+    fn copy_range(&mut self, range: &str) -> bool {
+        let Some(range) = self.parse_range(range) else {
+            return false;
+        };
+        let clip = utils::clipboard::copy(
+            self.engine.database(),
+            self.engine.err(),
+            self.engine.opers(),
+            &self.styles,
+            &self.number_formats,
+            self.engine.len_h,
+            range,
+        );
+        self.clipbaord = serde_json::to_string(&clip).expect("Failed to serialize clipboard");
+        true
+    }
+
+    /// Pastes the clipboard with its top-left corner at a cell.
+    ///
+    /// # Arguments
+    /// * `cell` - The top-left cell of the pasted block, e.g. "B2"
+    /// * `mode` - What to write into the target cells
+    /// * `transpose` - Whether rows of the copied range become columns
+    ///
+    /// # Returns
+    /// The number of cells that were updated, or `None` if the clipboard is empty.
+    fn paste_at(
+        &mut self,
+        cell: &str,
+        mode: utils::clipboard::PasteMode,
+        transpose: bool,
+    ) -> Option<usize> {
+        let clip: utils::clipboard::Clip = serde_json::from_str(&self.clipbaord).ok()?;
+        let k = crate::cell_to_int(cell);
+        if mode == utils::clipboard::PasteMode::Formats {
+            let changed = utils::clipboard::paste_formats(
+                &clip,
+                (k / 1000, k % 1000),
+                transpose,
+                &mut self.styles,
+                &mut self.number_formats,
+                self.engine.len_h,
+                self.engine.len_v,
+            );
+            if changed > 0 {
+                self.dirty = true;
+            }
+            return Some(changed);
+        }
+        let commands = utils::clipboard::paste(
+            &clip,
+            (k / 1000, k % 1000),
+            mode,
+            transpose,
+            self.engine.database(),
+            self.engine.err(),
+            self.engine.len_h,
+            self.engine.len_v,
+        );
+        Some(self.apply_commands(&commands))
+    }
+
+    /// Parses a range such as "A1:C4".
+    ///
+    /// # Arguments
+    /// * `range` - The range text
+    ///
+    /// # Returns
+    /// First column, first row, last column and last row, or `None` if the range is invalid.
+    fn parse_range(&self, range: &str) -> Option<(i32, i32, i32, i32)> {
+        utils::input::parse_range(range, self.engine.len_h, self.engine.len_v)
+    }
+
+    /// Applies assignment commands produced by paste or auto-fill.
+    ///
+    /// # Arguments
+    /// * `commands` - Commands such as "B2=5"
+    ///
+    /// # Returns
+    /// The number of commands that updated a cell.
+    fn apply_commands(&mut self, commands: &[String]) -> usize {
+        let outs = commands
+            .iter()
+            .filter_map(|command| self.engine.parse_assignment(command).ok())
+            .collect();
+        self.apply_assignments(outs)
+    }
+
+    /// Applies parsed assignments, such as the ones emptying the cells of a block.
+    ///
+    /// # Arguments
+    /// * `outs` - The assignments, applied in order
+    ///
+    /// # Returns
+    /// The number of assignments that updated a cell.
+    fn apply_assignments(&mut self, outs: Vec<Assignment>) -> usize {
+        self.finish_recalc();
+        let mut applied = 0;
+        for out in outs {
+            if self.watch_break.is_some() {
+                self.watch_pending.push(out);
+            } else if self.update_cell(&out).is_ok() {
+                applied += 1;
+            }
+        }
+        if applied > 0 {
+            self.dirty = true;
+        }
+        applied
+    }
+
+    /// Searches the cell values and formulas for the find query again, or the values if
+    /// the query is a comparison.
+    ///
+    /// # Returns
+    /// `false` if the query is a comparison that is not valid.
+    fn find_all(&mut self) -> bool {
+        self.finish_recalc();
+        if self.find_where {
+            let found = utils::find::matches_where(
+                &self.find_query,
+                self.engine.database(),
+                self.engine.err(),
+                self.engine.opers(),
+            );
+            self.find_matches = found.clone().unwrap_or_default();
+            return found.is_some();
+        }
+        self.find_matches = utils::find::matches(
+            &self.find_query,
+            self.engine.opers(),
+            self.engine.len_h,
+            |c| {
+                utils::date::value_text(
+                    self.engine.database(),
+                    self.engine.opers(),
+                    c,
+                    self.engine.len_h,
+                )
+            },
+        );
+        true
+    }
+
+    /// Jumps to the next or previous cell matching the find query, after or before the
+    /// selected cell, wrapping around at the end of the sheet.
+    ///
+    /// # Arguments
+    /// * `forward` - Whether to jump to the next match rather than the previous one
+    fn find_step(&mut self, forward: bool) {
+        if !self.find_all() {
+            Notification::new()
+                .summary("Invalid Predicate")
+                .body("Use a comparison such as >1000 or <>0, or ERR for cells in an error state")
+                .show()
+                .unwrap();
+            return;
+        }
+        let here = self.selected_cell.unwrap_or(0);
+        let next = if forward {
+            let after = self.find_matches.iter().find(|&&c| c > here);
+            after.or(self.find_matches.first())
+        } else {
+            let before = self.find_matches.iter().rev().find(|&&c| c < here);
+            before.or(self.find_matches.last())
+        };
+        match next.copied() {
+            Some(ind) => self.jump_to_cell(ind),
+            None => {
+                let body = if self.find_where {
+                    format!("No cell matches {}", self.find_query.trim())
+                } else {
+                    format!("No cell contains \"{}\"", self.find_query.trim())
+                };
+                Notification::new()
+                    .summary("Not Found")
+                    .body(body.as_str())
+                    .show()
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Replaces the find query in the formulas of all matching cells and recalculates.
+    fn replace_all(&mut self) {
+        let commands = utils::find::replace(
+            &self.find_query,
+            &self.find_replacement,
+            self.engine.opers(),
+            self.engine.len_h,
+        );
+        let applied = self.apply_commands(&commands);
+        let body = if applied < commands.len() {
+            format!(
+                "Replaced in {} of {} cells; the other formulas would be invalid or circular",
+                applied,
+                commands.len()
+            )
+        } else {
+            format!("Replaced in {} cells", applied)
+        };
+        Notification::new()
+            .summary("Replace All")
+            .body(body.as_str())
+            .show()
+            .unwrap();
+        self.find_all();
+    }
+
+    /// Gives the formulas changed by the rewrite set up in the Replace in Formulas dialog.
+    ///
+    /// # Returns
+    /// The changes, or `None` if the columns or functions are invalid.
+    fn rewrite_preview(&mut self) -> Option<Vec<utils::rewrite::Change>> {
+        self.finish_recalc();
+        let kind = if self.rewrite_function { "fn" } else { "col" };
+        let command = format!(
+            "rewrite {} {} {}",
+            kind,
+            self.rewrite_from.trim(),
+            self.rewrite_to.trim()
+        );
+        let rewrite = utils::rewrite::Rewrite::parse(&command, self.engine.len_h)?;
+        Some(utils::rewrite::preview(
+            &rewrite,
+            self.engine.opers(),
+            self.engine.len_h,
+        ))
+    }
+
+    /// Applies the previewed rewrite as one batch, so either every formula changes or none
+    /// does.
+    ///
+    /// If the formulas changed since the preview, the new preview is shown instead and
+    /// nothing is applied.
+    fn apply_rewrite(&mut self) {
+        let changes = self.rewrite_preview().unwrap_or_default();
+        if changes != self.rewrite_changes {
+            self.rewrite_changes = changes;
+            Notification::new()
+                .summary("Replace in Formulas")
+                .body("The formulas changed since the preview. Check the new preview and apply again.")
+                .show()
+                .unwrap();
+            return;
+        }
+        let before =
+            utils::watch::snapshot(&self.watches, self.engine.database(), self.engine.err());
+        match utils::rewrite::apply(&self.engine, &changes) {
+            Ok(sheet) => {
+                self.engine = sheet;
+                self.dirty = true;
+                let cells: Vec<i32> = changes.iter().map(|c| c.cell).collect();
+                for cell in &cells {
+                    self.provenance.remove(cell);
+                }
+                self.check_watches(&cells, &before);
+                self.rewrite_changes.clear();
+                Notification::new()
+                    .summary("Replace in Formulas")
+                    .body(format!("Rewrote {} formulas", cells.len()).as_str())
+                    .show()
+                    .unwrap();
+            }
+            Err(message) => {
+                Notification::new()
+                    .summary("Rewrite Refused")
+                    .body(format!("{}. No formula was changed.", message).as_str())
+                    .show()
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Auto-fills from a range up to a cell and opens the fill options.
+    ///
+    /// # Arguments
+    /// * `source` - First column, first row, last column and last row of the source range
+    /// * `end` - Column and row of the last cell to fill
+    /// * `mode` - How to continue the source values
+    fn apply_fill(
+        &mut self,
+        source: (i32, i32, i32, i32),
+        end: (i32, i32),
+        mode: utils::fill::FillMode,
+    ) {
+        let commands = utils::fill::fill(
+            self.engine.database(),
+            self.engine.err(),
+            self.engine.opers(),
+            self.engine.len_h,
+            self.engine.len_v,
+            source,
+            end,
+            mode,
+        );
+        self.apply_commands(&commands);
+        self.fill_last = Some((source, end));
+        self.fill_mode = mode;
+    }
+
+    /// Auto-fills after a drag from one cell to another.
+    ///
+    /// The source is the cell the drag started on, together with the cell before it in the
+    /// direction of the drag if that cell is not empty, so dragging from the 2 in 1, 2
+    /// continues the series with 3, 4, ... A formula is filled on its own, with its
+    /// references moved along.
+    ///
+    /// # Arguments
+    /// * `start` - Index of the cell the drag started on
+    /// * `end` - Index of the cell the drag ended on
+    fn fill_from_drag(&mut self, start: i32, end: i32) {
+        let pos = |ind: i32| {
+            let mut col = ind % self.engine.len_h;
+            if col == 0 {
+                col = self.engine.len_h;
+            }
+            (col, (ind - col) / self.engine.len_h + 1)
+        };
+        let ((sc, sr), (ec, er)) = (pos(start), pos(end));
+        let (dc, dr) = ((ec - sc).signum(), (er - sr).signum());
+        if dc != 0 && dr != 0 {
+            return;
+        }
+        let (pc, pr) = (sc - dc, sr - dr);
+        let mut source = (sc, sr, sc, sr);
+        let formula = !matches!(
+            self.engine.opers()[start as usize].opcpde.as_str(),
+            "" | "EQV"
+        );
+        if !formula
+            && (1..=self.engine.len_h).contains(&pc)
+            && (1..=self.engine.len_v).contains(&pr)
+        {
+            let prev = ((pr - 1) * self.engine.len_h + pc) as usize;
+            if !self.engine.opers()[prev].opcpde.is_empty() && !self.engine.err()[prev] {
+                source = (sc.min(pc), sr.min(pr), sc.max(pc), sr.max(pr));
+            }
+        }
+        self.apply_fill(source, (ec, er), utils::fill::FillMode::Series);
+    }
+
+    /// Validates the formula being edited.
+    ///
+    /// # Returns
+    /// `None` if no cell is edited or its formula is valid, otherwise the error message and the
+    /// byte range of the formula to mark.
+    fn edit_error(&self) -> Option<(String, std::ops::Range<usize>)> {
+        let ind = self.selected_cell?;
+        utils::input::formula_error(
+            &crate::ind_to_cell(ind, self.engine.len_h),
+            &self.temp_txt.0,
+            self.engine.len_h,
+            self.engine.len_v,
+        )
+    }
+
+    /// Points the formula being edited at a range of cells.
+    ///
+    /// The reference replaces whatever was pointed at before, so moving or extending the
+    /// range only ever changes the end of the formula.
+    ///
+    /// # Arguments
+    /// * `anchor` - Index of the cell where the range starts
+    /// * `cursor` - Index of the cell where the range ends
+    fn point_at(&mut self, anchor: i32, cursor: i32) {
+        let prefix = match self.pointing.take() {
+            Some((prefix, _, _)) => prefix,
+            None => self.temp_txt.0.clone(),
+        };
+        self.temp_txt.0 = format!("{}{}", prefix, self.pointed_text(anchor, cursor));
+        self.pointing = Some((prefix, anchor, cursor));
+    }
+
+    /// Writes a pointed reference in the reference style of the view.
+    ///
+    /// # Arguments
+    /// * `anchor` - Index of the cell where the range starts
+    /// * `cursor` - Index of the cell where the range ends
+    fn pointed_text(&self, anchor: i32, cursor: i32) -> String {
+        utils::cellref::show_formula(
+            &utils::input::reference_text(anchor, cursor, self.engine.len_h),
+            self.config.r1c1,
+        )
+    }
+
+    /// Checks whether the formula being edited can take a pointed reference.
+    ///
+    /// # Returns
+    /// `true` if a reference is being pointed at, or the text ends where a reference may follow.
+    fn can_point(&mut self) -> bool {
+        // Typing after a pointed reference ends pointing
+        if let Some((prefix, anchor, cursor)) = &self.pointing
+            && self.temp_txt.0 != format!("{}{}", prefix, self.pointed_text(*anchor, *cursor))
+        {
+            self.pointing = None;
+        }
+        self.pointing.is_some() || utils::input::accepts_reference(&self.temp_txt.0)
+    }
+
+    /// Moves the pointed reference with the arrow keys while a formula is edited.
+    ///
+    /// An arrow key moves the reference to the next cell, and Shift with an arrow key extends
+    /// it into a range. The first press starts next to the edited cell.
+    ///
+    /// # Arguments
+    /// * `ui` - The ui whose key presses are used
+    /// * `ind` - Index of the cell being edited
+    ///
+    /// # Returns
+    /// `true` if the formula text was changed.
+    fn point_with_keys(&mut self, ui: &egui::Ui, ind: i32) -> bool {
+        if !self.can_point() {
+            return false;
+        }
+        let keys = [
+            (egui::Key::ArrowLeft, -1, 0),
+            (egui::Key::ArrowRight, 1, 0),
+            (egui::Key::ArrowUp, 0, -1),
+            (egui::Key::ArrowDown, 0, 1),
+        ];
+        let mut moved = false;
+        for (key, dc, dr) in keys {
+            // Shift is checked first, since a plain key press also matches Shift
+            let extend = ui.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, key));
+            if !extend && !ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, key)) {
+                continue;
+            }
+            let (anchor, cursor) = match self.pointing {
+                Some((_, anchor, cursor)) => (anchor, cursor),
+                None => (ind, ind),
+            };
+            let mut col = (cursor - 1) % self.engine.len_h + 1;
+            let mut row = (cursor - 1) / self.engine.len_h + 1;
+            col = (col + dc).clamp(1, self.engine.len_h);
+            row = (row + dr).clamp(1, self.engine.len_v);
+            let cursor = col + (row - 1) * self.engine.len_h;
+            let anchor = if extend && self.pointing.is_some() {
+                anchor
+            } else {
+                cursor
+            };
+            self.point_at(anchor, cursor);
+            moved = true;
+        }
+        moved
+    }
+
+    /// Checks whether a cell is part of the reference being pointed at.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the cell
+    fn is_pointed(&self, ind: i32) -> bool {
+        match &self.pointing {
+            Some((_, anchor, cursor)) => self.block_contains(*anchor, *cursor, ind),
+            None => false,
+        }
+    }
+
+    /// Checks whether a cell is part of the selected block.
+    ///
+    /// A block of a single cell is not shown as selected.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the cell
+    fn is_blocked(&self, ind: i32) -> bool {
+        match self.block {
+            Some((anchor, cursor)) if anchor != cursor => self.block_contains(anchor, cursor, ind),
+            _ => false,
+        }
+    }
+
+    /// Checks whether a cell lies in the block between two corner cells.
+    ///
+    /// # Arguments
+    /// * `anchor` - Index of one corner of the block
+    /// * `cursor` - Index of the opposite corner
+    /// * `ind` - Index of the cell
+    fn block_contains(&self, anchor: i32, cursor: i32, ind: i32) -> bool {
+        let pos = |c: i32| ((c - 1) % self.engine.len_h, (c - 1) / self.engine.len_h);
+        let ((c1, r1), (c2, r2), (c, r)) = (pos(anchor), pos(cursor), pos(ind));
+        (c1.min(c2)..=c1.max(c2)).contains(&c) && (r1.min(r2)..=r1.max(r2)).contains(&r)
+    }
+
+    /// Pastes the clipboard and reports the result in a notification.
+    ///
+    /// # Arguments
+    /// * `cell` - The top-left cell of the pasted block
+    /// * `mode` - What to write into the target cells
+    /// * `transpose` - Whether rows of the copied range become columns
+    fn paste_and_notify(&mut self, cell: &str, mode: utils::clipboard::PasteMode, transpose: bool) {
+        let body = if !utils::input::is_valid_cell(cell, self.engine.len_h, self.engine.len_v) {
+            String::from("The target cell is invalid. Please check your input.")
+        } else {
+            match self.paste_at(cell, mode, transpose) {
+                Some(applied) => format!("Pasted {} cells.", applied),
+                None => String::from("Nothing to paste. Use copy <cell>:<cell> first."),
+            }
+        };
+        Notification::new()
+            .summary("Paste")
+            .body(&body)
+            .show()
+            .unwrap();
+    }
+
+    /// Gets the width of a column in the grid.
+    ///
+    /// # Arguments
+    /// * `col` - The column number
+    fn col_width(&self, col: i32) -> f32 {
+        self.col_widths
+            .get(&col)
+            .copied()
+            .unwrap_or(DEFAULT_COL_WIDTH)
+    }
+
+    /// Sets the width of a column to fit the widest value or formula in the visible rows.
+    ///
+    /// # Arguments
+    /// * `ctx` - The egui context used to measure text
+    /// * `col` - The column number
+    fn fit_column(&mut self, ctx: &egui::Context, col: i32) {
+        let font = FontId::proportional(20.0);
+        let mut widest: f32 = 0.0;
+        for row in visible_lines(
+            self.top_v,
+            self.engine.len_v,
+            &self.hidden_rows,
+            self.view.0,
+        ) {
+            let ind = ((row - 1) * self.engine.len_h + col) as usize;
+            let value = if self.engine.err()[ind] {
+                String::from("ERR")
+            } else {
+                self.value_text(ind as i32)
+            };
+            let formula = self.shown_formula(ind as i32);
+            for text in [value, formula] {
+                let width = ctx.fonts(|f| {
+                    f.layout_no_wrap(text, font.clone(), Color32::WHITE)
+                        .size()
+                        .x
+                });
+                widest = widest.max(width);
+            }
+        }
+        // Leave room for the frame and the text edit margins
+        self.set_col_width(col, (widest + 24.0).clamp(60.0, 400.0));
+    }
+
+    /// Sets the width of a column, forgetting it if it is the default width.
+    ///
+    /// # Arguments
+    /// * `col` - The column number
+    /// * `width` - The new width
+    fn set_col_width(&mut self, col: i32, width: f32) {
+        if width == DEFAULT_COL_WIDTH {
+            self.col_widths.remove(&col);
+        } else {
+            self.col_widths.insert(col, width);
+        }
+        self.dirty = true;
+    }
+
+    /// Sets or removes the custom label shown above a column.
+    ///
+    /// Labels are only shown in the header and exports; formulas still refer to the
+    /// column by its letters.
+    ///
+    /// # Arguments
+    /// * `col` - The column number
+    /// * `text` - The label, or an empty string to remove it
+    fn set_col_label(&mut self, col: i32, text: &str) {
+        if text.is_empty() {
+            self.col_labels.remove(&col);
+        } else {
+            self.col_labels.insert(col, text.to_string());
+        }
+        self.dirty = true;
+    }
+
+    /// Inserts or deletes a row or column, moving the cells after it.
+    ///
+    /// An insertion that would drop assigned cells off the end of the sheet is refused with a
+    /// notification. A deletion that removes assigned cells or breaks formulas waits until
+    /// the user confirms it.
+    ///
+    /// # Arguments
+    /// * `edit` - The row or column to insert or delete
+    fn edit_lines(&mut self, edit: utils::structure::LineEdit) {
+        self.finish_recalc();
+        if edit.loses_cells(self.engine.opers(), self.engine.len_h) {
+            let (line, last) = if edit.row {
+                ("row", self.engine.len_v.to_string())
+            } else {
+                ("column", utils::display::get_label(self.engine.len_h))
+            };
+            Notification::new()
+                .summary("Cannot Insert")
+                .body(&format!(
+                    "The last {} ({}) is not empty. Delete it first to make room.",
+                    line, last
+                ))
+                .show()
+                .unwrap();
+            return;
+        }
+        let impact = edit.impact(self.engine.opers(), self.engine.len_h);
+        if impact.is_destructive() {
+            self.line_impact = Some((edit, impact));
+            return;
+        }
+        self.apply_line_edit(edit);
+    }
+
+    /// Inserts a row below a cell and copies the formulas of its row into the new row, with
+    /// their references moved down, then focuses the cell below.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the cell to insert below
+    fn insert_row_below(&mut self, ind: i32) {
+        self.finish_recalc();
+        let (col, row) = (
+            (ind - 1) % self.engine.len_h + 1,
+            (ind - 1) / self.engine.len_h + 1,
+        );
+        if row == self.engine.len_v {
+            Notification::new()
+                .summary("Cannot Insert")
+                .body("There is no row below the last row.")
+                .show()
+                .unwrap();
+            return;
+        }
+        let edit = utils::structure::LineEdit {
+            row: true,
+            line: row + 1,
+            insert: true,
+            len: self.engine.len_v,
+        };
+        if edit.loses_cells(self.engine.opers(), self.engine.len_h) {
+            // Shows why the row cannot be inserted
+            self.edit_lines(edit);
+            return;
+        }
+        self.apply_line_edit(edit);
+        let commands = utils::fill::copy_formulas_down(
+            self.engine.opers(),
+            self.engine.len_h,
+            self.engine.len_v,
+            row,
+        );
+        self.apply_commands(&commands);
+        let next = col + row * self.engine.len_h;
+        self.block = Some((next, next));
+        self.scroll_into_view(next);
+        self.focus_todo = Some(next);
+    }
+
+    /// Applies a row or column insertion or deletion.
+    ///
+    /// Formulas that referenced deleted cells show "#REF!". Hidden lines, column widths,
+    /// labels, header text and watches move along with their cells.
+    ///
+    /// # Arguments
+    /// * `edit` - The row or column to insert or delete
+    fn apply_line_edit(&mut self, edit: utils::structure::LineEdit) {
+        self.finish_recalc();
+        self.engine.edit_lines(edit);
+
+        let len_h = self.engine.len_h;
+        let move_lines = |lines: &mut Vec<i32>| {
+            *lines = lines.iter().filter_map(|&l| edit.shift(l)).collect();
+        };
+        if edit.row {
+            move_lines(&mut self.hidden_rows);
+        } else {
+            move_lines(&mut self.hidden_cols);
+            self.col_widths = self
+                .col_widths
+                .iter()
+                .filter_map(|(&col, &width)| Some((edit.shift(col)?, width)))
+                .collect();
+            self.col_labels = std::mem::take(&mut self.col_labels)
+                .into_iter()
+                .filter_map(|(col, text)| Some((edit.shift(col)?, text)))
+                .collect();
+            self.col_types = std::mem::take(&mut self.col_types)
+                .into_iter()
+                .filter_map(|(col, col_type)| Some((edit.shift(col)?, col_type)))
+                .collect();
+        }
+        utils::rules::edit_lines(&mut self.display_rules, edit, len_h);
+        utils::style::edit_lines(&mut self.styles, edit, len_h);
+        utils::colformula::edit_lines(&mut self.col_formulas, edit);
+        utils::table::edit_lines(&mut self.tables, edit, len_h);
+        utils::numfmt::edit_lines(&mut self.number_formats, edit, len_h);
+        utils::provenance::edit_lines(&mut self.provenance, edit, len_h);
+        self.watches = self
+            .watches
+            .iter()
+            .filter_map(|&w| edit.move_cell(w, len_h))
+            .collect();
+        self.selected_cell = self
+            .selected_cell
+            .and_then(|ind| edit.move_cell(ind, len_h));
+        self.block = None;
+        self.dirty = true;
+    }
+
+    /// Evaluates every cell again if the header labels or the skip_errors setting change what
+    /// range functions read (see `RangeOptions`).
+    pub fn recalculate_ranges(&mut self) {
+        if *self.engine.ranges() != Default::default() {
+            self.finish_recalc();
+            self.engine.recalculate();
+        }
+    }
+
+    /// Recalculates the sheet so that cells using TODAY or NOW show the current date and
+    /// time. The sheet is not marked as changed, and nothing is done if no cell uses them.
+    fn refresh_volatile(&mut self) {
+        if !self.engine.opers().iter().any(utils::refresh::is_volatile) {
+            return;
+        }
+        self.finish_recalc();
+        self.engine.recalculate();
+    }
+
+    /// Gets the text label shown in a cell of the header row.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the cell
+    ///
+    /// # Returns
+    /// The label, or `None` if the cell shows its value.
+    fn header_label(&self, ind: i32) -> Option<&String> {
+        self.engine.ranges().label(ind)
+    }
+
+    /// Moves the viewport and selection to a saved view.
+    ///
+    /// # Arguments
+    /// * `view` - The view to show
+    fn show_view(&mut self, view: &View) {
+        self.top_h = view.top_h.clamp(1, self.engine.len_h);
+        self.top_v = view.top_v.clamp(1, self.engine.len_v);
+        self.selected_cell = view.selected_cell;
+        if self.selected_cell.is_some() {
+            self.temp_txt.1 = true;
+        }
+    }
+
+    /// Hides or shows a row or column.
+    ///
+    /// # Arguments
+    /// * `row` - `true` for a row, `false` for a column
+    /// * `line` - The row or column number
+    /// * `hide` - `true` to hide the line, `false` to show it again
+    fn set_hidden(&mut self, row: bool, line: i32, hide: bool) {
+        let hidden = if row {
+            &mut self.hidden_rows
+        } else {
+            &mut self.hidden_cols
+        };
+        hidden.retain(|&l| l != line);
+        if hide {
+            hidden.push(line);
+            hidden.sort();
+        }
+        self.dirty = true;
+    }
+
+    /// Draws the plot set up in the Plot dialog.
+    ///
+    /// # Arguments
+    /// * `path` - Path where the plot image is saved
+    ///
+    /// # Returns
+    /// `Ok(())` if the plot was saved, or a message for the user otherwise.
+    fn render_plot(&self, path: &str) -> Result<(), String> {
+        let column = |text: &str, name: &str, optional: bool| {
+            let text = text.trim().to_uppercase();
+            if optional && text.is_empty() {
+                return Ok(None);
+            }
+            utils::input::parse_col(&text, self.engine.len_h)
+                .map(|col| Some((col, text)))
+                .ok_or(format!(
+                    "The {} column is not a column of the spreadsheet.",
+                    name
+                ))
+        };
+        let x = column(&self.plot_x_axis, "X-axis", false)?.unwrap();
+        let y = column(&self.plot_y_axis, "Y-axis", false)?.unwrap();
+        let err = column(&self.plot_err_axis, "error", true)?;
+        let y2 = column(&self.plot_y2_axis, "Y2-axis", true)?;
+        let rows = self
+            .plot_rows
+            .split_once(':')
+            .and_then(|(start, end)| Some((start.trim().parse().ok()?, end.trim().parse().ok()?)))
+            .filter(|&(start, end): &(i32, i32)| {
+                1 <= start && start <= end && end <= self.engine.len_v
+            })
+            .ok_or("The rows are not a range of the spreadsheet, such as 1:10.")?;
+
+        let value = |col: i32, row: i32| {
+            self.engine.database()[(col + (row - 1) * self.engine.len_h) as usize] as f64
+        };
+        let mut data: Vec<(f64, f64)> = vec![];
+        let mut errors: Vec<f64> = vec![];
+        let mut secondary: Vec<(f64, f64)> = vec![];
+        for row in rows.0..=rows.1 {
+            data.push((value(x.0, row), value(y.0, row)));
+            if let Some((col, _)) = &err {
+                errors.push(value(*col, row));
+            }
+            if let Some((col, _)) = &y2 {
+                secondary.push((value(x.0, row), value(*col, row)));
+            }
+        }
+
+        let mut style = self.plot_style.clone();
+        if style.x_label.trim().is_empty() {
+            style.x_label = format!("Column {}", x.1);
+        }
+        if style.y_label.trim().is_empty() {
+            style.y_label = format!("Column {}", y.1);
+        }
+        if let Some((_, label)) = &y2
+            && style.y2_label.trim().is_empty()
+        {
+            style.y2_label = format!("Column {}", label);
+        }
+        let plot = if self.plot_type == Plot::Scatter {
+            utils::ui::plot::scatter_plot
+        } else {
+            utils::ui::plot::line_plot
+        };
+        plot(&data, &errors, &secondary, path, &style).map_err(|e| e.to_string())
+    }
+
+    /// Saves a report of the grid, the plot set up in the Plot dialog and the analyses of the
+    /// Describe dialog to `pdf_path`.
+    ///
+    /// The plot is left out if it is not set up or cannot be drawn.
+    fn save_report(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut charts = Vec::new();
+        let chart = std::env::temp_dir().join("report_chart.png");
+        let chart = chart.display().to_string();
+        if self.render_plot(&chart).is_ok() {
+            let caption = if self.plot_style.title.trim().is_empty() {
+                format!(
+                    "Column {} against column {}, rows {}",
+                    self.plot_y_axis.trim().to_uppercase(),
+                    self.plot_x_axis.trim().to_uppercase(),
+                    self.plot_rows.trim()
+                )
+            } else {
+                self.plot_style.title.trim().to_string()
+            };
+            charts.push((caption, chart));
+        }
+        let title = if self.pdf_title.trim().is_empty() {
+            if self.title.is_empty() {
+                "Spreadsheet Report"
+            } else {
+                &self.title
+            }
+        } else {
+            self.pdf_title.trim()
+        };
+        utils::ui::loadnsave::save_report(
+            self.engine.database(),
+            self.engine.err(),
+            self.engine.opers(),
+            self.engine.len_h,
+            self.engine.len_v,
+            self.export_hidden(),
+            &self.col_labels,
+            &self.number_formats,
+            &self.styles,
+            &|col| self.col_width(col),
+            &charts,
+            &self.describe_history,
+            title,
+            &self.pdf_path,
+        )
+    }
+
+    /// Gives the texts of the header clock.
+    ///
+    /// # Returns
+    /// Whether a recalculation is running or how long the last one took if it is shown, the
+    /// date and the time.
+    fn clock(&self) -> (String, String, String) {
+        let now = chrono::Utc::now();
+        let recalc = match self.last_recalc {
+            _ if self.recalc.is_some() => String::from("Recalculating…"),
+            Some(took) if self.config.show_recalc_time => {
+                format!("Last recalculation: {} ms", took.as_millis())
+            }
+            _ => String::new(),
+        };
+        (
+            recalc,
+            self.config.format_time(now, &self.config.date_format),
+            self.config.format_time(now, &self.config.time_format),
+        )
+    }
+
+    /// Adds an entry to an open menu of the menu bar.
+    ///
+    /// The first entry of a menu opened from the keyboard gets keyboard focus, so the
+    /// entries can be walked through with Tab and chosen with Enter.
+    ///
+    /// # Arguments
+    /// * `ui` - The ui of the menu
+    /// * `text` - Name of the entry
+    /// * `shortcut` - Keyboard shortcut shown next to the name, or an empty string
+    ///
+    /// # Returns
+    /// `true` if the entry was chosen, in which case the menu is closed.
+    fn menu_item(&mut self, ui: &mut egui::Ui, text: &str, shortcut: &str) -> bool {
+        let response = ui.add(Button::new(text).shortcut_text(shortcut));
+        if self.menu_focus {
+            self.menu_focus = false;
+            response.request_focus();
+        }
+        if response.clicked() {
+            ui.close_menu();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Shows the menu bar. Its entries run the same actions as the toolbar, the keyboard
+    /// shortcuts and the terminal commands.
+    ///
+    /// # Arguments
+    /// * `ui` - The ui of the panel holding the menu bar
+    fn menu_bar(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            let bar_id = ui.id();
+            for (i, (name, _)) in MENUS.iter().enumerate() {
+                let response = ui
+                    .menu_button(*name, |ui| match *name {
+                        "File" => self.file_menu(ui),
+                        "Edit" => self.edit_menu(ui),
+                        "View" => self.view_menu(ui),
+                        "Insert" => self.insert_menu(ui),
+                        "Data" => self.data_menu(ui),
+                        _ => self.help_menu(ui),
+                    })
+                    .response;
+                if self.menu_todo == Some(i) {
+                    // Opens the menu as if its button had been clicked
+                    self.menu_todo = None;
+                    self.menu_focus = true;
+                    let mut pos = response.rect.left_bottom();
+                    pos.x -= egui::Frame::menu(ui.style()).total_margin().left;
+                    pos.y += ui.style().spacing.menu_spacing;
+                    let mut bar = egui::menu::BarState::load(ui.ctx(), bar_id);
+                    **bar = Some(egui::menu::MenuRoot::new(pos, response.id));
+                    bar.store(ui.ctx(), bar_id);
+                    ui.ctx().request_repaint();
+                }
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if self.recalc.is_some()
+                    && ui
+                        .button("Cancel")
+                        .on_hover_text("Cancel the recalculation (Escape)")
+                        .clicked()
+                {
+                    self.cancel_recalc();
+                }
+                if !self.config.toolbar {
+                    // The clock is in the toolbar otherwise
+                    let (recalc, date, time) = self.clock();
+                    ui.label(format!("{}  {}  {}", recalc, date, time).trim());
+                }
+                if let Some(summary) = self.block_summary() {
+                    ui.label(summary);
+                }
+                if self.refresh.interval.is_some() && self.engine.limits().safe_mode() {
+                    ui.label("Refresh frozen in safe mode");
+                } else if let Some(label) = self.refresh.label(std::time::Instant::now()) {
+                    let hint = if self.refresh.paused {
+                        "Resume auto refresh"
+                    } else {
+                        "Pause auto refresh"
+                    };
+                    if ui.button(label).on_hover_text(hint).clicked() {
+                        self.refresh.toggle_pause();
+                    }
+                }
+            });
+        });
+    }
+
+    /// Shows the entries of the File menu.
+    fn file_menu(&mut self, ui: &mut egui::Ui) {
+        self.action_item(ui, "Open…", "Open…");
+        self.action_item(ui, "Save…", "Save…");
+        ui.separator();
+        self.action_item(ui, "Export PDF…", "Export PDF…");
+        self.action_item(ui, "Export Report…", "Export Report…");
+        ui.separator();
+        self.action_item(ui, "Settings…", "Settings…");
+    }
+
+    /// Shows the entries of the Edit menu.
+    fn edit_menu(&mut self, ui: &mut egui::Ui) {
+        self.action_item(ui, "Command Palette…", "Command Palette…");
+        self.action_item(ui, "Go to Cell", "Go to Cell");
+        self.action_item(ui, "Command Terminal", "Command Terminal");
+        self.action_item(ui, "Find and Replace…", "Find and Replace…");
+        self.action_item(ui, "Replace in Formulas…", "Replace in Formulas…");
+        ui.separator();
+        self.action_item(ui, "Paste Special…", "Paste Special…");
+    }
+
+    /// Shows the entries of the View menu.
+    fn view_menu(&mut self, ui: &mut egui::Ui) {
+        let toolbar = if self.config.toolbar {
+            "Hide Toolbar"
+        } else {
+            "Show Toolbar"
+        };
+        self.action_item(ui, toolbar, "Toggle Toolbar");
+        let errors = if self.error_panel {
+            "Hide Error Panel"
+        } else {
+            "Show Error Panel"
+        };
+        self.action_item(ui, errors, "Toggle Error Panel");
+        let formulas = if self.show_formulas {
+            "Show Values"
+        } else {
+            "Show Formulas"
+        };
+        self.action_item(ui, formulas, "Toggle Formulas");
+        ui.menu_button("Grid Size", |ui| {
+            let sizes = [
+                None,
+                Some((10, 10)),
+                Some((20, 10)),
+                Some((30, 15)),
+                Some((50, 20)),
+            ];
+            for size in sizes {
+                let text = match size {
+                    Some((rows, cols)) => format!("{} rows x {} columns", rows, cols),
+                    None => String::from("Fit to Window"),
+                };
+                if ui.radio(self.config.view == size, text).clicked() {
+                    self.set_view_size(size);
+                    ui.close_menu();
+                }
+            }
+        });
+        ui.separator();
+        self.action_item(ui, "Go to Top", "Go to Top");
+        self.action_item(ui, "Go to End", "Go to End");
+        if !self.hidden_rows.is_empty() {
+            self.action_item(ui, "Unhide All Rows", "Unhide All Rows");
+        }
+        if !self.hidden_cols.is_empty() {
+            self.action_item(ui, "Unhide All Columns", "Unhide All Columns");
+        }
+    }
+
+    /// Shows the entries of the Insert menu.
+    fn insert_menu(&mut self, ui: &mut egui::Ui) {
+        self.action_item(ui, "Plot…", "Plot…");
+    }
+
+    /// Shows the entries of the Data menu.
+    fn data_menu(&mut self, ui: &mut egui::Ui) {
+        self.action_item(ui, "Describe…", "Describe…");
+        self.action_item(ui, "Stats Tests…", "Stats Tests…");
+        ui.separator();
+        self.action_item(ui, "Dependency Graph Stats", "Dependency Graph Stats");
+        self.action_item(ui, "Verify Recalculation", "Verify Recalculation");
+    }
+
+    /// Shows the entries of the Help menu.
+    fn help_menu(&mut self, ui: &mut egui::Ui) {
+        self.action_item(ui, "Function Reference", "Function Reference");
+        self.action_item(ui, "About", "About");
+    }
+
+    /// Shows a menu entry that runs an action of the command palette, with its shortcut.
+    ///
+    /// # Arguments
+    /// * `ui` - The ui of the menu
+    /// * `text` - Text of the entry
+    /// * `action` - Name of the action, one of `ACTIONS`
+    fn action_item(&mut self, ui: &mut egui::Ui, text: &str, action: &str) {
+        let shortcut = ACTIONS
+            .iter()
+            .find(|(name, _)| *name == action)
+            .map_or("", |(_, shortcut)| shortcut);
+        if self.menu_item(ui, text, shortcut) {
+            self.run_action(ui.ctx(), action);
+        }
+    }
+
+    /// Runs an action of the command palette or the menus.
+    ///
+    /// # Arguments
+    /// * `ctx` - The egui context, used to move the focus
+    /// * `action` - Name of the action, one of `ACTIONS`
+    fn run_action(&mut self, ctx: &egui::Context, action: &str) {
+        match action {
+            "Open…" => self.load_dialog = true,
+            "Save…" => self.save_dialog = true,
+            "Export PDF…" | "Export Report…" => {
+                self.pdf_report = action == "Export Report…";
+                self.pdf_dialog = true;
+            }
+            "Settings…" => self.open_settings(),
+            "Command Palette…" => self.palette = Some((String::new(), 0)),
+            "Go to Cell" => {
+                self.cell_ref.1 = true;
+                self.cell_ref.2 = true;
+            }
+            "Command Terminal" => ctx.memory_mut(|m| m.request_focus(egui::Id::new("terminal"))),
+            "Find and Replace…" => {
+                self.find_dialog = true;
+                ctx.memory_mut(|m| m.request_focus(egui::Id::new("find_query")));
+            }
+            "Replace in Formulas…" => self.rewrite_dialog = true,
+            "Paste Special…" => {
+                self.run_command("paste_special");
+            }
+            "Insert Row Below" => match self.block.map(|(_, cursor)| cursor).or(self.selected_cell)
+            {
+                Some(ind) => self.insert_row_below(ind),
+                None => {
+                    Notification::new()
+                        .summary("Cannot Insert")
+                        .body("Select a cell to insert a row below it.")
+                        .show()
+                        .unwrap();
+                }
+            },
+            "Toggle Toolbar" => {
+                self.config.toolbar = !self.config.toolbar;
+                if let Err(e) = utils::config::save(&self.config) {
+                    Notification::new()
+                        .summary("Settings Not Saved")
+                        .body(
+                            format!("The toolbar setting applies until the app is closed: {}", e)
+                                .as_str(),
+                        )
+                        .show()
+                        .unwrap();
+                }
+                self.config_todo = Some(self.config.clone());
+            }
+            "Toggle Error Panel" => self.error_panel = !self.error_panel,
+            "Toggle Formulas" => self.show_formulas = !self.show_formulas,
+            "Go to Top" => {
+                self.top_h = 1;
+                self.top_v = 1;
+            }
+            "Go to End" => self.scroll_by(self.engine.len_v, self.engine.len_h),
+            "Unhide All Rows" => {
+                self.hidden_rows.clear();
+                self.dirty = true;
+            }
+            "Unhide All Columns" => {
+                self.hidden_cols.clear();
+                self.dirty = true;
+            }
+            "Plot…" => self.plot_dialog = true,
+            "Describe…" => self.describe_dialog = true,
+            "Stats Tests…" => {
+                self.run_command("stats_tests");
+            }
+            "Dependency Graph Stats" => {
+                self.run_command("graph_stats");
+            }
+            "Verify Recalculation" => {
+                self.run_command("verify");
+            }
+            "Function Reference" => {
+                self.run_command("help");
+            }
+            "About" => self.about_dialog = true,
+            _ => {}
+        }
+    }
+
+    /// Shows the command palette, which searches the actions by name and runs the chosen one.
+    ///
+    /// Up and Down move between the matches, Enter runs the highlighted one and Escape
+    /// closes the palette.
+    fn command_palette(&mut self, ctx: &egui::Context) {
+        let Some((mut query, mut highlight)) = self.palette.take() else {
+            return;
+        };
+        let names: Vec<&str> = ACTIONS.iter().map(|(name, _)| *name).collect();
+        let matches = utils::fuzzy::rank(&query, &names);
+        let (up, down, enter, escape) = ctx.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                i.key_pressed(egui::Key::Enter),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+            )
+        });
+        if up {
+            highlight = highlight.saturating_sub(1);
+        }
+        if down {
+            highlight += 1;
+        }
+        highlight = highlight.min(matches.len().saturating_sub(1));
+        let mut chosen = None;
+        let mut open = !escape;
+        egui::Window::new("Command Palette")
+            .open(&mut open)
+            .order(egui::Order::Foreground)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 80.0))
+            .fixed_size(egui::vec2(460.0, 0.0))
+            .collapsible(false)
+            .show(ctx, |ui| {
+                let field = ui.add(
+                    egui::TextEdit::singleline(&mut query)
+                        .id(egui::Id::new("palette_query"))
+                        .hint_text("Search actions")
+                        .desired_width(f32::INFINITY)
+                        .font(FontId::proportional(18.0)),
+                );
+                field.request_focus();
+                if field.changed() {
+                    highlight = 0;
+                }
+                ui.separator();
+                if matches.is_empty() {
+                    ui.label("No action matches.");
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        for (row, &i) in matches.iter().enumerate() {
+                            let (name, shortcut) = ACTIONS[i];
+                            let response = ui.add(
+                                Button::new(RichText::new(name).font(FontId::proportional(16.0)))
+                                    .shortcut_text(shortcut)
+                                    .selected(row == highlight)
+                                    .min_size(egui::vec2(ui.available_width(), 0.0)),
+                            );
+                            if row == highlight && (up || down) {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                chosen = Some(name);
+                            }
+                        }
+                    });
+            });
+        if enter {
+            chosen = chosen.or(matches.get(highlight).map(|&i| ACTIONS[i].0));
+        }
+        match chosen {
+            Some(action) => self.run_action(ctx, action),
+            None if open => self.palette = Some((query, highlight)),
+            None => {}
+        }
+    }
+
+    /// Shows the formatting toolbar, which styles the selected block or the focused cell.
+    ///
+    /// The buttons show the style of the first cell of the block, and are disabled when no
+    /// cell is selected.
+    ///
+    /// # Arguments
+    /// * `ui` - The ui of the toolbar
+    #[allow(clippy::type_complexity)]
+    fn format_bar(&mut self, ui: &mut egui::Ui) {
+        let range = self.block.map(|(_, cursor)| self.block_range(cursor));
+        let current = range
+            .and_then(|(x, y, _, _)| self.styles.get(&(x + (y - 1) * self.engine.len_h)))
+            .copied()
+            .unwrap_or_default();
+        let mut change: Option<Box<dyn Fn(&mut utils::style::CellStyle)>> = None;
+        ui.add_enabled_ui(range.is_some(), |ui| {
+            ui.horizontal(|ui| {
+                let bold = RichText::new("B").font(cell_font(true)).size(18.0);
+                if ui
+                    .selectable_label(current.bold, bold)
+                    .on_hover_text("Bold")
+                    .clicked()
+                {
+                    change = Some(Box::new(move |s| s.bold = !current.bold));
+                }
+                ui.separator();
+                for align in utils::style::Align::ALL {
+                    let chosen = current.align == Some(align);
+                    let name = RichText::new(align.name()).font(FontId::proportional(18.0));
+                    if ui
+                        .selectable_label(chosen, name)
+                        .on_hover_text(format!("Align {}", align.name()))
+                        .clicked()
+                    {
+                        // Choosing the alignment a cell has puts it back to the default
+                        change = Some(Box::new(move |s| s.align = (!chosen).then_some(align)));
+                    }
+                }
+                ui.separator();
+                // Unstyled cells show the colors of the theme
+                let rgb = |c: Color32| [c.r(), c.g(), c.b()];
+                let mut color = current.color.unwrap_or(rgb(ui.visuals().text_color()));
+                ui.label(RichText::new("Text:").font(FontId::proportional(18.0)));
+                if ui.color_edit_button_srgb(&mut color).changed() {
+                    change = Some(Box::new(move |s| s.color = Some(color)));
+                }
+                let mut background = current.background.unwrap_or(rgb(ui.visuals().panel_fill));
+                ui.label(RichText::new("Fill:").font(FontId::proportional(18.0)));
+                if ui.color_edit_button_srgb(&mut background).changed() {
+                    change = Some(Box::new(move |s| s.background = Some(background)));
+                }
+                ui.separator();
+                if ui
+                    .button(RichText::new("Clear Format").font(FontId::proportional(18.0)))
+                    .clicked()
+                {
+                    change = Some(Box::new(|s| *s = utils::style::CellStyle::default()));
+                }
+            });
+        });
+        if let (Some(range), Some(change)) = (range, change) {
+            utils::style::restyle(&mut self.styles, range, self.engine.len_h, change);
+            self.dirty = true;
+        }
+    }
+
+    /// Gets the rows and columns to leave out of CSV and PDF exports.
+    ///
+    /// # Returns
+    /// The hidden rows and columns if `skip_hidden` is set, otherwise empty slices.
+    fn export_hidden(&self) -> (&[i32], &[i32]) {
+        if self.skip_hidden {
+            (&self.hidden_rows, &self.hidden_cols)
+        } else {
+            (&[], &[])
+        }
+    }
+
+    /// Checks the workbook before a save or export.
+    ///
+    /// If problems are found they are shown to the user, and the save or export waits until
+    /// the user goes ahead or cancels it.
+    ///
+    /// # Returns
+    /// `true` if the save or export can go ahead now.
+    fn export_checked(&mut self) -> bool {
+        if self.export_confirmed {
+            self.export_confirmed = false;
+            return true;
+        }
+        if self.validation.is_some() {
+            return false;
+        }
+        self.finish_recalc();
+        let (problems, total) = utils::validate::check(
+            self.engine.database(),
+            self.engine.err(),
+            self.engine.opers(),
+            self.engine.len_h,
+            self.export_hidden(),
+        );
+        if problems.is_empty() {
+            return true;
+        }
+        self.validation = Some((problems, total));
+        false
+    }
+
+    /// Opens the Settings window with the current preferences and the resource limits of the
+    /// workbook.
+    fn open_settings(&mut self) {
+        let limits = self.engine.limits();
+        let limits = [
+            limits.max_cells().map_or(0, |c| c as u64),
+            limits.max_eval_time().map_or(0, |t| t.as_millis() as u64),
+            limits.max_sleep().map_or(0, |s| s as u64),
+        ];
+        self.settings_draft = Some((self.config.clone(), limits));
+    }
+
+    /// Applies the preferences and resource limits of the Settings window and stores them.
+    ///
+    /// # Arguments
+    /// * `config` - The preferences
+    /// * `limits` - The cell, time and sleep limits, with 0 for no limit
+    ///
+    /// # Returns
+    /// `false` if a preference is invalid, in which case nothing is applied.
+    fn apply_settings(&mut self, config: utils::config::Config, limits: [u64; 3]) -> bool {
+        if let Err(message) = config.check() {
+            Notification::new()
+                .summary("Invalid Settings")
+                .body(message)
+                .show()
+                .unwrap();
+            return false;
+        }
+        let mut config = config;
+        config.set_limits(limits);
+        self.config = config;
+        if let Err(e) = utils::config::save(&self.config) {
+            Notification::new()
+                .summary("Settings Not Saved")
+                .body(format!("The settings apply until the app is closed: {}", e).as_str())
+                .show()
+                .unwrap();
+        }
+        self.config_todo = Some(self.config.clone());
+        true
+    }
+
+    /// Builds the tooltip text shown when hovering a cell.
+    ///
+    /// # Arguments
+    /// * `ind` - Index of the hovered cell
+    ///
+    /// # Returns
+    /// A multi-line summary with the formula, value as shown in the grid, error cause and the
+    /// number of precedents/dependents of the cell.
+    fn cell_tooltip(&self, ind: i32) -> String {
+        let formula = self.shown_formula(ind);
+        let formula = if formula.is_empty() {
+            String::from("(empty)")
+        } else {
+            format!("={}", formula)
+        };
+        let value = if self.engine.err()[ind as usize] {
+            String::from("ERR")
+        } else {
+            self.value_text(ind)
+        };
+        let mut text = format!(
+            "{}\nFormula: {}\nValue: {}",
+            crate::ind_to_cell(ind, self.engine.len_h),
+            formula,
+            value
+        );
+        if let Some(cause) = utils::graph::error_cause(
+            ind,
+            self.engine.database(),
+            self.engine.opers(),
+            self.engine.err(),
+            self.engine.len_h,
+        ) {
+            text.push_str(&format!("\nError: {}", cause));
+        }
+        text.push_str(&format!(
+            "\nPrecedents: {}\nDependents: {}",
+            utils::graph::precedents(&self.engine.opers()[ind as usize], self.engine.len_h).len(),
+            utils::graph::dependents(self.engine.sensi(), ind).len()
+        ));
+        let col = (ind - 1) % self.engine.len_h + 1;
+        if let Some(col_type) = self.col_types.get(&col) {
+            text.push_str(&format!("\nColumn type: {}", col_type.name()));
+        }
+        if let Some(rule) = self.col_formulas.get(&col) {
+            text.push_str(&format!(
+                "\nColumn formula from row {}: ={}",
+                rule.first_row, rule.formula
+            ));
+        }
+        if let Some(format) = self.number_formats.get(&ind) {
+            text.push_str(&format!("\nFormat: {}", format.text()));
+        }
+        if let Some(provenance) = self.provenance.get(&ind) {
+            text.push_str(&format!("\n{}", provenance.text()));
+        }
+        if let Some(left) = self.engine.schedule().left(ind, std::time::Instant::now()) {
+            text.push_str(&format!(
+                "\nWaiting on SLEEP, resolves in {:.0}s",
+                left.as_secs_f64().ceil()
+            ));
+        } else if self.is_pending(ind) {
+            text.push_str("\nRecalculating, the value shown is the last one");
+        }
+        text
+    }
+}
+
+impl eframe::App for Spreadsheet {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Take over a finished background recalculation, which repaints the window when done
+        self.repaint = Some(ctx.clone());
+        // Escape cancels it, unless a text field takes the key
+        if self.recalc.is_some()
+            && ctx.memory(|m| m.focused().is_none())
+            && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape))
+        {
+            self.cancel_recalc();
+        }
+        if self.recalc.as_ref().is_some_and(|r| r.is_finished()) {
+            let recalc = self.recalc.take().unwrap();
+            self.install(recalc);
+        }
+        // Cells waiting on SLEEP are drawn again as they resolve
+        let now = std::time::Instant::now();
+        if let Some(next) = self.engine.schedule().next(now) {
+            ctx.request_repaint_after(next - now);
+        }
+
+        // Autosave, some minutes after the first change that is not saved
+        if self.config.autosave > 0
+            && self.dirty
+            && self.recalc.is_none()
+            && let Some(path) = self.saved_path.clone()
+        {
+            let since = *self
+                .autosave_since
+                .get_or_insert_with(std::time::Instant::now);
+            if since.elapsed().as_secs() >= u64::from(self.config.autosave) * 60 {
+                match ui::loadnsave::save_to_file(self, &path, self.config.backups) {
+                    Ok(()) => {
+                        self.dirty = false;
+                        self.autosave_since = None;
+                    }
+                    Err(e) => {
+                        // Try again after another interval rather than on every frame
+                        self.autosave_since = Some(std::time::Instant::now());
+                        Notification::new()
+                            .summary("Autosave Failed")
+                            .body(format!("Could not save {}: {}", path, e).as_str())
+                            .show()
+                            .unwrap();
+                    }
+                }
+            }
+        }
+
+        // Auto refresh, waiting for background recalculations and watch breaks to end
+        if self.recalc.is_none()
+            && self.watch_break.is_none()
+            && !self.engine.limits().safe_mode()
+            && self.refresh.due(std::time::Instant::now())
+        {
+            self.refresh_volatile();
+        }
+        if self.refresh.interval.is_some() && !self.refresh.paused {
+            // Keeps the countdown ticking
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
+
+        // Save dialog
+        egui::Window::new("Save Spreadsheet")
+        .open(&mut self.save_dialog)
+        .order(egui::Order::Foreground)
+        .fixed_size(egui::vec2(800.0, 500.0))
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.add_space(10.0);
+            ui.add_sized([500.0,30.0],egui::TextEdit::singleline(&mut self.save_name).hint_text("Enter file name").font(FontId::proportional(20.0)));
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                ui.add_sized([400.0,30.0],egui::TextEdit::singleline(&mut self.save_path).hint_text("Enter folder path").font(FontId::proportional(20.0)));
+                // ui.text_edit_singleline(&mut self.save_path);
+                if ui.add_sized([90.0,30.0],Button::new(RichText::new("Browse").font(FontId::proportional(20.0)))).clicked()
+                    && let Some(path) = rfd::FileDialog::new().pick_folder() {
+                        self.save_path = path.display().to_string();
+                    };});
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                ui.label("\t\t\t\t\t\t\t");
+                if ui.add(egui::RadioButton::new(self.save_type==Save::Rsk, RichText::new("RSK\t\t\t\t\t\t\t\t").font(FontId::proportional(20.0)))).on_hover_text("Save to a custom file extension that saves the state of program when you next load it").clicked() {
+                    self.save_type = Save::Rsk;
+                }
+                if ui.add(egui::RadioButton::new(self.save_type==Save::Csv, RichText::new("CSV").font(FontId::proportional(20.0)))).on_hover_text("Save all visible values to a CSV but all the formula's are lost").clicked() {
+                    self.save_type = Save::Csv;
+                }
+                if ui.add(egui::RadioButton::new(self.save_type==Save::Text, RichText::new("TEXT").font(FontId::proportional(20.0)))).on_hover_text("Save the formulas as plain text, one line per cell, for use with version control").clicked() {
+                    self.save_type = Save::Text;
+                }
+
+            });
+            if self.save_type == Save::Csv {
+                ui.horizontal(|ui| {
+                    ui.label("\t\t\t\t\t\t\t");
+                    ui.checkbox(&mut self.skip_hidden, RichText::new("Skip hidden rows and columns").font(FontId::proportional(20.0)));
+                });
+            }
+            ui.horizontal(|ui|{
+                ui.label("\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t");
+
+                if ui.add_sized([100.0,30.0], Button::new(RichText::new("Save").font(FontId::proportional(20.0)))).clicked() {
+                    if self.save_type == Save::Rsk {
+                        let path = format!("{}/{}.rsk", self.save_path,self.save_name);
+                        self.save_todo = Some((self.save_type.clone(),path));
+                    } else if self.save_type == Save::Csv {
+                        let path = format!("{}/{}.csv", self.save_path,self.save_name);
+                        self.save_todo = Some((self.save_type.clone(),path));
+                    } else if self.save_type == Save::Text {
+                        let path = format!("{}/{}.{}", self.save_path,self.save_name,utils::textformat::EXTENSION);
+                        self.save_todo = Some((self.save_type.clone(),path));
+                    }
+                }
+            });
+        });
+
+        if self.save_todo.is_some() && self.export_checked() {
+            self.finish_recalc();
+            println!("{:?}", self.save_todo);
+            let (save_type, path) = self.save_todo.clone().unwrap();
+            self.save_todo = None;
+            self.save_dialog = false;
+            let saved = match save_type {
+                Save::Rsk => {
+                    ui::loadnsave::save_to_file(self, &path, self.config.backups).map(|()| {
+                        self.title = self.save_name.clone();
+                        self.dirty = false;
+                        self.saved_path = Some(path.clone());
+                        self.autosave_since = None;
+                    })
+                }
+                Save::Csv => ui::loadnsave::save_1d_as_csv(
+                    self.engine.database(),
+                    self.engine.err(),
+                    self.engine.opers(),
+                    self.engine.len_h,
+                    self.engine.len_v,
+                    self.export_hidden(),
+                    &self.col_labels,
+                    &self.number_formats,
+                    self.config.delimiter(),
+                    &path,
+                ),
+                Save::Text => ui::loadnsave::save_as_text(
+                    self.engine.opers(),
+                    self.engine.len_h,
+                    self.engine.len_v,
+                    &self.col_types,
+                    &self.number_formats,
+                    &path,
+                )
+                .map(|()| {
+                    self.title = self.save_name.clone();
+                    self.dirty = false;
+                }),
+            };
+
+            match saved {
+                Ok(()) if !self.config.notify_saves => {}
+                Ok(()) => {
+                    Notification::new()
+                        .summary("File Saved")
+                        .body(format!("File saved to {}", path).as_str())
+                        .show()
+                        .unwrap();
+                }
+                Err(e) => {
+                    Notification::new()
+                        .summary("Save Failed")
+                        .body(format!("Could not save {}: {}", path, e).as_str())
+                        .show()
+                        .unwrap();
+                }
+            }
+        }
+
+        // Load dialog
+        egui::Window::new("Load Spreadsheet")
+            .open(&mut self.load_dialog)
+            .order(egui::Order::Foreground)
+            .fixed_size(egui::vec2(800.0, 500.0))
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    ui.add_sized(
+                        [400.0, 30.0],
+                        egui::TextEdit::singleline(&mut self.load_path)
+                            .hint_text("Enter file path")
+                            .font(FontId::proportional(20.0)),
+                    );
+                    // ui.text_edit_singleline(&mut self.save_path);
+                    if ui
+                        .add_sized(
+                            [90.0, 30.0],
+                            Button::new(RichText::new("Browse").font(FontId::proportional(20.0))),
+                        )
+                        .clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Rust Spreadsheet", &["rsk", utils::textformat::EXTENSION])
+                            .pick_file()
+                    {
+                        self.load_path = path.display().to_string();
+                    };
+                });
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    ui.label("\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t");
+
+                    if ui
+                        .add_sized(
+                            [100.0, 30.0],
+                            Button::new(RichText::new("Load").font(FontId::proportional(20.0))),
+                        )
+                        .clicked()
+                    {
+                        self.load_todo = true;
+                    }
+                });
+
+                // Earlier versions of the file, kept when it was saved over
+                let backups = utils::backup::list(&self.load_path);
+                if !backups.is_empty() {
+                    ui.add_space(10.0);
+                    ui.separator();
+                    ui.label(RichText::new("Restore backup:").font(FontId::proportional(20.0)));
+                    ui.label("A backup opens in a new tab, and saving it replaces the file.");
+                    for (n, modified) in backups {
+                        let time = self.config.format_time(
+                            modified.into(),
+                            &format!("{} {}", self.config.date_format, self.config.time_format),
+                        );
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                RichText::new(format!("Backup {}: saved {}", n, time))
+                                    .font(FontId::proportional(18.0)),
+                            );
+                            if ui.button("Restore").clicked() {
+                                self.restore_todo = Some((self.load_path.clone(), n));
+                            }
+                        });
+                    }
+                }
+            });
+        if self.restore_todo.is_some() {
+            self.load_dialog = false;
+        }
+
+        if self.load_todo {
+            self.load_dialog = false;
+            self.load_todo = false;
+            let path = self.load_path.clone();
+            // The session opens the workbook in a new tab
+            self.open_todo = Some(path.clone());
+            Notification::new()
+                .summary("File Loaded")
+                .body(format!("File Loaded from {}", path).as_str())
+                .show()
+                .unwrap();
+        }
+
+        //  Plot dialog
+        egui::Window::new("Plot Data")
+            .open(&mut self.plot_dialog)
+            .order(egui::Order::Foreground)
+            .fixed_size(egui::vec2(800.0, 820.0))
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("X-Axis:\t").font(FontId::proportional(20.0)));
+                    ui.add_sized(
+                        [450.0, 30.0],
+                        egui::TextEdit::singleline(&mut self.plot_x_axis)
+                            .hint_text("Enter column for X-axis")
+                            .font(FontId::proportional(20.0)),
+                    );
+                });
+
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Y-Axis:\t").font(FontId::proportional(20.0)));
+                    ui.add_sized(
+                        [450.0, 30.0],
+                        egui::TextEdit::singleline(&mut self.plot_y_axis)
+                            .hint_text("Enter column for Y-axis")
+                            .font(FontId::proportional(20.0)),
+                    );
+                });
+
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Y2-Axis:	").font(FontId::proportional(20.0)));
+                    ui.add_sized(
+                        [450.0, 30.0],
+                        egui::TextEdit::singleline(&mut self.plot_y2_axis)
+                            .hint_text("Optional column for a secondary Y-axis")
+                            .font(FontId::proportional(20.0)),
+                    );
+                });
+
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Error:\t").font(FontId::proportional(20.0)));
+                    ui.add_sized(
+                        [450.0, 30.0],
+                        egui::TextEdit::singleline(&mut self.plot_err_axis)
+                            .hint_text("Optional column of errors, e.g. standard deviations")
+                            .font(FontId::proportional(20.0)),
+                    );
+                });
+
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Rows: \t").font(FontId::proportional(20.0)));
+                    ui.add_sized(
+                        [450.0, 30.0],
+                        egui::TextEdit::singleline(&mut self.plot_rows)
+                            .hint_text("Enter row range (e.g., 1-10)")
+                            .font(FontId::proportional(20.0)),
+                    );
+                });
+
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Plot Type:\t\t").font(FontId::proportional(20.0)));
+                    if ui
+                        .add(egui::RadioButton::new(
+                            self.plot_type == Plot::Line,
+                            RichText::new("Line\t\t\t\t").font(FontId::proportional(20.0)),
+                        ))
+                        .clicked()
+                    {
+                        self.plot_type = Plot::Line;
+                    }
+                    if ui
+                        .add(egui::RadioButton::new(
+                            self.plot_type == Plot::Scatter,
+                            RichText::new("Scatter").font(FontId::proportional(20.0)),
+                        ))
+                        .clicked()
+                    {
+                        self.plot_type = Plot::Scatter;
+                    }
+                });
+
+                ui.add_space(10.0);
+                let style = &mut self.plot_style;
+                for (label, text, hint) in [
+                    (
+                        "Title:\t\t",
+                        &mut style.title,
+                        "Default title of the plot type",
+                    ),
+                    (
+                        "X Label:\t",
+                        &mut style.x_label,
+                        "Defaults to the X-axis column",
+                    ),
+                    (
+                        "Y Label:\t",
+                        &mut style.y_label,
+                        "Defaults to the Y-axis column",
+                    ),
+                    (
+                        "Y2 Label:\t",
+                        &mut style.y2_label,
+                        "Defaults to the Y2-axis column",
+                    ),
+                ] {
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new(label).font(FontId::proportional(20.0)));
+                        ui.add_sized(
+                            [450.0, 30.0],
+                            egui::TextEdit::singleline(text)
+                                .hint_text(hint)
+                                .font(FontId::proportional(20.0)),
+                        );
+                    });
+                    ui.add_space(10.0);
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Theme:\t\t").font(FontId::proportional(20.0)));
+                    egui::ComboBox::from_id_salt("plot_theme")
+                        .selected_text(style.theme.name())
+                        .show_ui(ui, |ui| {
+                            for theme in utils::ui::plot::Theme::ALL {
+                                if ui
+                                    .selectable_label(style.theme == theme, theme.name())
+                                    .clicked()
+                                {
+                                    theme.apply(style);
+                                }
+                            }
+                        });
+                    ui.label(RichText::new("Color:").font(FontId::proportional(20.0)));
+                    ui.color_edit_button_srgb(&mut style.color);
+                    ui.label(RichText::new("Background:").font(FontId::proportional(20.0)));
+                    ui.color_edit_button_srgb(&mut style.background);
+                });
+
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Marker:\t\t").font(FontId::proportional(20.0)));
+                    egui::ComboBox::from_id_salt("plot_marker")
+                        .selected_text(style.marker.name())
+                        .show_ui(ui, |ui| {
+                            for marker in utils::ui::plot::Marker::ALL {
+                                ui.selectable_value(&mut style.marker, marker, marker.name());
+                            }
+                        });
+                    ui.add(egui::Slider::new(&mut style.marker_size, 1..=12).text("size"));
+                    ui.add(egui::Slider::new(&mut style.line_width, 1..=8).text("line width"));
+                    ui.checkbox(&mut style.grid, "Grid");
+                    ui.checkbox(&mut style.log_x, "Log X");
+                    ui.checkbox(&mut style.log_y, "Log Y");
+                });
+
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Save Path:\t").font(FontId::proportional(20.0)));
+                    ui.add_sized(
+                        [300.0, 30.0],
+                        egui::TextEdit::singleline(&mut self.plot_save)
+                            .hint_text("Enter save path")
+                            .font(FontId::proportional(20.0)),
+                    );
+                    if ui
+                        .add_sized(
+                            [90.0, 30.0],
+                            Button::new(RichText::new("Browse").font(FontId::proportional(20.0))),
+                        )
+                        .clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("PNG Image", &["png"])
+                            .save_file()
+                    {
+                        self.plot_save = path.display().to_string();
+                    };
+                });
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    ui.label("\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t");
+
+                    if ui
+                        .add_sized(
+                            [100.0, 30.0],
+                            Button::new(RichText::new("Plot").font(FontId::proportional(20.0))),
+                        )
+                        .clicked()
+                    {
+                        self.plot_todo = true;
+                    };
+                });
+            });
+
+        if self.plot_todo {
+            self.plot_todo = false;
+            match self.render_plot(&self.plot_save) {
+                Ok(()) => {
+                    self.plot_dialog = false;
+                    // The image is read again, as an earlier plot may have had the same path
+                    ctx.forget_image(&format!("file://{}", self.plot_save));
+                    self.plot_preview = Some(self.plot_save.clone());
+                }
+                Err(message) => {
+                    Notification::new()
+                        .summary("Plot Failed")
+                        .body(&message)
+                        .show()
+                        .unwrap();
+                }
+            }
+        }
+
+        // Plot preview
+        let mut preview_open = self.plot_preview.is_some();
+        let mut open_external = false;
+        if let Some(path) = &self.plot_preview {
+            egui::Window::new("Plot Preview")
+                .open(&mut preview_open)
+                .order(egui::Order::Foreground)
+                .default_size(egui::vec2(820.0, 660.0))
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    ui.label(RichText::new(path).font(FontId::proportional(16.0)));
+                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_sized(
+                                [180.0, 30.0],
+                                Button::new(
+                                    RichText::new("Open in Viewer")
+                                        .font(FontId::proportional(20.0)),
+                                ),
+                            )
+                            .clicked()
+                        {
+                            open_external = true;
+                        }
+                    });
+                    ui.add_space(10.0);
+                    ui.add(
+                        egui::Image::new(format!("file://{}", path))
+                            .max_size(ui.available_size())
+                            .maintain_aspect_ratio(true),
+                    );
+                });
+        }
+        if open_external
+            && let Some(path) = &self.plot_preview
+            && let Err(e) = utils::ui::open::that(path)
+        {
+            Notification::new()
+                .summary("Could Not Open Plot")
+                .body(&format!("{}: {}", path, e))
+                .show()
+                .unwrap();
+        }
+        if !preview_open {
+            self.plot_preview = None;
+        }
+
+        // PDF dialog
+        egui::Window::new("Save as PDF")
+            .open(&mut self.pdf_dialog)
+            .order(egui::Order::Foreground)
+            .fixed_size(egui::vec2(800.0, 500.0))
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    ui.add_sized(
+                        [400.0, 30.0],
+                        egui::TextEdit::singleline(&mut self.pdf_path)
+                            .hint_text("Enter PDF path")
+                            .font(FontId::proportional(20.0)),
+                    );
+                    // ui.text_edit_singleline(&mut self.save_path);
+                    if ui
+                        .add_sized(
+                            [90.0, 30.0],
+                            Button::new(RichText::new("Browse").font(FontId::proportional(20.0))),
+                        )
+                        .clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("PDF Document", &["pdf"])
+                            .save_file()
+                    {
+                        self.pdf_path = path.display().to_string();
+                    };
+                });
+                ui.add_space(10.0);
+                ui.checkbox(
+                    &mut self.skip_hidden,
+                    RichText::new("Skip hidden rows and columns").font(FontId::proportional(20.0)),
+                );
+                ui.add_space(10.0);
+                ui.checkbox(
+                    &mut self.pdf_report,
+                    RichText::new("Report with title page, chart and statistics")
+                        .font(FontId::proportional(20.0)),
+                );
+                if self.pdf_report {
+                    ui.add_space(10.0);
+                    ui.add_sized(
+                        [400.0, 30.0],
+                        egui::TextEdit::singleline(&mut self.pdf_title)
+                            .hint_text("Report title")
+                            .font(FontId::proportional(20.0)),
+                    );
+                }
+                ui.add_space(10.0);
+
+                ui.horizontal(|ui| {
+                    ui.label("\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t");
+
+                    if ui
+                        .add_sized(
+                            [100.0, 30.0],
+                            Button::new(RichText::new("Save").font(FontId::proportional(20.0))),
+                        )
+                        .clicked()
+                    {
+                        self.pdf_todo = true;
+                    }
+                });
+            });
+
+        if self.pdf_todo && self.export_checked() {
+            self.pdf_dialog = false;
+            self.pdf_todo = false;
+            if self.pdf_report {
+                let body = match self.save_report() {
+                    Ok(()) => (self.config.notify_saves)
+                        .then(|| format!("Report saved to {}", self.pdf_path)),
+                    Err(e) => Some(format!("Could not save the report: {}", e)),
+                };
+                if let Some(body) = body {
+                    Notification::new()
+                        .summary("Report")
+                        .body(&body)
+                        .show()
+                        .unwrap();
+                }
+            } else if self.pdf_export.is_some() {
+                Notification::new()
+                    .summary("PDF Export Running")
+                    .body("Wait for the current export to finish or cancel it first.")
+                    .show()
+                    .unwrap();
+            } else {
+                // The worker gets its own copy of the grid, so editing can go on meanwhile
+                let sheet = self.engine.clone();
+                let (rows, cols) = self.export_hidden();
+                let hidden = (rows.to_vec(), cols.to_vec());
+                let widths: Vec<f32> = (0..=self.engine.len_h).map(|c| self.col_width(c)).collect();
+                let labels = self.col_labels.clone();
+                let formats = self.number_formats.clone();
+                let styles = self.styles.clone();
+                let path = self.pdf_path.clone();
+                self.pdf_export = Some(utils::ui::loadnsave::PdfExport::start(
+                    path,
+                    move |path, progress| {
+                        utils::ui::loadnsave::save_1d_as_pdf(
+                            sheet.database(),
+                            sheet.err(),
+                            sheet.opers(),
+                            sheet.len_h,
+                            sheet.len_v,
+                            (&hidden.0, &hidden.1),
+                            &labels,
+                            &formats,
+                            &styles,
+                            &|col| widths[col as usize],
+                            path,
+                            progress,
+                        )
+                    },
+                ));
+            }
+        }
+
+        // Progress of a PDF export
+        let mut export_cancel = false;
+        if let Some(export) = &self.pdf_export {
+            let (pages, total) = export.progress().pages();
+            egui::Window::new("Exporting PDF")
+                .order(egui::Order::Foreground)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(RichText::new(export.path()).font(FontId::proportional(16.0)));
+                    ui.add_space(10.0);
+                    let text = if export.progress().is_cancelled() {
+                        String::from("Cancelling...")
+                    } else if total == 0 {
+                        String::from("Laying out pages...")
+                    } else if pages == total {
+                        format!("{} pages rendered, writing the file...", total)
+                    } else {
+                        format!("{} of {} pages rendered", pages, total)
+                    };
+                    let fraction = if total == 0 {
+                        0.0
+                    } else {
+                        pages as f32 / total as f32
+                    };
+                    ui.add(
+                        egui::ProgressBar::new(fraction)
+                            .desired_width(400.0)
+                            .text(text),
+                    );
+                    ui.add_space(10.0);
+                    if ui.button("Cancel").clicked() {
+                        export_cancel = true;
+                    }
+                });
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+        if export_cancel && let Some(export) = &self.pdf_export {
+            export.progress().cancel();
+        }
+        if self.pdf_export.as_ref().is_some_and(|e| e.is_finished()) {
+            let export = self.pdf_export.take().unwrap();
+            let cancelled = export.progress().is_cancelled();
+            let path = export.path().to_string();
+            let result = export.finish();
+            let (summary, body) = match &result {
+                Ok(()) => ("PDF Saved", format!("PDF saved to {}", path)),
+                Err(_) if cancelled => ("PDF Export Cancelled", format!("{} was not saved", path)),
+                Err(e) => (
+                    "PDF Export Failed",
+                    format!("Could not save {}: {}", path, e),
+                ),
+            };
+            if result.is_err() || self.config.notify_saves {
+                Notification::new()
+                    .summary(summary)
+                    .body(&body)
+                    .show()
+                    .unwrap();
+            }
+        }
+
+        // Settings dialog
+        let mut settings_answer = None;
+        if let Some((draft, limits)) = &mut self.settings_draft {
+            egui::Window::new("Settings")
+                .order(egui::Order::Foreground)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    egui::Grid::new("settings")
+                        .num_columns(2)
+                        .spacing([20.0, 8.0])
+                        .show(ui, |ui| {
+                            ui.strong("Appearance");
+                            ui.end_row();
+                            ui.label("Theme");
+                            egui::ComboBox::from_id_salt("settings_theme")
+                                .selected_text(draft.theme.name())
+                                .show_ui(ui, |ui| {
+                                    for theme in utils::config::Theme::ALL {
+                                        ui.selectable_value(&mut draft.theme, theme, theme.name());
+                                    }
+                                });
+                            ui.end_row();
+                            ui.label("Toolbar");
+                            ui.checkbox(&mut draft.toolbar, "Show the image toolbar");
+                            ui.end_row();
+
+                            ui.strong("Locale");
+                            ui.end_row();
+                            ui.label("Date format");
+                            ui.text_edit_singleline(&mut draft.date_format);
+                            ui.end_row();
+                            ui.label("Time format");
+                            ui.text_edit_singleline(&mut draft.time_format);
+                            ui.end_row();
+                            ui.label("Timezone");
+                            ui.text_edit_singleline(&mut draft.timezone)
+                                .on_hover_text("local, UTC or an offset such as +05:30");
+                            ui.end_row();
+
+                            ui.strong("Files");
+                            ui.end_row();
+                            ui.label("Autosave every");
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::DragValue::new(&mut draft.autosave)
+                                        .range(0..=utils::config::MAX_AUTOSAVE)
+                                        .suffix(" min"),
+                                );
+                                ui.label("0 turns it off. Only workbooks saved as .rsk are saved.");
+                            });
+                            ui.end_row();
+                            ui.label("Backups");
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::DragValue::new(&mut draft.backups)
+                                        .range(0..=utils::backup::MAX_BACKUPS),
+                                );
+                                ui.label("Earlier versions kept when a .rsk file is saved over.");
+                            });
+                            ui.end_row();
+                            ui.label("CSV delimiter");
+                            let name = utils::config::DELIMITERS
+                                .iter()
+                                .find(|(d, _)| *d == draft.csv_delimiter)
+                                .map_or("Comma", |(_, name)| name);
+                            egui::ComboBox::from_id_salt("settings_delimiter")
+                                .selected_text(name)
+                                .show_ui(ui, |ui| {
+                                    for (delimiter, name) in utils::config::DELIMITERS {
+                                        ui.selectable_value(
+                                            &mut draft.csv_delimiter,
+                                            delimiter,
+                                            name,
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+                            ui.label("Notifications");
+                            ui.checkbox(
+                                &mut draft.notify_saves,
+                                "Notify when a file is saved or exported",
+                            );
+                            ui.end_row();
+
+                            ui.strong("Recalculation");
+                            ui.end_row();
+                            ui.label("Mode");
+                            ui.checkbox(
+                                &mut draft.background_recalc,
+                                "Recalculate edits that recompute many cells in the background",
+                            );
+                            ui.end_row();
+                            ui.label("Cells per edit");
+                            ui.add(egui::DragValue::new(&mut limits[0]))
+                                .on_hover_text("0 for no limit");
+                            ui.end_row();
+                            ui.label("Time per edit");
+                            ui.add(egui::DragValue::new(&mut limits[1]).suffix(" ms"))
+                                .on_hover_text("0 for no limit");
+                            ui.end_row();
+                            ui.label("Longest SLEEP");
+                            ui.add(egui::DragValue::new(&mut limits[2]).suffix(" s"))
+                                .on_hover_text("0 for no limit");
+                            ui.end_row();
+                        });
+                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        if ui.button("Save").clicked() {
+                            settings_answer = Some(true);
+                        }
+                        if ui.button("Cancel").clicked() {
+                            settings_answer = Some(false);
+                        }
+                    });
+                });
+        }
+        if let Some(save) = settings_answer
+            && let Some((draft, limits)) = self.settings_draft.take()
+            && save
+            && !self.apply_settings(draft.clone(), limits)
+        {
+            // The window stays open so the invalid preference can be corrected
+            self.settings_draft = Some((draft, limits));
+        }
+
+        // Problems found before a save or export
+        let mut validation_answer = None;
+        let mut validation_jump = None;
+        if let Some((problems, total)) = &self.validation {
+            egui::Window::new("Check Before Saving")
+                .order(egui::Order::Foreground)
+                .collapsible(false)
+                .default_size(egui::vec2(500.0, 400.0))
+                .show(ctx, |ui| {
+                    ui.label(
+                        RichText::new(format!(
+                            "{} possible problem(s) found. Click a cell to go to it.",
+                            total
+                        ))
+                        .font(FontId::proportional(18.0)),
+                    );
+                    ui.add_space(10.0);
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            for problem in problems {
+                                ui.horizontal(|ui| {
+                                    if ui.link(self.cell_name(problem.cell)).clicked() {
+                                        validation_jump = Some(problem.cell);
+                                    }
+                                    ui.label(&problem.message);
+                                });
+                            }
+                            if *total > problems.len() {
+                                ui.label(format!("... and {} more", total - problems.len()));
+                            }
+                        });
+                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        if ui.button("Save anyway").clicked() {
+                            validation_answer = Some(true);
+                        }
+                        if ui.button("Cancel").clicked() {
+                            validation_answer = Some(false);
+                        }
+                    });
+                });
+        }
+        if let Some(cell) = validation_jump {
+            self.jump_to_cell(cell);
+        }
+        if let Some(go_ahead) = validation_answer {
+            self.validation = None;
+            self.export_confirmed = go_ahead;
+            if !go_ahead {
+                self.save_todo = None;
+                self.pdf_todo = false;
+            }
+        }
+
+        // Impact of a row or column deletion
+        let mut line_answer = None;
+        if let Some((edit, impact)) = &self.line_impact {
+            let (line, name) = if edit.row {
+                ("Row", edit.line.to_string())
+            } else {
+                ("Column", utils::display::get_label(edit.line))
+            };
+            egui::Window::new(format!("Delete {} {}", line, name))
+                .order(egui::Order::Foreground)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    for text in impact.summary(self.engine.len_h) {
+                        ui.label(RichText::new(text).font(FontId::proportional(18.0)));
+                    }
+                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        if ui.button("Delete anyway").clicked() {
+                            line_answer = Some(true);
+                        }
+                        if ui.button("Cancel").clicked() {
+                            line_answer = Some(false);
+                        }
+                    });
+                });
+        }
+        if let Some(go_ahead) = line_answer {
+            let line_impact = self.line_impact.take();
+            if let Some((edit, _)) = line_impact.filter(|_| go_ahead) {
+                self.apply_line_edit(edit);
+            }
+        }
+
+        // Describe dialog
+        let mut describe_export = None;
+        let mut describe_export_all = false;
+        let mut describe_restore = None;
+        let mut describe_open = self.describe_dialog;
+        egui::Window::new("Describe Data")
+            .open(&mut describe_open)
+            .order(egui::Order::Foreground)
+            .fixed_size(egui::vec2(420.0, 720.0))
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.add_space(10.0);
 
                 ui.horizontal(|ui| {
-                    ui.label(RichText::new("Rows: \t").font(FontId::proportional(20.0)));
+                    ui.label(RichText::new("Range:").font(FontId::proportional(20.0)));
                     ui.add_sized(
-                        [450.0, 30.0],
-                        egui::TextEdit::singleline(&mut self.plot_rows)
-                            .hint_text("Enter row range (e.g., 1-10)")
+                        [250.0, 30.0],
+                        egui::TextEdit::singleline(&mut self.describe_range)
+                            .hint_text("Enter range (e.g., A1:B10)")
                             .font(FontId::proportional(20.0)),
                     );
                 });
 
                 ui.add_space(10.0);
+                // let mut ans = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+                ui.horizontal(|ui| {
+                    ui.label("\t\t\t\t\t\t\t\t\t");
+
+                    if ui
+                        .add_sized(
+                            [100.0, 30.0],
+                            Button::new(RichText::new("Describe").font(FontId::proportional(20.0))),
+                        )
+                        .clicked()
+                    {
+                        let range = self.describe_range.trim().to_string();
+                        if let Some(bounds) = self.parse_range(&range) {
+                            // Empty cells and cells in error are left out, like in AVG
+                            let data = utils::analysis::values(
+                                self.engine.database(),
+                                self.engine.err(),
+                                self.engine.opers(),
+                                self.engine.len_h,
+                                bounds,
+                            );
+                            self.describe_data = utils::ui::stats::calculate_stats(&data);
+                            self.describe_shown = range.clone();
+                            self.describe_history.insert(0, (range, self.describe_data));
+                            self.describe_history.truncate(DESCRIBE_HISTORY);
+                        } else {
+                            Notification::new()
+                                .summary("Invalid Range")
+                                .body("The range is invalid. Please check your input.")
+                                .show()
+                                .unwrap();
+                        }
+                    }
+                });
+                ui.add_space(10.0);
+
+                let labels = [
+                    "Count:", "Mean:", "Std Dev:", "Min:", "25%:", "50%:", "75%:",
+                    "Max:",
+                    // (count, mean, std, min, p25, p50, p75, max)
+                ];
+
+                for (i, item) in labels.iter().enumerate() {
+                    egui::Grid::new(format!("describe_grid_{}", i))
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            egui::Frame::new()
+                                .stroke(egui::Stroke::new(1.0, Color32::GRAY))
+                                .show(ui, |ui| {
+                                    ui.add_sized(
+                                        [100.0, 35.0],
+                                        egui::Label::new(
+                                            RichText::new(item.to_string())
+                                                .font(FontId::proportional(20.0)),
+                                        ),
+                                    );
+                                });
+                            egui::Frame::new()
+                                .stroke(egui::Stroke::new(1.0, Color32::GRAY))
+                                .show(ui, |ui| {
+                                    ui.add_sized(
+                                        [200.0, 35.0],
+                                        egui::Label::new(
+                                            RichText::new(format!("{}", self.describe_data[i]))
+                                                .font(FontId::proportional(20.0)),
+                                        ),
+                                    );
+                                });
+                            ui.end_row();
+                        });
+                    ui.add_space(10.0);
+                }
 
+                // Export of the shown statistics
                 ui.horizontal(|ui| {
-                    ui.label(RichText::new("Plot Type:\t\t").font(FontId::proportional(20.0)));
                     if ui
-                        .add(egui::RadioButton::new(
-                            self.plot_type == Plot::Line,
-                            RichText::new("Line\t\t\t\t").font(FontId::proportional(20.0)),
-                        ))
+                        .add_enabled(
+                            !self.describe_shown.is_empty(),
+                            Button::new(RichText::new("Export CSV").font(FontId::proportional(18.0))),
+                        )
+                        .on_hover_text("Save the statistics and their range to a CSV file")
                         .clicked()
                     {
-                        self.plot_type = Plot::Line;
+                        describe_export = Some(false);
                     }
+                    ui.add_sized(
+                        [90.0, 30.0],
+                        egui::TextEdit::singleline(&mut self.describe_target)
+                            .hint_text("Eg. E1")
+                            .font(FontId::proportional(18.0)),
+                    );
                     if ui
-                        .add(egui::RadioButton::new(
-                            self.plot_type == Plot::Scatter,
-                            RichText::new("Scatter").font(FontId::proportional(20.0)),
-                        ))
+                        .add_enabled(
+                            !self.describe_shown.is_empty(),
+                            Button::new(RichText::new("Write to cells").font(FontId::proportional(18.0))),
+                        )
+                        .on_hover_text("Write the statistics, rounded to integers, into 8 cells going down from this cell")
                         .clicked()
                     {
-                        self.plot_type = Plot::Scatter;
+                        describe_export = Some(true);
                     }
                 });
 
+                // Previous analyses, click one to show it again
                 ui.add_space(10.0);
+                ui.label(RichText::new("History:").font(FontId::proportional(18.0)));
+                egui::ScrollArea::vertical().max_height(120.0).show(ui, |ui| {
+                    for (range, stats) in &self.describe_history {
+                        let text = format!("{}  (count {}, mean {:.2})", range, stats[0], stats[1]);
+                        if ui.link(RichText::new(text).font(FontId::proportional(16.0))).clicked() {
+                            describe_restore = Some((range.clone(), *stats));
+                        }
+                    }
+                });
                 ui.horizontal(|ui| {
-                    ui.label(RichText::new("Save Path:\t").font(FontId::proportional(20.0)));
-                    ui.add_sized(
-                        [300.0, 30.0],
-                        egui::TextEdit::singleline(&mut self.plot_save)
-                            .hint_text("Enter save path")
-                            .font(FontId::proportional(20.0)),
-                    );
+                    if !self.describe_history.is_empty()
+                        && ui.button("Export history to CSV").clicked()
+                    {
+                        describe_export_all = true;
+                    }
                     if ui
-                        .add_sized(
-                            [90.0, 30.0],
-                            Button::new(RichText::new("Browse").font(FontId::proportional(20.0))),
-                        )
+                        .button("Stats Tests")
+                        .on_hover_text("Compare two ranges with a t-test or chi-square test")
                         .clicked()
                     {
-                        if let Some(path) = rfd::FileDialog::new()
-                            .add_filter("PNG Image", &["png"])
-                            .save_file()
+                        self.tests_dialog = true;
+                    }
+                });
+            });
+
+        self.describe_dialog = describe_open;
+        if let Some((range, stats)) = describe_restore {
+            self.describe_range = range.clone();
+            self.describe_shown = range;
+            self.describe_data = stats;
+        }
+        if describe_export_all {
+            let history = self.describe_history.clone();
+            self.export_stats(&history);
+        }
+        match describe_export {
+            Some(false) => {
+                let current = [(self.describe_shown.clone(), self.describe_data)];
+                self.export_stats(&current);
+            }
+            Some(true) => self.write_stats(),
+            None => {}
+        }
+
+        // Stats Tests dialog
+        let mut tests_run = false;
+        let mut tests_open = self.tests_dialog;
+        egui::Window::new("Stats Tests")
+            .open(&mut tests_open)
+            .order(egui::Order::Foreground)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                use utils::ui::stats::StatTest;
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    for (kind, text) in [
+                        (StatTest::TTest, "T-test"),
+                        (StatTest::ChiSquare, "Chi-square"),
+                    ] {
+                        if ui
+                            .selectable_label(
+                                self.tests_kind == kind,
+                                RichText::new(text).font(FontId::proportional(20.0)),
+                            )
+                            .clicked()
+                            && self.tests_kind != kind
                         {
-                            self.plot_save = path.display().to_string();
+                            self.tests_kind = kind;
+                            self.tests_result = None;
                         }
+                    }
+                });
+                ui.add_space(10.0);
+                let labels = match self.tests_kind {
+                    StatTest::TTest => ["Sample 1:", "Sample 2:"],
+                    StatTest::ChiSquare => ["Observed:", "Expected:"],
+                };
+                egui::Grid::new("tests_grid").num_columns(2).show(ui, |ui| {
+                    for (label, range) in labels
+                        .iter()
+                        .zip([&mut self.tests_ranges.0, &mut self.tests_ranges.1])
+                    {
+                        ui.label(RichText::new(*label).font(FontId::proportional(20.0)));
+                        ui.add_sized(
+                            [250.0, 30.0],
+                            egui::TextEdit::singleline(range)
+                                .hint_text("Enter range (e.g., A1:A10)")
+                                .font(FontId::proportional(20.0)),
+                        );
+                        ui.end_row();
+                    }
+                });
+                ui.add_space(10.0);
+                if ui
+                    .add_sized(
+                        [100.0, 30.0],
+                        Button::new(RichText::new("Run").font(FontId::proportional(20.0))),
+                    )
+                    .clicked()
+                {
+                    tests_run = true;
+                }
+                ui.add_space(10.0);
+                if let Some(result) = self.tests_result {
+                    let statistic = match self.tests_kind {
+                        StatTest::TTest => "t",
+                        StatTest::ChiSquare => "Chi-square",
                     };
+                    for (label, value) in [
+                        (statistic, format!("{:.4}", result.statistic)),
+                        ("Degrees of freedom", format!("{:.2}", result.df)),
+                        ("p-value", format!("{:.4e}", result.p_value)),
+                    ] {
+                        ui.label(
+                            RichText::new(format!("{}: {}", label, value))
+                                .font(FontId::proportional(20.0)),
+                        );
+                    }
+                    ui.label(
+                        RichText::new(format!(
+                            "{}({}, {})",
+                            self.tests_kind.name(),
+                            self.tests_ranges.0.trim(),
+                            self.tests_ranges.1.trim()
+                        ))
+                        .font(FontId::monospace(16.0))
+                        .color(Color32::GRAY),
+                    );
+                }
+            });
+        self.tests_dialog = tests_open;
+        if tests_run {
+            self.run_test();
+        }
+
+        // Help dialog
+        egui::Window::new("Function Reference")
+            .open(&mut self.help_dialog)
+            .order(egui::Order::Foreground)
+            .default_size(egui::vec2(500.0, 500.0))
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.add_sized(
+                    [300.0, 30.0],
+                    egui::TextEdit::singleline(&mut self.help_query)
+                        .hint_text("Search functions")
+                        .font(FontId::proportional(20.0)),
+                );
+                ui.add_space(10.0);
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    let functions = utils::functions::search(&self.help_query);
+                    if functions.is_empty() {
+                        ui.label("No function matches the search.");
+                    }
+                    for function in functions {
+                        ui.label(
+                            RichText::new(function.signature())
+                                .font(FontId::monospace(18.0))
+                                .color(Color32::from_rgb(0, 120, 215)),
+                        );
+                        ui.label(
+                            RichText::new(function.description).font(FontId::proportional(16.0)),
+                        );
+                        ui.label(
+                            RichText::new(format!("Example: {}", function.example))
+                                .font(FontId::monospace(16.0)),
+                        );
+                        ui.separator();
+                    }
                 });
+            });
+
+        self.command_palette(ctx);
+
+        // About dialog
+        egui::Window::new("About Rust Spreadsheet")
+            .open(&mut self.about_dialog)
+            .order(egui::Order::Foreground)
+            .fixed_size(egui::vec2(600.0, 760.0))
+            .collapsible(false)
+            .show(ctx, |ui| {
                 ui.add_space(10.0);
-                ui.horizontal(|ui| {
-                    ui.label("\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t");
+                ui.label(RichText::new("Rust Spreadsheet Project").font(FontId::proportional(30.0)).color(Color32::from_rgb(0, 120, 215)));
+                ui.add_space(10.0);
+                ui.label(RichText::new("Version: 1.0.0").font(FontId::proportional(20.0)));
+                ui.add_space(10.0);
+                ui.label(RichText::new("Developed by:").font(FontId::proportional(20.0)));
+                ui.label(RichText::new("Rajat Soni: 2023CS10229").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Krish Bhimani: 2023CS10712").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Priyanshu Gaurav").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("IIT Delhi, Semester 4, COP290").font(FontId::proportional(18.0)));
+                ui.add_space(10.0);
+                ui.label(RichText::new("Description:").font(FontId::proportional(20.0)));
+                ui.label(RichText::new("This project is a spreadsheet application built purely using rust and the eframe/egui library. It supports various features such as saving, loading, plotting, and statistical analysis.").font(FontId::proportional(18.0)));
+                ui.add_space(10.0);
+                ui.label(RichText::new("Keyboard:").font(FontId::proportional(20.0)));
+                ui.label(RichText::new("Tab / Shift+Tab: Move right or left between cells, or between buttons and fields").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Arrow keys: Move between cells").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Shift+Arrow keys / Shift+Click / Drag: Select a block of cells").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Ctrl+Drag: Auto-fill from a cell to the cell it is released on").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Delete: Set the selected cells to 0").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Ctrl+C / Ctrl+X / Ctrl+V: Copy, cut or paste cells with other apps").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Ctrl+D / Ctrl+R: Fill the selected block down or right").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Enter: Edit the focused cell, or save the edit and move down").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Ctrl+Enter: Insert a row below, copying down the formulas of the row").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Typing on a focused cell: Start editing it").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Page Up / Page Down: Scroll by a screen of rows").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Ctrl+Home / Ctrl+End: Scroll to the start or end of the sheet").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Ctrl+G: Go to a cell").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Ctrl+K: Type a command").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Ctrl+P: Search the actions of the menus by name and run one").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Ctrl+F: Find and replace in values and formulas, or find cells by value").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Ctrl+`: Show the formulas of cells instead of their values, or back").font(FontId::proportional(18.0)));
+                ui.label(RichText::new("Escape: Cancel a recalculation running in the background").font(FontId::proportional(18.0)));
+                ui.add_space(10.0);
+                ui.label(RichText::new("Cell markers:").font(FontId::proportional(20.0)));
+                for decoration in utils::decoration::Decoration::ALL {
+                    let [r, g, b] = decoration.rgb();
+                    ui.label(RichText::new(format!("● {}", decoration.meaning())).font(FontId::proportional(18.0)).color(Color32::from_rgb(r, g, b)));
+                }
+                ui.add_space(10.0);
+                // ui.label(RichText::new("Contact:").font(FontId::proportional(20.0)));
+                // ui.label(RichText::new("Email: rustspreadsheet@iitd.ac.in").font(FontId::proportional(18.0)));
+            });
 
-                    if ui
-                        .add_sized(
-                            [100.0, 30.0],
-                            Button::new(RichText::new("Plot").font(FontId::proportional(20.0))),
-                        )
-                        .clicked()
-                    {
-                        let mut data: Vec<(f64, f64)> = vec![];
-                        let rows: Vec<&str> = self.plot_rows.split(':').collect();
-                        if rows.len() == 2 {
-                            if let (Ok(start), Ok(end)) =
-                                (rows[0].trim().parse::<i32>(), rows[1].trim().parse::<i32>())
-                            {
-                                if start <= end {
-                                    for i in start..=end {
-                                        data.push((
-                                            self.database[crate::cell_to_ind(
-                                                format!("{}{}", self.plot_x_axis, i).as_str(),
-                                                self.len_h,
-                                            )
-                                                as usize]
-                                                as f64,
-                                            self.database[crate::cell_to_ind(
-                                                format!("{}{}", self.plot_y_axis, i).as_str(),
-                                                self.len_h,
-                                            )
-                                                as usize]
-                                                as f64,
-                                        ));
-                                    }
+        // Error inspector
+        let mut error_jump = None;
+        let mut error_open = self.error_panel;
+        egui::Window::new("Error Inspector")
+            .open(&mut error_open)
+            .order(egui::Order::Foreground)
+            .default_pos(egui::pos2(760.0, 150.0))
+            .default_size(egui::vec2(420.0, 500.0))
+            .collapsible(true)
+            .resizable(true)
+            .show(ctx, |ui| {
+                let errors: Vec<i32> = (1..=self.engine.len_h * self.engine.len_v)
+                    .filter(|&i| self.engine.err()[i as usize])
+                    .collect();
+                ui.label(
+                    RichText::new(format!("{} cell(s) in error", errors.len()))
+                        .font(FontId::proportional(20.0)),
+                );
+                ui.add_space(10.0);
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("error_inspector_grid")
+                        .num_columns(3)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label(RichText::new("Cell").font(FontId::proportional(18.0)));
+                            ui.label(RichText::new("Error").font(FontId::proportional(18.0)));
+                            ui.label(RichText::new("Formula").font(FontId::proportional(18.0)));
+                            ui.end_row();
+                            for ind in errors {
+                                if ui
+                                    .link(
+                                        RichText::new(self.cell_name(ind))
+                                            .font(FontId::proportional(16.0)),
+                                    )
+                                    .clicked()
+                                {
+                                    error_jump = Some(ind);
                                 }
+                                ui.label(
+                                    RichText::new(
+                                        utils::graph::error_cause(
+                                            ind,
+                                            self.engine.database(),
+                                            self.engine.opers(),
+                                            self.engine.err(),
+                                            self.engine.len_h,
+                                        )
+                                        .unwrap_or_default(),
+                                    )
+                                    .font(FontId::proportional(16.0)),
+                                );
+                                ui.label(
+                                    RichText::new(format!("={}", self.shown_formula(ind)))
+                                        .font(FontId::proportional(16.0)),
+                                );
+                                ui.end_row();
                             }
-                        }
+                        });
+                });
+            });
+        self.error_panel = error_open;
 
-                        if self.plot_type == Plot::Scatter {
-                            utils::ui::plot::scatter_plot(&data, self.plot_save.as_str()).unwrap();
-                        } else {
-                            utils::ui::plot::line_plot(&data, self.plot_save.as_str()).unwrap();
-                        }
+        if let Some(ind) = error_jump {
+            self.jump_to_cell(ind);
+        }
+
+        // Graph stats dialog
+        let mut graph_open = self.graph_stats.is_some();
+        if let Some(stats) = &self.graph_stats {
+            egui::Window::new("Dependency Statistics")
+                .open(&mut graph_open)
+                .order(egui::Order::Foreground)
+                .fixed_size(egui::vec2(400.0, 300.0))
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    ui.add_space(10.0);
+                    for line in stats.report(self.engine.len_h) {
+                        ui.label(RichText::new(line).font(FontId::proportional(20.0)));
+                    }
+                });
+        }
+        if !graph_open {
+            self.graph_stats = None;
+        }
 
-                        #[cfg(target_os = "windows")]
-                        {
-                            // Windows: Use "start" to open the image
-                            let _ = std::process::Command::new("cmd")
-                                .args(["/C", "start", &self.plot_save])
-                                .spawn()
-                                .expect("Failed to open image")
-                                .wait();
+        // Watch break dialog
+        let mut watch_answer = None;
+        if let Some(lines) = &self.watch_break {
+            egui::Window::new("Watch Break")
+                .order(egui::Order::Foreground)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(
+                        RichText::new("Editing is paused because a watched cell changed.")
+                            .font(FontId::proportional(18.0)),
+                    );
+                    ui.add_space(10.0);
+                    for line in lines {
+                        ui.label(RichText::new(line).font(FontId::monospace(16.0)));
+                    }
+                    ui.add_space(10.0);
+                    ui.label(format!(
+                        "{} update(s) waiting to be applied",
+                        self.watch_pending.len()
+                    ));
+                    ui.horizontal(|ui| {
+                        if ui.button("Continue").clicked() {
+                            watch_answer = Some(true);
                         }
-                        #[cfg(target_os = "linux")]
+                        if ui
+                            .button(RichText::new("Discard waiting updates").color(Color32::RED))
+                            .clicked()
                         {
-                            // Linux: Use "xdg-open" to open the image
-                            std::process::Command::new("xdg-open")
-                                .arg(&self.plot_save)
-                                .spawn()
-                                .expect("Failed to open image");
+                            watch_answer = Some(false);
                         }
-
-                        self.plot_todo = true;
-                    };
+                    });
                 });
-            });
+        }
+        match watch_answer {
+            Some(true) => self.continue_watch(),
+            Some(false) => {
+                self.watch_pending.clear();
+                self.watch_break = None;
+            }
+            None => {}
+        }
 
-        if self.plot_todo {
-            self.plot_dialog = false;
-            self.plot_todo = false;
+        // Evaluation trace dialog
+        let mut trace_open = self.trace.is_some();
+        if let Some((cell, lines)) = &self.trace {
+            egui::Window::new(format!("Trace of {}", cell))
+                .open(&mut trace_open)
+                .order(egui::Order::Foreground)
+                .default_size(egui::vec2(500.0, 400.0))
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    egui::ScrollArea::both().show(ui, |ui| {
+                        for line in lines {
+                            let color = if line.contains("<- error") {
+                                Color32::RED
+                            } else {
+                                ui.visuals().text_color()
+                            };
+                            ui.label(
+                                RichText::new(line)
+                                    .font(FontId::monospace(16.0))
+                                    .color(color),
+                            );
+                        }
+                    });
+                });
+        }
+        if !trace_open {
+            self.trace = None;
         }
 
-        // PDF dialog
-        egui::Window::new("Save as PDF")
-            .open(&mut self.pdf_dialog)
+        // Find and replace dialog
+        let (mut find_todo, mut replace_todo) = (None, false);
+        egui::Window::new("Find and Replace")
+            .open(&mut self.find_dialog)
             .order(egui::Order::Foreground)
-            .fixed_size(egui::vec2(800.0, 500.0))
             .collapsible(false)
+            .resizable(false)
             .show(ctx, |ui| {
                 ui.add_space(10.0);
-
-                ui.horizontal(|ui| {
-                    ui.add_sized(
-                        [400.0, 30.0],
-                        egui::TextEdit::singleline(&mut self.pdf_path)
-                            .hint_text("Enter PDF path")
-                            .font(FontId::proportional(20.0)),
-                    );
-                    // ui.text_edit_singleline(&mut self.save_path);
-                    if ui
-                        .add_sized(
-                            [90.0, 30.0],
-                            Button::new(RichText::new("Browse").font(FontId::proportional(20.0))),
-                        )
-                        .clicked()
-                    {
-                        if let Some(path) = rfd::FileDialog::new()
-                            .add_filter("PDF Document", &["pdf"])
-                            .save_file()
-                        {
-                            self.pdf_path = path.display().to_string();
-                        }
-                    };
-                });
+                let query = ui.add_sized(
+                    [300.0, 30.0],
+                    egui::TextEdit::singleline(&mut self.find_query)
+                        .id(egui::Id::new("find_query"))
+                        .hint_text(if self.find_where {
+                            "Values such as >1000, or ERR"
+                        } else {
+                            "Find in values and formulas"
+                        })
+                        .font(FontId::proportional(20.0)),
+                );
+                if query.changed() {
+                    self.find_matches.clear();
+                }
+                if ui
+                    .checkbox(&mut self.find_where, "Match values")
+                    .on_hover_text("Find cells whose value meets a comparison, or ERR for errors")
+                    .changed()
+                {
+                    self.find_matches.clear();
+                }
+                if query.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    find_todo = Some(!ui.input(|i| i.modifiers.shift));
+                    query.request_focus();
+                }
+                ui.add_space(5.0);
+                ui.add_sized(
+                    [300.0, 30.0],
+                    egui::TextEdit::singleline(&mut self.find_replacement)
+                        .hint_text("Replace in formulas with")
+                        .font(FontId::proportional(20.0)),
+                );
                 ui.add_space(10.0);
-
                 ui.horizontal(|ui| {
-                    ui.label("\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t");
-
+                    if ui.button("Previous").clicked() {
+                        find_todo = Some(false);
+                    }
+                    if ui.button("Next").clicked() {
+                        find_todo = Some(true);
+                    }
+                    // Comparisons look at values, so there is nothing to replace
+                    let enabled = !self.find_query.trim().is_empty() && !self.find_where;
                     if ui
-                        .add_sized(
-                            [100.0, 30.0],
-                            Button::new(RichText::new("Save").font(FontId::proportional(20.0))),
-                        )
+                        .add_enabled(enabled, egui::Button::new("Replace All"))
                         .clicked()
                     {
-                        self.pdf_todo = true;
+                        replace_todo = true;
                     }
                 });
+                if !self.find_matches.is_empty() {
+                    let position = self
+                        .selected_cell
+                        .and_then(|c| self.find_matches.iter().position(|&m| m == c));
+                    ui.label(match position {
+                        Some(i) => format!("{} of {} matches", i + 1, self.find_matches.len()),
+                        None => format!("{} matches", self.find_matches.len()),
+                    });
+                }
             });
-
-        if self.pdf_todo {
-            self.pdf_dialog = false;
-            self.pdf_todo = false;
-            utils::ui::loadnsave::save_1d_as_pdf(
-                &self.database,
-                &self.err,
-                self.len_h,
-                self.len_v,
-                &self.pdf_path,
-            )
-            .unwrap();
-            Notification::new()
-                .summary("PDF Saved")
-                .body(format!("PDF saved to {}", self.pdf_path).as_str())
-                .show()
-                .unwrap();
+        if let Some(forward) = find_todo {
+            self.find_step(forward);
+        }
+        if replace_todo {
+            self.replace_all();
         }
 
-        // Describe dialog
-        egui::Window::new("Describe Data")
-            .open(&mut self.describe_dialog)
+        // Replace in formulas dialog
+        let (mut preview_todo, mut rewrite_todo) = (false, false);
+        egui::Window::new("Replace in Formulas")
+            .open(&mut self.rewrite_dialog)
             .order(egui::Order::Foreground)
-            .fixed_size(egui::vec2(400.0, 500.0))
             .collapsible(false)
+            .resizable(false)
             .show(ctx, |ui| {
                 ui.add_space(10.0);
-
                 ui.horizontal(|ui| {
-                    ui.label(RichText::new("Range:").font(FontId::proportional(20.0)));
-                    ui.add_sized(
-                        [250.0, 30.0],
-                        egui::TextEdit::singleline(&mut self.describe_range)
-                            .hint_text("Enter range (e.g., A1:B10)")
-                            .font(FontId::proportional(20.0)),
-                    );
+                    for (function, name) in [(false, "Column"), (true, "Function")] {
+                        if ui
+                            .radio_value(&mut self.rewrite_function, function, name)
+                            .changed()
+                        {
+                            self.rewrite_changes.clear();
+                        }
+                    }
+                });
+                ui.add_space(5.0);
+                let (from_hint, to_hint) = if self.rewrite_function {
+                    ("Eg. SUM", "Eg. AVG")
+                } else {
+                    ("Eg. B", "Eg. C")
+                };
+                ui.horizontal(|ui| {
+                    for (label, text, hint) in [
+                        ("Every", &mut self.rewrite_from, from_hint),
+                        ("becomes", &mut self.rewrite_to, to_hint),
+                    ] {
+                        ui.label(RichText::new(label).font(FontId::proportional(20.0)));
+                        let field = ui.add_sized(
+                            [120.0, 30.0],
+                            egui::TextEdit::singleline(text)
+                                .hint_text(hint)
+                                .font(FontId::proportional(20.0)),
+                        );
+                        if field.changed() {
+                            self.rewrite_changes.clear();
+                        }
+                    }
                 });
-
                 ui.add_space(10.0);
-                // let mut ans = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
                 ui.horizontal(|ui| {
-                    ui.label("\t\t\t\t\t\t\t\t\t");
-
+                    if ui.button("Preview").clicked() {
+                        preview_todo = true;
+                    }
                     if ui
-                        .add_sized(
-                            [100.0, 30.0],
-                            Button::new(RichText::new("Describe").font(FontId::proportional(20.0))),
-                        )
+                        .add_enabled(!self.rewrite_changes.is_empty(), egui::Button::new("Apply"))
                         .clicked()
                     {
-                        let range = self.describe_range.clone();
-                        let mut start = 0;
-                        let mut end = 0;
-                        if range.contains(':') {
-                            let parts: Vec<&str> = range.split(':').collect();
-                            start = crate::cell_to_ind(parts[0], self.len_h);
-                            end = crate::cell_to_ind(parts[1], self.len_h);
-                        }
-                        let n_cols = self.len_h;
-                        let mut y1 = start / n_cols;
-                        let mut y2 = end / n_cols;
-                        let mut x1 = start % (n_cols);
-                        if x1 == 0 {
-                            x1 = n_cols;
-                        }
-                        let mut x2 = end % (n_cols);
-                        if x2 == 0 {
-                            x2 = n_cols;
-                        }
-                        if x1 != n_cols {
-                            y1 += 1;
-                        }
-                        if x2 != n_cols {
-                            y2 += 1;
-                        }
-                        let mut data = Vec::new();
-                        for i in x1..x2 + 1 {
-                            for j in y1..y2 + 1 {
-                                data.push(self.database[(i + (j - 1) * n_cols) as usize]);
-                            }
-                        }
-                        self.describe_data = utils::ui::stats::calculate_stats(&data);
+                        rewrite_todo = true;
                     }
                 });
-                ui.add_space(10.0);
-
-                let labels = [
-                    "Count:", "Mean:", "Std Dev:", "Min:", "25%:", "50%:", "75%:",
-                    "Max:",
-                    // (count, mean, std, min, p25, p50, p75, max)
-                ];
-
-                for (i, item) in labels.iter().enumerate() {
-                    egui::Grid::new(format!("describe_grid_{}", i))
-                        .num_columns(2)
+                if !self.rewrite_changes.is_empty() {
+                    ui.add_space(5.0);
+                    ui.label(format!("{} formulas change:", self.rewrite_changes.len()));
+                    egui::ScrollArea::vertical()
+                        .max_height(200.0)
                         .show(ui, |ui| {
-                            egui::Frame::new()
-                                .stroke(egui::Stroke::new(1.0, Color32::GRAY))
-                                .show(ui, |ui| {
-                                    ui.add_sized(
-                                        [100.0, 35.0],
-                                        egui::Label::new(
-                                            RichText::new(item.to_string())
-                                                .font(FontId::proportional(20.0)),
-                                        ),
-                                    );
-                                });
-                            egui::Frame::new()
-                                .stroke(egui::Stroke::new(1.0, Color32::GRAY))
-                                .show(ui, |ui| {
-                                    ui.add_sized(
-                                        [200.0, 35.0],
-                                        egui::Label::new(
-                                            RichText::new(format!("{}", self.describe_data[i]))
-                                                .font(FontId::proportional(20.0)),
-                                        ),
-                                    );
-                                });
-                            ui.end_row();
+                            for change in &self.rewrite_changes {
+                                ui.label(change.text(self.engine.len_h));
+                            }
                         });
-                    ui.add_space(10.0);
                 }
             });
+        if preview_todo {
+            match self.rewrite_preview() {
+                Some(changes) if changes.is_empty() => {
+                    Notification::new()
+                        .summary("Not Found")
+                        .body("No formula uses it")
+                        .show()
+                        .unwrap();
+                }
+                Some(changes) => self.rewrite_changes = changes,
+                None => {
+                    let body = if self.rewrite_function {
+                        "Type the names of two functions, such as SUM and AVG"
+                    } else {
+                        "Type two columns, such as B and C"
+                    };
+                    Notification::new()
+                        .summary("Invalid Rewrite")
+                        .body(body)
+                        .show()
+                        .unwrap();
+                }
+            }
+        }
+        if rewrite_todo {
+            self.apply_rewrite();
+        }
 
-        // About dialog
-        egui::Window::new("About Rust Spreadsheet")
-            .open(&mut self.about_dialog)
+        // Paste special dialog
+        egui::Window::new("Paste Special")
+            .open(&mut self.paste_dialog)
             .order(egui::Order::Foreground)
-            .fixed_size(egui::vec2(600.0, 400.0))
             .collapsible(false)
+            .resizable(false)
             .show(ctx, |ui| {
+                use utils::clipboard::PasteMode;
                 ui.add_space(10.0);
-                ui.label(RichText::new("Rust Spreadsheet Project").font(FontId::proportional(30.0)).color(Color32::from_rgb(0, 120, 215)));
-                ui.add_space(10.0);
-                ui.label(RichText::new("Version: 1.0.0").font(FontId::proportional(20.0)));
-                ui.add_space(10.0);
-                ui.label(RichText::new("Developed by:").font(FontId::proportional(20.0)));
-                ui.label(RichText::new("Rajat Soni: 2023CS10229").font(FontId::proportional(18.0)));
-                ui.label(RichText::new("Krish Bhimani: 2023CS10712").font(FontId::proportional(18.0)));
-                ui.label(RichText::new("Priyanshu Gaurav").font(FontId::proportional(18.0)));
-                ui.label(RichText::new("IIT Delhi, Semester 4, COP290").font(FontId::proportional(18.0)));
+                ui.add_sized(
+                    [300.0, 30.0],
+                    egui::TextEdit::singleline(&mut self.paste_target)
+                        .hint_text("Top-left cell, eg. B2")
+                        .font(FontId::proportional(20.0)),
+                );
                 ui.add_space(10.0);
-                ui.label(RichText::new("Description:").font(FontId::proportional(20.0)));
-                ui.label(RichText::new("This project is a spreadsheet application built purely using rust and the eframe/egui library. It supports various features such as saving, loading, plotting, and statistical analysis.").font(FontId::proportional(18.0)));
+                for (mode, label, hint) in [
+                    (
+                        PasteMode::Values,
+                        "Values only",
+                        "Paste the copied values as constants",
+                    ),
+                    (
+                        PasteMode::Formulas,
+                        "Formulas only",
+                        "Paste the copied formulas, moving their references with the cell",
+                    ),
+                    (
+                        PasteMode::Add,
+                        "Add",
+                        "Add the copied values to the target cells",
+                    ),
+                    (
+                        PasteMode::Subtract,
+                        "Subtract",
+                        "Subtract the copied values from the target cells",
+                    ),
+                    (
+                        PasteMode::Formats,
+                        "Formats only",
+                        "Paste the copied styles and number formats, keeping the target values",
+                    ),
+                ] {
+                    ui.radio_value(
+                        &mut self.paste_mode,
+                        mode,
+                        RichText::new(label).font(FontId::proportional(18.0)),
+                    )
+                    .on_hover_text(hint);
+                }
+                ui.checkbox(
+                    &mut self.paste_transpose,
+                    RichText::new("Transpose").font(FontId::proportional(18.0)),
+                );
                 ui.add_space(10.0);
-                // ui.label(RichText::new("Contact:").font(FontId::proportional(20.0)));
-                // ui.label(RichText::new("Email: rustspreadsheet@iitd.ac.in").font(FontId::proportional(18.0)));
+                if ui
+                    .add_sized(
+                        [100.0, 30.0],
+                        Button::new(RichText::new("Paste").font(FontId::proportional(20.0))),
+                    )
+                    .clicked()
+                {
+                    self.paste_todo = true;
+                }
             });
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let scroll_delta = ctx.input(|i| i.raw_scroll_delta);
-            if scroll_delta.y > 0.0 && self.top_v > 1 {
-                self.top_v-=1;
-            }
 
-            if scroll_delta.x >0.0 && self.top_h > 1{
-                self.top_h-=1;
+        if self.paste_todo {
+            self.paste_todo = false;
+            self.paste_dialog = false;
+            let target = self.paste_target.trim().to_string();
+            self.paste_and_notify(&target, self.paste_mode, self.paste_transpose);
+        }
+
+        // Auto-fill options, shown after a fill so it can be switched between copy and series
+        if let Some((source, end)) = self.fill_last {
+            let mut open = true;
+            let mut mode = self.fill_mode;
+            egui::Window::new("Auto Fill Options")
+                .open(&mut open)
+                .order(egui::Order::Foreground)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.radio_value(
+                        &mut mode,
+                        utils::fill::FillMode::Series,
+                        RichText::new("Fill series").font(FontId::proportional(18.0)),
+                    );
+                    ui.radio_value(
+                        &mut mode,
+                        utils::fill::FillMode::Copy,
+                        RichText::new("Copy cells").font(FontId::proportional(18.0)),
+                    );
+                });
+            if mode != self.fill_mode {
+                self.apply_fill(source, end, mode);
             }
+            if !open {
+                self.fill_last = None;
+            }
+        }
+
+        if let Some(col) = self.fit_todo.take() {
+            self.fit_column(ctx, col);
+        }
+
+        // Keyboard shortcuts for the parts of the window that are far away in the Tab order
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::G)) {
+            self.cell_ref.1 = true;
+            self.cell_ref.2 = true;
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::P)) {
+            self.palette = Some((String::new(), 0));
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::K)) {
+            ctx.memory_mut(|m| m.request_focus(egui::Id::new("terminal")));
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Backtick)) {
+            self.show_formulas = !self.show_formulas;
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::F)) {
+            self.find_dialog = true;
+            ctx.memory_mut(|m| m.request_focus(egui::Id::new("find_query")));
+        }
 
-            if scroll_delta.x <0.0 && self.top_h <= self.len_h-10{
-                self.top_h+=1;
+        for (i, (_, key)) in MENUS.iter().enumerate() {
+            if ctx.input_mut(|input| input.consume_key(egui::Modifiers::ALT, *key)) {
+                self.menu_todo = Some(i);
             }
+        }
+        egui::TopBottomPanel::top("menu").show(ctx, |ui| self.menu_bar(ui));
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            // Scroll by as many lines as the wheel or touchpad moved, so long sheets scroll fast
+            self.scroll_accum += ctx.input(|i| i.raw_scroll_delta);
+            let lines = egui::vec2(
+                (self.scroll_accum.x / SCROLL_STEP).trunc(),
+                (self.scroll_accum.y / SCROLL_STEP).trunc(),
+            );
+            self.scroll_accum -= lines * SCROLL_STEP;
+            self.scroll_by(-lines.y as i32, -lines.x as i32);
 
-            if scroll_delta.y <0.0 && self.top_v <= self.len_v-10{
-                self.top_v+=1;
+            if !ctx.wants_keyboard_input() {
+                if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::PageDown)) {
+                    self.scroll_by(self.view.0, 0);
+                }
+                if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::PageUp)) {
+                    self.scroll_by(-self.view.0, 0);
+                }
+                if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Home)) {
+                    self.top_h = 1;
+                    self.top_v = 1;
+                }
+                if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::End)) {
+                    self.scroll_by(self.engine.len_v, self.engine.len_h);
+                }
             }
 
-            ui.add_space(10.0);
-            // Header
-            ui.horizontal(|ui| {
-                // ui.add_sized([120.0,100.0],egui::Button::image(egui::Image::new(egui::include_image!("assets/copy.png")).fit_to_exact_size(egui::Vec2 { x: 100.0, y: 80.0 })));
-                if ui
-                    .add_sized(
-                        [120.0, 100.0],
-                        egui::Button::image(
-                            egui::Image::new(egui::include_image!("assets/info.png"))
-                                .fit_to_exact_size(egui::Vec2 { x: 100.0, y: 80.0 }),
-                        ),
-                    )
-                    .clicked()
-                {
-                    self.about_dialog = true;
-                };
-                if ui
-                    .add_sized(
-                        [120.0, 100.0],
-                        egui::Button::image(
-                            egui::Image::new(egui::include_image!("assets/describe.png"))
-                                .fit_to_exact_size(egui::Vec2 { x: 100.0, y: 80.0 }),
-                        ),
-                    )
-                    .clicked()
-                {
-                    self.describe_dialog = true;
-                };
-                if ui
-                    .add_sized(
-                        [120.0, 100.0],
-                        egui::Button::image(
-                            egui::Image::new(egui::include_image!("assets/plot.png"))
-                                .fit_to_exact_size(egui::Vec2 { x: 100.0, y: 80.0 }),
-                        ),
-                    )
-                    .clicked()
-                {
-                    self.plot_dialog = true;
-                };
-                if ui
-                    .add_sized(
-                        [120.0, 100.0],
-                        egui::Button::image(
-                            egui::Image::new(egui::include_image!("assets/pdf.png"))
-                                .fit_to_exact_size(egui::Vec2 { x: 100.0, y: 80.0 }),
-                        ),
-                    )
-                    .clicked()
-                {
-                    self.pdf_dialog = true;
-                };
-                if ui
-                    .add_sized(
-                        [120.0, 100.0],
-                        egui::Button::image(
-                            egui::Image::new(egui::include_image!("assets/folder.png"))
-                                .fit_to_exact_size(egui::Vec2 { x: 100.0, y: 80.0 }),
-                        ),
-                    )
-                    .clicked()
-                {
-                    self.load_dialog = true;
-                };
-                if ui
-                    .add_sized(
-                        [120.0, 100.0],
-                        egui::Button::image(
-                            egui::Image::new(egui::include_image!("assets/save.png"))
-                                .fit_to_exact_size(egui::Vec2 { x: 100.0, y: 80.0 }),
-                        ),
-                    )
-                    .clicked()
-                {
-                    self.save_dialog = true;
-                };
-                ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
-                    let current_date = chrono::Local::now().format("%A, %B %d, %Y").to_string();
-                    let current_time = chrono::Local::now().format("%H:%M:%S").to_string();
+            if self.config.toolbar {
+                ui.add_space(10.0);
+                // Header
+                ui.horizontal(|ui| {
+                    // ui.add_sized([120.0,100.0],egui::Button::image(egui::Image::new(egui::include_image!("assets/copy.png")).fit_to_exact_size(egui::Vec2 { x: 100.0, y: 80.0 })));
+                    if tool_button(ui, egui::include_image!("assets/info.png"), "About").clicked() {
+                        self.about_dialog = true;
+                    };
+                    if tool_button(ui, egui::include_image!("assets/describe.png"), "Describe")
+                        .clicked()
+                    {
+                        self.describe_dialog = true;
+                    };
+                    if tool_button(ui, egui::include_image!("assets/plot.png"), "Plot").clicked() {
+                        self.plot_dialog = true;
+                    };
+                    if tool_button(ui, egui::include_image!("assets/pdf.png"), "PDF").clicked() {
+                        self.pdf_dialog = true;
+                    };
+                    if tool_button(ui, egui::include_image!("assets/folder.png"), "Load").clicked()
+                    {
+                        self.load_dialog = true;
+                    };
+                    if tool_button(ui, egui::include_image!("assets/save.png"), "Save").clicked() {
+                        self.save_dialog = true;
+                    };
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                        let (recalc, current_date, current_time) = self.clock();
+                        ui.add_sized(
+                            [310.0, 80.0],
+                            egui::Label::new(
+                                RichText::new(format!(
+                                    "Rust Spreadsheet Project\n{}\nDate: {}\nTime: {}",
+                                    recalc, current_date, current_time
+                                ))
+                                .font(FontId::proportional(20.0)),
+                            ),
+                        );
+                    });
+                });
+                ui.horizontal(|ui| {
+                    // ui.add_sized([120.0,4.0],egui::Label::new(RichText::new("Copy").font(FontId::proportional(15.0))));
                     ui.add_sized(
-                        [310.0, 80.0],
+                        [120.0, 4.0],
+                        egui::Label::new(RichText::new("About").font(FontId::proportional(15.0))),
+                    );
+                    ui.add_sized(
+                        [120.0, 4.0],
                         egui::Label::new(
-                            RichText::new(format!(
-                                "Rust Spreadsheet Project\n\nDate: {}\nTime: {}",
-                                current_date, current_time
-                            ))
-                            .font(FontId::proportional(20.0)),
+                            RichText::new("Describe").font(FontId::proportional(15.0)),
                         ),
                     );
+                    ui.add_sized(
+                        [120.0, 4.0],
+                        egui::Label::new(RichText::new("Plot").font(FontId::proportional(15.0))),
+                    );
+                    ui.add_sized(
+                        [120.0, 4.0],
+                        egui::Label::new(RichText::new("PDF").font(FontId::proportional(15.0))),
+                    );
+                    ui.add_sized(
+                        [120.0, 4.0],
+                        egui::Label::new(RichText::new("Load").font(FontId::proportional(15.0))),
+                    );
+                    ui.add_sized(
+                        [120.0, 4.0],
+                        egui::Label::new(RichText::new("Save").font(FontId::proportional(15.0))),
+                    );
                 });
-            });
-            ui.horizontal(|ui| {
-                // ui.add_sized([120.0,4.0],egui::Label::new(RichText::new("Copy").font(FontId::proportional(15.0))));
-                ui.add_sized(
-                    [120.0, 4.0],
-                    egui::Label::new(RichText::new("About").font(FontId::proportional(15.0))),
-                );
-                ui.add_sized(
-                    [120.0, 4.0],
-                    egui::Label::new(RichText::new("Describe").font(FontId::proportional(15.0))),
-                );
-                ui.add_sized(
-                    [120.0, 4.0],
-                    egui::Label::new(RichText::new("Plot").font(FontId::proportional(15.0))),
-                );
-                ui.add_sized(
-                    [120.0, 4.0],
-                    egui::Label::new(RichText::new("PDF").font(FontId::proportional(15.0))),
-                );
-                ui.add_sized(
-                    [120.0, 4.0],
-                    egui::Label::new(RichText::new("Load").font(FontId::proportional(15.0))),
-                );
-                ui.add_sized(
-                    [120.0, 4.0],
-                    egui::Label::new(RichText::new("Save").font(FontId::proportional(15.0))),
-                );
-            });
-
+                ui.add_space(10.0);
+                self.format_bar(ui);
+            }
             ui.add_space(10.0); // Add bottom margin
             ui.horizontal(|ui| {
                 if self.cell_ref.1 {
@@ -876,16 +5722,12 @@ impl eframe::App for Spreadsheet {
 
                     if self.cell_ref.2 {
                         cell.request_focus();
-                        self.cell_ref.2 = true;
+                        self.cell_ref.2 = false;
                     }
 
                     if cell.gained_focus() {
-                        if self.selected_cell.is_some() {
-                            self.cell_ref.0 = format!(
-                                "{}{}",
-                                utils::display::get_label(self.selected_cell.unwrap() % self.len_h),
-                                self.selected_cell.unwrap() / self.len_h + 1
-                            );
+                        if let Some(selected) = self.selected_cell {
+                            self.cell_ref.0 = self.cell_name(selected);
                         } else {
                             self.cell_ref.0 = String::new();
                         }
@@ -895,43 +5737,11 @@ impl eframe::App for Spreadsheet {
 
                     if cell.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
                         let temp = format!("scroll_to {}", self.cell_ref.0);
-                        let out = utils::input::input(&temp, self.len_h, self.len_v);
-                        let status = out[4].clone();
-                        if status == "ok" && out[1] == "SRL" {
-                            let t = crate::cell_to_ind(out[0].as_str(), self.len_h);
-                            let mut x1 = t % self.len_h;
-                            if x1 == 0 {
-                                x1 = self.len_h;
-                            }
-                            let y1 = t / self.len_h + ((x1 != self.len_h) as i32);
-
-                            if x1 < self.top_h
-                                || x1 >= self.top_h + 10
-                                || y1 < self.top_v
-                                || y1 >= self.top_v + 10
-                            {
-                                let mut shift_h = 0;
-                                let mut shift_v = 0;
-
-                                if x1 < self.top_h {
-                                    shift_h = x1 - self.top_h;
-                                } else if x1 >= self.top_h + 10 {
-                                    shift_h = x1 - (self.top_h + 9);
-                                }
-
-                                if y1 < self.top_v {
-                                    shift_v = y1 - self.top_v;
-                                } else if y1 >= self.top_v + 10 {
-                                    shift_v = y1 - (self.top_v + 9);
-                                }
-
-                                self.top_h += shift_h;
-                                self.top_v += shift_v;
-                            }
-                            self.selected_cell = Some(t);
-                            self.temp_txt.1 = true;
-                        }
-                        else{
+                        let out = utils::input::input(&temp, self.engine.len_h, self.engine.len_v);
+                        if let Ok(ParsedCommand::ScrollTo { cell }) = out {
+                            let t = crate::cell_to_ind(&cell, self.engine.len_h);
+                            self.jump_to_cell(t);
+                        } else {
                             Notification::new()
                                 .summary("Invalid Cell")
                                 .body("The cell reference is invalid. Please check your input.")
@@ -941,18 +5751,10 @@ impl eframe::App for Spreadsheet {
                         self.cell_ref.1 = false;
                     };
                 } else {
-                    if self.selected_cell.is_some() {
-                        self.cell_ref.0 = format!(
-                            "{}{}",
-                            utils::display::get_label(self.selected_cell.unwrap() % self.len_h),
-                            self.selected_cell.unwrap() / self.len_h + 1
-                        );
-                    } else if self.hovered_cell.is_some() {
-                        self.cell_ref.0 = format!(
-                            "{}{}",
-                            utils::display::get_label(self.hovered_cell.unwrap() % self.len_h),
-                            self.hovered_cell.unwrap() / self.len_h + 1
-                        );
+                    if let Some(selected) = self.selected_cell {
+                        self.cell_ref.0 = self.cell_name(selected);
+                    } else if let Some(hovered) = self.hovered_cell {
+                        self.cell_ref.0 = self.cell_name(hovered);
                     } else {
                         self.cell_ref.0 = String::new();
                     }
@@ -974,95 +5776,464 @@ impl eframe::App for Spreadsheet {
                         });
                 }
 
+                // The formula bar marks errors in the formula as it is typed
+                let error = self.edit_error();
+                let color = ui.visuals().text_color();
                 egui::Frame::new()
                     .stroke(egui::Stroke::new(1.0, Color32::GRAY))
                     .show(ui, |ui| {
-                        ui.add_sized(
-                            [950.0, 30.0],
-                            egui::Label::new(
-                                RichText::new(self.temp_txt.0.to_string())
-                                    .font(FontId::proportional(20.0)),
-                            ),
+                        let mut job = marked_text(
+                            &self.temp_txt.0,
+                            error.as_ref().map(|e| e.1.clone()),
+                            color,
                         );
+                        if let Some((message, _)) = &error {
+                            job.append(
+                                &format!("    {}", message),
+                                0.0,
+                                egui::TextFormat::simple(FontId::proportional(16.0), Color32::RED),
+                            );
+                        }
+                        ui.add_sized([780.0, 30.0], egui::Label::new(job));
+                    });
+
+                let mut chosen = None;
+                egui::ComboBox::from_id_salt("views")
+                    .selected_text(RichText::new("Views").font(FontId::proportional(20.0)))
+                    .width(160.0)
+                    .show_ui(ui, |ui| {
+                        if self.views.is_empty() {
+                            ui.label("Use save_view <name>");
+                        }
+                        for view in &self.views {
+                            if ui.selectable_label(false, &view.name).clicked() {
+                                chosen = Some(view.clone());
+                            }
+                        }
                     });
+                if let Some(view) = chosen {
+                    self.show_view(&view);
+                }
             });
 
             ui.add_space(10.0);
             // Main
 
-            egui::Grid::new("spreadsheet_grid").show(ui, |ui| {
-                // Header
-                egui::Frame::new().show(ui, |ui| {
-                    ui.add_sized(
-                        [70.0, 35.0],
-                        egui::Label::new(
-                            RichText::new(String::new()).font(FontId::proportional(20.0)),
-                        ),
+            // Only the cells in the viewport are built each frame. The scroll bars move the
+            // viewport, so any part of a large sheet is reached without building more widgets.
+            self.view = self
+                .config
+                .view
+                .unwrap_or_else(|| self.fit_view(ui.available_size(), ui.spacing().item_spacing));
+            let grid = ui.horizontal_top(|ui| {
+                let grid = egui::Grid::new("spreadsheet_grid").show(ui, |ui| {
+                    let cols = visible_lines(
+                        self.top_h,
+                        self.engine.len_h,
+                        &self.hidden_cols,
+                        self.view.1,
                     );
-                });
-                for col in 0..10 {
-                    egui::Frame::new()
-                        .stroke(egui::Stroke::new(1.0, Color32::GRAY))
-                        .show(ui, |ui| {
-                            ui.add_sized(
-                                [100.0, 35.0],
-                                egui::Label::new(
-                                    RichText::new(
-                                        utils::display::get_label(col + self.top_h).to_string(),
+                    let mut rows = visible_lines(
+                        self.top_v,
+                        self.engine.len_v,
+                        &self.hidden_rows,
+                        self.view.0,
+                    );
+                    if self.engine.ranges().header_row && !rows.contains(&1) && !self.hidden_rows.contains(&1) {
+                        // The header row stays in view while scrolling
+                        rows.pop();
+                        rows.insert(0, 1);
+                    }
+                    // (is_row, line, hide) requested from a header context menu
+                    let mut toggle: Option<(bool, i32, bool)> = None;
+                    let mut unhide_all: Option<bool> = None;
+                    let mut fit = None;
+                    let mut resize = None;
+                    let mut insert_below = None;
+                    let mut unlabel = None;
+                    let mut line_edit = None;
+
+                    // Column labels
+                    if !self.col_labels.is_empty() {
+                        ui.add_sized([70.0, 30.0], egui::Label::new(""));
+                        for &col in &cols {
+                            let text = self.col_labels.get(&col).cloned().unwrap_or_default();
+                            egui::Frame::new()
+                                .stroke(header_stroke(false))
+                                .show(ui, |ui| {
+                                    ui.add_sized(
+                                        [self.col_width(col), 30.0],
+                                        egui::Label::new(
+                                            RichText::new(&text)
+                                                .font(FontId::proportional(16.0))
+                                                .color(Color32::from_rgb(0, 120, 215)),
+                                        )
+                                        .truncate()
+                                        .sense(egui::Sense::click()),
                                     )
-                                    .font(FontId::proportional(20.0)),
-                                ),
-                            );
-                        });
-                }
+                                    .on_hover_text(&text)
+                                    .context_menu(|ui| {
+                                        if ui.button("Remove label").clicked() {
+                                            unlabel = Some(col);
+                                            ui.close_menu();
+                                        }
+                                    });
+                                });
+                        }
+                        ui.end_row();
+                    }
 
-                ui.end_row();
+                    // Header
+                    egui::Frame::new().show(ui, |ui| {
+                        ui.add_sized(
+                            [70.0, 35.0],
+                            egui::Label::new(
+                                RichText::new(String::new()).font(FontId::proportional(20.0)),
+                            ),
+                        );
+                    });
+                    for &col in &cols {
+                        let label = utils::cellref::col_header(col, self.config.r1c1);
+                        let marked = self.hidden_cols.contains(&(col - 1))
+                            || self.hidden_cols.contains(&(col + 1));
+                        egui::Frame::new()
+                            .stroke(header_stroke(marked))
+                            .show(ui, |ui| {
+                                let header = ui.add_sized(
+                                    [self.col_width(col), 35.0],
+                                    egui::Label::new(
+                                        RichText::new(label.to_string())
+                                            .font(FontId::proportional(20.0)),
+                                    )
+                                    .selectable(false)
+                                    .sense(egui::Sense::click()),
+                                );
+                                if header.double_clicked() {
+                                    fit = Some(col);
+                                }
+                                // Dragging the right edge of the header resizes the column
+                                let edge = egui::Rect::from_x_y_ranges(
+                                    header.rect.right() - 4.0..=header.rect.right() + 4.0,
+                                    header.rect.y_range(),
+                                );
+                                let handle = ui.interact(
+                                    edge,
+                                    ui.id().with(("resize", col)),
+                                    egui::Sense::drag(),
+                                );
+                                if handle.hovered() || handle.dragged() {
+                                    ui.ctx().set_cursor_icon(egui::CursorIcon::ResizeHorizontal);
+                                }
+                                if handle.dragged() && handle.drag_delta().x != 0.0 {
+                                    let width = self.col_width(col) + handle.drag_delta().x;
+                                    resize = Some((
+                                        col,
+                                        width.clamp(
+                                            *COL_WIDTH_RANGE.start(),
+                                            *COL_WIDTH_RANGE.end(),
+                                        ),
+                                    ));
+                                }
+                                header.context_menu(|ui| {
+                                    if ui.button("Fit column width").clicked() {
+                                        fit = Some(col);
+                                        ui.close_menu();
+                                    }
+                                    if ui.button(format!("Hide column {}", label)).clicked() {
+                                        toggle = Some((false, col, true));
+                                        ui.close_menu();
+                                    }
+                                    if ui
+                                        .button(format!("Insert column before {}", label))
+                                        .clicked()
+                                    {
+                                        line_edit = Some(utils::structure::LineEdit {
+                                            row: false,
+                                            line: col,
+                                            insert: true,
+                                            len: self.engine.len_h,
+                                        });
+                                        ui.close_menu();
+                                    }
+                                    if ui.button(format!("Delete column {}", label)).clicked() {
+                                        line_edit = Some(utils::structure::LineEdit {
+                                            row: false,
+                                            line: col,
+                                            insert: false,
+                                            len: self.engine.len_h,
+                                        });
+                                        ui.close_menu();
+                                    }
+                                    if !self.hidden_cols.is_empty()
+                                        && ui.button("Unhide all columns").clicked()
+                                    {
+                                        unhide_all = Some(false);
+                                        ui.close_menu();
+                                    }
+                                });
+                            });
+                    }
 
-                self.hovered_cell = None;
-                for row in 0..10 {
-                    // Number
-                    egui::Frame::new()
-                        .stroke(egui::Stroke::new(1.0, Color32::GRAY))
-                        .show(ui, |ui| {
-                            ui.add_sized(
-                                [70.0, 45.0],
-                                egui::Label::new(
-                                    RichText::new(format!("{}", row + self.top_v))
-                                        .font(FontId::proportional(20.0)),
-                                )
-                                .selectable(false),
-                            );
-                        });
+                    ui.end_row();
 
-                    for col in 0..10 {
-                        let data = if !(self.err
-                            [((self.top_v + row - 1) * self.len_h + col + self.top_h) as usize])
-                        {
-                            format!(
-                                "{}",
-                                self.database[((self.top_v + row - 1) * self.len_h
-                                    + col
-                                    + self.top_h)
-                                    as usize]
-                            )
-                        } else {
-                            "ERR".to_string()
-                        };
-                        let ind = (self.top_v + row - 1) * self.len_h + col + self.top_h;
+                    let hover_before = self.hovered_cell.take();
+                    for &row in &rows {
+                        // Number
+                        let marked = self.hidden_rows.contains(&(row - 1))
+                            || self.hidden_rows.contains(&(row + 1));
                         egui::Frame::new()
-                            .stroke(egui::Stroke::new(1.0, Color32::GRAY))
+                            .stroke(header_stroke(marked))
                             .show(ui, |ui| {
+                                ui.add_sized(
+                                    [70.0, 45.0],
+                                    egui::Label::new(
+                                        RichText::new(format!("{}", row))
+                                            .font(FontId::proportional(20.0)),
+                                    )
+                                    .selectable(false)
+                                    .sense(egui::Sense::click()),
+                                )
+                                .context_menu(|ui| {
+                                    if ui.button(format!("Hide row {}", row)).clicked() {
+                                        toggle = Some((true, row, true));
+                                        ui.close_menu();
+                                    }
+                                    if ui.button(format!("Insert row before {}", row)).clicked() {
+                                        line_edit = Some(utils::structure::LineEdit {
+                                            row: true,
+                                            line: row,
+                                            insert: true,
+                                            len: self.engine.len_v,
+                                        });
+                                        ui.close_menu();
+                                    }
+                                    if ui.button(format!("Delete row {}", row)).clicked() {
+                                        line_edit = Some(utils::structure::LineEdit {
+                                            row: true,
+                                            line: row,
+                                            insert: false,
+                                            len: self.engine.len_v,
+                                        });
+                                        ui.close_menu();
+                                    }
+                                    if !self.hidden_rows.is_empty()
+                                        && ui.button("Unhide all rows").clicked()
+                                    {
+                                        unhide_all = Some(true);
+                                        ui.close_menu();
+                                    }
+                                });
+                            });
+
+                        for &col in &cols {
+                            let ind = (row - 1) * self.engine.len_h + col;
+                            let (data, rule_color) = if let Some(text) = self.header_label(ind) {
+                                (text.clone(), None)
+                            } else if self.show_formulas {
+                                let op = &self.engine.opers()[ind as usize];
+                                (utils::display::formula_cell(op, self.engine.len_h, self.config.r1c1), None)
+                            } else if self.engine.opers()[ind as usize].opcpde.is_empty() {
+                                // Cells that were never assigned are blank
+                                (String::new(), None)
+                            } else if !self.engine.err()[ind as usize] {
+                                utils::rules::apply(
+                                    &self.display_rules,
+                                    ind,
+                                    self.engine.database()[ind as usize],
+                                    self.value_text(ind),
+                                    utils::date::cell_kind(
+                                        self.engine.opers(),
+                                        ind,
+                                        self.engine.len_h,
+                                    ) == utils::date::Kind::Number,
+                                    self.engine.len_h,
+                                )
+                            } else if self.engine.opers()[ind as usize].opcpde
+                                == utils::structure::REF_OP
+                            {
+                                (utils::structure::REF_TEXT.to_string(), None)
+                            } else {
+                                ("ERR".to_string(), None)
+                            };
+                            let stroke = if self.is_pointed(ind) || self.is_blocked(ind) {
+                                egui::Stroke::new(2.0, Color32::LIGHT_BLUE)
+                            } else {
+                                egui::Stroke::new(1.0, Color32::GRAY)
+                            };
+                            let style = self.styles.get(&ind).copied().unwrap_or_default();
+                            let fill =
+                                style.background.map_or(Color32::TRANSPARENT, |[r, g, b]| {
+                                    Color32::from_rgb(r, g, b)
+                                });
+                            egui::Frame::new().stroke(stroke).fill(fill).show(ui, |ui| {
                                 if self.selected_cell.is_none()
                                     || (self.selected_cell.unwrap() != ind)
                                 {
-                                    let frame = ui.add_sized(
-                                        [100.0, 45.0],
-                                        egui::Label::new(
-                                            RichText::new(data).font(FontId::proportional(20.0)),
-                                        ),
-                                    );
-                                    if frame.clicked() {
+                                    let frame = ui
+                                        .add_sized(
+                                            [self.col_width(col), 45.0],
+                                            egui::Label::new(if self.is_pending(ind) {
+                                                // Last consistent value, marked until the background recalculation ends
+                                                RichText::new(format!("{} …", data))
+                                                    .font(cell_font(style.bold))
+                                                    .italics()
+                                                    .color(Color32::GRAY)
+                                            } else if let Some([r, g, b]) =
+                                                rule_color.or(style.color)
+                                            {
+                                                RichText::new(data)
+                                                    .font(cell_font(style.bold))
+                                                    .color(Color32::from_rgb(r, g, b))
+                                            } else {
+                                                RichText::new(data).font(cell_font(style.bold))
+                                            })
+                                            .halign(match style.align {
+                                                Some(utils::style::Align::Left) => {
+                                                    egui::Align::LEFT
+                                                }
+                                                Some(utils::style::Align::Right) => {
+                                                    egui::Align::RIGHT
+                                                }
+                                                _ => egui::Align::Center,
+                                            })
+                                            .selectable(false)
+                                            .sense(egui::Sense::click_and_drag()),
+                                        )
+                                        .on_hover_ui(|ui| {
+                                            ui.label(
+                                                RichText::new(self.cell_tooltip(ind))
+                                                    .font(FontId::proportional(16.0)),
+                                            );
+                                        });
+                                    frame.widget_info(|| {
+                                        egui::WidgetInfo::labeled(
+                                            egui::WidgetType::Label,
+                                            true,
+                                            self.cell_label(ind),
+                                        )
+                                    });
+                                    // Markers for what the cell carries besides its value, from the
+                                    // top right corner inwards
+                                    let corner = frame.rect.right_top() + egui::vec2(-5.0, 5.0);
+                                    for (i, decoration) in self
+                                        .decoration_layers()
+                                        .decorations(ind, self.is_pending(ind))
+                                        .enumerate()
+                                    {
+                                        let [r, g, b] = decoration.rgb();
+                                        ui.painter().circle_filled(
+                                            corner - egui::vec2(8.0 * i as f32, 0.0),
+                                            3.0,
+                                            Color32::from_rgb(r, g, b),
+                                        );
+                                    }
+
+                                    // Cells can be focused with Tab, moved between with the arrow
+                                    // keys and edited with Enter
+                                    if self.focus_todo == Some(ind) {
+                                        frame.request_focus();
+                                        self.focus_todo = None;
+                                    }
+                                    if frame.has_focus() {
+                                        ui.memory_mut(|m| {
+                                            m.set_focus_lock_filter(
+                                                frame.id,
+                                                egui::EventFilter {
+                                                    tab: true,
+                                                    horizontal_arrows: true,
+                                                    vertical_arrows: true,
+                                                    ..Default::default()
+                                                },
+                                            )
+                                        });
+                                        self.move_focus_with_keys(ui, ind);
+                                        // Typing on a focused cell starts editing it
+                                        let typed: String = ui.input(|i| {
+                                            i.events
+                                                .iter()
+                                                .filter_map(|e| match e {
+                                                    egui::Event::Text(t) => Some(t.as_str()),
+                                                    _ => None,
+                                                })
+                                                .collect()
+                                        });
+                                        if !typed.trim().is_empty()
+                                            && !ui.input(|i| i.modifiers.command)
+                                        {
+                                            self.selected_cell = Some(ind);
+                                            self.temp_txt.1 = true;
+                                            self.typed = Some(typed);
+                                        }
+                                        // Focus moved with Tab starts a new block at the focused cell
+                                        if self.focus_todo.is_none()
+                                            && self.block.is_none_or(|b| b.1 != ind)
+                                        {
+                                            self.block = Some((ind, ind));
+                                        }
+                                        // Ctrl+D fills the block down and Ctrl+R fills it right
+                                        for (key, down) in
+                                            [(egui::Key::D, true), (egui::Key::R, false)]
+                                        {
+                                            if ui.input_mut(|i| {
+                                                i.consume_key(egui::Modifiers::COMMAND, key)
+                                            }) {
+                                                let (source, end) = utils::fill::fill_in(
+                                                    self.block_range(ind),
+                                                    down,
+                                                );
+                                                self.apply_fill(
+                                                    source,
+                                                    end,
+                                                    utils::fill::FillMode::Copy,
+                                                );
+                                            }
+                                        }
+                                        if ui.input_mut(|i| {
+                                            i.consume_key(egui::Modifiers::NONE, egui::Key::Delete)
+                                        }) {
+                                            self.clear_block(self.block_range(ind));
+                                        }
+                                        // Ctrl+Enter inserts a row below with the formulas copied down
+                                        if ui.input_mut(|i| {
+                                            i.consume_key(
+                                                egui::Modifiers::COMMAND,
+                                                egui::Key::Enter,
+                                            )
+                                        }) {
+                                            insert_below = Some(ind);
+                                        }
+                                        let events = ui.input(|i| i.events.clone());
+                                        for event in events {
+                                            match event {
+                                                egui::Event::Copy => {
+                                                    self.copy_block(ui.ctx(), ind, false)
+                                                }
+                                                egui::Event::Cut => {
+                                                    self.copy_block(ui.ctx(), ind, true)
+                                                }
+                                                egui::Event::Paste(text) => {
+                                                    self.paste_system(ind, &text)
+                                                }
+                                                _ => {}
+                                            }
+                                        }
+                                    }
+                                    // Shift with a click extends the block instead of editing
+                                    let shift = ui.input(|i| i.modifiers.shift_only());
+                                    if frame.clicked()
+                                        && self.pointing.is_none()
+                                        && shift
+                                        && let Some((anchor, _)) = self.block
+                                    {
+                                        self.block = Some((anchor, ind));
+                                        self.focus_todo = Some(ind);
+                                    // While pointing, the click has already put the cell into the formula
+                                    } else if frame.clicked()
+                                        && self.pointing.is_none()
+                                        && insert_below.is_none()
+                                    {
                                         self.selected_cell = Some(ind);
+                                        self.block = Some((ind, ind));
                                         // println!("{:?}",self.selected_cell);
 
                                         self.temp_txt.1 = true;
@@ -1071,12 +6242,71 @@ impl eframe::App for Spreadsheet {
                                     if frame.hovered() {
                                         self.hovered_cell = Some(ind);
                                     }
+
+                                    // Dragging from a cell selects a block, or with Ctrl held
+                                    // auto-fills up to the cell it is released on
+                                    if frame.drag_started() && self.pointing.is_none() {
+                                        if ui.input(|i| i.modifiers.command) {
+                                            self.fill_drag = Some((ind, ind));
+                                        } else {
+                                            self.select_drag = true;
+                                            self.block = Some((ind, ind));
+                                        }
+                                    }
+                                    if self.select_drag
+                                        && frame.contains_pointer()
+                                        && let Some((anchor, cursor)) = self.block
+                                        && cursor != ind
+                                    {
+                                        self.block = Some((anchor, ind));
+                                    }
+                                    if frame.drag_stopped() && self.select_drag {
+                                        // Keyboard shortcuts act on the block from its last cell
+                                        self.select_drag = false;
+                                        self.focus_todo = self.block.map(|b| b.1);
+                                    }
+
+                                    // Dragging while pointing extends the pointed reference into a range
+                                    if let Some((_, anchor, cursor)) = self.pointing
+                                        && cursor != ind
+                                        && frame.contains_pointer()
+                                        && ui.input(|i| i.pointer.primary_down())
+                                    {
+                                        self.point_at(anchor, ind);
+                                    }
+                                    if let Some((start, _)) = self.fill_drag
+                                        && frame.contains_pointer()
+                                    {
+                                        self.fill_drag = Some((start, ind));
+                                    }
+                                    if frame.drag_stopped()
+                                        && let Some((start, end)) = self.fill_drag.take()
+                                        && start != end
+                                    {
+                                        self.fill_from_drag(start, end);
+                                    }
                                 } else {
                                     let ind = self.selected_cell.unwrap();
 
+                                    let edit_id = egui::Id::new("cell_edit");
+                                    let pointed = ui.memory(|m| m.has_focus(edit_id))
+                                        && self.point_with_keys(ui, ind);
+
+                                    // Errors are marked on the text as it is being typed
+                                    let cell = crate::ind_to_cell(ind, self.engine.len_h);
+                                    let (len_h, len_v) = (self.engine.len_h, self.engine.len_v);
+                                    let color = ui.visuals().text_color();
+                                    let mut layouter = |ui: &egui::Ui, text: &str, _: f32| {
+                                        let mark =
+                                            utils::input::formula_error(&cell, text, len_h, len_v)
+                                                .map(|e| e.1);
+                                        ui.fonts(|f| f.layout_job(marked_text(text, mark, color)))
+                                    };
                                     let field = ui.add_sized(
-                                        [100.0, 45.0],
+                                        [self.col_width(col), 45.0],
                                         egui::TextEdit::singleline(&mut self.temp_txt.0)
+                                            .id(edit_id)
+                                            .layouter(&mut layouter)
                                             .font(FontId::proportional(20.0))
                                             .vertical_align(egui::Align::Center)
                                             .horizontal_align(egui::Align::Center),
@@ -1088,83 +6318,237 @@ impl eframe::App for Spreadsheet {
                                         self.temp_txt.1 = false;
                                     }
 
-                                    if field.gained_focus() {
-                                        self.temp_txt.0 = self.formula[ind as usize].to_string();
+                                    if pointed {
+                                        // Keep the text cursor after the pointed reference
+                                        if let Some(mut state) =
+                                            egui::TextEdit::load_state(ui.ctx(), edit_id)
+                                        {
+                                            let end = self.temp_txt.0.chars().count();
+                                            state.cursor.set_char_range(Some(
+                                                egui::text::CCursorRange::one(
+                                                    egui::text::CCursor::new(end),
+                                                ),
+                                            ));
+                                            state.store(ui.ctx(), edit_id);
+                                        }
+                                    }
+                                    if field.has_focus() && self.can_point() {
+                                        // Up and down arrows point at cells instead of moving focus
+                                        ui.memory_mut(|m| {
+                                            m.set_focus_lock_filter(
+                                                edit_id,
+                                                egui::EventFilter {
+                                                    horizontal_arrows: true,
+                                                    vertical_arrows: true,
+                                                    ..Default::default()
+                                                },
+                                            )
+                                        });
+                                    }
+
+                                    // Clicking another cell while a reference can be typed points at it
+                                    let clicked = hover_before.filter(|&c| {
+                                        c != ind && ui.input(|i| i.pointer.any_pressed())
+                                    });
+                                    if field.lost_focus()
+                                        && let Some(clicked) = clicked
+                                        && self.can_point()
+                                    {
+                                        self.point_at(clicked, clicked);
+                                        field.request_focus();
+                                    } else if field.gained_focus() && self.pointing.is_none() {
+                                        self.temp_txt.0 =
+                                            match (self.typed.take(), self.header_label(ind)) {
+                                                (Some(text), _) => text,
+                                                (None, Some(text)) => text.clone(),
+                                                (None, None) => self.shown_formula(ind),
+                                            };
                                     }
 
                                     if field.lost_focus() {
-                                        if self.temp_txt.0.starts_with('=') {
+                                        self.pointing = None;
+                                        // Editing from the keyboard returns focus to the grid:
+                                        // Enter moves down, Tab moves right and Shift moves back
+                                        let (shift, enter, tab, escape) = ui.input(|i| {
+                                            (
+                                                i.modifiers.shift,
+                                                i.key_pressed(egui::Key::Enter),
+                                                i.key_pressed(egui::Key::Tab),
+                                                i.key_pressed(egui::Key::Escape),
+                                            )
+                                        });
+                                        // Ctrl+Enter saves the edit and inserts a row below
+                                        if enter && ui.input(|i| i.modifiers.command) {
+                                            insert_below = Some(ind);
+                                        }
+                                        let step = if shift { -1 } else { 1 };
+                                        let next = if enter {
+                                            Some(self.neighbour(ind, 0, step))
+                                        } else if tab {
+                                            Some(self.neighbour(ind, step, 0))
+                                        } else if escape {
+                                            Some(ind)
+                                        } else {
+                                            None
+                                        };
+                                        if let Some(next) = next {
+                                            self.scroll_into_view(next);
+                                            self.focus_todo = Some(next);
+                                        }
+                                        let formula = self.temp_txt.0.starts_with('=');
+                                        if formula {
                                             self.temp_txt.0.remove(0);
                                         }
+                                        let text = self.temp_txt.0.trim().to_string();
 
                                         if self.temp_txt.0.is_empty() {
                                             self.temp_txt.0 = "0".to_string();
                                         }
-                                        let tmp_formuala = self.formula[ind as usize].clone();
-                                        self.formula[ind as usize] = self.temp_txt.0.clone();
                                         self.temp_txt.0 = format!(
                                             "{}{}={}",
-                                            utils::display::get_label(col + self.top_h),
-                                            row + self.top_v,
+                                            utils::display::get_label(col),
+                                            row,
                                             self.temp_txt.0
                                         );
 
                                         self.selected_cell = None;
-                                        let out = utils::input::input(
-                                            &self.temp_txt.0,
-                                            self.len_h,
-                                            self.len_v,
-                                        );
-                                        let status = out[4].clone();
-                                        // println!("{:?}", out);
-                                        if status == "ok" && out[1] != "SRL" {
-                                            let suc = crate::cell_update(
-                                                &out,
-                                                &mut self.database,
-                                                &mut self.sensi,
-                                                &mut self.opers,
-                                                self.len_h,
-                                                &mut self.indegree,
-                                                &mut self.err,
+                                        // Text typed into the header row may become a label
+                                        let command = if self.engine.ranges().header_row && row == 1 {
+                                            Some(self.temp_txt.0.clone())
+                                        } else {
+                                            self.check_type(&self.temp_txt.0)
+                                        };
+                                        if let Some(command) = command {
+                                            let out = utils::input::input(
+                                                &command,
+                                                self.engine.len_h,
+                                                self.engine.len_v,
                                             );
-                                            if suc == 0 {
-                                                Notification::new()
-                                                    .summary("Cycle Detected")
-                                                    .body("Cycle detected in the graph. Please check your formulas. The change has been reverted")
-                                                    .show()
-                                                    .unwrap();
-                                                self.formula[ind as usize] = tmp_formuala;
+                                            // println!("{:?}", out);
+                                            match out {
+                                                Ok(ParsedCommand::Assign(out)) => self.commit(out),
+                                                _ if self.engine.ranges().header_row && row == 1 && !formula => {
+                                                    // Text typed into the header row becomes a label,
+                                                    // set once the cell is assigned so it is not replaced
+                                                    if let Ok(out) =
+                                                        self.engine.parse_assignment(&format!(
+                                                            "{}1=0",
+                                                            utils::display::get_label(col)
+                                                        ))
+                                                    {
+                                                        self.finish_recalc();
+                                                        let suc = self.update_cell(&out);
+                                                        if suc.is_ok() {
+                                                            let mut ranges =
+                                                                self.engine.ranges().clone();
+                                                            ranges.labels.insert(ind, text);
+                                                            self.engine.set_ranges(ranges);
+                                                        }
+                                                        self.report_update(suc);
+                                                    }
+                                                }
+                                                out => {
+                                                    Notification::new()
+                                                        .summary(utils::error::status(
+                                                            out.map(|_| ()),
+                                                        ))
+                                                        .body(
+                                                            "Invalid formula. Please check your input.",
+                                                        )
+                                                        .show()
+                                                        .unwrap();
+                                                }
                                             }
-                                        }else{
-                                            Notification::new()
-                                                .summary(&status)
-                                                .body("Invalid formula. Please check your input.")
-                                                .show()
-                                                .unwrap();
-                                            self.formula[ind as usize] = tmp_formuala;
                                         }
                                         self.temp_txt.0 = String::new();
                                     }
                                 }
                             });
+                        }
+                        ui.end_row(); // called once per row
                     }
-                    ui.end_row(); // called once per row
-                }
+
+                    if let Some((is_row, line, hide)) = toggle {
+                        self.set_hidden(is_row, line, hide);
+                    }
+                    match unhide_all {
+                        Some(true) => self.hidden_rows.clear(),
+                        Some(false) => self.hidden_cols.clear(),
+                        None => {}
+                    }
+                    if fit.is_some() {
+                        self.fit_todo = fit;
+                    }
+                    if let Some((col, width)) = resize {
+                        self.set_col_width(col, width);
+                    }
+                    if let Some(ind) = insert_below {
+                        self.insert_row_below(ind);
+                    }
+                    if let Some(col) = unlabel {
+                        self.set_col_label(col, "");
+                    }
+                    if let Some(edit) = line_edit {
+                        self.edit_lines(edit);
+                    }
+                });
+
+                let max_top = max_top(self.engine.len_v, self.view.0);
+                ui.spacing_mut().slider_width = grid.response.rect.height();
+                ui.add(
+                    egui::Slider::new(&mut self.top_v, max_top..=1)
+                        .vertical()
+                        .show_value(false),
+                )
+                .on_hover_text(format!("Rows from {}", self.top_v))
+                .widget_info(|| {
+                    egui::WidgetInfo::labeled(egui::WidgetType::Slider, true, "Scroll rows")
+                });
+                grid.response.rect.width()
             });
+            let max_top = max_top(self.engine.len_h, self.view.1);
+            ui.spacing_mut().slider_width = grid.inner;
+            ui.add(egui::Slider::new(&mut self.top_h, 1..=max_top).show_value(false))
+                .on_hover_text(format!(
+                    "Columns from {}",
+                    utils::cellref::col_header(self.top_h, self.config.r1c1)
+                ))
+                .widget_info(|| {
+                    egui::WidgetInfo::labeled(egui::WidgetType::Slider, true, "Scroll columns")
+                });
 
             // Footer
             ui.add_space(10.0);
+            let suggestions = utils::functions::completions(&self.terminal);
+            let mut completion = None;
             ui.horizontal(|ui| {
                 ui.add(
                     egui::Image::new(egui::include_image!("assets/terminal.png"))
                         .fit_to_exact_size(egui::vec2(50.0, 30.0)),
                 );
                 let term = ui.add_sized(
-                    [700.0, 30.0],
+                    [640.0, 30.0],
                     egui::TextEdit::singleline(&mut self.terminal)
+                        .id(egui::Id::new("terminal"))
                         .hint_text("Enter command here")
                         .font(FontId::proportional(20.0)),
                 );
+                if term.has_focus() {
+                    // Tab completes a function name instead of moving focus
+                    let complete = !suggestions.is_empty();
+                    ui.memory_mut(|m| {
+                        m.set_focus_lock_filter(
+                            term.id,
+                            egui::EventFilter {
+                                tab: complete,
+                                ..Default::default()
+                            },
+                        )
+                    });
+                    if complete && ui.input(|i| i.key_pressed(egui::Key::Tab)) {
+                        completion = Some(suggestions[0]);
+                    }
+                }
                 let go = ui.add_sized(
                     [50.0, 30.0],
                     Button::new(RichText::new("GO").font(FontId::proportional(20.0))),
@@ -1173,150 +6557,165 @@ impl eframe::App for Spreadsheet {
                 if go.clicked()
                     || (term.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)))
                 {
-                    let mut cell = String::new();
-                    let mut formullaaaa = String::new();
-                    if self.terminal.contains('=') {
-                        let parts: Vec<&str> = self.terminal.splitn(2, '=').collect();
-                        if parts.len() == 2 {
-                            cell = parts[0].trim().to_string();
-                            formullaaaa = parts[1].trim().to_string();
-                        }
-                    }
-                    if !crate::utils::input::is_valid_cell(cell.as_str(), self.len_h, self.len_v) {
-                        Notification::new()
-                            .summary("Invalid Cell")
-                            .body("The cell reference is invalid. Please check your input.")
-                            .show()
-                            .unwrap();
-                    }else{
-                    let ind = crate::cell_to_ind(cell.as_str(), self.len_h);
-                    let tmp_formuala = self.formula[ind as usize].clone();
-                    self.formula[ind as usize] = formullaaaa;
-                    let out = utils::input::input(&self.terminal, self.len_h, self.len_v);
-                    let status = out[4].clone();
-                    println!("{:?}", out);
-                    if status == "ok" {
-                        if out[1] == "SRL" {
-                            let t = crate::cell_to_ind(out[0].as_str(), self.len_h);
-                            let mut x1 = t % self.len_h;
-                            if x1 == 0 {
-                                x1 = self.len_h;
+                    let command = self.terminal.trim().to_string();
+                    if !self.run_command(&command) {
+                        let mut cell = String::new();
+                        if self.terminal.contains('=') {
+                            let parts: Vec<&str> = self.terminal.splitn(2, '=').collect();
+                            if parts.len() == 2 {
+                                cell = parts[0].trim().to_string();
                             }
-                            let y1 = t / self.len_h + ((x1 != self.len_h) as i32);
-                            self.top_h = x1;
-                            self.top_v = y1;
-                        } else {
-                            let suc = crate::cell_update(
-                                &out,
-                                &mut self.database,
-                                &mut self.sensi,
-                                &mut self.opers,
-                                self.len_h,
-                                &mut self.indegree,
-                                &mut self.err,
-                            );
-                            if suc == 0 {
-                                Notification::new()
-                                    .summary("Cycle Detected")
-                                    .body("Cycle detected in the graph. Please check your formulas. The change has been reverted")
-                                    .show()
-                                    .unwrap();
-                                self.formula[ind as usize] = tmp_formuala;
+                        }
+                        if !crate::utils::input::is_valid_cell(
+                            cell.as_str(),
+                            self.engine.len_h,
+                            self.engine.len_v,
+                        ) {
+                            Notification::new()
+                                .summary("Invalid Cell")
+                                .body("The cell reference is invalid. Please check your input.")
+                                .show()
+                                .unwrap();
+                        } else if let Some(command) = self.check_type(&self.terminal) {
+                            let out =
+                                utils::input::input(&command, self.engine.len_h, self.engine.len_v);
+                            println!("{:?}", out);
+                            match out {
+                                Ok(ParsedCommand::ScrollTo { cell }) => {
+                                    let t = crate::cell_to_ind(&cell, self.engine.len_h);
+                                    let mut x1 = t % self.engine.len_h;
+                                    if x1 == 0 {
+                                        x1 = self.engine.len_h;
+                                    }
+                                    let y1 =
+                                        t / self.engine.len_h + ((x1 != self.engine.len_h) as i32);
+                                    self.top_h = x1;
+                                    self.top_v = y1;
+                                }
+                                Ok(ParsedCommand::Assign(out)) => self.commit(out),
+                                Err(e) => {
+                                    Notification::new()
+                                        .summary(e.status())
+                                        .body("Invalid formula. Please check your input.")
+                                        .show()
+                                        .unwrap();
+                                }
                             }
                         }
-                    }else{
-                        Notification::new()
-                            .summary(&status)
-                            .body("Invalid formula. Please check your input.")
-                            .show()
-                            .unwrap();
-                        self.formula[ind as usize] = tmp_formuala;
                     }
-                }
                     self.terminal = String::new();
                     term.request_focus();
                 };
-                if ui
-                    .add_sized(
-                        [50.0, 30.0],
-                        Button::new(RichText::new("<").font(FontId::proportional(20.0))),
-                    )
-                    .clicked()
-                {
-                    self.top_h = crate::max(self.top_h - 10, 1);
+                if scroll_button(ui, "<", "Scroll left").clicked() {
+                    self.scroll_by(0, -self.view.1);
                 };
-                if ui
-                    .add_sized(
-                        [50.0, 30.0],
-                        Button::new(RichText::new("v").font(FontId::proportional(20.0))),
-                    )
-                    .clicked()
-                {
-                    self.top_v = min(self.top_v + 10, self.len_v - 9);
+                if scroll_button(ui, "v", "Scroll down").clicked() {
+                    self.scroll_by(self.view.0, 0);
                 };
 
                 let curr_time = chrono::Local::now().timestamp();
-                let time = (curr_time - self.initialized_time) as i32;
-                let hours = time / 3600;
-                let minutes = (time % 3600) / 60;
-                let seconds = time % 60;
-                let formatted_time = format!("{:02}:{:02}:{:02}", hours, minutes, seconds);
+                let formatted_time = utils::config::format_elapsed(
+                    curr_time - self.initialized_time,
+                    &self.config.elapsed_format,
+                );
 
                 ui.add_sized(
                     [120.0, 30.0],
-                    egui::Label::new(RichText::new(formatted_time).font(FontId::proportional(20.0))),
+                    egui::Label::new(
+                        RichText::new(formatted_time).font(FontId::proportional(20.0)),
+                    ),
                 );
 
-                if ui
-                    .add_sized(
-                        [50.0, 30.0],
-                        Button::new(RichText::new("^").font(FontId::proportional(20.0))),
-                    )
-                    .clicked()
-                {
-                    self.top_v = crate::max(self.top_v - 10, 1);
+                if scroll_button(ui, "^", "Scroll up").clicked() {
+                    self.scroll_by(-self.view.0, 0);
+                };
+                if scroll_button(ui, ">", "Scroll right").clicked() {
+                    self.scroll_by(0, self.view.1);
                 };
                 if ui
                     .add_sized(
-                        [50.0, 30.0],
-                        Button::new(RichText::new(">").font(FontId::proportional(20.0))),
+                        [60.0, 30.0],
+                        Button::new(RichText::new("ERR").font(FontId::proportional(20.0))),
                     )
+                    .on_hover_text("Open the error inspector")
                     .clicked()
                 {
-                    self.top_h = min(self.top_h + 10, self.len_h - 9);
+                    self.error_panel = !self.error_panel;
                 };
             });
+            if !suggestions.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.add_space(55.0);
+                    for function in &suggestions {
+                        if ui
+                            .button(
+                                RichText::new(function.signature()).font(FontId::monospace(14.0)),
+                            )
+                            .on_hover_text(format!(
+                                "{}\nExample: {}",
+                                function.description, function.example
+                            ))
+                            .clicked()
+                        {
+                            completion = Some(*function);
+                        }
+                    }
+                });
+            }
+            if let Some(function) = completion {
+                self.terminal = utils::functions::complete(&self.terminal, function);
+                let id = egui::Id::new("terminal");
+                if let Some(mut state) = egui::TextEdit::load_state(ui.ctx(), id) {
+                    let end = self.terminal.chars().count();
+                    state
+                        .cursor
+                        .set_char_range(Some(egui::text::CCursorRange::one(
+                            egui::text::CCursor::new(end),
+                        )));
+                    state.store(ui.ctx(), id);
+                }
+                ui.memory_mut(|m| m.request_focus(id));
+            }
         });
     }
 }
 
+/// Size of the window at UI scale 1.
+pub const WINDOW_SIZE: [f32; 2] = [1200.0, 800.0];
+
 /// Runs the graphical user interface for the spreadsheet.
 ///
 /// # Arguments
 ///
 /// * `len_h` - Width of the spreadsheet (number of columns)
 /// * `len_v` - Height of the spreadsheet (number of rows)
+/// * `limits` - `safe_mode` and `limit` commands applied to every workbook
 ///
 /// # Returns
 ///
 /// Result from the eframe application run
 ///
-pub fn ui(len_h: i32, len_v: i32) -> eframe::Result {
-    let database = vec![0; (len_h * len_v + 1) as usize];
-    let err = vec![false; (len_h * len_v + 1) as usize];
-    let opers = vec![
-        crate::Ops {
-            opcpde: String::new(),
-            cell1: -1,
-            cell2: -1
-        };
-        (len_h * len_v + 1) as usize
-    ];
-    let indegree = vec![0; (len_h * len_v + 1) as usize];
-    let sensi = vec![Vec::<i32>::new(); (len_h * len_v + 1) as usize];
+pub fn ui(len_h: i32, len_v: i32, limits: Vec<String>) -> eframe::Result {
+    run(utils::ui::session::Session::new(len_h, len_v, limits))
+}
+
+/// Starts the GUI with a saved workbook open, sized like the workbook.
+///
+/// # Arguments
+/// * `path` - Path of the .rsk or .rskt file
+/// * `limits` - `safe_mode` and `limit` commands applied to every workbook
+pub fn open(path: &str, limits: Vec<String>) -> eframe::Result {
+    run(utils::ui::session::Session::open(path, 10, 10, limits))
+}
+
+/// Runs the GUI window until it is closed.
+///
+/// # Arguments
+/// * `session` - The workbooks to show
+fn run(session: utils::ui::session::Session) -> eframe::Result {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1200.0, 800.0])
+            .with_inner_size(WINDOW_SIZE)
             .with_resizable(false)
             .with_maximize_button(false),
 
@@ -1327,9 +6726,8 @@ pub fn ui(len_h: i32, len_v: i32) -> eframe::Result {
         options,
         Box::new(|cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Ok(Box::new(utils::ui::gui::Spreadsheet::new(
-                len_h, len_v, database, err, opers, indegree, sensi,
-            )))
+            install_fonts(&cc.egui_ctx);
+            Ok(Box::new(session))
         }),
     )
 }
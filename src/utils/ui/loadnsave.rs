@@ -3,35 +3,54 @@
 //! This module provides functions to save and load spreadsheet data in different formats:
 //! - Native format (.rsk) for preserving all spreadsheet state using JSON serialization
 //! - CSV export for compatibility with other spreadsheet applications
-//! - PDF export for creating printable documents from spreadsheet data
+//! - PDF export for creating printable documents from spreadsheet data, which can run on a
+//!   worker thread that reports its progress and can be cancelled
+//! - Report export combining the grid, charts and statistics into one PDF
 //!
 //! The module handles serialization and deserialization of the spreadsheet state and
 //! creation of formatted output files.
 
+use crate::utils::numfmt::NumberFormat;
 use crate::utils::ui;
-use csv::Writer;
+use csv::WriterBuilder;
 use genpdf::{Document, Element, elements};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs::File;
 use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::JoinHandle;
 
 /// Saves spreadsheet data to a file in the native format (.rsk).
 ///
 /// This function serializes the entire spreadsheet state to JSON and writes it to the specified path.
 /// The native format preserves all application state including formulas, cell relationships,
-/// and UI settings.
+/// and UI settings. A file already at the path is kept as a backup first (see `utils::backup`).
 ///
 /// # Arguments
 /// * `data` - Mutable reference to the spreadsheet to be saved
 /// * `path` - Path where the file will be saved
-pub fn save_to_file(data: &mut ui::gui::Spreadsheet, path: &str) {
-    let json_data = serde_json::to_string(data).expect("Failed to serialize data");
+/// * `backups` - Number of backups of the file to keep
+///
+/// # Returns
+/// `Ok(())` if the operation was successful, or an error otherwise. Nothing is written if the
+/// backup cannot be made.
+pub fn save_to_file(
+    data: &mut ui::gui::Spreadsheet,
+    path: &str,
+    backups: u32,
+) -> Result<(), Box<dyn Error>> {
+    let json_data = serde_json::to_string(data)?;
+    crate::utils::backup::rotate(path, backups)
+        .map_err(|e| format!("could not keep a backup: {}", e))?;
 
-    let mut file = File::create(path).expect("Failed to create file");
-    file.write_all(json_data.as_bytes())
-        .expect("Failed to write to file");
+    let mut file = File::create(path)?;
+    file.write_all(json_data.as_bytes())?;
 
     println!("Data saved successfully to {}", path);
+    tracing::info!(path, bytes = json_data.len(), "workbook saved");
+    Ok(())
 }
 
 /// Reads spreadsheet data from a file in the native format (.rsk).
@@ -43,123 +62,461 @@ pub fn save_to_file(data: &mut ui::gui::Spreadsheet, path: &str) {
 /// * `path` - Path to the file to be read
 ///
 /// # Returns
-/// A new Spreadsheet instance with the loaded data
-pub fn read_from_file(path: &str) -> ui::gui::Spreadsheet {
-    let file_content = std::fs::read_to_string(path).expect("Failed to read file");
-    let spreadsheet: ui::gui::Spreadsheet =
-        serde_json::from_str(&file_content).expect("Failed to deserialize data");
+/// A new Spreadsheet instance with the loaded data, or an error if the file cannot be read
+/// or is not a workbook
+pub fn read_from_file(path: &str) -> Result<ui::gui::Spreadsheet, Box<dyn Error>> {
+    let file_content = std::fs::read_to_string(path)?;
+    let mut spreadsheet: ui::gui::Spreadsheet = serde_json::from_str(&file_content)?;
+    // Workbooks saved before the engine read the header labels were evaluated without them
+    spreadsheet.recalculate_ranges();
 
     println!("Data loaded successfully from {}", path);
-    spreadsheet
+    tracing::info!(path, bytes = file_content.len(), "workbook loaded");
+    Ok(spreadsheet)
+}
+
+/// Saves the formulas of a spreadsheet in the plain-text format (.rskt).
+///
+/// See `utils::textformat` for a description of the format.
+///
+/// # Arguments
+/// * `opers` - Operations of each cell
+/// * `len_h` - Number of columns in the spreadsheet
+/// * `len_v` - Number of rows in the spreadsheet
+/// * `col_types` - Declared type of each column
+/// * `number_formats` - Number format of each formatted cell
+/// * `path` - Path where the file will be saved
+///
+/// # Returns
+/// `Ok(())` if the operation was successful, or an error otherwise
+pub fn save_as_text(
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    len_h: i32,
+    len_v: i32,
+    col_types: &BTreeMap<i32, crate::utils::coltype::ColType>,
+    number_formats: &BTreeMap<i32, crate::utils::numfmt::NumberFormat>,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    std::fs::write(
+        path,
+        crate::utils::textformat::to_text(opers, len_h, len_v, col_types, number_formats),
+    )?;
+    println!("Data saved successfully to {}", path);
+    tracing::info!(path, "formulas saved as text");
+    Ok(())
+}
+
+/// Reads a spreadsheet saved in the plain-text format (.rskt).
+///
+/// # Arguments
+/// * `path` - Path to the file to be read
+///
+/// # Returns
+/// A new Spreadsheet instance with the formulas from the file evaluated, its column types
+/// declared and its number formats set
+pub fn read_from_text_file(path: &str) -> Result<ui::gui::Spreadsheet, Box<dyn Error>> {
+    let text = std::fs::read_to_string(path)?;
+    let (sheet, col_types, number_formats) = crate::utils::textformat::from_text(&text)?;
+    println!("Data loaded successfully from {}", path);
+    tracing::info!(path, "formulas loaded from text");
+    Ok(ui::gui::Spreadsheet::from_sheet(sheet)
+        .with_col_types(col_types)
+        .with_number_formats(number_formats))
+}
+
+/// Lists the rows or columns included in an export.
+///
+/// # Arguments
+/// * `len` - Number of rows or columns in the spreadsheet
+/// * `hidden` - Rows or columns to leave out
+///
+/// # Returns
+/// The remaining rows or columns in order
+fn shown_lines(len: i32, hidden: &[i32]) -> Vec<i32> {
+    (1..=len).filter(|l| !hidden.contains(l)).collect()
+}
+
+/// Lists the custom labels of the exported columns.
+///
+/// # Arguments
+/// * `cols` - The exported columns in order
+/// * `labels` - Custom column labels
+///
+/// # Returns
+/// One label per column, empty for columns without a label, or `None` if no exported
+/// column has a label.
+fn label_row(cols: &[i32], labels: &BTreeMap<i32, String>) -> Option<Vec<String>> {
+    if !cols.iter().any(|c| labels.contains_key(c)) {
+        return None;
+    }
+    Some(
+        cols.iter()
+            .map(|c| labels.get(c).cloned().unwrap_or_default())
+            .collect(),
+    )
+}
+
+/// Gives the text exported for a cell.
+///
+/// # Arguments
+/// * `data` - Slice containing cell values
+/// * `err` - Slice indicating which cells have errors
+/// * `opers` - Operations of each cell
+/// * `len_h` - Number of columns in the spreadsheet
+/// * `formats` - Number formats of the formatted cells
+/// * `index` - Index of the cell
+///
+/// # Returns
+/// "ERR" for a cell with an error, an empty string for a cell that was never assigned,
+/// otherwise its value in its number format, with dates and times in ISO format
+fn cell_text(
+    data: &[i32],
+    err: &[bool],
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    len_h: i32,
+    formats: &BTreeMap<i32, NumberFormat>,
+    index: usize,
+) -> String {
+    if opers[index].opcpde == crate::utils::structure::REF_OP {
+        crate::utils::structure::REF_TEXT.to_string()
+    } else if err[index] {
+        "ERR".to_string()
+    } else if opers[index].opcpde.is_empty() {
+        String::new()
+    } else {
+        crate::utils::numfmt::value_text(formats, data, opers, index as i32, len_h)
+    }
 }
 
 /// Exports spreadsheet data to a CSV file.
 ///
 /// This function creates a CSV file containing the visible values from the spreadsheet.
-/// Cells with errors are marked with "ERR".
+/// Cells with errors are marked with "ERR" and empty cells are left as empty fields. If any exported column has a custom label,
+/// the file starts with a row of labels.
 ///
 /// # Arguments
 /// * `data` - Slice containing cell values
 /// * `err` - Slice indicating which cells have errors
+/// * `opers` - Operations of each cell, used to tell empty cells from zeros
 /// * `len_h` - Number of columns in the spreadsheet
 /// * `len_v` - Number of rows in the spreadsheet
+/// * `hidden` - Rows and columns to leave out of the export
+/// * `labels` - Custom column labels
+/// * `formats` - Number formats of the formatted cells, used to write their values
+/// * `delimiter` - Byte separating the values of a row
 /// * `filename` - Path where the CSV file will be saved
 ///
 /// # Returns
 /// `Ok(())` if the operation was successful, or an error otherwise
+#[allow(clippy::too_many_arguments)]
 pub fn save_1d_as_csv(
     data: &[i32],
     err: &[bool],
+    opers: &crate::utils::cells::Cells<crate::Ops>,
     len_h: i32,
     len_v: i32,
+    hidden: (&[i32], &[i32]),
+    labels: &BTreeMap<i32, String>,
+    formats: &BTreeMap<i32, NumberFormat>,
+    delimiter: u8,
     filename: &str,
 ) -> Result<(), Box<dyn Error>> {
-    let mut wtr = Writer::from_path(filename)?;
+    let mut wtr = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_path(filename)?;
+    let cols = shown_lines(len_h, hidden.1);
+    if let Some(row) = label_row(&cols, labels) {
+        wtr.write_record(row)?;
+    }
 
-    for j in 1..=len_v {
-        let mut ans = vec![String::new(); len_h as usize];
-        for i in 1..=len_h {
+    for j in shown_lines(len_v, hidden.0) {
+        let mut ans = Vec::with_capacity(cols.len());
+        for &i in &cols {
             let index: usize = ((j - 1) * len_h + i) as usize;
-            if err[index] {
-                ans[(i - 1) as usize] = "ERR".to_string();
-            } else {
-                ans[(i - 1) as usize] = data[index].to_string();
-            }
+            ans.push(cell_text(data, err, opers, len_h, formats, index));
         }
         wtr.write_record(ans)?;
     }
 
     wtr.flush()?;
+    tracing::info!(path = filename, "values exported to CSV");
     Ok(())
 }
 
-/// Exports spreadsheet data to a PDF file.
+/// Exports results of the Describe dialog to a CSV file.
 ///
-/// This function creates a formatted PDF document representing the spreadsheet content.
-/// The PDF includes proper pagination for large spreadsheets, with each page showing up to
-/// 10x10 cells. Cells with errors are marked with "ERR".
+/// The file has a header row followed by one row per analysis, giving the range the
+/// statistics were computed over and the statistics in the order of `stats::STAT_NAMES`.
 ///
 /// # Arguments
-/// * `data` - Slice containing cell values
-/// * `err` - Slice indicating which cells have errors
-/// * `len_h` - Number of columns in the spreadsheet
-/// * `len_v` - Number of rows in the spreadsheet
-/// * `filename` - Path where the PDF file will be saved
+/// * `analyses` - Pairs of a range such as "A1:B10" and the statistics computed over it
+/// * `delimiter` - Byte separating the values of a row
+/// * `filename` - Path where the CSV file will be saved
 ///
 /// # Returns
 /// `Ok(())` if the operation was successful, or an error otherwise
-pub fn save_1d_as_pdf(
-    data: &[i32],
-    err: &[bool],
-    len_h: i32,
-    len_v: i32,
+pub fn save_stats_as_csv(
+    analyses: &[(String, [f64; 8])],
+    delimiter: u8,
     filename: &str,
 ) -> Result<(), Box<dyn Error>> {
+    let mut wtr = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_path(filename)?;
+    let mut header = vec!["Range"];
+    header.extend(ui::stats::STAT_NAMES);
+    wtr.write_record(header)?;
+    for (range, stats) in analyses {
+        let mut row = vec![range.clone()];
+        row.extend(stats.iter().map(|v| v.to_string()));
+        wtr.write_record(row)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Progress of a PDF export, shared with the thread running it.
+///
+/// # Fields
+/// * `pages` - Number of pages rendered so far
+/// * `total` - Number of pages the export has, or 0 while they are being laid out
+/// * `cancelled` - Whether the export should stop at the next page
+#[derive(Debug, Default)]
+pub struct Progress {
+    pages: AtomicUsize,
+    total: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    /// Gives the number of pages rendered so far and the number of pages of the export.
+    pub fn pages(&self) -> (usize, usize) {
+        let total = self.total.load(Ordering::Relaxed);
+        (self.pages.load(Ordering::Relaxed).min(total), total)
+    }
+
+    /// Asks the export to stop. The file is removed once it has stopped.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Checks whether the export was asked to stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Lays out the pages of a document with the margins of the exports, counting them as they
+/// are rendered and stopping the rendering once the export is cancelled.
+struct Decorator {
+    inner: genpdf::SimplePageDecorator,
+    progress: Arc<Progress>,
+}
+
+impl genpdf::PageDecorator for Decorator {
+    fn decorate_page<'a>(
+        &mut self,
+        context: &genpdf::Context,
+        area: genpdf::render::Area<'a>,
+        style: genpdf::style::Style,
+    ) -> Result<genpdf::render::Area<'a>, genpdf::error::Error> {
+        if self.progress.is_cancelled() {
+            return Err(genpdf::error::Error::new(
+                "Export cancelled",
+                genpdf::error::ErrorKind::Internal,
+            ));
+        }
+        self.progress.pages.fetch_add(1, Ordering::Relaxed);
+        self.inner.decorate_page(context, area, style)
+    }
+}
+
+/// A PDF export running on a worker thread.
+///
+/// The thread works on its own copy of the grid, so the spreadsheet can be edited while the
+/// export runs.
+#[derive(Debug)]
+pub struct PdfExport {
+    handle: JoinHandle<Result<(), String>>,
+    progress: Arc<Progress>,
+    path: String,
+}
+
+impl PdfExport {
+    /// Starts an export on a worker thread.
+    /// # Arguments
+    /// * `path` - Path where the PDF file will be saved
+    /// * `export` - Writes the PDF to the path, reporting to the progress it is given
+    pub fn start(
+        path: String,
+        export: impl FnOnce(&str, Arc<Progress>) -> Result<(), Box<dyn Error>> + Send + 'static,
+    ) -> Self {
+        let progress = Arc::new(Progress::default());
+        let shared = Arc::clone(&progress);
+        let target = path.clone();
+        let handle = std::thread::spawn(move || export(&target, shared).map_err(|e| e.to_string()));
+        Self {
+            handle,
+            progress,
+            path,
+        }
+    }
+
+    /// Gives the path where the PDF file is being saved.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Gives the progress of the export, which is also used to cancel it.
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    /// Checks whether the export is finished, without waiting.
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits for the export to finish.
+    /// # Returns
+    /// `Ok(())` if the PDF was saved, or a message telling why it was not.
+    pub fn finish(self) -> Result<(), String> {
+        self.handle
+            .join()
+            .unwrap_or_else(|_| Err(String::from("The export stopped unexpectedly")))
+    }
+}
+
+/// Creates an empty landscape PDF document with the font and margins of the exports.
+///
+/// # Arguments
+/// * `title` - Title stored in the document
+/// * `progress` - Progress the rendered pages are counted in, if any
+fn new_document(title: &str, progress: Option<Arc<Progress>>) -> Result<Document, Box<dyn Error>> {
     // Load font
     // println!("{:?}", std::fs::canonicalize("./src/utils/ui/assets/ARIAL.ttf"));
     let font = genpdf::fonts::from_files("./src/utils/ui/assets", "ARIAL", None)?;
 
     let mut doc = Document::new(font);
-    doc.set_title("1D Grid Export");
+    doc.set_title(title);
     doc.set_paper_size(genpdf::Size::new(841.89, 595.28));
     doc.set_line_spacing(2.0);
 
     let mut decorator = genpdf::SimplePageDecorator::new();
     decorator.set_margins(genpdf::Margins::trbl(50.0, 20.0, 20.0, 20.0));
+    match progress {
+        Some(progress) => doc.set_page_decorator(Decorator {
+            inner: decorator,
+            progress,
+        }),
+        None => doc.set_page_decorator(decorator),
+    }
+    Ok(doc)
+}
+
+/// Lays out the text of a cell of the grid with its style.
+///
+/// Fill colors are not exported, as the table cells of genpdf cannot be filled.
+///
+/// # Arguments
+/// * `text` - The text of the cell
+/// * `style` - The style of the text of unstyled cells
+/// * `cell_style` - The style of the cell
+fn styled_cell(
+    text: String,
+    mut style: genpdf::style::Style,
+    cell_style: crate::utils::style::CellStyle,
+) -> impl genpdf::Element {
+    if cell_style.bold {
+        style.set_bold();
+    }
+    if let Some([r, g, b]) = cell_style.color {
+        style.set_color(genpdf::style::Color::Rgb(r, g, b));
+    }
+    let alignment = match cell_style.align {
+        Some(crate::utils::style::Align::Center) => genpdf::Alignment::Center,
+        Some(crate::utils::style::Align::Right) => genpdf::Alignment::Right,
+        _ => genpdf::Alignment::Left,
+    };
+    elements::Paragraph::new("")
+        .styled_string(text, style)
+        .aligned(alignment)
+        .padded(15.0)
+}
+
+/// Adds the pages of the grid to a PDF document, each page showing up to 10x10 cells.
+///
+/// The arguments are those of `save_1d_as_pdf`.
+///
+/// # Returns
+/// The number of pages added
+#[allow(clippy::too_many_arguments)]
+fn push_grid(
+    doc: &mut Document,
+    data: &[i32],
+    err: &[bool],
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    len_h: i32,
+    len_v: i32,
+    hidden: (&[i32], &[i32]),
+    labels: &BTreeMap<i32, String>,
+    formats: &BTreeMap<i32, NumberFormat>,
+    styles: &BTreeMap<i32, crate::utils::style::CellStyle>,
+    col_width: &dyn Fn(i32) -> f32,
+) -> Result<usize, Box<dyn Error>> {
+    let rows = shown_lines(len_v, hidden.0);
+    let cols = shown_lines(len_h, hidden.1);
 
     let mut style = genpdf::style::Style::new();
     style.set_font_size(45);
 
-    doc.set_page_decorator(decorator);
     // Set up table layout
 
     let mut pages = 1;
-    let hz = (len_h as f64 / 10.0).ceil() as i32;
-    let vz = (len_v as f64 / 10.0).ceil() as i32;
+    let hz = (cols.len() as f64 / 10.0).ceil() as usize;
+    let vz = (rows.len() as f64 / 10.0).ceil() as usize;
     let total_pages = hz * vz;
     for top_h in 0..hz {
         for top_v in 0..vz {
-            let mut table = elements::TableLayout::new(vec![1; 10_usize]);
+            // Columns keep the relative widths they have in the grid
+            let weights = (1..=10)
+                .map(|i| {
+                    let width = cols
+                        .get(top_h * 10 + i - 1)
+                        .map_or(100.0, |&c| col_width(c));
+                    (width / 10.0).round().max(1.0) as usize
+                })
+                .collect();
+            let mut table = elements::TableLayout::new(weights);
             table.set_cell_decorator(elements::FrameCellDecorator::new(true, true, false));
+            let page_cols = &cols[top_h * 10..(top_h * 10 + 10).min(cols.len())];
+            if let Some(page_labels) = label_row(page_cols, labels) {
+                let mut row = table.row();
+                for i in 0..10 {
+                    let label = page_labels.get(i).cloned().unwrap_or_default();
+                    row.push_element(
+                        elements::Paragraph::new("")
+                            .styled_string(label, style.bold())
+                            .padded(15.0),
+                    );
+                }
+                row.push()?;
+            }
             for j in 1..=10 {
                 let mut row = table.row();
                 // let mut row = Vec::with_capacity(len_h as usize);
                 for i in 1..=10 {
-                    let index = if top_h * 10 + i > len_h || top_v * 10 + j > len_v {
+                    let index = if top_h * 10 + i > cols.len() || top_v * 10 + j > rows.len() {
                         0
                     } else {
-                        ((top_v * 10 + j - 1) * len_h + i + top_h * 10) as usize
-                    };
-                    let cell = if err[index] {
-                        "ERR".to_string()
-                    } else {
-                        data[index].to_string()
+                        ((rows[top_v * 10 + j - 1] - 1) * len_h + cols[top_h * 10 + i - 1]) as usize
                     };
-                    row.push_element(
-                        elements::Paragraph::new("")
-                            .styled_string(cell, style)
-                            .padded(15.0),
-                    );
+                    let cell = cell_text(data, err, opers, len_h, formats, index);
+                    row.push_element(styled_cell(
+                        cell,
+                        style,
+                        styles.get(&(index as i32)).copied().unwrap_or_default(),
+                    ));
                 }
                 row.push()?;
             }
@@ -169,10 +526,10 @@ pub fn save_1d_as_pdf(
                     "Page {} of {}, Displaying - {}{} to {}{}",
                     pages,
                     total_pages,
-                    crate::utils::display::get_label(top_h * 10 + 1),
-                    top_v * 10 + 1,
-                    crate::utils::display::get_label(top_h * 10 + 10),
-                    top_v * 10 + 10
+                    crate::utils::display::get_label(cols[top_h * 10]),
+                    rows[top_v * 10],
+                    crate::utils::display::get_label(cols[(top_h * 10 + 9).min(cols.len() - 1)]),
+                    rows[(top_v * 10 + 9).min(rows.len() - 1)]
                 ))
                 .styled(style),
             );
@@ -183,12 +540,211 @@ pub fn save_1d_as_pdf(
         }
     }
 
-    // Fill table rows
+    Ok(total_pages)
+}
+
+/// Exports spreadsheet data to a PDF file.
+///
+/// This function creates a formatted PDF document representing the spreadsheet content.
+/// The PDF includes proper pagination for large spreadsheets, with each page showing up to
+/// 10x10 cells. Cells with errors are marked with "ERR" and empty cells are left blank. If any column on a page has a
+/// custom label, the table on that page starts with a row of labels.
+///
+/// # Arguments
+/// * `data` - Slice containing cell values
+/// * `err` - Slice indicating which cells have errors
+/// * `opers` - Operations of each cell, used to tell empty cells from zeros
+/// * `len_h` - Number of columns in the spreadsheet
+/// * `len_v` - Number of rows in the spreadsheet
+/// * `hidden` - Rows and columns to leave out of the export
+/// * `labels` - Custom column labels
+/// * `formats` - Number formats of the formatted cells, used to write their values
+/// * `styles` - Styles of the styled cells, whose text color, weight and alignment are kept
+/// * `col_width` - Gives the width of a column in the grid, used to size the table columns
+/// * `filename` - Path where the PDF file will be saved
+/// * `progress` - Progress of the export, through which it can be cancelled
+///
+/// # Returns
+/// `Ok(())` if the operation was successful, or an error otherwise. A file left incomplete
+/// by an error or a cancellation is removed.
+#[allow(clippy::too_many_arguments)]
+pub fn save_1d_as_pdf(
+    data: &[i32],
+    err: &[bool],
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    len_h: i32,
+    len_v: i32,
+    hidden: (&[i32], &[i32]),
+    labels: &BTreeMap<i32, String>,
+    formats: &BTreeMap<i32, NumberFormat>,
+    styles: &BTreeMap<i32, crate::utils::style::CellStyle>,
+    col_width: &dyn Fn(i32) -> f32,
+    filename: &str,
+    progress: Arc<Progress>,
+) -> Result<(), Box<dyn Error>> {
+    let mut doc = new_document("1D Grid Export", Some(Arc::clone(&progress)))?;
+    let total_pages = push_grid(
+        &mut doc, data, err, opers, len_h, len_v, hidden, labels, formats, styles, col_width,
+    )?;
+    progress.total.store(total_pages, Ordering::Relaxed);
 
     // Add to document and render
 
-    doc.render_to_file(filename)?;
+    if let Err(e) = doc.render_to_file(filename) {
+        let _ = std::fs::remove_file(filename);
+        if progress.is_cancelled() {
+            tracing::info!(path = filename, "PDF export cancelled");
+            return Err("Export cancelled".into());
+        }
+        return Err(e.into());
+    }
+    tracing::info!(
+        path = filename,
+        pages = total_pages,
+        "values exported to PDF"
+    );
 
     println!("PDF saved to {}", filename);
     Ok(())
 }
+
+/// Exports a report to a PDF file.
+///
+/// The report starts with a title page, followed by the grid pages of `save_1d_as_pdf`, one
+/// page per chart and a table of descriptive statistics. Charts and statistics are left out
+/// when there are none.
+///
+/// # Arguments
+/// * `data`, `err`, `opers`, `len_h`, `len_v`, `hidden`, `labels`, `formats`, `styles`,
+///   `col_width` - The grid, as for `save_1d_as_pdf`
+/// * `charts` - Caption and image path of each chart
+/// * `analyses` - Pairs of a range and the statistics computed over it
+/// * `title` - Title of the report
+/// * `filename` - Path where the PDF file will be saved
+///
+/// # Returns
+/// `Ok(())` if the operation was successful, or an error otherwise
+#[allow(clippy::too_many_arguments)]
+pub fn save_report(
+    data: &[i32],
+    err: &[bool],
+    opers: &crate::utils::cells::Cells<crate::Ops>,
+    len_h: i32,
+    len_v: i32,
+    hidden: (&[i32], &[i32]),
+    labels: &BTreeMap<i32, String>,
+    formats: &BTreeMap<i32, NumberFormat>,
+    styles: &BTreeMap<i32, crate::utils::style::CellStyle>,
+    col_width: &dyn Fn(i32) -> f32,
+    charts: &[(String, String)],
+    analyses: &[(String, [f64; 8])],
+    title: &str,
+    filename: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut doc = new_document(title, None)?;
+    let mut heading = genpdf::style::Style::new();
+    heading.set_font_size(24);
+    heading.set_bold();
+    let mut style = genpdf::style::Style::new();
+    style.set_font_size(12);
+
+    // Title page
+    doc.push(elements::Break::new(8.0));
+    doc.push(
+        elements::Paragraph::new("")
+            .styled_string(title, heading)
+            .aligned(genpdf::Alignment::Center),
+    );
+    doc.push(
+        elements::Paragraph::new("")
+            .styled_string(
+                format!("Created {}", chrono::Local::now().format("%Y-%m-%d %H:%M")),
+                style,
+            )
+            .aligned(genpdf::Alignment::Center),
+    );
+    doc.push(
+        elements::Paragraph::new("")
+            .styled_string(
+                format!(
+                    "{} x {} cells, {} chart(s), {} statistics table row(s)",
+                    len_h,
+                    len_v,
+                    charts.len(),
+                    analyses.len()
+                ),
+                style,
+            )
+            .aligned(genpdf::Alignment::Center),
+    );
+    doc.push(elements::PageBreak::new());
+
+    let grid_pages = push_grid(
+        &mut doc, data, err, opers, len_h, len_v, hidden, labels, formats, styles, col_width,
+    )?;
+
+    // The grid ends without a page break, and may have no pages at all
+    let mut fresh_page = grid_pages == 0;
+    for (caption, path) in charts {
+        if !fresh_page {
+            doc.push(elements::PageBreak::new());
+        }
+        fresh_page = false;
+        doc.push(elements::Paragraph::new("").styled_string(caption, heading));
+        doc.push(
+            elements::Image::from_path(path)?
+                .with_dpi(150.0)
+                .with_alignment(genpdf::Alignment::Center),
+        );
+    }
+
+    if !analyses.is_empty() {
+        if !fresh_page {
+            doc.push(elements::PageBreak::new());
+        }
+        doc.push(elements::Paragraph::new("").styled_string("Statistics", heading));
+        let mut table = elements::TableLayout::new(vec![2, 1, 1, 1, 1, 1, 1, 1, 1]);
+        table.set_cell_decorator(elements::FrameCellDecorator::new(true, true, false));
+        let mut row = table.row();
+        row.push_element(
+            elements::Paragraph::new("")
+                .styled_string("Range", style.bold())
+                .padded(5.0),
+        );
+        for name in ui::stats::STAT_NAMES {
+            row.push_element(
+                elements::Paragraph::new("")
+                    .styled_string(name, style.bold())
+                    .padded(5.0),
+            );
+        }
+        row.push()?;
+        for (range, stats) in analyses {
+            let mut row = table.row();
+            row.push_element(
+                elements::Paragraph::new("")
+                    .styled_string(range.clone(), style)
+                    .padded(5.0),
+            );
+            for value in stats {
+                row.push_element(
+                    elements::Paragraph::new("")
+                        .styled_string(format!("{:.2}", value), style)
+                        .padded(5.0),
+                );
+            }
+            row.push()?;
+        }
+        doc.push(table);
+    }
+
+    doc.render_to_file(filename)?;
+    tracing::info!(
+        path = filename,
+        grid_pages,
+        charts = charts.len(),
+        analyses = analyses.len(),
+        "report exported to PDF"
+    );
+    Ok(())
+}
@@ -1,5 +1,12 @@
 //! This module contains basic utilities for the GUI of srpeadsheet.
+#[cfg(feature = "gui")]
 pub mod gui;
+#[cfg(feature = "gui")]
 pub mod loadnsave;
+#[cfg(feature = "gui")]
+pub mod open;
+#[cfg(feature = "gui")]
 pub mod plot;
+#[cfg(feature = "gui")]
+pub mod session;
 pub mod stats;
@@ -0,0 +1,53 @@
+//! This module opens files in the application the system uses for them, such as a plot in
+//! the image viewer.
+//!
+//! Each system has its own launcher: `start` on Windows, `open` on macOS, and on other
+//! systems the first of `xdg-open`, `gio open`, `gnome-open` and `kde-open` that is
+//! installed. The launcher is waited for on its own thread, so the spreadsheet stays
+//! responsive while the file is open.
+
+use std::ffi::OsStr;
+use std::process::{Command, Stdio};
+
+/// Gives the launchers to try in order, as the program and the arguments before the path.
+fn launchers() -> &'static [(&'static str, &'static [&'static str])] {
+    if cfg!(target_os = "windows") {
+        // The empty argument is the window title, so a quoted path is not taken for it
+        &[("cmd", &["/C", "start", ""])]
+    } else if cfg!(target_os = "macos") {
+        &[("open", &[])]
+    } else {
+        &[
+            ("xdg-open", &[]),
+            ("gio", &["open"]),
+            ("gnome-open", &[]),
+            ("kde-open", &[]),
+        ]
+    }
+}
+
+/// Opens a file in the application the system uses for it.
+/// # Arguments
+/// * `path` - The file to open.
+/// # Returns
+/// `Ok` once a launcher has started, or the error of the last launcher tried if none could.
+pub fn that(path: impl AsRef<OsStr>) -> std::io::Result<()> {
+    let mut last = std::io::Error::new(std::io::ErrorKind::NotFound, "no launcher found");
+    for (program, args) in launchers() {
+        let started = Command::new(program)
+            .args(*args)
+            .arg(path.as_ref())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match started {
+            Ok(mut child) => {
+                std::thread::spawn(move || child.wait());
+                return Ok(());
+            }
+            Err(e) => last = e,
+        }
+    }
+    Err(last)
+}
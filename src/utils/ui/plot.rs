@@ -2,9 +2,301 @@
 //!
 //! This module provides functions to create visual representations of spreadsheet data
 //! using the plotters library. It supports different plot types including scatter plots
-//! and line plots with automatic axis scaling.
+//! and line plots with automatic axis scaling. The look of a plot (colors, markers, line
+//! width, grid, titles) is given by a `PlotStyle`, which can start from one of the `Theme`s.
+//! Both plot types can show vertical error bars, such as a standard deviation per point, and
+//! a second series against its own Y axis on the right. Either axis can be logarithmic, in
+//! which case points at 0 or below are left out.
+use plotters::coord::types::RangedCoordf64;
 use plotters::prelude::*;
 
+/// Shape drawn at each data point.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Marker {
+    #[default]
+    Circle,
+    Square,
+    Triangle,
+    Cross,
+    None,
+}
+
+impl Marker {
+    /// All markers, in the order shown in the Plot dialog.
+    pub const ALL: [Marker; 5] = [
+        Marker::Circle,
+        Marker::Square,
+        Marker::Triangle,
+        Marker::Cross,
+        Marker::None,
+    ];
+
+    /// Gives the name shown in the Plot dialog.
+    pub fn name(self) -> &'static str {
+        match self {
+            Marker::Circle => "Circle",
+            Marker::Square => "Square",
+            Marker::Triangle => "Triangle",
+            Marker::Cross => "Cross",
+            Marker::None => "None",
+        }
+    }
+}
+
+/// A predefined set of colors and markers.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Theme {
+    #[default]
+    Classic,
+    Dark,
+    Pastel,
+    Print,
+}
+
+impl Theme {
+    /// All themes, in the order shown in the Plot dialog.
+    pub const ALL: [Theme; 4] = [Theme::Classic, Theme::Dark, Theme::Pastel, Theme::Print];
+
+    /// Gives the name shown in the Plot dialog.
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Classic => "Classic",
+            Theme::Dark => "Dark",
+            Theme::Pastel => "Pastel",
+            Theme::Print => "Print",
+        }
+    }
+
+    /// Applies the theme to a style. Titles and axis labels are kept.
+    ///
+    /// # Arguments
+    /// * `style` - The style to change
+    pub fn apply(self, style: &mut PlotStyle) {
+        let (color, background, text, marker, line_width, grid) = match self {
+            Theme::Classic => (
+                [30, 90, 200],
+                [255, 255, 255],
+                [0, 0, 0],
+                Marker::Circle,
+                2,
+                true,
+            ),
+            Theme::Dark => (
+                [255, 170, 40],
+                [30, 30, 35],
+                [230, 230, 230],
+                Marker::Circle,
+                2,
+                true,
+            ),
+            Theme::Pastel => (
+                [220, 120, 150],
+                [250, 246, 238],
+                [80, 80, 90],
+                Marker::Triangle,
+                3,
+                false,
+            ),
+            Theme::Print => (
+                [0, 0, 0],
+                [255, 255, 255],
+                [0, 0, 0],
+                Marker::Cross,
+                1,
+                true,
+            ),
+        };
+        style.theme = self;
+        style.color = color;
+        style.background = background;
+        style.text = text;
+        style.marker = marker;
+        style.line_width = line_width;
+        style.grid = grid;
+        style.secondary_color = match self {
+            Theme::Classic => [220, 60, 40],
+            Theme::Dark => [90, 200, 250],
+            Theme::Pastel => [110, 170, 150],
+            Theme::Print => [130, 130, 130],
+        };
+    }
+}
+
+/// How a plot looks.
+///
+/// # Fields
+/// * `theme` - Theme the colors and marker were last taken from
+/// * `color` - Color of the data, as RGB
+/// * `background` - Color of the background, as RGB
+/// * `text` - Color of the titles, labels and axes, as RGB
+/// * `marker` - Shape drawn at each data point
+/// * `marker_size` - Size of the markers in pixels
+/// * `line_width` - Width of the line of a line plot in pixels
+/// * `grid` - Whether grid lines are drawn
+/// * `title` - Title above the plot, or empty for the default title of the plot type
+/// * `x_label` - Label of the X axis
+/// * `y_label` - Label of the Y axis
+/// * `secondary_color` - Color of the series on the secondary Y axis, as RGB
+/// * `y2_label` - Label of the secondary Y axis
+/// * `log_x` - Whether the X axis is logarithmic
+/// * `log_y` - Whether both Y axes are logarithmic
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct PlotStyle {
+    pub theme: Theme,
+    pub color: [u8; 3],
+    pub background: [u8; 3],
+    pub text: [u8; 3],
+    pub marker: Marker,
+    pub marker_size: u32,
+    pub line_width: u32,
+    pub grid: bool,
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    #[serde(default)]
+    pub secondary_color: [u8; 3],
+    #[serde(default)]
+    pub y2_label: String,
+    #[serde(default)]
+    pub log_x: bool,
+    #[serde(default)]
+    pub log_y: bool,
+}
+
+impl Default for PlotStyle {
+    fn default() -> Self {
+        let mut style = Self {
+            theme: Theme::Classic,
+            color: [0, 0, 0],
+            background: [0, 0, 0],
+            text: [0, 0, 0],
+            marker: Marker::Circle,
+            marker_size: 4,
+            line_width: 2,
+            grid: true,
+            title: String::new(),
+            x_label: String::new(),
+            y_label: String::new(),
+            secondary_color: [0, 0, 0],
+            y2_label: String::new(),
+            log_x: false,
+            log_y: false,
+        };
+        Theme::Classic.apply(&mut style);
+        style
+    }
+}
+
+/// Converts an RGB array to a plotters color.
+fn rgb([r, g, b]: [u8; 3]) -> RGBColor {
+    RGBColor(r, g, b)
+}
+
+/// The plotting area of a chart, in the coordinates of its axes.
+type Area<DB> = DrawingArea<DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>;
+
+/// Moves a value onto an axis, taking its logarithm on a logarithmic axis.
+///
+/// # Returns
+/// The position on the axis, or `None` for values a logarithmic axis cannot show (0 and
+/// below).
+fn to_axis(value: f64, log: bool) -> Option<f64> {
+    if !log {
+        Some(value)
+    } else if value > 0.0 {
+        Some(value.log10())
+    } else {
+        None
+    }
+}
+
+/// Moves data points onto the axes, leaving out the points a logarithmic axis cannot show.
+fn to_axes(data: &[(f64, f64)], style: &PlotStyle) -> Vec<(f64, f64)> {
+    data.iter()
+        .filter_map(|&(x, y)| Some((to_axis(x, style.log_x)?, to_axis(y, style.log_y)?)))
+        .collect()
+}
+
+/// Writes the label of a tick on a logarithmic axis, given its position on the axis.
+fn log_tick(position: &f64) -> String {
+    let value = 10f64.powf(*position);
+    // Three significant digits, so neighbouring ticks stay apart
+    let decimals = (2.0 - position.floor()).max(0.0) as usize;
+    let text = format!("{:.*}", decimals, value);
+    if decimals > 0 {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
+
+/// Draws the line and markers of a data series.
+///
+/// # Arguments
+/// * `area` - The plotting area of the axes the series belongs to
+/// * `points` - The data points, on the axes
+/// * `line` - Whether the points are joined by a line
+/// * `color` - Color of the series
+/// * `style` - The style giving the marker, its size and the line width
+fn draw_series<DB: DrawingBackend>(
+    area: &Area<DB>,
+    points: &[(f64, f64)],
+    line: bool,
+    color: RGBColor,
+    style: &PlotStyle,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    if line {
+        area.draw(&PathElement::new(
+            points.to_vec(),
+            color.stroke_width(style.line_width.max(1)),
+        ))?;
+    }
+    let size = style.marker_size as i32;
+    let fill = color.filled();
+    // A scatter plot without markers would be empty
+    let marker = match style.marker {
+        Marker::None if !line => Marker::Circle,
+        marker => marker,
+    };
+    for &p in points {
+        match marker {
+            Marker::Circle => area.draw(&Circle::new(p, size, fill))?,
+            Marker::Square => area.draw(
+                &(EmptyElement::at(p) + Rectangle::new([(-size, -size), (size, size)], fill)),
+            )?,
+            Marker::Triangle => area.draw(&TriangleMarker::new(p, size, fill))?,
+            Marker::Cross => area.draw(&Cross::new(p, size, fill))?,
+            Marker::None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Places a vertical error bar through each data point.
+///
+/// # Arguments
+/// * `data` - The data points
+/// * `errors` - Size of the error of each data point; the bar goes from y - error to y + error
+/// * `style` - The style giving the axes
+///
+/// # Returns
+/// The x position and the bottom, middle and top of each bar on the axes. Bars that do not
+/// fit on a logarithmic axis are left out.
+fn error_bars(data: &[(f64, f64)], errors: &[f64], style: &PlotStyle) -> Vec<[f64; 4]> {
+    data.iter()
+        .zip(errors)
+        .filter_map(|(&(x, y), e)| {
+            let e = e.abs();
+            Some([
+                to_axis(x, style.log_x)?,
+                to_axis(y - e, style.log_y)?,
+                to_axis(y, style.log_y)?,
+                to_axis(y + e, style.log_y)?,
+            ])
+        })
+        .collect()
+}
+
 /// Calculates appropriate axis ranges for a data series.
 ///
 /// This function automatically determines suitable x and y axis ranges based on the
@@ -17,6 +309,9 @@ use plotters::prelude::*;
 /// A tuple of (x_range, y_range) where each range is a `std::ops::Range<f64>`.
 /// suitable for use with plotters
 fn auto_range(data: &[(f64, f64)]) -> (std::ops::Range<f64>, std::ops::Range<f64>) {
+    if data.is_empty() {
+        return (0.0..1.0, 0.0..1.0);
+    }
     let (min_x, max_x) = data
         .iter()
         .map(|(x, _)| *x)
@@ -47,77 +342,170 @@ fn auto_range(data: &[(f64, f64)]) -> (std::ops::Range<f64>, std::ops::Range<f64
     (x_range, y_range)
 }
 
-/// Creates a scatter plot from a set of data points and saves it to a file.
-///
-/// This function generates a scatter plot where each data point is rendered as
-/// a separate circle. It automatically scales the axes to fit the data.
+/// Draws a plot and saves it to a file.
 ///
 /// # Arguments
 /// * `data` - Slice of (x, y) coordinate pairs to plot
+/// * `errors` - Size of the error of each data point, or an empty slice for no error bars
+/// * `secondary` - Points of a second series drawn against a Y axis on the right, or an
+///   empty slice for none
 /// * `path` - Path where the plot image will be saved
-///
-/// # Returns
-/// `Ok(())` if the operation was successful, or an error otherwise
-pub fn scatter_plot(data: &[(f64, f64)], path: &str) -> Result<(), Box<dyn std::error::Error>> {
+/// * `style` - How the plot looks
+/// * `line` - Whether the points are joined by lines
+/// * `default_title` - Title used when the style has none
+fn draw_plot(
+    data: &[(f64, f64)],
+    errors: &[f64],
+    secondary: &[(f64, f64)],
+    path: &str,
+    style: &PlotStyle,
+    line: bool,
+    default_title: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
     let root = BitMapBackend::new(path, (800, 600)).into_drawing_area();
-    root.fill(&WHITE)?;
+    root.fill(&rgb(style.background))?;
 
-    // Extract bounds
-    let (x_range, y_range) = auto_range(data);
+    let points = to_axes(data, style);
+    let bars = error_bars(data, errors, style);
+    // The ends of the error bars have to fit as well
+    let mut bounds = points.clone();
+    bounds.extend(
+        bars.iter()
+            .flat_map(|&[x, low, _, high]| [(x, low), (x, high)]),
+    );
+    let (x_range, y_range) = auto_range(&bounds);
+    let secondary = to_axes(secondary, style);
 
-    let mut chart = ChartBuilder::on(&root)
-        .caption("Scatter Plot (Auto Axes)", ("Arial", 30).into_font())
+    let title = if style.title.trim().is_empty() {
+        default_title
+    } else {
+        style.title.trim()
+    };
+    let text = rgb(style.text);
+    let log_tick = &log_tick;
+
+    let mut builder = ChartBuilder::on(&root);
+    builder
+        .caption(title, ("sans-serif", 30).into_font().color(&text))
         .margin(40)
         .x_label_area_size(40)
-        .y_label_area_size(40)
-        .build_cartesian_2d(x_range, y_range)?;
-
-    chart
-        .configure_mesh()
-        .x_desc("X Axis")
-        .y_desc("Y Axis")
-        .draw()?;
-
-    chart.draw_series(
-        data.iter()
-            .map(|(x, y)| Circle::new((*x, *y), 5, RED.filled())),
+        .y_label_area_size(40);
+    if !secondary.is_empty() {
+        builder.right_y_label_area_size(80);
+    }
+    // The secondary axis takes over the label area on the right, so it is set before the
+    // primary axes are drawn
+    let (_, y2_range) = auto_range(&secondary);
+    let mut chart = builder
+        .build_cartesian_2d(x_range.clone(), y_range)?
+        .set_secondary_coord(x_range, y2_range);
+
+    let mut mesh = chart.configure_mesh();
+    mesh.axis_style(text)
+        .label_style(("sans-serif", 15).into_font().color(&text))
+        .x_desc(style.x_label.as_str())
+        .y_desc(style.y_label.as_str());
+    if style.grid {
+        mesh.bold_line_style(text.mix(0.2))
+            .light_line_style(text.mix(0.05));
+    } else {
+        mesh.disable_mesh();
+    }
+    if style.log_x {
+        mesh.x_label_formatter(log_tick);
+    }
+    if style.log_y {
+        mesh.y_label_formatter(log_tick);
+    }
+    mesh.draw()?;
+
+    chart.draw_series(bars.iter().map(|&[x, low, mid, high]| {
+        ErrorBar::new_vertical(
+            x,
+            low,
+            mid,
+            high,
+            text.stroke_width(style.line_width.clamp(1, 2)),
+            10,
+        )
+    }))?;
+    draw_series(
+        chart.plotting_area(),
+        &points,
+        line,
+        rgb(style.color),
+        style,
     )?;
 
+    if !secondary.is_empty() {
+        let mut axes = chart.configure_secondary_axes();
+        axes.axis_style(text)
+            .label_style(("sans-serif", 15).into_font().color(&text))
+            .y_desc(style.y2_label.as_str());
+        if style.log_y {
+            axes.y_label_formatter(log_tick);
+        }
+        axes.draw()?;
+        draw_series(
+            chart.secondary_plotting_area(),
+            &secondary,
+            line,
+            rgb(style.secondary_color),
+            style,
+        )?;
+    }
+
+    root.present()?;
     Ok(())
 }
 
+/// Creates a scatter plot from a set of data points and saves it to a file.
+///
+/// This function generates a scatter plot where each data point is rendered as
+/// a separate marker. It automatically scales the axes to fit the data.
+///
+/// # Arguments
+/// * `data` - Slice of (x, y) coordinate pairs to plot
+/// * `errors` - Size of the error of each data point, or an empty slice for no error bars
+/// * `secondary` - Points of a second series drawn against a Y axis on the right, or an
+///   empty slice for none
+/// * `path` - Path where the plot image will be saved
+/// * `style` - How the plot looks
+///
+/// # Returns
+/// `Ok(())` if the operation was successful, or an error otherwise
+pub fn scatter_plot(
+    data: &[(f64, f64)],
+    errors: &[f64],
+    secondary: &[(f64, f64)],
+    path: &str,
+    style: &PlotStyle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    draw_plot(data, errors, secondary, path, style, false, "Scatter Plot")
+}
+
 /// Creates a line plot from a set of data points and saves it to a file.
 ///
 /// This function generates a line plot where data points are connected with lines
-/// and each point is marked with a small circle. It automatically scales the axes
-/// to fit the data.
+/// and each point is marked with the marker of the style. It automatically scales the
+/// axes to fit the data.
 ///
 /// # Arguments
 /// * `data` - Slice of (x, y) coordinate pairs to plot
+/// * `errors` - Size of the error of each data point, or an empty slice for no error bars
+/// * `secondary` - Points of a second series drawn against a Y axis on the right, or an
+///   empty slice for none
 /// * `path` - Path where the plot image will be saved
+/// * `style` - How the plot looks
 ///
 /// # Returns
 /// `Ok(())` if the operation was successful, or an error otherwise
-pub fn line_plot(data: &[(f64, f64)], path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let root = BitMapBackend::new(path, (800, 600)).into_drawing_area();
-    root.fill(&WHITE)?;
-
-    let (x_range, y_range) = auto_range(data);
-
-    let mut chart = ChartBuilder::on(&root)
-        .caption("Line Plot", ("Arial", 30).into_font())
-        .margin(40)
-        .x_label_area_size(40)
-        .y_label_area_size(40)
-        .build_cartesian_2d(x_range, y_range)?;
-
-    chart.configure_mesh().draw()?;
-
-    chart.draw_series(LineSeries::new(data.to_owned(), &BLUE))?;
-    chart.draw_series(
-        data.iter()
-            .map(|(x, y)| Circle::new((*x, *y), 3, BLUE.filled())),
-    )?;
-
-    Ok(())
+pub fn line_plot(
+    data: &[(f64, f64)],
+    errors: &[f64],
+    secondary: &[(f64, f64)],
+    path: &str,
+    style: &PlotStyle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    draw_plot(data, errors, secondary, path, style, true, "Line Plot")
 }
@@ -0,0 +1,341 @@
+//! Session handling for the GUI.
+//!
+//! A session keeps several workbooks open at once, each with its own engine state, and
+//! shows them as tabs. The clipboard is shared between tabs so that ranges can be copied
+//! from one workbook and pasted into another.
+
+use crate::utils::ui;
+use eframe::egui;
+use egui::{Color32, FontId, RichText};
+use notify_rust::Notification;
+
+/// The open workbooks of a GUI instance.
+///
+/// # Fields
+/// * `tabs` - The open workbooks
+/// * `active` - Index of the workbook shown
+/// * `clipboard` - Clipboard shared by all workbooks
+/// * `len_h` - Number of columns of new workbooks
+/// * `len_v` - Number of rows of new workbooks
+/// * `confirm_close` - Tab with unsaved changes the user asked to close, if any
+/// * `untitled` - Number of new workbooks created, used to name them
+/// * `config` - User preferences shared by all workbooks
+/// * `limits` - `safe_mode` and `limit` commands given on the command line, applied to every
+///   workbook after the limits of `config`
+/// * `scale_todo` - Whether the UI scale still has to be applied to the window
+pub struct Session {
+    tabs: Vec<ui::gui::Spreadsheet>,
+    active: usize,
+    clipboard: String,
+    len_h: i32,
+    len_v: i32,
+    confirm_close: Option<usize>,
+    untitled: usize,
+    config: crate::utils::config::Config,
+    limits: Vec<String>,
+    scale_todo: bool,
+}
+
+impl Session {
+    /// Creates a session with one empty workbook.
+    ///
+    /// # Arguments
+    /// * `len_h` - Number of columns of new workbooks
+    /// * `len_v` - Number of rows of new workbooks
+    /// * `limits` - `safe_mode` and `limit` commands applied to every workbook
+    pub fn new(len_h: i32, len_v: i32, limits: Vec<String>) -> Self {
+        let mut session = Self {
+            tabs: Vec::new(),
+            active: 0,
+            clipboard: String::new(),
+            len_h,
+            len_v,
+            confirm_close: None,
+            untitled: 0,
+            config: crate::utils::config::load(),
+            limits,
+            scale_todo: true,
+        };
+        session.new_tab();
+        session
+    }
+
+    /// Opens an empty workbook in a new tab and shows it.
+    fn new_tab(&mut self) {
+        self.untitled += 1;
+        let mut sheet = ui::gui::Spreadsheet::blank(self.len_h, self.len_v);
+        sheet.title = format!("Untitled {}", self.untitled);
+        self.add_tab(sheet);
+    }
+
+    /// Shows a workbook in a new tab, with the resource limits of the session.
+    ///
+    /// # Arguments
+    /// * `sheet` - The workbook
+    fn add_tab(&mut self, mut sheet: ui::gui::Spreadsheet) {
+        sheet.apply_limits(&self.config.limit_commands());
+        sheet.apply_limits(&self.limits);
+        self.tabs.push(sheet);
+        self.active = self.tabs.len() - 1;
+    }
+
+    /// Opens the demo workbook in a new tab and shows it.
+    fn demo_tab(&mut self) {
+        match ui::gui::Spreadsheet::demo(self.len_h, self.len_v) {
+            Some(mut sheet) => {
+                sheet.title = String::from("Demo");
+                self.add_tab(sheet);
+            }
+            None => {
+                let (len_h, len_v) = crate::utils::demo::MIN_SIZE;
+                Notification::new()
+                    .summary("Load Demo Failed")
+                    .body(
+                        format!(
+                            "The demo needs at least {} columns and {} rows.",
+                            len_h, len_v
+                        )
+                        .as_str(),
+                    )
+                    .show()
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Creates a session showing a saved workbook, such as one opened from the file manager.
+    ///
+    /// New workbooks get the size of the saved one. If it cannot be loaded, an empty workbook
+    /// of the given size is shown instead.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the .rsk or .rskt file
+    /// * `len_h` - Number of columns of new workbooks if the file cannot be loaded
+    /// * `len_v` - Number of rows of new workbooks if the file cannot be loaded
+    /// * `limits` - `safe_mode` and `limit` commands applied to every workbook
+    pub fn open(path: &str, len_h: i32, len_v: i32, limits: Vec<String>) -> Self {
+        let mut session = Self::new(len_h, len_v, limits);
+        session.tabs.clear();
+        session.untitled = 0;
+        session.open_tab(path);
+        match session.tabs.first() {
+            Some(sheet) => (session.len_h, session.len_v) = sheet.size(),
+            None => session.new_tab(),
+        }
+        session
+    }
+
+    /// Opens a saved workbook in a new tab and shows it.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the .rsk or .rskt file
+    fn open_tab(&mut self, path: &str) {
+        let text = path.ends_with(&format!(".{}", crate::utils::textformat::EXTENSION));
+        let loaded = if text {
+            ui::loadnsave::read_from_text_file(path)
+        } else {
+            ui::loadnsave::read_from_file(path)
+        };
+        let mut sheet = match loaded {
+            Ok(sheet) => sheet,
+            Err(e) => {
+                Notification::new()
+                    .summary("Load Failed")
+                    .body(format!("Could not load {}: {}", path, e).as_str())
+                    .show()
+                    .unwrap();
+                return;
+            }
+        };
+        if !text {
+            sheet.saved_path = Some(path.to_string());
+        }
+        sheet.title = std::path::Path::new(path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string());
+        self.add_tab(sheet);
+    }
+
+    /// Opens a backup of a workbook in a new tab, which saves to the workbook rather than to
+    /// the backup.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the .rsk file
+    /// * `n` - Number of the backup, see `utils::backup`
+    fn restore_tab(&mut self, path: &str, n: u32) {
+        let tabs = self.tabs.len();
+        self.open_tab(&crate::utils::backup::path(path, n));
+        if self.tabs.len() > tabs {
+            let sheet = &mut self.tabs[self.active];
+            sheet.saved_path = Some(path.to_string());
+            sheet.title = format!("{} (backup {})", sheet.title, n);
+            sheet.dirty = true;
+        }
+    }
+
+    /// Applies the UI scale and resizes the window to match.
+    ///
+    /// Without a scale set by the user, the largest scale at which the window fits on the
+    /// screen is used. The screen size is given in points, so system DPI scaling is kept.
+    ///
+    /// # Returns
+    /// `false` if the screen size is not known yet and the scale should be applied later.
+    fn apply_scale(&self, ctx: &egui::Context) -> bool {
+        let [width, height] = ui::gui::WINDOW_SIZE;
+        let scale = match self.config.ui_scale {
+            Some(scale) => scale,
+            None => match ctx.input(|i| i.viewport().monitor_size) {
+                // Leave room for the title bar and task bar
+                Some(screen) => {
+                    crate::utils::config::fit_scale((screen.x, screen.y), (width, height + 60.0))
+                }
+                None => return false,
+            },
+        };
+        ctx.set_zoom_factor(scale);
+        ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(width, height)));
+        true
+    }
+
+    /// Closes a tab. The last tab is replaced by an empty workbook.
+    ///
+    /// # Arguments
+    /// * `index` - Index of the tab to close
+    fn close_tab(&mut self, index: usize) {
+        self.tabs.remove(index);
+        if self.tabs.is_empty() {
+            self.new_tab();
+        } else if self.active >= index && self.active > 0 {
+            self.active -= 1;
+        }
+    }
+}
+
+impl eframe::App for Session {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        if self.scale_todo && self.apply_scale(ctx) {
+            self.scale_todo = false;
+        }
+        ctx.set_theme(match self.config.theme {
+            crate::utils::config::Theme::System => egui::ThemePreference::System,
+            crate::utils::config::Theme::Light => egui::ThemePreference::Light,
+            crate::utils::config::Theme::Dark => egui::ThemePreference::Dark,
+        });
+
+        let mut close = None;
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (i, tab) in self.tabs.iter().enumerate() {
+                    let title = if tab.dirty {
+                        format!("{} *", tab.title)
+                    } else {
+                        tab.title.clone()
+                    };
+                    if ui
+                        .selectable_label(
+                            i == self.active,
+                            RichText::new(title).font(FontId::proportional(16.0)),
+                        )
+                        .clicked()
+                    {
+                        self.active = i;
+                    }
+                    if ui
+                        .small_button("x")
+                        .on_hover_text("Close workbook")
+                        .clicked()
+                    {
+                        close = Some(i);
+                    }
+                    ui.separator();
+                }
+                if ui
+                    .button(RichText::new("+").font(FontId::proportional(16.0)))
+                    .on_hover_text("New workbook")
+                    .clicked()
+                {
+                    self.new_tab();
+                }
+                if ui
+                    .button(RichText::new("Load demo").font(FontId::proportional(16.0)))
+                    .on_hover_text("Open a sample workbook showing formulas and charts")
+                    .clicked()
+                {
+                    self.demo_tab();
+                }
+            });
+        });
+
+        if let Some(i) = close {
+            if self.tabs[i].dirty {
+                self.confirm_close = Some(i);
+            } else {
+                self.close_tab(i);
+            }
+        }
+
+        if let Some(i) = self.confirm_close {
+            let mut answer = None;
+            egui::Window::new("Unsaved Changes")
+                .order(egui::Order::Foreground)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(
+                        RichText::new(format!(
+                            "{} has unsaved changes. Close it anyway?",
+                            self.tabs[i].title
+                        ))
+                        .font(FontId::proportional(18.0)),
+                    );
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button(RichText::new("Close").color(Color32::RED))
+                            .clicked()
+                        {
+                            answer = Some(true);
+                        }
+                        if ui.button("Cancel").clicked() {
+                            answer = Some(false);
+                        }
+                    });
+                });
+            if let Some(close) = answer {
+                self.confirm_close = None;
+                if close {
+                    self.close_tab(i);
+                }
+            }
+        }
+
+        // The clocks tick even when nothing is happening
+        ctx.request_repaint_after(std::time::Duration::from_secs(1));
+
+        // The clipboard and preferences follow the active workbook
+        let tab = &mut self.tabs[self.active];
+        tab.clipbaord = self.clipboard.clone();
+        tab.config = self.config.clone();
+        tab.update(ctx, frame);
+        self.clipboard = tab.clipbaord.clone();
+
+        let config = tab.config_todo.take();
+        let restore = tab.restore_todo.take();
+        if let Some(path) = tab.open_todo.take() {
+            self.open_tab(&path);
+        }
+        if let Some((path, n)) = restore {
+            self.restore_tab(&path, n);
+        }
+        if let Some(config) = config {
+            // Limits changed in the Settings window apply to every open workbook
+            if config.limits() != self.config.limits() {
+                for tab in &mut self.tabs {
+                    tab.apply_limits(&config.limit_commands());
+                }
+            }
+            self.config = config;
+            self.scale_todo = true;
+        }
+    }
+}
@@ -5,6 +5,11 @@
 //! variability measures, and percentiles.
 use std::cmp;
 
+/// Names of the statistics returned by `calculate_stats`, in the same order.
+pub const STAT_NAMES: [&str; 8] = [
+    "Count", "Mean", "Std Dev", "Min", "25%", "50%", "75%", "Max",
+];
+
 /// Calculates descriptive statistics for a set of integer data.
 ///
 /// This function computes a comprehensive set of statistical measures for the given
@@ -67,3 +72,241 @@ pub fn calculate_stats(data: &[i32]) -> [f64; 8] {
         max as f64,
     ]
 }
+
+/// The statistical tests offered by the Stats Tests dialog.
+///
+/// # Variants
+/// * `TTest` - Welch's two-sample t-test, as TTEST(sample1, sample2)
+/// * `ChiSquare` - Pearson's chi-square goodness-of-fit test, as CHITEST(observed, expected)
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum StatTest {
+    #[default]
+    TTest,
+    ChiSquare,
+}
+
+impl StatTest {
+    /// The function name used for the test in the terminal.
+    pub fn name(self) -> &'static str {
+        match self {
+            StatTest::TTest => "TTEST",
+            StatTest::ChiSquare => "CHITEST",
+        }
+    }
+
+    /// Parses a test typed as a function, such as `TTEST(A1:A10, B1:B10)`.
+    ///
+    /// # Arguments
+    /// * `command` - The typed text
+    ///
+    /// # Returns
+    /// The test and its two ranges, or `None` if the text is not a test
+    pub fn parse(command: &str) -> Option<(StatTest, &str, &str)> {
+        let (name, args) = command.trim().split_once('(')?;
+        let test = [StatTest::TTest, StatTest::ChiSquare]
+            .into_iter()
+            .find(|t| t.name() == name.trim())?;
+        let (first, second) = args.strip_suffix(')')?.split_once(',')?;
+        Some((test, first.trim(), second.trim()))
+    }
+
+    /// Runs the test on two sets of values.
+    ///
+    /// # Arguments
+    /// * `first` - First sample, or the observed counts
+    /// * `second` - Second sample, or the expected counts
+    ///
+    /// # Returns
+    /// The result, or `None` if the test cannot be run on the values
+    pub fn run(self, first: &[i32], second: &[i32]) -> Option<TestResult> {
+        match self {
+            StatTest::TTest => ttest(first, second),
+            StatTest::ChiSquare => chitest(first, second),
+        }
+    }
+}
+
+/// Result of a statistical test.
+///
+/// # Fields
+/// * `statistic` - The test statistic (t or chi-square)
+/// * `df` - Degrees of freedom of the statistic's distribution
+/// * `p_value` - Probability of a statistic at least as extreme under the null hypothesis
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TestResult {
+    pub statistic: f64,
+    pub df: f64,
+    pub p_value: f64,
+}
+
+/// Performs Welch's two-sample t-test, which does not assume equal variances.
+///
+/// # Arguments
+/// * `a` - First sample
+/// * `b` - Second sample
+///
+/// # Returns
+/// The t statistic with its two-sided p-value, or `None` if a sample has fewer than two
+/// values or both samples have no variance
+pub fn ttest(a: &[i32], b: &[i32]) -> Option<TestResult> {
+    if a.len() < 2 || b.len() < 2 {
+        return None;
+    }
+    // Mean and variance of the mean
+    let moments = |data: &[i32]| {
+        let n = data.len() as f64;
+        let mean = data.iter().map(|&x| x as f64).sum::<f64>() / n;
+        let var = data.iter().map(|&x| (x as f64 - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        (mean, var / n)
+    };
+    let (mean_a, se_a) = moments(a);
+    let (mean_b, se_b) = moments(b);
+    let se = se_a + se_b;
+    if se == 0.0 {
+        return None;
+    }
+    let statistic = (mean_a - mean_b) / se.sqrt();
+    let df =
+        se * se / (se_a * se_a / (a.len() as f64 - 1.0) + se_b * se_b / (b.len() as f64 - 1.0));
+    let p_value = incomplete_beta(df / (df + statistic * statistic), df / 2.0, 0.5);
+    Some(TestResult {
+        statistic,
+        df,
+        p_value,
+    })
+}
+
+/// Performs Pearson's chi-square goodness-of-fit test.
+///
+/// # Arguments
+/// * `observed` - Observed counts
+/// * `expected` - Expected counts, in the same order
+///
+/// # Returns
+/// The chi-square statistic with its p-value, or `None` if the lengths differ, there are
+/// fewer than two categories or an expected count is not positive
+pub fn chitest(observed: &[i32], expected: &[i32]) -> Option<TestResult> {
+    if observed.len() != expected.len() || observed.len() < 2 || expected.iter().any(|&e| e <= 0) {
+        return None;
+    }
+    let statistic = observed
+        .iter()
+        .zip(expected)
+        .map(|(&o, &e)| (o as f64 - e as f64).powi(2) / e as f64)
+        .sum::<f64>();
+    let df = observed.len() as f64 - 1.0;
+    let p_value = upper_incomplete_gamma(df / 2.0, statistic / 2.0);
+    Some(TestResult {
+        statistic,
+        df,
+        p_value,
+    })
+}
+
+/// Natural logarithm of the gamma function, using the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFS[0]
+        + COEFFS[1..]
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| c / (x + i as f64 + 1.0))
+            .sum::<f64>();
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// Evaluates the continued fraction `b0 + a1 / (b1 + a2 / (b2 + ...))` with the modified
+/// Lentz method.
+///
+/// # Arguments
+/// * `b0` - The leading term
+/// * `term` - Gives `(am, bm)` for `m` starting at 1
+fn continued_fraction(b0: f64, term: impl Fn(usize) -> (f64, f64)) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut f = if b0 == 0.0 { TINY } else { b0 };
+    let (mut c, mut d) = (f, 0.0);
+    for m in 1..500 {
+        let (a, b) = term(m);
+        d = b + a * d;
+        d = if d == 0.0 { TINY } else { d };
+        c = b + a / c;
+        c = if c == 0.0 { TINY } else { c };
+        d = 1.0 / d;
+        let delta = c * d;
+        f *= delta;
+        if (delta - 1.0).abs() < 1e-14 {
+            break;
+        }
+    }
+    f
+}
+
+/// Regularized incomplete beta function I_x(a, b).
+fn incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    // The continued fraction converges quickly on this side, use symmetry otherwise
+    if x > (a + 1.0) / (a + b + 2.0) {
+        return 1.0 - incomplete_beta(1.0 - x, b, a);
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp() / a;
+    let fraction = continued_fraction(0.0, |m| {
+        if m == 1 {
+            return (1.0, 1.0);
+        }
+        let k = (m / 2) as f64;
+        let numerator = if m % 2 == 0 {
+            -(a + k - 1.0) * (a + b + k - 1.0) * x / ((a + 2.0 * k - 2.0) * (a + 2.0 * k - 1.0))
+        } else {
+            k * (b - k) * x / ((a + 2.0 * k - 1.0) * (a + 2.0 * k))
+        };
+        (numerator, 1.0)
+    });
+    front * fraction
+}
+
+/// Regularized upper incomplete gamma function Q(a, x).
+fn upper_incomplete_gamma(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let front = (a * x.ln() - x - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        // Series for the lower function
+        let (mut term, mut sum) = (1.0 / a, 1.0 / a);
+        for n in 1..500 {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        1.0 - front * sum
+    } else {
+        let fraction = continued_fraction(0.0, |m| {
+            if m == 1 {
+                return (1.0, x + 1.0 - a);
+            }
+            let n = (m - 1) as f64;
+            (-n * (n - a), x + 2.0 * n + 1.0 - a)
+        });
+        front * fraction
+    }
+}
@@ -0,0 +1,147 @@
+//! This module contains the checks run before a workbook is saved or exported.
+//!
+//! The checks look for conditions that are usually mistakes, such as formulas reading cells
+//! that were never filled in, so the user can fix them before the file is written.
+
+use crate::Ops;
+use crate::utils::cells::Cells;
+
+/// Maximum number of problems reported, so a single error spreading through a large sheet
+/// does not flood the report.
+pub const PROBLEM_LIMIT: usize = 200;
+
+/// A suspicious condition found in a workbook.
+///
+/// # Fields
+/// * `cell` - Index of the cell the problem is in (1-based)
+/// * `message` - Description of the problem
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub cell: i32,
+    pub message: String,
+}
+
+/// Looks for suspicious conditions in a workbook.
+///
+/// The conditions are cells in error (only where the error originates, not the cells it
+/// spreads to), formulas that read empty cells as 0, and cells with a value that are left out
+/// of the export because their row or column is hidden. Range functions leave empty cells
+/// out, so they are not reported.
+/// # Arguments
+/// * `database` - A reference to the data array.
+/// * `err` - A reference to the error array.
+/// * `opers` - The operations for each cell.
+/// * `n_cols` - The number of columns in the data array.
+/// * `hidden` - Rows and columns left out of the export.
+/// # Returns
+/// The problems in cell order, at most `PROBLEM_LIMIT`, and the total number found.
+pub fn check(
+    database: &[i32],
+    err: &[bool],
+    opers: &Cells<Ops>,
+    n_cols: i32,
+    hidden: (&[i32], &[i32]),
+) -> (Vec<Problem>, usize) {
+    let mut problems = Vec::new();
+    let mut total = 0;
+    let mut report = |cell: i32, message: String| {
+        total += 1;
+        if problems.len() < PROBLEM_LIMIT {
+            problems.push(Problem { cell, message });
+        }
+    };
+    for cell in 1..opers.len() as i32 {
+        let op = &opers[cell as usize];
+        if let Some(cause) = crate::utils::graph::error_cause(cell, database, opers, err, n_cols)
+            && !cause.starts_with("Depends on")
+        {
+            report(cell, format!("Error: {}", cause));
+        }
+        let ranged = crate::utils::graph::RANGE_OPS.contains(&op.opcpde.as_str());
+        let empty: Vec<String> = crate::utils::graph::precedents(op, n_cols)
+            .into_iter()
+            .filter(|&p| !ranged && opers[p as usize].opcpde.is_empty())
+            .map(|p| crate::ind_to_cell(p, n_cols))
+            .collect();
+        if let Some(first) = empty.first() {
+            let message = if empty.len() == 1 {
+                format!("Formula reads empty cell {}", first)
+            } else {
+                format!(
+                    "Formula reads {} empty cells, such as {}",
+                    empty.len(),
+                    first
+                )
+            };
+            report(cell, message);
+        }
+        let col = (cell - 1) % n_cols + 1;
+        let row = (cell - 1) / n_cols + 1;
+        if !op.opcpde.is_empty() && (hidden.0.contains(&row) || hidden.1.contains(&col)) {
+            report(
+                cell,
+                String::from("Left out of the export because its row or column is hidden"),
+            );
+        }
+    }
+    (problems, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(code: &str, cell1: i32, cell2: i32) -> Ops {
+        Ops {
+            opcpde: String::from(code),
+            cell1,
+            cell2,
+            expr: None,
+            criterion: None,
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        // 2x2 grid: A1=1/0, B1=A1+1, A2=B1*B2, B2 empty
+        let database = vec![0, 0, 0, 0, 0];
+        let err = vec![false, true, true, true, false];
+        let opers = Cells::from(vec![
+            op("", -1, -1),
+            op("VVD", 1, 0),
+            op("CVA", 1, 1),
+            op("CCM", 2, 4),
+            op("", -1, -1),
+        ]);
+        let cells = |(problems, _): (Vec<Problem>, usize)| -> Vec<(i32, String)> {
+            problems.into_iter().map(|p| (p.cell, p.message)).collect()
+        };
+        assert_eq!(
+            cells(check(&database, &err, &opers, 2, (&[], &[]))),
+            vec![
+                (1, String::from("Error: Division by zero")),
+                (3, String::from("Formula reads empty cell B2")),
+            ]
+        );
+        assert_eq!(
+            cells(check(&database, &err, &opers, 2, (&[2], &[2]))),
+            vec![
+                (1, String::from("Error: Division by zero")),
+                (
+                    2,
+                    String::from("Left out of the export because its row or column is hidden")
+                ),
+                (3, String::from("Formula reads empty cell B2")),
+                (
+                    3,
+                    String::from("Left out of the export because its row or column is hidden")
+                ),
+            ]
+        );
+        let empty = Cells::from(vec![op("", -1, -1); 5]);
+        assert_eq!(
+            check(&database, &[false; 5], &empty, 2, (&[], &[])),
+            (Vec::new(), 0)
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! This module contains watches, which pause editing when an update changes a watched cell.
+//!
+//! Before an update the caller takes a snapshot of the watched cells. If the update changes
+//! the value or error state of one of them, the caller shows the changes and waits for the
+//! user before applying further updates.
+
+use crate::utils::cells::Cells;
+/// Runs a `watch <cell>` or `unwatch <cell>` command.
+/// # Arguments
+/// * `command` - The full command string.
+/// * `watches` - The watched cells, updated in place.
+/// * `len_h` - The number of columns in the data array.
+/// * `len_v` - The number of rows in the data array.
+/// # Returns
+/// "ok" if the watches were updated, otherwise "Invalid Watch".
+pub fn watch_command(
+    command: &str,
+    watches: &mut Vec<i32>,
+    len_h: i32,
+    len_v: i32,
+) -> &'static str {
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    if parts.len() != 2 || !crate::utils::input::is_valid_cell(parts[1], len_h, len_v) {
+        return "Invalid Watch";
+    }
+    let cell = crate::cell_to_ind(parts[1], len_h);
+    match parts[0] {
+        "watch" if !watches.contains(&cell) => watches.push(cell),
+        "watch" => {}
+        "unwatch" => watches.retain(|&c| c != cell),
+        _ => return "Invalid Watch",
+    }
+    "ok"
+}
+
+/// Takes the value and error state of each watched cell.
+/// # Arguments
+/// * `watches` - The watched cells.
+/// * `database` - A reference to the data array.
+/// * `err` - A reference to the error array.
+/// # Returns
+/// One `(value, error)` pair per watched cell.
+pub fn snapshot(watches: &[i32], database: &[i32], err: &[bool]) -> Vec<(i32, bool)> {
+    watches
+        .iter()
+        .map(|&c| (database[c as usize], err[c as usize]))
+        .collect()
+}
+
+/// Formats the state of a cell.
+fn shown((value, error): (i32, bool)) -> String {
+    if error {
+        String::from("ERR")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Describes how an update changed the watched cells.
+/// # Arguments
+/// * `edits` - The cells assigned by the update, in order.
+/// * `watches` - The watched cells.
+/// * `before` - The snapshot of the watched cells taken before the update.
+/// * `database` - A reference to the data array after the update.
+/// * `err` - A reference to the error array after the update.
+/// * `sensi` - The sensitivity list after the update.
+/// * `n_cols` - The number of columns in the data array.
+/// # Returns
+/// For each watched cell that changed, a line with the old and new state and the edit that
+/// caused it, followed by the chain of dependencies from the edit. Empty if nothing changed.
+pub fn changes(
+    edits: &[i32],
+    watches: &[i32],
+    before: &[(i32, bool)],
+    database: &[i32],
+    err: &[bool],
+    sensi: &Cells<Vec<i32>>,
+    n_cols: i32,
+) -> Vec<String> {
+    let cell = |c: i32| crate::ind_to_cell(c, n_cols);
+    let mut lines = Vec::new();
+    for (&watched, &old) in watches.iter().zip(before) {
+        let new = (database[watched as usize], err[watched as usize]);
+        if new == old {
+            continue;
+        }
+        let path = edits
+            .iter()
+            .find_map(|&e| crate::utils::graph::dependency_path(sensi, e, watched));
+        match path {
+            Some(path) => {
+                lines.push(format!(
+                    "{} changed from {} to {} after editing {}",
+                    cell(watched),
+                    shown(old),
+                    shown(new),
+                    cell(path[0])
+                ));
+                let path: Vec<String> = path.into_iter().map(cell).collect();
+                lines.push(format!("  path: {}", path.join(" -> ")));
+            }
+            None => lines.push(format!(
+                "{} changed from {} to {}",
+                cell(watched),
+                shown(old),
+                shown(new)
+            )),
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_command() {
+        let mut watches = Vec::new();
+        assert_eq!(watch_command("watch B2", &mut watches, 3, 3), "ok");
+        assert_eq!(watch_command("watch B2", &mut watches, 3, 3), "ok");
+        assert_eq!(watch_command("watch A1", &mut watches, 3, 3), "ok");
+        assert_eq!(watches, vec![5, 1]);
+        assert_eq!(watch_command("unwatch B2", &mut watches, 3, 3), "ok");
+        assert_eq!(watches, vec![1]);
+        assert_eq!(
+            watch_command("watch D1", &mut watches, 3, 3),
+            "Invalid Watch"
+        );
+        assert_eq!(watch_command("watch", &mut watches, 3, 3), "Invalid Watch");
+        assert_eq!(
+            watch_command("watches A1", &mut watches, 3, 3),
+            "Invalid Watch"
+        );
+    }
+
+    #[test]
+    fn test_changes() {
+        // A1 -> B1 -> C1 in a 3 column grid, C1 now in error
+        let sensi = Cells::from(vec![vec![], vec![2], vec![3], vec![], vec![]]);
+        let database = vec![0, 4, 5, 0, 0];
+        let err = vec![false, false, false, true, false];
+        let watches = [3, 4];
+        let before = [(7, false), (0, false)];
+        assert_eq!(
+            changes(&[1], &watches, &before, &database, &err, &sensi, 3),
+            vec![
+                "C1 changed from 7 to ERR after editing A1",
+                "  path: A1 -> B1 -> C1",
+            ]
+        );
+        assert!(
+            changes(
+                &[1],
+                &watches,
+                &snapshot(&watches, &database, &err),
+                &database,
+                &err,
+                &sensi,
+                3
+            )
+            .is_empty()
+        );
+    }
+}